@@ -0,0 +1,19 @@
+//! Runs [`rustc_fuzz::target::parse`]: checks that the lexer is lossless
+//! and that pretty-printed output parses again. Mostly useful as a fast
+//! target for exercising the structured mutators themselves.
+
+#![no_main]
+
+use libfuzzer_sys::{fuzz_crossover, fuzz_mutator, fuzz_target};
+use rustc_fuzz::{libfuzzer, target};
+
+fuzz_target!(|data: &[u8]| target::parse(data));
+
+fuzz_mutator!(|data: &mut [u8], size: usize, max_size: usize, seed: u32| {
+    libfuzzer::custom_mutate(data, size, max_size, seed)
+        .unwrap_or_else(|| libfuzzer_sys::fuzzer_mutate(data, size, max_size))
+});
+
+fuzz_crossover!(|data1: &[u8], data2: &[u8], out: &mut [u8], seed: u32| {
+    libfuzzer::custom_crossover(data1, data2, out, seed).unwrap_or(0)
+});
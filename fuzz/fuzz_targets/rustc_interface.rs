@@ -0,0 +1,132 @@
+//! Compiles each input in-process through `rustc_driver`, stopping after
+//! the phase named by `RUSTC_FUZZ_STOP_AFTER`: `parse`, `hir`, `mir` (the
+//! default, which includes type checking and borrow checking) or
+//! `codegen`.
+//!
+//! Needs a nightly toolchain with the `rustc-dev` component. rustc's own
+//! code is only coverage-instrumented if the toolchain was built that way;
+//! with a stock toolchain libFuzzer sees coverage of this crate alone, and
+//! the target is mainly a fast crash oracle.
+
+#![no_main]
+#![feature(rustc_private)]
+
+extern crate rustc_ast;
+extern crate rustc_driver;
+extern crate rustc_interface;
+extern crate rustc_middle;
+extern crate rustc_session;
+extern crate rustc_span;
+
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use libfuzzer_sys::{fuzz_crossover, fuzz_mutator, fuzz_target};
+use rustc_driver::{Callbacks, Compilation};
+use rustc_fuzz::{libfuzzer, seed};
+use rustc_interface::interface::{Compiler, Config};
+use rustc_middle::ty::TyCtxt;
+use rustc_session::config::Input;
+use rustc_span::FileName;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum StopAfter {
+    Parse,
+    Hir,
+    Mir,
+    Codegen,
+}
+
+fn stop_after() -> StopAfter {
+    static PHASE: OnceLock<StopAfter> = OnceLock::new();
+    *PHASE.get_or_init(|| match std::env::var("RUSTC_FUZZ_STOP_AFTER").as_deref() {
+        Ok("parse") => StopAfter::Parse,
+        Ok("hir") => StopAfter::Hir,
+        Ok("mir") | Err(_) => StopAfter::Mir,
+        Ok("codegen") => StopAfter::Codegen,
+        Ok(other) => panic!("unknown RUSTC_FUZZ_STOP_AFTER phase {other:?}"),
+    })
+}
+
+struct Fuzz {
+    source: String,
+    stop_after: StopAfter,
+}
+
+impl Callbacks for Fuzz {
+    fn config(&mut self, config: &mut Config) {
+        config.input = Input::Str {
+            name: FileName::Custom("fuzz".into()),
+            input: std::mem::take(&mut self.source),
+        };
+    }
+
+    fn after_crate_root_parsing(
+        &mut self,
+        _compiler: &Compiler,
+        _krate: &mut rustc_ast::Crate,
+    ) -> Compilation {
+        match self.stop_after {
+            StopAfter::Parse => Compilation::Stop,
+            _ => Compilation::Continue,
+        }
+    }
+
+    fn after_expansion<'tcx>(&mut self, _compiler: &Compiler, tcx: TyCtxt<'tcx>) -> Compilation {
+        if self.stop_after != StopAfter::Hir {
+            return Compilation::Continue;
+        }
+        // Force AST lowering, which is otherwise done lazily by analysis.
+        let _ = tcx.hir_crate_items(());
+        Compilation::Stop
+    }
+
+    fn after_analysis<'tcx>(&mut self, _compiler: &Compiler, _tcx: TyCtxt<'tcx>) -> Compilation {
+        match self.stop_after {
+            StopAfter::Codegen => Compilation::Continue,
+            _ => Compilation::Stop,
+        }
+    }
+}
+
+fn out_dir() -> &'static PathBuf {
+    static DIR: OnceLock<PathBuf> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-inproc-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("creating output directory");
+        dir
+    })
+}
+
+// Ordinary fatal errors unwind with `resume_unwind`, which bypasses the
+// panic hook libfuzzer-sys installs to abort, and are caught again by
+// `catch_fatal_errors`. ICEs are real panics and abort as crashes.
+fuzz_target!(|data: &[u8]| {
+    let stop_after = stop_after();
+    let mut args = vec![
+        "rustc".to_owned(),
+        "fuzz.rs".to_owned(),
+        "--crate-type=lib".to_owned(),
+        "--edition=2021".to_owned(),
+        "--error-format=short".to_owned(),
+        "--out-dir".to_owned(),
+        out_dir().display().to_string(),
+    ];
+    if stop_after == StopAfter::Codegen {
+        args.push("--emit=obj".to_owned());
+    }
+    let mut callbacks = Fuzz {
+        source: seed::decode(data).into_owned(),
+        stop_after,
+    };
+    let _ = rustc_driver::catch_fatal_errors(|| rustc_driver::run_compiler(&args, &mut callbacks));
+});
+
+fuzz_mutator!(|data: &mut [u8], size: usize, max_size: usize, seed: u32| {
+    libfuzzer::custom_mutate(data, size, max_size, seed)
+        .unwrap_or_else(|| libfuzzer_sys::fuzzer_mutate(data, size, max_size))
+});
+
+fuzz_crossover!(|data1: &[u8], data2: &[u8], out: &mut [u8], seed: u32| {
+    libfuzzer::custom_crossover(data1, data2, out, seed).unwrap_or(0)
+});
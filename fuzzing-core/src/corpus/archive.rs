@@ -0,0 +1,546 @@
+//! Packed corpora.
+//!
+//! Millions of tiny seed files are slow to walk, copy and back up on any
+//! filesystem. An archive holds a whole corpus in one file: the seeds are
+//! concatenated into blocks of about [`BLOCK_SIZE`] bytes, each block is
+//! compressed with `zstd` on its own, and an index at the end records
+//! every seed's name, block, position and [`Metadata`]. Readers load the
+//! index and then decompress one block at a time, so neither side holds
+//! more than a block of seeds in memory.
+//!
+//! Layout: [`MAGIC`], the compressed blocks, the index as JSON, then the
+//! index's offset and length as little-endian `u64`s and [`MAGIC`] again.
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Component, Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::thread;
+
+use serde::{Deserialize, Serialize};
+
+use super::meta::Metadata;
+use super::Corpus;
+
+/// Marks the start and the end of an archive.
+pub const MAGIC: &[u8; 8] = b"RSCORP01";
+
+/// Uncompressed bytes of seeds collected before a block is compressed.
+pub const BLOCK_SIZE: usize = 1 << 20;
+
+const FOOTER_LEN: u64 = 8 + 8 + MAGIC.len() as u64;
+
+/// Compresses and decompresses blocks with the `zstd` command-line tool.
+#[derive(Debug, Clone)]
+pub struct Zstd {
+    pub program: PathBuf,
+    /// Compression level, 1 to 19.
+    pub level: u32,
+}
+
+impl Default for Zstd {
+    fn default() -> Self {
+        Zstd {
+            program: PathBuf::from("zstd"),
+            level: 9,
+        }
+    }
+}
+
+impl Zstd {
+    pub fn compress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        self.filter(&[format!("-{}", self.level).as_str(), "-c", "-q"], data)
+    }
+
+    pub fn decompress(&self, data: &[u8]) -> io::Result<Vec<u8>> {
+        self.filter(&["-d", "-c", "-q"], data)
+    }
+
+    /// Runs `zstd args` with `data` on stdin, returning its stdout.
+    fn filter(&self, args: &[&str], data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut child = Command::new(&self.program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                io::Error::new(e.kind(), format!("running {}: {e}", self.program.display()))
+            })?;
+        let mut stdin = child.stdin.take().expect("stdin is piped");
+        // Feed it from another thread so neither pipe fills up while the
+        // other waits.
+        let output = thread::scope(|scope| {
+            let writer = scope.spawn(move || stdin.write_all(data));
+            let output = child.wait_with_output();
+            writer.join().expect("the writer does not panic")?;
+            output
+        })?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "{} {}: {}",
+                self.program.display(),
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(output.stdout)
+    }
+}
+
+/// Where one seed is in an archive.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexEntry {
+    /// Path of the seed relative to the corpus root, with `/` separators.
+    pub name: String,
+    /// Index of its block in [`Index::blocks`].
+    pub block: usize,
+    /// Byte range of its source in the decompressed block.
+    pub start: usize,
+    pub len: usize,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<Metadata>,
+}
+
+/// Where one compressed block is in an archive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Block {
+    pub offset: u64,
+    pub len: u64,
+}
+
+/// The table of contents of an archive.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Index {
+    pub blocks: Vec<Block>,
+    /// Seeds in the order they were added.
+    pub seeds: Vec<IndexEntry>,
+}
+
+/// A seed read back from an archive.
+#[derive(Debug, Clone)]
+pub struct PackedSeed {
+    pub name: String,
+    pub source: String,
+    pub metadata: Option<Metadata>,
+}
+
+/// Writes an archive seed by seed.
+pub struct Writer {
+    out: BufWriter<File>,
+    zstd: Zstd,
+    offset: u64,
+    block: Vec<u8>,
+    index: Index,
+}
+
+impl Writer {
+    pub fn create(path: &Path, zstd: Zstd) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        out.write_all(MAGIC)?;
+        Ok(Writer {
+            out,
+            zstd,
+            offset: MAGIC.len() as u64,
+            block: Vec::new(),
+            index: Index::default(),
+        })
+    }
+
+    /// Adds the seed `name`. Names are not checked for uniqueness.
+    pub fn append(
+        &mut self,
+        name: &str,
+        source: &str,
+        metadata: Option<Metadata>,
+    ) -> io::Result<()> {
+        self.index.seeds.push(IndexEntry {
+            name: name.to_owned(),
+            block: self.index.blocks.len(),
+            start: self.block.len(),
+            len: source.len(),
+            metadata,
+        });
+        self.block.extend_from_slice(source.as_bytes());
+        if self.block.len() >= BLOCK_SIZE {
+            self.flush_block()?;
+        }
+        Ok(())
+    }
+
+    fn flush_block(&mut self) -> io::Result<()> {
+        if self.block.is_empty() {
+            return Ok(());
+        }
+        let compressed = self.zstd.compress(&self.block)?;
+        self.out.write_all(&compressed)?;
+        self.index.blocks.push(Block {
+            offset: self.offset,
+            len: compressed.len() as u64,
+        });
+        self.offset += compressed.len() as u64;
+        self.block.clear();
+        Ok(())
+    }
+
+    /// Writes the last block and the index, returning the number of
+    /// seeds.
+    pub fn finish(mut self) -> io::Result<usize> {
+        self.flush_block()?;
+        let index = serde_json::to_vec(&self.index)?;
+        self.out.write_all(&index)?;
+        self.out.write_all(&self.offset.to_le_bytes())?;
+        self.out.write_all(&(index.len() as u64).to_le_bytes())?;
+        self.out.write_all(MAGIC)?;
+        self.out.flush()?;
+        Ok(self.index.seeds.len())
+    }
+}
+
+/// Reads an archive.
+pub struct Reader {
+    file: File,
+    zstd: Zstd,
+    index: Index,
+    /// Where the index starts, which is where the blocks must end.
+    blocks_end: u64,
+}
+
+impl Reader {
+    pub fn open(path: &Path, zstd: Zstd) -> io::Result<Self> {
+        let mut file = File::open(path)?;
+        let invalid = |what: &str| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is not a corpus archive: {what}", path.display()),
+            )
+        };
+        let mut magic = [0; MAGIC.len()];
+        file.read_exact(&mut magic)
+            .map_err(|_| invalid("too short"))?;
+        if &magic != MAGIC {
+            return Err(invalid("bad header"));
+        }
+        let size = file.seek(SeekFrom::End(0))?;
+        if size < MAGIC.len() as u64 + FOOTER_LEN {
+            return Err(invalid("too short"));
+        }
+        file.seek(SeekFrom::End(-(FOOTER_LEN as i64)))?;
+        let mut footer = [0; FOOTER_LEN as usize];
+        file.read_exact(&mut footer)?;
+        if &footer[16..] != MAGIC {
+            return Err(invalid("bad footer"));
+        }
+        let offset = u64::from_le_bytes(footer[..8].try_into().expect("8 bytes"));
+        let len = u64::from_le_bytes(footer[8..16].try_into().expect("8 bytes"));
+        if offset.checked_add(len) != Some(size - FOOTER_LEN) {
+            return Err(invalid("bad index position"));
+        }
+        file.seek(SeekFrom::Start(offset))?;
+        let mut json = vec![0; len as usize];
+        file.read_exact(&mut json)?;
+        let index = serde_json::from_slice(&json)?;
+        Ok(Reader {
+            file,
+            zstd,
+            index,
+            blocks_end: offset,
+        })
+    }
+
+    pub fn index(&self) -> &Index {
+        &self.index
+    }
+
+    fn block(&mut self, block: usize) -> io::Result<Vec<u8>> {
+        let invalid = |what: &str| {
+            io::Error::new(io::ErrorKind::InvalidData, format!("block {block} {what}"))
+        };
+        let Block { offset, len } = *self
+            .index
+            .blocks
+            .get(block)
+            .ok_or_else(|| invalid("is not in the index"))?;
+        // The index is not trusted: a block must lie between the header
+        // and the index before anything is allocated for it.
+        let end = offset.checked_add(len);
+        if offset < MAGIC.len() as u64 || end.is_none_or(|end| end > self.blocks_end) {
+            return Err(invalid("is outside the archive's blocks"));
+        }
+        self.file.seek(SeekFrom::Start(offset))?;
+        let mut compressed = vec![0; len as usize];
+        self.file
+            .read_exact(&mut compressed)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::UnexpectedEof => invalid("is truncated"),
+                _ => e,
+            })?;
+        self.zstd.decompress(&compressed)
+    }
+
+    /// The seed at position `i` of the index.
+    pub fn get(&mut self, i: usize) -> io::Result<PackedSeed> {
+        let block = self
+            .index
+            .seeds
+            .get(i)
+            .map(|entry| entry.block)
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "the index has {} seeds, not {}",
+                        self.index.seeds.len(),
+                        i + 1
+                    ),
+                )
+            })?;
+        let block = self.block(block)?;
+        seed(&self.index.seeds[i], &block)
+    }
+
+    /// Calls `f` on every seed in index order, decompressing each block
+    /// once.
+    pub fn for_each(&mut self, mut f: impl FnMut(PackedSeed) -> io::Result<()>) -> io::Result<()> {
+        let mut current: Option<(usize, Vec<u8>)> = None;
+        for i in 0..self.index.seeds.len() {
+            let block = self.index.seeds[i].block;
+            if current.as_ref().map(|(b, _)| *b) != Some(block) {
+                current = Some((block, self.block(block)?));
+            }
+            let (_, data) = current.as_ref().expect("just loaded");
+            f(seed(&self.index.seeds[i], data)?)?;
+        }
+        Ok(())
+    }
+}
+
+/// Packs every seed of the corpus at `dir`, with its sidecar, into an
+/// archive at `output`, named by its path under `dir`. Files that are not
+/// UTF-8 are skipped. Returns how many seeds were packed.
+pub fn pack(dir: &Path, output: &Path, zstd: Zstd) -> io::Result<usize> {
+    let mut writer = Writer::create(output, zstd)?;
+    for path in Corpus::open(dir).paths()? {
+        let source = match fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => continue,
+            Err(e) => return Err(e),
+        };
+        let name = path
+            .strip_prefix(dir)
+            .unwrap_or(&path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        writer.append(&name, &source, Metadata::load(&path)?)?;
+    }
+    writer.finish()
+}
+
+/// Writes every seed of the archive at `archive`, with its sidecar, under
+/// `output`. Names that would land outside `output` are refused. Returns
+/// how many seeds were written.
+pub fn unpack(archive: &Path, output: &Path, zstd: Zstd) -> io::Result<usize> {
+    let mut reader = Reader::open(archive, zstd)?;
+    let mut count = 0;
+    reader.for_each(|seed| {
+        // Names come from the archive; keep them inside `output`.
+        let name = Path::new(&seed.name);
+        if !name.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "refusing to unpack {:?} outside the output directory",
+                    seed.name
+                ),
+            ));
+        }
+        let dest = output.join(name);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&dest, &seed.source)?;
+        if let Some(metadata) = seed.metadata {
+            metadata.save(&dest)?;
+        }
+        count += 1;
+        Ok(())
+    })?;
+    Ok(count)
+}
+
+fn seed(entry: &IndexEntry, block: &[u8]) -> io::Result<PackedSeed> {
+    let bytes = entry
+        .start
+        .checked_add(entry.len)
+        .and_then(|end| block.get(entry.start..end))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} is past the end of its block", entry.name),
+            )
+        })?;
+    let source = String::from_utf8(bytes.to_vec())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    Ok(PackedSeed {
+        name: entry.name.clone(),
+        source,
+        metadata: entry.metadata.clone(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An archive of `index` and no blocks, laid out as [`Writer`] lays
+    /// one out.
+    fn index_only(index: &Index) -> Vec<u8> {
+        let json = serde_json::to_vec(index).expect("serializes");
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend_from_slice(&json);
+        bytes.extend_from_slice(&(MAGIC.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&(json.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(MAGIC);
+        bytes
+    }
+
+    fn entry(block: usize, start: usize, len: usize) -> IndexEntry {
+        IndexEntry {
+            name: "a.rs".to_owned(),
+            block,
+            start,
+            len,
+            metadata: None,
+        }
+    }
+
+    /// Writes `bytes` to a file of its own and opens it.
+    fn open(name: &str, bytes: &[u8]) -> io::Result<Reader> {
+        let path =
+            std::env::temp_dir().join(format!("rustc-fuzz-archive-{}-{name}", std::process::id()));
+        std::fs::write(&path, bytes)?;
+        let reader = Reader::open(&path, Zstd::default());
+        std::fs::remove_file(&path)?;
+        reader
+    }
+
+    #[test]
+    fn truncated_archive_is_invalid_data() {
+        let bytes = index_only(&Index {
+            blocks: Vec::new(),
+            seeds: vec![entry(0, 0, 4)],
+        });
+        assert!(open("whole", &bytes).is_ok());
+        for len in 0..bytes.len() {
+            let error = open(&format!("truncated-{len}"), &bytes[..len])
+                .err()
+                .unwrap_or_else(|| panic!("opened {len} of {} bytes", bytes.len()));
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{len}: {error}");
+        }
+    }
+
+    #[test]
+    fn missing_block_is_invalid_data() {
+        let bytes = index_only(&Index {
+            blocks: Vec::new(),
+            seeds: vec![entry(3, 0, 4)],
+        });
+        let mut reader = open("missing-block", &bytes).expect("opens");
+        let error = reader.get(0).expect_err("no block 3");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{error}");
+        let error = reader.for_each(|_| Ok(())).expect_err("no block 3");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{error}");
+    }
+
+    #[test]
+    fn block_outside_the_archive_is_invalid_data() {
+        let blocks = [
+            Block {
+                offset: MAGIC.len() as u64,
+                len: 1 << 40,
+            },
+            Block {
+                offset: u64::MAX,
+                len: 2,
+            },
+            Block { offset: 0, len: 4 },
+        ];
+        for (i, block) in blocks.into_iter().enumerate() {
+            let bytes = index_only(&Index {
+                blocks: vec![block],
+                seeds: vec![entry(0, 0, 4)],
+            });
+            let mut reader = open(&format!("bad-block-{i}"), &bytes).expect("opens");
+            let error = reader.get(0).expect_err("block outside the archive");
+            assert_eq!(
+                error.kind(),
+                io::ErrorKind::InvalidData,
+                "{block:?}: {error}"
+            );
+        }
+    }
+
+    #[test]
+    fn seed_past_the_index_is_an_error() {
+        let bytes = index_only(&Index::default());
+        let mut reader = open("no-seeds", &bytes).expect("opens");
+        assert!(reader.get(0).is_err());
+    }
+
+    #[test]
+    fn seed_outside_its_block_is_invalid_data() {
+        let block = b"fn main() {}";
+        assert_eq!(
+            seed(&entry(0, 3, 4), block).expect("in range").source,
+            "main"
+        );
+        for (start, len) in [(0, block.len() + 1), (block.len(), 1), (usize::MAX, 2)] {
+            let error = seed(&entry(0, start, len), block).expect_err("out of range");
+            assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{error}");
+        }
+    }
+
+    #[test]
+    fn unpacks_what_was_packed() {
+        let root = std::env::temp_dir().join(format!(
+            "rustc-fuzz-archive-{}-round-trip",
+            std::process::id()
+        ));
+        let (dir, out) = (root.join("corpus"), root.join("out"));
+        fs::create_dir_all(dir.join("sub")).expect("creates the corpus");
+        fs::write(dir.join("a.rs"), "fn a() {}\n").expect("writes a seed");
+        fs::write(dir.join("sub/b.rs"), "fn b() {}\n").expect("writes a seed");
+        Metadata::new("fn b() {}\n", crate::corpus::meta::Provenance::Unknown)
+            .save(&dir.join("sub/b.rs"))
+            .expect("writes a sidecar");
+        let archive = root.join("corpus.rsc");
+        assert_eq!(pack(&dir, &archive, Zstd::default()).expect("packs"), 2);
+        assert_eq!(unpack(&archive, &out, Zstd::default()).expect("unpacks"), 2);
+        let b = out.join("sub/b.rs");
+        assert_eq!(fs::read_to_string(&b).expect("unpacked"), "fn b() {}\n");
+        assert!(Metadata::load(&b)
+            .expect("reads")
+            .is_some_and(|m| m.matches("fn b() {}\n")));
+        assert_eq!(Metadata::load(&out.join("a.rs")).expect("reads"), None);
+        fs::remove_dir_all(&root).expect("cleans up");
+    }
+
+    #[test]
+    fn unpack_stays_inside_the_output_directory() {
+        let root =
+            std::env::temp_dir().join(format!("rustc-fuzz-archive-{}-escape", std::process::id()));
+        fs::create_dir_all(&root).expect("creates the root");
+        let archive = root.join("evil.rsc");
+        let mut writer = Writer::create(&archive, Zstd::default()).expect("creates");
+        writer
+            .append("../escaped.rs", "fn main() {}", None)
+            .expect("appends");
+        writer.finish().expect("finishes");
+        let error = unpack(&archive, &root.join("out"), Zstd::default()).expect_err("escapes");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{error}");
+        assert!(!root.join("escaped.rs").exists());
+        fs::remove_dir_all(&root).expect("cleans up");
+    }
+}
@@ -0,0 +1,216 @@
+//! Corpus minimization.
+//!
+//! Every seed is described by a set of features, and [`minimize`] keeps,
+//! for each feature, the smallest seed that has it, the way `afl-cmin`
+//! does; [`merge`] keeps the seeds that add a feature to an existing
+//! corpus. Features are edges from an instrumented rustc when there is one,
+//! read through an [`EdgeMap`], and otherwise [`feature_tags`]: what the
+//! seed looks like syntactically. Either way the harness's findings are
+//! features too, so no reproducer is dropped.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::io;
+use std::ptr::NonNull;
+
+use crate::lex::{self, TokenKind};
+
+/// The indices of a minimal subset of `seeds` (source and features) that
+/// has every feature any of them has, in ascending order. Ties go to the
+/// shorter seed, then to the earlier one.
+pub fn minimize(seeds: &[(&str, BTreeSet<String>)]) -> Vec<usize> {
+    let mut best: HashMap<&str, usize> = HashMap::new();
+    for (i, (source, features)) in seeds.iter().enumerate() {
+        for feature in features {
+            let best = best.entry(feature.as_str()).or_insert(i);
+            if source.len() < seeds[*best].0.len() {
+                *best = i;
+            }
+        }
+    }
+    let kept: BTreeSet<usize> = best.into_values().collect();
+    tracing::debug!(seeds = seeds.len(), kept = kept.len(), "minimized");
+    kept.into_iter().collect()
+}
+
+/// The indices of the `candidates` (source and features) worth adding to
+/// a corpus whose seeds have the `existing` features: each kept seed has a
+/// feature nothing before it had. Shorter seeds go first, as in
+/// libFuzzer's `-merge=1`, so a feature is credited to the smallest seed
+/// that has it. Returned in ascending order.
+pub fn merge(existing: &[BTreeSet<String>], candidates: &[(&str, BTreeSet<String>)]) -> Vec<usize> {
+    let mut seen: HashSet<&str> = existing.iter().flatten().map(String::as_str).collect();
+    let mut order: Vec<usize> = (0..candidates.len()).collect();
+    order.sort_by_key(|&i| (candidates[i].0.len(), i));
+    let mut kept = BTreeSet::new();
+    for i in order {
+        let mut new = false;
+        for feature in &candidates[i].1 {
+            new |= seen.insert(feature.as_str());
+        }
+        if new {
+            kept.insert(i);
+        }
+    }
+    kept.into_iter().collect()
+}
+
+/// Syntactic features of `source`: the keywords and punctuation it uses,
+/// and which of them follow each other (`unsafe fn`, `async move`,
+/// `> ::`), with identifiers and literals reduced to their kind.
+pub fn feature_tags(source: &str) -> BTreeSet<String> {
+    let tokens = lex::tokenize(source);
+    let words: Vec<&str> = tokens
+        .iter()
+        .filter(|token| !token.kind.is_trivia())
+        .map(|token| match token.kind {
+            TokenKind::Ident if lex::is_keyword(token.text(source)) => token.text(source),
+            TokenKind::Ident => "ident",
+            TokenKind::Lifetime => "lifetime",
+            TokenKind::Literal => "literal",
+            TokenKind::LineComment { .. } | TokenKind::BlockComment { .. } => "doc",
+            _ => token.text(source),
+        })
+        .collect();
+    let mut tags: BTreeSet<String> = words.iter().map(|word| word.to_string()).collect();
+    tags.extend(words.windows(2).map(|pair| pair.join(" ")));
+    tags
+}
+
+/// An AFL-style edge coverage map in System V shared memory. An
+/// instrumented rustc started with `__AFL_SHM_ID` set to [`EdgeMap::id`]
+/// counts the edges it takes in it.
+pub struct EdgeMap {
+    id: libc::c_int,
+    map: NonNull<u8>,
+    len: usize,
+}
+
+impl EdgeMap {
+    pub fn new(len: usize) -> io::Result<Self> {
+        // SAFETY: plain syscalls; the segment is attached before use and
+        // detached and removed on drop.
+        unsafe {
+            let id = libc::shmget(libc::IPC_PRIVATE, len, libc::IPC_CREAT | 0o600);
+            if id < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let map = libc::shmat(id, std::ptr::null(), 0);
+            if map as isize == -1 {
+                let error = io::Error::last_os_error();
+                libc::shmctl(id, libc::IPC_RMID, std::ptr::null_mut());
+                return Err(error);
+            }
+            let map = NonNull::new(map.cast()).expect("shmat succeeded");
+            Ok(EdgeMap { id, map, len })
+        }
+    }
+
+    /// The value for `__AFL_SHM_ID`.
+    pub fn id(&self) -> String {
+        self.id.to_string()
+    }
+
+    /// The edges counted since the last call, each with its hit count
+    /// rounded to AFL's buckets, and clears the map.
+    pub fn take(&mut self) -> BTreeSet<String> {
+        // SAFETY: the segment is `len` bytes and attached until drop; the
+        // children writing to it have exited.
+        let map = unsafe { std::slice::from_raw_parts_mut(self.map.as_ptr(), self.len) };
+        let edges = map
+            .iter()
+            .enumerate()
+            .filter(|(_, &hits)| hits > 0)
+            .map(|(edge, &hits)| format!("edge {edge}/{}", bucket(hits)))
+            .collect();
+        map.fill(0);
+        edges
+    }
+}
+
+impl Drop for EdgeMap {
+    fn drop(&mut self) {
+        // SAFETY: detaches and removes the segment attached in `new`.
+        unsafe {
+            libc::shmdt(self.map.as_ptr().cast());
+            libc::shmctl(self.id, libc::IPC_RMID, std::ptr::null_mut());
+        }
+    }
+}
+
+/// AFL's hit-count classes, so loops taken a few more times do not count
+/// as new coverage.
+fn bucket(hits: u8) -> u8 {
+    match hits {
+        0..=3 => hits,
+        4..=7 => 4,
+        8..=15 => 8,
+        16..=31 => 16,
+        32..=127 => 32,
+        _ => 128,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn features(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|name| (*name).to_owned()).collect()
+    }
+
+    #[test]
+    fn the_smallest_seed_keeps_each_feature() {
+        let seeds = [
+            ("fn a() { loop {} }", features(&["fn", "loop"])),
+            ("fn b() {}", features(&["fn"])),
+            ("loop {}", features(&["loop"])),
+            ("fn c() {}", features(&["fn"])),
+        ];
+        assert_eq!(minimize(&seeds), [1, 2]);
+    }
+
+    #[test]
+    fn merging_keeps_only_new_features() {
+        let existing = [features(&["fn"])];
+        let candidates = [
+            ("fn a() { loop {} } // long", features(&["fn", "loop"])),
+            ("loop {}", features(&["loop"])),
+            ("fn b() {}", features(&["fn"])),
+            ("async {}", features(&["async"])),
+        ];
+        assert_eq!(merge(&existing, &candidates), [1, 3]);
+    }
+
+    #[test]
+    fn tags_reduce_names_to_their_kind() {
+        let tags = feature_tags("unsafe fn f<'a>(x: &'a u8) { /* c */ 1 }");
+        for tag in [
+            "unsafe fn",
+            "fn ident",
+            "< lifetime",
+            "{ literal",
+            "ident :",
+        ] {
+            assert!(tags.contains(tag), "{tag}: {tags:?}");
+        }
+        assert!(!tags.contains("f") && !tags.contains("1"));
+        assert!(!tags.contains("doc"), "plain comments are trivia");
+    }
+
+    #[test]
+    fn edges_are_bucketed_and_cleared() {
+        let mut map = EdgeMap::new(64).expect("makes a map");
+        assert!(map.id().parse::<i32>().is_ok());
+        // SAFETY: the map is 64 bytes and attached.
+        unsafe {
+            *map.map.as_ptr().add(3) = 1;
+            *map.map.as_ptr().add(9) = 5;
+            *map.map.as_ptr().add(10) = 200;
+        }
+        assert_eq!(
+            map.take(),
+            features(&["edge 3/1", "edge 9/4", "edge 10/128"])
+        );
+        assert!(map.take().is_empty());
+    }
+}
@@ -0,0 +1,65 @@
+//! Corpus deduplication.
+//!
+//! Two seeds are duplicates when they have the same [`normalize`]d form:
+//! they differ only in formatting, comments or local names, which no
+//! compiler pass after the lexer cares about.
+//!
+//! [`normalize`]: super::normalize
+
+use std::collections::BTreeMap;
+
+use super::meta::content_hash;
+use super::{normalize, Entry};
+
+/// The hash of `source`'s normal form.
+pub fn normalized_hash(source: &str) -> String {
+    content_hash(&normalize(source))
+}
+
+/// Indices into `entries` of every group of two or more duplicates, each
+/// in ascending order. Groups are ordered by their first member.
+pub fn duplicates(entries: &[Entry]) -> Vec<Vec<usize>> {
+    let mut groups: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (i, entry) in entries.iter().enumerate() {
+        groups
+            .entry(normalized_hash(&entry.source))
+            .or_default()
+            .push(i);
+    }
+    let mut groups: Vec<Vec<usize>> = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect();
+    groups.sort();
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn entry(name: &str, source: &str) -> Entry {
+        Entry {
+            path: PathBuf::from(name),
+            source: source.to_owned(),
+        }
+    }
+
+    #[test]
+    fn seeds_differing_in_formatting_and_local_names_are_duplicates() {
+        let entries = [
+            entry("a.rs", "fn f(x: u8) -> u8 { x }"),
+            entry("b.rs", "fn g() {}"),
+            entry("c.rs", "fn f(y: u8)->u8{\n    // same\n    y\n}\n"),
+            entry("d.rs", "fn g() {}"),
+            entry("e.rs", "fn h() {}"),
+        ];
+        assert_eq!(duplicates(&entries), [vec![0, 2], vec![1, 3]]);
+        assert_ne!(
+            normalized_hash(&entries[1].source),
+            normalized_hash(&entries[4].source)
+        );
+        assert!(duplicates(&entries[3..]).is_empty());
+    }
+}
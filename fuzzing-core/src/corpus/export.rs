@@ -0,0 +1,345 @@
+//! Corpus export for other fuzzers.
+//!
+//! Seeds are plain UTF-8 source, so any byte-oriented fuzzer can start
+//! from them, but each expects its input directory laid out its own way:
+//! flat, with its own file names and a size limit. [`Format`] knows those
+//! conventions, and [`flatten`] turns a seed that pulls in sibling files
+//! with `mod foo;` into one file, since a fuzzer input has nothing next
+//! to it.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// Nested `mod foo;` files followed before [`flatten`] gives up; only
+/// `#[path]` cycles get this deep.
+const MAX_DEPTH: usize = 16;
+
+/// An external fuzzer's input layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Format {
+    /// libFuzzer and cargo-fuzz: files named by the SHA-1 of their
+    /// content, as `-merge` writes them.
+    LibFuzzer,
+    /// AFL++ `-i` directories: `id:NNNNNN,orig:NAME`, as its queue names
+    /// imported seeds.
+    Aflpp,
+    /// honggfuzz `--input` directories: `HASH.SIZE.honggfuzz.cov`, as it
+    /// names the files it adds.
+    Honggfuzz,
+}
+
+impl Format {
+    pub const ALL: [Format; 3] = [Format::LibFuzzer, Format::Aflpp, Format::Honggfuzz];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::LibFuzzer => "libfuzzer",
+            Format::Aflpp => "aflpp",
+            Format::Honggfuzz => "honggfuzz",
+        }
+    }
+
+    /// The largest input the fuzzer loads by default: AFL++'s `MAX_FILE`
+    /// and honggfuzz's default `--max_file_size`. libFuzzer takes anything
+    /// and sizes `-max_len` from the corpus.
+    pub fn max_len(self) -> Option<usize> {
+        match self {
+            Format::LibFuzzer => None,
+            Format::Aflpp | Format::Honggfuzz => Some(1 << 20),
+        }
+    }
+
+    /// The file name of the `index`th exported seed, `source`, which was
+    /// `name` in the corpus.
+    pub fn file_name(self, index: usize, name: &str, source: &str) -> String {
+        match self {
+            Format::LibFuzzer => hex(&sha1(source.as_bytes())),
+            Format::Aflpp => {
+                // AFL++ keeps the original name after `orig:`; it cannot
+                // contain a path separator, and commas separate fields.
+                let orig: String = name
+                    .chars()
+                    .map(|c| {
+                        if matches!(c, '/' | '\\' | ',') {
+                            '_'
+                        } else {
+                            c
+                        }
+                    })
+                    .collect();
+                format!("id:{index:06},orig:{orig}")
+            }
+            Format::Honggfuzz => format!(
+                "{}.{:08x}.honggfuzz.cov",
+                hex(&sha1(source.as_bytes())),
+                source.len()
+            ),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownFormat(pub String);
+
+impl fmt::Display for UnknownFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown export format {:?}; expected libfuzzer, aflpp or honggfuzz",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownFormat {}
+
+impl FromStr for Format {
+    type Err = UnknownFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Format::ALL
+            .into_iter()
+            .find(|f| f.name() == s)
+            .ok_or_else(|| UnknownFormat(s.to_owned()))
+    }
+}
+
+/// A seed with its out-of-line modules inlined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Flattened {
+    pub source: String,
+    /// The module files that went into it.
+    pub files: BTreeSet<PathBuf>,
+}
+
+/// Inlines the `mod foo;` declarations of the seed at `path`, looking for
+/// `foo.rs` and `foo/mod.rs` where rustc would, and honouring `#[path]`.
+/// Returns `None` if the seed has none, or does not parse. Modules whose
+/// file is missing are left as they are.
+pub fn flatten(path: &Path, source: &str) -> io::Result<Option<Flattened>> {
+    let Ok(mut file) = syn::parse_file(source) else {
+        return Ok(None);
+    };
+    let dir = path.parent().unwrap_or(Path::new(""));
+    let mut files = BTreeSet::new();
+    inline(&mut file.items, dir, dir, 0, &mut files)?;
+    if files.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some(Flattened {
+        source: prettyplease::unparse(&file),
+        files,
+    }))
+}
+
+/// Inlines the out-of-line modules among `items`, which live in a file in
+/// `file_dir` and whose submodules' files live in `mod_dir`.
+fn inline(
+    items: &mut [syn::Item],
+    file_dir: &Path,
+    mod_dir: &Path,
+    depth: usize,
+    files: &mut BTreeSet<PathBuf>,
+) -> io::Result<()> {
+    for item in items {
+        let syn::Item::Mod(module) = item else {
+            continue;
+        };
+        let name = module.ident.to_string();
+        if let Some((_, items)) = &mut module.content {
+            inline(items, file_dir, &mod_dir.join(&name), depth, files)?;
+            continue;
+        }
+        if depth == MAX_DEPTH {
+            continue;
+        }
+        let explicit = module
+            .attrs
+            .iter()
+            .position(|attr| attr.path().is_ident("path"));
+        let candidates = match explicit.map(|i| &module.attrs[i].meta) {
+            Some(syn::Meta::NameValue(syn::MetaNameValue {
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(path),
+                        ..
+                    }),
+                ..
+            })) => vec![file_dir.join(path.value())],
+            Some(_) => continue,
+            None => vec![
+                mod_dir.join(format!("{name}.rs")),
+                mod_dir.join(&name).join("mod.rs"),
+            ],
+        };
+        let Some(found) = candidates.into_iter().find(|path| path.is_file()) else {
+            continue;
+        };
+        let text = match fs::read_to_string(&found) {
+            Ok(text) => text,
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => continue,
+            Err(e) => return Err(e),
+        };
+        let Ok(mut inner) = syn::parse_file(&text) else {
+            continue;
+        };
+        let inner_file_dir = found.parent().unwrap_or(Path::new("")).to_owned();
+        // `foo/mod.rs` and `foo.rs` both keep their submodules in `foo/`;
+        // a `#[path]` file keeps them next to itself.
+        let inner_mod_dir = match explicit {
+            Some(_) => inner_file_dir.clone(),
+            None => mod_dir.join(&name),
+        };
+        inline(
+            &mut inner.items,
+            &inner_file_dir,
+            &inner_mod_dir,
+            depth + 1,
+            files,
+        )?;
+        if let Some(i) = explicit {
+            module.attrs.remove(i);
+        }
+        module.attrs.extend(inner.attrs);
+        module.content = Some((syn::token::Brace::default(), inner.items));
+        module.semi = None;
+        files.insert(found);
+    }
+    Ok(())
+}
+
+pub(super) fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+/// SHA-1, for libFuzzer-style file names and the keys of a synced
+/// remote, where a collision would have to be made on purpose.
+pub(super) fn sha1(data: &[u8]) -> [u8; 20] {
+    let mut h: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+    for chunk in message.chunks_exact(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in chunk.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes(word.try_into().expect("4 bytes"));
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e] = h;
+        for (i, &word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..=19 => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let t = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = t;
+        }
+        for (h, v) in h.iter_mut().zip([a, b, c, d, e]) {
+            *h = h.wrapping_add(v);
+        }
+    }
+    let mut out = [0; 20];
+    for (i, word) in h.iter().enumerate() {
+        out[i * 4..i * 4 + 4].copy_from_slice(&word.to_be_bytes());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_names_follow_each_fuzzer() {
+        assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            Format::LibFuzzer.file_name(0, "a.rs", "abc"),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        assert_eq!(
+            Format::Aflpp.file_name(7, "traits/a,b.rs", "abc"),
+            "id:000007,orig:traits_a_b.rs"
+        );
+        assert_eq!(
+            Format::Honggfuzz.file_name(0, "a.rs", "abc"),
+            "a9993e364706816aba3e25717850c26c9cd0d89d.00000003.honggfuzz.cov"
+        );
+        for format in Format::ALL {
+            assert_eq!(format.name().parse(), Ok(format));
+        }
+        assert_eq!(
+            "afl".parse::<Format>(),
+            Err(UnknownFormat("afl".to_owned()))
+        );
+    }
+
+    #[test]
+    fn modules_are_inlined_from_where_rustc_looks() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-export-{}", std::process::id()));
+        fs::create_dir_all(dir.join("a")).expect("creates the seed");
+        fs::create_dir_all(dir.join("elsewhere")).expect("creates the seed");
+        fs::write(dir.join("a.rs"), "mod b;\npub fn a() {}\n").expect("writes a module");
+        fs::write(dir.join("a/b.rs"), "pub fn b() {}\n").expect("writes a module");
+        fs::write(dir.join("elsewhere/c.rs"), "pub fn c() {}\n").expect("writes a module");
+        let path = dir.join("main.rs");
+        let source = "mod a;\n#[path = \"elsewhere/c.rs\"]\nmod c;\nmod missing;\nfn main() {}\n";
+
+        let flattened = flatten(&path, source)
+            .expect("reads the modules")
+            .expect("has modules");
+        let file = syn::parse_file(&flattened.source).expect("parses");
+        assert_eq!(file.items.len(), 4);
+        assert!(
+            flattened.source.contains("pub fn b()"),
+            "{}",
+            flattened.source
+        );
+        assert!(
+            flattened.source.contains("pub fn c()"),
+            "{}",
+            flattened.source
+        );
+        assert!(!flattened.source.contains("#[path"), "{}", flattened.source);
+        assert!(
+            flattened.source.contains("mod missing;"),
+            "{}",
+            flattened.source
+        );
+        assert_eq!(
+            flattened.files,
+            BTreeSet::from([
+                dir.join("a.rs"),
+                dir.join("a/b.rs"),
+                dir.join("elsewhere/c.rs")
+            ])
+        );
+        assert_eq!(flatten(&path, "fn main() {}").expect("parses"), None);
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,172 @@
+//! Generated seeds on disk.
+//!
+//! [`write`] draws program `i` of a run from generator stream `i` of its
+//! master seed (see [`SplitRng`]), so the [`Provenance`] in a seed's
+//! sidecar is enough to regenerate it alone. [`instantiate`] writes every
+//! instantiation of a set of templates.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
+use super::meta::{self, Metadata, Provenance};
+use crate::gen::{ExprGen, Level, Template};
+use crate::rng::{SplitRng, Stream};
+
+/// Writes `count` programs from `generate`, for Rust `level`, to `output`
+/// as `<prefix>-<i>.rs`. Program `i` is drawn from generator stream `i`
+/// of `seed`, and its sidecar records the edition of `level` and the
+/// provenance `provenance` gives for `i`.
+pub fn write(
+    output: &Path,
+    prefix: &str,
+    count: usize,
+    seed: u64,
+    level: Level,
+    mut generate: impl FnMut(&mut dyn RngCore) -> String,
+    provenance: impl Fn(usize) -> Provenance,
+) -> io::Result<()> {
+    fs::create_dir_all(output)?;
+    let streams = SplitRng::new(seed);
+    for i in 0..count {
+        let mut rng = streams.rng(Stream::Generator, i as u64);
+        let path = output.join(format!("{prefix}-{i:05}.rs"));
+        let program = generate(&mut rng);
+        fs::write(&path, &program)?;
+        Metadata {
+            edition: Some(level.edition().to_owned()),
+            ..Metadata::new(&program, provenance(i))
+        }
+        .save(&path)?;
+    }
+    Ok(())
+}
+
+/// Writes the instantiations of each template in `templates` within a
+/// budget of `budget` each, filled for Rust `level` from `seed`, to
+/// `output` as `<template stem>-<i>.rs`, with sidecars naming the template.
+/// Returns each template's number of holes and of instantiations.
+pub fn instantiate(
+    templates: &[PathBuf],
+    budget: usize,
+    seed: u64,
+    level: Level,
+    output: &Path,
+) -> io::Result<Vec<(usize, usize)>> {
+    fs::create_dir_all(output)?;
+    let gen = ExprGen {
+        level,
+        ..ExprGen::default()
+    };
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut counts = Vec::new();
+    for path in templates {
+        let text = fs::read_to_string(path)
+            .map_err(|e| io::Error::new(e.kind(), format!("reading {}: {e}", path.display())))?;
+        let template = Template::parse(&text).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("parsing {}: {e}", path.display()),
+            )
+        })?;
+        let stem = path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .unwrap_or_else(|| "template".to_owned());
+        let programs = template.instantiate(&gen, budget, &mut rng);
+        for (i, program) in programs.iter().enumerate() {
+            let out = output.join(format!("{stem}-{i:05}.rs"));
+            fs::write(&out, program)?;
+            let provenance = Provenance::Template {
+                template: path.display().to_string(),
+                template_hash: meta::content_hash(&text),
+                seed,
+                index: i,
+                level: level.name().to_owned(),
+            };
+            Metadata {
+                edition: Some(level.edition().to_owned()),
+                ..Metadata::new(program, provenance)
+            }
+            .save(&out)?;
+        }
+        counts.push((template.holes().count(), programs.len()));
+    }
+    Ok(counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "rustc-fuzz-generated-{name}-{}",
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn each_program_comes_from_its_own_stream() {
+        let output = dir("streams");
+        let provenance = |index| Provenance::Generated {
+            seed: 5,
+            index,
+            level: Level::Latest.name().to_owned(),
+            constant: false,
+            library: None,
+            weights: None,
+            generator: None,
+        };
+        let draw = |rng: &mut dyn RngCore| format!("const N: u64 = {};\n", rng.next_u64());
+        write(&output, "gen", 3, 5, Level::Latest, draw, provenance).expect("writes");
+        let second = output.join("gen-00001.rs");
+        let source = fs::read_to_string(&second).expect("reads");
+        let alone = draw(&mut SplitRng::new(5).rng(Stream::Generator, 1));
+        assert_eq!(source, alone);
+        let metadata = Metadata::load(&second)
+            .expect("reads")
+            .expect("has a sidecar");
+        assert_eq!(metadata.provenance, provenance(1));
+        assert_eq!(metadata.edition.as_deref(), Some(Level::Latest.edition()));
+        assert!(!output.join("gen-00003.rs").exists());
+        fs::remove_dir_all(&output).expect("cleans up");
+    }
+
+    #[test]
+    fn instantiations_name_their_template() {
+        let output = dir("templates");
+        fs::create_dir_all(&output).expect("creates the output");
+        let path = output.join("skeleton.template");
+        let text = "fn main() {\n    let x: i32 = /*HOLE:expr:i32*/;\n}\n";
+        fs::write(&path, text).expect("writes the template");
+        let counts =
+            instantiate(std::slice::from_ref(&path), 3, 1, Level::Latest, &output).expect("writes");
+        let [(1, programs)] = counts[..] else {
+            panic!("one template of one hole: {counts:?}");
+        };
+        assert!((1..=3).contains(&programs), "{programs}");
+        let first = output.join("skeleton-00000.rs");
+        let metadata = Metadata::load(&first)
+            .expect("reads")
+            .expect("has a sidecar");
+        let Provenance::Template {
+            template,
+            template_hash,
+            ..
+        } = metadata.provenance
+        else {
+            panic!("{:?}", metadata.provenance);
+        };
+        assert_eq!(template, path.display().to_string());
+        assert_eq!(template_hash, meta::content_hash(text));
+
+        fs::write(&path, "fn main() { /*HOLE:nothing*/ }").expect("writes the template");
+        let error = instantiate(&[path], 3, 1, Level::Latest, &output).expect_err("bad hole");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData, "{error}");
+        fs::remove_dir_all(&output).expect("cleans up");
+    }
+}
@@ -0,0 +1,190 @@
+//! Seeds from rust-lang/glacier.
+//!
+//! Glacier collects reproducers for reported ICEs: `ices/` holds the ones
+//! still open and `fixed/` the ones that were fixed, each named after its
+//! rust-lang/rust issue. A reproducer is either a `.rs` file compiled with
+//! plain `rustc`, or a `.sh` script that feeds a heredoc to `rustc` with
+//! flags. Known crashers are the best possible seeds for the mutators,
+//! since they sit right next to fragile code, but the crash itself is not
+//! news: seeds from `ices/` record their issue in
+//! [`Metadata::known_issues`] so findings on them are not reported as new.
+//! Seeds from `fixed/` do not, since crashing again is a regression.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::meta::{Metadata, Provenance};
+
+/// The directories of a glacier checkout, and whether their ICEs are
+/// fixed.
+const DIRECTORIES: [(&str, bool); 2] = [("ices", false), ("fixed", true)];
+
+/// One reproducer.
+#[derive(Debug, Clone)]
+pub struct Reproducer {
+    /// rust-lang/rust issue number.
+    pub issue: u64,
+    pub fixed: bool,
+    /// Where to write it, relative to the output: `ices/N.rs` or
+    /// `fixed/N.rs`.
+    pub path: PathBuf,
+    pub source: String,
+    pub metadata: Metadata,
+}
+
+/// Every reproducer in the glacier checkout at `repo` that is a plain
+/// source file or a script in the usual `rustc ... - <<'EOF'` shape.
+/// Files not named after an issue, and scripts that do anything else, are
+/// left out.
+pub fn import(repo: &Path) -> io::Result<Vec<Reproducer>> {
+    let mut reproducers = Vec::new();
+    for (dir, fixed) in DIRECTORIES {
+        let mut paths: Vec<PathBuf> = match fs::read_dir(repo.join(dir)) {
+            Ok(entries) => entries
+                .map(|entry| entry.map(|entry| entry.path()))
+                .collect::<io::Result<_>>()?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        paths.sort();
+        for path in paths {
+            let Some(issue) = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .and_then(|stem| stem.parse::<u64>().ok())
+            else {
+                continue;
+            };
+            let text = match fs::read_to_string(&path) {
+                Ok(text) => text,
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => continue,
+                Err(e) => return Err(e),
+            };
+            let (source, flags) = match path.extension().and_then(|ext| ext.to_str()) {
+                Some("rs") => (text, Vec::new()),
+                Some("sh") => match script(&text) {
+                    Some(script) => script,
+                    None => continue,
+                },
+                _ => continue,
+            };
+            let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+            let provenance = Provenance::Imported {
+                origin: format!("rust-lang/glacier:{dir}/{file_name}"),
+            };
+            let (edition, flags) = split_edition(flags);
+            let metadata = Metadata {
+                edition,
+                flags,
+                known_issues: if fixed { Vec::new() } else { vec![issue] },
+                ..Metadata::new(&source, provenance)
+            };
+            reproducers.push(Reproducer {
+                issue,
+                fixed,
+                path: Path::new(dir).join(format!("{issue}.rs")),
+                source,
+                metadata,
+            });
+        }
+    }
+    Ok(reproducers)
+}
+
+/// The source and rustc flags of a script that pipes a heredoc into
+/// `rustc <flags> -`.
+fn script(text: &str) -> Option<(String, Vec<String>)> {
+    let mut lines = text.lines();
+    let command = lines.find(|line| line.trim_start().starts_with("rustc "))?;
+    let (invocation, marker) = command.split_once("<<")?;
+    let marker = marker
+        .trim()
+        .trim_start_matches('-')
+        .trim_matches(['\'', '"']);
+    let mut flags = Vec::new();
+    let mut reads_stdin = false;
+    for word in invocation.split_whitespace().skip(1) {
+        match word {
+            "-" => reads_stdin = true,
+            word => flags.push(word.trim_matches(['\'', '"']).to_owned()),
+        }
+    }
+    if !reads_stdin || marker.is_empty() {
+        return None;
+    }
+    let mut source = String::new();
+    for line in lines {
+        if line.trim() == marker {
+            return Some((source, flags));
+        }
+        source.push_str(line);
+        source.push('\n');
+    }
+    None
+}
+
+/// Moves `--edition X` or `--edition=X` out of `flags`.
+fn split_edition(flags: Vec<String>) -> (Option<String>, Vec<String>) {
+    let mut edition = None;
+    let mut rest = Vec::new();
+    let mut flags = flags.into_iter();
+    while let Some(flag) = flags.next() {
+        if flag == "--edition" {
+            edition = flags.next();
+        } else if let Some(value) = flag.strip_prefix("--edition=") {
+            edition = Some(value.to_owned());
+        } else {
+            rest.push(flag);
+        }
+    }
+    (edition, rest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scripts_give_their_heredoc_and_flags() {
+        let text = "#!/bin/bash\n\nrustc --edition=2021 -Zmir-opt-level=3 - <<'EOF'\n\
+                    fn main() {}\nEOF\n";
+        let (source, flags) = script(text).expect("a rustc heredoc");
+        assert_eq!(source, "fn main() {}\n");
+        assert_eq!(flags, ["--edition=2021", "-Zmir-opt-level=3"]);
+        assert_eq!(
+            split_edition(flags),
+            (
+                Some("2021".to_owned()),
+                vec!["-Zmir-opt-level=3".to_owned()]
+            )
+        );
+        assert_eq!(script("rustc main.rs\n"), None);
+        assert_eq!(script("rustc - <<EOF\nfn main() {}\n"), None);
+    }
+
+    #[test]
+    fn only_open_ices_are_known_issues() {
+        let repo = std::env::temp_dir().join(format!("rustc-fuzz-glacier-{}", std::process::id()));
+        fs::create_dir_all(repo.join("ices")).expect("creates ices");
+        fs::create_dir_all(repo.join("fixed")).expect("creates fixed");
+        fs::write(repo.join("ices/100.rs"), "fn open() {}\n").expect("writes an ice");
+        fs::write(
+            repo.join("ices/101.sh"),
+            "rustc --edition 2018 - <<'EOF'\nfn script() {}\nEOF\n",
+        )
+        .expect("writes an ice");
+        fs::write(repo.join("ices/README.rs"), "fn unnamed() {}\n").expect("writes a file");
+        fs::write(repo.join("fixed/50.rs"), "fn fixed() {}\n").expect("writes a fixed ice");
+
+        let reproducers = import(&repo).expect("imports");
+        let issues: Vec<(u64, bool)> = reproducers.iter().map(|r| (r.issue, r.fixed)).collect();
+        assert_eq!(issues, [(100, false), (101, false), (50, true)]);
+        assert_eq!(reproducers[0].metadata.known_issues, [100]);
+        assert_eq!(reproducers[1].source, "fn script() {}\n");
+        assert_eq!(reproducers[1].metadata.edition.as_deref(), Some("2018"));
+        assert_eq!(reproducers[1].path, Path::new("ices/101.rs"));
+        assert!(reproducers[2].metadata.known_issues.is_empty());
+        fs::remove_dir_all(&repo).expect("cleans up");
+    }
+}
@@ -0,0 +1,736 @@
+//! Seeds from crates.io.
+//!
+//! Synthetic seeds exercise what the generator and mutators know about;
+//! real crates exercise everything else. A [`Harvester`] lists crates on
+//! crates.io (the most downloaded, or a random sample), downloads and
+//! unpacks them with `curl` and `tar`, and cuts every source file into
+//! [`fragments`]: the file itself with everything that points outside it
+//! stripped, or failing that each group of items that only refer to each
+//! other. Fragments that compile on their own become seeds.
+//!
+//! crates.io asks crawlers for at most one request per second and a
+//! `User-Agent` saying who they are; [`Harvester::delay`] and
+//! [`Harvester::user_agent`] see to both.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+
+use proc_macro2::{TokenStream, TokenTree};
+use quote::ToTokens;
+use rand::Rng;
+use serde::Deserialize;
+
+use super::meta::{self, Metadata, Provenance};
+use super::Corpus;
+use crate::harness::{ExecOutcome, Rustc};
+
+/// Crate roots that every fragment can still refer to.
+const BUILTIN_ROOTS: &[&str] = &["std", "core", "alloc", "self"];
+
+/// Crate-level attributes worth keeping on a fragment. The rest only make
+/// it depend on files and configuration it does not have
+/// (`#![doc = include_str!(..)]`, `#![cfg_attr(docsrs, ..)]`) or turn the
+/// warnings of a cut-down crate into errors (`#![deny(unused)]`).
+const KEPT_INNER_ATTRIBUTES: &[&str] = &["feature", "no_std"];
+
+/// Attributes that keep an item from being compiled outside `--test`
+/// builds; dropped so the item is compiled as a plain one.
+const TEST_ATTRIBUTES: &[&str] = &["test", "should_panic", "ignore"];
+
+/// Largest number of item groups tried per file, so a huge file does not
+/// cost hundreds of compilations.
+const MAX_GROUPS: usize = 16;
+
+/// One published crate version.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrateVersion {
+    pub name: String,
+    pub version: String,
+}
+
+impl CrateVersion {
+    /// The crate a `name-version.crate` archive holds, from its file name.
+    pub fn from_archive(archive: &Path) -> Option<Self> {
+        let stem = archive.file_stem()?.to_str()?;
+        let split = stem
+            .match_indices('-')
+            .map(|(i, _)| i)
+            .find(|&i| stem[i + 1..].starts_with(|c: char| c.is_ascii_digit()))?;
+        Some(CrateVersion {
+            name: stem[..split].to_owned(),
+            version: stem[split + 1..].to_owned(),
+        })
+    }
+}
+
+/// A compilable piece of a crate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fragment {
+    pub source: String,
+    /// `crates.io:name@version/path/in/crate.rs`.
+    pub origin: String,
+    /// The crate's edition, from its manifest.
+    pub edition: String,
+}
+
+/// What [`Harvester::harvest_into`] got out of its crates.
+#[derive(Debug, Default)]
+pub struct Harvest {
+    /// Each crate harvested, with the number of fragments it gave.
+    pub crates: Vec<(CrateVersion, usize)>,
+    /// The crates that could not be downloaded, and why.
+    pub skipped: Vec<(CrateVersion, io::Error)>,
+}
+
+impl Harvest {
+    /// Fragments written in all.
+    pub fn seeds(&self) -> usize {
+        self.crates.iter().map(|(_, fragments)| fragments).sum()
+    }
+}
+
+/// Lists, downloads and cuts up crates.
+#[derive(Debug, Clone)]
+pub struct Harvester {
+    /// Compiles candidate fragments. Its phase decides how far a fragment
+    /// has to get to be kept.
+    pub driver: Rustc,
+    pub curl: PathBuf,
+    pub tar: PathBuf,
+    pub api: String,
+    pub downloads: String,
+    pub user_agent: String,
+    /// Pause before every request to crates.io.
+    pub delay: Duration,
+}
+
+impl Default for Harvester {
+    fn default() -> Self {
+        Harvester {
+            driver: Rustc::default(),
+            curl: PathBuf::from("curl"),
+            tar: PathBuf::from("tar"),
+            api: "https://crates.io/api/v1".to_owned(),
+            downloads: "https://static.crates.io/crates".to_owned(),
+            user_agent: "rustc-fuzz corpus harvester".to_owned(),
+            delay: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Listing {
+    crates: Vec<Listed>,
+    meta: ListingMeta,
+}
+
+#[derive(Deserialize)]
+struct Listed {
+    name: String,
+    max_stable_version: Option<String>,
+    max_version: String,
+}
+
+#[derive(Deserialize)]
+struct ListingMeta {
+    total: usize,
+}
+
+impl Listed {
+    fn version(self) -> CrateVersion {
+        CrateVersion {
+            version: self.max_stable_version.unwrap_or(self.max_version),
+            name: self.name,
+        }
+    }
+}
+
+impl Harvester {
+    /// The `count` most downloaded crates, at their newest stable versions.
+    pub fn top(&self, count: usize) -> io::Result<Vec<CrateVersion>> {
+        let mut crates = Vec::new();
+        let mut page = 1;
+        while crates.len() < count {
+            let listing = self.list("downloads", page)?;
+            if listing.crates.is_empty() {
+                break;
+            }
+            crates.extend(listing.crates.into_iter().map(Listed::version));
+            page += 1;
+        }
+        crates.truncate(count);
+        Ok(crates)
+    }
+
+    /// `count` crates picked at random, one from each of `count` random
+    /// pages of the alphabetical listing.
+    pub fn sample(&self, count: usize, rng: &mut impl Rng) -> io::Result<Vec<CrateVersion>> {
+        let pages = self.list("alpha", 1)?.meta.total.div_ceil(100).max(1);
+        let mut crates = Vec::new();
+        for _ in 0..count {
+            let mut listing = self.list("alpha", rng.gen_range(1..=pages))?;
+            if !listing.crates.is_empty() {
+                let i = rng.gen_range(0..listing.crates.len());
+                crates.push(listing.crates.swap_remove(i).version());
+            }
+        }
+        Ok(crates)
+    }
+
+    fn list(&self, sort: &str, page: usize) -> io::Result<Listing> {
+        let url = format!("{}/crates?sort={sort}&per_page=100&page={page}", self.api);
+        let json = self.fetch(&url, None)?;
+        serde_json::from_str(&json).map_err(io::Error::from)
+    }
+
+    /// Downloads `krate` and unpacks it under `dir`, returning the
+    /// directory it unpacked to.
+    pub fn download(&self, krate: &CrateVersion, dir: &Path) -> io::Result<PathBuf> {
+        let CrateVersion { name, version } = krate;
+        let archive = dir.join(format!("{name}-{version}.crate"));
+        let url = format!("{}/{name}/{name}-{version}.crate", self.downloads);
+        self.fetch(&url, Some(&archive))?;
+        self.unpack(&archive, dir)
+    }
+
+    /// Unpacks a `.crate` archive under `dir`, returning the directory it
+    /// unpacked to.
+    pub fn unpack(&self, archive: &Path, dir: &Path) -> io::Result<PathBuf> {
+        // tar runs in a scratch directory of its own, so relative paths
+        // would be looked up there.
+        let outcome = self.driver.run_program(
+            &self.tar,
+            [
+                "-xzf".as_ref(),
+                std::path::absolute(archive)?.as_os_str(),
+                "-C".as_ref(),
+                std::path::absolute(dir)?.as_os_str(),
+            ],
+            &[],
+        )?;
+        if !outcome.is_success() {
+            return Err(io::Error::other(format!(
+                "unpacking {}: {outcome}: {}",
+                archive.display(),
+                outcome.output().stderr.trim()
+            )));
+        }
+        // A `.crate` holds a single `name-version/` directory.
+        let stem = archive.file_stem().unwrap_or_default();
+        Ok(dir.join(stem))
+    }
+
+    /// Runs `curl` on `url`, writing to `output` or returning the body.
+    fn fetch(&self, url: &str, output: Option<&Path>) -> io::Result<String> {
+        thread::sleep(self.delay);
+        let mut args = vec![
+            "--silent".to_owned(),
+            "--show-error".to_owned(),
+            "--fail".to_owned(),
+            "--location".to_owned(),
+            "--user-agent".to_owned(),
+            self.user_agent.clone(),
+        ];
+        if let Some(output) = output {
+            let output = std::path::absolute(output)?;
+            args.extend(["--output".to_owned(), output.display().to_string()]);
+        }
+        args.push(url.to_owned());
+        // Downloads are not compilations; give them their own budget.
+        let curl = Rustc {
+            timeout: Duration::from_secs(120),
+            ..self.driver.clone()
+        };
+        match curl.run_program(&self.curl, &args, &[])? {
+            ExecOutcome::Success(output) => Ok(output.stdout),
+            outcome => Err(io::Error::other(format!(
+                "fetching {url}: {outcome}: {}",
+                outcome.output().stderr.trim()
+            ))),
+        }
+    }
+
+    /// The fragments of the unpacked crate at `dir` that compile.
+    /// `krate` names it in their origins.
+    pub fn harvest(&self, krate: &CrateVersion, dir: &Path) -> io::Result<Vec<Fragment>> {
+        let edition = fs::read_to_string(dir.join("Cargo.toml"))
+            .ok()
+            .and_then(|manifest| manifest_edition(&manifest))
+            .unwrap_or_else(|| "2015".to_owned());
+        let mut kept = Vec::new();
+        for entry in Corpus::open(dir).entries()? {
+            let path = entry.path.strip_prefix(dir).unwrap_or(&entry.path);
+            let origin = format!(
+                "crates.io:{}@{}/{}",
+                krate.name,
+                krate.version,
+                path.display()
+            );
+            let fragment = |source| Fragment {
+                source,
+                origin: origin.clone(),
+                edition: edition.clone(),
+            };
+            let mut candidates = fragments(&entry.source).into_iter();
+            // When the whole file compiles, its groups add nothing.
+            if let Some(whole) = candidates.next() {
+                if self.compiles(&whole, &edition)? {
+                    kept.push(fragment(whole));
+                    continue;
+                }
+            }
+            for group in candidates {
+                if self.compiles(&group, &edition)? {
+                    kept.push(fragment(group));
+                }
+            }
+        }
+        Ok(kept)
+    }
+
+    /// Downloads `crates` and unpacks them and the `.crate` files in
+    /// `archives` under `scratch`, then writes their fragments to a
+    /// `name-version` directory each under `output`, named by content hash,
+    /// with sidecars naming where they came from and recording that they
+    /// compiled with `verified_with`, a `rustc -V`. Crates that fail to
+    /// download are skipped; anything else going wrong stops the harvest.
+    pub fn harvest_into(
+        &self,
+        crates: &[CrateVersion],
+        archives: &[PathBuf],
+        scratch: &Path,
+        output: &Path,
+        verified_with: &str,
+    ) -> io::Result<Harvest> {
+        let mut harvest = Harvest::default();
+        let mut unpacked = Vec::new();
+        for krate in crates {
+            match self.download(krate, scratch) {
+                Ok(dir) => unpacked.push((krate.clone(), dir)),
+                Err(e) => harvest.skipped.push((krate.clone(), e)),
+            }
+        }
+        for archive in archives {
+            let krate = CrateVersion::from_archive(archive).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("{} is not a name-version.crate", archive.display()),
+                )
+            })?;
+            let dir = self.unpack(archive, scratch)?;
+            unpacked.push((krate, dir));
+        }
+        for (krate, dir) in unpacked {
+            let fragments = self.harvest(&krate, &dir).map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("harvesting {}@{}: {e}", krate.name, krate.version),
+                )
+            })?;
+            let dest = output.join(format!("{}-{}", krate.name, krate.version));
+            fs::create_dir_all(&dest)?;
+            for fragment in &fragments {
+                let path = dest.join(format!("{}.rs", meta::content_hash(&fragment.source)));
+                fs::write(&path, &fragment.source)?;
+                let provenance = Provenance::Imported {
+                    origin: fragment.origin.clone(),
+                };
+                Metadata {
+                    edition: Some(fragment.edition.clone()),
+                    verified_with: Some(verified_with.to_owned()),
+                    ..Metadata::new(&fragment.source, provenance)
+                }
+                .save(&path)?;
+            }
+            tracing::info!(
+                name = krate.name,
+                version = krate.version,
+                fragments = fragments.len(),
+                "harvested"
+            );
+            harvest.crates.push((krate, fragments.len()));
+        }
+        Ok(harvest)
+    }
+
+    fn compiles(&self, source: &str, edition: &str) -> io::Result<bool> {
+        let outcome = self.driver.run(
+            [
+                "--crate-type=lib",
+                "--edition",
+                edition,
+                "--cap-lints=allow",
+                "-",
+            ],
+            source.as_bytes(),
+        )?;
+        Ok(outcome.is_success())
+    }
+}
+
+/// Candidate seeds cut from one source file: first the whole file with
+/// its external references stripped, then each group of items that only
+/// refer to each other. Files that do not parse give none.
+pub fn fragments(source: &str) -> Vec<String> {
+    let Ok(file) = syn::parse_file(source) else {
+        return Vec::new();
+    };
+    let attrs: Vec<syn::Attribute> = file
+        .attrs
+        .into_iter()
+        .filter(|attr| {
+            KEPT_INNER_ATTRIBUTES
+                .iter()
+                .any(|name| attr.path().is_ident(name))
+        })
+        .collect();
+    let (uses, items): (Vec<syn::Item>, Vec<syn::Item>) = file
+        .items
+        .into_iter()
+        .filter_map(self_contained)
+        .partition(|item| matches!(item, syn::Item::Use(_)));
+    if items.is_empty() {
+        return Vec::new();
+    }
+    let render = |items: Vec<syn::Item>| {
+        prettyplease::unparse(&syn::File {
+            shebang: None,
+            attrs: attrs.clone(),
+            items: uses.iter().cloned().chain(items).collect(),
+        })
+    };
+
+    let mut fragments = vec![render(items.clone())];
+    let groups = item_groups(&items);
+    if groups.len() > 1 {
+        fragments.extend(
+            groups
+                .into_iter()
+                .take(MAX_GROUPS)
+                .map(|group| render(group.into_iter().map(|i| items[i].clone()).collect())),
+        );
+    }
+    fragments
+}
+
+/// `item` with what a single file cannot have taken out, or `None` if
+/// nothing is left: out-of-line modules, test-only items, `extern crate`s
+/// and `use`s of other crates or other modules of this one.
+fn self_contained(mut item: syn::Item) -> Option<syn::Item> {
+    if let Some(attrs) = attributes(&mut item) {
+        if is_test_only(attrs) {
+            return None;
+        }
+        // Doc attributes are often `include_str!`s of files the fragment
+        // does not come with.
+        attrs.retain(|attr| {
+            !attr.path().is_ident("doc")
+                && !TEST_ATTRIBUTES
+                    .iter()
+                    .any(|name| attr.path().is_ident(name))
+        });
+    }
+    match &mut item {
+        syn::Item::Mod(module) => {
+            let (_, items) = module.content.take()?;
+            let items = items.into_iter().filter_map(self_contained).collect();
+            module.content = Some((Default::default(), items));
+        }
+        syn::Item::ExternCrate(krate)
+            if !BUILTIN_ROOTS.contains(&krate.ident.to_string().as_str()) =>
+        {
+            return None
+        }
+        syn::Item::Use(item) if !is_builtin(&item.tree) => return None,
+        _ => {}
+    }
+    Some(item)
+}
+
+/// Whether a `use` tree only names the standard library or the current
+/// module.
+fn is_builtin(tree: &syn::UseTree) -> bool {
+    match tree {
+        syn::UseTree::Path(path) => BUILTIN_ROOTS.contains(&path.ident.to_string().as_str()),
+        syn::UseTree::Group(group) => group.items.iter().all(is_builtin),
+        _ => false,
+    }
+}
+
+/// Whether a `cfg` among `attrs` mentions `test`. Such items are compiled
+/// out of non-test builds, so they would compile whatever they contain.
+fn is_test_only(attrs: &[syn::Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("cfg")
+            && attr.meta.require_list().is_ok_and(|list| {
+                let mut idents = Vec::new();
+                collect_idents(list.tokens.clone(), &mut idents);
+                idents.iter().any(|ident| ident == "test")
+            })
+    })
+}
+
+fn attributes(item: &mut syn::Item) -> Option<&mut Vec<syn::Attribute>> {
+    Some(match item {
+        syn::Item::Const(item) => &mut item.attrs,
+        syn::Item::Enum(item) => &mut item.attrs,
+        syn::Item::ExternCrate(item) => &mut item.attrs,
+        syn::Item::Fn(item) => &mut item.attrs,
+        syn::Item::ForeignMod(item) => &mut item.attrs,
+        syn::Item::Impl(item) => &mut item.attrs,
+        syn::Item::Macro(item) => &mut item.attrs,
+        syn::Item::Mod(item) => &mut item.attrs,
+        syn::Item::Static(item) => &mut item.attrs,
+        syn::Item::Struct(item) => &mut item.attrs,
+        syn::Item::Trait(item) => &mut item.attrs,
+        syn::Item::TraitAlias(item) => &mut item.attrs,
+        syn::Item::Type(item) => &mut item.attrs,
+        syn::Item::Union(item) => &mut item.attrs,
+        syn::Item::Use(item) => &mut item.attrs,
+        _ => return None,
+    })
+}
+
+/// Partitions `items` into groups of items that refer to each other by
+/// name, each in source order, largest first.
+fn item_groups(items: &[syn::Item]) -> Vec<Vec<usize>> {
+    let mut defined: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, item) in items.iter().enumerate() {
+        if let Some(name) = item_name(item) {
+            defined.entry(name).or_default().push(i);
+        }
+    }
+    let mut parent: Vec<usize> = (0..items.len()).collect();
+    fn root(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for (i, item) in items.iter().enumerate() {
+        let mut idents = Vec::new();
+        collect_idents(item.to_token_stream(), &mut idents);
+        for ident in idents {
+            for &j in defined.get(&ident).into_iter().flatten() {
+                let (a, b) = (root(&mut parent, i), root(&mut parent, j));
+                parent[a] = b;
+            }
+        }
+    }
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..items.len() {
+        let r = root(&mut parent, i);
+        groups.entry(r).or_default().push(i);
+    }
+    let mut groups: Vec<Vec<usize>> = groups.into_values().collect();
+    groups.sort_by(|a, b| b.len().cmp(&a.len()).then(a.cmp(b)));
+    groups
+}
+
+/// The name an item defines, if any.
+pub(super) fn item_name(item: &syn::Item) -> Option<String> {
+    let ident = match item {
+        syn::Item::Const(item) => &item.ident,
+        syn::Item::Enum(item) => &item.ident,
+        syn::Item::Fn(item) => &item.sig.ident,
+        syn::Item::Macro(item) => item.ident.as_ref()?,
+        syn::Item::Mod(item) => &item.ident,
+        syn::Item::Static(item) => &item.ident,
+        syn::Item::Struct(item) => &item.ident,
+        syn::Item::Trait(item) => &item.ident,
+        syn::Item::TraitAlias(item) => &item.ident,
+        syn::Item::Type(item) => &item.ident,
+        syn::Item::Union(item) => &item.ident,
+        _ => return None,
+    };
+    Some(ident.to_string())
+}
+
+pub(super) fn collect_idents(tokens: TokenStream, out: &mut Vec<String>) {
+    for tree in tokens {
+        match tree {
+            TokenTree::Ident(ident) => out.push(ident.to_string()),
+            TokenTree::Group(group) => collect_idents(group.stream(), out),
+            _ => {}
+        }
+    }
+}
+
+/// The `edition` in a `Cargo.toml`'s `[package]` section.
+fn manifest_edition(manifest: &str) -> Option<String> {
+    let mut in_package = false;
+    for line in manifest.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_package = line == "[package]";
+        } else if in_package {
+            if let Some(value) = line.strip_prefix("edition") {
+                let value = value.trim_start().strip_prefix('=')?.trim();
+                return Some(value.trim_matches('"').to_owned());
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    #[test]
+    fn crate_version_comes_from_the_archive_name() {
+        assert_eq!(
+            CrateVersion::from_archive(Path::new("dir/serde-json-1.0.2-rc.1.crate")),
+            Some(CrateVersion {
+                name: "serde-json".to_owned(),
+                version: "1.0.2-rc.1".to_owned(),
+            })
+        );
+        assert_eq!(CrateVersion::from_archive(Path::new("serde.crate")), None);
+    }
+
+    #[test]
+    fn unpacks_relative_paths() {
+        // Relative to the package, where tests run, and so nowhere tar's
+        // scratch directory could resolve it.
+        let root = PathBuf::from(format!(
+            "../target/rustc-fuzz-harvest-{}",
+            std::process::id()
+        ));
+        let packed = root.join("x-1.0.0");
+        fs::create_dir_all(packed.join("src")).expect("creates the crate");
+        fs::write(packed.join("src/lib.rs"), "pub fn f() {}\n").expect("writes the crate");
+        let status = Command::new("tar")
+            .arg("-czf")
+            .arg(root.join("x-1.0.0.crate"))
+            .arg("-C")
+            .arg(&root)
+            .arg("x-1.0.0")
+            .status()
+            .expect("runs tar");
+        assert!(status.success());
+        let out = root.join("out");
+        fs::create_dir_all(&out).expect("creates the output");
+
+        let harvester = Harvester::default();
+        let unpacked = harvester
+            .unpack(&root.join("x-1.0.0.crate"), &out)
+            .expect("unpacks");
+        assert!(unpacked.join("src/lib.rs").is_file());
+        let error = harvester
+            .unpack(&root.join("y-1.0.0.crate"), &out)
+            .expect_err("no such archive");
+        assert!(error.to_string().contains("y-1.0.0.crate: "), "{error}");
+        assert!(!error.to_string().ends_with(": "), "no tar stderr: {error}");
+        fs::remove_dir_all(&root).expect("cleans up");
+    }
+
+    #[test]
+    fn harvests_archives_with_their_origin() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let root =
+            std::env::temp_dir().join(format!("rustc-fuzz-harvest-into-{}", std::process::id()));
+        let packed = root.join("z-0.1.0");
+        fs::create_dir_all(packed.join("src")).expect("creates the crate");
+        fs::write(
+            packed.join("Cargo.toml"),
+            "[package]\nname = \"z\"\nedition = \"2021\"\n",
+        )
+        .expect("writes the manifest");
+        fs::write(packed.join("src/lib.rs"), "pub fn f() {}\n").expect("writes the crate");
+        let status = Command::new("tar")
+            .arg("-czf")
+            .arg(root.join("z-0.1.0.crate"))
+            .arg("-C")
+            .arg(&root)
+            .arg("z-0.1.0")
+            .status()
+            .expect("runs tar");
+        assert!(status.success());
+        // Every fragment compiles with a rustc that reads its input and
+        // succeeds.
+        let rustc = root.join("rustc");
+        fs::write(&rustc, "#!/bin/sh\ncat >/dev/null\n").expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let (scratch, out) = (root.join("scratch"), root.join("out"));
+        fs::create_dir_all(&scratch).expect("creates the scratch directory");
+
+        let harvester = Harvester {
+            driver: Rustc::new(&rustc),
+            ..Harvester::default()
+        };
+        let harvest = harvester
+            .harvest_into(
+                &[],
+                &[root.join("z-0.1.0.crate")],
+                &scratch,
+                &out,
+                "rustc 1.0.0",
+            )
+            .expect("harvests");
+        let krate = CrateVersion {
+            name: "z".to_owned(),
+            version: "0.1.0".to_owned(),
+        };
+        assert_eq!(harvest.crates, [(krate, 1)]);
+        let seed = out.join(format!(
+            "z-0.1.0/{}.rs",
+            meta::content_hash("pub fn f() {}\n")
+        ));
+        let metadata = Metadata::load(&seed)
+            .expect("reads")
+            .expect("has a sidecar");
+        assert_eq!(
+            metadata.provenance,
+            Provenance::Imported {
+                origin: "crates.io:z@0.1.0/src/lib.rs".to_owned()
+            }
+        );
+        assert_eq!(metadata.edition.as_deref(), Some("2021"));
+        assert_eq!(metadata.verified_with.as_deref(), Some("rustc 1.0.0"));
+
+        let error = harvester
+            .harvest_into(&[], &[root.join("z.crate")], &scratch, &out, "")
+            .expect_err("not a name-version.crate");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput, "{error}");
+        fs::remove_dir_all(&root).expect("cleans up");
+    }
+
+    #[test]
+    fn edition_comes_from_the_package_section() {
+        let manifest =
+            "[dependencies]\nedition = \"x\"\n\n[package]\nname = \"a\"\nedition = \"2021\"\n";
+        assert_eq!(manifest_edition(manifest).as_deref(), Some("2021"));
+        assert_eq!(manifest_edition("[package]\nname = \"a\"\n"), None);
+    }
+
+    #[test]
+    fn fragments_drop_what_points_outside_the_file() {
+        let source = "\
+use serde::Serialize;
+use std::fmt;
+mod outside;
+#[cfg(test)]
+mod tests {}
+struct A;
+fn a() -> A { A }
+struct B;
+";
+        let cut = fragments(source);
+        assert_eq!(cut.len(), 3, "{cut:#?}");
+        let whole = &cut[0];
+        assert!(whole.contains("use std::fmt;"), "{whole}");
+        for gone in ["serde", "outside", "tests"] {
+            assert!(!whole.contains(gone), "{whole}");
+        }
+        assert!(cut[1].contains("struct A") && cut[1].contains("fn a"));
+        assert!(cut[2].contains("struct B") && !cut[2].contains("fn a"));
+        assert!(fragments("fn (").is_empty());
+    }
+}
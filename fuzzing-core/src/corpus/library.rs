@@ -0,0 +1,313 @@
+//! Fragment libraries.
+//!
+//! Seeds are full of functions, impls and type definitions that are worth
+//! reusing elsewhere. [`extract`] cuts them out, each with the items it
+//! refers to so it stands alone, and a [`Library`] keeps them in a JSON
+//! file. [`FragmentSplice`](crate::mutate::FragmentSplice) inserts library
+//! fragments into seeds being mutated, and
+//! [`ProgramGen`](crate::gen::ProgramGen) puts some next to the programs it
+//! generates, so what the corpus already covers feeds back into new
+//! inputs.
+
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rand::seq::SliceRandom;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use super::dedup::normalized_hash;
+use super::harvest::item_name;
+use super::meta::feature_gates;
+use super::split::{impl_self_name, Graph};
+
+/// What a fragment is built around.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Kind {
+    Fn,
+    Impl,
+    /// A struct, enum, union or type alias.
+    Type,
+    Trait,
+}
+
+/// A self-contained group of items.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fragment {
+    pub kind: Kind,
+    /// The item the fragment is built around, or `impl Type` for impls.
+    pub focus: String,
+    /// The items, with the `use`s of the file they came from.
+    pub source: String,
+    /// Every name the fragment defines or imports at the top level; two
+    /// fragments sharing one cannot go into the same file.
+    pub names: Vec<String>,
+    /// Feature gates the file it came from enabled.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// The edition it compiled with, if it needs a particular one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edition: Option<String>,
+    /// The seed it was cut from.
+    pub origin: String,
+}
+
+impl Fragment {
+    /// Whether it can go into a file that already has `names` at the top
+    /// level.
+    pub fn fits(&self, names: &HashSet<String>) -> bool {
+        !self.names.iter().any(|name| names.contains(name))
+    }
+}
+
+/// Fragments of `source`: one per function, impl, type and trait, each
+/// with what it refers to and the file's `use`s, but not its crate
+/// attributes, so fragments can be put together in one file. `fn main`
+/// and anything that needs it are left out, as is anything behind a
+/// `#[cfg]`, which would compile to nothing. Seeds that do not parse give none. The edition and origin are
+/// left for the caller, who knows them.
+pub fn extract(source: &str) -> Vec<Fragment> {
+    let Ok(file) = syn::parse_file(source) else {
+        return Vec::new();
+    };
+    let features = feature_gates(source);
+    let graph = Graph::new(file);
+    let mut imported = Vec::new();
+    for item in &graph.uses {
+        if let syn::Item::Use(item) = item {
+            use_names(&item.tree, &mut imported);
+        }
+    }
+    let mut fragments = Vec::new();
+    for (focus, item) in graph.items.iter().enumerate() {
+        let (kind, label) = match item {
+            syn::Item::Fn(_) => (Kind::Fn, item_name(item)),
+            syn::Item::Trait(_) => (Kind::Trait, item_name(item)),
+            syn::Item::Struct(_)
+            | syn::Item::Enum(_)
+            | syn::Item::Union(_)
+            | syn::Item::Type(_) => (Kind::Type, item_name(item)),
+            syn::Item::Impl(_) => (
+                Kind::Impl,
+                impl_self_name(item).map(|name| format!("impl {name}")),
+            ),
+            _ => continue,
+        };
+        let Some(label) = label else {
+            continue;
+        };
+        let included = graph.closure(focus);
+        let mut names: Vec<String> = included
+            .iter()
+            .filter_map(|&i| item_name(&graph.items[i]))
+            .chain(imported.iter().cloned())
+            .collect();
+        let configured = included.iter().any(|&i| {
+            attributes(&graph.items[i])
+                .iter()
+                .any(|a| a.path().is_ident("cfg"))
+        });
+        if configured || names.iter().any(|name| name == "main") {
+            continue;
+        }
+        names.sort();
+        names.dedup();
+        fragments.push(Fragment {
+            kind,
+            focus: label,
+            source: prettyplease::unparse(&syn::File {
+                shebang: None,
+                attrs: Vec::new(),
+                items: graph.with_uses(&included),
+            }),
+            names,
+            features: features.clone(),
+            edition: None,
+            origin: String::new(),
+        });
+    }
+    fragments
+}
+
+fn attributes(item: &syn::Item) -> &[syn::Attribute] {
+    match item {
+        syn::Item::Const(item) => &item.attrs,
+        syn::Item::Enum(item) => &item.attrs,
+        syn::Item::Fn(item) => &item.attrs,
+        syn::Item::Impl(item) => &item.attrs,
+        syn::Item::Macro(item) => &item.attrs,
+        syn::Item::Mod(item) => &item.attrs,
+        syn::Item::Static(item) => &item.attrs,
+        syn::Item::Struct(item) => &item.attrs,
+        syn::Item::Trait(item) => &item.attrs,
+        syn::Item::TraitAlias(item) => &item.attrs,
+        syn::Item::Type(item) => &item.attrs,
+        syn::Item::Union(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+/// The names `file` defines or imports at the top level.
+pub fn top_level_names(file: &syn::File) -> HashSet<String> {
+    let mut names = Vec::new();
+    for item in &file.items {
+        match item {
+            syn::Item::Use(item) => use_names(&item.tree, &mut names),
+            item => names.extend(item_name(item)),
+        }
+    }
+    names.into_iter().collect()
+}
+
+/// The names a `use` tree brings into scope. Globs bring none we know of.
+fn use_names(tree: &syn::UseTree, out: &mut Vec<String>) {
+    match tree {
+        syn::UseTree::Path(path) => use_names(&path.tree, out),
+        syn::UseTree::Name(name) if name.ident != "self" => out.push(name.ident.to_string()),
+        syn::UseTree::Name(_) => {}
+        syn::UseTree::Rename(rename) => out.push(rename.rename.to_string()),
+        syn::UseTree::Glob(_) => {}
+        syn::UseTree::Group(group) => {
+            for tree in &group.items {
+                use_names(tree, out);
+            }
+        }
+    }
+}
+
+/// A collection of fragments without duplicates.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Library {
+    pub fragments: Vec<Fragment>,
+    /// [`normalized_hash`]es of the fragments.
+    #[serde(skip)]
+    hashes: HashSet<String>,
+}
+
+impl Library {
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let mut library: Library = serde_json::from_str(&fs::read_to_string(path)?)?;
+        library.hashes = library
+            .fragments
+            .iter()
+            .map(|fragment| normalized_hash(&fragment.source))
+            .collect();
+        Ok(library)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        fs::write(path, json)
+    }
+
+    pub fn len(&self) -> usize {
+        self.fragments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fragments.is_empty()
+    }
+
+    /// Whether the library has a fragment with the normal form of
+    /// `source`.
+    pub fn contains(&self, source: &str) -> bool {
+        self.hashes.contains(&normalized_hash(source))
+    }
+
+    /// Adds `fragment` unless the library has one with the same normal
+    /// form. Returns whether it was added.
+    pub fn add(&mut self, fragment: Fragment) -> bool {
+        if !self.hashes.insert(normalized_hash(&fragment.source)) {
+            return false;
+        }
+        self.fragments.push(fragment);
+        true
+    }
+
+    /// A random fragment that `accept`s, if there is one.
+    pub fn choose(
+        &self,
+        rng: &mut dyn RngCore,
+        accept: impl Fn(&Fragment) -> bool,
+    ) -> Option<&Fragment> {
+        let candidates: Vec<&Fragment> = self.fragments.iter().filter(|f| accept(f)).collect();
+        candidates.choose(rng).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const SEED: &str = "#![feature(never_type)]\n\
+                        use std::collections::HashMap;\n\
+                        struct P(u8);\n\
+                        impl P { fn get(&self) -> u8 { self.0 } }\n\
+                        fn make() -> P { P(1) }\n\
+                        #[cfg(test)]\n\
+                        fn hidden() {}\n\
+                        fn main() { make(); }\n";
+
+    #[test]
+    fn fragments_stand_alone() {
+        let fragments = extract(SEED);
+        let foci: Vec<(Kind, &str)> = fragments
+            .iter()
+            .map(|fragment| (fragment.kind, fragment.focus.as_str()))
+            .collect();
+        assert_eq!(
+            foci,
+            [
+                (Kind::Type, "P"),
+                (Kind::Impl, "impl P"),
+                (Kind::Fn, "make")
+            ]
+        );
+        let make = &fragments[2];
+        assert_eq!(make.names, ["HashMap", "P", "make"]);
+        assert_eq!(make.features, ["never_type"]);
+        assert!(!make.source.contains("feature"), "{}", make.source);
+        assert!(make.source.contains("impl P"), "{}", make.source);
+        let file = syn::parse_file(SEED).expect("parses");
+        let names = top_level_names(&file);
+        assert!(!make.fits(&names));
+        assert!(make.fits(&HashSet::from(["Q".to_owned()])));
+    }
+
+    #[test]
+    fn libraries_keep_one_fragment_per_normal_form() {
+        let mut library = Library::default();
+        // `P` and `impl P` bring each other along, so they are one
+        // fragment.
+        let added: Vec<bool> = extract(SEED)
+            .into_iter()
+            .map(|fragment| library.add(fragment))
+            .collect();
+        assert_eq!(added, [true, false, true]);
+        let mut renamed = extract("fn make() -> u8 { let x = 1; x }").remove(0);
+        assert!(library.add(renamed.clone()));
+        renamed.source = "fn make() -> u8 {\n    let y = 1;\n    y\n}\n".to_owned();
+        assert!(!library.add(renamed));
+        assert_eq!(library.len(), 3);
+
+        let path =
+            std::env::temp_dir().join(format!("rustc-fuzz-library-{}.json", std::process::id()));
+        library.save(&path).expect("saves");
+        let loaded = Library::load(&path).expect("loads");
+        assert_eq!(loaded.fragments, library.fragments);
+        assert!(loaded.contains("fn make() -> u8 { let z = 1; z }"));
+        let mut rng = StdRng::seed_from_u64(0);
+        let chosen = loaded
+            .choose(&mut rng, |fragment| fragment.kind == Kind::Type)
+            .expect("a type");
+        assert_eq!(chosen.focus, "P");
+        assert!(loaded.choose(&mut rng, |_| false).is_none());
+        fs::remove_file(&path).expect("cleans up");
+    }
+}
@@ -0,0 +1,332 @@
+//! Per-seed metadata.
+//!
+//! Each seed `foo.rs` may have a JSON sidecar, `foo.meta.json`, recording
+//! where it came from, what it needs to compile, a hash of the content the
+//! rest was recorded for, the language features it uses, and the last
+//! rustc it was checked against.
+//! Corpus walks only pick up `.rs` files, so sidecars never become seeds.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::quarantine::Quarantine;
+use crate::coverage::{tagger, SeedCoverage};
+use crate::lex::{self, TokenKind};
+use crate::mutate::Trace;
+use crate::triage::{FlakeReport, HangReport, Severity, Signature};
+
+/// Where a seed came from.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Provenance {
+    /// Nothing was recorded.
+    #[default]
+    Unknown,
+    /// Copied in from elsewhere: a test suite, a bug report, another
+    /// fuzzer's corpus.
+    Imported { origin: String },
+    /// Program `index` of `rustc-fuzz generate --seed <seed>` at `level`,
+    /// with `--const` if `constant`, with `--library` pointing at a
+    /// fragment library whose file hashed to `library` if there is one,
+    /// and with `--weights` pointing at production weights whose file
+    /// hashed to `weights` if there are. With `--generator` naming a
+    /// built-in or plugin generator if `generator` is set, in which case
+    /// `level` is the one that generator wrote for.
+    Generated {
+        seed: u64,
+        index: usize,
+        level: String,
+        constant: bool,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        library: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        weights: Option<String>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        generator: Option<String>,
+    },
+    /// Instantiation `index` of the template at `template`, whose content
+    /// hashed to `template_hash`, from `rustc-fuzz generate --template
+    /// --seed <seed>` at `level`.
+    Template {
+        template: String,
+        template_hash: String,
+        seed: u64,
+        index: usize,
+        level: String,
+    },
+    /// The piece of the seed at `parent` about `focus`, cut out by
+    /// [`split`](super::split::split).
+    Split {
+        parent: String,
+        parent_hash: String,
+        focus: String,
+    },
+    /// `trace` applied to the seed at `parent`, whose content hashed to
+    /// `parent_hash`.
+    Mutated {
+        parent: String,
+        parent_hash: String,
+        trace: Trace,
+    },
+    /// The seed at `parent` brought from edition `from` to the edition in
+    /// [`Metadata::edition`] by [`migrate`](super::migrate), applying the
+    /// suggestions of `lints`.
+    Migrated {
+        parent: String,
+        parent_hash: String,
+        from: String,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        lints: Vec<String>,
+    },
+    /// The crasher at `parent` shrunk by
+    /// [`triage::reduce`](crate::triage::reduce) while it kept crashing
+    /// with the signature whose id is `signature`.
+    Reduced {
+        parent: String,
+        parent_hash: String,
+        signature: String,
+    },
+}
+
+/// What is known about one seed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub provenance: Provenance,
+    /// The edition the seed needs, if it needs a particular one.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub edition: Option<String>,
+    /// Feature gates it enables.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// What it is made of, from [`tagger::tags`].
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// rustc flags it needs besides the edition.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flags: Vec<String>,
+    /// [`content_hash`] of the seed this was recorded for. A seed edited
+    /// since no longer matches.
+    pub hash: String,
+    /// `rustc -V` of the last compiler the seed compiled with.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified_with: Option<String>,
+    /// How long that compile took, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compile_ms: Option<u64>,
+    /// Peak RSS of the last `corpus verify` compile, in bytes, whether or
+    /// not it ran out of memory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub peak_rss: Option<u64>,
+    /// rust-lang/rust issues the seed is known to reproduce. Findings on
+    /// it are not new.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub known_issues: Vec<u64>,
+    /// Why `corpus verify` moved it out of the corpus, if it did.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub quarantined: Option<Quarantine>,
+    /// How bad the finding this is the reproducer of is, for findings.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    /// What `triage hangs` made of it timing out, if it looked.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hang: Option<HangReport>,
+    /// How reliably it crashes, as `triage buckets` found when filing it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub flake: Option<FlakeReport>,
+    /// What it covers of an instrumented rustc, as `coverage measure`
+    /// found.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rustc_coverage: Option<SeedCoverage>,
+    /// The crash it reproduces, for seeds of a regression corpus.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<Signature>,
+    /// Whether `corpus reduce` has shrunk it as far as it goes.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub reduced: bool,
+    /// Where seeds that were dropped as duplicates of this one came from.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub duplicates: Vec<Provenance>,
+}
+
+impl Metadata {
+    /// Metadata for `source`, with its hash, feature gates and tags filled
+    /// in.
+    pub fn new(source: &str, provenance: Provenance) -> Self {
+        Metadata {
+            provenance,
+            features: feature_gates(source),
+            tags: tagger::tags(source),
+            hash: content_hash(source),
+            ..Metadata::default()
+        }
+    }
+
+    /// Whether this was recorded for `source`.
+    pub fn matches(&self, source: &str) -> bool {
+        self.hash == content_hash(source)
+    }
+
+    /// Folds in the metadata of a duplicate of this seed that is being
+    /// dropped: its provenance is kept in [`Metadata::duplicates`], its
+    /// known issues become this seed's, and what it needed to compile
+    /// fills in what is not recorded here.
+    pub fn merge(&mut self, duplicate: Metadata) {
+        if self.edition.is_none() {
+            self.edition = duplicate.edition;
+        }
+        for flag in duplicate.flags {
+            if !self.flags.contains(&flag) {
+                self.flags.push(flag);
+            }
+        }
+        for issue in duplicate.known_issues {
+            if !self.known_issues.contains(&issue) {
+                self.known_issues.push(issue);
+            }
+        }
+        for provenance in std::iter::once(duplicate.provenance).chain(duplicate.duplicates) {
+            if provenance != Provenance::Unknown
+                && provenance != self.provenance
+                && !self.duplicates.contains(&provenance)
+            {
+                self.duplicates.push(provenance);
+            }
+        }
+    }
+
+    /// The sidecar of the seed at `seed`, if it has one.
+    pub fn load(seed: &Path) -> io::Result<Option<Self>> {
+        match fs::read_to_string(sidecar(seed)) {
+            Ok(json) => Ok(Some(serde_json::from_str(&json)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Writes the sidecar of the seed at `seed`.
+    pub fn save(&self, seed: &Path) -> io::Result<()> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        fs::write(sidecar(seed), json)
+    }
+}
+
+/// Where the sidecar of the seed at `seed` lives.
+pub fn sidecar(seed: &Path) -> PathBuf {
+    seed.with_extension("meta.json")
+}
+
+/// A stable hash of a seed's content: 64-bit FNV-1a, in hex. Stable across
+/// platforms and toolchains, unlike `std`'s hashers, since it is stored.
+pub fn content_hash(source: &str) -> String {
+    let hash = source
+        .bytes()
+        .fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x100_0000_01b3)
+        });
+    format!("{hash:016x}")
+}
+
+/// The names in the seed's `#![feature(...)]` attributes.
+pub fn feature_gates(source: &str) -> Vec<String> {
+    let tokens = lex::tokenize(source);
+    let words: Vec<(TokenKind, &str)> = tokens
+        .iter()
+        .filter(|token| !token.kind.is_trivia())
+        .map(|token| (token.kind, token.text(source)))
+        .collect();
+    let mut gates = Vec::new();
+    for (i, window) in words.windows(5).enumerate() {
+        let texts: Vec<&str> = window.iter().map(|(_, text)| *text).collect();
+        if texts != ["#", "!", "[", "feature", "("] {
+            continue;
+        }
+        for (kind, text) in words[i + 5..].iter().take_while(|(_, text)| *text != ")") {
+            if *kind == TokenKind::Ident && !gates.iter().any(|gate| gate == text) {
+                gates.push(text.to_string());
+            }
+        }
+    }
+    gates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sidecars_round_trip_next_to_their_seed() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-meta-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the corpus");
+        let seed = dir.join("seed.rs");
+        assert_eq!(sidecar(&seed), dir.join("seed.meta.json"));
+        assert_eq!(Metadata::load(&seed).expect("reads"), None);
+
+        let source = "#![feature(never_type, never_type)]\nfn f() -> ! { loop {} }\n";
+        let metadata = Metadata {
+            edition: Some("2021".to_owned()),
+            flags: vec!["-Zmir-opt-level=4".to_owned()],
+            verified_with: Some("rustc 1.80.0".to_owned()),
+            ..Metadata::new(
+                source,
+                Provenance::Imported {
+                    origin: "tests/ui".to_owned(),
+                },
+            )
+        };
+        assert_eq!(metadata.features, ["never_type"]);
+        metadata.save(&seed).expect("writes the sidecar");
+        let json = fs::read_to_string(sidecar(&seed)).expect("reads the sidecar");
+        assert!(json.contains(r#""kind": "imported""#), "{json}");
+        assert!(
+            !json.contains("known_issues"),
+            "empty fields are left out: {json}"
+        );
+        assert_eq!(Metadata::load(&seed).expect("reads"), Some(metadata));
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+
+    #[test]
+    fn edited_seeds_no_longer_match() {
+        let metadata = Metadata::new("fn main() {}", Provenance::Unknown);
+        assert!(metadata.matches("fn main() {}"));
+        assert!(!metadata.matches("fn main() { }"));
+        // FNV-1a of nothing is its offset basis; the hash is stored, so it
+        // must not change.
+        assert_eq!(content_hash(""), "cbf29ce484222325");
+        assert_eq!(content_hash("a"), "af63dc4c8601ec8c");
+    }
+
+    #[test]
+    fn merging_a_duplicate_keeps_where_it_came_from() {
+        let imported = |origin: &str| Provenance::Imported {
+            origin: origin.to_owned(),
+        };
+        let mut kept = Metadata {
+            flags: vec!["-O".to_owned()],
+            known_issues: vec![1],
+            ..Metadata::new("fn f() {}", imported("a"))
+        };
+        kept.merge(Metadata {
+            edition: Some("2018".to_owned()),
+            flags: vec!["-O".to_owned(), "-g".to_owned()],
+            known_issues: vec![1, 2],
+            duplicates: vec![imported("a"), Provenance::Unknown, imported("c")],
+            ..Metadata::new("fn g() {}", imported("b"))
+        });
+        assert_eq!(kept.edition.as_deref(), Some("2018"));
+        assert_eq!(kept.flags, ["-O", "-g"]);
+        assert_eq!(kept.known_issues, [1, 2]);
+        assert_eq!(kept.duplicates, [imported("b"), imported("c")]);
+        assert!(kept.matches("fn f() {}"));
+    }
+
+    #[test]
+    fn feature_gates_come_from_inner_feature_attributes_only() {
+        let source = "#![feature(a, b)]\n#![feature(c)]\n#[feature(d)]\n// #![feature(e)]\n";
+        assert_eq!(feature_gates(source), ["a", "b", "c"]);
+    }
+}
@@ -0,0 +1,273 @@
+//! Edition migration.
+//!
+//! Every edition changes what the parser, name resolution and the
+//! borrow checker do with the same text, so a seed is worth one more seed
+//! per edition it can be brought to. [`Migrator`] does what `cargo fix
+//! --edition` does, one edition at a time: it forces the next edition's
+//! compatibility lints on, applies their machine-applicable suggestions
+//! until there are none left, and keeps the result if it compiles under
+//! the next edition.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use super::meta::{self, Metadata, Provenance};
+use super::Corpus;
+use crate::harness::clippy::apply_suggestions;
+use crate::harness::Rustc;
+
+/// Editions, oldest first.
+pub const EDITIONS: [&str; 4] = ["2015", "2018", "2021", "2024"];
+
+/// The editions after `from`, up to and including `to`. Unknown editions
+/// give none.
+pub fn editions_between(from: &str, to: &str) -> &'static [&'static str] {
+    let position = |edition| EDITIONS.iter().position(|e| *e == edition);
+    match (position(from), position(to)) {
+        (Some(from), Some(to)) if from < to => &EDITIONS[from + 1..=to],
+        _ => &[],
+    }
+}
+
+/// A seed brought to a newer edition.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Migration {
+    pub edition: &'static str,
+    pub source: String,
+    /// The lints whose suggestions were applied on the way from the
+    /// previous edition.
+    pub lints: Vec<String>,
+}
+
+/// Migrates seeds with rustc's edition compatibility lints.
+#[derive(Debug, Clone)]
+pub struct Migrator {
+    pub driver: Rustc,
+    /// Rounds of suggestions applied per edition; `cargo fix` also stops
+    /// after a few, since one fix can make room for another.
+    pub rounds: usize,
+}
+
+impl Default for Migrator {
+    fn default() -> Self {
+        Migrator {
+            driver: Rustc::default(),
+            rounds: 4,
+        }
+    }
+}
+
+impl Migrator {
+    /// `source`, which compiles with `flags` at edition `from`, migrated
+    /// to every edition up to `to` in turn. Stops at the first edition it
+    /// does not compile under.
+    pub fn migrate(
+        &self,
+        source: &str,
+        from: &str,
+        to: &str,
+        flags: &[String],
+    ) -> io::Result<Vec<Migration>> {
+        let mut migrations = Vec::new();
+        let mut source = source.to_owned();
+        let mut edition = from;
+        for &next in editions_between(from, to) {
+            let Some(migration) = self.step(&source, edition, next, flags)? else {
+                break;
+            };
+            source.clone_from(&migration.source);
+            edition = next;
+            migrations.push(migration);
+        }
+        Ok(migrations)
+    }
+
+    /// Writes a variant of every seed of the corpus at `dir` for each
+    /// edition after its own up to `to` that it can be migrated to, as
+    /// `foo.edition2021.rs` next to `foo.rs` under `output`. Seeds without
+    /// a recorded edition are 2015 seeds, as they are to rustc; variants
+    /// that exist already are skipped. Returns how many variants were
+    /// written, and of how many seeds.
+    pub fn migrate_corpus(
+        &self,
+        dir: &Path,
+        to: &str,
+        output: &Path,
+    ) -> io::Result<(usize, usize)> {
+        let entries = Corpus::open(dir).entries().map_err(|e| {
+            io::Error::new(e.kind(), format!("reading corpus {}: {e}", dir.display()))
+        })?;
+        let mut written = 0;
+        for entry in &entries {
+            let metadata =
+                Metadata::load(&entry.path)?.filter(|metadata| metadata.matches(&entry.source));
+            let (edition, flags) = metadata
+                .map(|metadata| (metadata.edition, metadata.flags))
+                .unwrap_or_default();
+            let from = edition.unwrap_or_else(|| "2015".to_owned());
+            let relative = entry.path.strip_prefix(dir).unwrap_or(&entry.path);
+            let stem = relative.with_extension("");
+            let stem = stem.to_string_lossy();
+            // Variants of variants are named after the original.
+            let stem = match stem.rsplit_once(".edition") {
+                Some((original, edition)) if EDITIONS.contains(&edition) => original,
+                _ => &stem,
+            };
+            let variant = |edition: &str| output.join(format!("{stem}.edition{edition}.rs"));
+            if editions_between(&from, to)
+                .iter()
+                .all(|edition| variant(edition).exists())
+            {
+                continue;
+            }
+            let migrations = self
+                .migrate(&entry.source, &from, to, &flags)
+                .map_err(|e| {
+                    io::Error::new(e.kind(), format!("migrating {}: {e}", entry.path.display()))
+                })?;
+            let mut parent = (entry.path.clone(), entry.source.clone(), from);
+            for migration in migrations {
+                let path = variant(migration.edition);
+                if !path.exists() {
+                    if let Some(dir) = path.parent() {
+                        fs::create_dir_all(dir)?;
+                    }
+                    fs::write(&path, &migration.source).map_err(|e| {
+                        io::Error::new(e.kind(), format!("writing {}: {e}", path.display()))
+                    })?;
+                    let provenance = Provenance::Migrated {
+                        parent: parent.0.display().to_string(),
+                        parent_hash: meta::content_hash(&parent.1),
+                        from: parent.2,
+                        lints: migration.lints,
+                    };
+                    Metadata {
+                        edition: Some(migration.edition.to_owned()),
+                        flags: flags.clone(),
+                        ..Metadata::new(&migration.source, provenance)
+                    }
+                    .save(&path)
+                    .map_err(|e| {
+                        io::Error::new(
+                            e.kind(),
+                            format!("writing metadata for {}: {e}", path.display()),
+                        )
+                    })?;
+                    written += 1;
+                }
+                parent = (path, migration.source, migration.edition.to_owned());
+            }
+        }
+        Ok((written, entries.len()))
+    }
+
+    fn step(
+        &self,
+        source: &str,
+        from: &str,
+        to: &'static str,
+        flags: &[String],
+    ) -> io::Result<Option<Migration>> {
+        let lint = format!("rust-{to}-compatibility");
+        let mut source = source.to_owned();
+        let mut lints = Vec::new();
+        for _ in 0..self.rounds {
+            let mut args = vec![
+                "--crate-type=lib".to_owned(),
+                "--error-format=json".to_owned(),
+                "--edition".to_owned(),
+                from.to_owned(),
+                "--force-warn".to_owned(),
+                lint.clone(),
+            ];
+            args.extend(flags.iter().cloned());
+            args.push("-".to_owned());
+            let outcome = self.driver.run(&args, source.as_bytes())?;
+            if !outcome.is_success() {
+                break;
+            }
+            let Some((fixed, applied)) = apply_suggestions(&source, &outcome.output().stderr)
+            else {
+                break;
+            };
+            source = fixed;
+            for applied in applied {
+                if !lints.contains(&applied) {
+                    lints.push(applied);
+                }
+            }
+        }
+        let mut args = vec![
+            "--crate-type=lib".to_owned(),
+            "--edition".to_owned(),
+            to.to_owned(),
+        ];
+        args.extend(flags.iter().cloned());
+        args.push("-".to_owned());
+        if !self.driver.run(&args, source.as_bytes())?.is_success() {
+            return Ok(None);
+        }
+        Ok(Some(Migration {
+            edition: to,
+            source,
+            lints,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn corpora_get_a_variant_per_edition_once() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-migrate-{}", std::process::id()));
+        let corpus = dir.join("corpus");
+        fs::create_dir_all(&corpus).expect("creates the corpus");
+        // Compiles everything, with nothing to suggest.
+        let rustc = dir.join("rustc");
+        fs::write(&rustc, "#!/bin/sh\ncat >/dev/null\n").expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        fs::write(corpus.join("seed.rs"), "fn seed() {}").expect("writes a seed");
+        let migrator = Migrator {
+            driver: Rustc::new(&rustc),
+            ..Migrator::default()
+        };
+
+        let migrated = migrator
+            .migrate_corpus(&corpus, "2021", &corpus)
+            .expect("migrates");
+        assert_eq!(migrated, (2, 1));
+        let latest = corpus.join("seed.edition2021.rs");
+        let metadata = Metadata::load(&latest)
+            .expect("reads")
+            .expect("has a sidecar");
+        assert_eq!(metadata.edition.as_deref(), Some("2021"));
+        let Provenance::Migrated { parent, from, .. } = metadata.provenance else {
+            panic!("{:?}", metadata.provenance);
+        };
+        assert_eq!(
+            parent,
+            corpus.join("seed.edition2018.rs").display().to_string()
+        );
+        assert_eq!(from, "2018");
+
+        // The variants are seeds now too, and are not migrated again.
+        let again = migrator
+            .migrate_corpus(&corpus, "2021", &corpus)
+            .expect("migrates");
+        assert_eq!(again, (0, 3));
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+
+    #[test]
+    fn editions_between_excludes_the_first() {
+        assert_eq!(editions_between("2015", "2021"), ["2018", "2021"]);
+        assert_eq!(editions_between("2021", "2024"), ["2024"]);
+        assert!(editions_between("2021", "2021").is_empty());
+        assert!(editions_between("2024", "2018").is_empty());
+        assert!(editions_between("2015", "2027").is_empty());
+    }
+}
@@ -0,0 +1,84 @@
+//! On-disk seed corpora.
+//!
+//! A corpus is a directory tree of `.rs` files, one seed per file.
+
+pub mod archive;
+pub mod cmin;
+pub mod dedup;
+pub mod export;
+pub mod generated;
+pub mod glacier;
+pub mod harvest;
+pub mod library;
+pub mod meta;
+pub mod migrate;
+pub mod normalize;
+pub mod quarantine;
+pub mod reduce;
+pub mod split;
+pub mod stats;
+pub mod sync;
+pub mod ui;
+pub mod verify;
+
+pub use normalize::normalize;
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// A seed loaded from a corpus directory.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    pub path: PathBuf,
+    pub source: String,
+}
+
+/// A directory of seeds.
+#[derive(Debug, Clone)]
+pub struct Corpus {
+    root: PathBuf,
+}
+
+impl Corpus {
+    pub fn open(root: impl Into<PathBuf>) -> Self {
+        Corpus { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Paths of every `.rs` file under the root, in sorted order.
+    pub fn paths(&self) -> io::Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        walk(&self.root, &mut paths)?;
+        paths.sort();
+        Ok(paths)
+    }
+
+    /// Loads every seed. Files that are not valid UTF-8 are skipped.
+    pub fn entries(&self) -> io::Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for path in self.paths()? {
+            match fs::read_to_string(&path) {
+                Ok(source) => entries.push(Entry { path, source }),
+                Err(e) if e.kind() == io::ErrorKind::InvalidData => {}
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(entries)
+    }
+}
+
+fn walk(dir: &Path, out: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            walk(&path, out)?;
+        } else if path.extension().is_some_and(|ext| ext == "rs") {
+            out.push(path);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,261 @@
+//! Seed normalization.
+//!
+//! Maps trivially different seeds onto the same text so the corpus does not
+//! fill up with variants that only differ in formatting, comments or local
+//! variable names. Seeds that parse are pretty-printed after renaming every
+//! local binding to a positional name (`l0`, `l1`, ... per function);
+//! seeds that do not parse are reduced to their significant tokens joined
+//! by single spaces.
+
+use proc_macro2::{Group, Ident, Literal, TokenStream, TokenTree};
+use std::collections::{HashMap, HashSet};
+use syn::visit::{self, Visit};
+use syn::visit_mut::{self, VisitMut};
+
+use crate::lex::{self, TokenKind};
+
+/// Returns the normal form of `source`.
+pub fn normalize(source: &str) -> String {
+    match syn::parse_file(source) {
+        Ok(mut file) => {
+            let mut bound = BoundNames::default();
+            bound.visit_file(&file);
+            let prefix = fresh_prefix(source, &bound.0);
+            Renamer {
+                prefix,
+                names: HashMap::new(),
+            }
+            .visit_file_mut(&mut file);
+            prettyplease::unparse(&file)
+        }
+        Err(_) => tokens_only(source),
+    }
+}
+
+/// Significant tokens joined by single spaces.
+fn tokens_only(source: &str) -> String {
+    let tokens = lex::tokenize(source);
+    let mut out = lex::significant(source, &tokens).join(" ");
+    out.push('\n');
+    out
+}
+
+/// A prefix such that `{prefix}{n}` collides with no identifier in `source`
+/// other than the `bound` names, which are renamed away. Ignoring those
+/// keeps normalization idempotent: the `l0` of a normalized seed does not
+/// push the next pass to `ll0`.
+fn fresh_prefix(source: &str, bound: &HashSet<String>) -> String {
+    let tokens = lex::tokenize(source);
+    let idents: HashSet<&str> = tokens
+        .iter()
+        .filter(|t| t.kind == TokenKind::Ident)
+        .map(|t| t.text(source))
+        .filter(|ident| !bound.contains(*ident))
+        .collect();
+    let mut prefix = String::from("l");
+    while idents.iter().any(|ident| {
+        ident
+            .strip_prefix(prefix.as_str())
+            .is_some_and(|rest| rest.bytes().all(|b| b.is_ascii_digit()))
+    }) {
+        prefix.push('l');
+    }
+    prefix
+}
+
+/// Renames bindings function by function.
+struct Renamer {
+    prefix: String,
+    /// Original name to positional name, for the current function.
+    names: HashMap<String, String>,
+}
+
+impl Renamer {
+    fn in_fn(&mut self, f: impl FnOnce(&mut Self)) {
+        let outer = std::mem::take(&mut self.names);
+        f(self);
+        self.names = outer;
+    }
+
+    fn collect(&mut self, collector: Bindings) {
+        for name in collector.0 {
+            let next = format!("{}{}", self.prefix, self.names.len());
+            self.names.entry(name).or_insert(next);
+        }
+    }
+
+    fn rename_tokens(&self, tokens: TokenStream) -> TokenStream {
+        tokens
+            .into_iter()
+            .map(|tt| match tt {
+                TokenTree::Ident(ident) => match self.names.get(&ident.to_string()) {
+                    Some(new) => TokenTree::Ident(Ident::new(new, ident.span())),
+                    None => TokenTree::Ident(ident),
+                },
+                TokenTree::Group(g) => {
+                    let mut new = Group::new(g.delimiter(), self.rename_tokens(g.stream()));
+                    new.set_span(g.span());
+                    TokenTree::Group(new)
+                }
+                TokenTree::Literal(lit) => self.rename_captures(lit),
+                other => other,
+            })
+            .collect()
+    }
+
+    /// Renames inline format arguments (`"{x}"`, `"{x:?}"`) in a literal.
+    fn rename_captures(&self, lit: Literal) -> TokenTree {
+        let mut text = lit.to_string();
+        if !text.starts_with('"') {
+            return TokenTree::Literal(lit);
+        }
+        for (old, new) in &self.names {
+            for close in ["}", ":"] {
+                text = text.replace(&format!("{{{old}{close}"), &format!("{{{new}{close}"));
+            }
+        }
+        match text.parse::<Literal>() {
+            Ok(mut renamed) => {
+                renamed.set_span(lit.span());
+                TokenTree::Literal(renamed)
+            }
+            Err(_) => TokenTree::Literal(lit),
+        }
+    }
+}
+
+impl VisitMut for Renamer {
+    fn visit_item_fn_mut(&mut self, item: &mut syn::ItemFn) {
+        self.in_fn(|this| {
+            let mut bindings = Bindings::default();
+            bindings.visit_item_fn(item);
+            this.collect(bindings);
+            visit_mut::visit_item_fn_mut(this, item);
+        });
+    }
+
+    fn visit_impl_item_fn_mut(&mut self, item: &mut syn::ImplItemFn) {
+        self.in_fn(|this| {
+            let mut bindings = Bindings::default();
+            bindings.visit_impl_item_fn(item);
+            this.collect(bindings);
+            visit_mut::visit_impl_item_fn_mut(this, item);
+        });
+    }
+
+    fn visit_trait_item_fn_mut(&mut self, item: &mut syn::TraitItemFn) {
+        self.in_fn(|this| {
+            let mut bindings = Bindings::default();
+            bindings.visit_trait_item_fn(item);
+            this.collect(bindings);
+            visit_mut::visit_trait_item_fn_mut(this, item);
+        });
+    }
+
+    fn visit_pat_ident_mut(&mut self, pat: &mut syn::PatIdent) {
+        if let Some(new) = self.names.get(&pat.ident.to_string()) {
+            pat.ident = Ident::new(new, pat.ident.span());
+        }
+        visit_mut::visit_pat_ident_mut(self, pat);
+    }
+
+    fn visit_expr_path_mut(&mut self, expr: &mut syn::ExprPath) {
+        if expr.qself.is_none() && expr.path.segments.len() == 1 {
+            let segment = &mut expr.path.segments[0];
+            if let Some(new) = self.names.get(&segment.ident.to_string()) {
+                segment.ident = Ident::new(new, segment.ident.span());
+            }
+        }
+        visit_mut::visit_expr_path_mut(self, expr);
+    }
+
+    fn visit_field_value_mut(&mut self, field: &mut syn::FieldValue) {
+        // `S { x }` binds field `x` from local `x`; expand it so the field
+        // name survives the rename.
+        if field.colon_token.is_none() {
+            field.colon_token = Some(Default::default());
+        }
+        visit_mut::visit_field_value_mut(self, field);
+    }
+
+    fn visit_macro_mut(&mut self, mac: &mut syn::Macro) {
+        if !self.names.is_empty() {
+            mac.tokens = self.rename_tokens(std::mem::take(&mut mac.tokens));
+        }
+    }
+
+    fn visit_field_pat_mut(&mut self, field: &mut syn::FieldPat) {
+        // Same for `let S { x } = s;`.
+        if field.colon_token.is_none() {
+            field.colon_token = Some(Default::default());
+        }
+        visit_mut::visit_field_pat_mut(self, field);
+    }
+
+    fn visit_item_mut(&mut self, item: &mut syn::Item) {
+        // Nested items do not see the enclosing function's locals.
+        self.in_fn(|this| visit_mut::visit_item_mut(this, item));
+    }
+}
+
+/// Names bound by patterns in a function, in order of appearance.
+/// Capitalised names are skipped: in patterns they are almost always unit
+/// structs, variants or constants rather than bindings.
+#[derive(Default)]
+struct Bindings(Vec<String>);
+
+impl<'ast> Visit<'ast> for Bindings {
+    fn visit_pat_ident(&mut self, pat: &'ast syn::PatIdent) {
+        let name = pat.ident.to_string();
+        if !name.starts_with(char::is_uppercase) && !self.0.contains(&name) {
+            self.0.push(name);
+        }
+        visit::visit_pat_ident(self, pat);
+    }
+
+    fn visit_item(&mut self, _: &'ast syn::Item) {}
+}
+
+/// Every name bound by a pattern anywhere in a file.
+#[derive(Default)]
+struct BoundNames(HashSet<String>);
+
+impl<'ast> Visit<'ast> for BoundNames {
+    fn visit_pat_ident(&mut self, pat: &'ast syn::PatIdent) {
+        self.0.insert(pat.ident.to_string());
+        visit::visit_pat_ident(self, pat);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formatting_and_local_names_do_not_matter() {
+        let a = "fn f(count: u32) -> u32 {\n    let twice = count * 2; // double\n    twice\n}\n";
+        let b = "fn f(n: u32)->u32{let m=n*2;m}";
+        assert_eq!(normalize(a), normalize(b));
+        assert_eq!(
+            normalize(a),
+            "fn f(l0: u32) -> u32 {\n    let l1 = l0 * 2;\n    l1\n}\n"
+        );
+    }
+
+    #[test]
+    fn normal_forms_are_normal() {
+        let source = "struct S { x: u8 }\n\
+                      fn l0() {}\n\
+                      fn f(x: u8) { let s = S { x }; println!(\"{x:?}\"); l0(); }\n";
+        let once = normalize(source);
+        assert_eq!(normalize(&once), once);
+        // Fields and captured format arguments follow their local.
+        assert!(once.contains("S { x: ll0 }"), "{once}");
+        assert!(once.contains("\"{ll0:?}\""), "{once}");
+    }
+
+    #[test]
+    fn unparsed_seeds_keep_their_significant_tokens() {
+        assert_eq!(normalize("fn  f( /* c */ x\n"), "fn f ( x\n");
+    }
+}
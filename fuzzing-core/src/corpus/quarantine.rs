@@ -0,0 +1,105 @@
+//! Quarantine for seeds that stopped compiling.
+//!
+//! Nightly removes features and changes syntax, and a seed that compiled
+//! last month may be rejected today. Such a seed still runs through every
+//! harness, costing time and finding nothing past the parser, and counts
+//! in every statistic as if it were live. `corpus verify --quarantine`
+//! moves seeds that compiled before and fail now out of the corpus, with
+//! their sidecars and a [`Quarantine`] record of why, so they can be
+//! fixed up or verified again with a later compiler.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use super::meta::sidecar;
+
+/// Why a seed was quarantined.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Quarantine {
+    /// `rustc -V` of the compiler that rejected it.
+    pub rustc: String,
+    /// The first error it reported.
+    pub error: String,
+}
+
+/// The first `error` line of rustc's `stderr`, or its first line if it
+/// has none.
+pub fn first_error(stderr: &str) -> String {
+    stderr
+        .lines()
+        .find(|line| line.starts_with("error"))
+        .or_else(|| stderr.lines().next())
+        .unwrap_or("")
+        .trim()
+        .to_owned()
+}
+
+/// Moves the seed at `seed`, in the corpus at `root`, and its sidecar to
+/// the same relative path under `quarantine`. Returns the new path.
+pub fn move_seed(root: &Path, seed: &Path, quarantine: &Path) -> io::Result<PathBuf> {
+    let relative = seed.strip_prefix(root).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is not under {}", seed.display(), root.display()),
+        )
+    })?;
+    let dest = quarantine.join(relative);
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    rename(seed, &dest)?;
+    if sidecar(seed).exists() {
+        rename(&sidecar(seed), &sidecar(&dest))?;
+    }
+    Ok(dest)
+}
+
+/// `fs::rename`, falling back to copying for quarantine directories on
+/// another filesystem.
+fn rename(from: &Path, to: &Path) -> io::Result<()> {
+    if fs::rename(from, to).is_ok() {
+        return Ok(());
+    }
+    fs::copy(from, to)?;
+    fs::remove_file(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn the_first_error_is_what_gets_recorded() {
+        let stderr = "warning: unused variable\nerror[E0658]: feature removed\n  --> <anon>:1:1\n";
+        assert_eq!(first_error(stderr), "error[E0658]: feature removed");
+        assert_eq!(
+            first_error("  thread panicked\nnote: ...\n"),
+            "thread panicked"
+        );
+        assert_eq!(first_error(""), "");
+    }
+
+    #[test]
+    fn seeds_keep_their_place_and_sidecar() {
+        let dir =
+            std::env::temp_dir().join(format!("rustc-fuzz-quarantine-{}", std::process::id()));
+        let root = dir.join("corpus");
+        fs::create_dir_all(root.join("traits")).expect("creates the corpus");
+        let seed = root.join("traits/old.rs");
+        fs::write(&seed, "fn old() {}").expect("writes a seed");
+        fs::write(sidecar(&seed), "{}").expect("writes a sidecar");
+
+        let moved = move_seed(&root, &seed, &dir.join("quarantine")).expect("moves");
+        assert_eq!(moved, dir.join("quarantine/traits/old.rs"));
+        assert_eq!(fs::read_to_string(&moved).expect("reads"), "fn old() {}");
+        assert!(sidecar(&moved).exists());
+        assert!(!seed.exists() && !sidecar(&seed).exists());
+
+        let error = move_seed(&root, &moved, &dir).expect_err("not in the corpus");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
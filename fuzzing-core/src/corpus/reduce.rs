@@ -0,0 +1,197 @@
+//! Seed reduction.
+//!
+//! Every byte of a seed is paid for on every execution of every mutant
+//! derived from it. [`reduce`] shrinks a seed as long as a caller-supplied
+//! test still accepts it: whole items, impl and trait members and
+//! statements are removed one at a time, last first, until none can go.
+//! Seeds that do not parse are reduced line by line instead.
+//!
+//! The caller decides what must survive; `corpus reduce` keeps a seed's
+//! tags, whether it compiles, and whether the harness finds something.
+
+use syn::visit_mut::{self, VisitMut};
+
+/// Shrinks `source` while `interesting` holds, returning the smallest
+/// version found. `source` itself is assumed to be interesting. When
+/// nothing shorter was found the result is `source`, unformatted.
+pub fn reduce<E>(
+    source: &str,
+    mut interesting: impl FnMut(&str) -> Result<bool, E>,
+) -> Result<String, E> {
+    if let Ok(file) = syn::parse_file(source) {
+        if interesting(&prettyplease::unparse(&file))? {
+            return reduce_file(file, source, &mut interesting);
+        }
+    }
+    reduce_lines(source, &mut interesting)
+}
+
+/// Removes syntax nodes until a fixed point. Removing node `i` only
+/// renumbers the nodes after it, so one pass from the last node to the
+/// first tries every node once.
+fn reduce_file<E>(
+    mut file: syn::File,
+    source: &str,
+    interesting: &mut impl FnMut(&str) -> Result<bool, E>,
+) -> Result<String, E> {
+    let mut best = None;
+    loop {
+        let mut changed = false;
+        for target in (0..removable(&mut file)).rev() {
+            let mut candidate = file.clone();
+            let mut remover = Remover::at(target);
+            remover.visit_file_mut(&mut candidate);
+            if !remover.removed {
+                continue;
+            }
+            let text = prettyplease::unparse(&candidate);
+            if interesting(&text)? {
+                file = candidate;
+                best = Some(text);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    Ok(best
+        .filter(|best| best.len() < source.len())
+        .unwrap_or_else(|| source.to_owned()))
+}
+
+/// Removes lines, last first, until a fixed point.
+fn reduce_lines<E>(
+    source: &str,
+    interesting: &mut impl FnMut(&str) -> Result<bool, E>,
+) -> Result<String, E> {
+    let mut lines: Vec<&str> = source.lines().collect();
+    loop {
+        let mut changed = false;
+        for i in (0..lines.len()).rev() {
+            let mut candidate = lines.clone();
+            candidate.remove(i);
+            let mut text = candidate.join("\n");
+            text.push('\n');
+            if interesting(&text)? {
+                lines = candidate;
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    let mut text = lines.join("\n");
+    text.push('\n');
+    Ok(if text.len() < source.len() {
+        text
+    } else {
+        source.to_owned()
+    })
+}
+
+/// How many nodes of `file` [`Remover`] can remove.
+fn removable(file: &mut syn::File) -> usize {
+    let mut counter = Remover::at(usize::MAX);
+    counter.visit_file_mut(file);
+    counter.seen
+}
+
+/// Removes the `target`th removable node in pre-order: an item, an impl
+/// or trait member, or a statement.
+struct Remover {
+    target: usize,
+    seen: usize,
+    removed: bool,
+}
+
+impl Remover {
+    fn at(target: usize) -> Self {
+        Remover {
+            target,
+            seen: 0,
+            removed: false,
+        }
+    }
+
+    fn list<T>(&mut self, list: &mut Vec<T>, mut visit: impl FnMut(&mut Self, &mut T)) {
+        let mut i = 0;
+        while !self.removed && i < list.len() {
+            if self.seen == self.target {
+                list.remove(i);
+                self.removed = true;
+                return;
+            }
+            self.seen += 1;
+            visit(self, &mut list[i]);
+            i += 1;
+        }
+    }
+}
+
+impl VisitMut for Remover {
+    fn visit_file_mut(&mut self, file: &mut syn::File) {
+        self.list(&mut file.items, |v, item| v.visit_item_mut(item));
+    }
+
+    fn visit_item_mod_mut(&mut self, item: &mut syn::ItemMod) {
+        if let Some((_, items)) = &mut item.content {
+            self.list(items, |v, item| v.visit_item_mut(item));
+        }
+    }
+
+    fn visit_item_impl_mut(&mut self, item: &mut syn::ItemImpl) {
+        self.list(&mut item.items, |v, item| v.visit_impl_item_mut(item));
+    }
+
+    fn visit_item_trait_mut(&mut self, item: &mut syn::ItemTrait) {
+        self.list(&mut item.items, |v, item| v.visit_trait_item_mut(item));
+    }
+
+    fn visit_block_mut(&mut self, block: &mut syn::Block) {
+        self.list(&mut block.stmts, |v, stmt| {
+            visit_mut::visit_stmt_mut(v, stmt)
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::Infallible;
+
+    #[test]
+    fn items_members_and_statements_go_until_none_can() {
+        let source = "struct Keep;\n\
+                      impl Keep { fn a() {} fn b() {} }\n\
+                      fn main() { let x = 1; let keep = Keep; drop(x); }\n\
+                      fn unused() {}\n";
+        let reduced = reduce(source, |text| {
+            Ok::<_, Infallible>(text.contains("struct Keep") && text.contains("let keep"))
+        })
+        .expect("infallible");
+        assert_eq!(
+            reduced,
+            "struct Keep;\nfn main() {\n    let keep = Keep;\n}\n"
+        );
+    }
+
+    #[test]
+    fn unparsed_seeds_lose_lines() {
+        let source = "fn (\nkeep\nthis\n";
+        let reduced =
+            reduce(source, |text| Ok::<_, Infallible>(text.contains("keep"))).expect("infallible");
+        assert_eq!(reduced, "keep\n");
+    }
+
+    #[test]
+    fn seeds_that_cannot_shrink_come_back_as_they_were() {
+        let source = "fn   main() {}";
+        let reduced =
+            reduce(source, |text| Ok::<_, Infallible>(text.contains("main"))).expect("infallible");
+        assert_eq!(reduced, source);
+        let error = reduce(source, |_| Err("the test failed")).expect_err("an error");
+        assert_eq!(error, "the test failed");
+    }
+}
@@ -0,0 +1,193 @@
+//! Seed splitting.
+//!
+//! A seed that packs many unrelated items into one file mutates badly (a
+//! mutation anywhere can break all of it) and minimizes slowly. [`split`]
+//! cuts it into one seed per function, impl, trait, macro and module, each
+//! carrying along the items it refers to, the impls of the types among
+//! those, and every `use` and crate attribute of the original.
+
+use std::collections::{BTreeSet, HashMap};
+
+use quote::ToTokens;
+
+use super::harvest::{collect_idents, item_name};
+
+/// A piece of a split seed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Piece {
+    /// The item the piece is about: its name, or `impl Type` for impls.
+    pub focus: String,
+    pub source: String,
+}
+
+/// The top-level items of a file and which of them refer to which.
+pub(super) struct Graph {
+    pub(super) attrs: Vec<syn::Attribute>,
+    pub(super) uses: Vec<syn::Item>,
+    /// Every item but the `use`s.
+    pub(super) items: Vec<syn::Item>,
+    references: Vec<BTreeSet<usize>>,
+    /// Impls by the name of the type they are for.
+    impls: HashMap<String, Vec<usize>>,
+}
+
+impl Graph {
+    pub(super) fn new(file: syn::File) -> Self {
+        let (uses, items): (Vec<syn::Item>, Vec<syn::Item>) = file
+            .items
+            .into_iter()
+            .partition(|item| matches!(item, syn::Item::Use(_)));
+
+        let mut defined: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, item) in items.iter().enumerate() {
+            if let Some(name) = item_name(item) {
+                defined.entry(name).or_default().push(i);
+            }
+        }
+        let references = items
+            .iter()
+            .enumerate()
+            .map(|(i, item)| {
+                let mut idents = Vec::new();
+                collect_idents(item.to_token_stream(), &mut idents);
+                idents
+                    .iter()
+                    .filter_map(|ident| defined.get(ident))
+                    .flatten()
+                    .copied()
+                    .filter(|&j| j != i)
+                    .collect()
+            })
+            .collect();
+        let mut impls: HashMap<String, Vec<usize>> = HashMap::new();
+        for (i, item) in items.iter().enumerate() {
+            if let Some(name) = impl_self_name(item) {
+                impls.entry(name).or_default().push(i);
+            }
+        }
+        Graph {
+            attrs: file.attrs,
+            uses,
+            items,
+            references,
+            impls,
+        }
+    }
+
+    /// Item `focus`, what it refers to, and the impls of every type among
+    /// those, to a fixed point.
+    pub(super) fn closure(&self, focus: usize) -> BTreeSet<usize> {
+        let mut included = BTreeSet::from([focus]);
+        let mut queue = vec![focus];
+        while let Some(i) = queue.pop() {
+            let attached = item_name(&self.items[i])
+                .and_then(|name| self.impls.get(&name))
+                .into_iter()
+                .flatten();
+            for &j in self.references[i].iter().chain(attached) {
+                if included.insert(j) {
+                    queue.push(j);
+                }
+            }
+        }
+        included
+    }
+
+    /// Every `use`, then the `included` items.
+    pub(super) fn with_uses(&self, included: &BTreeSet<usize>) -> Vec<syn::Item> {
+        self.uses
+            .iter()
+            .cloned()
+            .chain(included.iter().map(|&i| self.items[i].clone()))
+            .collect()
+    }
+}
+
+/// The pieces of `source`, in source order of their focus items. Seeds
+/// that do not parse, or that would come out as a single piece, give
+/// none.
+pub fn split(source: &str) -> Vec<Piece> {
+    let Ok(file) = syn::parse_file(source) else {
+        return Vec::new();
+    };
+    let graph = Graph::new(file);
+    let mut pieces = Vec::new();
+    let mut seen = BTreeSet::new();
+    for (focus, item) in graph.items.iter().enumerate() {
+        let label = match item {
+            syn::Item::Fn(_) | syn::Item::Trait(_) | syn::Item::Macro(_) | syn::Item::Mod(_) => {
+                item_name(item)
+            }
+            syn::Item::Impl(_) => impl_self_name(item).map(|name| format!("impl {name}")),
+            _ => None,
+        };
+        let Some(label) = label else {
+            continue;
+        };
+        let included = graph.closure(focus);
+        if included.len() == graph.items.len() || !seen.insert(included.clone()) {
+            continue;
+        }
+        pieces.push(Piece {
+            focus: label,
+            source: prettyplease::unparse(&syn::File {
+                shebang: None,
+                attrs: graph.attrs.clone(),
+                items: graph.with_uses(&included),
+            }),
+        });
+    }
+    pieces
+}
+
+/// The name of the type an impl is for, if it is a plain path.
+pub(super) fn impl_self_name(item: &syn::Item) -> Option<String> {
+    let syn::Item::Impl(item) = item else {
+        return None;
+    };
+    let syn::Type::Path(ty) = &*item.self_ty else {
+        return None;
+    };
+    Some(ty.path.segments.last()?.ident.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pieces_carry_what_they_refer_to() {
+        let source = "#![allow(dead_code)]\n\
+                      use std::fmt;\n\
+                      struct S;\n\
+                      impl S { fn new() -> S { S } }\n\
+                      fn a() -> S { S::new() }\n\
+                      fn b() -> u8 { 1 }\n";
+        let pieces = split(source);
+        let foci: Vec<&str> = pieces.iter().map(|piece| piece.focus.as_str()).collect();
+        assert_eq!(foci, ["impl S", "a", "b"]);
+        for piece in &pieces {
+            assert!(piece
+                .source
+                .starts_with("#![allow(dead_code)]\nuse std::fmt;\n"));
+        }
+        // Items bring the impls of the types they use.
+        assert!(
+            pieces[1].source.contains("struct S;"),
+            "{}",
+            pieces[1].source
+        );
+        assert!(pieces[1].source.contains("impl S"), "{}", pieces[1].source);
+        assert!(
+            !pieces[2].source.contains("struct S"),
+            "{}",
+            pieces[2].source
+        );
+    }
+
+    #[test]
+    fn seeds_that_are_one_piece_are_not_split() {
+        assert!(split("struct S;\nfn f() -> S { S }\n").is_empty());
+        assert!(split("fn f(").is_empty());
+    }
+}
@@ -0,0 +1,406 @@
+//! Corpus statistics.
+//!
+//! A long campaign's corpus drifts: mutants pile up around a few shapes,
+//! seeds grow, editions skew. [`Stats`] summarizes a corpus from its seeds
+//! and their [`Metadata`], and counts how many findings derive from each
+//! seed, so the drift shows before it costs throughput. It also lists the
+//! seeds that took the most memory to compile, which are worth a look as
+//! memory-exhaustion bugs in their own right.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use serde::Serialize;
+
+use super::meta::{content_hash, Metadata, Provenance};
+use crate::coverage::tagger;
+use crate::triage::Severity;
+
+/// Seeds listed by name in [`Stats`]'s text form.
+const TOP_SEEDS: usize = 10;
+
+/// How many seeds fall into one size range.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Bucket {
+    /// Smallest size in the bucket, in bytes.
+    pub from: usize,
+    /// One past the largest.
+    pub to: usize,
+    pub seeds: usize,
+}
+
+/// Seed sizes in bytes.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Sizes {
+    pub total: usize,
+    pub min: usize,
+    pub median: usize,
+    pub mean: f64,
+    pub max: usize,
+    /// Power-of-two buckets, smallest first, empty ones left out.
+    pub histogram: Vec<Bucket>,
+}
+
+/// How many findings derive from one seed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Derived {
+    pub seed: String,
+    pub findings: usize,
+}
+
+/// The peak RSS recorded for one seed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Memory {
+    pub seed: String,
+    /// In bytes.
+    pub peak_rss: u64,
+}
+
+/// What a corpus looks like.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Stats {
+    pub seeds: usize,
+    /// Seeds whose sidecar matches their content.
+    pub with_metadata: usize,
+    pub sizes: Sizes,
+    /// Seeds per tag.
+    pub tags: BTreeMap<String, usize>,
+    /// Seeds per recorded edition; `none` for seeds without one.
+    pub editions: BTreeMap<String, usize>,
+    /// Seeds per provenance kind.
+    pub provenance: BTreeMap<String, usize>,
+    /// Mean compile time of seeds with one recorded, in milliseconds.
+    pub mean_compile_ms: Option<f64>,
+    /// Seeds with a compile time recorded.
+    pub timed: usize,
+    /// Seeds with a peak RSS recorded, hungriest first.
+    pub memory: Vec<Memory>,
+    pub findings: usize,
+    /// Findings per severity, worst first; findings recorded without one
+    /// are left out.
+    pub severities: BTreeMap<Severity, usize>,
+    /// Seeds findings derive from, directly or through mutants in the
+    /// corpus, most productive first.
+    pub derived: Vec<Derived>,
+    #[serde(skip)]
+    sizes_seen: Vec<usize>,
+    #[serde(skip)]
+    compile_ms: u64,
+    /// Name and parent hash of each seed, by content hash.
+    #[serde(skip)]
+    lineage: HashMap<String, (String, Option<String>)>,
+    #[serde(skip)]
+    derived_counts: HashMap<String, usize>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Stats::default()
+    }
+
+    /// Counts the seed `name`. `metadata` is ignored unless it was
+    /// recorded for `source`; seeds without it are tagged on the spot.
+    pub fn add(&mut self, name: &str, source: &str, metadata: Option<&Metadata>) {
+        let metadata = metadata.filter(|metadata| metadata.matches(source));
+        self.seeds += 1;
+        self.sizes_seen.push(source.len());
+        let tags = match metadata {
+            Some(metadata) => metadata.tags.clone(),
+            None => tagger::tags(source),
+        };
+        for tag in tags {
+            *self.tags.entry(tag).or_default() += 1;
+        }
+        let Some(metadata) = metadata else {
+            *self.editions.entry("none".to_owned()).or_default() += 1;
+            *self.provenance.entry("unknown".to_owned()).or_default() += 1;
+            self.lineage
+                .insert(content_hash(source), (name.to_owned(), None));
+            return;
+        };
+        self.with_metadata += 1;
+        let edition = metadata.edition.as_deref().unwrap_or("none");
+        *self.editions.entry(edition.to_owned()).or_default() += 1;
+        *self
+            .provenance
+            .entry(kind(&metadata.provenance).to_owned())
+            .or_default() += 1;
+        if let Some(ms) = metadata.compile_ms {
+            self.timed += 1;
+            self.compile_ms += ms;
+        }
+        if let Some(peak_rss) = metadata.peak_rss {
+            self.memory.push(Memory {
+                seed: name.to_owned(),
+                peak_rss,
+            });
+        }
+        self.lineage.insert(
+            metadata.hash.clone(),
+            (name.to_owned(), parent_hash(&metadata.provenance)),
+        );
+    }
+
+    /// Counts a finding, crediting every seed it derives from. Call after
+    /// adding the seeds.
+    pub fn add_finding(&mut self, metadata: &Metadata) {
+        self.findings += 1;
+        if let Some(severity) = metadata.severity {
+            *self.severities.entry(severity).or_default() += 1;
+        }
+        let mut parent = parent_hash(&metadata.provenance);
+        // Mutants of mutants lead back through the corpus; a cycle can
+        // only come from hand-edited sidecars, but stop on one anyway.
+        let mut steps = 0;
+        while let Some(hash) = parent.take() {
+            let Some((name, grandparent)) = self.lineage.get(&hash) else {
+                break;
+            };
+            *self.derived_counts.entry(name.clone()).or_default() += 1;
+            steps += 1;
+            if steps > self.lineage.len() {
+                break;
+            }
+            parent = grandparent.clone();
+        }
+    }
+
+    /// Computes the summary fields from what was added.
+    pub fn finish(&mut self) {
+        let sizes = &mut self.sizes_seen;
+        sizes.sort_unstable();
+        if let (Some(&min), Some(&max)) = (sizes.first(), sizes.last()) {
+            let total: usize = sizes.iter().sum();
+            let mut histogram: Vec<Bucket> = Vec::new();
+            for &size in sizes.iter() {
+                let from = if size == 0 { 0 } else { 1 << size.ilog2() };
+                match histogram.last_mut() {
+                    Some(bucket) if bucket.from == from => bucket.seeds += 1,
+                    _ => histogram.push(Bucket {
+                        from,
+                        to: (from * 2).max(1),
+                        seeds: 1,
+                    }),
+                }
+            }
+            self.sizes = Sizes {
+                total,
+                min,
+                median: sizes[sizes.len() / 2],
+                mean: total as f64 / sizes.len() as f64,
+                max,
+                histogram,
+            };
+        }
+        self.mean_compile_ms = (self.timed > 0).then(|| self.compile_ms as f64 / self.timed as f64);
+        self.memory
+            .sort_by(|a, b| b.peak_rss.cmp(&a.peak_rss).then(a.seed.cmp(&b.seed)));
+        let mut derived: Vec<Derived> = self
+            .derived_counts
+            .iter()
+            .map(|(seed, &findings)| Derived {
+                seed: seed.clone(),
+                findings,
+            })
+            .collect();
+        derived.sort_by(|a, b| b.findings.cmp(&a.findings).then(a.seed.cmp(&b.seed)));
+        self.derived = derived;
+    }
+}
+
+fn kind(provenance: &Provenance) -> &'static str {
+    match provenance {
+        Provenance::Unknown => "unknown",
+        Provenance::Imported { .. } => "imported",
+        Provenance::Generated { .. } => "generated",
+        Provenance::Template { .. } => "template",
+        Provenance::Split { .. } => "split",
+        Provenance::Mutated { .. } => "mutated",
+        Provenance::Migrated { .. } => "migrated",
+        Provenance::Reduced { .. } => "reduced",
+    }
+}
+
+fn parent_hash(provenance: &Provenance) -> Option<String> {
+    match provenance {
+        Provenance::Split { parent_hash, .. }
+        | Provenance::Mutated { parent_hash, .. }
+        | Provenance::Migrated { parent_hash, .. }
+        | Provenance::Reduced { parent_hash, .. } => Some(parent_hash.clone()),
+        _ => None,
+    }
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "{} seeds, {} with metadata, {} bytes",
+            self.seeds, self.with_metadata, self.sizes.total
+        )?;
+        let sizes = &self.sizes;
+        writeln!(
+            f,
+            "size: min {}, median {}, mean {:.0}, max {}",
+            sizes.min, sizes.median, sizes.mean, sizes.max
+        )?;
+        let widest = sizes.histogram.iter().map(|b| b.seeds).max().unwrap_or(0);
+        for bucket in &sizes.histogram {
+            let bar = "#".repeat((bucket.seeds * 40).div_ceil(widest.max(1)));
+            writeln!(
+                f,
+                "{:>8}..{:<8} {:>6}  {bar}",
+                bucket.from, bucket.to, bucket.seeds
+            )?;
+        }
+        writeln!(f, "editions:")?;
+        for (edition, count) in &self.editions {
+            writeln!(f, "{count:>8}  {edition}")?;
+        }
+        writeln!(f, "provenance:")?;
+        for (kind, count) in &self.provenance {
+            writeln!(f, "{count:>8}  {kind}")?;
+        }
+        writeln!(f, "tags:")?;
+        let mut tags: Vec<(&String, &usize)> = self.tags.iter().collect();
+        tags.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (tag, count) in tags {
+            writeln!(f, "{count:>8}  {tag}")?;
+        }
+        match self.mean_compile_ms {
+            Some(ms) => writeln!(f, "compile time: mean {ms:.0} ms over {} seeds", self.timed)?,
+            None => writeln!(f, "compile time: not recorded; run `corpus verify`")?,
+        }
+        if self.memory.is_empty() {
+            writeln!(f, "peak RSS: not recorded; run `corpus verify`")?;
+        } else {
+            writeln!(f, "peak RSS, in MiB:")?;
+            for memory in self.memory.iter().take(TOP_SEEDS) {
+                writeln!(f, "{:>8}  {}", memory.peak_rss >> 20, memory.seed)?;
+            }
+        }
+        writeln!(f, "findings: {}", self.findings)?;
+        for (severity, count) in &self.severities {
+            writeln!(f, "{count:>8}  {severity}")?;
+        }
+        if !self.derived.is_empty() {
+            writeln!(f, "findings by seed:")?;
+        }
+        for derived in self.derived.iter().take(TOP_SEEDS) {
+            writeln!(f, "{:>8}  {}", derived.findings, derived.seed)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn split_of(parent: &str, source: &str) -> Metadata {
+        Metadata::new(
+            source,
+            Provenance::Split {
+                parent: String::new(),
+                parent_hash: content_hash(parent),
+                focus: "f".to_owned(),
+            },
+        )
+    }
+
+    #[test]
+    fn findings_are_credited_to_every_ancestor() {
+        let (root, child) = ("fn f() {}\nfn g() {}\n", "fn f() {}\n");
+        let mut stats = Stats::new();
+        let root_metadata = Metadata {
+            edition: Some("2021".to_owned()),
+            compile_ms: Some(30),
+            ..Metadata::new(root, Provenance::Unknown)
+        };
+        stats.add("root.rs", root, Some(&root_metadata));
+        let child_metadata = Metadata {
+            compile_ms: Some(10),
+            ..split_of(root, child)
+        };
+        stats.add("child.rs", child, Some(&child_metadata));
+        // Recorded for other content, so ignored.
+        stats.add("edited.rs", "fn h() {}", Some(&root_metadata));
+        stats.add_finding(&split_of(child, "fn f() { loop {} }"));
+        stats.add_finding(&split_of(root, "fn g() {}"));
+        stats.finish();
+
+        assert_eq!((stats.seeds, stats.with_metadata), (3, 2));
+        assert_eq!(stats.editions["2021"], 1);
+        assert_eq!(stats.editions["none"], 2);
+        assert_eq!(stats.provenance["split"], 1);
+        assert_eq!(stats.mean_compile_ms, Some(20.0));
+        assert_eq!(stats.findings, 2);
+        assert_eq!(
+            stats.derived,
+            [
+                Derived {
+                    seed: "root.rs".to_owned(),
+                    findings: 2
+                },
+                Derived {
+                    seed: "child.rs".to_owned(),
+                    findings: 1
+                },
+            ]
+        );
+        let text = stats.to_string();
+        assert!(text.starts_with("3 seeds, 2 with metadata, "), "{text}");
+        assert!(
+            text.contains("findings by seed:\n       2  root.rs\n"),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn the_hungriest_seeds_come_first() {
+        let mut stats = Stats::new();
+        for (name, source, peak_rss) in [
+            ("a.rs", "fn a() {}", 1 << 20),
+            ("b.rs", "fn b() {}", 3 << 20),
+        ] {
+            let metadata = Metadata {
+                peak_rss: Some(peak_rss),
+                ..Metadata::new(source, Provenance::Unknown)
+            };
+            stats.add(name, source, Some(&metadata));
+        }
+        stats.add("c.rs", "fn c() {}", None);
+        stats.finish();
+        let seeds: Vec<&str> = stats
+            .memory
+            .iter()
+            .map(|memory| memory.seed.as_str())
+            .collect();
+        assert_eq!(seeds, ["b.rs", "a.rs"]);
+        let text = stats.to_string();
+        assert!(
+            text.contains("peak RSS, in MiB:\n       3  b.rs\n       1  a.rs\n"),
+            "{text}"
+        );
+    }
+
+    #[test]
+    fn sizes_fall_into_power_of_two_buckets() {
+        let mut stats = Stats::new();
+        for source in ["", "a", "abc", "abcd", "abcde"] {
+            stats.add("seed.rs", source, None);
+        }
+        stats.finish();
+        let sizes = &stats.sizes;
+        assert_eq!(
+            (sizes.min, sizes.median, sizes.max, sizes.total),
+            (0, 3, 5, 13)
+        );
+        let buckets: Vec<(usize, usize, usize)> = sizes
+            .histogram
+            .iter()
+            .map(|bucket| (bucket.from, bucket.to, bucket.seeds))
+            .collect();
+        assert_eq!(buckets, [(0, 1, 1), (1, 2, 1), (2, 4, 1), (4, 8, 2)]);
+    }
+}
@@ -0,0 +1,433 @@
+//! Remote corpus synchronization.
+//!
+//! Workers on different machines, and teammates, fuzz better from one
+//! shared corpus than from several that drift apart. [`Syncer`] keeps a
+//! corpus directory in step with a remote copy in S3-compatible object
+//! storage (through the `aws` CLI, with an endpoint for MinIO, R2 and the
+//! like) or anywhere rsync reaches, over ssh or locally.
+//!
+//! The remote is flat and content-addressed: every seed is stored as
+//! `<hash>.rs` beside its sidecar `<hash>.meta.json`, where the hash is
+//! the SHA-1 of its content, from [`key`]. Listing the remote is enough to
+//! tell which seeds each side lacks, so only those are transferred, and
+//! seeds are never overwritten: a seed's content is its name. Pulled seeds
+//! land at the top of the corpus directory under the same names, and one
+//! whose content does not hash to its name is dropped. The sidecars'
+//! [`content_hash`](super::meta::content_hash) is too short for names
+//! anyone with write access to the remote could pick content for.
+
+use std::collections::{BTreeSet, HashSet};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+use serde::Serialize;
+
+use super::export::{hex, sha1};
+use super::meta::{self, Metadata};
+use super::Corpus;
+
+/// Seeds named on one `aws s3 cp` command line.
+const S3_BATCH: usize = 200;
+
+/// Where the shared corpus lives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Remote {
+    /// `s3://bucket/prefix/`, with the endpoint of an S3-compatible
+    /// service other than AWS.
+    S3 {
+        url: String,
+        endpoint: Option<String>,
+    },
+    /// An rsync destination directory: `host:path`, `user@host:path` or a
+    /// local path.
+    Rsync { dest: String },
+}
+
+impl Remote {
+    /// The remote at `location`: an `s3://` URL or an rsync destination.
+    pub fn parse(location: &str, endpoint: Option<String>) -> Self {
+        let mut location = location.to_owned();
+        if !location.ends_with('/') {
+            location.push('/');
+        }
+        if location.starts_with("s3://") {
+            Remote::S3 {
+                url: location,
+                endpoint,
+            }
+        } else {
+            Remote::Rsync { dest: location }
+        }
+    }
+}
+
+/// Which way seeds go.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Direction {
+    /// Only upload what the remote lacks.
+    Push,
+    /// Only download what the corpus lacks.
+    Pull,
+    /// Both.
+    #[default]
+    Both,
+}
+
+/// What a sync did, or would do.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SyncReport {
+    /// Distinct seeds in the corpus before syncing.
+    pub local: usize,
+    /// Seeds on the remote before syncing.
+    pub remote: usize,
+    /// Keys uploaded.
+    pub pushed: Vec<String>,
+    /// Keys downloaded.
+    pub pulled: Vec<String>,
+    /// Keys downloaded whose content did not match them.
+    pub corrupt: Vec<String>,
+}
+
+/// Synchronizes corpus directories with a [`Remote`].
+#[derive(Debug, Clone)]
+pub struct Syncer {
+    pub remote: Remote,
+    pub direction: Direction,
+    /// List what would be transferred without transferring it.
+    pub dry_run: bool,
+    pub aws: PathBuf,
+    pub rsync: PathBuf,
+}
+
+impl Syncer {
+    pub fn new(remote: Remote) -> Self {
+        Syncer {
+            remote,
+            direction: Direction::default(),
+            dry_run: false,
+            aws: PathBuf::from("aws"),
+            rsync: PathBuf::from("rsync"),
+        }
+    }
+
+    /// Brings `dir` and the remote up to date with each other.
+    pub fn sync(&self, dir: &Path) -> io::Result<SyncReport> {
+        let mut local = Vec::new();
+        let mut hashes = BTreeSet::new();
+        if dir.exists() {
+            for entry in Corpus::open(dir).entries()? {
+                let hash = key(&entry.source);
+                if hashes.insert(hash.clone()) {
+                    local.push((hash, entry.path));
+                }
+            }
+        }
+        let remote = self.list()?;
+        let mut report = SyncReport {
+            local: hashes.len(),
+            remote: remote.len(),
+            ..SyncReport::default()
+        };
+        if self.direction != Direction::Pull {
+            report.pushed = local
+                .iter()
+                .filter(|(hash, _)| !remote.contains(hash))
+                .map(|(hash, _)| hash.clone())
+                .collect();
+        }
+        if self.direction != Direction::Push {
+            report.pulled = remote.difference(&hashes).cloned().collect();
+        }
+        if self.dry_run || (report.pushed.is_empty() && report.pulled.is_empty()) {
+            return Ok(report);
+        }
+        let staging = std::env::temp_dir().join(format!("rustc-fuzz-sync-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&staging);
+        let result = self.transfer(dir, &local, &staging, &mut report);
+        let _ = fs::remove_dir_all(&staging);
+        result.map(|()| report)
+    }
+
+    fn transfer(
+        &self,
+        dir: &Path,
+        local: &[(String, PathBuf)],
+        staging: &Path,
+        report: &mut SyncReport,
+    ) -> io::Result<()> {
+        if !report.pushed.is_empty() {
+            let outbox = staging.join("push");
+            fs::create_dir_all(&outbox)?;
+            let pushed: HashSet<&String> = report.pushed.iter().collect();
+            for (hash, path) in local.iter().filter(|(hash, _)| pushed.contains(hash)) {
+                let source = fs::read_to_string(path)?;
+                let seed = outbox.join(format!("{hash}.rs"));
+                fs::write(&seed, &source)?;
+                // A sidecar recorded for other content would mislead
+                // whoever pulls it.
+                if let Some(metadata) = Metadata::load(path)?.filter(|m| m.matches(&source)) {
+                    metadata.save(&seed)?;
+                }
+            }
+            self.upload(&outbox)?;
+        }
+        if !report.pulled.is_empty() {
+            let inbox = staging.join("pull");
+            fs::create_dir_all(&inbox)?;
+            self.download(&report.pulled, &inbox)?;
+            fs::create_dir_all(dir)?;
+            let mut pulled = Vec::new();
+            for hash in std::mem::take(&mut report.pulled) {
+                match accept(&inbox, dir, &hash)? {
+                    Some(true) => pulled.push(hash),
+                    Some(false) => report.corrupt.push(hash),
+                    // Removed from the remote since it was listed.
+                    None => {}
+                }
+            }
+            report.pulled = pulled;
+        }
+        Ok(())
+    }
+
+    /// The keys of the seeds on the remote.
+    fn list(&self) -> io::Result<BTreeSet<String>> {
+        let names: Vec<String> = match &self.remote {
+            Remote::S3 { url, .. } => {
+                let output = self.aws(&["s3", "ls", url])?;
+                // `aws s3 ls` fails without a word when nothing matches.
+                if !output.status.success() && !output.stderr.is_empty() {
+                    return Err(failure("aws s3 ls", &output));
+                }
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .filter_map(|line| line.split_whitespace().nth(3).map(str::to_owned))
+                    .collect()
+            }
+            Remote::Rsync { dest } => {
+                let output = self.rsync_output(&["--list-only", dest], None)?;
+                if !output.status.success() {
+                    let stderr = String::from_utf8_lossy(&output.stderr);
+                    // Nothing has been pushed there yet.
+                    if stderr.contains("No such file or directory") {
+                        return Ok(BTreeSet::new());
+                    }
+                    return Err(failure("rsync --list-only", &output));
+                }
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .filter(|line| line.starts_with('-'))
+                    .filter_map(|line| line.split_whitespace().last().map(str::to_owned))
+                    .collect()
+            }
+        };
+        Ok(names
+            .iter()
+            .filter_map(|name| name.strip_suffix(".rs"))
+            .filter(|hash| hash.len() == 40 && hash.bytes().all(|b| b.is_ascii_hexdigit()))
+            .map(str::to_owned)
+            .collect())
+    }
+
+    /// Uploads every file in `outbox`.
+    fn upload(&self, outbox: &Path) -> io::Result<()> {
+        let outbox = format!("{}/", outbox.display());
+        let (program, output) = match &self.remote {
+            Remote::S3 { url, .. } => (
+                "aws s3 cp",
+                self.aws(&[
+                    "s3",
+                    "cp",
+                    "--recursive",
+                    "--only-show-errors",
+                    &outbox,
+                    url,
+                ])?,
+            ),
+            Remote::Rsync { dest } => (
+                "rsync",
+                self.rsync_output(&["--recursive", "--ignore-existing", &outbox, dest], None)?,
+            ),
+        };
+        if !output.status.success() {
+            return Err(failure(program, &output));
+        }
+        Ok(())
+    }
+
+    /// Downloads the seeds `hashes` and their sidecars into `inbox`.
+    fn download(&self, hashes: &[String], inbox: &Path) -> io::Result<()> {
+        let inbox_arg = format!("{}/", inbox.display());
+        match &self.remote {
+            Remote::S3 { url, .. } => {
+                for batch in hashes.chunks(S3_BATCH) {
+                    let mut args = vec![
+                        "s3".to_owned(),
+                        "cp".to_owned(),
+                        "--recursive".to_owned(),
+                        "--only-show-errors".to_owned(),
+                        url.clone(),
+                        inbox_arg.clone(),
+                        "--exclude".to_owned(),
+                        "*".to_owned(),
+                    ];
+                    for hash in batch {
+                        args.extend([
+                            "--include".to_owned(),
+                            format!("{hash}.rs"),
+                            "--include".to_owned(),
+                            format!("{hash}.meta.json"),
+                        ]);
+                    }
+                    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+                    let output = self.aws(&args)?;
+                    if !output.status.success() {
+                        return Err(failure("aws s3 cp", &output));
+                    }
+                }
+            }
+            Remote::Rsync { dest } => {
+                let mut names = String::new();
+                for hash in hashes {
+                    names.push_str(&format!("{hash}.rs\n{hash}.meta.json\n"));
+                }
+                let output = self.rsync_output(
+                    &["--files-from=-", "--ignore-missing-args", dest, &inbox_arg],
+                    Some(names.as_bytes()),
+                )?;
+                if !output.status.success() {
+                    return Err(failure("rsync", &output));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn aws(&self, args: &[&str]) -> io::Result<Output> {
+        tracing::debug!(program = %self.aws.display(), ?args, "running");
+        let mut command = Command::new(&self.aws);
+        command.args(args);
+        if let Remote::S3 {
+            endpoint: Some(endpoint),
+            ..
+        } = &self.remote
+        {
+            command.arg("--endpoint-url").arg(endpoint);
+        }
+        command
+            .output()
+            .map_err(|e| io::Error::new(e.kind(), format!("running {}: {e}", self.aws.display())))
+    }
+
+    fn rsync_output(&self, args: &[&str], stdin: Option<&[u8]>) -> io::Result<Output> {
+        tracing::debug!(program = %self.rsync.display(), ?args, "running");
+        let mut child = Command::new(&self.rsync)
+            .args(args)
+            .stdin(if stdin.is_some() {
+                Stdio::piped()
+            } else {
+                Stdio::null()
+            })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                io::Error::new(e.kind(), format!("running {}: {e}", self.rsync.display()))
+            })?;
+        if let (Some(input), Some(mut pipe)) = (stdin, child.stdin.take()) {
+            pipe.write_all(input)?;
+        }
+        child.wait_with_output()
+    }
+}
+
+/// Moves the seed `hash` downloaded to `inbox` into `dir`, with its
+/// sidecar if that was recorded for it. Returns whether its content
+/// matched `hash`, or `None` if it was not downloaded.
+fn accept(inbox: &Path, dir: &Path, hash: &str) -> io::Result<Option<bool>> {
+    let name = format!("{hash}.rs");
+    let source = match fs::read_to_string(inbox.join(&name)) {
+        Ok(source) => source,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if key(&source) != hash {
+        return Ok(Some(false));
+    }
+    let seed = dir.join(&name);
+    fs::write(&seed, &source)?;
+    if let Ok(json) = fs::read_to_string(meta::sidecar(&inbox.join(&name))) {
+        if serde_json::from_str::<Metadata>(&json).is_ok_and(|m| m.matches(&source)) {
+            fs::write(meta::sidecar(&seed), json)?;
+        }
+    }
+    Ok(Some(true))
+}
+
+/// The name a seed with `source` has on a remote: the SHA-1 of its
+/// content, in hex.
+pub fn key(source: &str) -> String {
+    hex(&sha1(source.as_bytes()))
+}
+
+fn failure(program: &str, output: &Output) -> io::Error {
+    io::Error::other(format!(
+        "{program}: {}",
+        String::from_utf8_lossy(&output.stderr).trim()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::corpus::meta::Provenance;
+
+    #[test]
+    fn keys_are_sha1() {
+        assert_eq!(key(""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(key("abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    }
+
+    #[test]
+    fn remotes_parse_by_scheme() {
+        assert_eq!(
+            Remote::parse("s3://bucket/corpus", None),
+            Remote::S3 {
+                url: "s3://bucket/corpus/".to_owned(),
+                endpoint: None,
+            }
+        );
+        assert_eq!(
+            Remote::parse("host:corpus/", None),
+            Remote::Rsync {
+                dest: "host:corpus/".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn pulled_seeds_must_match_their_key() {
+        let root =
+            std::env::temp_dir().join(format!("rustc-fuzz-sync-test-{}", std::process::id()));
+        let (inbox, dir) = (root.join("inbox"), root.join("corpus"));
+        fs::create_dir_all(&inbox).expect("creates the inbox");
+        fs::create_dir_all(&dir).expect("creates the corpus");
+        let source = "fn main() {}\n";
+        let good = key(source);
+        fs::write(inbox.join(format!("{good}.rs")), source).expect("writes");
+        Metadata::new(source, Provenance::Unknown)
+            .save(&inbox.join(format!("{good}.rs")))
+            .expect("writes");
+        let bad = key("fn other() {}\n");
+        fs::write(inbox.join(format!("{bad}.rs")), source).expect("writes");
+
+        assert_eq!(accept(&inbox, &dir, &good).expect("accepts"), Some(true));
+        assert!(meta::sidecar(&dir.join(format!("{good}.rs"))).is_file());
+        assert_eq!(accept(&inbox, &dir, &bad).expect("checks"), Some(false));
+        assert!(!dir.join(format!("{bad}.rs")).exists());
+        assert_eq!(accept(&inbox, &dir, &key("")).expect("checks"), None);
+        fs::remove_dir_all(&root).expect("cleans up");
+    }
+}
@@ -0,0 +1,205 @@
+//! Seeds from rustc's UI test suite.
+//!
+//! `tests/ui` in rust-lang/rust is tens of thousands of small programs
+//! written to hit compiler edge cases, each with `//@` directives saying
+//! how compiletest builds it. [`import`] reads the suite and turns each
+//! test that stands alone into a seed, with its edition and flags moved
+//! into [`Metadata`].
+
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use super::meta::{Metadata, Provenance};
+use super::Corpus;
+
+/// The `//@` directives of one test that matter to a seed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Directives {
+    pub edition: Option<String>,
+    /// `compile-flags`, in order.
+    pub flags: Vec<String>,
+    /// `revisions`; compiletest builds the test once per revision with
+    /// `--cfg <revision>` and that revision's own directives.
+    pub revisions: Vec<String>,
+    /// `compile-flags` of each revision.
+    pub revision_flags: Vec<(String, Vec<String>)>,
+    /// Auxiliary crates the test links (`aux-build`, `aux-crate`,
+    /// `proc-macro`); a test that needs them does not stand alone.
+    pub aux: Vec<String>,
+}
+
+impl Directives {
+    /// Parses the `//@` lines of `source`.
+    pub fn parse(source: &str) -> Self {
+        let mut directives = Directives::default();
+        for line in source.lines() {
+            let Some(directive) = line.trim_start().strip_prefix("//@") else {
+                continue;
+            };
+            let mut directive = directive.trim();
+            let mut revision = None;
+            if let Some(rest) = directive.strip_prefix('[') {
+                if let Some((name, rest)) = rest.split_once(']') {
+                    revision = Some(name.trim().to_owned());
+                    directive = rest.trim();
+                }
+            }
+            let (name, value) = match directive.split_once(':') {
+                Some((name, value)) => (name.trim(), value.trim()),
+                None => (directive, ""),
+            };
+            match name {
+                // A range (`2018..`, `..2021`) means any edition in it;
+                // take the oldest.
+                "edition" if revision.is_none() => {
+                    directives.edition = match value.split_once("..") {
+                        Some(("", _)) => Some("2015".to_owned()),
+                        Some((oldest, _)) => Some(oldest.trim().to_owned()),
+                        None => Some(value.to_owned()),
+                    }
+                }
+                "compile-flags" => {
+                    let flags = value.split_whitespace().map(str::to_owned);
+                    match revision {
+                        None => directives.flags.extend(flags),
+                        Some(revision) => {
+                            directives.revision_flags.push((revision, flags.collect()))
+                        }
+                    }
+                }
+                "revisions" => directives
+                    .revisions
+                    .extend(value.split_whitespace().map(str::to_owned)),
+                "aux-build" | "aux-crate" | "aux-bin" | "aux-codegen-backend" | "proc-macro" => {
+                    directives.aux.push(value.to_owned())
+                }
+                _ => {}
+            }
+        }
+        directives
+    }
+
+    /// The flags of the first revision, or of the test if it has none.
+    pub fn first_revision_flags(&self) -> Vec<String> {
+        let mut flags = self.flags.clone();
+        if let Some(revision) = self.revisions.first() {
+            flags.push(format!("--cfg={revision}"));
+            for (name, extra) in &self.revision_flags {
+                if name == revision {
+                    flags.extend(extra.iter().cloned());
+                }
+            }
+        }
+        flags
+    }
+}
+
+/// A UI test turned into a seed.
+#[derive(Debug, Clone)]
+pub struct UiSeed {
+    /// Path relative to the suite root.
+    pub path: PathBuf,
+    pub source: String,
+    pub metadata: Metadata,
+}
+
+/// The tests under `suite` (a `tests/ui` directory) that stand alone, as
+/// seeds. Auxiliary crates, and tests that need them, are left out. Tests
+/// with revisions get the first revision's flags.
+pub fn import(suite: &Path) -> io::Result<Vec<UiSeed>> {
+    let mut seeds = Vec::new();
+    for entry in Corpus::open(suite).entries()? {
+        let path = entry
+            .path
+            .strip_prefix(suite)
+            .unwrap_or(&entry.path)
+            .to_owned();
+        if path
+            .components()
+            .any(|component| component == Component::Normal("auxiliary".as_ref()))
+        {
+            continue;
+        }
+        let directives = Directives::parse(&entry.source);
+        if !directives.aux.is_empty() {
+            continue;
+        }
+        let provenance = Provenance::Imported {
+            origin: format!("rust-lang/rust:tests/ui/{}", path.display()),
+        };
+        let metadata = Metadata {
+            edition: directives.edition.clone(),
+            flags: directives.first_revision_flags(),
+            ..Metadata::new(&entry.source, provenance)
+        };
+        seeds.push(UiSeed {
+            path,
+            source: entry.source,
+            metadata,
+        });
+    }
+    Ok(seeds)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn directives_follow_compiletest() {
+        let source = "//@ edition: 2018..\n\
+                      //@ compile-flags: -O -Zunstable-options\n\
+                      //@ revisions: old new\n\
+                      //@[new] compile-flags: -Znext-solver\n\
+                      //@[old] edition: 2021\n\
+                      //@ check-pass\n\
+                      fn main() {}\n";
+        let directives = Directives::parse(source);
+        assert_eq!(directives.edition.as_deref(), Some("2018"));
+        assert_eq!(directives.flags, ["-O", "-Zunstable-options"]);
+        assert_eq!(directives.revisions, ["old", "new"]);
+        assert_eq!(
+            directives.revision_flags,
+            [("new".to_owned(), vec!["-Znext-solver".to_owned()])]
+        );
+        assert_eq!(
+            directives.first_revision_flags(),
+            ["-O", "-Zunstable-options", "--cfg=old"]
+        );
+        let upto = Directives::parse("//@ edition:..2021\n");
+        assert_eq!(upto.edition.as_deref(), Some("2015"));
+    }
+
+    #[test]
+    fn tests_that_need_other_crates_are_left_out() {
+        let suite = std::env::temp_dir().join(format!("rustc-fuzz-ui-{}", std::process::id()));
+        fs::create_dir_all(suite.join("traits/auxiliary")).expect("creates the suite");
+        fs::write(
+            suite.join("traits/alone.rs"),
+            "//@ edition: 2021\nfn main() {}\n",
+        )
+        .expect("writes a test");
+        fs::write(
+            suite.join("traits/linked.rs"),
+            "//@ aux-build: helper.rs\nextern crate helper;\n",
+        )
+        .expect("writes a test");
+        fs::write(suite.join("traits/auxiliary/helper.rs"), "pub fn f() {}\n")
+            .expect("writes an auxiliary crate");
+
+        let seeds = import(&suite).expect("imports");
+        let [seed] = &seeds[..] else {
+            panic!("{seeds:?}");
+        };
+        assert_eq!(seed.path, Path::new("traits/alone.rs"));
+        assert_eq!(seed.metadata.edition.as_deref(), Some("2021"));
+        assert_eq!(
+            seed.metadata.provenance,
+            Provenance::Imported {
+                origin: "rust-lang/rust:tests/ui/traits/alone.rs".to_owned()
+            }
+        );
+        fs::remove_dir_all(&suite).expect("cleans up");
+    }
+}
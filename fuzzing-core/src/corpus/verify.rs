@@ -0,0 +1,183 @@
+//! Checking that a corpus still compiles.
+//!
+//! [`verify`] compiles every seed of a corpus with the edition and flags
+//! its sidecar records, and brings the sidecar up to date: the feature
+//! tags, the peak RSS, and for seeds that compile, the compiler and how
+//! long it took. Seeds that compiled with an earlier compiler and are
+//! rejected now can be moved to a quarantine directory (see
+//! [`quarantine`](super::quarantine)).
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use super::meta::{Metadata, Provenance};
+use super::quarantine::{self, Quarantine};
+use super::Corpus;
+use crate::coverage::tagger;
+use crate::harness::{ExecOutcome, Rustc};
+
+/// What [`verify`] found.
+#[derive(Debug, Default)]
+pub struct Verification {
+    /// Seeds in the corpus.
+    pub seeds: usize,
+    /// Seeds that compile.
+    pub verified: usize,
+    /// Seeds that compiled before and do not now, with the compiler they
+    /// last compiled with and the first error they get now.
+    pub stale: Vec<(PathBuf, String, String)>,
+    /// Seeds rustc ran out of memory on, with how.
+    pub oom: Vec<(PathBuf, ExecOutcome)>,
+}
+
+/// Compiles every seed of the corpus at `dir` with `rustc`, whose `-V` is
+/// `version`, and updates their sidecars. With `quarantine_dir`, stale
+/// seeds are moved there with a [`Quarantine`] record.
+pub fn verify(
+    dir: &Path,
+    rustc: &Rustc,
+    version: &str,
+    quarantine_dir: Option<&Path>,
+) -> io::Result<Verification> {
+    let entries = Corpus::open(dir)
+        .entries()
+        .map_err(|e| io::Error::new(e.kind(), format!("reading corpus {}: {e}", dir.display())))?;
+    let mut verification = Verification {
+        seeds: entries.len(),
+        ..Verification::default()
+    };
+    for entry in entries {
+        let mut metadata = match Metadata::load(&entry.path)
+            .map_err(about("reading metadata for", &entry.path))?
+        {
+            Some(metadata) if metadata.matches(&entry.source) => metadata,
+            Some(stale) => Metadata {
+                edition: stale.edition,
+                flags: stale.flags,
+                ..Metadata::new(&entry.source, stale.provenance)
+            },
+            None => Metadata::new(&entry.source, Provenance::Unknown),
+        };
+        // Sidecars written before tagging, or by an older tagger.
+        metadata.tags = tagger::tags(&entry.source);
+        let mut args = vec!["--crate-type=lib".to_owned()];
+        if let Some(edition) = &metadata.edition {
+            args.extend(["--edition".to_owned(), edition.clone()]);
+        }
+        args.extend(metadata.flags.iter().cloned());
+        args.push("-".to_owned());
+        let outcome = rustc.run(&args, entry.source.as_bytes())?;
+        metadata.peak_rss = outcome.output().peak_rss;
+        if outcome.is_success() {
+            metadata.verified_with = Some(version.to_owned());
+            metadata.compile_ms = Some(outcome.output().elapsed.as_millis() as u64);
+            metadata.quarantined = None;
+            verification.verified += 1;
+        } else if let (ExecOutcome::Error { output, .. }, Some(last)) =
+            (&outcome, &metadata.verified_with)
+        {
+            let error = quarantine::first_error(&output.stderr);
+            verification
+                .stale
+                .push((entry.path.clone(), last.clone(), error.clone()));
+            if let Some(quarantine_dir) = quarantine_dir {
+                metadata.quarantined = Some(Quarantine {
+                    rustc: version.to_owned(),
+                    error,
+                });
+                metadata
+                    .save(&entry.path)
+                    .map_err(about("writing metadata for", &entry.path))?;
+                quarantine::move_seed(dir, &entry.path, quarantine_dir).map_err(|e| {
+                    io::Error::new(
+                        e.kind(),
+                        format!(
+                            "moving {} to {}: {e}",
+                            entry.path.display(),
+                            quarantine_dir.display()
+                        ),
+                    )
+                })?;
+                continue;
+            }
+        }
+        metadata
+            .save(&entry.path)
+            .map_err(about("writing metadata for", &entry.path))?;
+        if let ExecOutcome::OutOfMemory(_) = outcome {
+            verification.oom.push((entry.path, outcome));
+        }
+    }
+    Ok(verification)
+}
+
+/// Says what was being done to the seed at `path` when an error came.
+fn about(what: &'static str, path: &Path) -> impl FnOnce(io::Error) -> io::Error {
+    let path = path.display().to_string();
+    move |e| io::Error::new(e.kind(), format!("{what} {path}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn seeds_that_stopped_compiling_are_quarantined() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-verify-{}", std::process::id()));
+        let corpus = dir.join("corpus");
+        let quarantined = dir.join("quarantine");
+        fs::create_dir_all(&corpus).expect("creates the corpus");
+        // Rejects any seed that mentions `gone`.
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            "#!/bin/sh\nif grep -q gone; then\n  \
+             echo 'error: feature has been removed' >&2\n  exit 1\nfi\n",
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let fine = corpus.join("fine.rs");
+        fs::write(&fine, "fn fine() {}").expect("writes a seed");
+        let gone = corpus.join("gone.rs");
+        fs::write(&gone, "fn gone() {}").expect("writes a seed");
+        Metadata {
+            verified_with: Some("rustc 1.0.0".to_owned()),
+            ..Metadata::new("fn gone() {}", Provenance::Unknown)
+        }
+        .save(&gone)
+        .expect("writes the sidecar");
+
+        let verification = verify(
+            &corpus,
+            &Rustc::new(&rustc),
+            "rustc 2.0.0",
+            Some(&quarantined),
+        )
+        .expect("verifies");
+        assert_eq!((verification.seeds, verification.verified), (2, 1));
+        assert_eq!(
+            verification.stale,
+            [(
+                gone.clone(),
+                "rustc 1.0.0".to_owned(),
+                "error: feature has been removed".to_owned()
+            )]
+        );
+        assert!(verification.oom.is_empty());
+        let metadata = Metadata::load(&fine)
+            .expect("reads")
+            .expect("has a sidecar");
+        assert_eq!(metadata.verified_with.as_deref(), Some("rustc 2.0.0"));
+        assert!(!gone.exists());
+        let moved = Metadata::load(&quarantined.join("gone.rs"))
+            .expect("reads")
+            .expect("moved with its sidecar");
+        assert_eq!(
+            moved.quarantined.map(|q| q.rustc).as_deref(),
+            Some("rustc 2.0.0")
+        );
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
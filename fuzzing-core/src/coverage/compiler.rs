@@ -0,0 +1,645 @@
+//! Compiler coverage.
+//!
+//! Feature tags say what a seed is made of, not what it made the compiler
+//! do. With a rustc whose compiler crates were built with
+//! `-Cinstrument-coverage`, a [`Profiler`] compiles a seed with
+//! `LLVM_PROFILE_FILE` pointing into a scratch directory, merges the
+//! profiles with `llvm-profdata` and reads the covered source [`Regions`]
+//! back from `llvm-cov export`. Measuring a corpus seed by seed gives each
+//! seed the regions no seed before it reached, and a [`CorpusCoverage`]
+//! sums them up per compiler module, so it is plain which subsystems the
+//! corpus never reaches.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use serde::{Deserialize, Serialize};
+
+use crate::harness::rustc::Scratch;
+use crate::harness::{ExecOutcome, Rustc};
+
+/// Covered source regions of the compiler, by file relative to the
+/// rust-lang/rust checkout, as the line and column each region starts at.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Regions {
+    pub files: BTreeMap<String, BTreeSet<(u32, u32)>>,
+}
+
+impl Regions {
+    pub fn len(&self) -> usize {
+        self.files.values().map(BTreeSet::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.files.values().all(BTreeSet::is_empty)
+    }
+
+    /// The regions here that are not in `other`.
+    pub fn difference(&self, other: &Regions) -> Regions {
+        let mut files = BTreeMap::new();
+        for (file, regions) in &self.files {
+            let new: BTreeSet<_> = match other.files.get(file) {
+                Some(seen) => regions.difference(seen).copied().collect(),
+                None => regions.clone(),
+            };
+            if !new.is_empty() {
+                files.insert(file.clone(), new);
+            }
+        }
+        Regions { files }
+    }
+
+    /// Adds the regions of `other`.
+    pub fn extend(&mut self, other: &Regions) {
+        for (file, regions) in &other.files {
+            self.files
+                .entry(file.clone())
+                .or_default()
+                .extend(regions.iter().copied());
+        }
+    }
+
+    /// The number of regions in each [`module`].
+    pub fn by_module(&self) -> BTreeMap<String, usize> {
+        let mut modules = BTreeMap::new();
+        for (file, regions) in &self.files {
+            *modules.entry(module(file)).or_default() += regions.len();
+        }
+        modules
+    }
+}
+
+/// The compiler module `file` belongs to: its crate, as
+/// `compiler/rustc_hir_typeck`, or its library, as `library/core`.
+pub fn module(file: &str) -> String {
+    let mut components = file.split('/');
+    match (components.next(), components.next()) {
+        (Some(top), Some(krate)) if top == "compiler" || top == "library" => {
+            format!("{top}/{krate}")
+        }
+        _ => file.to_owned(),
+    }
+}
+
+/// `file` relative to the rust-lang/rust checkout it was built in, if it
+/// is in one.
+fn relative(file: &str) -> &str {
+    ["compiler/", "library/"]
+        .iter()
+        .filter_map(|dir| {
+            if file.starts_with(dir) {
+                Some(0)
+            } else {
+                file.find(&format!("/{dir}")).map(|i| i + 1)
+            }
+        })
+        .min()
+        .map_or(file, |i| &file[i..])
+}
+
+/// What one compile covered.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Measurement {
+    pub covered: Regions,
+    /// Instrumented regions per module, covered or not.
+    pub totals: BTreeMap<String, usize>,
+}
+
+/// A seed's share of a corpus's compiler coverage, as `coverage measure`
+/// recorded it in its metadata.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeedCoverage {
+    /// Regions it covers.
+    pub covered: usize,
+    /// Regions it covers that no seed measured before it did.
+    pub new: usize,
+    /// Regions it covers per module.
+    pub modules: BTreeMap<String, usize>,
+    /// New regions per module, for the modules it added any to.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub new_modules: BTreeMap<String, usize>,
+}
+
+impl SeedCoverage {
+    /// The coverage of a seed that covered `covered`, of which `new` was
+    /// new.
+    pub fn new(covered: &Regions, new: &Regions) -> Self {
+        SeedCoverage {
+            covered: covered.len(),
+            new: new.len(),
+            modules: covered.by_module(),
+            new_modules: new.by_module(),
+        }
+    }
+}
+
+/// A corpus's compiler coverage per module, kept in the corpus directory's
+/// [`CorpusCoverage::FILE`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CorpusCoverage {
+    /// `rustc -V` of the instrumented compiler.
+    pub rustc: String,
+    /// Instrumented regions per module.
+    pub totals: BTreeMap<String, usize>,
+    /// Regions some seed covers, per module.
+    pub covered: BTreeMap<String, usize>,
+}
+
+impl CorpusCoverage {
+    /// The summary's file name in a corpus directory.
+    pub const FILE: &'static str = "rustc-coverage.json";
+
+    /// The summary of corpus directory `dir`, if it was measured.
+    pub fn load(dir: &Path) -> io::Result<Option<Self>> {
+        match fs::read_to_string(dir.join(Self::FILE)) {
+            Ok(json) => Ok(Some(serde_json::from_str(&json)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        fs::write(dir.join(Self::FILE), json)
+    }
+
+    /// Every instrumented module with the seeds that reach it, given each
+    /// seed's recorded coverage by name; the least covered first.
+    pub fn report(&self, seeds: &[(String, SeedCoverage)]) -> Vec<ModuleReport> {
+        let mut modules: Vec<ModuleReport> = self
+            .totals
+            .iter()
+            .map(|(module, &regions)| {
+                let reaching: Vec<&(String, SeedCoverage)> = seeds
+                    .iter()
+                    .filter(|(_, seed)| seed.modules.contains_key(module))
+                    .collect();
+                let best = reaching
+                    .iter()
+                    .max_by_key(|(name, seed)| {
+                        (
+                            seed.new_modules.get(module).copied().unwrap_or(0),
+                            std::cmp::Reverse(name),
+                        )
+                    })
+                    .map(|(name, _)| name.clone());
+                ModuleReport {
+                    module: module.clone(),
+                    regions,
+                    covered: self.covered.get(module).copied().unwrap_or(0),
+                    seeds: reaching.len(),
+                    best,
+                }
+            })
+            .collect();
+        modules.sort_by(|a, b| {
+            a.share()
+                .total_cmp(&b.share())
+                .then_with(|| b.regions.cmp(&a.regions))
+                .then_with(|| a.module.cmp(&b.module))
+        });
+        modules
+    }
+}
+
+/// How much of one compiler module a corpus reaches.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ModuleReport {
+    pub module: String,
+    /// Instrumented regions.
+    pub regions: usize,
+    /// Regions some seed covers.
+    pub covered: usize,
+    /// Seeds that cover any of it.
+    pub seeds: usize,
+    /// The seed that first covered most of it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub best: Option<String>,
+}
+
+impl ModuleReport {
+    /// The share of its regions covered.
+    pub fn share(&self) -> f64 {
+        if self.regions == 0 {
+            1.0
+        } else {
+            self.covered as f64 / self.regions as f64
+        }
+    }
+}
+
+impl fmt::Display for ModuleReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:>6.1}% {:>7}/{:<7} {:>5} seeds  {}",
+            100.0 * self.share(),
+            self.covered,
+            self.regions,
+            self.seeds,
+            self.module
+        )?;
+        match &self.best {
+            Some(best) => write!(f, "  (most from {best})"),
+            None if self.covered == 0 => f.write_str("  never reached"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Compiles seeds with an instrumented rustc and reads back what they
+/// covered.
+#[derive(Debug, Clone)]
+pub struct Profiler {
+    /// The instrumented rustc; its phase decides how much of the
+    /// compiler a seed gets to run.
+    pub driver: Rustc,
+    /// Arguments before the input, which is read from stdin.
+    pub args: Vec<String>,
+    pub profdata: PathBuf,
+    pub llvm_cov: PathBuf,
+    /// The instrumented binaries: rustc and the `librustc_driver` it
+    /// loads.
+    pub objects: Vec<PathBuf>,
+}
+
+impl Profiler {
+    /// A profiler for the instrumented rustc `driver`, reading coverage
+    /// from rustc itself and the `librustc_driver` beside it in its
+    /// sysroot.
+    pub fn new(driver: Rustc) -> io::Result<Self> {
+        let objects = objects(&driver.path)?;
+        Ok(Profiler {
+            driver,
+            args: vec!["--crate-type=lib".to_owned()],
+            profdata: PathBuf::from("llvm-profdata"),
+            llvm_cov: PathBuf::from("llvm-cov"),
+            objects,
+        })
+    }
+
+    /// What compiling `source` covered, whether or not it compiled, or
+    /// `None` if rustc wrote no profile and so is not instrumented.
+    pub fn measure(&self, source: &str) -> io::Result<Option<Measurement>> {
+        let dir = Scratch::new(&self.driver.scratch_root)?;
+        let mut driver = self.driver.clone();
+        driver.env.push((
+            "LLVM_PROFILE_FILE".into(),
+            dir.path().join("rustc-%p-%m.profraw").into(),
+        ));
+        let mut args = self.args.clone();
+        args.push("-".to_owned());
+        driver.run(&args, source.as_bytes())?;
+
+        let profiles: Vec<PathBuf> = fs::read_dir(dir.path())?
+            .filter_map(|entry| Some(entry.ok()?.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "profraw"))
+            .collect();
+        if profiles.is_empty() {
+            return Ok(None);
+        }
+        let merged = dir.path().join("merged.profdata");
+        let mut merge = vec![
+            "merge".into(),
+            "-sparse".into(),
+            "-o".into(),
+            merged.clone().into_os_string(),
+        ];
+        merge.extend(profiles.into_iter().map(PathBuf::into_os_string));
+        match self.driver.run_program(&self.profdata, merge, &[])? {
+            ExecOutcome::Success(_) => {}
+            outcome => {
+                return Err(io::Error::other(format!(
+                    "{} merge: {outcome}",
+                    self.profdata.display()
+                )))
+            }
+        }
+        self.export(&merged).map(Some)
+    }
+
+    /// Reads the regions `profile` covers with `llvm-cov export`. Its
+    /// output for a whole compiler runs to hundreds of megabytes, so it is
+    /// parsed as it streams instead of captured.
+    fn export(&self, profile: &Path) -> io::Result<Measurement> {
+        let (first, rest) = self
+            .objects
+            .split_first()
+            .ok_or_else(|| io::Error::other("no instrumented objects to read coverage from"))?;
+        let mut command = Command::new(&self.llvm_cov);
+        command
+            .args([
+                "export",
+                "-format=text",
+                "-skip-expansions",
+                "-instr-profile",
+            ])
+            .arg(profile)
+            .arg(first);
+        for object in rest {
+            command.arg("-object").arg(object);
+        }
+        let mut child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let export: Result<Export, _> = serde_json::from_reader(BufReader::new(stdout));
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "{} export: {status}",
+                self.llvm_cov.display()
+            )));
+        }
+        let mut measurement = Measurement::default();
+        for file in export?.data.into_iter().flat_map(|data| data.files) {
+            let name = relative(&file.filename).to_owned();
+            *measurement.totals.entry(module(&name)).or_default() += file.summary.regions.count;
+            let covered: BTreeSet<(u32, u32)> = file
+                .segments
+                .iter()
+                .filter_map(|segment| covered_entry(segment))
+                .collect();
+            if !covered.is_empty() {
+                measurement
+                    .covered
+                    .files
+                    .entry(name)
+                    .or_default()
+                    .extend(covered);
+            }
+        }
+        Ok(measurement)
+    }
+}
+
+/// The start of the region `segment` enters, if it is a covered region
+/// entry. Segments are `[line, column, count, has count, is region entry]`,
+/// followed by whether it is a gap region in newer LLVMs.
+fn covered_entry(segment: &[serde_json::Value]) -> Option<(u32, u32)> {
+    let flag = |i: usize| segment.get(i).and_then(serde_json::Value::as_bool);
+    let count = segment.get(2)?.as_u64()?;
+    if count == 0 || flag(3) != Some(true) || flag(4) != Some(true) || flag(5) == Some(true) {
+        return None;
+    }
+    let line = u32::try_from(segment.first()?.as_u64()?).ok()?;
+    let column = u32::try_from(segment.get(1)?.as_u64()?).ok()?;
+    Some((line, column))
+}
+
+/// `rustc` and the `librustc_driver-*.so` in the `lib` directory of its
+/// sysroot, where the compiler's code lives.
+pub fn objects(rustc: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut objects = vec![rustc.to_path_buf()];
+    let Some(lib) = rustc
+        .parent()
+        .and_then(Path::parent)
+        .map(|root| root.join("lib"))
+    else {
+        return Ok(objects);
+    };
+    let Ok(entries) = fs::read_dir(&lib) else {
+        return Ok(objects);
+    };
+    for entry in entries {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if name.starts_with("librustc_driver-") && name.ends_with(".so") {
+            objects.push(path);
+        }
+    }
+    Ok(objects)
+}
+
+#[derive(Deserialize)]
+struct Export {
+    data: Vec<ExportData>,
+}
+
+#[derive(Deserialize)]
+struct ExportData {
+    files: Vec<ExportFile>,
+}
+
+#[derive(Deserialize)]
+struct ExportFile {
+    filename: String,
+    #[serde(default)]
+    segments: Vec<Vec<serde_json::Value>>,
+    summary: FileSummary,
+}
+
+#[derive(Deserialize)]
+struct FileSummary {
+    regions: Counts,
+}
+
+#[derive(Deserialize)]
+struct Counts {
+    count: usize,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn regions(files: &[(&str, &[(u32, u32)])]) -> Regions {
+        Regions {
+            files: files
+                .iter()
+                .map(|(file, regions)| (file.to_string(), regions.iter().copied().collect()))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn files_belong_to_crates() {
+        assert_eq!(
+            module("compiler/rustc_hir_typeck/src/lib.rs"),
+            "compiler/rustc_hir_typeck"
+        );
+        assert_eq!(module("library/core/src/fmt/mod.rs"), "library/core");
+        assert_eq!(module("src/tools/x.rs"), "src/tools/x.rs");
+        assert_eq!(
+            relative("/checkout/compiler/rustc_middle/src/ty/mod.rs"),
+            "compiler/rustc_middle/src/ty/mod.rs"
+        );
+        assert_eq!(
+            relative("/rustc/library/std/src/rt.rs"),
+            "library/std/src/rt.rs"
+        );
+        assert_eq!(relative("/elsewhere/x.rs"), "/elsewhere/x.rs");
+    }
+
+    #[test]
+    fn regions_count_only_what_is_new() {
+        let a = "compiler/rustc_parse/src/lib.rs";
+        let b = "compiler/rustc_parse/src/lexer.rs";
+        let c = "library/core/src/lib.rs";
+        let mut seen = regions(&[(a, &[(1, 1), (2, 1)])]);
+        let now = regions(&[(a, &[(2, 1), (3, 1)]), (b, &[(1, 1)]), (c, &[(9, 9)])]);
+        let new = now.difference(&seen);
+        assert_eq!(
+            new,
+            regions(&[(a, &[(3, 1)]), (b, &[(1, 1)]), (c, &[(9, 9)])])
+        );
+        assert!(seen.difference(&seen).is_empty());
+        seen.extend(&now);
+        assert_eq!(seen.len(), 5);
+        assert_eq!(
+            seen.by_module(),
+            BTreeMap::from([
+                ("compiler/rustc_parse".to_owned(), 4),
+                ("library/core".to_owned(), 1)
+            ])
+        );
+        let coverage = SeedCoverage::new(&now, &new);
+        assert_eq!((coverage.covered, coverage.new), (4, 3));
+        assert_eq!(coverage.new_modules["compiler/rustc_parse"], 2);
+    }
+
+    #[test]
+    fn only_covered_region_entries_count() {
+        let entry = |segment: serde_json::Value| {
+            covered_entry(segment.as_array().expect("an array"))
+                .map(|(line, column)| [line, column])
+        };
+        assert_eq!(entry(json!([3, 5, 2, true, true, false])), Some([3, 5]));
+        assert_eq!(entry(json!([3, 5, 2, true, true])), Some([3, 5]));
+        assert_eq!(entry(json!([3, 5, 0, true, true, false])), None);
+        assert_eq!(entry(json!([3, 5, 2, true, false, false])), None);
+        assert_eq!(entry(json!([3, 5, 2, true, true, true])), None);
+    }
+
+    #[test]
+    fn reports_put_the_least_covered_first() {
+        let coverage = CorpusCoverage {
+            rustc: "rustc 1.80.0".to_owned(),
+            totals: BTreeMap::from([
+                ("compiler/rustc_parse".to_owned(), 10),
+                ("compiler/rustc_borrowck".to_owned(), 20),
+                ("compiler/rustc_lint".to_owned(), 5),
+            ]),
+            covered: BTreeMap::from([
+                ("compiler/rustc_parse".to_owned(), 9),
+                ("compiler/rustc_borrowck".to_owned(), 5),
+            ]),
+        };
+        let seed = |module: &str, new: usize| SeedCoverage {
+            modules: BTreeMap::from([(module.to_owned(), new)]),
+            new_modules: BTreeMap::from([(module.to_owned(), new)]),
+            ..SeedCoverage::default()
+        };
+        let seeds = [
+            ("a.rs".to_owned(), seed("compiler/rustc_parse", 4)),
+            ("b.rs".to_owned(), seed("compiler/rustc_parse", 5)),
+            ("c.rs".to_owned(), seed("compiler/rustc_borrowck", 5)),
+        ];
+        let report = coverage.report(&seeds);
+        let modules: Vec<&str> = report.iter().map(|m| m.module.as_str()).collect();
+        assert_eq!(
+            modules,
+            [
+                "compiler/rustc_lint",
+                "compiler/rustc_borrowck",
+                "compiler/rustc_parse"
+            ]
+        );
+        assert_eq!(report[2].seeds, 2);
+        assert_eq!(report[2].best.as_deref(), Some("b.rs"));
+        assert!(report[0]
+            .to_string()
+            .ends_with("compiler/rustc_lint  never reached"));
+        assert_eq!(
+            report[1].to_string(),
+            "  25.0%       5/20          1 seeds  compiler/rustc_borrowck  (most from c.rs)"
+        );
+
+        let dir = std::env::temp_dir().join(format!(
+            "rustc-fuzz-compiler-coverage-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("creates the corpus");
+        assert_eq!(CorpusCoverage::load(&dir).expect("reads"), None);
+        coverage.save(&dir).expect("writes");
+        assert_eq!(CorpusCoverage::load(&dir).expect("reads"), Some(coverage));
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+
+    #[test]
+    fn measures_what_a_compile_covered() {
+        let dir = std::env::temp_dir().join(format!(
+            "rustc-fuzz-compiler-profiler-{}",
+            std::process::id()
+        ));
+        let bin = dir.join("bin");
+        let lib = dir.join("lib");
+        fs::create_dir_all(&bin).expect("creates the sysroot");
+        fs::create_dir_all(&lib).expect("creates the sysroot");
+        fs::write(lib.join("librustc_driver-0123.so"), "").expect("writes the driver");
+        fs::write(lib.join("libstd-0123.so"), "").expect("writes std");
+        let script = |path: &Path, text: &str| {
+            fs::write(path, text).expect("writes the script");
+            fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+                .expect("makes it runnable");
+        };
+        // Writes a profile, unless the seed mentions `plain`.
+        let rustc = bin.join("rustc");
+        script(
+            &rustc,
+            "#!/bin/sh\ngrep -q plain && exit 0\n\
+             echo profile > \"$(echo \"$LLVM_PROFILE_FILE\" | sed 's/%p/1/; s/%m/2/')\"\n",
+        );
+        let profdata = dir.join("llvm-profdata");
+        script(&profdata, "#!/bin/sh\necho merged > \"$4\"\n");
+        let export = json!({"data": [{"files": [
+            {
+                "filename": "/checkout/compiler/rustc_parse/src/lib.rs",
+                "segments": [[1, 1, 3, true, true, false], [2, 1, 0, true, true, false]],
+                "summary": {"regions": {"count": 2}}
+            },
+            {
+                "filename": "/checkout/library/core/src/lib.rs",
+                "summary": {"regions": {"count": 4}}
+            }
+        ]}]});
+        let llvm_cov = dir.join("llvm-cov");
+        script(&llvm_cov, &format!("#!/bin/sh\necho '{export}'\n"));
+
+        let profiler = Profiler {
+            profdata,
+            llvm_cov,
+            ..Profiler::new(Rustc::new(&rustc)).expect("finds the objects")
+        };
+        assert_eq!(
+            profiler.objects,
+            [rustc.clone(), lib.join("librustc_driver-0123.so")]
+        );
+        let measurement = profiler
+            .measure("fn main() {}")
+            .expect("measures")
+            .expect("is instrumented");
+        assert_eq!(
+            measurement.covered,
+            regions(&[("compiler/rustc_parse/src/lib.rs", &[(1, 1)])])
+        );
+        assert_eq!(
+            measurement.totals,
+            BTreeMap::from([
+                ("compiler/rustc_parse".to_owned(), 2),
+                ("library/core".to_owned(), 4)
+            ])
+        );
+        assert_eq!(profiler.measure("// plain").expect("measures"), None);
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
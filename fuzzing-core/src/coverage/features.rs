@@ -0,0 +1,676 @@
+//! Language-feature coverage.
+//!
+//! [`classify`] walks a seed's syntax tree and reports which language
+//! [`Feature`]s it uses; a [`Matrix`] tallies them over a corpus, so it is
+//! plain which parts of the language no seed exercises and where
+//! generation and mutation effort should go next. Features are syntactic:
+//! a seed that names a generic parameter `N` uses const generics only if
+//! it declares it `const N`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use syn::visit::{self, Visit};
+
+/// A language feature a seed can use.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Feature {
+    // Items.
+    Struct,
+    TupleStruct,
+    Enum,
+    Union,
+    Module,
+    ExternBlock,
+    Static,
+    StaticMut,
+    ConstItem,
+    TypeAlias,
+    MacroRules,
+    // Traits.
+    Trait,
+    TraitImpl,
+    InherentImpl,
+    DefaultMethod,
+    Supertrait,
+    AssocType,
+    AssocConst,
+    GenericAssocType,
+    DynTrait,
+    ImplTraitArg,
+    ImplTraitReturn,
+    HigherRankedBound,
+    // Generics.
+    TypeGenerics,
+    LifetimeGenerics,
+    ConstGenerics,
+    ConstGenericExpr,
+    WhereClause,
+    // Functions and closures.
+    ConstFn,
+    Closure,
+    MoveClosure,
+    // unsafe.
+    UnsafeBlock,
+    UnsafeFn,
+    UnsafeTrait,
+    RawPointer,
+    // async.
+    AsyncFn,
+    AsyncBlock,
+    Await,
+    // Control flow.
+    Match,
+    IfLet,
+    LetElse,
+    LetChain,
+    Label,
+    BreakValue,
+    Try,
+    ConstBlock,
+    // Patterns.
+    OrPattern,
+    SlicePattern,
+    RangePattern,
+    AtBinding,
+    // Types.
+    FnPointer,
+    Never,
+    Slice,
+    Array,
+    Tuple,
+    // Attributes and macros.
+    Derive,
+    Repr,
+    MacroCall,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 58] = [
+        Feature::Struct,
+        Feature::TupleStruct,
+        Feature::Enum,
+        Feature::Union,
+        Feature::Module,
+        Feature::ExternBlock,
+        Feature::Static,
+        Feature::StaticMut,
+        Feature::ConstItem,
+        Feature::TypeAlias,
+        Feature::MacroRules,
+        Feature::Trait,
+        Feature::TraitImpl,
+        Feature::InherentImpl,
+        Feature::DefaultMethod,
+        Feature::Supertrait,
+        Feature::AssocType,
+        Feature::AssocConst,
+        Feature::GenericAssocType,
+        Feature::DynTrait,
+        Feature::ImplTraitArg,
+        Feature::ImplTraitReturn,
+        Feature::HigherRankedBound,
+        Feature::TypeGenerics,
+        Feature::LifetimeGenerics,
+        Feature::ConstGenerics,
+        Feature::ConstGenericExpr,
+        Feature::WhereClause,
+        Feature::ConstFn,
+        Feature::Closure,
+        Feature::MoveClosure,
+        Feature::UnsafeBlock,
+        Feature::UnsafeFn,
+        Feature::UnsafeTrait,
+        Feature::RawPointer,
+        Feature::AsyncFn,
+        Feature::AsyncBlock,
+        Feature::Await,
+        Feature::Match,
+        Feature::IfLet,
+        Feature::LetElse,
+        Feature::LetChain,
+        Feature::Label,
+        Feature::BreakValue,
+        Feature::Try,
+        Feature::ConstBlock,
+        Feature::OrPattern,
+        Feature::SlicePattern,
+        Feature::RangePattern,
+        Feature::AtBinding,
+        Feature::FnPointer,
+        Feature::Never,
+        Feature::Slice,
+        Feature::Array,
+        Feature::Tuple,
+        Feature::Derive,
+        Feature::Repr,
+        Feature::MacroCall,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::Struct => "struct",
+            Feature::TupleStruct => "tuple struct",
+            Feature::Enum => "enum",
+            Feature::Union => "union",
+            Feature::Module => "mod",
+            Feature::ExternBlock => "extern block",
+            Feature::Static => "static",
+            Feature::StaticMut => "static mut",
+            Feature::ConstItem => "const item",
+            Feature::TypeAlias => "type alias",
+            Feature::MacroRules => "macro_rules!",
+            Feature::Trait => "trait",
+            Feature::TraitImpl => "trait impl",
+            Feature::InherentImpl => "inherent impl",
+            Feature::DefaultMethod => "default method",
+            Feature::Supertrait => "supertrait",
+            Feature::AssocType => "associated type",
+            Feature::AssocConst => "associated const",
+            Feature::GenericAssocType => "generic associated type",
+            Feature::DynTrait => "dyn Trait",
+            Feature::ImplTraitArg => "impl Trait argument",
+            Feature::ImplTraitReturn => "impl Trait return",
+            Feature::HigherRankedBound => "for<'a> bound",
+            Feature::TypeGenerics => "type generics",
+            Feature::LifetimeGenerics => "lifetime generics",
+            Feature::ConstGenerics => "const generics",
+            Feature::ConstGenericExpr => "const generic expression",
+            Feature::WhereClause => "where clause",
+            Feature::ConstFn => "const fn",
+            Feature::Closure => "closure",
+            Feature::MoveClosure => "move closure",
+            Feature::UnsafeBlock => "unsafe block",
+            Feature::UnsafeFn => "unsafe fn",
+            Feature::UnsafeTrait => "unsafe trait",
+            Feature::RawPointer => "raw pointer",
+            Feature::AsyncFn => "async fn",
+            Feature::AsyncBlock => "async block",
+            Feature::Await => ".await",
+            Feature::Match => "match",
+            Feature::IfLet => "if let",
+            Feature::LetElse => "let else",
+            Feature::LetChain => "let chain",
+            Feature::Label => "label",
+            Feature::BreakValue => "break with value",
+            Feature::Try => "? operator",
+            Feature::ConstBlock => "const block",
+            Feature::OrPattern => "or pattern",
+            Feature::SlicePattern => "slice pattern",
+            Feature::RangePattern => "range pattern",
+            Feature::AtBinding => "@ binding",
+            Feature::FnPointer => "fn pointer",
+            Feature::Never => "never type",
+            Feature::Slice => "slice type",
+            Feature::Array => "array type",
+            Feature::Tuple => "tuple type",
+            Feature::Derive => "derive",
+            Feature::Repr => "repr",
+            Feature::MacroCall => "macro call",
+        }
+    }
+
+    /// A stable snake_case identifier, as stored in seed metadata.
+    pub fn tag(self) -> &'static str {
+        match self {
+            Feature::Struct => "struct",
+            Feature::TupleStruct => "tuple_struct",
+            Feature::Enum => "enum",
+            Feature::Union => "union",
+            Feature::Module => "mod",
+            Feature::ExternBlock => "extern_block",
+            Feature::Static => "static",
+            Feature::StaticMut => "static_mut",
+            Feature::ConstItem => "const_item",
+            Feature::TypeAlias => "type_alias",
+            Feature::MacroRules => "macro_def",
+            Feature::Trait => "trait",
+            Feature::TraitImpl => "trait_impl",
+            Feature::InherentImpl => "inherent_impl",
+            Feature::DefaultMethod => "default_method",
+            Feature::Supertrait => "supertrait",
+            Feature::AssocType => "assoc_type",
+            Feature::AssocConst => "assoc_const",
+            Feature::GenericAssocType => "gat",
+            Feature::DynTrait => "dyn_trait",
+            Feature::ImplTraitArg => "impl_trait_arg",
+            Feature::ImplTraitReturn => "impl_trait_return",
+            Feature::HigherRankedBound => "hrtb",
+            Feature::TypeGenerics => "type_generics",
+            Feature::LifetimeGenerics => "lifetime_generics",
+            Feature::ConstGenerics => "const_generics",
+            Feature::ConstGenericExpr => "const_generic_expr",
+            Feature::WhereClause => "where_clause",
+            Feature::ConstFn => "const_fn",
+            Feature::Closure => "closure",
+            Feature::MoveClosure => "move_closure",
+            Feature::UnsafeBlock => "unsafe_block",
+            Feature::UnsafeFn => "unsafe_fn",
+            Feature::UnsafeTrait => "unsafe_trait",
+            Feature::RawPointer => "raw_pointer",
+            Feature::AsyncFn => "async_fn",
+            Feature::AsyncBlock => "async_block",
+            Feature::Await => "await",
+            Feature::Match => "match",
+            Feature::IfLet => "if_let",
+            Feature::LetElse => "let_else",
+            Feature::LetChain => "let_chain",
+            Feature::Label => "label",
+            Feature::BreakValue => "break_value",
+            Feature::Try => "try",
+            Feature::ConstBlock => "const_block",
+            Feature::OrPattern => "or_pattern",
+            Feature::SlicePattern => "slice_pattern",
+            Feature::RangePattern => "range_pattern",
+            Feature::AtBinding => "at_binding",
+            Feature::FnPointer => "fn_pointer",
+            Feature::Never => "never",
+            Feature::Slice => "slice",
+            Feature::Array => "array",
+            Feature::Tuple => "tuple",
+            Feature::Derive => "derive",
+            Feature::Repr => "repr",
+            Feature::MacroCall => "macro_call",
+        }
+    }
+
+    /// The feature whose [`Feature::tag`] is `tag`.
+    pub fn from_tag(tag: &str) -> Option<Feature> {
+        Feature::ALL
+            .into_iter()
+            .find(|feature| feature.tag() == tag)
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The features `source` uses, or `None` if it does not parse.
+pub fn classify(source: &str) -> Option<BTreeSet<Feature>> {
+    let file = syn::parse_file(source).ok()?;
+    let mut features = Features::default();
+    features.visit_file(&file);
+    Some(features.0)
+}
+
+/// How many seeds of a corpus use each feature.
+#[derive(Debug, Clone, Default)]
+pub struct Matrix {
+    /// Seeds added, including ones that do not parse.
+    pub seeds: usize,
+    /// Seeds that do not parse, and so count towards no feature.
+    pub unparsed: usize,
+    pub counts: BTreeMap<Feature, usize>,
+}
+
+impl Matrix {
+    pub fn new() -> Self {
+        Matrix::default()
+    }
+
+    /// Counts the features of one seed.
+    pub fn add(&mut self, source: &str) {
+        self.add_features(classify(source));
+    }
+
+    /// Counts one seed with already classified `features`, `None` if it
+    /// does not parse.
+    pub fn add_features(&mut self, features: Option<BTreeSet<Feature>>) {
+        self.seeds += 1;
+        match features {
+            Some(features) => {
+                for feature in features {
+                    *self.counts.entry(feature).or_default() += 1;
+                }
+            }
+            None => self.unparsed += 1,
+        }
+    }
+
+    /// How many seeds use `feature`.
+    pub fn count(&self, feature: Feature) -> usize {
+        self.counts.get(&feature).copied().unwrap_or(0)
+    }
+
+    /// The features no seed uses, in declaration order.
+    pub fn missing(&self) -> Vec<Feature> {
+        Feature::ALL
+            .into_iter()
+            .filter(|&feature| self.count(feature) == 0)
+            .collect()
+    }
+
+    /// The features used by fewer than `threshold` seeds, rarest first:
+    /// where generation should aim.
+    pub fn rarest(&self, threshold: usize) -> Vec<Feature> {
+        let mut rare: Vec<Feature> = Feature::ALL
+            .into_iter()
+            .filter(|&feature| self.count(feature) < threshold)
+            .collect();
+        rare.sort_by_key(|&feature| self.count(feature));
+        rare
+    }
+}
+
+impl fmt::Display for Matrix {
+    /// One line per feature with the number and share of seeds using it,
+    /// then the missing features.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parsed = self.seeds - self.unparsed;
+        writeln!(f, "{parsed} seeds parsed, {} did not", self.unparsed)?;
+        for feature in Feature::ALL {
+            let count = self.count(feature);
+            let share = if parsed == 0 {
+                0.0
+            } else {
+                100.0 * count as f64 / parsed as f64
+            };
+            writeln!(f, "{:>6} {share:>5.1}%  {feature}", count)?;
+        }
+        let missing = self.missing();
+        if !missing.is_empty() {
+            let names: Vec<&str> = missing.iter().map(|feature| feature.name()).collect();
+            writeln!(f, "missing: {}", names.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Features(BTreeSet<Feature>);
+
+impl Features {
+    fn add(&mut self, feature: Feature) {
+        self.0.insert(feature);
+    }
+
+    fn attributes(&mut self, attrs: &[syn::Attribute]) {
+        for attr in attrs {
+            if attr.path().is_ident("derive") {
+                self.add(Feature::Derive);
+            } else if attr.path().is_ident("repr") {
+                self.add(Feature::Repr);
+            }
+        }
+    }
+
+    /// Whether `ty` is or contains an `impl Trait`.
+    fn has_impl_trait(ty: &syn::Type) -> bool {
+        struct Finder(bool);
+        impl<'ast> Visit<'ast> for Finder {
+            fn visit_type_impl_trait(&mut self, _: &'ast syn::TypeImplTrait) {
+                self.0 = true;
+            }
+        }
+        let mut finder = Finder(false);
+        finder.visit_type(ty);
+        finder.0
+    }
+
+    fn signature(&mut self, sig: &syn::Signature) {
+        if sig.constness.is_some() {
+            self.add(Feature::ConstFn);
+        }
+        if sig.asyncness.is_some() {
+            self.add(Feature::AsyncFn);
+        }
+        if sig.unsafety.is_some() {
+            self.add(Feature::UnsafeFn);
+        }
+        let arg_impl = sig.inputs.iter().any(|arg| match arg {
+            syn::FnArg::Typed(arg) => Self::has_impl_trait(&arg.ty),
+            syn::FnArg::Receiver(_) => false,
+        });
+        if arg_impl {
+            self.add(Feature::ImplTraitArg);
+        }
+        if let syn::ReturnType::Type(_, ty) = &sig.output {
+            if Self::has_impl_trait(ty) {
+                self.add(Feature::ImplTraitReturn);
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Features {
+    fn visit_item(&mut self, item: &'ast syn::Item) {
+        match item {
+            syn::Item::Struct(item) => {
+                self.attributes(&item.attrs);
+                self.add(match item.fields {
+                    syn::Fields::Unnamed(_) => Feature::TupleStruct,
+                    _ => Feature::Struct,
+                });
+            }
+            syn::Item::Enum(item) => {
+                self.attributes(&item.attrs);
+                self.add(Feature::Enum);
+            }
+            syn::Item::Union(item) => {
+                self.attributes(&item.attrs);
+                self.add(Feature::Union);
+            }
+            syn::Item::Mod(_) => self.add(Feature::Module),
+            syn::Item::ForeignMod(_) => self.add(Feature::ExternBlock),
+            syn::Item::Static(item) => self.add(match item.mutability {
+                syn::StaticMutability::Mut(_) => Feature::StaticMut,
+                _ => Feature::Static,
+            }),
+            syn::Item::Const(_) => self.add(Feature::ConstItem),
+            syn::Item::Type(_) => self.add(Feature::TypeAlias),
+            syn::Item::Macro(item) if item.mac.path.is_ident("macro_rules") => {
+                self.add(Feature::MacroRules)
+            }
+            syn::Item::Trait(item) => {
+                self.add(Feature::Trait);
+                if item.unsafety.is_some() {
+                    self.add(Feature::UnsafeTrait);
+                }
+                if !item.supertraits.is_empty() {
+                    self.add(Feature::Supertrait);
+                }
+            }
+            syn::Item::Impl(item) => {
+                if item.trait_.is_some() {
+                    self.add(Feature::TraitImpl);
+                } else {
+                    self.add(Feature::InherentImpl);
+                }
+                if item.unsafety.is_some() {
+                    self.add(Feature::UnsafeTrait);
+                }
+            }
+            _ => {}
+        }
+        visit::visit_item(self, item);
+    }
+
+    fn visit_trait_item(&mut self, item: &'ast syn::TraitItem) {
+        match item {
+            syn::TraitItem::Fn(item) if item.default.is_some() => self.add(Feature::DefaultMethod),
+            syn::TraitItem::Type(item) => {
+                self.add(Feature::AssocType);
+                if !item.generics.params.is_empty() {
+                    self.add(Feature::GenericAssocType);
+                }
+            }
+            syn::TraitItem::Const(_) => self.add(Feature::AssocConst),
+            _ => {}
+        }
+        visit::visit_trait_item(self, item);
+    }
+
+    fn visit_signature(&mut self, sig: &'ast syn::Signature) {
+        self.signature(sig);
+        visit::visit_signature(self, sig);
+    }
+
+    fn visit_generic_param(&mut self, param: &'ast syn::GenericParam) {
+        self.add(match param {
+            syn::GenericParam::Type(_) => Feature::TypeGenerics,
+            syn::GenericParam::Lifetime(_) => Feature::LifetimeGenerics,
+            syn::GenericParam::Const(_) => Feature::ConstGenerics,
+        });
+        visit::visit_generic_param(self, param);
+    }
+
+    fn visit_generic_argument(&mut self, arg: &'ast syn::GenericArgument) {
+        if let syn::GenericArgument::Const(syn::Expr::Block(_)) = arg {
+            self.add(Feature::ConstGenericExpr);
+        }
+        visit::visit_generic_argument(self, arg);
+    }
+
+    fn visit_where_clause(&mut self, clause: &'ast syn::WhereClause) {
+        self.add(Feature::WhereClause);
+        visit::visit_where_clause(self, clause);
+    }
+
+    fn visit_bound_lifetimes(&mut self, bound: &'ast syn::BoundLifetimes) {
+        self.add(Feature::HigherRankedBound);
+        visit::visit_bound_lifetimes(self, bound);
+    }
+
+    fn visit_expr(&mut self, expr: &'ast syn::Expr) {
+        match expr {
+            syn::Expr::Closure(closure) => {
+                self.add(Feature::Closure);
+                if closure.capture.is_some() {
+                    self.add(Feature::MoveClosure);
+                }
+            }
+            syn::Expr::Unsafe(_) => self.add(Feature::UnsafeBlock),
+            syn::Expr::Async(_) => self.add(Feature::AsyncBlock),
+            syn::Expr::Await(_) => self.add(Feature::Await),
+            syn::Expr::Match(_) => self.add(Feature::Match),
+            syn::Expr::If(expr) => match &*expr.cond {
+                syn::Expr::Let(_) => self.add(Feature::IfLet),
+                cond if has_let(cond) => self.add(Feature::LetChain),
+                _ => {}
+            },
+            syn::Expr::While(expr)
+                if has_let(&expr.cond) && !matches!(&*expr.cond, syn::Expr::Let(_)) =>
+            {
+                self.add(Feature::LetChain)
+            }
+            syn::Expr::Break(expr) if expr.expr.is_some() => self.add(Feature::BreakValue),
+            syn::Expr::Try(_) => self.add(Feature::Try),
+            syn::Expr::Const(_) => self.add(Feature::ConstBlock),
+            syn::Expr::Macro(_) => self.add(Feature::MacroCall),
+            _ => {}
+        }
+        visit::visit_expr(self, expr);
+    }
+
+    fn visit_label(&mut self, label: &'ast syn::Label) {
+        self.add(Feature::Label);
+        visit::visit_label(self, label);
+    }
+
+    fn visit_local(&mut self, local: &'ast syn::Local) {
+        if local
+            .init
+            .as_ref()
+            .is_some_and(|init| init.diverge.is_some())
+        {
+            self.add(Feature::LetElse);
+        }
+        visit::visit_local(self, local);
+    }
+
+    fn visit_stmt_macro(&mut self, mac: &'ast syn::StmtMacro) {
+        self.add(Feature::MacroCall);
+        visit::visit_stmt_macro(self, mac);
+    }
+
+    fn visit_pat(&mut self, pat: &'ast syn::Pat) {
+        match pat {
+            syn::Pat::Or(_) => self.add(Feature::OrPattern),
+            syn::Pat::Slice(_) => self.add(Feature::SlicePattern),
+            syn::Pat::Range(_) => self.add(Feature::RangePattern),
+            syn::Pat::Ident(pat) if pat.subpat.is_some() => self.add(Feature::AtBinding),
+            _ => {}
+        }
+        visit::visit_pat(self, pat);
+    }
+
+    fn visit_type(&mut self, ty: &'ast syn::Type) {
+        match ty {
+            syn::Type::TraitObject(_) => self.add(Feature::DynTrait),
+            syn::Type::Ptr(_) => self.add(Feature::RawPointer),
+            syn::Type::BareFn(_) => self.add(Feature::FnPointer),
+            syn::Type::Never(_) => self.add(Feature::Never),
+            syn::Type::Slice(_) => self.add(Feature::Slice),
+            syn::Type::Array(_) => self.add(Feature::Array),
+            syn::Type::Tuple(tuple) if !tuple.elems.is_empty() => self.add(Feature::Tuple),
+            _ => {}
+        }
+        visit::visit_type(self, ty);
+    }
+}
+
+/// Whether `cond` contains a `let`, through `&&` chains.
+fn has_let(cond: &syn::Expr) -> bool {
+    match cond {
+        syn::Expr::Let(_) => true,
+        syn::Expr::Binary(binary) if matches!(binary.op, syn::BinOp::And(_)) => {
+            has_let(&binary.left) || has_let(&binary.right)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn features_are_syntactic() {
+        let source = "struct S<const N: usize>([u8; N]);\n\
+                      fn f<T>(x: Option<T>) -> impl Fn() {\n    \
+                          let Some(_) = x else { return move || {} };\n    \
+                          if let Some(_) = None::<u8> {}\n    \
+                          move || {}\n\
+                      }\n";
+        let features = classify(source).expect("parses");
+        for feature in [
+            Feature::TupleStruct,
+            Feature::ConstGenerics,
+            Feature::TypeGenerics,
+            Feature::Array,
+            Feature::ImplTraitReturn,
+            Feature::LetElse,
+            Feature::IfLet,
+            Feature::Closure,
+            Feature::MoveClosure,
+        ] {
+            assert!(features.contains(&feature), "{feature} in {features:?}");
+        }
+        assert!(!features.contains(&Feature::Struct), "{features:?}");
+        assert!(!features.contains(&Feature::LetChain), "{features:?}");
+        assert_eq!(classify("fn ("), None);
+    }
+
+    #[test]
+    fn the_matrix_points_at_what_no_seed_uses() {
+        let mut matrix = Matrix::new();
+        matrix.add("enum E { A }");
+        matrix.add("enum F { B }\nunion U { x: u8 }");
+        matrix.add("not rust");
+        assert_eq!((matrix.seeds, matrix.unparsed), (3, 1));
+        assert_eq!(matrix.count(Feature::Enum), 2);
+        assert_eq!(matrix.count(Feature::Union), 1);
+        let missing = matrix.missing();
+        assert!(missing.contains(&Feature::Struct), "{missing:?}");
+        assert!(!missing.contains(&Feature::Enum), "{missing:?}");
+        let rarest = matrix.rarest(2);
+        assert!(!rarest.contains(&Feature::Enum), "{rarest:?}");
+        assert_eq!(rarest.last(), Some(&Feature::Union));
+        assert!(matrix
+            .to_string()
+            .starts_with("2 seeds parsed, 1 did not\n"));
+    }
+}
@@ -0,0 +1,10 @@
+//! What a corpus covers: of the language, in [`features`] and
+//! [`productions`], and of the compiler, in [`compiler`].
+
+pub mod compiler;
+pub mod features;
+pub mod productions;
+pub mod tagger;
+
+pub use compiler::{CorpusCoverage, Profiler, Regions, SeedCoverage};
+pub use features::{classify, Feature, Matrix};
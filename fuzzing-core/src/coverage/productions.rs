@@ -0,0 +1,266 @@
+//! Generator-production coverage.
+//!
+//! [`ExprGen`](crate::gen::ExprGen) builds expressions and statements from
+//! a fixed set of productions: a wrapping method call, a cast, an `if`
+//! with an `else`, a `let` and so on. [`productions`] recognizes them in
+//! any seed's syntax, whoever wrote it, and [`Counts`] tallies how many
+//! seeds of a corpus use each. The generator's [`Weights`] are derived
+//! from the counts, so what the corpus rarely exercises is generated
+//! more often.
+//!
+//! Recognition is syntactic and ignores types: `a ^ b` counts as a
+//! bitwise operation whether it is on integers or booleans.
+//!
+//! [`Weights`]: crate::gen::Weights
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use syn::visit::{self, Visit};
+
+/// Every production [`productions`] recognizes, which are the ones the
+/// generator chooses between.
+pub const PRODUCTIONS: [&str; 31] = [
+    "wrapping_op",
+    "bit_op",
+    "cast",
+    "count_ones",
+    "rotate",
+    "len",
+    "arith",
+    "float_method",
+    "not",
+    "logic",
+    "compare",
+    "is_some",
+    "is_empty",
+    "char_from",
+    "string_from",
+    "format",
+    "to_uppercase",
+    "to_string",
+    "drop",
+    "unit",
+    "none",
+    "or",
+    "some",
+    "if",
+    "match",
+    "block",
+    "index",
+    "let",
+    "let_underscore",
+    "expr_stmt",
+    "for",
+];
+
+/// The productions `source` uses, or `None` if it does not parse.
+pub fn productions(source: &str) -> Option<BTreeSet<&'static str>> {
+    let file = syn::parse_file(source).ok()?;
+    let mut found = Found::default();
+    found.visit_file(&file);
+    Some(found.0)
+}
+
+/// How many seeds of a corpus use each production.
+#[derive(Debug, Clone, Default)]
+pub struct Counts {
+    /// Seeds added, including ones that do not parse.
+    pub seeds: usize,
+    /// Seeds that do not parse, and so count towards no production.
+    pub unparsed: usize,
+    pub counts: BTreeMap<&'static str, usize>,
+}
+
+impl Counts {
+    pub fn new() -> Self {
+        Counts::default()
+    }
+
+    /// Counts the productions of one seed.
+    pub fn add(&mut self, source: &str) {
+        self.seeds += 1;
+        match productions(source) {
+            Some(productions) => {
+                for production in productions {
+                    *self.counts.entry(production).or_default() += 1;
+                }
+            }
+            None => self.unparsed += 1,
+        }
+    }
+
+    /// How many seeds use `production`.
+    pub fn count(&self, production: &str) -> usize {
+        self.counts.get(production).copied().unwrap_or(0)
+    }
+
+    /// Seeds that parsed.
+    pub fn parsed(&self) -> usize {
+        self.seeds - self.unparsed
+    }
+}
+
+impl fmt::Display for Counts {
+    /// One line per production with the number and share of seeds using
+    /// it, rarest first.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parsed = self.parsed();
+        writeln!(f, "{parsed} seeds parsed, {} did not", self.unparsed)?;
+        let mut productions = PRODUCTIONS;
+        productions.sort_by_key(|production| self.count(production));
+        for production in productions {
+            let count = self.count(production);
+            let share = if parsed == 0 {
+                0.0
+            } else {
+                100.0 * count as f64 / parsed as f64
+            };
+            writeln!(f, "{count:>6} {share:>5.1}%  {production}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+struct Found(BTreeSet<&'static str>);
+
+impl<'ast> Visit<'ast> for Found {
+    fn visit_expr(&mut self, expr: &'ast syn::Expr) {
+        let production = match expr {
+            syn::Expr::MethodCall(call) => match call.method.to_string().as_str() {
+                "wrapping_add" | "wrapping_sub" | "wrapping_mul" | "min" | "max" | "abs_diff" => {
+                    Some("wrapping_op")
+                }
+                "count_ones" => Some("count_ones"),
+                "rotate_left" | "rotate_right" => Some("rotate"),
+                "len" => Some("len"),
+                "sqrt" | "abs" | "floor" | "sin" | "recip" => Some("float_method"),
+                "is_some" => Some("is_some"),
+                "is_empty" => Some("is_empty"),
+                "to_uppercase" => Some("to_uppercase"),
+                "to_string" => Some("to_string"),
+                "or" => Some("or"),
+                _ => None,
+            },
+            syn::Expr::Binary(binary) => match binary.op {
+                syn::BinOp::BitXor(_) | syn::BinOp::BitAnd(_) | syn::BinOp::BitOr(_) => {
+                    Some("bit_op")
+                }
+                syn::BinOp::Add(_)
+                | syn::BinOp::Sub(_)
+                | syn::BinOp::Mul(_)
+                | syn::BinOp::Div(_) => Some("arith"),
+                syn::BinOp::And(_) | syn::BinOp::Or(_) | syn::BinOp::Eq(_) => Some("logic"),
+                syn::BinOp::Lt(_)
+                | syn::BinOp::Le(_)
+                | syn::BinOp::Gt(_)
+                | syn::BinOp::Ge(_)
+                | syn::BinOp::Ne(_) => Some("compare"),
+                _ => None,
+            },
+            syn::Expr::Unary(unary) if matches!(unary.op, syn::UnOp::Not(_)) => Some("not"),
+            syn::Expr::Cast(_) => Some("cast"),
+            syn::Expr::Call(call) => match &*call.func {
+                syn::Expr::Path(path) => {
+                    let mut idents = path.path.segments.iter().rev().map(|s| s.ident.to_string());
+                    let (last, before) = (idents.next().unwrap_or_default(), idents.next());
+                    match (before.as_deref(), last.as_str()) {
+                        (Some("char"), "from") => Some("char_from"),
+                        (Some("String"), "from") => Some("string_from"),
+                        (_, "is_some") => Some("is_some"),
+                        (_, "drop") => Some("drop"),
+                        (_, "Some") => Some("some"),
+                        _ => None,
+                    }
+                }
+                _ => None,
+            },
+            syn::Expr::Path(path)
+                if path.path.segments.last().is_some_and(|s| s.ident == "None") =>
+            {
+                Some("none")
+            }
+            syn::Expr::Tuple(tuple) if tuple.elems.is_empty() => Some("unit"),
+            syn::Expr::Macro(mac) if mac.mac.path.is_ident("format") => Some("format"),
+            syn::Expr::If(_) => Some("if"),
+            syn::Expr::Match(_) => Some("match"),
+            syn::Expr::Block(_) => Some("block"),
+            syn::Expr::Index(_) => Some("index"),
+            syn::Expr::ForLoop(_) => Some("for"),
+            _ => None,
+        };
+        self.0.extend(production);
+        visit::visit_expr(self, expr);
+    }
+
+    fn visit_stmt(&mut self, stmt: &'ast syn::Stmt) {
+        match stmt {
+            syn::Stmt::Local(local) => {
+                let mut pat = &local.pat;
+                if let syn::Pat::Type(typed) = pat {
+                    pat = &typed.pat;
+                }
+                self.0.insert(match pat {
+                    syn::Pat::Wild(_) => "let_underscore",
+                    _ => "let",
+                });
+            }
+            syn::Stmt::Expr(_, Some(_)) => {
+                self.0.insert("expr_stmt");
+            }
+            _ => {}
+        }
+        visit::visit_stmt(self, stmt);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn productions_are_recognized_in_any_seed() {
+        let source = "fn f(x: u32) -> u32 {\n    \
+                          let _ = x as u8;\n    \
+                          let y = x.wrapping_add(1) ^ 2;\n    \
+                          if y < 3 { y } else { drop(()); x }\n\
+                      }\n";
+        let found = productions(source).expect("parses");
+        for production in [
+            "let_underscore",
+            "cast",
+            "let",
+            "wrapping_op",
+            "bit_op",
+            "if",
+            "compare",
+            "drop",
+            "unit",
+            "expr_stmt",
+        ] {
+            assert!(found.contains(production), "{production} in {found:?}");
+        }
+        assert!(!found.contains("for"), "{found:?}");
+        assert!(found
+            .iter()
+            .all(|production| PRODUCTIONS.contains(production)));
+        assert_eq!(productions("fn ("), None);
+    }
+
+    #[test]
+    fn counts_list_the_rarest_first() {
+        let mut counts = Counts::new();
+        counts.add("fn f() { let x = 1; }");
+        counts.add("fn g() { let y = 2 as u8; }");
+        counts.add("fn (");
+        assert_eq!((counts.seeds, counts.unparsed, counts.parsed()), (3, 1, 2));
+        assert_eq!(counts.count("let"), 2);
+        assert_eq!(counts.count("cast"), 1);
+        let text = counts.to_string();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "2 seeds parsed, 1 did not");
+        assert_eq!(lines.last(), Some(&"     2 100.0%  let"));
+        assert_eq!(lines[lines.len() - 2], "     1  50.0%  cast");
+    }
+}
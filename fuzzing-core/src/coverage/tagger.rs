@@ -0,0 +1,79 @@
+//! Seed tags.
+//!
+//! [`tags`] names what a seed is made of in the form its metadata keeps:
+//! the [`Feature::tag`] of every feature [`classify`] finds, plus a tag for
+//! each family of features that matters on its own (`unsafe`, `async`).
+//! Seeds are tagged when their metadata is created, so the scheduler and
+//! the feature matrix can read tags without parsing every seed again.
+
+use super::features::{classify, Feature};
+
+/// Family tags and the features that imply them.
+const FAMILIES: &[(&str, &[Feature])] = &[
+    (
+        "unsafe",
+        &[
+            Feature::UnsafeBlock,
+            Feature::UnsafeFn,
+            Feature::UnsafeTrait,
+        ],
+    ),
+    (
+        "async",
+        &[Feature::AsyncFn, Feature::AsyncBlock, Feature::Await],
+    ),
+    (
+        "generics",
+        &[
+            Feature::TypeGenerics,
+            Feature::LifetimeGenerics,
+            Feature::ConstGenerics,
+        ],
+    ),
+];
+
+/// The tags of `source`, sorted. Seeds that do not parse have none.
+pub fn tags(source: &str) -> Vec<String> {
+    let Some(features) = classify(source) else {
+        return Vec::new();
+    };
+    let mut tags: Vec<String> = features
+        .iter()
+        .map(|feature| feature.tag().to_owned())
+        .collect();
+    for (family, members) in FAMILIES {
+        if members.iter().any(|member| features.contains(member)) {
+            tags.push((*family).to_owned());
+        }
+    }
+    tags.sort();
+    tags
+}
+
+/// The features among `tags`; family tags and tags of features this
+/// version does not know are skipped.
+pub fn features(tags: &[String]) -> impl Iterator<Item = Feature> + '_ {
+    tags.iter().filter_map(|tag| Feature::from_tag(tag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn families_are_tagged_alongside_their_features() {
+        let tags = tags("unsafe fn f<T>(x: T) -> T { x }");
+        assert_eq!(tags, ["generics", "type_generics", "unsafe", "unsafe_fn"]);
+        assert!(super::tags("fn (").is_empty());
+    }
+
+    #[test]
+    fn only_feature_tags_come_back_as_features() {
+        let tags = ["unsafe", "unsafe_fn", "from_a_newer_tagger", "closure"].map(str::to_owned);
+        let features: Vec<Feature> = features(&tags).collect();
+        assert_eq!(features, [Feature::UnsafeFn, Feature::Closure]);
+        for feature in Feature::ALL {
+            assert_eq!(Feature::from_tag(feature.tag()), Some(feature));
+        }
+    }
+}
@@ -0,0 +1,263 @@
+//! Fuzzing dictionaries for AFL++ and libFuzzer.
+//!
+//! A [`Dictionary`] starts out with the fixed vocabulary of the Rust grammar
+//! (keywords, punctuation, common attribute names) and grows with type,
+//! trait and macro names harvested from corpus seeds, so it can be
+//! regenerated whenever the corpus changes.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+use syn::visit::{self, Visit};
+
+use crate::lex;
+
+/// Token sequences that are not single tokens but show up constantly.
+const SEQUENCES: &[&str] = &[
+    "#[",
+    "#![",
+    "::<",
+    "'static",
+    "'_",
+    "&'a ",
+    "&mut ",
+    "*const ",
+    "*mut ",
+    "r#\"",
+    "\"#",
+    "b\"",
+    "b'",
+    "c\"",
+    "=> {",
+    "-> ",
+    "|| ",
+    "move ||",
+    "async move",
+    ".await",
+    "?;",
+    "..=",
+    "impl<T> ",
+    "where T: ",
+    "dyn ",
+    "for<'a> ",
+    "macro_rules! ",
+    "$($x:expr),*",
+    "unsafe {",
+    "const {",
+    "'label: loop {",
+    "break 'label",
+    "as _",
+    "0u8",
+    "1i128",
+    "1e10",
+    "0x7f",
+    "'\\u{0}'",
+];
+
+/// Builtin and commonly used attributes.
+const ATTRIBUTES: &[&str] = &[
+    "derive",
+    "cfg",
+    "cfg_attr",
+    "inline",
+    "cold",
+    "repr",
+    "allow",
+    "warn",
+    "deny",
+    "forbid",
+    "expect",
+    "must_use",
+    "no_mangle",
+    "export_name",
+    "link_section",
+    "test",
+    "ignore",
+    "should_panic",
+    "doc",
+    "path",
+    "macro_export",
+    "macro_use",
+    "proc_macro",
+    "track_caller",
+    "non_exhaustive",
+    "feature",
+    "deprecated",
+    "target_feature",
+    "automatically_derived",
+    "no_std",
+    "no_main",
+    "recursion_limit",
+    "type_length_limit",
+    "crate_type",
+    "crate_name",
+    "rustc_layout_scalar_valid_range_start",
+    "rustc_nonnull_optimization_guaranteed",
+    "rustc_on_unimplemented",
+    "rustc_dump_user_args",
+    "lang",
+    "fundamental",
+    "marker",
+    "const_trait",
+    "diagnostic::on_unimplemented",
+    "coverage",
+    "optimize",
+    "naked",
+    "used",
+];
+
+/// Fixed grammar vocabulary plus names harvested from a corpus.
+#[derive(Debug, Clone)]
+pub struct Dictionary {
+    harvested: BTreeMap<String, usize>,
+}
+
+impl Default for Dictionary {
+    fn default() -> Self {
+        Dictionary::new()
+    }
+}
+
+impl Dictionary {
+    pub fn new() -> Self {
+        Dictionary {
+            harvested: BTreeMap::new(),
+        }
+    }
+
+    /// Records the type, trait and macro names used in `source`. Seeds that
+    /// do not parse contribute nothing.
+    pub fn harvest(&mut self, source: &str) {
+        if let Ok(file) = syn::parse_file(source) {
+            let mut names = Names(&mut self.harvested);
+            names.visit_file(&file);
+        }
+    }
+
+    /// Renders the dictionary in the `name="value"` format understood by
+    /// both AFL++ (`-x`) and libFuzzer (`-dict=`). Harvested names seen
+    /// fewer than `min_count` times are left out.
+    pub fn render(&self, min_count: usize) -> String {
+        let mut out = String::new();
+        let mut seen = BTreeSet::new();
+        let mut emit = |prefix: &str, value: &str| {
+            if seen.insert(value.to_owned()) {
+                let _ = writeln!(out, "{prefix}_{}=\"{}\"", seen.len(), escape(value));
+            }
+        };
+        for kw in lex::KEYWORDS {
+            emit("kw", kw);
+        }
+        for p in lex::PUNCT {
+            emit("punct", p);
+        }
+        for c in "!#$%&*+,-./:;<=>?@^_|~()[]{}'\"".chars() {
+            emit("punct", c.encode_utf8(&mut [0; 4]));
+        }
+        for seq in SEQUENCES {
+            emit("seq", seq);
+        }
+        for attr in ATTRIBUTES {
+            emit("attr", attr);
+        }
+        for (name, &count) in &self.harvested {
+            if count >= min_count {
+                emit("name", name);
+            }
+        }
+        out
+    }
+}
+
+/// Escapes a dictionary value: quotes, backslashes and anything that is
+/// not printable ASCII.
+fn escape(value: &str) -> String {
+    let mut out = String::new();
+    for b in value.bytes() {
+        match b {
+            b'"' | b'\\' => {
+                out.push('\\');
+                out.push(b as char);
+            }
+            0x20..=0x7e => out.push(b as char),
+            _ => {
+                let _ = write!(out, "\\x{b:02x}");
+            }
+        }
+    }
+    out
+}
+
+struct Names<'a>(&'a mut BTreeMap<String, usize>);
+
+impl Names<'_> {
+    fn add(&mut self, ident: &syn::Ident) {
+        *self.0.entry(ident.to_string()).or_default() += 1;
+    }
+}
+
+impl<'ast> Visit<'ast> for Names<'_> {
+    fn visit_type_path(&mut self, ty: &'ast syn::TypePath) {
+        if let Some(last) = ty.path.segments.last() {
+            self.add(&last.ident);
+        }
+        visit::visit_type_path(self, ty);
+    }
+
+    fn visit_trait_bound(&mut self, bound: &'ast syn::TraitBound) {
+        if let Some(last) = bound.path.segments.last() {
+            self.add(&last.ident);
+        }
+        visit::visit_trait_bound(self, bound);
+    }
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        if let Some(last) = mac.path.segments.last() {
+            self.add(&last.ident);
+        }
+        visit::visit_macro(self, mac);
+    }
+
+    fn visit_item_struct(&mut self, item: &'ast syn::ItemStruct) {
+        self.add(&item.ident);
+        visit::visit_item_struct(self, item);
+    }
+
+    fn visit_item_enum(&mut self, item: &'ast syn::ItemEnum) {
+        self.add(&item.ident);
+        visit::visit_item_enum(self, item);
+    }
+
+    fn visit_item_trait(&mut self, item: &'ast syn::ItemTrait) {
+        self.add(&item.ident);
+        visit::visit_item_trait(self, item);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn harvested_names_need_min_count_uses() {
+        let mut dict = Dictionary::new();
+        dict.harvest("struct Frob; fn f(x: Frob, y: Vec<Frob>) { println!(); }");
+        dict.harvest("not rust at all {");
+        let once = dict.render(1);
+        assert!(once.contains("=\"Frob\"\n"), "{once}");
+        assert!(once.contains("=\"Vec\"\n"), "{once}");
+        assert!(once.contains("=\"println\"\n"), "{once}");
+        let twice = dict.render(2);
+        assert!(twice.contains("=\"Frob\"\n"));
+        assert!(!twice.contains("=\"Vec\"\n"));
+    }
+
+    #[test]
+    fn entries_are_numbered_and_escaped() {
+        let rendered = Dictionary::new().render(1);
+        assert!(rendered.starts_with("kw_1=\"as\"\n"), "{rendered}");
+        assert!(rendered.contains("=\"r#\\\"\"\n"), "{rendered}");
+        assert!(rendered.contains("=\"'\\\\u{0}'\"\n"), "{rendered}");
+        // Each value is listed once, under the first kind it appears as.
+        assert_eq!(rendered.matches("=\"..=\"").count(), 1);
+    }
+}
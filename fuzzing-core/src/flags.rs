@@ -0,0 +1,326 @@
+//! Compiler flag combinations.
+//!
+//! Many ICEs only reproduce under flags nobody passes together: MIR
+//! inlining at an unusual opt level, a sanitizer with `-Zrandomize-layout`,
+//! coverage instrumentation with panic=abort. [`FlagFuzzer`] samples sets
+//! of `-C` and `-Z` options from [`SCHEMA`], compiles a seed under each,
+//! and reduces the set that crashed to the flags it needs.
+
+use std::fmt;
+use std::io;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+
+use crate::harness::{ExecOutcome, Rustc};
+use crate::triage::Severity;
+
+/// An option and the values worth trying. A flag without values is passed
+/// bare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Flag {
+    pub name: &'static str,
+    pub values: &'static [&'static str],
+}
+
+impl Flag {
+    pub const fn new(name: &'static str, values: &'static [&'static str]) -> Self {
+        Flag { name, values }
+    }
+
+    fn sample(&self, rng: &mut dyn RngCore) -> String {
+        match self.values.choose(rng) {
+            Some(value) => format!("{}={value}", self.name),
+            None => self.name.to_owned(),
+        }
+    }
+}
+
+/// Options that change what the compiler does to valid code. Flags that
+/// only affect linking, or that are unsound by design
+/// (`-Zunsound-mir-opts`), are left out.
+pub const SCHEMA: &[Flag] = &[
+    Flag::new("-Copt-level", &["0", "1", "2", "3", "s", "z"]),
+    Flag::new("-Cdebuginfo", &["0", "line-tables-only", "2"]),
+    Flag::new("-Cdebug-assertions", &["yes", "no"]),
+    Flag::new("-Coverflow-checks", &["yes", "no"]),
+    Flag::new("-Cpanic", &["unwind", "abort"]),
+    Flag::new("-Ccodegen-units", &["1", "16"]),
+    Flag::new("-Clto", &["fat", "thin"]),
+    Flag::new("-Cembed-bitcode", &["yes", "no"]),
+    Flag::new("-Crelocation-model", &["pic", "static"]),
+    Flag::new("-Cforce-frame-pointers", &["yes"]),
+    Flag::new("-Cinstrument-coverage", &[]),
+    Flag::new("-Zmir-opt-level", &["0", "1", "2", "3", "4"]),
+    Flag::new("-Zinline-mir", &["yes", "no"]),
+    Flag::new("-Zinline-mir-threshold", &["0", "1000"]),
+    Flag::new("-Zcross-crate-inline-threshold", &["always", "never"]),
+    Flag::new("-Zvalidate-mir", &[]),
+    Flag::new("-Zlint-mir", &[]),
+    Flag::new("-Zmir-preserve-ub", &[]),
+    Flag::new("-Zub-checks", &["yes", "no"]),
+    Flag::new("-Zshare-generics", &["yes", "no"]),
+    Flag::new("-Zpolonius", &[]),
+    Flag::new("-Znext-solver", &["globally", "coherence"]),
+    Flag::new("-Zrandomize-layout", &[]),
+    Flag::new("-Zprecise-enum-drop-elaboration", &["no"]),
+    Flag::new("-Csymbol-mangling-version", &["v0"]),
+    Flag::new("-Zmerge-functions", &["disabled", "trampolines", "aliases"]),
+    Flag::new("-Zstack-protector", &["basic", "strong", "all"]),
+    Flag::new("-Zsanitizer", &["address", "leak"]),
+    Flag::new("-Zmutable-noalias", &["no"]),
+    Flag::new("-Zbox-noalias", &["no"]),
+    Flag::new("-Zfewer-names", &[]),
+    Flag::new("-Zhuman-readable-cgu-names", &[]),
+    Flag::new("-Zverify-llvm-ir", &[]),
+];
+
+/// Pairs rustc refuses to combine, as prefixes of the rendered flags.
+/// Sampling them would only spend executions on a usage error.
+pub const INCOMPATIBLE: &[(&str, &str)] = &[
+    ("-Clto=fat", "-Cembed-bitcode=no"),
+    ("-Clto=thin", "-Cembed-bitcode=no"),
+];
+
+/// Samples flag sets and compiles seeds under them.
+#[derive(Debug, Clone)]
+pub struct FlagFuzzer {
+    /// Compiles the seeds; every build goes through codegen by default,
+    /// which is where most flags take effect.
+    pub driver: Rustc,
+    pub schema: &'static [Flag],
+    pub incompatible: &'static [(&'static str, &'static str)],
+    /// Flag sets tried on every seed before the sampled ones.
+    pub matrix: Vec<Vec<String>>,
+    /// Flag sets sampled per seed.
+    pub samples: usize,
+    /// Most flags in one set.
+    pub max_flags: usize,
+    pub edition: String,
+}
+
+impl Default for FlagFuzzer {
+    fn default() -> Self {
+        FlagFuzzer {
+            driver: Rustc::default(),
+            schema: SCHEMA,
+            incompatible: INCOMPATIBLE,
+            matrix: Vec::new(),
+            samples: 4,
+            max_flags: 6,
+            edition: "2021".to_owned(),
+        }
+    }
+}
+
+/// What compiling one seed under sampled flags led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FlagOutcome {
+    /// No flag set made rustc crash.
+    Clean,
+    /// The seed does not compile with default flags.
+    Rejected,
+    /// rustc crashed under `flags`, reduced to the ones the crash needs.
+    /// Empty when it crashes with default flags too.
+    Crash {
+        flags: Vec<String>,
+        outcome: ExecOutcome,
+    },
+}
+
+impl FlagOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            FlagOutcome::Crash { outcome, .. } => Some(Severity::of_failure(outcome)),
+            FlagOutcome::Clean | FlagOutcome::Rejected => None,
+        }
+    }
+}
+
+impl fmt::Display for FlagOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FlagOutcome::Clean => f.write_str("clean"),
+            FlagOutcome::Rejected => f.write_str("rejected"),
+            FlagOutcome::Crash { flags, outcome } if flags.is_empty() => {
+                write!(f, "{outcome} with default flags")
+            }
+            FlagOutcome::Crash { flags, outcome } => {
+                write!(f, "{outcome} with {}", flags.join(" "))
+            }
+        }
+    }
+}
+
+impl FlagFuzzer {
+    pub fn check(&self, source: &str, rng: &mut dyn RngCore) -> io::Result<FlagOutcome> {
+        let baseline = self.compile(source, &[])?;
+        if baseline.is_crash() {
+            return Ok(FlagOutcome::Crash {
+                flags: Vec::new(),
+                outcome: baseline,
+            });
+        }
+        if !baseline.is_success() {
+            return Ok(FlagOutcome::Rejected);
+        }
+        for flags in &self.matrix {
+            let outcome = self.compile(source, flags)?;
+            if outcome.is_crash() {
+                return self.reduce(source, flags.clone(), outcome);
+            }
+        }
+        for _ in 0..self.samples {
+            let flags = self.sample(rng);
+            let outcome = self.compile(source, &flags)?;
+            if outcome.is_crash() {
+                return self.reduce(source, flags, outcome);
+            }
+        }
+        Ok(FlagOutcome::Clean)
+    }
+
+    /// A random set of compatible flags, each option at most once.
+    pub fn sample(&self, rng: &mut dyn RngCore) -> Vec<String> {
+        let count = rng.gen_range(1..=self.max_flags.max(1));
+        sample(self.schema, self.incompatible, count, rng)
+    }
+
+    /// Drops each flag the crash still reproduces without. A crash of a
+    /// different kind does not count as reproducing.
+    fn reduce(
+        &self,
+        source: &str,
+        mut flags: Vec<String>,
+        mut outcome: ExecOutcome,
+    ) -> io::Result<FlagOutcome> {
+        let mut i = 0;
+        while i < flags.len() {
+            let mut fewer = flags.clone();
+            fewer.remove(i);
+            let retry = self.compile(source, &fewer)?;
+            if retry.kind() == outcome.kind() {
+                flags = fewer;
+                outcome = retry;
+            } else {
+                i += 1;
+            }
+        }
+        Ok(FlagOutcome::Crash { flags, outcome })
+    }
+
+    fn compile(&self, source: &str, flags: &[String]) -> io::Result<ExecOutcome> {
+        let mut args = vec![
+            "--crate-type=lib".to_owned(),
+            "--edition".to_owned(),
+            self.edition.clone(),
+        ];
+        args.extend(flags.iter().cloned());
+        args.push("-".to_owned());
+        self.driver.run(&args, source.as_bytes())
+    }
+}
+
+/// Up to `count` flags for distinct options of `schema`, in random order,
+/// leaving out any that would form a pair in `incompatible`.
+pub fn sample(
+    schema: &[Flag],
+    incompatible: &[(&str, &str)],
+    count: usize,
+    rng: &mut dyn RngCore,
+) -> Vec<String> {
+    let mut flags: Vec<String> = Vec::with_capacity(count.min(schema.len()));
+    let mut options: Vec<&Flag> = schema.iter().collect();
+    options.shuffle(rng);
+    for option in options {
+        if flags.len() == count {
+            break;
+        }
+        let flag = option.sample(rng);
+        if flags
+            .iter()
+            .all(|chosen| compatible(incompatible, chosen, &flag))
+        {
+            flags.push(flag);
+        }
+    }
+    flags
+}
+
+fn compatible(incompatible: &[(&str, &str)], a: &str, b: &str) -> bool {
+    !incompatible.iter().any(|(x, y)| {
+        (a.starts_with(x) && b.starts_with(y)) || (a.starts_with(y) && b.starts_with(x))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::collections::HashSet;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn samples_set_each_option_once_and_compatibly() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..200 {
+            let flags = sample(SCHEMA, INCOMPATIBLE, 12, &mut rng);
+            assert_eq!(flags.len(), 12);
+            let options: HashSet<_> = flags
+                .iter()
+                .map(|flag| flag.split('=').next().expect("has a name"))
+                .collect();
+            assert_eq!(options.len(), flags.len(), "{flags:?}");
+            for a in &flags {
+                assert!(flags.iter().all(|b| compatible(INCOMPATIBLE, a, b)));
+            }
+        }
+        let everything = sample(SCHEMA, &[], usize::MAX, &mut rng);
+        assert_eq!(everything.len(), SCHEMA.len());
+    }
+
+    #[test]
+    fn crashes_are_reduced_to_the_flags_they_need() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-flags-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        // ICEs when MIR inlining meets the highest MIR opt level.
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            "#!/bin/sh\ncase \"$*\" in\n  *-Zinline-mir=yes*-Zmir-opt-level=4*)\n    \
+             echo 'error: internal compiler error: inlining' >&2\n    exit 101 ;;\nesac\n",
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let matrix = [
+            "-Cpanic=abort",
+            "-Zinline-mir=yes",
+            "-Ccodegen-units=1",
+            "-Zmir-opt-level=4",
+        ];
+        let fuzzer = FlagFuzzer {
+            driver: Rustc::new(&rustc),
+            matrix: vec![matrix.map(str::to_owned).to_vec()],
+            samples: 0,
+            ..FlagFuzzer::default()
+        };
+        let outcome = fuzzer
+            .check("fn f() {}", &mut StdRng::seed_from_u64(0))
+            .expect("compiles");
+        let FlagOutcome::Crash { flags, .. } = &outcome else {
+            panic!("{outcome:?}");
+        };
+        assert_eq!(flags, &["-Zinline-mir=yes", "-Zmir-opt-level=4"]);
+        assert!(outcome
+            .to_string()
+            .ends_with("with -Zinline-mir=yes -Zmir-opt-level=4"));
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,338 @@
+//! Pairs of crates whose impls skirt the orphan rules.
+//!
+//! Coherence is a property of a crate graph: whether an impl may be
+//! written depends on which crate owns the trait and each type in its
+//! header, on which types are `#[fundamental]` (`&T`, `&mut T`, `Box<T>`,
+//! `Pin<P>` and wrappers marked so), and on what impls an upstream crate
+//! may add later without breaking anyone. A single-file seed owns every
+//! type it names, so none of that is ever exercised.
+//!
+//! [`CoherenceGen`] writes a downstream crate with its upstream crate
+//! inlined as `mod upstream`, which the `coherence` harness compiles as a
+//! crate of its own first. Each program encodes one [`Case`], with random
+//! local types, wrappers and foreign types, whose [`Expect`]ation follows
+//! from the rules: a local type under any number of fundamental wrappers
+//! still counts as local, under any other type it does not, and a type
+//! parameter is only covered under a type that is not fundamental.
+//!
+//! Every program starts with [`MARKER`], the expectation and the name of
+//! its case. A rustc that accepts a program it must reject lets two
+//! crates implement the same trait for the same type; one that rejects a
+//! program it must accept is wrong too, if less dangerously.
+
+use std::fmt;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+
+use super::marker::{self, Expect};
+use super::ty::Ty;
+
+/// The first line of every program [`CoherenceGen`] writes, followed by
+/// the expectation and the case's name.
+pub const MARKER: &str = "// rustc-fuzz: coherence: ";
+
+/// The name of the module holding the upstream crate, and of that crate.
+pub const UPSTREAM: &str = "upstream";
+
+/// The upstream crate: a trait for the downstream crate to implement, two
+/// with blanket impls to overlap, and a wrapper of each kind.
+const UPSTREAM_ITEMS: &str = "
+    pub trait Foreign<T = ()> {
+        fn id(&self) -> u32 {
+            0
+        }
+    }
+
+    pub trait Blanket {}
+
+    impl<T: Copy> Blanket for T {}
+
+    pub trait ByRef {}
+
+    impl<T: ?Sized> ByRef for &T {}
+
+    pub struct Remote<T>(pub T);
+
+    #[fundamental]
+    pub struct Wrap<T>(pub T);
+";
+
+/// Most fundamental wrappers put around a type.
+const MAX_WRAPPERS: usize = 2;
+
+/// Field types of local types, all `Copy` so a local type can derive it.
+const FIELDS: [&str; 5] = ["u8", "i32", "u64", "bool", "char"];
+
+/// One way of placing an impl relative to the orphan and overlap rules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Case {
+    /// The foreign trait for a local type under fundamental wrappers.
+    LocalSelf,
+    /// The foreign trait for a local type under a wrapper that is not
+    /// fundamental (E0117).
+    WrappedLocal,
+    /// The foreign trait for a foreign type under fundamental wrappers
+    /// (E0117).
+    ForeignSelf,
+    /// The foreign trait for a foreign type, with a local type argument.
+    LocalArgument,
+    /// Likewise, for a type parameter covered by a type that is not
+    /// fundamental.
+    CoveredParameter,
+    /// Likewise, for a type parameter under fundamental wrappers only,
+    /// which comes before the local type (E0210).
+    UncoveredParameter,
+    /// The foreign trait for every type, or every type under fundamental
+    /// wrappers (E0210).
+    BlanketForeign,
+    /// A local trait for every type.
+    BlanketLocal,
+    /// The upstream blanket impl over `Copy` types, and one for a local
+    /// type that is not `Copy`.
+    DisjointBlanket,
+    /// Likewise, for a local type that is `Copy` (E0119).
+    OverlappingBlanket,
+    /// The upstream impl for every `&T`, and one for a reference to a
+    /// local type (E0119).
+    OverlappingReference,
+    /// A local blanket impl over the foreign trait, and one for a foreign
+    /// type upstream may yet implement it for (E0119).
+    UpstreamMayImplement,
+    /// Likewise, for a local type under fundamental wrappers, which only
+    /// this crate could implement it for.
+    FundamentalNegative,
+}
+
+impl Case {
+    pub const ALL: [Case; 13] = [
+        Case::LocalSelf,
+        Case::WrappedLocal,
+        Case::ForeignSelf,
+        Case::LocalArgument,
+        Case::CoveredParameter,
+        Case::UncoveredParameter,
+        Case::BlanketForeign,
+        Case::BlanketLocal,
+        Case::DisjointBlanket,
+        Case::OverlappingBlanket,
+        Case::OverlappingReference,
+        Case::UpstreamMayImplement,
+        Case::FundamentalNegative,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Case::LocalSelf => "local-self",
+            Case::WrappedLocal => "wrapped-local",
+            Case::ForeignSelf => "foreign-self",
+            Case::LocalArgument => "local-argument",
+            Case::CoveredParameter => "covered-parameter",
+            Case::UncoveredParameter => "uncovered-parameter",
+            Case::BlanketForeign => "blanket-foreign",
+            Case::BlanketLocal => "blanket-local",
+            Case::DisjointBlanket => "disjoint-blanket",
+            Case::OverlappingBlanket => "overlapping-blanket",
+            Case::OverlappingReference => "overlapping-reference",
+            Case::UpstreamMayImplement => "upstream-may-implement",
+            Case::FundamentalNegative => "fundamental-negative",
+        }
+    }
+
+    /// What rustc must make of a program encoding this case.
+    pub fn expect(self) -> Expect {
+        match self {
+            Case::LocalSelf
+            | Case::LocalArgument
+            | Case::CoveredParameter
+            | Case::BlanketLocal
+            | Case::DisjointBlanket
+            | Case::FundamentalNegative => Expect::Accept,
+            Case::WrappedLocal
+            | Case::ForeignSelf
+            | Case::UncoveredParameter
+            | Case::BlanketForeign
+            | Case::OverlappingBlanket
+            | Case::OverlappingReference
+            | Case::UpstreamMayImplement => Expect::Reject,
+        }
+    }
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The expectation and case a program is marked with, if it is marked.
+pub fn marked(source: &str) -> Option<(Expect, &str)> {
+    marker::marked(MARKER, source)
+}
+
+/// Writes pairs of crates with impls at the edges of coherence.
+#[derive(Debug, Clone, Default)]
+pub struct CoherenceGen;
+
+impl CoherenceGen {
+    /// A program encoding a random [`Case`].
+    pub fn generate(&self, rng: &mut dyn RngCore) -> String {
+        let case = *Case::ALL.choose(rng).expect("there are cases");
+        self.generate_case(case, rng)
+    }
+
+    /// A program encoding `case`.
+    pub fn generate_case(&self, case: Case, rng: &mut dyn RngCore) -> String {
+        let copy = match case {
+            Case::DisjointBlanket => false,
+            Case::OverlappingBlanket => true,
+            _ => rng.gen_bool(0.5),
+        };
+        let local = local_type(copy, rng);
+        let impls = match case {
+            Case::LocalSelf => foreign_impl("", "", &fundamental("Local", rng), rng),
+            Case::WrappedLocal => foreign_impl(
+                "",
+                "",
+                &not_fundamental(&maybe_fundamental("Local", rng), rng),
+                rng,
+            ),
+            Case::ForeignSelf => {
+                let foreign = Ty::random(2, rng).to_string();
+                foreign_impl("", "", &fundamental(&foreign, rng), rng)
+            }
+            Case::LocalArgument => {
+                let self_ty = Ty::random(2, rng).to_string();
+                foreign_impl("", &maybe_fundamental("Local", rng), &self_ty, rng)
+            }
+            Case::CoveredParameter => {
+                let self_ty = maybe_fundamental(&not_fundamental("T", rng), rng);
+                foreign_impl("<T>", &maybe_fundamental("Local", rng), &self_ty, rng)
+            }
+            Case::UncoveredParameter => {
+                let self_ty = maybe_fundamental("T", rng);
+                foreign_impl("<T>", &maybe_fundamental("Local", rng), &self_ty, rng)
+            }
+            Case::BlanketForeign => foreign_impl("<T>", "", &maybe_fundamental("T", rng), rng),
+            Case::BlanketLocal => "impl<T: ?Sized> LocalTrait for T {}\n".to_owned(),
+            Case::DisjointBlanket | Case::OverlappingBlanket => {
+                "impl upstream::Blanket for Local {}\n".to_owned()
+            }
+            Case::OverlappingReference => format!(
+                "impl upstream::ByRef for &{} {{}}\n",
+                maybe_fundamental("Local", rng)
+            ),
+            Case::UpstreamMayImplement => format!(
+                "impl<T: upstream::Foreign> LocalTrait for T {{}}\n\n\
+                 impl LocalTrait for upstream::Remote<{}> {{}}\n",
+                Ty::random(2, rng)
+            ),
+            Case::FundamentalNegative => format!(
+                "impl<T: upstream::Foreign> LocalTrait for T {{}}\n\n\
+                 impl LocalTrait for {} {{}}\n",
+                fundamental("Local", rng)
+            ),
+        };
+        format!(
+            "{MARKER}{} {case}\n#![feature(fundamental)]\n#![allow(dead_code)]\n\n\
+             mod {UPSTREAM} {{{UPSTREAM_ITEMS}}}\n\n\
+             {local}\npub trait LocalTrait {{}}\n\n{impls}",
+            case.expect()
+        )
+    }
+}
+
+/// The local type, deriving `Copy` if `copy`.
+fn local_type(copy: bool, rng: &mut dyn RngCore) -> String {
+    let derive = if copy { "#[derive(Clone, Copy)]\n" } else { "" };
+    let fields = rng.gen_range(1..=2);
+    let mut fields: Vec<&str> = (0..fields)
+        .map(|_| *FIELDS.choose(rng).expect("there are field types"))
+        .collect();
+    match rng.gen_range(0..3) {
+        0 => format!("{derive}pub struct Local;\n"),
+        1 => format!("{derive}pub struct Local(pub {});\n", fields.join(", pub ")),
+        _ => {
+            fields.truncate(1);
+            format!(
+                "{derive}pub enum Local {{\n    A,\n    B({}),\n}}\n",
+                fields[0]
+            )
+        }
+    }
+}
+
+/// An impl of `upstream::Foreign<argument>` for `self_ty`, with
+/// `generics`, sometimes overriding its method.
+fn foreign_impl(generics: &str, argument: &str, self_ty: &str, rng: &mut dyn RngCore) -> String {
+    let trait_ref = if argument.is_empty() {
+        "upstream::Foreign".to_owned()
+    } else {
+        format!("upstream::Foreign<{argument}>")
+    };
+    let body = if rng.gen_bool(0.5) {
+        format!(
+            " {{\n    fn id(&self) -> u32 {{\n        {}\n    }}\n}}",
+            rng.gen_range(1..100u32)
+        )
+    } else {
+        " {}".to_owned()
+    };
+    format!("impl{generics} {trait_ref} for {self_ty}{body}\n")
+}
+
+/// `ty` under one to [`MAX_WRAPPERS`] fundamental wrappers.
+fn fundamental(ty: &str, rng: &mut dyn RngCore) -> String {
+    let wrappers = rng.gen_range(1..=MAX_WRAPPERS);
+    (0..wrappers).fold(ty.to_owned(), |ty, _| wrap_fundamental(&ty, rng))
+}
+
+/// `ty` under up to [`MAX_WRAPPERS`] fundamental wrappers.
+fn maybe_fundamental(ty: &str, rng: &mut dyn RngCore) -> String {
+    let wrappers = rng.gen_range(0..=MAX_WRAPPERS);
+    (0..wrappers).fold(ty.to_owned(), |ty, _| wrap_fundamental(&ty, rng))
+}
+
+fn wrap_fundamental(ty: &str, rng: &mut dyn RngCore) -> String {
+    match rng.gen_range(0..5) {
+        0 => format!("&{ty}"),
+        1 => format!("&mut {ty}"),
+        2 => format!("Box<{ty}>"),
+        3 => format!("std::pin::Pin<{ty}>"),
+        _ => format!("upstream::Wrap<{ty}>"),
+    }
+}
+
+/// `ty` under one wrapper that is not fundamental.
+fn not_fundamental(ty: &str, rng: &mut dyn RngCore) -> String {
+    match rng.gen_range(0..6) {
+        0 => format!("Vec<{ty}>"),
+        1 => format!("Option<{ty}>"),
+        2 => format!("std::rc::Rc<{ty}>"),
+        3 => format!("upstream::Remote<{ty}>"),
+        4 => format!("({ty},)"),
+        _ => format!("[{ty}; {}]", rng.gen_range(1..4)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::{SplitRng, Stream};
+
+    #[test]
+    fn marked_round_trips_every_case() {
+        let streams = SplitRng::new(0);
+        for (i, case) in Case::ALL.into_iter().enumerate() {
+            for j in 0..8 {
+                let mut rng = streams.rng(Stream::Generator, (i * 8 + j) as u64);
+                let program = CoherenceGen.generate_case(case, &mut rng);
+                assert_eq!(
+                    marked(&program),
+                    Some((case.expect(), case.name())),
+                    "{program}"
+                );
+            }
+        }
+    }
+}
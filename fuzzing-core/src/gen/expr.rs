@@ -0,0 +1,528 @@
+//! Type-directed expression and statement generation.
+//!
+//! [`ExprGen`] builds well-typed expressions for a requested [`Ty`],
+//! drawing leaves from the variables in a [`Scope`]. Integer arithmetic
+//! uses wrapping methods so generated code does not trip the
+//! `arithmetic_overflow` lint during constant propagation. With
+//! [`ExprGen::constant`] set, only what a `const fn` may contain is
+//! generated.
+
+use std::sync::Arc;
+
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+
+use super::level::Level;
+use super::ty::{Ty, INTS};
+use super::weights::Weights;
+
+/// Variables visible at the generation site.
+#[derive(Debug, Clone, Default)]
+pub struct Scope {
+    vars: Vec<(String, Ty)>,
+}
+
+impl Scope {
+    pub fn new() -> Self {
+        Scope::default()
+    }
+
+    pub fn push(&mut self, name: impl Into<String>, ty: Ty) {
+        self.vars.push((name.into(), ty));
+    }
+
+    pub fn len(&self) -> usize {
+        self.vars.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vars.is_empty()
+    }
+
+    /// Drops variables declared after the scope had `len` entries.
+    pub fn truncate(&mut self, len: usize) {
+        self.vars.truncate(len);
+    }
+
+    /// Names of the variables in scope, in declaration order.
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.vars.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// The type of the innermost variable called `name`.
+    pub fn lookup(&self, name: &str) -> Option<&Ty> {
+        self.vars
+            .iter()
+            .rev()
+            .find(|(n, _)| n == name)
+            .map(|(_, t)| t)
+    }
+
+    fn of_type(&self, ty: &Ty) -> Vec<&str> {
+        self.vars
+            .iter()
+            .filter(|(_, t)| t == ty)
+            .map(|(name, _)| name.as_str())
+            .collect()
+    }
+
+    /// A variable name not yet in scope.
+    pub fn fresh(&self) -> String {
+        (self.vars.len()..)
+            .map(|i| format!("v{i}"))
+            .find(|name| self.vars.iter().all(|(n, _)| n != name))
+            .unwrap()
+    }
+}
+
+const STRS: &[&str] = &["", "a", "rustc", "\\n", "\\u{1F980}", "{}", "\\\"", "é"];
+const CHARS: &[&str] = &["'a'", "'\\0'", "'\\u{10FFFF}'", "'\\''", "'é'", "'\\n'"];
+
+/// Generates expressions and statements of bounded depth.
+#[derive(Debug, Clone)]
+pub struct ExprGen {
+    pub max_depth: usize,
+    /// Leave out operations whose result a correct compiler may change,
+    /// so that output can be compared across builds: `sin` has no
+    /// guaranteed precision and may be folded at compile time.
+    pub deterministic: bool,
+    /// The newest Rust the generated code may need.
+    pub level: Level,
+    /// Stay within what a `const fn` body may contain: no heap types, no
+    /// loops, and only operations that are `const` themselves. Needs
+    /// [`Level::Latest`] for floating-point arithmetic.
+    pub constant: bool,
+    /// How often each production is picked when several fit; uniformly
+    /// without.
+    pub weights: Option<Arc<Weights>>,
+}
+
+impl Default for ExprGen {
+    fn default() -> Self {
+        ExprGen {
+            max_depth: 4,
+            deterministic: false,
+            level: Level::default(),
+            constant: false,
+            weights: None,
+        }
+    }
+}
+
+impl ExprGen {
+    /// An expression of type `ty`.
+    pub fn expr(&self, ty: &Ty, scope: &Scope, rng: &mut dyn RngCore) -> String {
+        self.gen(ty, scope, self.max_depth, rng)
+    }
+
+    /// An expression of a random type, together with that type.
+    pub fn any_expr(&self, scope: &Scope, rng: &mut dyn RngCore) -> (Ty, String) {
+        self.gen_any(scope, self.max_depth, rng)
+    }
+
+    fn gen_any(&self, scope: &Scope, depth: usize, rng: &mut dyn RngCore) -> (Ty, String) {
+        let ty = self.random_ty(2, rng);
+        let expr = self.gen(&ty, scope, depth, rng);
+        (ty, expr)
+    }
+
+    /// A random type this generator can build values of.
+    fn random_ty(&self, depth: usize, rng: &mut dyn RngCore) -> Ty {
+        loop {
+            let ty = Ty::random(depth, rng);
+            if !self.constant || ty.is_const() {
+                return ty;
+            }
+        }
+    }
+
+    /// A statement. `let` bindings it introduces are added to `scope`.
+    pub fn stmt(&self, scope: &mut Scope, rng: &mut dyn RngCore) -> String {
+        self.gen_stmt(scope, self.max_depth, rng)
+    }
+
+    fn gen_stmt(&self, scope: &mut Scope, depth: usize, rng: &mut dyn RngCore) -> String {
+        // `for` desugars to `Iterator` calls, which are not `const`.
+        let count = match depth {
+            0 => 3,
+            _ if self.constant => 4,
+            _ => 5,
+        };
+        let forms = ["let", "let_underscore", "expr_stmt", "if", "for"];
+        match self.form(&forms[..count], rng) {
+            0 => {
+                let (ty, expr) = self.gen_any(scope, depth, rng);
+                let name = scope.fresh();
+                let stmt = format!("let {name}: {ty} = {expr};");
+                scope.push(name, ty);
+                stmt
+            }
+            1 => {
+                let (ty, expr) = self.gen_any(scope, depth, rng);
+                format!("let _: {ty} = {expr};")
+            }
+            2 => format!("{};", self.gen(&Ty::Unit, scope, depth, rng)),
+            3 => {
+                let cond = self.gen(&Ty::Bool, scope, depth - 1, rng);
+                let mark = scope.len();
+                let then = self.gen_stmt(scope, depth - 1, rng);
+                scope.truncate(mark);
+                let otherwise = self.gen_stmt(scope, depth - 1, rng);
+                scope.truncate(mark);
+                format!("if {cond} {{ {then} }} else {{ {otherwise} }}")
+            }
+            _ => {
+                let mark = scope.len();
+                let var = scope.fresh();
+                let bound = rng.gen_range(0..4);
+                scope.push(var.clone(), Ty::Int("usize"));
+                let body = self.gen_stmt(scope, depth - 1, rng);
+                scope.truncate(mark);
+                format!("for {var} in 0..{bound}usize {{ {body} }}")
+            }
+        }
+    }
+
+    fn gen(&self, ty: &Ty, scope: &Scope, depth: usize, rng: &mut dyn RngCore) -> String {
+        let vars = scope.of_type(ty);
+        if !vars.is_empty() && rng.gen_bool(0.3) {
+            let var = vars.choose(rng).unwrap();
+            return if is_copy(ty) {
+                (*var).to_owned()
+            } else {
+                format!("{var}.clone()")
+            };
+        }
+        if depth == 0 || rng.gen_bool(0.25) {
+            return self.leaf(ty, scope, rng);
+        }
+        let d = depth - 1;
+        match ty {
+            Ty::Int(name) => match self.form(
+                &[
+                    "wrapping_op",
+                    "bit_op",
+                    "cast",
+                    "count_ones",
+                    "rotate",
+                    "len",
+                    "control",
+                    "control",
+                    "control",
+                ],
+                rng,
+            ) {
+                0 => {
+                    // `abs_diff` (1.60) returns the unsigned type, so only
+                    // unsigned operands keep the expression's type.
+                    // `min` and `max` come from `Ord`, whose methods are
+                    // not `const`.
+                    let ops: &[&str] =
+                        if self.constant && self.level >= Level::Latest && name.starts_with('u') {
+                            &["wrapping_add", "wrapping_sub", "wrapping_mul", "abs_diff"]
+                        } else if self.constant {
+                            &["wrapping_add", "wrapping_sub", "wrapping_mul"]
+                        } else if self.level >= Level::Latest && name.starts_with('u') {
+                            &[
+                                "wrapping_add",
+                                "wrapping_sub",
+                                "wrapping_mul",
+                                "min",
+                                "max",
+                                "abs_diff",
+                            ]
+                        } else {
+                            &["wrapping_add", "wrapping_sub", "wrapping_mul", "min", "max"]
+                        };
+                    let op = ops.choose(rng).unwrap();
+                    let (a, b) = (self.gen(ty, scope, d, rng), self.gen(ty, scope, d, rng));
+                    format!("({a}).{op}({b})")
+                }
+                1 => {
+                    let op = ["^", "&", "|"].choose(rng).unwrap();
+                    let (a, b) = (self.gen(ty, scope, d, rng), self.gen(ty, scope, d, rng));
+                    format!("({a} {op} {b})")
+                }
+                2 => {
+                    let from = Ty::Int(INTS.choose(rng).unwrap());
+                    format!("({} as {name})", self.gen(&from, scope, d, rng))
+                }
+                3 => format!("(({}).count_ones() as {name})", self.gen(ty, scope, d, rng)),
+                4 => format!(
+                    "({}).rotate_left({})",
+                    self.gen(ty, scope, d, rng),
+                    rng.gen_range(0..130)
+                ),
+                5 => format!(
+                    "(({}).len() as {name})",
+                    self.gen(&self.text(), scope, d, rng)
+                ),
+                _ => self.control(ty, scope, d, rng),
+            },
+            Ty::Float(name) => match self.form(
+                &["arith", "float_method", "cast", "control", "control"],
+                rng,
+            ) {
+                0 => {
+                    let op = ["+", "-", "*", "/"].choose(rng).unwrap();
+                    let (a, b) = (self.gen(ty, scope, d, rng), self.gen(ty, scope, d, rng));
+                    format!("({a} {op} {b})")
+                }
+                1 => {
+                    let operand = self.gen(ty, scope, d, rng);
+                    let methods: &[&str] = if self.constant {
+                        &["abs"]
+                    } else if self.deterministic {
+                        &["sqrt", "abs", "floor", "recip"]
+                    } else {
+                        &["sqrt", "abs", "floor", "sin", "recip"]
+                    };
+                    format!("({operand}).{}()", methods.choose(rng).unwrap())
+                }
+                2 => {
+                    let from = Ty::Int(INTS.choose(rng).unwrap());
+                    format!("({} as {name})", self.gen(&from, scope, d, rng))
+                }
+                _ => self.control(ty, scope, d, rng),
+            },
+            Ty::Bool => match self.form(
+                &["not", "logic", "compare", "is_some", "is_empty", "control"],
+                rng,
+            ) {
+                0 => format!("!{}", self.gen(ty, scope, d, rng)),
+                1 => {
+                    let op = ["&&", "||", "^", "=="].choose(rng).unwrap();
+                    let (a, b) = (self.gen(ty, scope, d, rng), self.gen(ty, scope, d, rng));
+                    format!("({a} {op} {b})")
+                }
+                2 => {
+                    let int = Ty::Int(INTS.choose(rng).unwrap());
+                    let op = ["<", "<=", ">", ">=", "!="].choose(rng).unwrap();
+                    let (a, b) = (self.gen(&int, scope, d, rng), self.gen(&int, scope, d, rng));
+                    format!("({a} {op} {b})")
+                }
+                3 => {
+                    let inner = self.random_ty(1, rng);
+                    let opt = Ty::Option(Box::new(inner.clone()));
+                    let expr = self.gen(&opt, scope, d, rng);
+                    format!("Option::<{inner}>::is_some(&{expr})")
+                }
+                4 => format!("{}.is_empty()", self.gen(&self.text(), scope, d, rng)),
+                _ => self.control(ty, scope, d, rng),
+            },
+            Ty::Char => match self.form(&["char_from", "control", "control"], rng) {
+                0 if self.constant => {
+                    format!("({} as char)", self.gen(&Ty::Int("u8"), scope, d, rng))
+                }
+                0 => format!("char::from({})", self.gen(&Ty::Int("u8"), scope, d, rng)),
+                _ => self.control(ty, scope, d, rng),
+            },
+            Ty::Str => self.control(ty, scope, d, rng),
+            Ty::String => match self.form(
+                &[
+                    "string_from",
+                    "format",
+                    "to_uppercase",
+                    "to_string",
+                    "control",
+                ],
+                rng,
+            ) {
+                0 => format!("String::from({})", self.gen(&Ty::Str, scope, d, rng)),
+                1 => {
+                    let a = self.gen(&Ty::Int(INTS.choose(rng).unwrap()), scope, d, rng);
+                    let b = self.gen(&Ty::Str, scope, d, rng);
+                    format!("format!(\"{{}}{{}}\", {a}, {b})")
+                }
+                2 => format!("{}.to_uppercase()", self.gen(ty, scope, d, rng)),
+                3 => format!("{}.to_string()", self.gen(&Ty::Char, scope, d, rng)),
+                _ => self.control(ty, scope, d, rng),
+            },
+            Ty::Unit => match self.form(&["drop", "let_underscore", "unit"], rng) {
+                // Annotated, since `None` or `vec![]` alone leave the
+                // element type to inference.
+                0 if !self.constant => {
+                    let (ty, expr) = self.gen_any(scope, d, rng);
+                    format!("drop::<{ty}>({expr})")
+                }
+                1 => {
+                    let (ty, expr) = self.gen_any(scope, d, rng);
+                    format!("{{ let _: {ty} = {expr}; }}")
+                }
+                _ => "()".to_owned(),
+            },
+            Ty::Tuple(ts) => {
+                let elems: Vec<String> = ts.iter().map(|t| self.gen(t, scope, d, rng)).collect();
+                tuple(&elems)
+            }
+            Ty::Array(t, n) => {
+                let elems: Vec<String> = (0..*n).map(|_| self.gen(t, scope, d, rng)).collect();
+                format!("[{}]", elems.join(", "))
+            }
+            Ty::Option(t) => match self.form(&["none", "or", "some"], rng) {
+                0 => format!("None::<{t}>"),
+                1 if !self.constant => format!("Some({}).or(None)", self.gen(t, scope, d, rng)),
+                _ => format!("Some({})", self.gen(t, scope, d, rng)),
+            },
+            Ty::Vec(t) => {
+                let elems: Vec<String> = (0..rng.gen_range(0..4))
+                    .map(|_| self.gen(t, scope, d, rng))
+                    .collect();
+                format!("vec![{}]", elems.join(", "))
+            }
+        }
+    }
+
+    /// Control-flow forms that work for any type.
+    fn control(&self, ty: &Ty, scope: &Scope, d: usize, rng: &mut dyn RngCore) -> String {
+        match self.form(&["if", "match", "block", "index"], rng) {
+            0 => {
+                let cond = self.gen(&Ty::Bool, scope, d, rng);
+                let (a, b) = (self.gen(ty, scope, d, rng), self.gen(ty, scope, d, rng));
+                format!("if {cond} {{ {a} }} else {{ {b} }}")
+            }
+            1 => {
+                let scrutinee = self.gen(&Ty::Bool, scope, d, rng);
+                let (a, b) = (self.gen(ty, scope, d, rng), self.gen(ty, scope, d, rng));
+                format!("match {scrutinee} {{ true => {a}, false => {b} }}")
+            }
+            2 => {
+                let mut inner = scope.clone();
+                let stmt = self.gen_stmt(&mut inner, d, rng);
+                format!("{{ {stmt} {} }}", self.gen(ty, &inner, d, rng))
+            }
+            // Indexing moves out of the array, which needs `Copy`.
+            _ if is_copy(ty) => {
+                let (a, b) = (self.gen(ty, scope, d, rng), self.gen(ty, scope, d, rng));
+                format!("[{a}, {b}][{}]", rng.gen_range(0..2))
+            }
+            _ => self.leaf(ty, scope, rng),
+        }
+    }
+
+    /// The index of the production in `forms` to use, by
+    /// [`ExprGen::weights`]. Productions may repeat to weigh more.
+    fn form(&self, forms: &[&str], rng: &mut dyn RngCore) -> usize {
+        // Drawn as an `i32`, as the choices were before weights, so that
+        // unweighted programs stay the same for the same seed.
+        let uniform = |rng: &mut dyn RngCore| rng.gen_range(0..forms.len() as i32) as usize;
+        let Some(weights) = &self.weights else {
+            return uniform(rng);
+        };
+        let weights = forms.iter().map(|form| weights.weight(form));
+        match WeightedIndex::new(weights) {
+            Ok(index) => index.sample(rng),
+            Err(_) => uniform(rng),
+        }
+    }
+
+    /// The text type operations like `len` are generated on: `String`,
+    /// or `&str` where there is no heap.
+    fn text(&self) -> Ty {
+        if self.constant {
+            Ty::Str
+        } else {
+            Ty::String
+        }
+    }
+
+    fn leaf(&self, ty: &Ty, scope: &Scope, rng: &mut dyn RngCore) -> String {
+        match ty {
+            Ty::Unit => "()".to_owned(),
+            Ty::Bool => ["true", "false"].choose(rng).unwrap().to_string(),
+            Ty::Char => CHARS.choose(rng).unwrap().to_string(),
+            Ty::Int(name) => int_literal(name, rng),
+            Ty::Float(name) => match rng.gen_range(0..6) {
+                0 => format!("{name}::NAN"),
+                1 => format!("{name}::INFINITY"),
+                2 => format!("{name}::MIN_POSITIVE"),
+                3 => format!("-0.0{name}"),
+                _ => format!("{:.3}{name}", rng.gen_range(-1000.0..1000.0)),
+            },
+            Ty::Str => format!("\"{}\"", STRS.choose(rng).unwrap()),
+            Ty::String => "String::new()".to_owned(),
+            _ => self.gen(ty, scope, 1, rng),
+        }
+    }
+}
+
+fn int_literal(name: &str, rng: &mut dyn RngCore) -> String {
+    let signed = name.starts_with('i');
+    match rng.gen_range(0..6) {
+        0 => format!("{name}::MAX"),
+        1 if signed => format!("{name}::MIN"),
+        2 if signed => format!("-{}{name}", rng.gen_range(1..128)),
+        3 => format!("0{name}"),
+        _ => format!("{}{name}", rng.gen_range(0..128)),
+    }
+}
+
+fn tuple(elems: &[String]) -> String {
+    match elems {
+        [] => "()".to_owned(),
+        [one] => format!("({one},)"),
+        _ => format!("({})", elems.join(", ")),
+    }
+}
+
+/// Whether values of `ty` can be used more than once without `.clone()`.
+pub fn is_copy(ty: &Ty) -> bool {
+    match ty {
+        Ty::String | Ty::Vec(_) => false,
+        Ty::Tuple(ts) => ts.iter().all(is_copy),
+        Ty::Array(t, _) | Ty::Option(t) => is_copy(t),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn expressions_parse_at_any_type() {
+        let gen = ExprGen::default();
+        let mut scope = Scope::new();
+        scope.push("x", Ty::Int("i32"));
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..200 {
+            let ty = Ty::random(2, &mut rng);
+            let expr = gen.expr(&ty, &scope, &mut rng);
+            let item = format!("fn f(x: i32) -> {ty} {{ {expr} }}");
+            assert!(syn::parse_file(&item).is_ok(), "{item}");
+        }
+    }
+
+    #[test]
+    fn statements_bind_fresh_names() {
+        let gen = ExprGen::default();
+        let mut scope = Scope::new();
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut body = String::new();
+        for _ in 0..20 {
+            body.push_str(&gen.stmt(&mut scope, &mut rng));
+            body.push('\n');
+        }
+        let item = format!("fn f() {{\n{body}}}");
+        assert!(syn::parse_file(&item).is_ok(), "{item}");
+        let names: Vec<&str> = scope.names().collect();
+        let mut unique = names.clone();
+        unique.dedup();
+        assert_eq!(names, unique);
+        assert!(scope.lookup(&scope.fresh()).is_none());
+    }
+
+    #[test]
+    fn lookups_find_the_innermost_binding() {
+        let mut scope = Scope::new();
+        scope.push("v0", Ty::Bool);
+        scope.push("v0", Ty::Char);
+        assert_eq!(scope.lookup("v0"), Some(&Ty::Char));
+        assert_eq!(scope.fresh(), "v2");
+        scope.truncate(1);
+        assert_eq!(scope.lookup("v0"), Some(&Ty::Bool));
+    }
+}
@@ -0,0 +1,420 @@
+//! Closures and functions against higher-ranked bounds.
+//!
+//! A bound such as `for<'a> Fn(&'a Pair) -> &'a u8` asks for one
+//! implementation that works for every lifetime at once. A closure only
+//! gets a signature that general when rustc can see the bound while it
+//! infers the closure, passed straight to the function or through a
+//! helper that constrains it, or when it is written with a `for<'a>`
+//! binder; bound to a `let` first, its return lifetime is fixed, and it
+//! falls short. A function item does too when its lifetime is
+//! early-bound, named in a bound such as `'a: 'a`, so that it must be
+//! chosen when the function is named rather than when it is called. This
+//! is where rustc's higher-ranked inference and leak check run, and where
+//! their diagnostics are least exercised.
+//!
+//! [`HigherRankedGen`] writes a program that passes a closure or function
+//! to a function with such a bound, as one [`Case`]. The struct projected
+//! from, the `Fn` trait, how the bound is spelled and the body of the
+//! closure are random. Every program starts with [`MARKER`], the case's
+//! [`Expect`]ation and its name.
+
+use std::fmt;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+
+use super::marker::Expect;
+
+/// The first line of every program [`HigherRankedGen`] writes, followed
+/// by the expectation and the case's name.
+pub const MARKER: &str = "// rustc-fuzz: higher-ranked: ";
+
+/// Field types of the struct projected from, and a value of each.
+const FIELDS: [(&str, &str); 5] = [
+    ("u8", "1"),
+    ("i32", "-2"),
+    ("u64", "3"),
+    ("bool", "true"),
+    ("char", "'c'"),
+];
+
+/// The `Fn` traits a bound may name.
+const TRAITS: [&str; 3] = ["Fn", "FnMut", "FnOnce"];
+
+/// One way of meeting, or failing to meet, a higher-ranked bound.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Case {
+    /// A closure passed straight to the function with the bound, which
+    /// rustc infers it against.
+    InlineClosure,
+    /// A closure with a `for<'a>` binder, bound to a `let` first.
+    BinderClosure,
+    /// A closure passed through a helper with the same bound, then on.
+    ConstrainedClosure,
+    /// A function item whose lifetime is late-bound.
+    LateBoundFn,
+    /// A closure returning a reference to a `static`, whatever its
+    /// argument.
+    StaticReturn,
+    /// A closure coerced to a higher-ranked function pointer first.
+    FnPointer,
+    /// A closure boxed as a higher-ranked trait object first.
+    BoxedDyn,
+    /// A function item whose lifetime is early-bound, for a bound that
+    /// is not higher-ranked.
+    EarlyBoundSpecific,
+    /// A function item with a late-bound and an early-bound lifetime,
+    /// for a bound that is higher-ranked over the late-bound one only.
+    MixedInstantiated,
+    /// A closure of two arguments returning from the first, for a bound
+    /// higher-ranked over both.
+    FirstOfTwo,
+    /// A closure with an annotated argument bound to a `let` first, so
+    /// its return lifetime is not tied to the argument's.
+    LetClosure,
+    /// A function item whose lifetime is early-bound ("implementation of
+    /// `Fn` is not general enough").
+    EarlyBoundFn,
+    /// A function item with a late-bound and an early-bound lifetime,
+    /// for a bound that is higher-ranked over both.
+    MixedHigherRanked,
+    /// A closure returning a reference to a local it captures (E0597).
+    CapturedLocal,
+    /// A closure of two arguments returning from the second.
+    SecondOfTwo,
+    /// A closure coerced to a function pointer for `'static` only first.
+    SpecificFnPointer,
+}
+
+impl Case {
+    pub const ALL: [Case; 16] = [
+        Case::InlineClosure,
+        Case::BinderClosure,
+        Case::ConstrainedClosure,
+        Case::LateBoundFn,
+        Case::StaticReturn,
+        Case::FnPointer,
+        Case::BoxedDyn,
+        Case::EarlyBoundSpecific,
+        Case::MixedInstantiated,
+        Case::FirstOfTwo,
+        Case::LetClosure,
+        Case::EarlyBoundFn,
+        Case::MixedHigherRanked,
+        Case::CapturedLocal,
+        Case::SecondOfTwo,
+        Case::SpecificFnPointer,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Case::InlineClosure => "inline-closure",
+            Case::BinderClosure => "binder-closure",
+            Case::ConstrainedClosure => "constrained-closure",
+            Case::LateBoundFn => "late-bound-fn",
+            Case::StaticReturn => "static-return",
+            Case::FnPointer => "fn-pointer",
+            Case::BoxedDyn => "boxed-dyn",
+            Case::EarlyBoundSpecific => "early-bound-specific",
+            Case::MixedInstantiated => "mixed-instantiated",
+            Case::FirstOfTwo => "first-of-two",
+            Case::LetClosure => "let-closure",
+            Case::EarlyBoundFn => "early-bound-fn",
+            Case::MixedHigherRanked => "mixed-higher-ranked",
+            Case::CapturedLocal => "captured-local",
+            Case::SecondOfTwo => "second-of-two",
+            Case::SpecificFnPointer => "specific-fn-pointer",
+        }
+    }
+
+    /// What rustc must make of a program encoding this case.
+    pub fn expect(self) -> Expect {
+        match self {
+            Case::InlineClosure
+            | Case::BinderClosure
+            | Case::ConstrainedClosure
+            | Case::LateBoundFn
+            | Case::StaticReturn
+            | Case::FnPointer
+            | Case::BoxedDyn
+            | Case::EarlyBoundSpecific
+            | Case::MixedInstantiated
+            | Case::FirstOfTwo => Expect::Accept,
+            Case::LetClosure
+            | Case::EarlyBoundFn
+            | Case::MixedHigherRanked
+            | Case::CapturedLocal
+            | Case::SecondOfTwo
+            | Case::SpecificFnPointer => Expect::Reject,
+        }
+    }
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The pieces of one program that every case shares.
+struct Shape {
+    /// `Fn`, `FnMut` or `FnOnce`.
+    trait_name: &'static str,
+    /// The projected field and its type and value.
+    field: &'static str,
+    ty: &'static str,
+    value: &'static str,
+    /// How `apply` spells its bound: 0 in a `where` clause, 1 inline, 2
+    /// as `impl Trait`.
+    spelling: usize,
+    /// How the closure body projects: 0 directly, 1 through a `let`, 2
+    /// through `id`.
+    body: usize,
+}
+
+impl Shape {
+    fn random(rng: &mut dyn RngCore) -> Shape {
+        let (ty, value) = *FIELDS.choose(rng).expect("there are fields");
+        Shape {
+            trait_name: TRAITS.choose(rng).expect("there are traits"),
+            field: if rng.gen_bool(0.5) { "left" } else { "right" },
+            ty,
+            value,
+            spelling: rng.gen_range(0..3),
+            body: rng.gen_range(0..3),
+        }
+    }
+
+    /// An expression projecting the field out of `var`.
+    fn project(&self, var: &str) -> String {
+        let field = self.field;
+        match self.body {
+            0 => format!("&{var}.{field}"),
+            1 => format!("{{\n        let r = &{var}.{field};\n        r\n    }}"),
+            _ => format!("id(&{var}.{field})"),
+        }
+    }
+
+    /// `for<binder> Trait(inputs) -> &'a T`.
+    fn bound(&self, binder: &str, inputs: &str) -> String {
+        let binder = if binder.is_empty() {
+            String::new()
+        } else {
+            format!("for<{binder}> ")
+        };
+        format!("{binder}{}({inputs}) -> &'a {}", self.trait_name, self.ty)
+    }
+
+    /// A function `name` calling its argument with `args`, under `bound`.
+    fn apply(&self, name: &str, bound: &str, args: &str) -> String {
+        let signature = match self.spelling {
+            0 => format!("fn {name}<F>(f: F)\nwhere\n    F: {bound},\n"),
+            1 => format!("fn {name}<F: {bound}>(f: F) "),
+            _ => format!("fn {name}(f: impl {bound}) "),
+        };
+        format!(
+            "{signature}{{\n    let pair = pair();\n    let mut f = f;\n    let _ = f({args});\n}}\n"
+        )
+    }
+}
+
+/// Writes closures and functions passed where higher-ranked bounds hold
+/// or fail.
+#[derive(Debug, Clone, Default)]
+pub struct HigherRankedGen;
+
+impl HigherRankedGen {
+    /// A program encoding a random [`Case`].
+    pub fn generate(&self, rng: &mut dyn RngCore) -> String {
+        let case = *Case::ALL.choose(rng).expect("there are cases");
+        self.generate_case(case, rng)
+    }
+
+    /// A program encoding `case`.
+    pub fn generate_case(&self, case: Case, rng: &mut dyn RngCore) -> String {
+        let shape = Shape::random(rng);
+        let ty = shape.ty;
+        let project = |var: &str| shape.project(var);
+        let one = shape.bound("'a", "&'a Pair");
+        let early = if rng.gen_bool(0.5) {
+            format!(
+                "fn project<'a: 'a>(x: &'a Pair) -> &'a {ty} {{\n    {}\n}}\n",
+                project("x")
+            )
+        } else {
+            format!(
+                "fn project<'a>(x: &'a Pair) -> &'a {ty}\nwhere\n    'a: 'a,\n{{\n    {}\n}}\n",
+                project("x")
+            )
+        };
+        let mixed = format!(
+            "fn project<'a, 'b>(x: &'a Pair, _y: &'b u8) -> &'a {ty}\nwhere\n    'b: 'b,\n{{\n    {}\n}}\n",
+            project("x")
+        );
+        let closure = format!(
+            "{}|x| {}",
+            if rng.gen_bool(0.3) { "move " } else { "" },
+            project("x")
+        );
+        let (items, call) = match case {
+            Case::InlineClosure => (
+                shape.apply("apply", &one, "&pair"),
+                format!("apply({closure});"),
+            ),
+            Case::BinderClosure => (
+                shape.apply("apply", &one, "&pair"),
+                format!(
+                    "let f = for<'a> |x: &'a Pair| -> &'a {ty} {{ {} }};\n    apply(f);",
+                    project("x")
+                ),
+            ),
+            Case::ConstrainedClosure => (
+                format!(
+                    "{}\nfn constrain<F: {one}>(f: F) -> F {{\n    f\n}}\n",
+                    shape.apply("apply", &one, "&pair")
+                ),
+                format!("let f = constrain({closure});\n    apply(f);"),
+            ),
+            Case::LateBoundFn => (
+                format!(
+                    "{}\nfn project(x: &Pair) -> &{ty} {{\n    {}\n}}\n",
+                    shape.apply("apply", &one, "&pair"),
+                    project("x")
+                ),
+                "apply(project);".to_owned(),
+            ),
+            Case::StaticReturn => (
+                format!(
+                    "{}\nstatic ANSWER: {ty} = {};\n",
+                    shape.apply("apply", &one, "&pair"),
+                    shape.value
+                ),
+                "apply(|_x| &ANSWER);".to_owned(),
+            ),
+            Case::FnPointer | Case::SpecificFnPointer => {
+                let pointer = if case == Case::FnPointer {
+                    format!("for<'a> fn(&'a Pair) -> &'a {ty}")
+                } else {
+                    format!("fn(&'static Pair) -> &'static {ty}")
+                };
+                (
+                    shape.apply("apply", &one, "&pair"),
+                    format!("let f: {pointer} = |x| {};\n    apply(f);", project("x")),
+                )
+            }
+            Case::BoxedDyn => (
+                shape.apply("apply", &one, "&pair"),
+                format!(
+                    "let f: Box<dyn {}> = Box::new({closure});\n    apply(f);",
+                    shape.bound("'a", "&'a Pair")
+                ),
+            ),
+            Case::EarlyBoundSpecific => {
+                let bound = shape.bound("", "&'a Pair");
+                (
+                    format!(
+                        "fn apply_at<'a, F>(pair: &'a Pair, f: F) -> &'a {ty}\nwhere\n    \
+                         F: {bound},\n{{\n    let mut f = f;\n    f(pair)\n}}\n\n{early}"
+                    ),
+                    "let pair = pair();\n    let _ = apply_at(&pair, project);".to_owned(),
+                )
+            }
+            Case::EarlyBoundFn => (
+                format!("{}\n{early}", shape.apply("apply", &one, "&pair")),
+                "apply(project);".to_owned(),
+            ),
+            Case::MixedInstantiated | Case::MixedHigherRanked => {
+                let (binder, inputs) = if case == Case::MixedInstantiated {
+                    ("'a", "&'a Pair, &'static u8")
+                } else {
+                    ("'a, 'b", "&'a Pair, &'b u8")
+                };
+                let bound = shape.bound(binder, inputs);
+                (
+                    format!("{}\n{mixed}", shape.apply("apply", &bound, "&pair, &0")),
+                    "apply(project);".to_owned(),
+                )
+            }
+            Case::FirstOfTwo | Case::SecondOfTwo => {
+                let bound = shape.bound("'a, 'b", "&'a Pair, &'b Pair");
+                let closure = if case == Case::FirstOfTwo {
+                    format!("|x, _y| {}", project("x"))
+                } else {
+                    format!("|_x, y| {}", project("y"))
+                };
+                (
+                    shape.apply("apply", &bound, "&pair, &pair"),
+                    format!("apply({closure});"),
+                )
+            }
+            Case::LetClosure => {
+                let ret = if rng.gen_bool(0.5) {
+                    format!(" -> &{ty}")
+                } else {
+                    String::new()
+                };
+                let body = if ret.is_empty() {
+                    project("x")
+                } else {
+                    format!("{{ {} }}", project("x"))
+                };
+                (
+                    shape.apply("apply", &one, "&pair"),
+                    format!("let f = |x: &Pair|{ret} {body};\n    apply(f);"),
+                )
+            }
+            Case::CapturedLocal => (
+                shape.apply("apply", &one, "&pair"),
+                format!(
+                    "let local: {ty} = {};\n    apply({}|_x| &local);",
+                    shape.value,
+                    if rng.gen_bool(0.5) { "move " } else { "" }
+                ),
+            ),
+        };
+        let feature = if case == Case::BinderClosure {
+            "#![feature(closure_lifetime_binder)]\n"
+        } else {
+            ""
+        };
+        let (left, right) = match shape.field {
+            "left" => ((ty, shape.value), ("u8", "0")),
+            _ => (("u8", "0"), (ty, shape.value)),
+        };
+        format!(
+            "{MARKER}{} {case}\n{feature}#![allow(dead_code, unused_mut, unused_variables)]\n\n\
+             struct Pair {{\n    left: {},\n    right: {},\n}}\n\n\
+             fn pair() -> Pair {{\n    Pair {{\n        left: {},\n        right: {},\n    }}\n}}\n\n\
+             fn id<T>(t: T) -> T {{\n    t\n}}\n\n\
+             {items}\nfn main() {{\n    {call}\n}}\n",
+            case.expect(),
+            left.0,
+            right.0,
+            left.1,
+            right.1,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gen::marker::marked;
+    use crate::rng::{SplitRng, Stream};
+
+    #[test]
+    fn marked_round_trips_every_case() {
+        let streams = SplitRng::new(0);
+        for (i, case) in Case::ALL.into_iter().enumerate() {
+            for j in 0..8 {
+                let mut rng = streams.rng(Stream::Generator, (i * 8 + j) as u64);
+                let program = HigherRankedGen.generate_case(case, &mut rng);
+                assert_eq!(
+                    marked(MARKER, &program),
+                    Some((case.expect(), case.name())),
+                    "{program}"
+                );
+            }
+        }
+    }
+}
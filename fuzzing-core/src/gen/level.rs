@@ -0,0 +1,107 @@
+//! How new a compiler the generated code may require.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// The newest language and library features generated code may use.
+///
+/// Alternative compilers track old rustc releases; code that uses
+/// anything newer only tells them apart by what they have not caught up
+/// with yet.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Level {
+    /// Rust 1.54, edition 2018: what mrustc implements.
+    Rust1_54,
+    /// Whatever the current stable compiler accepts.
+    #[default]
+    Latest,
+}
+
+impl Level {
+    pub const ALL: [Level; 2] = [Level::Rust1_54, Level::Latest];
+
+    /// The newest edition available at this level.
+    pub fn edition(self) -> &'static str {
+        match self {
+            Level::Rust1_54 => "2018",
+            Level::Latest => "2021",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Rust1_54 => "1.54",
+            Level::Latest => "latest",
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownLevel(pub String);
+
+impl fmt::Display for UnknownLevel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown language level {:?}; expected 1.54 or latest",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownLevel {}
+
+impl FromStr for Level {
+    type Err = UnknownLevel;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Level::ALL
+            .into_iter()
+            .find(|l| l.name() == s)
+            .ok_or_else(|| UnknownLevel(s.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gen::{ExprGen, ProgramGen};
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn levels_parse_from_their_names() {
+        for level in Level::ALL {
+            assert_eq!(level.name().parse(), Ok(level));
+        }
+        assert_eq!(
+            "2018".parse::<Level>(),
+            Err(UnknownLevel("2018".to_owned()))
+        );
+    }
+
+    #[test]
+    fn old_levels_stay_within_their_library() {
+        let gen = ProgramGen {
+            gen: ExprGen {
+                level: Level::Rust1_54,
+                deterministic: true,
+                ..ExprGen::default()
+            },
+            max_stmts: 16,
+            ..ProgramGen::default()
+        };
+        for seed in 0..200 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            for program in [gen.generate(&mut rng), gen.generate_const(&mut rng)] {
+                assert!(!program.contains("abs_diff"), "{program}");
+            }
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Markers of programs rustc must accept or reject.
+//!
+//! The [`coherence`](super::coherence), [`variance`](super::variance) and
+//! [`higher_ranked`](super::higher_ranked) generators start every program
+//! with a marker of their own, then the program's [`Expect`]ation and the
+//! name of its case, as in `// rustc-fuzz: variance: reject
+//! let-widen-covariant`. Their harnesses read it back with [`marked`] and
+//! compare the expectation with what rustc did.
+
+use std::fmt;
+
+/// Whether rustc must accept a program.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Expect {
+    Accept,
+    Reject,
+}
+
+impl Expect {
+    pub const ALL: [Expect; 2] = [Expect::Accept, Expect::Reject];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Expect::Accept => "accept",
+            Expect::Reject => "reject",
+        }
+    }
+}
+
+impl fmt::Display for Expect {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The expectation and case after `marker` on a program's first line, if
+/// it has one.
+pub fn marked<'a>(marker: &str, source: &'a str) -> Option<(Expect, &'a str)> {
+    let (expect, case) = source
+        .lines()
+        .next()?
+        .strip_prefix(marker)?
+        .trim()
+        .split_once(' ')?;
+    let expect = Expect::ALL.into_iter().find(|e| e.name() == expect)?;
+    Some((expect, case.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn marked_needs_the_marker_and_an_expectation() {
+        assert_eq!(
+            marked("// m: ", "// m: reject some-case\nfn main() {}"),
+            Some((Expect::Reject, "some-case"))
+        );
+        assert_eq!(
+            marked("// m: ", "// m:  accept  some-case \n"),
+            Some((Expect::Accept, "some-case"))
+        );
+        assert_eq!(marked("// m: ", "// n: accept some-case\n"), None);
+        assert_eq!(marked("// m: ", "// m: maybe some-case\n"), None);
+        assert_eq!(marked("// m: ", "// m: accept\n"), None);
+        assert_eq!(marked("// m: ", "fn main() {}\n// m: accept x\n"), None);
+        assert_eq!(marked("// m: ", ""), None);
+    }
+}
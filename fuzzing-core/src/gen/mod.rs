@@ -0,0 +1,42 @@
+//! Random program generation.
+//!
+//! [`ty`] models the types the generator can produce values of, and
+//! [`expr`] builds well-typed expressions and statements over them.
+//! [`program`] turns those into whole programs that print their results,
+//! and [`level`] bounds how new a compiler they may need.
+//! [`template`] fills the holes of hand-written skeletons instead, and
+//! [`weights`] steers production choices towards what a corpus lacks.
+//! [`unsound`] writes programs rustc must reject, [`coherence`] pairs of
+//! crates it must accept or reject by the orphan and overlap rules,
+//! [`variance`] coercions it must accept or reject by the variance of
+//! the types coerced, and [`higher_ranked`] closures and functions that
+//! meet or miss higher-ranked bounds, each marked as [`marker`]
+//! describes. [`trait_alias`] writes programs that use trait aliases as
+//! trait objects and bounds.
+//! Output is Rust source text, ready to be spliced into a seed.
+
+pub mod coherence;
+pub mod expr;
+pub mod higher_ranked;
+pub mod level;
+pub mod marker;
+pub mod program;
+pub mod template;
+pub mod trait_alias;
+pub mod ty;
+pub mod unsound;
+pub mod variance;
+pub mod weights;
+
+pub use coherence::{Case, CoherenceGen};
+pub use expr::{ExprGen, Scope};
+pub use higher_ranked::HigherRankedGen;
+pub use level::{Level, UnknownLevel};
+pub use marker::Expect;
+pub use program::{ProgramGen, CONST_LINES};
+pub use template::{Hole, Template, TemplateError};
+pub use trait_alias::TraitAliasGen;
+pub use ty::Ty;
+pub use unsound::{Pattern, UnsoundGen};
+pub use variance::{Direction, Variance, VarianceGen};
+pub use weights::Weights;
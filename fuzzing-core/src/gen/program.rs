@@ -0,0 +1,174 @@
+//! Whole-program generation.
+//!
+//! [`ProgramGen`] wraps random statements in `fn main` and prints every
+//! top-level binding at the end with `{:?}`. With a deterministic
+//! [`ExprGen`] the output depends only on the program's semantics, which
+//! is what differential harnesses compare.
+//!
+//! [`ProgramGen::generate_const`] instead puts the statements in a
+//! `const fn` and evaluates it twice, once in a `const` item and once at
+//! run time, printing both results.
+//!
+//! With a fragment [`Library`], [`ProgramGen::generate`] also puts a few
+//! of its fragments before `main`. They print nothing, so the output is
+//! unchanged, but the compiler has real-world items to get through too.
+
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use rand::{Rng, RngCore};
+
+use super::expr::{ExprGen, Scope};
+use super::level::Level;
+use super::ty::Ty;
+use crate::corpus::library::Library;
+
+/// Prefixes of the two lines a [`ProgramGen::generate_const`] program
+/// prints: the result const evaluation computed, then the one the
+/// compiled code did.
+pub const CONST_LINES: [&str; 2] = ["compile time: ", "run time: "];
+
+/// Generates executable programs with observable output.
+#[derive(Debug, Clone)]
+pub struct ProgramGen {
+    pub gen: ExprGen,
+    pub max_stmts: usize,
+    /// Fragments to add to programs. Only used at [`Level::Latest`], since
+    /// the library does not record how new a compiler a fragment needs.
+    pub library: Option<Arc<Library>>,
+    /// Most fragments added to one program.
+    pub max_fragments: usize,
+}
+
+impl Default for ProgramGen {
+    fn default() -> Self {
+        ProgramGen {
+            gen: ExprGen {
+                deterministic: true,
+                ..ExprGen::default()
+            },
+            max_stmts: 8,
+            library: None,
+            max_fragments: 3,
+        }
+    }
+}
+
+impl ProgramGen {
+    pub fn generate(&self, rng: &mut dyn RngCore) -> String {
+        let mut program = self.fragments(rng);
+        let mut scope = Scope::new();
+        program.push_str("fn main() {\n");
+        for _ in 0..rng.gen_range(1..=self.max_stmts.max(1)) {
+            program.push_str("    ");
+            program.push_str(&self.gen.stmt(&mut scope, rng));
+            program.push('\n');
+        }
+        // Only bindings still in scope at the end are printed; the
+        // generator clones non-`Copy` variables on every use, so none has
+        // been moved out of.
+        for name in scope.names() {
+            program.push_str(&format!("    println!(\"{{:?}}\", {name});\n"));
+        }
+        program.push_str("}\n");
+        program
+    }
+
+    /// Up to `max_fragments` library fragments that fit together and need
+    /// no feature gates and no other edition than the level's.
+    fn fragments(&self, rng: &mut dyn RngCore) -> String {
+        let mut out = String::new();
+        let Some(library) = &self.library else {
+            return out;
+        };
+        if self.gen.level != Level::Latest || library.is_empty() {
+            return out;
+        }
+        let edition = self.gen.level.edition();
+        let mut names = HashSet::from(["main".to_owned()]);
+        for _ in 0..rng.gen_range(0..=self.max_fragments) {
+            let Some(fragment) = library.choose(rng, |f| {
+                f.features.is_empty()
+                    && f.edition.as_deref().is_none_or(|e| e == edition)
+                    && f.fits(&names)
+            }) else {
+                break;
+            };
+            names.extend(fragment.names.iter().cloned());
+            out.push_str(&fragment.source);
+            out.push('\n');
+        }
+        out
+    }
+
+    /// A program computing the same values with const evaluation and at
+    /// run time. `self.gen.constant` need not be set.
+    pub fn generate_const(&self, rng: &mut dyn RngCore) -> String {
+        let gen = ExprGen {
+            constant: true,
+            ..self.gen.clone()
+        };
+        let mut scope = Scope::new();
+        let mut body = String::new();
+        for _ in 0..rng.gen_range(1..=self.max_stmts.max(1)) {
+            body.push_str("    ");
+            body.push_str(&gen.stmt(&mut scope, rng));
+            body.push('\n');
+        }
+        let names: Vec<&str> = scope.names().collect();
+        let ty = Ty::Tuple(
+            names
+                .iter()
+                .map(|name| scope.lookup(name).expect("in scope").clone())
+                .collect(),
+        );
+        let result = match names.as_slice() {
+            [one] => format!("({one},)"),
+            names => format!("({})", names.join(", ")),
+        };
+        let [compile_time, run_time] = CONST_LINES;
+        // Calling through an opaque function pointer keeps the run-time
+        // result from being folded at compile time too.
+        format!(
+            "const fn compute() -> {ty} {{\n{body}    {result}\n}}\n\n\
+             const COMPILE_TIME: {ty} = compute();\n\n\
+             fn main() {{\n    \
+             let run_time = std::hint::black_box(compute as fn() -> {ty})();\n    \
+             println!(\"{compile_time}{{:?}}\", COMPILE_TIME);\n    \
+             println!(\"{run_time}{{:?}}\", run_time);\n\
+             }}\n"
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn programs_print_what_they_bind() {
+        let gen = ProgramGen::default();
+        for seed in 0..20 {
+            let program = gen.generate(&mut StdRng::seed_from_u64(seed));
+            syn::parse_file(&program).unwrap_or_else(|e| panic!("{e}:\n{program}"));
+            let lets = program.matches("    let ").count();
+            let prints = program.matches("println!(\"{:?}\", ").count();
+            assert!(prints <= lets, "{program}");
+        }
+    }
+
+    #[test]
+    fn const_programs_compute_twice() {
+        let gen = ProgramGen::default();
+        for seed in 0..20 {
+            let program = gen.generate_const(&mut StdRng::seed_from_u64(seed));
+            syn::parse_file(&program).unwrap_or_else(|e| panic!("{e}:\n{program}"));
+            assert!(program.contains("const COMPILE_TIME: "), "{program}");
+            for line in CONST_LINES {
+                assert!(program.contains(&format!("println!(\"{line}{{:?}}\"")));
+            }
+        }
+    }
+}
@@ -0,0 +1,265 @@
+//! Seed templates.
+//!
+//! A template is a hand-written seed with typed holes in block comments:
+//!
+//! * `/*HOLE:expr:TYPE*/`, an expression of `TYPE`, which must be a type
+//!   [`Ty`] models (`i32`, `Option<String>`, `[u8; 3]`, ...);
+//! * `/*HOLE:expr*/`, an expression of any type;
+//! * `/*HOLE:stmt*/`, a statement;
+//! * `/*HOLE:type*/`, a type.
+//!
+//! Holes are filled from [`ExprGen`] with an empty scope, so fillings
+//! never refer to the template's own variables; statement fillings may
+//! bind `v0`, `v1`, ..., so templates should not use those names for
+//! variables they read after a hole. A template is still valid
+//! Rust with its holes unfilled, which keeps it editable with ordinary
+//! tools; the skeleton decides what the compiler is pushed through and the
+//! holes vary it. [`plan`] spreads an instantiation budget over the holes
+//! and [`Template::instantiate`] enumerates the combinations.
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+use rand::RngCore;
+
+use super::expr::{ExprGen, Scope};
+use super::ty::Ty;
+
+const OPEN: &str = "/*HOLE:";
+const CLOSE: &str = "*/";
+
+/// Attempts at a distinct filling per filling wanted, before settling
+/// for fewer.
+const ATTEMPTS: usize = 4;
+
+/// What a hole is filled with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Hole {
+    Expr(Option<Ty>),
+    Stmt,
+    Type,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    Text(String),
+    Hole(Hole),
+}
+
+/// A parsed template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+    segments: Vec<Segment>,
+}
+
+/// Why a template did not parse.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TemplateError {
+    /// Byte offset of the offending hole.
+    pub offset: usize,
+    pub message: String,
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "at byte {}: {}", self.offset, self.message)
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
+impl Template {
+    pub fn parse(source: &str) -> Result<Self, TemplateError> {
+        let mut segments = Vec::new();
+        let mut rest = source;
+        let mut offset = 0;
+        while let Some(start) = rest.find(OPEN) {
+            let error = |message: String| TemplateError {
+                offset: offset + start,
+                message,
+            };
+            let body = &rest[start + OPEN.len()..];
+            let end = body
+                .find(CLOSE)
+                .ok_or_else(|| error("unterminated hole".to_owned()))?;
+            let spec = body[..end].trim();
+            let hole = match spec.split_once(':') {
+                None if spec == "expr" => Hole::Expr(None),
+                None if spec == "stmt" => Hole::Stmt,
+                None if spec == "type" => Hole::Type,
+                Some(("expr", ty)) => {
+                    let ty = syn::parse_str::<syn::Type>(ty)
+                        .ok()
+                        .and_then(|ty| Ty::from_syn(&ty))
+                        .ok_or_else(|| error(format!("cannot generate values of type `{ty}`")))?;
+                    Hole::Expr(Some(ty))
+                }
+                _ => return Err(error(format!("unknown hole `{spec}`"))),
+            };
+            segments.push(Segment::Text(rest[..start].to_owned()));
+            segments.push(Segment::Hole(hole));
+            let consumed = start + OPEN.len() + end + CLOSE.len();
+            rest = &rest[consumed..];
+            offset += consumed;
+        }
+        segments.push(Segment::Text(rest.to_owned()));
+        Ok(Template { segments })
+    }
+
+    /// The holes, in source order.
+    pub fn holes(&self) -> impl Iterator<Item = &Hole> {
+        self.segments.iter().filter_map(|segment| match segment {
+            Segment::Hole(hole) => Some(hole),
+            Segment::Text(_) => None,
+        })
+    }
+
+    /// Up to `budget` distinct instantiations: [`plan`] decides how many
+    /// fillings each hole gets, and every combination of them is
+    /// produced. Holes for which the generator comes up with fewer
+    /// distinct fillings than planned make for fewer instantiations.
+    pub fn instantiate(&self, gen: &ExprGen, budget: usize, rng: &mut dyn RngCore) -> Vec<String> {
+        let holes: Vec<&Hole> = self.holes().collect();
+        let fillings: Vec<Vec<String>> = plan(holes.len(), budget)
+            .into_iter()
+            .zip(&holes)
+            .map(|(count, hole)| fill(gen, hole, count, rng))
+            .collect();
+        if fillings.iter().any(Vec::is_empty) || budget == 0 {
+            return Vec::new();
+        }
+        // A mixed-radix counter over the fillings of every hole.
+        let mut digits = vec![0; fillings.len()];
+        let mut out = Vec::new();
+        loop {
+            let mut text = String::new();
+            let mut hole = 0;
+            for segment in &self.segments {
+                match segment {
+                    Segment::Text(t) => text.push_str(t),
+                    Segment::Hole(_) => {
+                        text.push_str(&fillings[hole][digits[hole]]);
+                        hole += 1;
+                    }
+                }
+            }
+            out.push(text);
+            let mut i = 0;
+            loop {
+                if i == digits.len() {
+                    return out;
+                }
+                digits[i] += 1;
+                if digits[i] < fillings[i].len() {
+                    break;
+                }
+                digits[i] = 0;
+                i += 1;
+            }
+        }
+    }
+}
+
+/// How many fillings each of `holes` holes gets so that every
+/// combination fits in `budget`: counts are raised one hole at a time,
+/// round robin, while the product stays within it. With no holes the
+/// plan is empty and the template is its one instantiation.
+pub fn plan(holes: usize, budget: usize) -> Vec<usize> {
+    let mut counts = vec![1; holes];
+    if budget == 0 {
+        return counts;
+    }
+    let mut product = 1;
+    loop {
+        let mut grew = false;
+        for count in counts.iter_mut() {
+            let next = product / *count * (*count + 1);
+            if next <= budget {
+                product = next;
+                *count += 1;
+                grew = true;
+            }
+        }
+        if !grew {
+            return counts;
+        }
+    }
+}
+
+/// Up to `count` distinct fillings of `hole`.
+fn fill(gen: &ExprGen, hole: &Hole, count: usize, rng: &mut dyn RngCore) -> Vec<String> {
+    let mut seen = BTreeSet::new();
+    let mut fillings = Vec::new();
+    for _ in 0..count * ATTEMPTS {
+        if fillings.len() == count {
+            break;
+        }
+        let filling = match hole {
+            Hole::Expr(Some(ty)) => gen.expr(ty, &Scope::new(), rng),
+            Hole::Expr(None) => gen.any_expr(&Scope::new(), rng).1,
+            Hole::Stmt => gen.stmt(&mut Scope::new(), rng),
+            Hole::Type => Ty::random(2, rng).to_string(),
+        };
+        if seen.insert(filling.clone()) {
+            fillings.push(filling);
+        }
+    }
+    fillings
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn holes_are_typed_block_comments() {
+        let template = Template::parse(
+            "fn f() -> i32 { /*HOLE:stmt*/ /*HOLE:expr: i32 */ }\ntype T = /*HOLE:type*/;\n",
+        )
+        .expect("parses");
+        let holes: Vec<&Hole> = template.holes().collect();
+        assert_eq!(
+            holes,
+            [&Hole::Stmt, &Hole::Expr(Some(Ty::Int("i32"))), &Hole::Type]
+        );
+
+        let unknown = Template::parse("fn f() { /*HOLE:item*/ }").expect_err("unknown");
+        assert_eq!(unknown.offset, 9);
+        assert_eq!(unknown.to_string(), "at byte 9: unknown hole `item`");
+        let open = Template::parse("/*HOLE:expr").expect_err("unterminated");
+        assert_eq!(open.message, "unterminated hole");
+        let ty = Template::parse("/*HOLE:expr:dyn Fn()*/").expect_err("unmodelled type");
+        assert!(ty.message.contains("dyn Fn()"), "{ty}");
+    }
+
+    #[test]
+    fn plans_spread_the_budget_round_robin() {
+        assert_eq!(plan(2, 6), [3, 2]);
+        assert_eq!(plan(3, 8), [2, 2, 2]);
+        assert_eq!(plan(2, 0), [1, 1]);
+        assert!(plan(0, 5).is_empty());
+    }
+
+    #[test]
+    fn instantiations_are_distinct_and_valid() {
+        let template = Template::parse(
+            "fn main() {\n    /*HOLE:stmt*/\n    let _x: u8 = /*HOLE:expr:u8*/;\n}\n",
+        )
+        .expect("parses");
+        let gen = ExprGen::default();
+        let mut rng = StdRng::seed_from_u64(3);
+        let programs = template.instantiate(&gen, 6, &mut rng);
+        assert!(!programs.is_empty() && programs.len() <= 6, "{programs:?}");
+        let distinct: BTreeSet<&String> = programs.iter().collect();
+        assert_eq!(distinct.len(), programs.len());
+        for program in &programs {
+            syn::parse_file(program).expect("instantiations parse");
+            assert!(!program.contains(OPEN), "{program}");
+        }
+        let fixed = Template::parse("fn main() {}\n").expect("parses");
+        assert_eq!(fixed.instantiate(&gen, 4, &mut rng), ["fn main() {}\n"]);
+        assert!(template.instantiate(&gen, 0, &mut rng).is_empty());
+    }
+}
@@ -0,0 +1,460 @@
+//! Programs built on `#![feature(trait_alias)]`.
+//!
+//! A trait alias such as `trait Alias = Shape + Send;` stands for its
+//! bounds wherever it is used, so rustc has to expand it before checking
+//! a `dyn Alias`: that there is at most one trait in it besides auto
+//! traits, that this trait is dyn compatible, and that the auto traits it
+//! adds hold for what is coerced into it. Aliases of aliases, generic
+//! aliases and aliases with `where` clauses each make the expansion
+//! longer, and the checks for trait objects are written against the
+//! traits themselves first.
+//!
+//! [`TraitAliasGen`] writes a program around one [`Case`], with the
+//! traits, auto traits, pointer and spelling of each bound random.
+//! Programs of an accepting case print what the trait object's methods
+//! return, so they are seeds for every harness. Programs of a rejecting
+//! case break a rule that keeps trait objects sound, and start with the
+//! [`unsound::MARKER`](super::unsound::MARKER), so the `soundness`
+//! harness reports rustc accepting them.
+
+use std::fmt;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+
+use super::unsound;
+
+/// The traits every program implements for `Square`: two dyn-compatible
+/// ones, a generic one, and three that are not dyn compatible, each for
+/// a different reason. `Borrowed` holds a reference and `Shared` is not
+/// `Send`.
+const PRELUDE: &str = "\
+#![feature(trait_alias)]
+#![allow(dead_code)]
+
+use std::rc::Rc;
+use std::sync::Arc;
+
+trait Shape {
+    fn area(&self) -> u32;
+}
+
+trait Named {
+    fn name(&self) -> String;
+}
+
+trait Convert<T> {
+    fn convert(&self) -> T;
+}
+
+trait Make {
+    fn make() -> Self;
+}
+
+trait Same {
+    fn same(&self, other: &Self) -> bool;
+}
+
+trait Each {
+    fn each<T>(&self, t: T) -> u32;
+}
+
+struct Square(u32);
+
+impl Shape for Square {
+    fn area(&self) -> u32 {
+        self.0 * self.0
+    }
+}
+
+impl Named for Square {
+    fn name(&self) -> String {
+        format!(\"square {}\", self.0)
+    }
+}
+
+impl Convert<u64> for Square {
+    fn convert(&self) -> u64 {
+        u64::from(self.0) + 1
+    }
+}
+
+impl Make for Square {
+    fn make() -> Self {
+        Square(1)
+    }
+}
+
+impl Same for Square {
+    fn same(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl Each for Square {
+    fn each<T>(&self, _t: T) -> u32 {
+        self.0
+    }
+}
+
+struct Borrowed<'a>(&'a u32);
+
+impl Shape for Borrowed<'_> {
+    fn area(&self) -> u32 {
+        *self.0
+    }
+}
+
+struct Shared(Rc<u32>);
+
+impl Shape for Shared {
+    fn area(&self) -> u32 {
+        *self.0
+    }
+}
+";
+
+/// Auto traits an alias or a trait object may add.
+const AUTO_TRAITS: [&str; 4] = ["Send", "Sync", "Unpin", "std::panic::UnwindSafe"];
+
+/// The traits that are not dyn compatible.
+const NOT_DYN_COMPATIBLE: [&str; 3] = ["Make", "Same", "Each"];
+
+/// One use of trait aliases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Case {
+    /// A trait object of an alias of one trait and auto traits.
+    AutoTraits,
+    /// Likewise, with more auto traits added to the trait object.
+    ExtraAutoTraits,
+    /// A trait object of an alias of an alias.
+    NestedAlias,
+    /// A trait object of a generic alias.
+    GenericAlias,
+    /// A trait object of an alias with a lifetime parameter, holding a
+    /// reference of that lifetime.
+    LifetimeAlias,
+    /// A trait object of an alias with a `where` clause.
+    WhereClauseAlias,
+    /// A trait object of an alias binding `Iterator::Item`.
+    AssociatedBinding,
+    /// An alias of traits that are not dyn compatible, as a bound.
+    NotDynCompatibleBound,
+    /// An alias of two traits, as a bound.
+    TwoTraitsBound,
+    /// A trait object of an alias, or of aliases, with two traits that
+    /// are not auto traits (E0225).
+    TwoTraits,
+    /// A trait object of an alias of a trait that is not dyn compatible
+    /// (E0038).
+    NotDynCompatible,
+    /// A value that is not `Send` coerced to a trait object of an alias
+    /// with `Send` (E0277).
+    MissingAutoTrait,
+}
+
+impl Case {
+    pub const ALL: [Case; 12] = [
+        Case::AutoTraits,
+        Case::ExtraAutoTraits,
+        Case::NestedAlias,
+        Case::GenericAlias,
+        Case::LifetimeAlias,
+        Case::WhereClauseAlias,
+        Case::AssociatedBinding,
+        Case::NotDynCompatibleBound,
+        Case::TwoTraitsBound,
+        Case::TwoTraits,
+        Case::NotDynCompatible,
+        Case::MissingAutoTrait,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Case::AutoTraits => "auto-traits",
+            Case::ExtraAutoTraits => "extra-auto-traits",
+            Case::NestedAlias => "nested-alias",
+            Case::GenericAlias => "generic-alias",
+            Case::LifetimeAlias => "lifetime-alias",
+            Case::WhereClauseAlias => "where-clause-alias",
+            Case::AssociatedBinding => "associated-binding",
+            Case::NotDynCompatibleBound => "not-dyn-compatible-bound",
+            Case::TwoTraitsBound => "two-traits-bound",
+            Case::TwoTraits => "two-traits",
+            Case::NotDynCompatible => "not-dyn-compatible",
+            Case::MissingAutoTrait => "missing-auto-trait",
+        }
+    }
+
+    /// Whether rustc must accept a program encoding this case.
+    pub fn accepted(self) -> bool {
+        !matches!(
+            self,
+            Case::TwoTraits | Case::NotDynCompatible | Case::MissingAutoTrait
+        )
+    }
+}
+
+impl fmt::Display for Case {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Writes programs that use trait aliases as trait objects and bounds.
+#[derive(Debug, Clone, Default)]
+pub struct TraitAliasGen;
+
+impl TraitAliasGen {
+    /// A program encoding a random [`Case`].
+    pub fn generate(&self, rng: &mut dyn RngCore) -> String {
+        let case = *Case::ALL.choose(rng).expect("there are cases");
+        self.generate_case(case, rng)
+    }
+
+    /// A program encoding `case`.
+    pub fn generate_case(&self, case: Case, rng: &mut dyn RngCore) -> String {
+        let side = rng.gen_range(1..=9);
+        let square = format!("Square({side})");
+        let autos = auto_traits(rng);
+        let (items, body) = match case {
+            Case::AutoTraits | Case::ExtraAutoTraits => {
+                let extra = if case == Case::ExtraAutoTraits {
+                    let more: Vec<&str> = AUTO_TRAITS
+                        .iter()
+                        .copied()
+                        .filter(|auto| !autos.contains(auto) && rng.gen_bool(0.5))
+                        .collect();
+                    let more = if more.is_empty() {
+                        vec![*AUTO_TRAITS
+                            .iter()
+                            .find(|auto| !autos.contains(auto))
+                            .unwrap_or(&"Send")]
+                    } else {
+                        more
+                    };
+                    format!(" + {}", more.join(" + "))
+                } else {
+                    String::new()
+                };
+                let (alias, method) = principal(rng);
+                (
+                    format!("trait Alias = {alias}{};\n", plus(&autos)),
+                    object(&format!("dyn Alias{extra}"), &square, method, rng),
+                )
+            }
+            Case::NestedAlias => {
+                let (alias, method) = principal(rng);
+                let outer = auto_traits(rng);
+                (
+                    format!(
+                        "trait Inner = {alias}{};\n\ntrait Outer = Inner{};\n",
+                        plus(&autos),
+                        plus(&outer)
+                    ),
+                    object("dyn Outer", &square, method, rng),
+                )
+            }
+            Case::GenericAlias => (
+                format!("trait Alias<T> = Convert<T>{};\n", plus(&autos)),
+                object("dyn Alias<u64>", &square, "convert", rng),
+            ),
+            Case::WhereClauseAlias => (
+                format!(
+                    "trait Alias<T> = Convert<T>{}\nwhere\n    T: Copy + Into<u64>;\n",
+                    plus(&autos)
+                ),
+                object("dyn Alias<u64>", &square, "convert", rng),
+            ),
+            Case::LifetimeAlias => (
+                format!(
+                    "trait Alias<'a> = Shape + 'a{};\n\n\
+                     fn borrow<'a>(side: &'a u32) -> Box<dyn Alias<'a> + 'a> {{\n    \
+                     Box::new(Borrowed(side))\n}}\n",
+                    plus(&autos)
+                ),
+                format!("let side = {side};\n    println!(\"{{}}\", borrow(&side).area());"),
+            ),
+            Case::AssociatedBinding => (
+                format!("trait Alias = Iterator<Item = u32>{};\n", plus(&autos)),
+                format!(
+                    "let items: Box<dyn Alias> = Box::new((1..={side}).map(|i| i * i));\n    \
+                     println!(\"{{}}\", items.sum::<u32>());"
+                ),
+            ),
+            Case::NotDynCompatibleBound => {
+                let mut traits = NOT_DYN_COMPATIBLE.to_vec();
+                traits.shuffle(rng);
+                traits.truncate(rng.gen_range(1..=3));
+                let body: Vec<&str> = traits
+                    .iter()
+                    .map(|t| match *t {
+                        "Make" => "u32::from(std::mem::size_of_val(&T::make()) > 0)",
+                        "Same" => "u32::from(x.same(x))",
+                        _ => "x.each(\"each\")",
+                    })
+                    .collect();
+                (
+                    format!(
+                        "trait Alias = {}{};\n\n{}",
+                        traits.join(" + "),
+                        plus(&autos),
+                        bounded("T", "Alias", &body.join(" + "), rng)
+                    ),
+                    format!("println!(\"{{}}\", check(&{square}));"),
+                )
+            }
+            Case::TwoTraitsBound => (
+                format!(
+                    "trait Alias = Shape + Named{};\n\n{}",
+                    plus(&autos),
+                    bounded("T", "Alias", "x.area() + x.name().len() as u32", rng)
+                ),
+                format!("println!(\"{{}}\", check(&{square}));"),
+            ),
+            Case::TwoTraits => {
+                let (items, object_ty) = match rng.gen_range(0..3) {
+                    0 => (
+                        format!("trait Alias = Shape + Named{};\n", plus(&autos)),
+                        "dyn Alias",
+                    ),
+                    1 => (
+                        format!(
+                            "trait First = Shape{};\n\ntrait Second = Named;\n\n\
+                             trait Alias = First + Second;\n",
+                            plus(&autos)
+                        ),
+                        "dyn Alias",
+                    ),
+                    _ => (
+                        format!("trait Alias = Shape{};\n", plus(&autos)),
+                        "dyn Alias + Named",
+                    ),
+                };
+                (items, object(object_ty, &square, "area", rng))
+            }
+            Case::NotDynCompatible => {
+                let culprit = *NOT_DYN_COMPATIBLE.choose(rng).expect("there are traits");
+                (
+                    format!("trait Alias = {culprit}{};\n", plus(&autos)),
+                    format!("let _object: Box<dyn Alias> = Box::new({square});"),
+                )
+            }
+            Case::MissingAutoTrait => {
+                let needed = ["Send", "Sync"].choose(rng).expect("there are auto traits");
+                let mut autos = autos;
+                autos.retain(|auto| auto != needed);
+                autos.push(needed);
+                autos.shuffle(rng);
+                (
+                    format!("trait Alias = Shape{};\n", plus(&autos)),
+                    format!(
+                        "let object: Box<dyn Alias> = Box::new(Shared(Rc::new({side})));\n    \
+                         println!(\"{{}}\", object.area());"
+                    ),
+                )
+            }
+        };
+        let marker = if case.accepted() {
+            String::new()
+        } else {
+            format!("{}trait-alias-{case}\n", unsound::MARKER)
+        };
+        format!("{marker}{PRELUDE}\n{items}\nfn main() {{\n    {body}\n}}\n")
+    }
+}
+
+/// A random set of auto traits, possibly empty.
+fn auto_traits(rng: &mut dyn RngCore) -> Vec<&'static str> {
+    AUTO_TRAITS
+        .iter()
+        .copied()
+        .filter(|_| rng.gen_bool(0.4))
+        .collect()
+}
+
+/// `bounds` each preceded by ` + `.
+fn plus(bounds: &[&str]) -> String {
+    bounds.iter().map(|bound| format!(" + {bound}")).collect()
+}
+
+/// A dyn-compatible trait and a method of it returning something
+/// printable.
+fn principal(rng: &mut dyn RngCore) -> (&'static str, &'static str) {
+    *[
+        ("Shape", "area"),
+        ("Named", "name"),
+        ("Convert<u64>", "convert"),
+    ]
+    .choose(rng)
+    .expect("there are traits")
+}
+
+/// Statements that put `value` behind a pointer to `object_ty` and print
+/// what `method` returns.
+fn object(object_ty: &str, value: &str, method: &str, rng: &mut dyn RngCore) -> String {
+    let (ty, init) = match rng.gen_range(0..4) {
+        0 => (format!("Box<{object_ty}>"), format!("Box::new({value})")),
+        1 if object_ty.contains('+') => (format!("&({object_ty})"), format!("&{value}")),
+        1 => (format!("&{object_ty}"), format!("&{value}")),
+        2 => (format!("Rc<{object_ty}>"), format!("Rc::new({value})")),
+        _ => (format!("Arc<{object_ty}>"), format!("Arc::new({value})")),
+    };
+    format!("let object: {ty} = {init};\n    println!(\"{{}}\", object.{method}());")
+}
+
+/// A function `check` of `x: &T`, with `T` bounded by `bound` in one of
+/// the ways a bound can be written, returning `body`.
+fn bounded(param: &str, bound: &str, body: &str, rng: &mut dyn RngCore) -> String {
+    match rng.gen_range(0..3) {
+        0 => format!("fn check<{param}: {bound}>(x: &{param}) -> u32 {{\n    {body}\n}}\n"),
+        1 => format!(
+            "fn check<{param}>(x: &{param}) -> u32\nwhere\n    {param}: {bound},\n{{\n    {body}\n}}\n"
+        ),
+        _ => format!(
+            "fn check<{param}: ?Sized>(x: &{param}) -> u32\nwhere\n    {param}: {bound} + Sized,\n{{\n    {body}\n}}\n"
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::{SplitRng, Stream};
+
+    #[test]
+    fn only_rejecting_cases_are_marked() {
+        let streams = SplitRng::new(0);
+        for (i, case) in Case::ALL.into_iter().enumerate() {
+            for j in 0..8 {
+                let mut rng = streams.rng(Stream::Generator, (i * 8 + j) as u64);
+                let program = TraitAliasGen.generate_case(case, &mut rng);
+                let expected = format!("trait-alias-{case}");
+                let marked = unsound::marked(&program);
+                if case.accepted() {
+                    assert_eq!(marked, None, "{program}");
+                } else {
+                    assert_eq!(marked, Some(expected.as_str()), "{program}");
+                }
+                if let Err(e) = syn::parse_file(&program) {
+                    panic!("{case}: {e}\n{program}");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn missing_auto_traits_are_required_by_the_alias() {
+        let streams = SplitRng::new(1);
+        for i in 0..16 {
+            let mut rng = streams.rng(Stream::Generator, i);
+            let program = TraitAliasGen.generate_case(Case::MissingAutoTrait, &mut rng);
+            let alias = program
+                .lines()
+                .find(|line| line.starts_with("trait Alias = "))
+                .expect("declares the alias");
+            assert!(alias.contains("Send") || alias.contains("Sync"), "{alias}");
+            assert!(program.contains("Shared(Rc::new("), "{program}");
+        }
+    }
+}
@@ -0,0 +1,206 @@
+//! The generator's model of Rust types.
+//!
+//! Only types the generator knows how to build values of are modelled;
+//! everything else is "unknown" to it and represented as `None` by
+//! [`Ty::from_syn`].
+
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+use std::fmt;
+
+pub const INTS: &[&str] = &[
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+];
+pub const FLOATS: &[&str] = &["f32", "f64"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Ty {
+    Unit,
+    Bool,
+    Char,
+    Int(&'static str),
+    Float(&'static str),
+    /// `&'static str`
+    Str,
+    String,
+    Tuple(Vec<Ty>),
+    Array(Box<Ty>, usize),
+    Option(Box<Ty>),
+    Vec(Box<Ty>),
+}
+
+impl Ty {
+    /// A random type, nesting compound types at most `depth` levels.
+    pub fn random(depth: usize, rng: &mut dyn RngCore) -> Ty {
+        let scalar = rng.gen_range(0..7);
+        if depth == 0 || rng.gen_bool(0.7) {
+            return match scalar {
+                0 => Ty::Bool,
+                1 => Ty::Char,
+                2 => Ty::Float(FLOATS.choose(rng).unwrap()),
+                3 => Ty::Str,
+                4 => Ty::String,
+                _ => Ty::Int(INTS.choose(rng).unwrap()),
+            };
+        }
+        match rng.gen_range(0..5) {
+            0 => Ty::Tuple(
+                (0..rng.gen_range(0..4))
+                    .map(|_| Ty::random(depth - 1, rng))
+                    .collect(),
+            ),
+            1 => Ty::Array(Box::new(Ty::random(depth - 1, rng)), rng.gen_range(0..5)),
+            2 => Ty::Option(Box::new(Ty::random(depth - 1, rng))),
+            3 => Ty::Vec(Box::new(Ty::random(depth - 1, rng))),
+            _ => Ty::Unit,
+        }
+    }
+
+    /// Maps a syntactic type onto the model, if it is one the generator
+    /// understands.
+    pub fn from_syn(ty: &syn::Type) -> Option<Ty> {
+        match ty {
+            syn::Type::Tuple(t) if t.elems.is_empty() => Some(Ty::Unit),
+            syn::Type::Tuple(t) => t
+                .elems
+                .iter()
+                .map(Ty::from_syn)
+                .collect::<Option<_>>()
+                .map(Ty::Tuple),
+            syn::Type::Paren(p) => Ty::from_syn(&p.elem),
+            syn::Type::Array(a) => {
+                let len = match &a.len {
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Int(n),
+                        ..
+                    }) => n.base10_parse().ok()?,
+                    _ => return None,
+                };
+                Some(Ty::Array(Box::new(Ty::from_syn(&a.elem)?), len))
+            }
+            syn::Type::Reference(r) if r.mutability.is_none() => match &*r.elem {
+                syn::Type::Path(p) if p.path.is_ident("str") => Some(Ty::Str),
+                _ => None,
+            },
+            syn::Type::Path(p) if p.qself.is_none() => {
+                let last = p.path.segments.last()?;
+                let name = last.ident.to_string();
+                let arg = match &last.arguments {
+                    syn::PathArguments::AngleBracketed(a) if a.args.len() == 1 => {
+                        match &a.args[0] {
+                            syn::GenericArgument::Type(t) => Some(Ty::from_syn(t)?),
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                match (name.as_str(), arg) {
+                    ("bool", None) => Some(Ty::Bool),
+                    ("char", None) => Some(Ty::Char),
+                    ("String", None) => Some(Ty::String),
+                    ("Option", Some(t)) => Some(Ty::Option(Box::new(t))),
+                    ("Vec", Some(t)) => Some(Ty::Vec(Box::new(t))),
+                    (n, None) => INTS
+                        .iter()
+                        .find(|i| **i == n)
+                        .map(|i| Ty::Int(i))
+                        .or_else(|| FLOATS.iter().find(|f| **f == n).map(|f| Ty::Float(f))),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Whether values of this type can be built in a `const fn`, which
+    /// has no heap.
+    pub fn is_const(&self) -> bool {
+        match self {
+            Ty::String | Ty::Vec(_) => false,
+            Ty::Tuple(ts) => ts.iter().all(Ty::is_const),
+            Ty::Array(t, _) | Ty::Option(t) => t.is_const(),
+            _ => true,
+        }
+    }
+
+    /// Whether `==` and `{:?}` work on values of this type.
+    pub fn is_comparable(&self) -> bool {
+        match self {
+            Ty::Float(_) => false,
+            Ty::Tuple(ts) => ts.iter().all(Ty::is_comparable),
+            Ty::Array(t, _) | Ty::Option(t) | Ty::Vec(t) => t.is_comparable(),
+            _ => true,
+        }
+    }
+}
+
+impl fmt::Display for Ty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Ty::Unit => f.write_str("()"),
+            Ty::Bool => f.write_str("bool"),
+            Ty::Char => f.write_str("char"),
+            Ty::Int(name) | Ty::Float(name) => f.write_str(name),
+            Ty::Str => f.write_str("&'static str"),
+            Ty::String => f.write_str("String"),
+            Ty::Tuple(ts) if ts.len() == 1 => write!(f, "({},)", ts[0]),
+            Ty::Tuple(ts) => {
+                f.write_str("(")?;
+                for (i, t) in ts.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{t}")?;
+                }
+                f.write_str(")")
+            }
+            Ty::Array(t, n) => write!(f, "[{t}; {n}]"),
+            Ty::Option(t) => write!(f, "Option<{t}>"),
+            Ty::Vec(t) => write!(f, "Vec<{t}>"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn random_types_read_back_from_their_syntax() {
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..200 {
+            let ty = Ty::random(3, &mut rng);
+            let text = ty.to_string();
+            let syntax: syn::Type = syn::parse_str(&text).expect("parses");
+            // The empty tuple reads back as `Unit`, which is written the same.
+            let read = Ty::from_syn(&syntax).expect("a modelled type");
+            assert_eq!(read.to_string(), text);
+        }
+    }
+
+    #[test]
+    fn types_outside_the_model_are_unknown() {
+        for ty in [
+            "&mut str",
+            "Box<u8>",
+            "[u8; N]",
+            "HashMap<u8, u8>",
+            "&String",
+        ] {
+            let syntax: syn::Type = syn::parse_str(ty).expect("parses");
+            assert_eq!(Ty::from_syn(&syntax), None, "{ty}");
+        }
+    }
+
+    #[test]
+    fn heap_types_are_not_const_and_floats_not_comparable() {
+        let vec = Ty::Option(Box::new(Ty::Vec(Box::new(Ty::Bool))));
+        assert!(!vec.is_const());
+        assert!(vec.is_comparable());
+        let floats = Ty::Tuple(vec![Ty::Int("u8"), Ty::Float("f32")]);
+        assert!(floats.is_const());
+        assert!(!floats.is_comparable());
+    }
+}
@@ -0,0 +1,270 @@
+//! Programs that must not compile.
+//!
+//! [`UnsoundGen`] writes programs that each encode one pattern the borrow
+//! checker, variance or the `Send`/`Sync` rules exist to reject: a
+//! reference to a local that outlives it, a short lifetime smuggled into
+//! a `'static` slot through an invariant `&mut` or `Cell`, use after move,
+//! aliased `&mut`, a borrow escaping into a closure or thread, an `Rc`
+//! sent to another thread. Types and values come from [`ExprGen`], and
+//! the offending reference passes through a random number of calls to an
+//! identity function, so the checks see many shapes of each violation.
+//!
+//! Every program starts with [`MARKER`] and the name of its [`Pattern`].
+//! A rustc that accepts one has a soundness hole; the `soundness` harness
+//! reports it.
+
+use std::fmt;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+
+use super::expr::{ExprGen, Scope};
+use super::ty::Ty;
+
+/// The first line of every program [`UnsoundGen`] writes, followed by
+/// the pattern's name.
+pub const MARKER: &str = "// rustc-fuzz: must not compile: ";
+
+/// Most calls to `id` the offending reference is passed through.
+const MAX_WRAPS: usize = 2;
+
+/// A way of breaking memory safety that rustc must reject.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Pattern {
+    /// A function returns a reference to its own local (E0515).
+    ReturnLocal,
+    /// A `'static` struct holds a reference to a local (E0515).
+    StructLocal,
+    /// A short reference is stored through `&mut &'static T`, which is
+    /// invariant in `T`'s lifetime.
+    MutVariance,
+    /// Likewise through `&Cell<&'static T>`.
+    CellVariance,
+    /// A value is used after it was moved (E0382).
+    UseAfterMove,
+    /// Two live `&mut` to the same place (E0499).
+    AliasedMut,
+    /// A place is assigned while borrowed (E0506).
+    AssignBorrowed,
+    /// A vector grows while it is being iterated (E0502).
+    IteratorInvalidation,
+    /// A `'static` closure borrows a local (E0373).
+    ClosureEscape,
+    /// A spawned thread borrows a local (E0373).
+    ThreadBorrow,
+    /// An `Rc` is moved to another thread (E0277).
+    RcSend,
+    /// A `RefCell` is passed where `Sync` is required (E0277).
+    RefCellSync,
+}
+
+impl Pattern {
+    pub const ALL: [Pattern; 12] = [
+        Pattern::ReturnLocal,
+        Pattern::StructLocal,
+        Pattern::MutVariance,
+        Pattern::CellVariance,
+        Pattern::UseAfterMove,
+        Pattern::AliasedMut,
+        Pattern::AssignBorrowed,
+        Pattern::IteratorInvalidation,
+        Pattern::ClosureEscape,
+        Pattern::ThreadBorrow,
+        Pattern::RcSend,
+        Pattern::RefCellSync,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Pattern::ReturnLocal => "return-local",
+            Pattern::StructLocal => "struct-local",
+            Pattern::MutVariance => "mut-variance",
+            Pattern::CellVariance => "cell-variance",
+            Pattern::UseAfterMove => "use-after-move",
+            Pattern::AliasedMut => "aliased-mut",
+            Pattern::AssignBorrowed => "assign-borrowed",
+            Pattern::IteratorInvalidation => "iterator-invalidation",
+            Pattern::ClosureEscape => "closure-escape",
+            Pattern::ThreadBorrow => "thread-borrow",
+            Pattern::RcSend => "rc-send",
+            Pattern::RefCellSync => "refcell-sync",
+        }
+    }
+
+    /// The program, with `{T}` the type, `{V}` and `{W}` two values of
+    /// it, and `{wrap}(` ... `)` the identity calls around the offending
+    /// reference.
+    fn skeleton(self) -> &'static str {
+        match self {
+            Pattern::ReturnLocal => {
+                "fn dangle<'a>() -> &'a {T} {\n    let local: {T} = {V};\n    {wrap}(&local)\n}\n\n\
+                 fn main() {\n    println!(\"{:?}\", dangle());\n}\n"
+            }
+            Pattern::StructLocal => {
+                "struct Holder<'a>(&'a {T});\n\n\
+                 fn make() -> Holder<'static> {\n    let local: {T} = {V};\n    Holder({wrap}(&local))\n}\n\n\
+                 fn main() {\n    println!(\"{:?}\", make().0);\n}\n"
+            }
+            Pattern::MutVariance => {
+                "fn extend<'a>(slot: &mut &'static {T}, short: &'a {T}) {\n    *slot = {wrap}(short);\n}\n\n\
+                 fn main() {\n    let mut slot: &'static {T} = Box::leak(Box::new({V}));\n    \
+                 {\n        let local: {T} = {W};\n        extend(&mut slot, &local);\n    }\n    \
+                 println!(\"{:?}\", slot);\n}\n"
+            }
+            Pattern::CellVariance => {
+                "use std::cell::Cell;\n\n\
+                 fn extend<'a>(cell: &Cell<&'static {T}>, short: &'a {T}) {\n    cell.set({wrap}(short));\n}\n\n\
+                 fn main() {\n    let cell: Cell<&'static {T}> = Cell::new(Box::leak(Box::new({V})));\n    \
+                 {\n        let local: {T} = {W};\n        extend(&cell, &local);\n    }\n    \
+                 println!(\"{:?}\", cell.get());\n}\n"
+            }
+            Pattern::UseAfterMove => {
+                "fn main() {\n    let moved: Vec<{T}> = vec![{V}];\n    let taken = {wrap}(moved);\n    \
+                 drop(taken);\n    println!(\"{:?}\", moved);\n}\n"
+            }
+            Pattern::AliasedMut => {
+                "fn main() {\n    let mut place: {T} = {V};\n    let first = &mut place;\n    \
+                 let second = {wrap}(&mut place);\n    *first = {W};\n    println!(\"{:?}\", second);\n}\n"
+            }
+            Pattern::AssignBorrowed => {
+                "fn main() {\n    let mut place: {T} = {V};\n    let borrowed = {wrap}(&place);\n    \
+                 place = {W};\n    println!(\"{:?} {:?}\", borrowed, place);\n}\n"
+            }
+            Pattern::IteratorInvalidation => {
+                "fn main() {\n    let mut items: Vec<{T}> = vec![{V}];\n    \
+                 for item in {wrap}(&items) {\n        items.push(item.clone());\n    }\n    \
+                 println!(\"{:?}\", items);\n}\n"
+            }
+            Pattern::ClosureEscape => {
+                "fn make() -> Box<dyn Fn() -> usize> {\n    let local: {T} = {V};\n    \
+                 Box::new(|| std::mem::size_of_val({wrap}(&local)))\n}\n\n\
+                 fn main() {\n    println!(\"{}\", make()());\n}\n"
+            }
+            Pattern::ThreadBorrow => {
+                "fn main() {\n    let local: {T} = {V};\n    \
+                 let handle = std::thread::spawn(|| println!(\"{:?}\", {wrap}(&local)));\n    \
+                 handle.join().unwrap();\n}\n"
+            }
+            Pattern::RcSend => {
+                "use std::rc::Rc;\n\n\
+                 fn main() {\n    let shared: Rc<{T}> = Rc::new({V});\n    let kept = Rc::clone(&shared);\n    \
+                 let handle = std::thread::spawn(move || println!(\"{:?}\", {wrap}(shared)));\n    \
+                 handle.join().unwrap();\n    println!(\"{:?}\", kept);\n}\n"
+            }
+            Pattern::RefCellSync => {
+                "use std::cell::RefCell;\n\n\
+                 fn share<X: Sync>(x: &X) -> &X {\n    x\n}\n\n\
+                 fn main() {\n    let cell: RefCell<{T}> = RefCell::new({V});\n    \
+                 println!(\"{:?}\", share({wrap}(&cell)));\n}\n"
+            }
+        }
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The pattern a program is marked with, if it is marked.
+pub fn marked(source: &str) -> Option<&str> {
+    source
+        .lines()
+        .next()?
+        .strip_prefix(MARKER)
+        .map(str::trim)
+        .filter(|pattern| !pattern.is_empty())
+}
+
+/// Writes programs that must not compile.
+#[derive(Debug, Clone, Default)]
+pub struct UnsoundGen {
+    /// Builds the values the programs hold.
+    pub gen: ExprGen,
+}
+
+impl UnsoundGen {
+    /// A program encoding a random [`Pattern`].
+    pub fn generate(&self, rng: &mut dyn RngCore) -> String {
+        let pattern = *Pattern::ALL.choose(rng).expect("there are patterns");
+        self.generate_pattern(pattern, rng)
+    }
+
+    /// A program encoding `pattern`.
+    pub fn generate_pattern(&self, pattern: Pattern, rng: &mut dyn RngCore) -> String {
+        let ty = Ty::random(2, rng);
+        let scope = Scope::new();
+        let v = self.gen.expr(&ty, &scope, rng);
+        let w = self.gen.expr(&ty, &scope, rng);
+        let program = wrap(pattern.skeleton(), rng.gen_range(0..=MAX_WRAPS))
+            .replace("{T}", &ty.to_string())
+            .replace("{V}", &v)
+            .replace("{W}", &w);
+        format!(
+            "{MARKER}{pattern}\n\n#[allow(dead_code)]\nfn id<X>(x: X) -> X {{\n    x\n}}\n\n{program}"
+        )
+    }
+}
+
+/// Replaces the `{wrap}(` ... `)` in `skeleton` with its argument
+/// passed through `wraps` calls to `id`.
+fn wrap(skeleton: &str, wraps: usize) -> String {
+    let (before, rest) = skeleton
+        .split_once("{wrap}(")
+        .expect("the skeleton wraps something");
+    let mut depth = 0;
+    let end = rest
+        .char_indices()
+        .find_map(|(offset, c)| match c {
+            '(' => {
+                depth += 1;
+                None
+            }
+            ')' if depth == 0 => Some(offset),
+            ')' => {
+                depth -= 1;
+                None
+            }
+            _ => None,
+        })
+        .expect("the wrapped argument is closed");
+    format!(
+        "{before}{}{}{}{}",
+        "id(".repeat(wraps),
+        &rest[..end],
+        ")".repeat(wraps),
+        &rest[end + 1..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn every_pattern_is_marked_and_parses() {
+        let gen = UnsoundGen::default();
+        let mut rng = StdRng::seed_from_u64(9);
+        for pattern in Pattern::ALL {
+            for _ in 0..4 {
+                let program = gen.generate_pattern(pattern, &mut rng);
+                assert_eq!(marked(&program), Some(pattern.name()), "{program}");
+                syn::parse_file(&program).expect("the program parses");
+                assert!(!program.contains("{wrap}"), "{program}");
+                assert!(!program.contains("{T}"), "{program}");
+            }
+        }
+        assert_eq!(marked("fn main() {}"), None);
+        assert_eq!(marked(MARKER), None);
+    }
+
+    #[test]
+    fn wrapping_passes_the_argument_through_id() {
+        let skeleton = "let x = {wrap}(&f(a, (b))); g(x)";
+        assert_eq!(wrap(skeleton, 0), "let x = &f(a, (b)); g(x)");
+        assert_eq!(wrap(skeleton, 2), "let x = id(id(&f(a, (b)))); g(x)");
+    }
+}
@@ -0,0 +1,518 @@
+//! Programs that coerce between instantiations of a generic type.
+//!
+//! rustc computes the variance of every lifetime and type parameter of a
+//! type from where the parameter appears in its fields: behind `&`,
+//! `Box` or a `fn` return it is covariant, in a `fn` argument
+//! contravariant, inside `Cell`, `*mut` or a trait object's arguments
+//! invariant, and a parameter appearing in several fields takes the
+//! strictest of them. Subtyping then lets a value of `Holder<'long>` pass
+//! as a `Holder<'short>` exactly when `Holder` is covariant in that
+//! lifetime, and the other way around when it is contravariant. Seeds
+//! written as values rarely nest parameters deeply enough to make either
+//! computation interesting.
+//!
+//! [`VarianceGen`] writes a struct whose parameters each appear under a
+//! random stack of wrappers, so their [`Variance`] is a composition of
+//! the wrappers', and functions that coerce between two instantiations of
+//! it: returned, bound by `let`, passed on, behind a shared or mutable
+//! reference, in a slice or a `Cell`, or as the argument of a function
+//! pointer, which flips the direction. A struct whose fields are all
+//! `PhantomData`, a `PhantomHolder`, has only marker parameters. A
+//! program rustc must accept has only coercions its variance allows; one
+//! it must reject has exactly one that it does not, so its diagnostic has
+//! one place to point at.
+//!
+//! Every program starts with [`MARKER`], the expectation and a case
+//! naming a coercion: the one that breaks the rules if there is one, the
+//! first otherwise.
+
+use std::fmt;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+
+use super::marker::Expect;
+
+/// The first line of every program [`VarianceGen`] writes, followed by
+/// the expectation and the case.
+pub const MARKER: &str = "// rustc-fuzz: variance: ";
+
+/// Most parameters of the struct.
+const MAX_PARAMS: usize = 3;
+
+/// Most wrappers around a parameter in one field.
+const MAX_DEPTH: usize = 2;
+
+/// Most coercions in one program.
+const MAX_SITES: usize = 3;
+
+/// How subtyping of a parameter carries over to the type around it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Variance {
+    Covariant,
+    Contravariant,
+    Invariant,
+}
+
+impl Variance {
+    pub fn name(self) -> &'static str {
+        match self {
+            Variance::Covariant => "covariant",
+            Variance::Contravariant => "contravariant",
+            Variance::Invariant => "invariant",
+        }
+    }
+
+    /// The variance of a parameter under `inner`, inside a position of
+    /// variance `self`.
+    pub fn compose(self, inner: Variance) -> Variance {
+        match (self, inner) {
+            (Variance::Invariant, _) | (_, Variance::Invariant) => Variance::Invariant,
+            (outer, Variance::Covariant) => outer,
+            (Variance::Covariant, Variance::Contravariant) => Variance::Contravariant,
+            (Variance::Contravariant, Variance::Contravariant) => Variance::Covariant,
+        }
+    }
+
+    /// The variance of a parameter that appears both as `self` and as
+    /// `other`.
+    pub fn meet(self, other: Variance) -> Variance {
+        if self == other {
+            self
+        } else {
+            Variance::Invariant
+        }
+    }
+
+    /// Whether a type of this variance in a lifetime may be coerced in
+    /// `direction`.
+    pub fn allows(self, direction: Direction) -> bool {
+        matches!(
+            (self, direction),
+            (Variance::Covariant, Direction::Shrink) | (Variance::Contravariant, Direction::Widen)
+        )
+    }
+}
+
+impl fmt::Display for Variance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Which way a coercion goes between a longer and a shorter lifetime.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    /// From `'long` to `'short`, or from a higher-ranked function pointer
+    /// to one instance of it.
+    Shrink,
+    /// From `'short` to `'long`, or from one instance to the
+    /// higher-ranked function pointer.
+    Widen,
+}
+
+impl Direction {
+    pub fn name(self) -> &'static str {
+        match self {
+            Direction::Shrink => "shrink",
+            Direction::Widen => "widen",
+        }
+    }
+
+    fn flip(self) -> Direction {
+        match self {
+            Direction::Shrink => Direction::Widen,
+            Direction::Widen => Direction::Shrink,
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Wrappers around a parameter in a field, `{}` standing for what they
+/// wrap, and their variance in it.
+const WRAPPERS: [(&str, Variance); 16] = [
+    ("Box<{}>", Variance::Covariant),
+    ("Vec<{}>", Variance::Covariant),
+    ("Option<{}>", Variance::Covariant),
+    ("[{}; 2]", Variance::Covariant),
+    ("({}, u8)", Variance::Covariant),
+    ("*const {}", Variance::Covariant),
+    ("fn() -> {}", Variance::Covariant),
+    ("PhantomData<{}>", Variance::Covariant),
+    ("fn({})", Variance::Contravariant),
+    ("PhantomData<fn({})>", Variance::Contravariant),
+    ("Cell<{}>", Variance::Invariant),
+    ("RefCell<{}>", Variance::Invariant),
+    ("*mut {}", Variance::Invariant),
+    ("fn({}) -> {}", Variance::Invariant),
+    ("Box<dyn Fn({})>", Variance::Invariant),
+    ("Box<dyn Fn() -> {}>", Variance::Invariant),
+];
+
+/// What a lifetime parameter `'{}` appears in at the bottom of a field;
+/// all covariant in it.
+const LIFETIME_BASES: [&str; 4] = ["&'{} u8", "&'{} mut u8", "&'{} str", "PhantomData<&'{} ()>"];
+
+/// A parameter of the struct and its variance.
+#[derive(Debug, Clone)]
+struct Param {
+    /// `'a` or `T`.
+    name: String,
+    lifetime: bool,
+    variance: Variance,
+}
+
+impl Param {
+    /// What the parameter is instantiated with on the `'long` or
+    /// `'short` side of a coercion.
+    fn subject(&self, long: bool) -> &'static str {
+        match (self.lifetime, long) {
+            (true, true) => "'long",
+            (true, false) => "'short",
+            (false, true) => "&'long u8",
+            (false, false) => "&'short u8",
+        }
+    }
+
+    /// What the parameter is instantiated with when it is not the one
+    /// being coerced.
+    fn fixed(&self) -> &'static str {
+        if self.lifetime {
+            "'static"
+        } else {
+            "u8"
+        }
+    }
+}
+
+/// Where a coercion happens, and the variance of that position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Site {
+    Return,
+    Let,
+    Argument,
+    Reference,
+    Slice,
+    MutReference,
+    Cell,
+    Callback,
+    /// Between `for<'x> fn(&'x u8) -> &'x u8` and one instance of it,
+    /// whatever the struct.
+    HigherRanked,
+}
+
+impl Site {
+    const ALL: [Site; 9] = [
+        Site::Return,
+        Site::Let,
+        Site::Argument,
+        Site::Reference,
+        Site::Slice,
+        Site::MutReference,
+        Site::Cell,
+        Site::Callback,
+        Site::HigherRanked,
+    ];
+
+    fn name(self) -> &'static str {
+        match self {
+            Site::Return => "return",
+            Site::Let => "let",
+            Site::Argument => "argument",
+            Site::Reference => "reference",
+            Site::Slice => "slice",
+            Site::MutReference => "mut-reference",
+            Site::Cell => "cell",
+            Site::Callback => "callback",
+            Site::HigherRanked => "higher-ranked",
+        }
+    }
+
+    fn variance(self) -> Variance {
+        match self {
+            Site::MutReference | Site::Cell => Variance::Invariant,
+            Site::Callback => Variance::Contravariant,
+            _ => Variance::Covariant,
+        }
+    }
+
+    /// A function named `name` coercing `from` to `to` here.
+    fn function(self, name: &str, from: &str, to: &str) -> String {
+        let generics = "<'short, 'long: 'short>";
+        let reborrow = "<'r, 'short, 'long: 'short>";
+        match self {
+            Site::Return | Site::HigherRanked => {
+                format!("fn {name}{generics}(x: {from}) -> {to} {{\n    x\n}}\n")
+            }
+            Site::Let => {
+                format!("fn {name}{generics}(x: {from}) {{\n    let _y: {to} = x;\n}}\n")
+            }
+            Site::Argument => {
+                format!("fn {name}{generics}(x: {from}, sink: fn({to})) {{\n    sink(x)\n}}\n")
+            }
+            Site::Reference => {
+                format!("fn {name}{reborrow}(x: &'r {from}) -> &'r {to} {{\n    x\n}}\n")
+            }
+            Site::Slice => {
+                format!("fn {name}{reborrow}(x: &'r [{from}]) -> &'r [{to}] {{\n    x\n}}\n")
+            }
+            Site::MutReference => {
+                format!("fn {name}{reborrow}(x: &'r mut {from}) -> &'r mut {to} {{\n    x\n}}\n")
+            }
+            Site::Cell => {
+                format!("fn {name}{generics}(x: Cell<{from}>) -> Cell<{to}> {{\n    x\n}}\n")
+            }
+            Site::Callback => {
+                format!("fn {name}{generics}(x: fn({from})) -> fn({to}) {{\n    x\n}}\n")
+            }
+        }
+    }
+}
+
+/// One coercion of a program.
+#[derive(Debug, Clone)]
+struct Coercion {
+    site: Site,
+    /// Index of the parameter coerced; unused by [`Site::HigherRanked`].
+    param: usize,
+    direction: Direction,
+    /// The variance of the whole position in the coerced parameter.
+    variance: Variance,
+}
+
+impl Coercion {
+    /// The case this coercion names.
+    fn case(&self) -> String {
+        format!("{}-{}-{}", self.site.name(), self.direction, self.variance)
+    }
+}
+
+/// Writes structs of many variances and coercions between their
+/// instantiations.
+#[derive(Debug, Clone, Default)]
+pub struct VarianceGen;
+
+impl VarianceGen {
+    /// A program rustc must accept or reject, equally likely.
+    pub fn generate(&self, rng: &mut dyn RngCore) -> String {
+        let expect = if rng.gen_bool(0.5) {
+            Expect::Accept
+        } else {
+            Expect::Reject
+        };
+        self.generate_expecting(expect, rng)
+    }
+
+    /// A program rustc must accept, or reject for one coercion.
+    pub fn generate_expecting(&self, expect: Expect, rng: &mut dyn RngCore) -> String {
+        let phantom = rng.gen_bool(0.3);
+        let (params, fields) = params(phantom, rng);
+        let count = rng.gen_range(1..=MAX_SITES);
+        let violation = (expect == Expect::Reject).then(|| rng.gen_range(0..count));
+        let coercions: Vec<Coercion> = (0..count)
+            .map(|i| coercion(&params, violation == Some(i), rng))
+            .collect();
+        let case = violation.unwrap_or(0);
+        let holder = if phantom { "PhantomHolder" } else { "Holder" };
+        let mut out = format!(
+            "{MARKER}{expect} {}\n#![allow(dead_code)]\n\n\
+             use std::cell::{{Cell, RefCell}};\nuse std::marker::PhantomData;\n\n\
+             struct {holder}<{}> {{\n",
+            coercions[case].case(),
+            params
+                .iter()
+                .map(|p| p.name.as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        for (i, field) in fields.iter().enumerate() {
+            out.push_str(&format!("    f{i}: {field},\n"));
+        }
+        out.push_str("}\n");
+        for (i, coercion) in coercions.iter().enumerate() {
+            let (from, to) = if coercion.site == Site::HigherRanked {
+                (
+                    "for<'x> fn(&'x u8) -> &'x u8".to_owned(),
+                    "fn(&'short u8) -> &'short u8".to_owned(),
+                )
+            } else {
+                (
+                    instance(holder, &params, coercion.param, true),
+                    instance(holder, &params, coercion.param, false),
+                )
+            };
+            let (from, to) = match coercion.direction {
+                Direction::Shrink => (from, to),
+                Direction::Widen => (to, from),
+            };
+            out.push('\n');
+            out.push_str(&coercion.site.function(&format!("coerce{i}"), &from, &to));
+        }
+        out.push_str("\nfn main() {}\n");
+        out
+    }
+}
+
+/// The struct's parameters, lifetimes first, and its fields, each
+/// parameter in one or two of them.
+fn params(phantom: bool, rng: &mut dyn RngCore) -> (Vec<Param>, Vec<String>) {
+    let count = rng.gen_range(1..=MAX_PARAMS);
+    let mut lifetimes = 0;
+    let mut types = 0;
+    for _ in 0..count {
+        if rng.gen_bool(0.7) {
+            lifetimes += 1;
+        } else {
+            types += 1;
+        }
+    }
+    let names = (0..lifetimes)
+        .map(|i| (format!("'{}", (b'a' + i as u8) as char), true))
+        .chain(
+            (0..types)
+                .map(|i| ((b'T' + i as u8) as char).to_string())
+                .map(|n| (n, false)),
+        );
+    let mut params = Vec::new();
+    let mut fields = Vec::new();
+    for (name, lifetime) in names {
+        let uses = if rng.gen_bool(0.25) { 2 } else { 1 };
+        let mut variance = None;
+        for _ in 0..uses {
+            let (field, v) = field(&name, lifetime, phantom, rng);
+            fields.push(field);
+            variance = Some(variance.map_or(v, |old: Variance| old.meet(v)));
+        }
+        params.push(Param {
+            name,
+            lifetime,
+            variance: variance.expect("every parameter has a field"),
+        });
+    }
+    fields.shuffle(rng);
+    (params, fields)
+}
+
+/// A field type using the parameter `name`, and its variance in it.
+fn field(name: &str, lifetime: bool, phantom: bool, rng: &mut dyn RngCore) -> (String, Variance) {
+    let mut ty = if lifetime {
+        LIFETIME_BASES
+            .choose(rng)
+            .expect("there are bases")
+            .replace("'{}", name)
+    } else {
+        name.to_owned()
+    };
+    let mut variance = Variance::Covariant;
+    for _ in 0..rng.gen_range(0..=MAX_DEPTH) {
+        let (wrapper, v) = WRAPPERS.choose(rng).expect("there are wrappers");
+        ty = wrapper.replace("{}", &ty);
+        variance = v.compose(variance);
+    }
+    if phantom && !ty.starts_with("PhantomData<") {
+        ty = format!("PhantomData<{ty}>");
+    }
+    (ty, variance)
+}
+
+/// The struct instantiated with parameter `subject` on the `'long` side
+/// if `long`, or the `'short` one, and every other parameter fixed.
+fn instance(holder: &str, params: &[Param], subject: usize, long: bool) -> String {
+    let args: Vec<&str> = params
+        .iter()
+        .enumerate()
+        .map(|(i, p)| {
+            if i == subject {
+                p.subject(long)
+            } else {
+                p.fixed()
+            }
+        })
+        .collect();
+    format!("{holder}<{}>", args.join(", "))
+}
+
+/// A coercion the rules forbid if `violation`, or one they allow. An
+/// invariant position allows none, so an allowed coercion is at a site
+/// that is not invariant, of a parameter that is not either; if every
+/// parameter is, it is between higher-ranked function pointers.
+fn coercion(params: &[Param], violation: bool, rng: &mut dyn RngCore) -> Coercion {
+    let variant: Vec<usize> = (0..params.len())
+        .filter(|&i| params[i].variance != Variance::Invariant)
+        .collect();
+    let sites: Vec<Site> = Site::ALL
+        .into_iter()
+        .filter(|site| violation || site.variance() != Variance::Invariant)
+        .collect();
+    let site = *sites.choose(rng).expect("there are sites");
+    let candidate = if violation {
+        Some(rng.gen_range(0..params.len()))
+    } else {
+        variant.choose(rng).copied()
+    };
+    let (site, param, variance) = match candidate {
+        Some(param) if site != Site::HigherRanked => {
+            (site, param, site.variance().compose(params[param].variance))
+        }
+        _ => (Site::HigherRanked, 0, Variance::Covariant),
+    };
+    let direction = match variance {
+        Variance::Covariant => Direction::Shrink,
+        Variance::Contravariant => Direction::Widen,
+        Variance::Invariant if rng.gen_bool(0.5) => Direction::Shrink,
+        Variance::Invariant => Direction::Widen,
+    };
+    let direction = if violation && variance.allows(direction) {
+        direction.flip()
+    } else {
+        direction
+    };
+    Coercion {
+        site,
+        param,
+        direction,
+        variance,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::gen::marker::marked;
+    use crate::rng::{SplitRng, Stream};
+
+    #[test]
+    fn marked_round_trips_every_expectation() {
+        let cases: Vec<String> = Site::ALL
+            .into_iter()
+            .flat_map(|site| {
+                [Direction::Shrink, Direction::Widen]
+                    .into_iter()
+                    .flat_map(move |direction| {
+                        [
+                            Variance::Covariant,
+                            Variance::Contravariant,
+                            Variance::Invariant,
+                        ]
+                        .into_iter()
+                        .map(move |variance| format!("{}-{direction}-{variance}", site.name()))
+                    })
+            })
+            .collect();
+        let streams = SplitRng::new(0);
+        for (i, expect) in [Expect::Accept, Expect::Reject].into_iter().enumerate() {
+            for j in 0..64 {
+                let mut rng = streams.rng(Stream::Generator, (i * 64 + j) as u64);
+                let program = VarianceGen.generate_expecting(expect, &mut rng);
+                let (marked_expect, case) = marked(MARKER, &program).expect("marked");
+                assert_eq!(marked_expect, expect, "{program}");
+                assert!(cases.iter().any(|c| c == case), "{case}");
+            }
+        }
+    }
+}
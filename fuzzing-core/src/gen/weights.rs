@@ -0,0 +1,107 @@
+//! Production weights.
+//!
+//! Without [`Weights`], [`ExprGen`](super::ExprGen) picks between the
+//! productions that fit uniformly, so whatever the corpus already has
+//! plenty of keeps being generated as often as what it lacks. Weights
+//! derived from a corpus's [`Counts`] favour the rare productions: one
+//! that a quarter as many seeds use is picked twice as often.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::coverage::productions::{Counts, PRODUCTIONS};
+
+/// The most a rare production is favoured over one every seed uses.
+pub const MAX_WEIGHT: f64 = 8.0;
+
+/// The productions that pick a control-flow form rather than build a
+/// value directly; the generator's "any control flow" choice weighs as
+/// much as they do on average.
+const CONTROL: [&str; 4] = ["if", "match", "block", "index"];
+
+/// Relative weights of generator productions. Productions without one
+/// weigh 1.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Weights {
+    pub weights: BTreeMap<String, f64>,
+}
+
+impl Weights {
+    /// Weights that favour the productions few seeds of `counts` use:
+    /// the square root of how many times rarer than the most common a
+    /// production is, at most [`MAX_WEIGHT`].
+    pub fn from_counts(counts: &Counts) -> Self {
+        let most = PRODUCTIONS
+            .iter()
+            .map(|production| counts.count(production))
+            .max()
+            .unwrap_or(0);
+        let weights = PRODUCTIONS
+            .iter()
+            .map(|&production| {
+                let rarity = (most + 1) as f64 / (counts.count(production) + 1) as f64;
+                (production.to_owned(), rarity.sqrt().min(MAX_WEIGHT))
+            })
+            .collect();
+        Weights { weights }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        fs::write(path, json)
+    }
+
+    /// The weight of `production`; `control` stands for the control-flow
+    /// productions together.
+    pub fn weight(&self, production: &str) -> f64 {
+        if production == "control" {
+            return CONTROL.iter().map(|p| self.weight(p)).sum::<f64>() / CONTROL.len() as f64;
+        }
+        self.weights.get(production).copied().unwrap_or(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rare_productions_weigh_more() {
+        let mut counts = Counts::new();
+        for _ in 0..3 {
+            counts.add("fn f() { let x = 1; }");
+        }
+        let weights = Weights::from_counts(&counts);
+        assert_eq!(weights.weight("let"), 1.0);
+        assert_eq!(weights.weight("cast"), 2.0);
+        assert_eq!(weights.weight("control"), 2.0);
+        assert_eq!(weights.weight("not a production"), 1.0);
+        assert_eq!(Weights::default().weight("cast"), 1.0);
+
+        for _ in 0..100 {
+            counts.add("fn f() { let x = 1; }");
+        }
+        assert_eq!(Weights::from_counts(&counts).weight("cast"), MAX_WEIGHT);
+    }
+
+    #[test]
+    fn weights_round_trip() {
+        let path =
+            std::env::temp_dir().join(format!("rustc-fuzz-weights-{}.json", std::process::id()));
+        let weights = Weights {
+            weights: BTreeMap::from([("cast".to_owned(), 2.5)]),
+        };
+        weights.save(&path).expect("saves");
+        assert_eq!(Weights::load(&path).expect("loads"), weights);
+        fs::remove_file(&path).expect("cleans up");
+    }
+}
@@ -0,0 +1,245 @@
+//! Codegen backend differential: Cranelift against LLVM.
+//!
+//! Builds each executable seed with both backends and otherwise identical
+//! flags, runs both binaries and compares what they did. A divergence is a
+//! miscompilation in one of the two, which neither backend would reveal
+//! alone. An ICE in only one backend is attributed to it. Needs a nightly
+//! toolchain with the `rustc-codegen-cranelift-preview` component.
+
+use std::fmt;
+use std::io;
+
+use super::behavior::{build_and_run, Behavior};
+use super::rustc::Scratch;
+use super::{Captured, ExecOutcome, Rustc};
+use crate::triage::Severity;
+
+/// A codegen backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Backend {
+    Llvm,
+    Cranelift,
+}
+
+impl Backend {
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Llvm => "llvm",
+            Backend::Cranelift => "cranelift",
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Compiles and runs seeds with each backend.
+#[derive(Debug, Clone)]
+pub struct BackendDiff {
+    /// Compiles the seeds and runs the binaries. Its phase is ignored;
+    /// every build goes through codegen.
+    pub driver: Rustc,
+    /// Codegen flags shared by both builds.
+    pub codegen: Vec<String>,
+    pub edition: String,
+}
+
+impl Default for BackendDiff {
+    fn default() -> Self {
+        BackendDiff {
+            driver: Rustc::default(),
+            codegen: vec!["-Copt-level=0".to_owned()],
+            edition: "2021".to_owned(),
+        }
+    }
+}
+
+/// What building and running one seed with both backends led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackendOutcome {
+    /// Both binaries behaved the same.
+    Agree,
+    /// The seed does not compile with LLVM.
+    Rejected,
+    /// Cranelift declined code LLVM compiled, typically a feature it does
+    /// not implement yet.
+    Unsupported(Captured),
+    /// A run timed out, so there is nothing reliable to compare.
+    Timeout { backend: Backend },
+    /// Compiling with `backend` ICEd or crashed.
+    Ice {
+        backend: Backend,
+        outcome: ExecOutcome,
+    },
+    /// The two binaries behaved differently.
+    Diverged { llvm: Behavior, cranelift: Behavior },
+}
+
+impl BackendOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            BackendOutcome::Ice { outcome, .. } => Some(Severity::of_failure(outcome)),
+            BackendOutcome::Diverged { .. } => Some(Severity::WrongCode),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for BackendOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendOutcome::Agree => f.write_str("backends agree"),
+            BackendOutcome::Rejected => f.write_str("rejected"),
+            BackendOutcome::Unsupported(_) => f.write_str("unsupported by cranelift"),
+            BackendOutcome::Timeout { backend } => write!(f, "timeout ({backend})"),
+            BackendOutcome::Ice { backend, outcome } => write!(f, "{backend} backend: {outcome}"),
+            BackendOutcome::Diverged { llvm, cranelift } if llvm.exit != cranelift.exit => write!(
+                f,
+                "backends diverge: llvm {} but cranelift {}",
+                llvm.exit, cranelift.exit
+            ),
+            BackendOutcome::Diverged { .. } => f.write_str("backend outputs diverge"),
+        }
+    }
+}
+
+impl BackendDiff {
+    /// Fails with an I/O error when the Cranelift backend is not
+    /// installed, rather than reporting every seed as unsupported.
+    pub fn check(&self, source: &str) -> io::Result<BackendOutcome> {
+        let dir = Scratch::new(&self.driver.scratch_root)?;
+        let llvm = match self.build_and_run(Backend::Llvm, source, &dir)? {
+            Ok(run) => run,
+            Err(ExecOutcome::Error { .. }) => return Ok(BackendOutcome::Rejected),
+            Err(outcome) => {
+                return Ok(BackendOutcome::Ice {
+                    backend: Backend::Llvm,
+                    outcome,
+                })
+            }
+        };
+        let cranelift = match self.build_and_run(Backend::Cranelift, source, &dir)? {
+            Ok(run) => run,
+            Err(ExecOutcome::Error { output, .. }) if missing_backend(&output.stderr) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    "the cranelift codegen backend is not installed",
+                ))
+            }
+            Err(ExecOutcome::Error { output, .. }) => {
+                return Ok(BackendOutcome::Unsupported(output))
+            }
+            Err(outcome) => {
+                return Ok(BackendOutcome::Ice {
+                    backend: Backend::Cranelift,
+                    outcome,
+                })
+            }
+        };
+        for (backend, run) in [(Backend::Llvm, &llvm), (Backend::Cranelift, &cranelift)] {
+            if let ExecOutcome::Timeout(_) = run {
+                return Ok(BackendOutcome::Timeout { backend });
+            }
+        }
+        let (llvm, cranelift) = (Behavior::of(&llvm), Behavior::of(&cranelift));
+        Ok(if llvm == cranelift {
+            BackendOutcome::Agree
+        } else {
+            BackendOutcome::Diverged { llvm, cranelift }
+        })
+    }
+
+    fn build_and_run(
+        &self,
+        backend: Backend,
+        source: &str,
+        dir: &Scratch,
+    ) -> io::Result<Result<ExecOutcome, ExecOutcome>> {
+        let mut args = vec![
+            "--edition".to_owned(),
+            self.edition.clone(),
+            format!("-Zcodegen-backend={backend}"),
+        ];
+        args.extend(self.codegen.iter().cloned());
+        let binary = dir.path().join(backend.name());
+        build_and_run(&self.driver, &args, source, &binary)
+    }
+}
+
+fn missing_backend(stderr: &str) -> bool {
+    stderr.contains("codegen-backends` folder") || stderr.contains("couldn't load codegen backend")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Checks a seed with a rustc whose Cranelift builds run `cranelift`
+    /// in place of the usual build.
+    fn check(name: &str, cranelift: &str) -> io::Result<BackendOutcome> {
+        let dir =
+            std::env::temp_dir().join(format!("rustc-fuzz-backend-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            format!(
+                "#!/bin/sh\ncat >/dev/null\nfor arg; do\n  \
+                 [ \"$prev\" = -o ] && out=$arg\n  prev=$arg\n  \
+                 [ \"$arg\" = -Zcodegen-backend=cranelift ] && cranelift=1\ndone\n\
+                 if [ -n \"$cranelift\" ]; then\n  {cranelift}\nfi\n\
+                 printf '#!/bin/sh\\necho 42\\n' > \"$out\"\nchmod +x \"$out\"\n"
+            ),
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let diff = BackendDiff {
+            driver: Rustc::new(&rustc),
+            ..BackendDiff::default()
+        };
+        let outcome = diff.check("fn main() {}");
+        fs::remove_dir_all(&dir).expect("cleans up");
+        outcome
+    }
+
+    #[test]
+    fn backends_are_compared() {
+        assert_eq!(check("agree", ":").expect("runs"), BackendOutcome::Agree);
+        let exits = "printf '#!/bin/sh\\nexit 3\\n' > \"$out\"; chmod +x \"$out\"; exit 0";
+        let diverged = check("diverge", exits).expect("runs");
+        assert_eq!(diverged.severity(), Some(Severity::WrongCode));
+        assert_eq!(
+            diverged.to_string(),
+            "backends diverge: llvm success but cranelift error 3"
+        );
+        let ice = "echo 'error: internal compiler error: unimplemented' >&2; exit 101";
+        let BackendOutcome::Ice { backend, .. } = check("ice", ice).expect("runs") else {
+            panic!("cranelift ICEd");
+        };
+        assert_eq!(backend, Backend::Cranelift);
+    }
+
+    #[test]
+    fn a_missing_backend_is_an_error() {
+        let missing = "echo \"error: couldn't load codegen backend\" >&2; exit 1";
+        let error = check("missing", missing).expect_err("cannot compare");
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        let unsupported = "echo 'error: unsupported: inline assembly' >&2; exit 1";
+        let outcome = check("unsupported", unsupported).expect("runs");
+        assert!(
+            matches!(outcome, BackendOutcome::Unsupported(_)),
+            "{outcome}"
+        );
+        assert!(!outcome.is_finding());
+    }
+}
@@ -0,0 +1,105 @@
+//! Building seeds into binaries and observing what they do, for the
+//! differential harnesses.
+
+use std::io;
+use std::iter;
+use std::path::Path;
+
+use super::{ExecOutcome, Rustc};
+
+/// What one run of a compiled seed did, as far as a comparison goes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Behavior {
+    /// [`ExecOutcome::kind`], with the exit code for errors.
+    pub exit: String,
+    pub stdout: String,
+}
+
+impl Behavior {
+    pub fn of(outcome: &ExecOutcome) -> Self {
+        let exit = match outcome {
+            ExecOutcome::Error { code, .. } => format!("error {code}"),
+            outcome => outcome.kind().to_owned(),
+        };
+        Behavior {
+            exit,
+            stdout: outcome.output().stdout.clone(),
+        }
+    }
+
+    /// Like [`Behavior::of`], but with every kind of failure folded into
+    /// one, for builds that may legitimately fail differently: a panic
+    /// exits with 101 when it unwinds and dies of `SIGABRT` when it aborts.
+    pub fn verdict(outcome: &ExecOutcome) -> Self {
+        let mut behavior = Behavior::of(outcome);
+        if !outcome.is_success() {
+            behavior.exit = "failure".to_owned();
+        }
+        behavior
+    }
+}
+
+/// Compiles `source` to `binary` with `args` and runs it in the driver's
+/// sandbox. Returns the run, or the compilation if that did not succeed.
+/// The driver's phase does not apply; a binary needs codegen.
+pub(crate) fn build_and_run(
+    driver: &Rustc,
+    args: &[String],
+    source: &str,
+    binary: &Path,
+) -> io::Result<Result<ExecOutcome, ExecOutcome>> {
+    let compiled = build(driver, args, source, binary)?;
+    if !compiled.is_success() {
+        return Ok(Err(compiled));
+    }
+    Ok(Ok(driver.run_sandboxed(
+        binary,
+        iter::empty::<&str>(),
+        &[],
+    )?))
+}
+
+/// Compiles `source` to `binary` with `args`, for binaries that need
+/// something else to run them.
+pub(crate) fn build(
+    driver: &Rustc,
+    args: &[String],
+    source: &str,
+    binary: &Path,
+) -> io::Result<ExecOutcome> {
+    let mut args = args.to_vec();
+    args.push("-o".to_owned());
+    args.push(binary.display().to_string());
+    args.push("-".to_owned());
+    driver.run_program(&driver.path, &args, source.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::Captured;
+
+    #[test]
+    fn verdicts_fold_failures_together() {
+        let output = Captured {
+            stdout: "partial\n".to_owned(),
+            ..Captured::default()
+        };
+        let unwound = ExecOutcome::Error {
+            code: 101,
+            output: output.clone(),
+        };
+        let aborted = ExecOutcome::Crash {
+            signal: 6,
+            output: output.clone(),
+        };
+        assert_eq!(Behavior::of(&unwound).exit, "error 101");
+        assert_ne!(Behavior::of(&unwound), Behavior::of(&aborted));
+        assert_eq!(Behavior::verdict(&unwound), Behavior::verdict(&aborted));
+        assert_eq!(Behavior::verdict(&unwound).stdout, "partial\n");
+        assert_eq!(
+            Behavior::verdict(&ExecOutcome::Success(output)).exit,
+            "success"
+        );
+    }
+}
@@ -0,0 +1,339 @@
+//! Clippy harness.
+//!
+//! Runs `clippy-driver` on each seed with every lint group enabled, so
+//! that all of Clippy's lint passes see it, and reports panics in them.
+//! With [`Clippy::fix`] set, the machine-applicable suggestions from a
+//! clean run are applied and the result is compiled again: a suggestion
+//! that turns compiling code into code that does not compile is a bug in
+//! the lint even though nothing crashed.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use super::{Captured, ExecOutcome, Rustc};
+use crate::triage::Severity;
+
+/// Lint groups enabled on every run. `clippy::cargo` is left out; it needs
+/// a Cargo manifest to look at.
+pub const LINT_GROUPS: [&str; 4] = [
+    "clippy::all",
+    "clippy::pedantic",
+    "clippy::nursery",
+    "clippy::restriction",
+];
+
+/// Runs `clippy-driver` on seeds under the limits of a [`Rustc`] driver.
+#[derive(Debug, Clone)]
+pub struct Clippy {
+    pub path: PathBuf,
+    pub edition: String,
+    /// Apply machine-applicable suggestions and compile the result.
+    pub fix: bool,
+    /// Supplies timeout, memory and output limits.
+    pub driver: Rustc,
+}
+
+impl Default for Clippy {
+    fn default() -> Self {
+        Clippy {
+            path: PathBuf::from("clippy-driver"),
+            edition: "2021".to_owned(),
+            fix: false,
+            driver: Rustc::default(),
+        }
+    }
+}
+
+/// What running Clippy on one seed led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ClippyOutcome {
+    /// Compiled, and any applied suggestions compiled too.
+    Clean,
+    /// The seed does not compile.
+    Rejected,
+    /// clippy-driver panicked, crashed, hung or ran out of memory.
+    Failed(ExecOutcome),
+    /// Applying the suggestions of `lints` made the code fail to compile.
+    BrokenFix {
+        lints: Vec<String>,
+        fixed: String,
+        output: Captured,
+    },
+}
+
+impl ClippyOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            ClippyOutcome::Failed(outcome) => Some(Severity::of_failure(outcome)),
+            ClippyOutcome::BrokenFix { .. } => Some(Severity::Diagnostic),
+            ClippyOutcome::Clean | ClippyOutcome::Rejected => None,
+        }
+    }
+}
+
+impl fmt::Display for ClippyOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClippyOutcome::Clean => f.write_str("clean"),
+            ClippyOutcome::Rejected => f.write_str("rejected"),
+            ClippyOutcome::Failed(outcome) => write!(f, "clippy failed: {outcome}"),
+            ClippyOutcome::BrokenFix { lints, .. } => {
+                write!(f, "suggestions broke the code: {}", lints.join(", "))
+            }
+        }
+    }
+}
+
+impl Clippy {
+    pub fn check(&self, source: &str) -> io::Result<ClippyOutcome> {
+        // rustc reports byte offsets into the text after dropping a BOM
+        // and CRLF line endings; hand it text where the two agree.
+        let source = source.trim_start_matches('\u{feff}').replace("\r\n", "\n");
+        let outcome = match self.lint(&source)? {
+            Ok(output) => output,
+            Err(outcome) => return Ok(outcome),
+        };
+        if !self.fix {
+            return Ok(ClippyOutcome::Clean);
+        }
+        let Some((fixed, lints)) = apply_suggestions(&source, &outcome.stderr) else {
+            return Ok(ClippyOutcome::Clean);
+        };
+        Ok(match self.lint(&fixed)? {
+            Ok(_) => ClippyOutcome::Clean,
+            Err(ClippyOutcome::Rejected) => {
+                let output =
+                    self.driver
+                        .run_program(&self.path, self.args(false), fixed.as_bytes())?;
+                ClippyOutcome::BrokenFix {
+                    lints,
+                    fixed,
+                    output: output.output().clone(),
+                }
+            }
+            Err(outcome) => outcome,
+        })
+    }
+
+    /// Lints `source` with JSON diagnostics, or says why it could not.
+    fn lint(&self, source: &str) -> io::Result<Result<Captured, ClippyOutcome>> {
+        let outcome = self
+            .driver
+            .run_program(&self.path, self.args(true), source.as_bytes())?;
+        Ok(match outcome {
+            ExecOutcome::Success(output) => Ok(output),
+            ExecOutcome::Error { .. } => Err(ClippyOutcome::Rejected),
+            outcome => Err(ClippyOutcome::Failed(outcome)),
+        })
+    }
+
+    fn args(&self, json: bool) -> Vec<String> {
+        let mut args = vec![
+            "--edition".to_owned(),
+            self.edition.clone(),
+            "--crate-type=lib".to_owned(),
+            "--emit=metadata".to_owned(),
+            // Deny-by-default lints would otherwise fail the build and
+            // read as a rejected seed.
+            "--cap-lints=warn".to_owned(),
+        ];
+        if json {
+            args.push("--error-format=json".to_owned());
+        }
+        for group in LINT_GROUPS {
+            args.push("-W".to_owned());
+            args.push(group.to_owned());
+        }
+        args.push("-".to_owned());
+        args
+    }
+}
+
+#[derive(Deserialize)]
+struct Diagnostic {
+    code: Option<Code>,
+    #[serde(default)]
+    spans: Vec<Span>,
+    #[serde(default)]
+    children: Vec<Diagnostic>,
+}
+
+#[derive(Deserialize)]
+struct Code {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct Span {
+    file_name: String,
+    byte_start: usize,
+    byte_end: usize,
+    suggested_replacement: Option<String>,
+    suggestion_applicability: Option<String>,
+}
+
+/// One suggestion: replacements that only make sense together.
+struct Suggestion<'a> {
+    lint: &'a str,
+    edits: Vec<(usize, usize, &'a str)>,
+}
+
+/// Applies every machine-applicable suggestion in the JSON diagnostics on
+/// `stderr` that does not overlap one applied before it, as `cargo clippy
+/// --fix` would. Returns the new source and the lints whose suggestions
+/// were applied, or `None` if there was nothing to apply.
+pub(crate) fn apply_suggestions(source: &str, stderr: &str) -> Option<(String, Vec<String>)> {
+    let diagnostics: Vec<Diagnostic> = stderr
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    let mut suggestions = Vec::new();
+    for diagnostic in &diagnostics {
+        let lint = diagnostic
+            .code
+            .as_ref()
+            .map_or("", |code| code.code.as_str());
+        collect_suggestions(lint, diagnostic, &mut suggestions);
+    }
+
+    let mut taken: Vec<(usize, usize)> = Vec::new();
+    let mut edits = Vec::new();
+    let mut lints = BTreeSet::new();
+    for suggestion in suggestions {
+        let in_bounds = suggestion.edits.iter().all(|&(start, end, _)| {
+            source.is_char_boundary(start) && source.is_char_boundary(end) && start <= end
+        });
+        let overlaps = suggestion.edits.iter().any(|&(start, end, _)| {
+            taken
+                .iter()
+                .any(|&(s, e)| start < e && s < end || start == s && end == e)
+        });
+        if !in_bounds || overlaps {
+            continue;
+        }
+        taken.extend(suggestion.edits.iter().map(|&(start, end, _)| (start, end)));
+        edits.extend(suggestion.edits);
+        lints.insert(suggestion.lint.to_owned());
+    }
+    if edits.is_empty() {
+        return None;
+    }
+
+    edits.sort_by_key(|&(start, end, _)| (start, end));
+    let mut fixed = String::with_capacity(source.len());
+    let mut at = 0;
+    for (start, end, replacement) in edits {
+        fixed.push_str(&source[at..start]);
+        fixed.push_str(replacement);
+        at = end;
+    }
+    fixed.push_str(&source[at..]);
+    Some((fixed, lints.into_iter().collect()))
+}
+
+fn collect_suggestions<'a>(
+    lint: &'a str,
+    diagnostic: &'a Diagnostic,
+    out: &mut Vec<Suggestion<'a>>,
+) {
+    let edits: Vec<_> = diagnostic
+        .spans
+        .iter()
+        .filter(|span| span.file_name == "<anon>")
+        .filter(|span| span.suggestion_applicability.as_deref() == Some("MachineApplicable"))
+        .filter_map(|span| {
+            let replacement = span.suggested_replacement.as_deref()?;
+            Some((span.byte_start, span.byte_end, replacement))
+        })
+        .collect();
+    if !edits.is_empty() {
+        out.push(Suggestion { lint, edits });
+    }
+    for child in &diagnostic.children {
+        collect_suggestions(lint, child, out);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// A diagnostic line for `lint` suggesting `edits` with `applicability`.
+    fn diagnostic(lint: &str, applicability: &str, edits: &[(usize, usize, &str)]) -> String {
+        let spans: Vec<_> = edits
+            .iter()
+            .map(|&(start, end, replacement)| {
+                json!({
+                    "file_name": "<anon>",
+                    "byte_start": start,
+                    "byte_end": end,
+                    "suggested_replacement": replacement,
+                    "suggestion_applicability": applicability,
+                })
+            })
+            .collect();
+        json!({
+            "code": {"code": lint},
+            "spans": [],
+            "children": [{"code": null, "spans": spans, "children": []}],
+        })
+        .to_string()
+    }
+
+    #[test]
+    fn machine_applicable_suggestions_are_applied_once() {
+        let source = "fn f() -> bool { x == true && y.len() == 0 }";
+        let stderr = [
+            diagnostic(
+                "clippy::bool_comparison",
+                "MachineApplicable",
+                &[(17, 26, "x")],
+            ),
+            // Overlaps the one before it.
+            diagnostic(
+                "clippy::nonminimal_bool",
+                "MachineApplicable",
+                &[(17, 42, "z")],
+            ),
+            diagnostic(
+                "clippy::len_zero",
+                "MachineApplicable",
+                &[(30, 42, "y.is_empty()")],
+            ),
+            diagnostic("clippy::style", "MaybeIncorrect", &[(3, 4, "g")]),
+            "warning: not JSON".to_owned(),
+        ]
+        .join("\n");
+        let (fixed, lints) = apply_suggestions(source, &stderr).expect("applies");
+        assert_eq!(fixed, "fn f() -> bool { x && y.is_empty() }");
+        assert_eq!(lints, ["clippy::bool_comparison", "clippy::len_zero"]);
+
+        let maybe = diagnostic("clippy::style", "MaybeIncorrect", &[(3, 4, "g")]);
+        assert_eq!(apply_suggestions(source, &maybe), None);
+    }
+
+    #[test]
+    fn broken_fixes_are_diagnostic_bugs() {
+        let broken = ClippyOutcome::BrokenFix {
+            lints: vec!["clippy::a".to_owned(), "clippy::b".to_owned()],
+            fixed: String::new(),
+            output: Captured::default(),
+        };
+        assert_eq!(broken.severity(), Some(Severity::Diagnostic));
+        assert_eq!(
+            broken.to_string(),
+            "suggestions broke the code: clippy::a, clippy::b"
+        );
+        assert!(!ClippyOutcome::Rejected.is_finding());
+    }
+}
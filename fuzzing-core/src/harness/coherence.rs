@@ -0,0 +1,353 @@
+//! Cross-crate coherence harness.
+//!
+//! Seeds from [`CoherenceGen`] are a downstream crate with its upstream
+//! crate inlined as `mod upstream`. The harness cuts the module out,
+//! compiles it to metadata as a crate of its own, and checks the rest
+//! against it with `--extern`, so the orphan and overlap rules see two
+//! crates. A marked seed says whether rustc must accept it; rustc
+//! deciding otherwise is a finding. Mutants have lost their mark, but
+//! still go through both crates, for the ICEs.
+//!
+//! [`CoherenceGen`]: crate::gen::coherence::CoherenceGen
+
+use std::fmt;
+use std::io;
+
+use super::rustc::Scratch;
+use super::{ExecOutcome, Rustc};
+use crate::gen::coherence::{self, UPSTREAM};
+use crate::gen::marker::Expect;
+use crate::oracle::stability;
+use crate::triage::Severity;
+
+/// Which of the two crates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Krate {
+    Upstream,
+    Downstream,
+}
+
+impl fmt::Display for Krate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Krate::Upstream => "upstream crate",
+            Krate::Downstream => "downstream crate",
+        })
+    }
+}
+
+/// Compiles seeds as an upstream and a downstream crate.
+#[derive(Debug, Clone)]
+pub struct Coherence {
+    /// Compiles both crates. Its phase is ignored: both are checked to
+    /// metadata, which is as far as coherence goes.
+    pub driver: Rustc,
+    pub edition: String,
+}
+
+impl Default for Coherence {
+    fn default() -> Self {
+        Coherence {
+            driver: Rustc::default(),
+            edition: "2021".to_owned(),
+        }
+    }
+}
+
+/// What checking one seed led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CoherenceOutcome {
+    /// The seed has no `mod upstream` to make a crate of.
+    Single,
+    /// The upstream crate does not compile.
+    UpstreamRejected,
+    /// The downstream crate compiled as its case expects, or it has no
+    /// case.
+    Agreed {
+        case: Option<String>,
+        accepted: bool,
+    },
+    /// rustc accepted impls its case says break the orphan or overlap
+    /// rules.
+    Accepted { case: String },
+    /// rustc rejected impls its case says are coherent; `errors` are its
+    /// error codes.
+    Rejected { case: String, errors: Vec<String> },
+    /// Compiling a crate timed out.
+    Timeout(Krate),
+    /// rustc ICEd, crashed or ran out of memory on a crate.
+    Failed { krate: Krate, outcome: ExecOutcome },
+}
+
+impl CoherenceOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            CoherenceOutcome::Accepted { .. } => Some(Severity::Unsound),
+            CoherenceOutcome::Rejected { .. } => Some(Severity::Diagnostic),
+            CoherenceOutcome::Failed { outcome, .. } => Some(Severity::of_failure(outcome)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for CoherenceOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CoherenceOutcome::Single => write!(f, "no `mod {UPSTREAM}`"),
+            CoherenceOutcome::UpstreamRejected => f.write_str("upstream crate rejected"),
+            CoherenceOutcome::Agreed {
+                case: Some(case),
+                accepted,
+            } => write!(
+                f,
+                "{case}: {}",
+                if *accepted { "accepted" } else { "rejected" }
+            ),
+            CoherenceOutcome::Agreed { case: None, .. } => f.write_str("unmarked pair"),
+            CoherenceOutcome::Accepted { case } => {
+                write!(f, "{case}: accepted impls that must not coexist")
+            }
+            CoherenceOutcome::Rejected { case, errors } => {
+                write!(
+                    f,
+                    "{case}: rejected coherent impls: [{}]",
+                    errors.join(", ")
+                )
+            }
+            CoherenceOutcome::Timeout(krate) => write!(f, "{krate}: timeout"),
+            CoherenceOutcome::Failed { krate, outcome } => write!(f, "{krate}: {outcome}"),
+        }
+    }
+}
+
+impl Coherence {
+    pub fn check(&self, source: &str) -> io::Result<CoherenceOutcome> {
+        let Some((upstream, downstream)) = split(source) else {
+            return Ok(CoherenceOutcome::Single);
+        };
+        let dir = Scratch::new(&self.driver.scratch_root)?;
+        let metadata = dir.path().join(format!("lib{UPSTREAM}.rmeta"));
+        let upstream = self.compile(
+            &upstream,
+            UPSTREAM,
+            &["-o".to_owned(), metadata.display().to_string()],
+        )?;
+        match upstream {
+            ExecOutcome::Success(_) => {}
+            ExecOutcome::Error { .. } => return Ok(CoherenceOutcome::UpstreamRejected),
+            ExecOutcome::Timeout(_) => return Ok(CoherenceOutcome::Timeout(Krate::Upstream)),
+            outcome => {
+                return Ok(CoherenceOutcome::Failed {
+                    krate: Krate::Upstream,
+                    outcome,
+                })
+            }
+        }
+        let extern_arg = format!("{UPSTREAM}={}", metadata.display());
+        let downstream = self.compile(
+            &downstream,
+            "downstream",
+            &["--extern".to_owned(), extern_arg],
+        )?;
+        let accepted = match downstream {
+            ExecOutcome::Success(_) => true,
+            ExecOutcome::Error { .. } => false,
+            ExecOutcome::Timeout(_) => return Ok(CoherenceOutcome::Timeout(Krate::Downstream)),
+            outcome => {
+                return Ok(CoherenceOutcome::Failed {
+                    krate: Krate::Downstream,
+                    outcome,
+                })
+            }
+        };
+        let Some((expect, case)) = coherence::marked(source) else {
+            return Ok(CoherenceOutcome::Agreed {
+                case: None,
+                accepted,
+            });
+        };
+        let case = case.to_owned();
+        Ok(match (expect, accepted) {
+            (Expect::Reject, true) => CoherenceOutcome::Accepted { case },
+            (Expect::Accept, false) => CoherenceOutcome::Rejected {
+                case,
+                errors: stability::error_codes(&downstream.output().stderr),
+            },
+            _ => CoherenceOutcome::Agreed {
+                case: Some(case),
+                accepted,
+            },
+        })
+    }
+
+    /// Checks `source` as library crate `name`, to metadata.
+    fn compile(&self, source: &str, name: &str, extra: &[String]) -> io::Result<ExecOutcome> {
+        let mut args = vec![
+            "--crate-type=lib".to_owned(),
+            format!("--crate-name={name}"),
+            "--emit=metadata".to_owned(),
+            "--edition".to_owned(),
+            self.edition.clone(),
+        ];
+        args.extend_from_slice(extra);
+        args.push("-".to_owned());
+        self.driver
+            .run_program(&self.driver.path, &args, source.as_bytes())
+    }
+}
+
+/// The upstream and downstream crates of `source`: the contents of its
+/// top-level `mod upstream`, and everything else. The file's inner
+/// attributes, such as feature gates, go to both.
+pub fn split(source: &str) -> Option<(String, String)> {
+    let mut file = syn::parse_file(source).ok()?;
+    let index = file.items.iter().position(
+        |item| matches!(item, syn::Item::Mod(m) if m.ident == UPSTREAM && m.content.is_some()),
+    )?;
+    let syn::Item::Mod(module) = file.items.remove(index) else {
+        unreachable!("the item was just found to be a module");
+    };
+    let (_, items) = module.content.expect("the module is inline");
+    let mut attrs = file.attrs.clone();
+    attrs.extend(
+        module
+            .attrs
+            .into_iter()
+            .filter(|attr| matches!(attr.style, syn::AttrStyle::Inner(_))),
+    );
+    let upstream = syn::File {
+        shebang: None,
+        attrs,
+        items,
+    };
+    Some((
+        prettyplease::unparse(&upstream),
+        prettyplease::unparse(&file),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn split_cuts_out_the_upstream_module() {
+        let source = "#![feature(fundamental)]\n\
+                      mod upstream {\n    #![allow(dead_code)]\n    pub trait Foreign {}\n}\n\
+                      struct Local;\n\
+                      impl upstream::Foreign for Local {}\n";
+        let (upstream, downstream) = split(source).expect("has an upstream module");
+        assert!(upstream.starts_with("#![feature(fundamental)]\n#![allow(dead_code)]\n"));
+        assert!(upstream.contains("pub trait Foreign {}"), "{upstream}");
+        assert!(downstream.starts_with("#![feature(fundamental)]\n"));
+        assert!(!downstream.contains("mod upstream"), "{downstream}");
+        assert!(downstream.contains("impl upstream::Foreign for Local {}"));
+        assert_eq!(split("mod upstream;\nstruct S;\n"), None);
+        assert_eq!(split("fn f() {"), None);
+    }
+
+    #[test]
+    fn a_fake_rustc_disagreeing_is_a_finding() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-coherence-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        // Rejects upstream crates with `Broken` in them, downstream crates
+        // with `Orphan`, and ICEs on downstream crates with `Ice`.
+        let path = dir.join("rustc");
+        fs::write(
+            &path,
+            "#!/bin/sh\nsource=$(cat)\ncase \"$*\" in\n  \
+             *crate-name=upstream*) case \"$source\" in *Broken*) exit 1 ;; esac ;;\n  \
+             *) case \"$source\" in\n    \
+             *Orphan*) echo 'error[E0117]: only traits defined in the current crate' >&2; exit 1 ;;\n    \
+             *Ice*) echo 'error: internal compiler error: coherence' >&2; exit 101 ;;\n  \
+             esac ;;\nesac\nexit 0\n",
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let harness = Coherence {
+            driver: Rustc {
+                path,
+                scratch_root: dir.clone(),
+                ..Rustc::default()
+            },
+            ..Coherence::default()
+        };
+        let check = |mark: &str, upstream: &str, downstream: &str| {
+            let source = format!(
+                "{mark}\nmod upstream {{\n    pub struct {upstream};\n}}\nstruct {downstream};\n"
+            );
+            harness.check(&source).expect("runs")
+        };
+        let accept = format!("{}accept case", coherence::MARKER);
+        let reject = format!("{}reject case", coherence::MARKER);
+
+        assert_eq!(
+            harness.check("struct S;\n").expect("runs"),
+            CoherenceOutcome::Single
+        );
+        assert_eq!(
+            check(&accept, "Broken", "Local"),
+            CoherenceOutcome::UpstreamRejected
+        );
+        assert_eq!(
+            check(&accept, "Foreign", "Local"),
+            CoherenceOutcome::Agreed {
+                case: Some("case".to_owned()),
+                accepted: true,
+            }
+        );
+        assert_eq!(
+            check(&reject, "Foreign", "Orphan"),
+            CoherenceOutcome::Agreed {
+                case: Some("case".to_owned()),
+                accepted: false,
+            }
+        );
+        assert_eq!(
+            check("", "Foreign", "Orphan"),
+            CoherenceOutcome::Agreed {
+                case: None,
+                accepted: false,
+            }
+        );
+
+        let accepted = check(&reject, "Foreign", "Local");
+        assert_eq!(
+            accepted,
+            CoherenceOutcome::Accepted {
+                case: "case".to_owned()
+            }
+        );
+        assert_eq!(accepted.severity(), Some(Severity::Unsound));
+        let rejected = check(&accept, "Foreign", "Orphan");
+        assert_eq!(
+            rejected,
+            CoherenceOutcome::Rejected {
+                case: "case".to_owned(),
+                errors: vec!["E0117".to_owned()],
+            }
+        );
+        assert_eq!(
+            rejected.to_string(),
+            "case: rejected coherent impls: [E0117]"
+        );
+        assert_eq!(rejected.severity(), Some(Severity::Diagnostic));
+        let failed = check(&accept, "Foreign", "Ice");
+        let CoherenceOutcome::Failed {
+            krate: Krate::Downstream,
+            ..
+        } = &failed
+        else {
+            panic!("{failed:?}");
+        };
+        assert_eq!(failed.severity(), Some(Severity::Ice));
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,197 @@
+//! Const evaluation against run time.
+//!
+//! Runs programs from [`ProgramGen::generate_const`], which compute the
+//! same values in a `const` item and in compiled code and print both, and
+//! compares the two lines. The const evaluator is a separate interpreter
+//! of MIR with its own model of integer and float semantics, so a result
+//! it disagrees with codegen on is a bug in one of them. The generated
+//! computations cannot panic, so a const evaluation error is a finding
+//! too.
+//!
+//! [`ProgramGen::generate_const`]: crate::gen::ProgramGen::generate_const
+
+use std::fmt;
+use std::io;
+
+use super::behavior::build_and_run;
+use super::rustc::Scratch;
+use super::{Captured, ExecOutcome, Rustc};
+use crate::gen::CONST_LINES;
+use crate::triage::Severity;
+
+/// Builds and runs const/run-time program pairs.
+#[derive(Debug, Clone)]
+pub struct ConstEval {
+    /// Compiles the seeds and runs the binaries. Its phase is ignored.
+    pub driver: Rustc,
+    /// Codegen flags for the run-time side.
+    pub codegen: Vec<String>,
+    pub edition: String,
+}
+
+impl Default for ConstEval {
+    fn default() -> Self {
+        ConstEval {
+            driver: Rustc::default(),
+            codegen: vec!["-Copt-level=0".to_owned()],
+            edition: "2021".to_owned(),
+        }
+    }
+}
+
+/// What building and running one seed led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConstOutcome {
+    /// Both results printed the same.
+    Agree,
+    /// The seed does not compile for a reason other than const
+    /// evaluation.
+    Rejected,
+    /// The seed ran but did not print both results: it is not a
+    /// generated pair, or it failed at run time.
+    Unpaired,
+    /// The run timed out.
+    Timeout,
+    /// rustc ICEd or crashed.
+    Ice(ExecOutcome),
+    /// Const evaluation reported an error (E0080) for a computation that
+    /// has none.
+    EvalFailed(Captured),
+    /// The two results differ.
+    Diverged {
+        compile_time: String,
+        run_time: String,
+    },
+}
+
+impl ConstOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            ConstOutcome::Ice(outcome) => Some(Severity::of_failure(outcome)),
+            ConstOutcome::EvalFailed(_) => Some(Severity::Ice),
+            ConstOutcome::Diverged { .. } => Some(Severity::WrongCode),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ConstOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConstOutcome::Agree => f.write_str("const and run time agree"),
+            ConstOutcome::Rejected => f.write_str("rejected"),
+            ConstOutcome::Unpaired => f.write_str("not a const/run-time pair"),
+            ConstOutcome::Timeout => f.write_str("timeout"),
+            ConstOutcome::Ice(outcome) => write!(f, "{outcome}"),
+            ConstOutcome::EvalFailed(_) => f.write_str("const evaluation failed"),
+            ConstOutcome::Diverged {
+                compile_time,
+                run_time,
+            } => write!(
+                f,
+                "const evaluation gives {compile_time} but run time {run_time}"
+            ),
+        }
+    }
+}
+
+impl ConstEval {
+    pub fn check(&self, source: &str) -> io::Result<ConstOutcome> {
+        let dir = Scratch::new(&self.driver.scratch_root)?;
+        let mut args = vec!["--edition".to_owned(), self.edition.clone()];
+        args.extend(self.codegen.iter().cloned());
+        let run = match build_and_run(&self.driver, &args, source, &dir.path().join("seed"))? {
+            Ok(run) => run,
+            Err(ExecOutcome::Error { output, .. }) if output.stderr.contains("error[E0080]") => {
+                return Ok(ConstOutcome::EvalFailed(output))
+            }
+            Err(ExecOutcome::Error { .. }) => return Ok(ConstOutcome::Rejected),
+            Err(outcome) => return Ok(ConstOutcome::Ice(outcome)),
+        };
+        let stdout = match run {
+            ExecOutcome::Success(output) => output.stdout,
+            ExecOutcome::Timeout(_) => return Ok(ConstOutcome::Timeout),
+            _ => return Ok(ConstOutcome::Unpaired),
+        };
+        let [compile_time, run_time] =
+            CONST_LINES.map(|prefix| stdout.lines().find_map(|line| line.strip_prefix(prefix)));
+        Ok(match (compile_time, run_time) {
+            (Some(compile_time), Some(run_time)) if compile_time != run_time => {
+                ConstOutcome::Diverged {
+                    compile_time: compile_time.to_owned(),
+                    run_time: run_time.to_owned(),
+                }
+            }
+            (Some(_), Some(_)) => ConstOutcome::Agree,
+            _ => ConstOutcome::Unpaired,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Checks a seed with a rustc whose binaries print `stdout`, or that
+    /// fails with `stderr` if there is one.
+    fn check(name: &str, stdout: &str, stderr: Option<&str>) -> ConstOutcome {
+        let dir = std::env::temp_dir().join(format!(
+            "rustc-fuzz-const-eval-{name}-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        let fail = stderr.map_or(String::new(), |stderr| {
+            format!("echo '{stderr}' >&2\nexit 1\n")
+        });
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            format!(
+                "#!/bin/sh\ncat >/dev/null\n{fail}\
+                 for arg; do [ \"$prev\" = -o ] && out=$arg; prev=$arg; done\n\
+                 printf '#!/bin/sh\\nprintf \"{stdout}\"\\n' > \"$out\"\nchmod +x \"$out\"\n"
+            ),
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let harness = ConstEval {
+            driver: Rustc::new(&rustc),
+            ..ConstEval::default()
+        };
+        let outcome = harness.check("fn main() {}").expect("builds and runs");
+        fs::remove_dir_all(&dir).expect("cleans up");
+        outcome
+    }
+
+    #[test]
+    fn the_two_results_are_compared() {
+        let agree = "compile time: (1,)\\\\nrun time: (1,)\\\\n";
+        assert_eq!(check("agree", agree, None), ConstOutcome::Agree);
+        let differ = "compile time: (1,)\\\\nrun time: (2,)\\\\n";
+        assert_eq!(
+            check("differ", differ, None),
+            ConstOutcome::Diverged {
+                compile_time: "(1,)".to_owned(),
+                run_time: "(2,)".to_owned(),
+            }
+        );
+        assert_eq!(check("one", "run time: (2,)", None), ConstOutcome::Unpaired);
+    }
+
+    #[test]
+    fn const_evaluation_errors_are_findings() {
+        let eval = "error[E0080]: evaluation of constant value failed";
+        let outcome = check("eval", "", Some(eval));
+        assert!(matches!(outcome, ConstOutcome::EvalFailed(_)), "{outcome}");
+        assert_eq!(outcome.severity(), Some(Severity::Ice));
+        let types = "error[E0308]: mismatched types";
+        assert_eq!(check("types", "", Some(types)), ConstOutcome::Rejected);
+    }
+}
@@ -0,0 +1,166 @@
+//! Running executions in containers.
+//!
+//! On a shared CI machine, or to reproduce a finding on exactly the
+//! toolchain it was found with, [`Rustc::container`](super::Rustc::container)
+//! runs every execution in a fresh podman or docker container of a pinned
+//! image instead of on the host. The scratch root is mounted at the same
+//! path inside, so paths mean the same on both sides: the execution's
+//! scratch directory, which is its working directory, and the directories
+//! harnesses build in and run binaries from across executions. The
+//! program path (`rustc` by default) is looked up in the image. Limits carry over: memory becomes
+//! the container's `--memory`, the network is off unless asked for, and a
+//! container that outlives its timeout is removed.
+
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Exit codes a container engine uses for its own failures: the engine
+/// itself, a program that cannot be executed, and one that is not there.
+pub const ENGINE_ERRORS: [i32; 3] = [125, 126, 127];
+
+/// Where and how executions are contained.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Container {
+    /// `podman`, `docker` or a path to either.
+    pub engine: PathBuf,
+    /// The image, pinned by digest (`name@sha256:…`) so every run uses
+    /// the same toolchain.
+    pub image: String,
+    /// Let the container reach the network.
+    pub network: bool,
+    /// Further arguments to `run`, such as `--cpus=1`.
+    pub args: Vec<String>,
+}
+
+impl Container {
+    pub fn new(image: impl Into<String>) -> Self {
+        Container {
+            engine: PathBuf::from("podman"),
+            image: image.into(),
+            network: false,
+            args: Vec::new(),
+        }
+    }
+
+    /// Whether the image is named by digest rather than by a tag that can
+    /// move.
+    pub fn is_pinned(&self) -> bool {
+        self.image.contains("@sha256:")
+    }
+
+    fn is_podman(&self) -> bool {
+        self.engine
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().contains("podman"))
+    }
+
+    /// The engine command that runs `program` with `args` and `env` in a
+    /// container named after `scratch`, working there, with the scratch
+    /// root holding it and its siblings mounted and at most `memory` bytes
+    /// if given.
+    pub fn command(
+        &self,
+        program: &OsStr,
+        args: &[OsString],
+        env: &[(OsString, OsString)],
+        scratch: &Path,
+        memory: Option<u64>,
+    ) -> Command {
+        let mut command = Command::new(&self.engine);
+        command.args(["run", "--rm", "--interactive", "--name"]);
+        command.arg(scratch.file_name().unwrap_or_default());
+        // Files the program writes to the scratch directory stay ours.
+        if self.is_podman() {
+            command.arg("--userns=keep-id");
+        } else {
+            // SAFETY: getuid and getgid cannot fail.
+            let (uid, gid) = unsafe { (libc::getuid(), libc::getgid()) };
+            command.arg(format!("--user={uid}:{gid}"));
+        }
+        if !self.network {
+            command.arg("--network=none");
+        }
+        if let Some(bytes) = memory {
+            // Swap would let it use more than the limit says.
+            command.args([
+                format!("--memory={bytes}"),
+                format!("--memory-swap={bytes}"),
+            ]);
+        }
+        let root = scratch.parent().unwrap_or(scratch);
+        let mut volume = root.as_os_str().to_owned();
+        volume.push(":");
+        volume.push(root);
+        command.arg("--volume").arg(volume);
+        command.arg("--workdir").arg(scratch);
+        for (key, value) in env {
+            let mut pair = key.clone();
+            pair.push("=");
+            pair.push(value);
+            command.arg("--env").arg(pair);
+        }
+        command.args(&self.args);
+        command.arg(&self.image).arg(program).args(args);
+        command
+    }
+
+    /// The engine command that removes container `name`, running or not.
+    pub fn remove(&self, name: &str) -> Command {
+        let mut command = Command::new(&self.engine);
+        command.args(["rm", "--force", name]);
+        command
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::rustc::Scratch;
+
+    /// The host side of each `--volume` of `command`, which this module
+    /// mounts at the same path inside.
+    fn mounts(command: &Command) -> Vec<PathBuf> {
+        let args: Vec<&OsStr> = command.get_args().collect();
+        args.windows(2)
+            .filter(|pair| pair[0] == "--volume")
+            .map(|pair| {
+                let volume = pair[1].to_string_lossy();
+                let (host, inside) = volume.split_once(':').expect("host:container");
+                assert_eq!(host, inside);
+                PathBuf::from(host)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn output_in_a_sibling_scratch_directory_is_mounted() {
+        let root = std::env::temp_dir();
+        let execution = Scratch::new(&root).expect("creates scratch");
+        let build = Scratch::new(&root).expect("creates scratch");
+        let binary = build.path().join("binary");
+        let args = ["-o".into(), binary.clone().into_os_string()];
+        let command = Container::new("rust@sha256:0").command(
+            "rustc".as_ref(),
+            &args,
+            &[],
+            execution.path(),
+            None,
+        );
+        let mounts = mounts(&command);
+        assert!(
+            mounts.iter().any(|mount| binary.starts_with(mount)),
+            "{} is not under {mounts:?}",
+            binary.display()
+        );
+        assert!(mounts
+            .iter()
+            .any(|mount| execution.path().starts_with(mount)));
+        let args: Vec<&OsStr> = command.get_args().collect();
+        let workdir = args.iter().position(|arg| *arg == "--workdir");
+        assert_eq!(
+            workdir.map(|i| args[i + 1]),
+            Some(execution.path().as_os_str())
+        );
+    }
+}
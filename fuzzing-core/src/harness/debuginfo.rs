@@ -0,0 +1,228 @@
+//! Debuginfo validation harness.
+//!
+//! Compiles each seed to an object file with `-Cdebuginfo=2` at a few
+//! optimization levels and runs `llvm-dwarfdump --verify` on it. Debuginfo
+//! is a large part of codegen that no other oracle looks at: nothing
+//! executes it, and a wrong type description or location list only shows
+//! when someone opens a debugger. Only the seed's own object is checked;
+//! linked binaries carry the standard library's DWARF too, which an
+//! older `llvm-dwarfdump` does not always accept.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use super::behavior::build;
+use super::rustc::Scratch;
+use super::{ExecOutcome, Rustc};
+use crate::triage::Severity;
+
+/// Compiles seeds with debuginfo and verifies it.
+#[derive(Debug, Clone)]
+pub struct DebugInfo {
+    /// Compiles the seeds and runs `llvm-dwarfdump`. Its phase is ignored;
+    /// debuginfo is emitted during codegen.
+    pub driver: Rustc,
+    pub dwarfdump: PathBuf,
+    /// Each seed is compiled once per level. Optimized builds describe
+    /// variables with location lists, unoptimized ones do not.
+    pub opt_levels: Vec<String>,
+    pub edition: String,
+}
+
+impl Default for DebugInfo {
+    fn default() -> Self {
+        DebugInfo {
+            driver: Rustc::default(),
+            dwarfdump: PathBuf::from("llvm-dwarfdump"),
+            opt_levels: vec!["0".to_owned(), "3".to_owned()],
+            edition: "2021".to_owned(),
+        }
+    }
+}
+
+/// What compiling and verifying one seed led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DebugOutcome {
+    /// The debuginfo verified at every level.
+    Valid,
+    /// The seed does not compile.
+    Rejected,
+    /// rustc ICEd or crashed at `opt_level`.
+    Ice {
+        opt_level: String,
+        outcome: ExecOutcome,
+    },
+    /// `llvm-dwarfdump --verify` reported errors for the object built at
+    /// `opt_level`.
+    Invalid {
+        opt_level: String,
+        errors: Vec<String>,
+    },
+    /// `llvm-dwarfdump` itself crashed or hung, which malformed input is
+    /// the usual cause of.
+    VerifierFailed {
+        opt_level: String,
+        outcome: ExecOutcome,
+    },
+}
+
+impl DebugOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            DebugOutcome::Ice { outcome, .. } => Some(Severity::of_failure(outcome)),
+            DebugOutcome::Invalid { .. } | DebugOutcome::VerifierFailed { .. } => {
+                Some(Severity::WrongCode)
+            }
+            DebugOutcome::Valid | DebugOutcome::Rejected => None,
+        }
+    }
+}
+
+impl fmt::Display for DebugOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DebugOutcome::Valid => f.write_str("valid debuginfo"),
+            DebugOutcome::Rejected => f.write_str("rejected"),
+            DebugOutcome::Ice { opt_level, outcome } => {
+                write!(f, "{outcome} at -Copt-level={opt_level}")
+            }
+            DebugOutcome::Invalid { opt_level, errors } => write!(
+                f,
+                "invalid debuginfo at -Copt-level={opt_level}: {}",
+                errors.first().map_or("", String::as_str)
+            ),
+            DebugOutcome::VerifierFailed { opt_level, outcome } => write!(
+                f,
+                "llvm-dwarfdump failed on -Copt-level={opt_level} output: {outcome}"
+            ),
+        }
+    }
+}
+
+impl DebugInfo {
+    pub fn check(&self, source: &str) -> io::Result<DebugOutcome> {
+        let dir = Scratch::new(&self.driver.scratch_root)?;
+        for opt_level in &self.opt_levels {
+            let object = dir.path().join(format!("seed-{opt_level}.o"));
+            let args = [
+                "--crate-type=lib",
+                "--emit=obj",
+                "--edition",
+                &self.edition,
+                "-Cdebuginfo=2",
+                &format!("-Copt-level={opt_level}"),
+            ]
+            .map(str::to_owned);
+            match build(&self.driver, &args, source, &object)? {
+                ExecOutcome::Success(_) => {}
+                ExecOutcome::Error { .. } => return Ok(DebugOutcome::Rejected),
+                outcome => {
+                    return Ok(DebugOutcome::Ice {
+                        opt_level: opt_level.clone(),
+                        outcome,
+                    })
+                }
+            }
+
+            let verified = self.driver.run_program(
+                &self.dwarfdump,
+                ["--verify", &object.display().to_string()],
+                &[],
+            )?;
+            match verified {
+                ExecOutcome::Success(_) => {}
+                ExecOutcome::Error { output, .. } => {
+                    return Ok(DebugOutcome::Invalid {
+                        opt_level: opt_level.clone(),
+                        errors: output
+                            .stdout
+                            .lines()
+                            .chain(output.stderr.lines())
+                            .filter_map(|line| line.trim().strip_prefix("error: "))
+                            .map(str::to_owned)
+                            .collect(),
+                    })
+                }
+                outcome => {
+                    return Ok(DebugOutcome::VerifierFailed {
+                        opt_level: opt_level.clone(),
+                        outcome,
+                    })
+                }
+            }
+        }
+        Ok(DebugOutcome::Valid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn verifier_errors_are_collected_per_level() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-debuginfo-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        // Writes the opt level into the object; the verifier rejects
+        // optimized ones.
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            "#!/bin/sh\ncat >/dev/null\nfor arg; do\n  \
+             [ \"$prev\" = -o ] && out=$arg\n  prev=$arg\n  \
+             case \"$arg\" in -Copt-level=*) level=${arg#-Copt-level=} ;; esac\ndone\n\
+             echo $level > \"$out\"\n",
+        )
+        .expect("writes the rustc");
+        let dwarfdump = dir.join("llvm-dwarfdump");
+        fs::write(
+            &dwarfdump,
+            "#!/bin/sh\n[ \"$(cat \"$2\")\" = 0 ] && exit 0\n\
+             echo 'Verifying .debug_info Unit Header Chain...'\n\
+             echo 'error: DIE has invalid DW_AT_location'\n\
+             echo 'error: overlapping address ranges' >&2\nexit 1\n",
+        )
+        .expect("writes the dwarfdump");
+        for program in [&rustc, &dwarfdump] {
+            fs::set_permissions(program, fs::Permissions::from_mode(0o755))
+                .expect("makes it runnable");
+        }
+        let harness = DebugInfo {
+            driver: Rustc::new(&rustc),
+            dwarfdump,
+            ..DebugInfo::default()
+        };
+        let outcome = harness.check("pub fn f() {}").expect("compiles");
+        assert_eq!(
+            outcome,
+            DebugOutcome::Invalid {
+                opt_level: "3".to_owned(),
+                errors: vec![
+                    "DIE has invalid DW_AT_location".to_owned(),
+                    "overlapping address ranges".to_owned(),
+                ],
+            }
+        );
+        assert_eq!(
+            outcome.to_string(),
+            "invalid debuginfo at -Copt-level=3: DIE has invalid DW_AT_location"
+        );
+        let unoptimized = DebugInfo {
+            opt_levels: vec!["0".to_owned()],
+            ..harness
+        };
+        assert_eq!(
+            unoptimized.check("pub fn f() {}").expect("compiles"),
+            DebugOutcome::Valid
+        );
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,334 @@
+//! gccrs frontend differential.
+//!
+//! Checks each seed with gccrs and with rustc and compares whether they
+//! accept it and, when both reject it, which errors they give. rustc is
+//! the reference, but a disagreement is as likely to point at behavior the
+//! Reference leaves unspecified as at a gccrs bug. gccrs does not
+//! implement the whole language yet; rejections whose messages match
+//! [`Gccrs::allowlist`] are set aside rather than reported.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use super::rustc::Scratch;
+use super::{ExecOutcome, Phase, Rustc};
+use crate::oracle::stability::error_codes;
+use crate::triage::Severity;
+
+/// Runs gccrs and rustc on seeds.
+#[derive(Debug, Clone)]
+pub struct Gccrs {
+    pub path: PathBuf,
+    pub edition: String,
+    /// Messages gccrs gives for features it does not implement.
+    pub allowlist: Vec<String>,
+    /// The reference compiler. Only the frontend is compared, so it stops
+    /// after analysis by default.
+    pub rustc: Rustc,
+}
+
+impl Default for Gccrs {
+    fn default() -> Self {
+        Gccrs {
+            path: PathBuf::from("gccrs"),
+            edition: "2021".to_owned(),
+            allowlist: [
+                "sorry, unimplemented",
+                "not yet implemented",
+                "is not supported",
+                "unknown lang item",
+            ]
+            .map(str::to_owned)
+            .to_vec(),
+            rustc: Rustc {
+                phase: Phase::Analysis,
+                ..Rustc::default()
+            },
+        }
+    }
+}
+
+/// Which compiler a finding is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Frontend {
+    Rustc,
+    Gccrs,
+}
+
+impl fmt::Display for Frontend {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Frontend::Rustc => "rustc",
+            Frontend::Gccrs => "gccrs",
+        })
+    }
+}
+
+/// What checking one seed with both frontends led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GccrsOutcome {
+    /// Both accepted, or both rejected with compatible errors.
+    Agree,
+    /// gccrs rejected the seed for a reason on the allowlist.
+    Unimplemented(String),
+    /// Only `accepted_by` accepted the seed; `errors` are the other
+    /// frontend's error codes.
+    AcceptanceDiffers {
+        accepted_by: Frontend,
+        errors: Vec<String>,
+    },
+    /// Both rejected the seed, with no error code in common.
+    ErrorsDiffer {
+        rustc: Vec<String>,
+        gccrs: Vec<String>,
+    },
+    /// A compiler crashed, hung or reported an internal error.
+    Failed {
+        frontend: Frontend,
+        outcome: ExecOutcome,
+    },
+}
+
+impl GccrsOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            GccrsOutcome::Failed { outcome, .. } => Some(Severity::of_failure(outcome)),
+            GccrsOutcome::AcceptanceDiffers { .. } | GccrsOutcome::ErrorsDiffer { .. } => {
+                Some(Severity::Diagnostic)
+            }
+            GccrsOutcome::Agree | GccrsOutcome::Unimplemented(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for GccrsOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GccrsOutcome::Agree => f.write_str("frontends agree"),
+            GccrsOutcome::Unimplemented(message) => write!(f, "unimplemented in gccrs: {message}"),
+            GccrsOutcome::AcceptanceDiffers {
+                accepted_by,
+                errors,
+            } => write!(f, "only {accepted_by} accepts: [{}]", errors.join(", ")),
+            GccrsOutcome::ErrorsDiffer { rustc, gccrs } => write!(
+                f,
+                "errors differ: rustc [{}], gccrs [{}]",
+                rustc.join(", "),
+                gccrs.join(", ")
+            ),
+            GccrsOutcome::Failed { frontend, outcome } => write!(f, "{frontend} failed: {outcome}"),
+        }
+    }
+}
+
+impl Gccrs {
+    pub fn check(&self, source: &str) -> io::Result<GccrsOutcome> {
+        let rustc = self.rustc.run(
+            ["--crate-type=lib", "--edition", &self.edition, "-"],
+            source.as_bytes(),
+        )?;
+        if !matches!(rustc, ExecOutcome::Success(_) | ExecOutcome::Error { .. }) {
+            return Ok(GccrsOutcome::Failed {
+                frontend: Frontend::Rustc,
+                outcome: rustc,
+            });
+        }
+
+        // gccrs picks the language from the file extension.
+        let dir = Scratch::new(&self.rustc.scratch_root)?;
+        let file = dir.path().join("seed.rs");
+        fs::write(&file, source)?;
+        let edition = format!("-frust-edition={}", self.edition);
+        let gccrs = self.rustc.run_program(
+            &self.path,
+            [
+                "-fsyntax-only",
+                "-frust-incomplete-and-experimental-compiler-do-not-use",
+                &edition,
+                &file.display().to_string(),
+            ],
+            &[],
+        )?;
+        let gccrs_stderr = &gccrs.output().stderr;
+        // GCC reports internal errors with its own exit code, not 101.
+        if !matches!(gccrs, ExecOutcome::Success(_) | ExecOutcome::Error { .. })
+            || gccrs_stderr.contains("internal compiler error")
+        {
+            return Ok(GccrsOutcome::Failed {
+                frontend: Frontend::Gccrs,
+                outcome: gccrs,
+            });
+        }
+        if !gccrs.is_success() {
+            if let Some(message) = self.unimplemented(gccrs_stderr) {
+                return Ok(GccrsOutcome::Unimplemented(message));
+            }
+        }
+
+        let rustc_errors = error_codes(&rustc.output().stderr);
+        let gccrs_errors = gccrs_error_codes(gccrs_stderr);
+        Ok(match (rustc.is_success(), gccrs.is_success()) {
+            (true, true) => GccrsOutcome::Agree,
+            (true, false) => GccrsOutcome::AcceptanceDiffers {
+                accepted_by: Frontend::Rustc,
+                errors: gccrs_errors,
+            },
+            (false, true) => GccrsOutcome::AcceptanceDiffers {
+                accepted_by: Frontend::Gccrs,
+                errors: rustc_errors,
+            },
+            (false, false) => {
+                // Error recovery differs too much for the full lists to
+                // match; only codes both sides give are comparable.
+                let ours: BTreeSet<_> = coded(&rustc_errors).collect();
+                let theirs: BTreeSet<_> = coded(&gccrs_errors).collect();
+                if ours.is_empty() || theirs.is_empty() || !ours.is_disjoint(&theirs) {
+                    GccrsOutcome::Agree
+                } else {
+                    GccrsOutcome::ErrorsDiffer {
+                        rustc: rustc_errors,
+                        gccrs: gccrs_errors,
+                    }
+                }
+            }
+        })
+    }
+
+    /// The first gccrs error message that matches the allowlist.
+    fn unimplemented(&self, stderr: &str) -> Option<String> {
+        stderr
+            .lines()
+            .filter(|line| line.contains("error:") || line.contains("sorry,"))
+            .find(|line| {
+                self.allowlist
+                    .iter()
+                    .any(|entry| line.contains(entry.as_str()))
+            })
+            .map(|line| line.trim().to_owned())
+    }
+}
+
+/// Error codes from GCC-style diagnostics (`seed.rs:3:5: error: mismatched
+/// types [E0308]`), with `error` for errors without one.
+fn gccrs_error_codes(stderr: &str) -> Vec<String> {
+    stderr
+        .lines()
+        .filter(|line| line.contains(" error: ") || line.starts_with("error: "))
+        .map(|line| {
+            line.trim_end()
+                .strip_suffix(']')
+                .and_then(|rest| rest.rsplit_once('['))
+                .map(|(_, code)| code)
+                .filter(|code| is_error_code(code))
+                .unwrap_or("error")
+                .to_owned()
+        })
+        .collect()
+}
+
+/// `E` and four digits.
+fn is_error_code(code: &str) -> bool {
+    code.len() == 5 && code.starts_with('E') && code[1..].bytes().all(|b| b.is_ascii_digit())
+}
+
+fn coded(errors: &[String]) -> impl Iterator<Item = &str> {
+    errors
+        .iter()
+        .map(String::as_str)
+        .filter(|code| *code != "error")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn gcc_diagnostics_give_their_codes() {
+        let stderr = "seed.rs:3:5: error: mismatched types [E0308]\n\
+                      seed.rs:4:1: error: failed to resolve\n\
+                      seed.rs:5:1: warning: unused [-Wunused-variable]\n\
+                      error: [not a code]\n";
+        assert_eq!(gccrs_error_codes(stderr), ["E0308", "error", "error"]);
+    }
+
+    /// Checks a seed that rustc rejects with E0308, with a gccrs that runs
+    /// `gccrs`.
+    fn check(name: &str, gccrs: &str) -> GccrsOutcome {
+        let dir =
+            std::env::temp_dir().join(format!("rustc-fuzz-gccrs-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        let rustc = dir.join("rustc");
+        let path = dir.join("gccrs");
+        fs::write(
+            &rustc,
+            "#!/bin/sh\necho 'error[E0308]: mismatched types' >&2\nexit 1\n",
+        )
+        .expect("writes the rustc");
+        fs::write(&path, format!("#!/bin/sh\n{gccrs}\n")).expect("writes the gccrs");
+        for program in [&rustc, &path] {
+            fs::set_permissions(program, fs::Permissions::from_mode(0o755))
+                .expect("makes it runnable");
+        }
+        let harness = Gccrs {
+            path,
+            rustc: Rustc::new(&rustc),
+            ..Gccrs::default()
+        };
+        let outcome = harness.check("fn f() -> u8 { true }").expect("runs both");
+        fs::remove_dir_all(&dir).expect("cleans up");
+        outcome
+    }
+
+    #[test]
+    fn frontends_are_compared_by_error_code() {
+        let same = "echo \"$4:1:16: error: mismatched types [E0308]\" >&2; exit 1";
+        assert_eq!(check("same", same), GccrsOutcome::Agree);
+        let other = "echo \"$4:1:16: error: oops [E0599]\" >&2; exit 1";
+        assert_eq!(
+            check("other", other),
+            GccrsOutcome::ErrorsDiffer {
+                rustc: vec!["E0308".to_owned()],
+                gccrs: vec!["E0599".to_owned()],
+            }
+        );
+        let accepted = check("accepts", "exit 0");
+        assert_eq!(
+            accepted,
+            GccrsOutcome::AcceptanceDiffers {
+                accepted_by: Frontend::Gccrs,
+                errors: vec!["E0308".to_owned()],
+            }
+        );
+        assert_eq!(accepted.severity(), Some(Severity::Diagnostic));
+    }
+
+    #[test]
+    fn unimplemented_features_and_gcc_ices_are_told_apart() {
+        let sorry = "echo \"$4:1:1: sorry, unimplemented: bool literals\" >&2; exit 1";
+        let GccrsOutcome::Unimplemented(message) = check("sorry", sorry) else {
+            panic!("on the allowlist");
+        };
+        assert!(message.ends_with("sorry, unimplemented: bool literals"));
+        let ice = "echo \"$4:1:1: internal compiler error: in visit\" >&2; exit 4";
+        let failed = check("ice", ice);
+        assert!(
+            matches!(
+                failed,
+                GccrsOutcome::Failed {
+                    frontend: Frontend::Gccrs,
+                    ..
+                }
+            ),
+            "{failed}"
+        );
+    }
+}
@@ -0,0 +1,319 @@
+//! Incremental compilation harness.
+//!
+//! Builds a seed with `-Cincremental`, mutates it, rebuilds the mutant
+//! into the same session directory and compares that with a clean build of
+//! the mutant. The dep graph decides what the second build reuses; reusing
+//! something the mutation invalidated shows up as different diagnostics,
+//! a binary that behaves differently, or an ICE when
+//! `-Zincremental-verify-ich` catches a fingerprint mismatch.
+
+use std::fmt;
+use std::io;
+use std::iter;
+use std::path::Path;
+
+use rand::RngCore;
+
+use super::behavior::Behavior;
+use super::rustc::Scratch;
+use super::{ExecOutcome, Rustc};
+use crate::mutate::Engine;
+use crate::oracle::stability::{self, Divergence};
+use crate::triage::Severity;
+
+/// One of the three builds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Build {
+    /// The seed, filling the session directory.
+    Initial,
+    /// The mutant, reusing the session.
+    Incremental,
+    /// The mutant from scratch.
+    Clean,
+}
+
+impl fmt::Display for Build {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Build::Initial => "initial build",
+            Build::Incremental => "incremental rebuild",
+            Build::Clean => "clean build",
+        })
+    }
+}
+
+/// Builds seeds and their mutants incrementally.
+#[derive(Debug, Clone)]
+pub struct Incremental {
+    /// Compiles the seeds. Its phase is ignored; incremental state is only
+    /// saved by builds that go through codegen.
+    pub driver: Rustc,
+    pub edition: String,
+    /// Passes `-Zincremental-verify-ich`, which recomputes the fingerprint
+    /// of every reused result and ICEs when it changed.
+    pub verify_ich: bool,
+}
+
+impl Default for Incremental {
+    fn default() -> Self {
+        Incremental {
+            driver: Rustc::default(),
+            edition: "2021".to_owned(),
+            verify_ich: true,
+        }
+    }
+}
+
+/// What building one seed and its mutant led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IncrementalOutcome {
+    /// The incremental rebuild matched the clean build.
+    Consistent,
+    /// The seed does not compile.
+    Rejected,
+    /// No operator could mutate the seed.
+    Unmutated,
+    /// `build` ICEd or crashed. `mutant` is the source of the second
+    /// build; the seed is the first.
+    Ice {
+        build: Build,
+        outcome: ExecOutcome,
+        mutant: String,
+    },
+    /// The rebuild reported different errors from the clean build.
+    DiagnosticsDiffer {
+        divergence: Divergence,
+        mutant: String,
+    },
+    /// Both builds of an executable mutant succeeded, but the binaries
+    /// behave differently.
+    BehaviorDiffers {
+        clean: Behavior,
+        incremental: Behavior,
+        mutant: String,
+    },
+}
+
+impl IncrementalOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            IncrementalOutcome::Ice { outcome, .. } => Some(Severity::of_failure(outcome)),
+            IncrementalOutcome::DiagnosticsDiffer { .. } => Some(Severity::Diagnostic),
+            IncrementalOutcome::BehaviorDiffers { .. } => Some(Severity::WrongCode),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for IncrementalOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IncrementalOutcome::Consistent => f.write_str("consistent"),
+            IncrementalOutcome::Rejected => f.write_str("rejected"),
+            IncrementalOutcome::Unmutated => f.write_str("no mutation applied"),
+            IncrementalOutcome::Ice { build, outcome, .. } => write!(f, "{build}: {outcome}"),
+            IncrementalOutcome::DiagnosticsDiffer { divergence, .. } => {
+                write!(f, "incremental rebuild: {divergence}")
+            }
+            IncrementalOutcome::BehaviorDiffers {
+                clean, incremental, ..
+            } if clean.exit != incremental.exit => write!(
+                f,
+                "incremental rebuild diverges: clean {} but incremental {}",
+                clean.exit, incremental.exit
+            ),
+            IncrementalOutcome::BehaviorDiffers { .. } => {
+                f.write_str("incremental rebuild output diverges")
+            }
+        }
+    }
+}
+
+impl Incremental {
+    pub fn check(
+        &self,
+        source: &str,
+        engine: &Engine,
+        rng: &mut dyn RngCore,
+    ) -> io::Result<IncrementalOutcome> {
+        let dir = Scratch::new(&self.driver.scratch_root)?;
+        let session = dir.path().join("incremental");
+        let initial = self.build(source, Some(&session), &dir.path().join("initial"))?;
+        if initial.is_crash() {
+            return Ok(IncrementalOutcome::Ice {
+                build: Build::Initial,
+                outcome: initial,
+                mutant: String::new(),
+            });
+        }
+        if !initial.is_success() {
+            return Ok(IncrementalOutcome::Rejected);
+        }
+        let Some(mutated) = engine.mutate(source, rng) else {
+            return Ok(IncrementalOutcome::Unmutated);
+        };
+        let mutant = mutated.output;
+
+        let incremental_binary = dir.path().join("incremental-bin");
+        let incremental = self.build(&mutant, Some(&session), &incremental_binary)?;
+        let clean_binary = dir.path().join("clean-bin");
+        let clean = self.build(&mutant, None, &clean_binary)?;
+        for (build, outcome) in [
+            (Build::Incremental, incremental.clone()),
+            (Build::Clean, clean.clone()),
+        ] {
+            if outcome.is_crash() {
+                return Ok(IncrementalOutcome::Ice {
+                    build,
+                    outcome,
+                    mutant,
+                });
+            }
+        }
+        if let Err(divergence) = stability::compare(
+            stability::error_codes(&clean.output().stderr),
+            stability::error_codes(&incremental.output().stderr),
+        ) {
+            return Ok(IncrementalOutcome::DiagnosticsDiffer { divergence, mutant });
+        }
+        if !clean.is_success() || !incremental.is_success() || !is_executable(&mutant) {
+            return Ok(IncrementalOutcome::Consistent);
+        }
+        let clean = Behavior::of(&self.execute(&clean_binary)?);
+        let incremental = Behavior::of(&self.execute(&incremental_binary)?);
+        Ok(if clean == incremental {
+            IncrementalOutcome::Consistent
+        } else {
+            IncrementalOutcome::BehaviorDiffers {
+                clean,
+                incremental,
+                mutant,
+            }
+        })
+    }
+
+    /// Builds `source` to `output`, as a binary when it has a `main`.
+    fn build(
+        &self,
+        source: &str,
+        session: Option<&Path>,
+        output: &Path,
+    ) -> io::Result<ExecOutcome> {
+        let crate_type = if is_executable(source) { "bin" } else { "lib" };
+        let mut args = vec![
+            format!("--crate-type={crate_type}"),
+            "--edition".to_owned(),
+            self.edition.clone(),
+            "-o".to_owned(),
+            output.display().to_string(),
+        ];
+        if let Some(session) = session {
+            args.push(format!("-Cincremental={}", session.display()));
+            if self.verify_ich {
+                args.push("-Zincremental-verify-ich".to_owned());
+            }
+        }
+        args.push("-".to_owned());
+        self.driver
+            .run_program(&self.driver.path, &args, source.as_bytes())
+    }
+
+    fn execute(&self, binary: &Path) -> io::Result<ExecOutcome> {
+        self.driver
+            .run_sandboxed(binary, iter::empty::<&str>(), &[])
+    }
+}
+
+/// Whether `source` declares a top-level `fn main`.
+fn is_executable(source: &str) -> bool {
+    syn::parse_file(source).is_ok_and(|file| {
+        file.items
+            .iter()
+            .any(|item| matches!(item, syn::Item::Fn(f) if f.sig.ident == "main"))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mutate::Mutator;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Adds an item, leaving `main` alone.
+    struct AddItem;
+
+    impl Mutator for AddItem {
+        fn name(&self) -> &'static str {
+            "add-item"
+        }
+
+        fn mutate(&self, input: &str, _rng: &mut dyn RngCore) -> Option<String> {
+            Some(format!("{input}\nfn helper() {{}}\n"))
+        }
+    }
+
+    #[test]
+    fn executables_need_a_top_level_main() {
+        assert!(is_executable("fn main() {}"));
+        assert!(!is_executable("mod m { fn main() {} }"));
+        assert!(!is_executable("fn main( {"));
+    }
+
+    #[test]
+    fn stale_reuse_shows_in_the_binary() {
+        let dir =
+            std::env::temp_dir().join(format!("rustc-fuzz-incremental-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        // Builds binaries that print `stale` when rebuilt into an existing
+        // session.
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            "#!/bin/sh\ncat >/dev/null\nbuilt=fresh\nfor arg; do\n  \
+             [ \"$prev\" = -o ] && out=$arg\n  prev=$arg\n  \
+             case \"$arg\" in -Cincremental=*)\n    session=${arg#-Cincremental=}\n    \
+             [ -d \"$session\" ] && built=stale\n    mkdir -p \"$session\" ;;\n  esac\ndone\n\
+             printf '#!/bin/sh\\necho %s\\n' $built > \"$out\"\nchmod +x \"$out\"\n",
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let harness = Incremental {
+            driver: Rustc::new(&rustc),
+            ..Incremental::default()
+        };
+        let mut engine = Engine::empty();
+        engine.push(Box::new(AddItem));
+        let mut rng = StdRng::seed_from_u64(0);
+        let outcome = harness
+            .check("fn main() {}\n", &engine, &mut rng)
+            .expect("builds");
+        let IncrementalOutcome::BehaviorDiffers {
+            clean,
+            incremental,
+            mutant,
+        } = &outcome
+        else {
+            panic!("{outcome:?}");
+        };
+        assert_eq!(
+            (&*clean.stdout, &*incremental.stdout),
+            ("fresh\n", "stale\n")
+        );
+        assert!(mutant.contains("fn helper()"));
+        assert_eq!(outcome.severity(), Some(Severity::WrongCode));
+
+        let libraries = harness
+            .check("fn f() {}\n", &engine, &mut rng)
+            .expect("builds");
+        assert_eq!(libraries, IncrementalOutcome::Consistent);
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,242 @@
+//! `-Zrandomize-layout` consistency harness.
+//!
+//! Builds each executable seed with the default layout and under a few
+//! `-Zlayout-seed`s with `-Zrandomize-layout`, which shuffles the fields of
+//! every `repr(Rust)` type, and compares the runs. Code that never asks
+//! about layout must not notice: a difference is a layout assumption baked
+//! into rustc (or into the program, for seeds that inspect it, which are
+//! skipped), and an ICE under shuffling is a layout computation bug.
+
+use std::fmt;
+use std::io;
+
+use super::behavior::{build_and_run, Behavior};
+use super::rustc::Scratch;
+use super::{ExecOutcome, Rustc};
+use crate::lex::{self, TokenKind};
+use crate::triage::Severity;
+
+/// Functions and macros whose results depend on layout.
+const LAYOUT_QUERIES: &[&str] = &[
+    "size_of",
+    "size_of_val",
+    "align_of",
+    "align_of_val",
+    "offset_of",
+    "transmute",
+    "transmute_copy",
+];
+
+/// Builds and runs seeds under randomized layouts.
+#[derive(Debug, Clone)]
+pub struct LayoutRandomization {
+    /// Compiles the seeds and runs the binaries. Its phase is ignored;
+    /// every build goes through codegen.
+    pub driver: Rustc,
+    /// `-Zlayout-seed`s to try.
+    pub seeds: Vec<u64>,
+    /// Codegen flags shared by every build.
+    pub codegen: Vec<String>,
+    pub edition: String,
+}
+
+impl Default for LayoutRandomization {
+    fn default() -> Self {
+        LayoutRandomization {
+            driver: Rustc::default(),
+            seeds: vec![1, 2, 3],
+            codegen: vec!["-Copt-level=0".to_owned()],
+            edition: "2021".to_owned(),
+        }
+    }
+}
+
+/// What building and running one seed under every layout led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LayoutOutcome {
+    /// Every layout behaved like the default one.
+    Agree,
+    /// The seed does not compile with the default layout.
+    Rejected,
+    /// The seed asks about layout, so its output may legitimately change.
+    ObservesLayout,
+    /// A run timed out, so there is nothing reliable to compare. `seed` is
+    /// `None` for the default layout.
+    Timeout { seed: Option<u64> },
+    /// rustc crashed, or failed with layout seed `seed` although the
+    /// default layout compiled. `seed` is `None` when the default build
+    /// crashed.
+    CompileFailed {
+        seed: Option<u64>,
+        outcome: ExecOutcome,
+    },
+    /// The build with layout seed `seed` behaved unlike the default one.
+    Diverged {
+        seed: u64,
+        baseline: Behavior,
+        behavior: Behavior,
+    },
+}
+
+impl LayoutOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            LayoutOutcome::CompileFailed { outcome, .. } => Some(Severity::of_failure(outcome)),
+            LayoutOutcome::Diverged { .. } => Some(Severity::WrongCode),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LayoutOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LayoutOutcome::Agree => f.write_str("layouts agree"),
+            LayoutOutcome::Rejected => f.write_str("rejected"),
+            LayoutOutcome::ObservesLayout => f.write_str("observes layout"),
+            LayoutOutcome::Timeout { seed: None } => f.write_str("timeout with default layout"),
+            LayoutOutcome::Timeout { seed: Some(seed) } => {
+                write!(f, "timeout with -Zlayout-seed={seed}")
+            }
+            LayoutOutcome::CompileFailed {
+                seed: None,
+                outcome,
+            } => write!(f, "{outcome} with default layout"),
+            LayoutOutcome::CompileFailed {
+                seed: Some(seed),
+                outcome,
+            } => write!(f, "{outcome} with -Zlayout-seed={seed}"),
+            LayoutOutcome::Diverged {
+                seed,
+                baseline,
+                behavior,
+            } if baseline.exit != behavior.exit => write!(
+                f,
+                "behavior diverges with -Zlayout-seed={seed}: {} -> {}",
+                baseline.exit, behavior.exit
+            ),
+            LayoutOutcome::Diverged { seed, .. } => {
+                write!(f, "output diverges with -Zlayout-seed={seed}")
+            }
+        }
+    }
+}
+
+impl LayoutRandomization {
+    pub fn check(&self, source: &str) -> io::Result<LayoutOutcome> {
+        if observes_layout(source) {
+            return Ok(LayoutOutcome::ObservesLayout);
+        }
+        let dir = Scratch::new(&self.driver.scratch_root)?;
+        let baseline = match self.build_and_run(source, None, &dir)? {
+            Ok(ExecOutcome::Timeout(_)) => return Ok(LayoutOutcome::Timeout { seed: None }),
+            Ok(run) => Behavior::of(&run),
+            Err(ExecOutcome::Error { .. }) => return Ok(LayoutOutcome::Rejected),
+            Err(outcome) => {
+                return Ok(LayoutOutcome::CompileFailed {
+                    seed: None,
+                    outcome,
+                })
+            }
+        };
+        for &seed in &self.seeds {
+            let behavior = match self.build_and_run(source, Some(seed), &dir)? {
+                Ok(ExecOutcome::Timeout(_)) => {
+                    return Ok(LayoutOutcome::Timeout { seed: Some(seed) })
+                }
+                Ok(run) => Behavior::of(&run),
+                Err(outcome) => {
+                    return Ok(LayoutOutcome::CompileFailed {
+                        seed: Some(seed),
+                        outcome,
+                    })
+                }
+            };
+            if behavior != baseline {
+                return Ok(LayoutOutcome::Diverged {
+                    seed,
+                    baseline,
+                    behavior,
+                });
+            }
+        }
+        Ok(LayoutOutcome::Agree)
+    }
+
+    fn build_and_run(
+        &self,
+        source: &str,
+        seed: Option<u64>,
+        dir: &Scratch,
+    ) -> io::Result<Result<ExecOutcome, ExecOutcome>> {
+        let mut args = vec!["--edition".to_owned(), self.edition.clone()];
+        args.extend(self.codegen.iter().cloned());
+        if let Some(seed) = seed {
+            args.push("-Zrandomize-layout".to_owned());
+            args.push(format!("-Zlayout-seed={seed}"));
+        }
+        build_and_run(&self.driver, &args, source, &dir.path().join("seed"))
+    }
+}
+
+/// Whether `source` calls something that reports layout. Printing
+/// addresses with `{:p}` is not caught.
+fn observes_layout(source: &str) -> bool {
+    lex::tokenize(source)
+        .iter()
+        .any(|token| token.kind == TokenKind::Ident && LAYOUT_QUERIES.contains(&token.text(source)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn layout_queries_are_recognized_in_code_only() {
+        assert!(observes_layout("let n = std::mem::size_of::<S>();"));
+        assert!(observes_layout("unsafe { transmute::<u32, f32>(x) }"));
+        assert!(!observes_layout("// size_of\nlet s = \"align_of\";"));
+        assert!(!observes_layout("let size_of_thing = 1;"));
+    }
+
+    #[test]
+    fn shuffled_layouts_are_compared_with_the_default() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-layout-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        // Binaries print the layout seed they were built with, and the
+        // same for seeds 1 and 3 as for the default layout.
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            "#!/bin/sh\ncat >/dev/null\nseed=0\nfor arg; do\n  \
+             [ \"$prev\" = -o ] && out=$arg\n  prev=$arg\n  \
+             [ \"$arg\" = -Zlayout-seed=2 ] && seed=2\ndone\n\
+             printf '#!/bin/sh\\necho %s\\n' $seed > \"$out\"\nchmod +x \"$out\"\n",
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let harness = LayoutRandomization {
+            driver: Rustc::new(&rustc),
+            ..LayoutRandomization::default()
+        };
+        let outcome = harness.check("fn main() {}").expect("builds and runs");
+        assert!(
+            matches!(outcome, LayoutOutcome::Diverged { seed: 2, .. }),
+            "{outcome:?}"
+        );
+        assert_eq!(
+            harness
+                .check("fn main() { std::mem::size_of::<u8>(); }")
+                .expect("skips"),
+            LayoutOutcome::ObservesLayout
+        );
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
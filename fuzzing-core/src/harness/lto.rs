@@ -0,0 +1,240 @@
+//! Link-time option matrix.
+//!
+//! Builds each executable seed with default link-time options and under a
+//! few sampled combinations of `-Clto`, `-Ccodegen-units`, `-Cpanic` and
+//! `-Cembed-bitcode`, runs every binary and compares it with the default
+//! build. LTO merges and re-optimizes modules a per-crate build never
+//! sees together, and miscompiles only it produces are a known class.
+
+use std::fmt;
+use std::io;
+
+use rand::RngCore;
+
+use super::behavior::{build_and_run, Behavior};
+use super::rustc::Scratch;
+use super::{ExecOutcome, Rustc};
+use crate::flags::{self, Flag, INCOMPATIBLE};
+use crate::triage::Severity;
+
+/// The options varied, with the values tried for each.
+pub const LINK_SCHEMA: &[Flag] = &[
+    Flag::new("-Clto", &["off", "thin", "fat"]),
+    Flag::new("-Ccodegen-units", &["1", "2", "16"]),
+    Flag::new("-Cpanic", &["unwind", "abort"]),
+    Flag::new("-Cembed-bitcode", &["yes", "no"]),
+];
+
+/// Builds and runs seeds under link-time option combinations.
+#[derive(Debug, Clone)]
+pub struct LtoMatrix {
+    /// Compiles the seeds and runs the binaries. Its phase is ignored;
+    /// every build links.
+    pub driver: Rustc,
+    /// Codegen flags shared by every build. LTO only re-optimizes when
+    /// there is an optimizer to run, so the default is `-Copt-level=2`.
+    pub codegen: Vec<String>,
+    /// Combinations tried per seed.
+    pub samples: usize,
+    pub edition: String,
+}
+
+impl Default for LtoMatrix {
+    fn default() -> Self {
+        LtoMatrix {
+            driver: Rustc::default(),
+            codegen: vec!["-Copt-level=2".to_owned()],
+            samples: 3,
+            edition: "2021".to_owned(),
+        }
+    }
+}
+
+/// What building and running one seed under every combination led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LtoOutcome {
+    /// Every build behaved like the default one.
+    Agree,
+    /// The seed does not build with default options.
+    Rejected,
+    /// A build or run timed out, so there is nothing reliable to compare.
+    Timeout { flags: Vec<String> },
+    /// Building under `flags` ICEd, crashed or failed although the default
+    /// build succeeded. `flags` is empty when the default build crashed.
+    Failed {
+        flags: Vec<String>,
+        outcome: ExecOutcome,
+    },
+    /// The build under `flags` behaved differently from the default one.
+    Diverged {
+        flags: Vec<String>,
+        baseline: Behavior,
+        behavior: Behavior,
+    },
+}
+
+impl LtoOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            LtoOutcome::Failed { outcome, .. } => Some(Severity::of_failure(outcome)),
+            LtoOutcome::Diverged { .. } => Some(Severity::WrongCode),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LtoOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LtoOutcome::Agree => f.write_str("link-time options agree"),
+            LtoOutcome::Rejected => f.write_str("rejected"),
+            LtoOutcome::Timeout { flags } if flags.is_empty() => {
+                f.write_str("timeout with default flags")
+            }
+            LtoOutcome::Timeout { flags } => write!(f, "timeout with {}", flags.join(" ")),
+            LtoOutcome::Failed { flags, outcome } if flags.is_empty() => {
+                write!(f, "{outcome} with default flags")
+            }
+            LtoOutcome::Failed { flags, outcome } => {
+                write!(f, "{outcome} with {}", flags.join(" "))
+            }
+            LtoOutcome::Diverged {
+                flags,
+                baseline,
+                behavior,
+            } if baseline.exit != behavior.exit => write!(
+                f,
+                "{} diverges: {} but default {}",
+                flags.join(" "),
+                behavior.exit,
+                baseline.exit
+            ),
+            LtoOutcome::Diverged { flags, .. } => {
+                write!(f, "output diverges with {}", flags.join(" "))
+            }
+        }
+    }
+}
+
+impl LtoMatrix {
+    pub fn check(&self, source: &str, rng: &mut dyn RngCore) -> io::Result<LtoOutcome> {
+        let dir = Scratch::new(&self.driver.scratch_root)?;
+        let baseline = match self.build_and_run(source, &[], &dir)? {
+            Ok(ExecOutcome::Timeout(_)) | Err(ExecOutcome::Timeout(_)) => {
+                return Ok(LtoOutcome::Timeout { flags: Vec::new() })
+            }
+            Ok(run) => run,
+            Err(ExecOutcome::Error { .. }) => return Ok(LtoOutcome::Rejected),
+            Err(outcome) => {
+                return Ok(LtoOutcome::Failed {
+                    flags: Vec::new(),
+                    outcome,
+                })
+            }
+        };
+        for _ in 0..self.samples {
+            let flags = flags::sample(LINK_SCHEMA, INCOMPATIBLE, LINK_SCHEMA.len(), rng);
+            let run = match self.build_and_run(source, &flags, &dir)? {
+                Ok(ExecOutcome::Timeout(_)) | Err(ExecOutcome::Timeout(_)) => {
+                    return Ok(LtoOutcome::Timeout { flags })
+                }
+                Ok(run) => run,
+                Err(outcome) => return Ok(LtoOutcome::Failed { flags, outcome }),
+            };
+            // A panic exits with 101 when it unwinds and dies of `SIGABRT`
+            // when it aborts; only whether it failed is comparable.
+            let (baseline, behavior) = if flags.iter().any(|f| f == "-Cpanic=abort") {
+                (Behavior::verdict(&baseline), Behavior::verdict(&run))
+            } else {
+                (Behavior::of(&baseline), Behavior::of(&run))
+            };
+            if baseline != behavior {
+                return Ok(LtoOutcome::Diverged {
+                    flags,
+                    baseline,
+                    behavior,
+                });
+            }
+        }
+        Ok(LtoOutcome::Agree)
+    }
+
+    fn build_and_run(
+        &self,
+        source: &str,
+        flags: &[String],
+        dir: &Scratch,
+    ) -> io::Result<Result<ExecOutcome, ExecOutcome>> {
+        let mut args = vec!["--edition".to_owned(), self.edition.clone()];
+        args.extend(self.codegen.iter().cloned());
+        args.extend(flags.iter().cloned());
+        build_and_run(&self.driver, &args, source, &dir.path().join("seed"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Checks a seed with a rustc that builds binaries running `binary`,
+    /// or `fat` under fat LTO.
+    fn check(name: &str, binary: &str, fat: &str) -> LtoOutcome {
+        let dir =
+            std::env::temp_dir().join(format!("rustc-fuzz-lto-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            format!(
+                "#!/bin/sh\ncat >/dev/null\nrun='{binary}'\nfor arg; do\n  \
+                 [ \"$prev\" = -o ] && out=$arg\n  prev=$arg\n  \
+                 [ \"$arg\" = -Clto=fat ] && run='{fat}'\n  \
+                 [ \"$arg\" = -Cpanic=abort ] && abort=1\ndone\n\
+                 [ -n \"$abort\" ] && run=\"$run; kill -ABRT \\$\\$\"\n\
+                 printf '#!/bin/sh\\n%s\\nexit 101\\n' \"$run\" > \"$out\"\nchmod +x \"$out\"\n"
+            ),
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let matrix = LtoMatrix {
+            driver: Rustc::new(&rustc),
+            samples: 16,
+            ..LtoMatrix::default()
+        };
+        let outcome = matrix
+            .check("fn main() { panic!() }", &mut StdRng::seed_from_u64(0))
+            .expect("builds and runs");
+        fs::remove_dir_all(&dir).expect("cleans up");
+        outcome
+    }
+
+    #[test]
+    fn aborting_panics_agree_with_unwinding_ones() {
+        assert_eq!(check("panics", "echo 1", "echo 1"), LtoOutcome::Agree);
+    }
+
+    #[test]
+    fn fat_lto_miscompiles_are_caught() {
+        let outcome = check("fat", "echo 1", "echo 2");
+        let LtoOutcome::Diverged {
+            flags,
+            baseline,
+            behavior,
+        } = &outcome
+        else {
+            panic!("{outcome:?}");
+        };
+        assert!(flags.iter().any(|flag| flag == "-Clto=fat"), "{flags:?}");
+        assert_eq!((&*baseline.stdout, &*behavior.stdout), ("1\n", "2\n"));
+        assert_eq!(outcome.severity(), Some(Severity::WrongCode));
+    }
+}
@@ -0,0 +1,286 @@
+//! Symbol mangling differential.
+//!
+//! Compiles each seed to an object file under both symbol mangling
+//! schemes, lists the symbols it defines with `nm`, and checks that every
+//! Rust symbol demangles with `rustc-demangle`. The v0 mangler encodes
+//! whole types, const generic values and closure paths, and exotic
+//! generics routinely produce symbols it cannot encode or a demangler
+//! cannot read back. A seed both schemes do not treat alike is a finding
+//! too: the scheme must not change whether a crate compiles.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use super::behavior::build;
+use super::rustc::Scratch;
+use super::{ExecOutcome, Rustc};
+use crate::triage::Severity;
+
+/// What rustc-demangle prints in place of parts of a v0 symbol it could
+/// not decode, instead of failing.
+const DEMANGLE_ERRORS: &[&str] = &[
+    "{invalid syntax}",
+    "{recursion limit reached}",
+    "{size limit reached}",
+];
+
+/// A `-Csymbol-mangling-version`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Scheme {
+    Legacy,
+    V0,
+}
+
+impl Scheme {
+    pub const ALL: [Scheme; 2] = [Scheme::Legacy, Scheme::V0];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Scheme::Legacy => "legacy",
+            Scheme::V0 => "v0",
+        }
+    }
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Compiles seeds under both mangling schemes and demangles the result.
+#[derive(Debug, Clone)]
+pub struct ManglingDiff {
+    /// Compiles the seeds and runs `nm`. Its phase is ignored; symbols are
+    /// only mangled during codegen.
+    pub driver: Rustc,
+    pub nm: PathBuf,
+    pub edition: String,
+}
+
+impl Default for ManglingDiff {
+    fn default() -> Self {
+        ManglingDiff {
+            driver: Rustc::default(),
+            nm: PathBuf::from("nm"),
+            edition: "2021".to_owned(),
+        }
+    }
+}
+
+/// What compiling one seed under both schemes led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ManglingOutcome {
+    /// Both schemes compiled and every symbol demangled.
+    Valid,
+    /// Neither scheme compiles the seed.
+    Rejected,
+    /// rustc ICEd or crashed under `scheme`, or rejected the seed although
+    /// the other scheme compiled it.
+    Failed {
+        scheme: Scheme,
+        outcome: ExecOutcome,
+    },
+    /// A symbol from the object built under `scheme` does not demangle.
+    Undemangled { scheme: Scheme, symbol: String },
+    /// `nm` could not read the object.
+    NmFailed {
+        scheme: Scheme,
+        outcome: ExecOutcome,
+    },
+}
+
+impl ManglingOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            ManglingOutcome::Failed { outcome, .. } | ManglingOutcome::NmFailed { outcome, .. } => {
+                Some(Severity::of_failure(outcome))
+            }
+            ManglingOutcome::Undemangled { .. } => Some(Severity::Diagnostic),
+            ManglingOutcome::Valid | ManglingOutcome::Rejected => None,
+        }
+    }
+}
+
+impl fmt::Display for ManglingOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ManglingOutcome::Valid => f.write_str("symbols demangle"),
+            ManglingOutcome::Rejected => f.write_str("rejected"),
+            ManglingOutcome::Failed { scheme, outcome } => {
+                write!(f, "{outcome} with {scheme} mangling")
+            }
+            ManglingOutcome::Undemangled { scheme, symbol } => {
+                write!(f, "{scheme} symbol does not demangle: {symbol}")
+            }
+            ManglingOutcome::NmFailed { scheme, outcome } => {
+                write!(f, "nm failed on the {scheme} object: {outcome}")
+            }
+        }
+    }
+}
+
+impl ManglingDiff {
+    /// Fails with an I/O error when `nm` is missing.
+    pub fn check(&self, source: &str) -> io::Result<ManglingOutcome> {
+        let dir = Scratch::new(&self.driver.scratch_root)?;
+        let mut objects = Vec::new();
+        let mut rejected = Vec::new();
+        for scheme in Scheme::ALL {
+            let object = dir.path().join(format!("{scheme}.o"));
+            let args = [
+                "--crate-type=lib",
+                "--emit=obj",
+                "--edition",
+                &self.edition,
+                "-Zunstable-options",
+                &format!("-Csymbol-mangling-version={scheme}"),
+            ]
+            .map(str::to_owned);
+            match build(&self.driver, &args, source, &object)? {
+                ExecOutcome::Success(_) => objects.push((scheme, object)),
+                outcome @ ExecOutcome::Error { .. } => rejected.push((scheme, outcome)),
+                outcome => return Ok(ManglingOutcome::Failed { scheme, outcome }),
+            }
+        }
+        if objects.is_empty() {
+            return Ok(ManglingOutcome::Rejected);
+        }
+        if let Some((scheme, outcome)) = rejected.pop() {
+            return Ok(ManglingOutcome::Failed { scheme, outcome });
+        }
+
+        for (scheme, object) in objects {
+            let listed = self.driver.run_program(
+                &self.nm,
+                [
+                    "--defined-only",
+                    "--format=posix",
+                    &object.display().to_string(),
+                ],
+                &[],
+            )?;
+            let ExecOutcome::Success(output) = listed else {
+                return Ok(ManglingOutcome::NmFailed {
+                    scheme,
+                    outcome: listed,
+                });
+            };
+            let undemangled = output
+                .stdout
+                .lines()
+                .filter_map(|line| line.split_whitespace().next())
+                .find(|symbol| !demangles(symbol));
+            if let Some(symbol) = undemangled {
+                return Ok(ManglingOutcome::Undemangled {
+                    scheme,
+                    symbol: symbol.to_owned(),
+                });
+            }
+        }
+        Ok(ManglingOutcome::Valid)
+    }
+}
+
+/// Whether `symbol` demangles cleanly, or is not a Rust symbol at all.
+fn demangles(symbol: &str) -> bool {
+    if !(symbol.starts_with("_R") || symbol.starts_with("_ZN")) {
+        return true;
+    }
+    match rustc_demangle::try_demangle(symbol) {
+        Ok(demangled) => {
+            let demangled = demangled.to_string();
+            !DEMANGLE_ERRORS
+                .iter()
+                .any(|error| demangled.contains(error))
+        }
+        Err(_) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn only_rust_symbols_must_demangle() {
+        assert!(demangles("_ZN4core3fmt9Formatter3pad17h0123456789abcdefE"));
+        assert!(demangles("_RNvC6_123foo3bar"));
+        assert!(demangles("main"));
+        assert!(!demangles("_RNvC"));
+        assert!(!demangles("_ZN3foo"));
+    }
+
+    /// Checks a seed with a rustc that rejects v0 builds if `v0_rejects`,
+    /// and an `nm` that lists `symbols`.
+    fn check(name: &str, v0_rejects: bool, symbols: &str) -> ManglingOutcome {
+        let dir =
+            std::env::temp_dir().join(format!("rustc-fuzz-mangling-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        let reject = if v0_rejects {
+            "case \"$*\" in *=v0*) echo 'error: cannot mangle' >&2; exit 1 ;; esac\n"
+        } else {
+            ""
+        };
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            format!(
+                "#!/bin/sh\ncat >/dev/null\n{reject}\
+                 for arg; do [ \"$prev\" = -o ] && touch \"$arg\"; prev=$arg; done\n"
+            ),
+        )
+        .expect("writes the rustc");
+        let nm = dir.join("nm");
+        fs::write(&nm, format!("#!/bin/sh\nprintf '{symbols}'\n")).expect("writes the nm");
+        for program in [&rustc, &nm] {
+            fs::set_permissions(program, fs::Permissions::from_mode(0o755))
+                .expect("makes it runnable");
+        }
+        let diff = ManglingDiff {
+            driver: Rustc::new(&rustc),
+            nm,
+            ..ManglingDiff::default()
+        };
+        let outcome = diff.check("pub fn f() {}").expect("compiles");
+        fs::remove_dir_all(&dir).expect("cleans up");
+        outcome
+    }
+
+    #[test]
+    fn symbols_are_demangled_from_both_objects() {
+        let valid = "_RNvC6_123foo3bar T 0 0\\nmain T 10 4\\n";
+        assert_eq!(check("valid", false, valid), ManglingOutcome::Valid);
+        let broken = "main T 10 4\\n_RNvC T 0 0\\n";
+        assert_eq!(
+            check("broken", false, broken),
+            ManglingOutcome::Undemangled {
+                scheme: Scheme::Legacy,
+                symbol: "_RNvC".to_owned(),
+            }
+        );
+    }
+
+    #[test]
+    fn schemes_must_agree_on_acceptance() {
+        let outcome = check("rejects", true, "");
+        assert!(
+            matches!(
+                outcome,
+                ManglingOutcome::Failed {
+                    scheme: Scheme::V0,
+                    outcome: ExecOutcome::Error { .. },
+                }
+            ),
+            "{outcome:?}"
+        );
+    }
+}
@@ -0,0 +1,205 @@
+//! MIR optimization level differential.
+//!
+//! Builds an executable seed once per `-Zmir-opt-level`, with every other
+//! flag the same, runs each binary and compares what they did. MIR
+//! optimizations must not change observable behavior, so the first level
+//! whose run differs from the lowest one names the pass set that miscompiled.
+//! Seeds need deterministic output; [`ProgramGen`](crate::gen::ProgramGen)
+//! writes such programs.
+
+use std::fmt;
+use std::io;
+use std::ops::RangeInclusive;
+
+use super::behavior::{build_and_run, Behavior};
+use super::rustc::Scratch;
+use super::{ExecOutcome, Rustc};
+use crate::triage::Severity;
+
+/// Compiles and runs seeds at each MIR optimization level.
+#[derive(Debug, Clone)]
+pub struct MirOptDiff {
+    /// Compiles the seeds and runs the binaries. Its phase is ignored;
+    /// every build goes through codegen.
+    pub driver: Rustc,
+    pub levels: RangeInclusive<u8>,
+    /// Codegen flags shared by every build. LLVM optimizations are off by
+    /// default so a difference comes from MIR.
+    pub codegen: Vec<String>,
+    pub edition: String,
+}
+
+impl Default for MirOptDiff {
+    fn default() -> Self {
+        MirOptDiff {
+            driver: Rustc::default(),
+            levels: 0..=4,
+            codegen: vec!["-Copt-level=0".to_owned()],
+            edition: "2021".to_owned(),
+        }
+    }
+}
+
+/// What compiling and running one seed at every level led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptOutcome {
+    /// Every level behaved the same.
+    Agree,
+    /// The seed does not compile at the lowest level.
+    Rejected,
+    /// A run timed out, so there is nothing reliable to compare.
+    Timeout { level: u8 },
+    /// rustc failed at `level` where the lowest level compiled, or
+    /// crashed outright.
+    CompileFailed { level: u8, outcome: ExecOutcome },
+    /// The binary built at `level`, the first to differ, behaved unlike
+    /// the one built at the lowest level.
+    Diverged {
+        level: u8,
+        baseline: Behavior,
+        behavior: Behavior,
+    },
+}
+
+impl OptOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            OptOutcome::CompileFailed { outcome, .. } => Some(Severity::of_failure(outcome)),
+            OptOutcome::Diverged { .. } => Some(Severity::WrongCode),
+            _ => None,
+        }
+    }
+
+    /// Groups findings by the first opt level that differed.
+    pub fn bucket(&self) -> Option<String> {
+        match self {
+            OptOutcome::CompileFailed { level, outcome } => {
+                Some(format!("mir-opt-level={level}/{}", outcome.kind()))
+            }
+            OptOutcome::Diverged { level, .. } => Some(format!("mir-opt-level={level}/output")),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for OptOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptOutcome::Agree => f.write_str("all levels agree"),
+            OptOutcome::Rejected => f.write_str("rejected"),
+            OptOutcome::Timeout { level } => write!(f, "timeout at -Zmir-opt-level={level}"),
+            OptOutcome::CompileFailed { level, outcome } => {
+                write!(f, "-Zmir-opt-level={level}: {outcome}")
+            }
+            OptOutcome::Diverged {
+                level,
+                baseline,
+                behavior,
+            } if baseline.exit != behavior.exit => write!(
+                f,
+                "behavior diverges at -Zmir-opt-level={level}: {} -> {}",
+                baseline.exit, behavior.exit
+            ),
+            OptOutcome::Diverged { level, .. } => {
+                write!(f, "output diverges at -Zmir-opt-level={level}")
+            }
+        }
+    }
+}
+
+impl MirOptDiff {
+    pub fn check(&self, source: &str) -> io::Result<OptOutcome> {
+        let dir = Scratch::new(&self.driver.scratch_root)?;
+        let mut baseline = None;
+        for level in self.levels.clone() {
+            let binary = dir.path().join(format!("opt{level}"));
+            let mut args = vec![
+                "--edition".to_owned(),
+                self.edition.clone(),
+                format!("-Zmir-opt-level={level}"),
+            ];
+            args.extend(self.codegen.iter().cloned());
+            let run = match build_and_run(&self.driver, &args, source, &binary)? {
+                Ok(run) => run,
+                Err(ExecOutcome::Error { .. }) if baseline.is_none() => {
+                    return Ok(OptOutcome::Rejected)
+                }
+                Err(outcome) => return Ok(OptOutcome::CompileFailed { level, outcome }),
+            };
+            if let ExecOutcome::Timeout(_) = run {
+                return Ok(OptOutcome::Timeout { level });
+            }
+            let behavior = Behavior::of(&run);
+            match &baseline {
+                None => baseline = Some(behavior),
+                Some(baseline) if *baseline != behavior => {
+                    return Ok(OptOutcome::Diverged {
+                        level,
+                        baseline: baseline.clone(),
+                        behavior,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(OptOutcome::Agree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn the_first_level_that_differs_is_blamed() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-mir-opt-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        // Builds binaries that print 2 from -Zmir-opt-level=3 up and 1
+        // below.
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            "#!/bin/sh\nwhile [ $# -gt 0 ]; do\n  case \"$1\" in\n    \
+             -o) out=$2; shift ;;\n    -Zmir-opt-level=*) level=${1#-Zmir-opt-level=} ;;\n  \
+             esac\n  shift\ndone\ncat >/dev/null\n\
+             if [ \"$level\" -ge 3 ]; then n=2; else n=1; fi\n\
+             printf '#!/bin/sh\\necho %s\\n' $n > \"$out\"\nchmod +x \"$out\"\n",
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let diff = MirOptDiff {
+            driver: Rustc::new(&rustc),
+            ..MirOptDiff::default()
+        };
+        let outcome = diff.check("fn main() {}").expect("builds and runs");
+        let OptOutcome::Diverged {
+            level,
+            baseline,
+            behavior,
+        } = &outcome
+        else {
+            panic!("{outcome:?}");
+        };
+        assert_eq!(*level, 3);
+        assert_eq!((&*baseline.stdout, &*behavior.stdout), ("1\n", "2\n"));
+        assert_eq!(outcome.bucket().as_deref(), Some("mir-opt-level=3/output"));
+        assert_eq!(outcome.to_string(), "output diverges at -Zmir-opt-level=3");
+
+        let agreeing = MirOptDiff {
+            levels: 0..=2,
+            ..diff
+        };
+        assert_eq!(
+            agreeing.check("fn main() {}").expect("builds and runs"),
+            OptOutcome::Agree
+        );
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,295 @@
+//! Miri harness: an undefined-behavior oracle.
+//!
+//! Interprets each seed under Miri. The generators only emit safe code, so
+//! when Miri reports undefined behavior in a seed without `unsafe`, either
+//! the standard library or the compiler let safe code do something it must
+//! not: a soundness bug. UB in seeds that do use `unsafe` is reported but
+//! is not a finding. Miri's JSON diagnostics are parsed into a [`UbReport`]
+//! whose [`UbReport::category`] buckets reports of the same kind.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use super::{ExecOutcome, Rustc};
+use crate::lex::{self, TokenKind};
+use crate::triage::Severity;
+
+/// Runs Miri on seeds under the limits of a [`Rustc`] driver.
+#[derive(Debug, Clone)]
+pub struct Miri {
+    /// The `miri` driver binary, as found with `rustup which miri`.
+    pub path: PathBuf,
+    /// Sysroot built for Miri (`cargo miri setup --print-sysroot`).
+    /// Defaults to `MIRI_SYSROOT`.
+    pub sysroot: Option<PathBuf>,
+    pub edition: String,
+    /// Extra `-Zmiri-*` flags.
+    pub flags: Vec<String>,
+    /// Supplies timeout, memory and output limits. Interpretation is slow,
+    /// so the default timeout is longer than rustc's.
+    pub driver: Rustc,
+}
+
+impl Default for Miri {
+    fn default() -> Self {
+        Miri {
+            path: PathBuf::from("miri"),
+            sysroot: std::env::var_os("MIRI_SYSROOT").map(PathBuf::from),
+            edition: "2021".to_owned(),
+            // Safe code may leak (`mem::forget`, `Box::leak`); that is not
+            // what this oracle is after.
+            flags: vec!["-Zmiri-ignore-leaks".to_owned()],
+            driver: Rustc {
+                timeout: Duration::from_secs(60),
+                ..Rustc::default()
+            },
+        }
+    }
+}
+
+/// One undefined-behavior error reported by Miri.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UbReport {
+    /// The message without its `Undefined Behavior: ` prefix.
+    pub message: String,
+    /// `line:column` in the seed where the UB occurred, if Miri pointed
+    /// into it.
+    pub location: Option<(usize, usize)>,
+}
+
+impl UbReport {
+    /// The kind of UB, without addresses, tags and sizes: the message up
+    /// to its first colon or comma, such as `memory access failed`.
+    pub fn category(&self) -> &str {
+        self.message
+            .split([':', ','])
+            .next()
+            .unwrap_or_default()
+            .trim()
+    }
+}
+
+impl fmt::Display for UbReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.location {
+            Some((line, column)) => write!(f, "{} at {line}:{column}", self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+/// What interpreting one seed led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MiriOutcome {
+    /// The program ran to completion, or panicked as ordinary Rust may.
+    Clean,
+    /// The seed does not compile, or has no `main`.
+    Rejected,
+    /// The program does something Miri cannot interpret, such as FFI.
+    Unsupported(String),
+    /// Interpretation did not finish in time; generated programs may
+    /// legitimately loop.
+    Timeout,
+    /// Miri found undefined behavior. `safe` says the seed has no
+    /// `unsafe` anywhere.
+    Ub { report: UbReport, safe: bool },
+    /// Miri itself panicked, crashed or ran out of memory.
+    Failed(ExecOutcome),
+}
+
+impl MiriOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            MiriOutcome::Ub { safe: true, .. } => Some(Severity::Unsound),
+            MiriOutcome::Failed(outcome) => Some(Severity::of_failure(outcome)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MiriOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MiriOutcome::Clean => f.write_str("clean"),
+            MiriOutcome::Rejected => f.write_str("rejected"),
+            MiriOutcome::Unsupported(message) => write!(f, "unsupported: {message}"),
+            MiriOutcome::Timeout => f.write_str("timeout"),
+            MiriOutcome::Ub { report, safe: true } => {
+                write!(f, "undefined behavior in safe code: {report}")
+            }
+            MiriOutcome::Ub {
+                report,
+                safe: false,
+            } => write!(f, "undefined behavior: {report}"),
+            MiriOutcome::Failed(outcome) => write!(f, "miri failed: {outcome}"),
+        }
+    }
+}
+
+impl Miri {
+    pub fn check(&self, source: &str) -> io::Result<MiriOutcome> {
+        let mut args = vec!["--edition".to_owned(), self.edition.clone()];
+        if let Some(sysroot) = &self.sysroot {
+            args.push("--sysroot".to_owned());
+            args.push(sysroot.display().to_string());
+        }
+        args.push("--error-format=json".to_owned());
+        args.extend(self.flags.iter().cloned());
+        args.push("-".to_owned());
+        let outcome = self
+            .driver
+            .run_program(&self.path, args, source.as_bytes())?;
+        Ok(classify(source, outcome))
+    }
+}
+
+/// Sorts a Miri execution into an outcome. Miri exits with 1 both for
+/// compile errors and for UB, and with 101 both when it panics and when
+/// the interpreted program does, so the diagnostics decide.
+pub fn classify(source: &str, outcome: ExecOutcome) -> MiriOutcome {
+    let stderr = match &outcome {
+        ExecOutcome::Success(_) => return MiriOutcome::Clean,
+        ExecOutcome::Timeout(_) => return MiriOutcome::Timeout,
+        ExecOutcome::Error { output, .. } | ExecOutcome::Ice(output) => &output.stderr,
+        ExecOutcome::Crash { .. } | ExecOutcome::OutOfMemory(_) => {
+            return MiriOutcome::Failed(outcome)
+        }
+    };
+    // The interpreter runs on a thread named `rustc`, the program's main
+    // thread is `main`.
+    if stderr.contains("thread 'rustc' panicked") || stderr.contains("internal compiler error") {
+        return MiriOutcome::Failed(outcome);
+    }
+    for diagnostic in stderr
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Diagnostic>(line).ok())
+        .filter(|diagnostic| diagnostic.level == "error")
+    {
+        if let Some(message) = diagnostic.message.strip_prefix("Undefined Behavior: ") {
+            let location = diagnostic
+                .spans
+                .iter()
+                .find(|span| span.is_primary && span.file_name == "<anon>")
+                .map(|span| (span.line_start, span.column_start));
+            let report = UbReport {
+                message: message.to_owned(),
+                location,
+            };
+            let safe = !uses_unsafe(source);
+            return MiriOutcome::Ub { report, safe };
+        }
+        if let Some(message) = diagnostic.message.strip_prefix("unsupported operation: ") {
+            return MiriOutcome::Unsupported(message.to_owned());
+        }
+    }
+    match outcome {
+        // The program's own panic; everything else was a compile error.
+        ExecOutcome::Ice(_) => MiriOutcome::Clean,
+        _ => MiriOutcome::Rejected,
+    }
+}
+
+/// Whether `source` contains the `unsafe` keyword outside comments and
+/// literals.
+fn uses_unsafe(source: &str) -> bool {
+    lex::tokenize(source)
+        .iter()
+        .any(|token| token.kind == TokenKind::Ident && token.text(source) == "unsafe")
+}
+
+#[derive(Deserialize)]
+struct Diagnostic {
+    message: String,
+    level: String,
+    #[serde(default)]
+    spans: Vec<Span>,
+}
+
+#[derive(Deserialize)]
+struct Span {
+    file_name: String,
+    line_start: usize,
+    column_start: usize,
+    is_primary: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::Captured;
+    use serde_json::json;
+
+    fn stderr(stderr: String) -> Captured {
+        Captured {
+            stderr,
+            ..Captured::default()
+        }
+    }
+
+    fn error(message: &str) -> ExecOutcome {
+        let diagnostic = json!({
+            "message": message,
+            "level": "error",
+            "spans": [{
+                "file_name": "<anon>",
+                "line_start": 3,
+                "column_start": 5,
+                "is_primary": true,
+            }],
+        });
+        ExecOutcome::Error {
+            code: 1,
+            output: stderr(diagnostic.to_string()),
+        }
+    }
+
+    #[test]
+    fn ub_is_a_finding_only_in_safe_code() {
+        let ub = "Undefined Behavior: memory access failed: alloc1 has been freed";
+        let MiriOutcome::Ub { report, safe: true } = classify("fn main() {}", error(ub)) else {
+            panic!("safe code");
+        };
+        assert_eq!(report.category(), "memory access failed");
+        assert_eq!(report.location, Some((3, 5)));
+        // `unsafe` in a comment does not count.
+        let commented = "// unsafe\nfn main() {}";
+        assert!(classify(commented, error(ub)).is_finding());
+        let unsafe_code = "fn main() { unsafe {} }";
+        let outcome = classify(unsafe_code, error(ub));
+        assert!(matches!(outcome, MiriOutcome::Ub { safe: false, .. }));
+        assert!(!outcome.is_finding());
+    }
+
+    #[test]
+    fn diagnostics_decide_what_exit_codes_mean() {
+        let source = "fn main() {}";
+        assert_eq!(
+            classify(
+                source,
+                error("unsupported operation: can't call foreign function")
+            ),
+            MiriOutcome::Unsupported("can't call foreign function".to_owned())
+        );
+        assert_eq!(
+            classify(source, error("mismatched types")),
+            MiriOutcome::Rejected
+        );
+        let program = "thread 'main' panicked at <anon>:1:1:\nexplicit panic".to_owned();
+        assert_eq!(
+            classify(source, ExecOutcome::Ice(stderr(program))),
+            MiriOutcome::Clean
+        );
+        let miri = "thread 'rustc' panicked at src/machine.rs:1:1:".to_owned();
+        let failed = classify(source, ExecOutcome::Ice(stderr(miri)));
+        assert_eq!(failed.severity(), Some(Severity::Ice));
+    }
+}
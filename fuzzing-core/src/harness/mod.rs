@@ -0,0 +1,73 @@
+//! Running the compiler under test.
+//!
+//! Every rustc execution goes through [`Rustc`], which stops compilation
+//! after a chosen [`Phase`], bounds wall-clock time, memory and captured
+//! output, and classifies the result as an [`ExecOutcome`]. Triage,
+//! scheduling and the pipelines consume that type rather than raw process
+//! output, so "what counts as an ICE" is decided in one place.
+//!
+//! The other tools in the toolchain have harnesses alongside: [`Clippy`],
+//! [`Miri`], [`Rustdoc`] and [`Rustfmt`] run under the same limits, and
+//! [`RustAnalyzer`] keeps a language server up across inputs.
+
+pub mod backend;
+pub mod behavior;
+pub mod clippy;
+pub mod coherence;
+pub mod const_eval;
+pub mod container;
+pub mod debuginfo;
+pub mod gccrs;
+pub mod incremental;
+pub mod layout;
+pub mod lto;
+pub mod mangling;
+pub mod mir_opt;
+pub mod miri;
+pub mod mrustc;
+pub mod opt_level;
+pub mod phase;
+pub mod pool;
+pub mod rust_analyzer;
+pub mod rustc;
+pub mod rustdoc;
+pub mod rustfmt;
+pub mod sandbox;
+pub mod soundness;
+pub mod subtyping;
+pub mod syn_parse;
+pub mod targets;
+pub mod threads;
+pub mod unpretty;
+pub mod wasm;
+
+pub use backend::{Backend, BackendDiff, BackendOutcome};
+pub use behavior::Behavior;
+pub use clippy::{Clippy, ClippyOutcome};
+pub use coherence::{Coherence, CoherenceOutcome, Krate};
+pub use const_eval::{ConstEval, ConstOutcome};
+pub use container::Container;
+pub use debuginfo::{DebugInfo, DebugOutcome};
+pub use gccrs::{Frontend, Gccrs, GccrsOutcome};
+pub use incremental::{Build, Incremental, IncrementalOutcome};
+pub use layout::{LayoutOutcome, LayoutRandomization};
+pub use lto::{LtoMatrix, LtoOutcome};
+pub use mangling::{ManglingDiff, ManglingOutcome, Scheme};
+pub use mir_opt::{MirOptDiff, OptOutcome};
+pub use miri::{Miri, MiriOutcome, UbReport};
+pub use mrustc::{Mrustc, MrustcOutcome};
+pub use opt_level::{OptLevelDiff, OptLevelOutcome};
+pub use phase::{Phase, UnknownPhase};
+pub use pool::{Pool, PoolOptions};
+pub use rust_analyzer::{AnalyzerOutcome, RustAnalyzer};
+pub use rustc::{Captured, ExecOutcome, MemoryLimit, Rustc};
+pub use rustdoc::{DocFormat, DocOutcome, Rustdoc};
+pub use rustfmt::{FormatOutcome, Rustfmt};
+pub use sandbox::Sandbox;
+pub use soundness::{Soundness, SoundnessOutcome};
+pub use subtyping::{Subtyping, SubtypingOutcome};
+pub use syn_parse::{Parser, ParserDiff, ParserOutcome};
+pub use targets::{Target, TargetMatrix, TargetOutcome};
+pub use threads::{ParallelStress, ThreadsOutcome};
+pub use unpretty::{RoundTrip, RoundTripOutcome, Unpretty};
+pub use wasm::{Platform, WasmDiff, WasmOutcome};
@@ -0,0 +1,240 @@
+//! mrustc acceptance differential.
+//!
+//! mrustc is a Rust compiler written in C++ that implements the language
+//! of rustc 1.54. This harness asks it and rustc whether each seed is a
+//! valid program and reports disagreements. Seeds should stay within that
+//! language; `rustc-fuzz generate --level 1.54` writes such seeds.
+//!
+//! mrustc does no borrow checking, so seeds rustc rejects only for
+//! borrow-check errors are set aside, as are features mrustc reports as
+//! not implemented.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use super::rustc::Scratch;
+use super::{ExecOutcome, Phase, Rustc};
+use crate::gen::Level;
+use crate::oracle::stability::error_codes;
+use crate::triage::Severity;
+
+/// Borrow-check and move errors, which mrustc does not detect.
+const BORROWCK_ERRORS: &[&str] = &[
+    "E0373", "E0381", "E0382", "E0384", "E0499", "E0502", "E0503", "E0505", "E0506", "E0507",
+    "E0515", "E0596", "E0597", "E0716",
+];
+
+/// Runs mrustc and rustc on seeds.
+#[derive(Debug, Clone)]
+pub struct Mrustc {
+    pub path: PathBuf,
+    /// Directory holding mrustc's own build of the standard library
+    /// (`-L`), such as `output/` in an mrustc checkout.
+    pub libdir: Option<PathBuf>,
+    /// The reference compiler. It stops after analysis by default, which
+    /// is as far as mrustc's checks go.
+    pub rustc: Rustc,
+}
+
+impl Default for Mrustc {
+    fn default() -> Self {
+        Mrustc {
+            path: PathBuf::from("mrustc"),
+            libdir: None,
+            rustc: Rustc {
+                phase: Phase::Analysis,
+                ..Rustc::default()
+            },
+        }
+    }
+}
+
+/// What checking one seed with both compilers led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MrustcOutcome {
+    /// Both accepted, or both rejected.
+    Agree,
+    /// rustc rejected the seed only for errors mrustc does not check.
+    Unchecked,
+    /// mrustc hit a `TODO` for something it does not implement.
+    Unimplemented(String),
+    /// Only rustc accepted the seed; `errors` is what mrustc said.
+    OnlyRustcAccepts { errors: Vec<String> },
+    /// Only mrustc accepted the seed; `errors` are rustc's error codes.
+    OnlyMrustcAccepts { errors: Vec<String> },
+    /// mrustc hit an internal `BUG`, crashed, hung or ran out of memory.
+    Failed(ExecOutcome),
+    /// rustc did not get as far as a verdict.
+    RustcFailed(ExecOutcome),
+}
+
+impl MrustcOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            MrustcOutcome::OnlyRustcAccepts { .. } | MrustcOutcome::OnlyMrustcAccepts { .. } => {
+                Some(Severity::Diagnostic)
+            }
+            MrustcOutcome::Failed(outcome) | MrustcOutcome::RustcFailed(outcome) => {
+                Some(Severity::of_failure(outcome))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for MrustcOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MrustcOutcome::Agree => f.write_str("compilers agree"),
+            MrustcOutcome::Unchecked => f.write_str("rejected by the borrow checker only"),
+            MrustcOutcome::Unimplemented(message) => {
+                write!(f, "unimplemented in mrustc: {message}")
+            }
+            MrustcOutcome::OnlyRustcAccepts { errors } => {
+                write!(f, "only rustc accepts: {}", errors.join("; "))
+            }
+            MrustcOutcome::OnlyMrustcAccepts { errors } => {
+                write!(f, "only mrustc accepts: [{}]", errors.join(", "))
+            }
+            MrustcOutcome::Failed(outcome) => write!(f, "mrustc failed: {outcome}"),
+            MrustcOutcome::RustcFailed(outcome) => write!(f, "rustc failed: {outcome}"),
+        }
+    }
+}
+
+impl Mrustc {
+    pub fn check(&self, source: &str) -> io::Result<MrustcOutcome> {
+        let edition = Level::Rust1_54.edition();
+        let rustc = self.rustc.run(
+            ["--crate-type=lib", "--edition", edition, "-"],
+            source.as_bytes(),
+        )?;
+        if !matches!(rustc, ExecOutcome::Success(_) | ExecOutcome::Error { .. }) {
+            return Ok(MrustcOutcome::RustcFailed(rustc));
+        }
+
+        let dir = Scratch::new(&self.rustc.scratch_root)?;
+        let file = dir.path().join("seed.rs");
+        fs::write(&file, source)?;
+        let mut args = vec![
+            file.display().to_string(),
+            "--crate-type".to_owned(),
+            "rlib".to_owned(),
+            "--edition".to_owned(),
+            edition.to_owned(),
+            "-o".to_owned(),
+            dir.path().join("libseed.rlib").display().to_string(),
+        ];
+        if let Some(libdir) = &self.libdir {
+            args.push("-L".to_owned());
+            args.push(libdir.display().to_string());
+        }
+        let mrustc = self.rustc.run_program(&self.path, &args, &[])?;
+
+        let stderr = &mrustc.output().stderr;
+        if let Some(todo) = stderr.lines().find(|line| line.contains("TODO")) {
+            return Ok(MrustcOutcome::Unimplemented(todo.trim().to_owned()));
+        }
+        if !matches!(mrustc, ExecOutcome::Success(_) | ExecOutcome::Error { .. })
+            || stderr.contains("BUG")
+        {
+            return Ok(MrustcOutcome::Failed(mrustc));
+        }
+
+        Ok(match (rustc.is_success(), mrustc.is_success()) {
+            (true, false) => MrustcOutcome::OnlyRustcAccepts {
+                errors: stderr
+                    .lines()
+                    .filter(|line| line.contains("error"))
+                    .map(|line| line.trim().to_owned())
+                    .collect(),
+            },
+            (false, true) => {
+                let errors = error_codes(&rustc.output().stderr);
+                if errors
+                    .iter()
+                    .all(|code| BORROWCK_ERRORS.contains(&code.as_str()))
+                {
+                    MrustcOutcome::Unchecked
+                } else {
+                    MrustcOutcome::OnlyMrustcAccepts { errors }
+                }
+            }
+            _ => MrustcOutcome::Agree,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Checks a seed with a rustc that runs `rustc` and an mrustc that runs
+    /// `mrustc`.
+    fn check(name: &str, rustc: &str, mrustc: &str) -> MrustcOutcome {
+        let dir =
+            std::env::temp_dir().join(format!("rustc-fuzz-mrustc-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        let (rustc_path, path) = (dir.join("rustc"), dir.join("mrustc"));
+        for (program, script) in [(&rustc_path, rustc), (&path, mrustc)] {
+            fs::write(program, format!("#!/bin/sh\n{script}\n")).expect("writes the compiler");
+            fs::set_permissions(program, fs::Permissions::from_mode(0o755))
+                .expect("makes it runnable");
+        }
+        let harness = Mrustc {
+            path,
+            libdir: None,
+            rustc: Rustc::new(&rustc_path),
+        };
+        let outcome = harness.check("fn f() {}").expect("runs both");
+        fs::remove_dir_all(&dir).expect("cleans up");
+        outcome
+    }
+
+    #[test]
+    fn borrow_check_errors_are_not_held_against_mrustc() {
+        let borrowck = "echo 'error[E0382]: use of moved value' >&2; exit 1";
+        assert_eq!(
+            check("borrowck", borrowck, "exit 0"),
+            MrustcOutcome::Unchecked
+        );
+        let types = "echo 'error[E0308]: mismatched types' >&2; exit 1";
+        let outcome = check("types", types, "exit 0");
+        assert_eq!(
+            outcome,
+            MrustcOutcome::OnlyMrustcAccepts {
+                errors: vec!["E0308".to_owned()]
+            }
+        );
+        assert!(outcome.is_finding());
+    }
+
+    #[test]
+    fn mrustc_todos_and_bugs_are_told_apart() {
+        let todo = "echo 'seed.rs:1: TODO: async fn' >&2; exit 1";
+        assert_eq!(
+            check("todo", "exit 0", todo),
+            MrustcOutcome::Unimplemented("seed.rs:1: TODO: async fn".to_owned())
+        );
+        let bug = "echo 'seed.rs:1: BUG: type mismatch in lowering' >&2; exit 1";
+        assert!(matches!(
+            check("bug", "exit 0", bug),
+            MrustcOutcome::Failed(_)
+        ));
+        let rejected = "echo 'seed.rs:1: error: unknown name' >&2; exit 1";
+        assert_eq!(
+            check("rejects", "exit 0", rejected),
+            MrustcOutcome::OnlyRustcAccepts {
+                errors: vec!["seed.rs:1: error: unknown name".to_owned()]
+            }
+        );
+    }
+}
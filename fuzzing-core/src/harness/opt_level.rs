@@ -0,0 +1,226 @@
+//! Debug against release: the `-Copt-level` differential.
+//!
+//! Builds an executable seed unoptimized and at each higher
+//! `-Copt-level`, runs every binary and compares it with the unoptimized
+//! one. Optimizations must not change what a program with deterministic
+//! output prints, so a difference is a miscompilation, and since this
+//! reaches the whole LLVM pipeline it is the oracle most likely to find
+//! one. Overflow checks are set explicitly for every build, so debug and
+//! release do not differ in whether arithmetic panics.
+
+use std::fmt;
+use std::io;
+
+use super::behavior::{build_and_run, Behavior};
+use super::rustc::Scratch;
+use super::{ExecOutcome, Rustc};
+use crate::triage::Severity;
+
+/// Compiles and runs seeds at several optimization levels.
+#[derive(Debug, Clone)]
+pub struct OptLevelDiff {
+    /// Compiles the seeds and runs the binaries. Its phase is ignored;
+    /// every build goes through codegen.
+    pub driver: Rustc,
+    /// Levels to build at; every build is compared with the first.
+    pub levels: Vec<String>,
+    /// `-Coverflow-checks` for every build. On by default, which also
+    /// keeps the checks' own codegen under test.
+    pub overflow_checks: bool,
+    pub edition: String,
+}
+
+impl Default for OptLevelDiff {
+    fn default() -> Self {
+        OptLevelDiff {
+            driver: Rustc::default(),
+            levels: vec!["0".to_owned(), "3".to_owned()],
+            overflow_checks: true,
+            edition: "2021".to_owned(),
+        }
+    }
+}
+
+/// What compiling and running one seed at every level led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OptLevelOutcome {
+    /// Every level behaved the same.
+    Agree,
+    /// The seed does not compile at the first level.
+    Rejected,
+    /// A run timed out, so there is nothing reliable to compare.
+    Timeout { opt_level: String },
+    /// rustc failed at `opt_level` where the first level compiled, or
+    /// crashed outright.
+    CompileFailed {
+        opt_level: String,
+        outcome: ExecOutcome,
+    },
+    /// The binary built at `opt_level` behaved unlike the one built at the
+    /// first level.
+    Diverged {
+        opt_level: String,
+        baseline: Behavior,
+        behavior: Behavior,
+    },
+}
+
+impl OptLevelOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            OptLevelOutcome::CompileFailed { outcome, .. } => Some(Severity::of_failure(outcome)),
+            OptLevelOutcome::Diverged { .. } => Some(Severity::WrongCode),
+            _ => None,
+        }
+    }
+
+    /// Groups findings by level and by what went wrong; divergences are
+    /// potential miscompilations and are bucketed by how the exits
+    /// differ.
+    pub fn bucket(&self) -> Option<String> {
+        match self {
+            OptLevelOutcome::CompileFailed { opt_level, outcome } => {
+                Some(format!("opt-level={opt_level}/{}", outcome.kind()))
+            }
+            OptLevelOutcome::Diverged {
+                opt_level,
+                baseline,
+                behavior,
+            } if baseline.exit != behavior.exit => Some(format!(
+                "opt-level={opt_level}/{}->{}",
+                baseline.exit, behavior.exit
+            )),
+            OptLevelOutcome::Diverged { opt_level, .. } => {
+                Some(format!("opt-level={opt_level}/output"))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for OptLevelOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OptLevelOutcome::Agree => f.write_str("all levels agree"),
+            OptLevelOutcome::Rejected => f.write_str("rejected"),
+            OptLevelOutcome::Timeout { opt_level } => {
+                write!(f, "timeout at -Copt-level={opt_level}")
+            }
+            OptLevelOutcome::CompileFailed { opt_level, outcome } => {
+                write!(f, "-Copt-level={opt_level}: {outcome}")
+            }
+            OptLevelOutcome::Diverged {
+                opt_level,
+                baseline,
+                behavior,
+            } if baseline.exit != behavior.exit => write!(
+                f,
+                "possible miscompilation at -Copt-level={opt_level}: {} -> {}",
+                baseline.exit, behavior.exit
+            ),
+            OptLevelOutcome::Diverged { opt_level, .. } => write!(
+                f,
+                "possible miscompilation: output diverges at -Copt-level={opt_level}"
+            ),
+        }
+    }
+}
+
+impl OptLevelDiff {
+    pub fn check(&self, source: &str) -> io::Result<OptLevelOutcome> {
+        let dir = Scratch::new(&self.driver.scratch_root)?;
+        let overflow_checks = if self.overflow_checks { "on" } else { "off" };
+        let mut baseline = None;
+        for opt_level in &self.levels {
+            let binary = dir.path().join(format!("opt{opt_level}"));
+            let args = vec![
+                "--edition".to_owned(),
+                self.edition.clone(),
+                format!("-Copt-level={opt_level}"),
+                format!("-Coverflow-checks={overflow_checks}"),
+            ];
+            let run = match build_and_run(&self.driver, &args, source, &binary)? {
+                Ok(run) => run,
+                Err(ExecOutcome::Error { .. }) if baseline.is_none() => {
+                    return Ok(OptLevelOutcome::Rejected)
+                }
+                Err(outcome) => {
+                    return Ok(OptLevelOutcome::CompileFailed {
+                        opt_level: opt_level.clone(),
+                        outcome,
+                    })
+                }
+            };
+            if let ExecOutcome::Timeout(_) = run {
+                return Ok(OptLevelOutcome::Timeout {
+                    opt_level: opt_level.clone(),
+                });
+            }
+            let behavior = Behavior::of(&run);
+            match &baseline {
+                None => baseline = Some(behavior),
+                Some(baseline) if *baseline != behavior => {
+                    return Ok(OptLevelOutcome::Diverged {
+                        opt_level: opt_level.clone(),
+                        baseline: baseline.clone(),
+                        behavior,
+                    })
+                }
+                Some(_) => {}
+            }
+        }
+        Ok(OptLevelOutcome::Agree)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn optimized_builds_are_held_to_the_unoptimized_one() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-opt-level-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        // Optimized binaries exit with 1; release builds without overflow
+        // checks fail to compile.
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            "#!/bin/sh\ncat >/dev/null\ncase \"$*\" in *-Coverflow-checks=off*)\n  \
+             echo 'error: internal compiler error: unchecked' >&2; exit 101 ;;\nesac\n\
+             exit=0\nfor arg; do\n  [ \"$prev\" = -o ] && out=$arg\n  prev=$arg\n  \
+             [ \"$arg\" = -Copt-level=3 ] && exit=1\ndone\n\
+             printf '#!/bin/sh\\nexit %s\\n' $exit > \"$out\"\nchmod +x \"$out\"\n",
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let diff = OptLevelDiff {
+            driver: Rustc::new(&rustc),
+            ..OptLevelDiff::default()
+        };
+        let outcome = diff.check("fn main() {}").expect("builds and runs");
+        assert!(
+            matches!(&outcome, OptLevelOutcome::Diverged { opt_level, .. } if opt_level == "3"),
+            "{outcome:?}"
+        );
+        assert_eq!(
+            outcome.bucket().as_deref(),
+            Some("opt-level=3/success->error 1")
+        );
+
+        let unchecked = OptLevelDiff {
+            overflow_checks: false,
+            ..diff
+        };
+        let outcome = unchecked.check("fn main() {}").expect("builds");
+        assert_eq!(outcome.bucket().as_deref(), Some("opt-level=0/ice"));
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
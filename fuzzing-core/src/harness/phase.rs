@@ -0,0 +1,122 @@
+//! How far rustc gets before it stops.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// The last compilation phase an execution runs.
+///
+/// Earlier phases are much cheaper: parsing alone is 5-50x faster than a
+/// full build, so campaigns aimed at the frontend should not pay for LLVM.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Phase {
+    /// Parse the crate root only (`-Zparse-crate-root-only`).
+    Parse,
+    /// Type-check and write metadata, as `cargo check` does
+    /// (`--emit=metadata`).
+    Metadata,
+    /// All analysis, MIR building and borrow checking included, but no
+    /// code generation (`-Zno-codegen`).
+    Analysis,
+    /// A full build through LLVM.
+    #[default]
+    Codegen,
+}
+
+impl Phase {
+    pub const ALL: [Phase; 4] = [
+        Phase::Parse,
+        Phase::Metadata,
+        Phase::Analysis,
+        Phase::Codegen,
+    ];
+
+    /// Flags that stop rustc after this phase.
+    pub fn args(self) -> &'static [&'static str] {
+        match self {
+            Phase::Parse => &["-Zparse-crate-root-only"],
+            Phase::Metadata => &["--emit=metadata"],
+            Phase::Analysis => &["-Zno-codegen"],
+            Phase::Codegen => &[],
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Phase::Parse => "parse",
+            Phase::Metadata => "metadata",
+            Phase::Analysis => "analysis",
+            Phase::Codegen => "codegen",
+        }
+    }
+}
+
+impl fmt::Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownPhase(pub String);
+
+impl fmt::Display for UnknownPhase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown phase {:?}; expected parse, metadata, analysis or codegen",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownPhase {}
+
+impl FromStr for Phase {
+    type Err = UnknownPhase;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Phase::ALL
+            .into_iter()
+            .find(|p| p.name() == s)
+            .ok_or_else(|| UnknownPhase(s.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::Rustc;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn phases_parse_by_name() {
+        for phase in Phase::ALL {
+            assert_eq!(phase.to_string().parse(), Ok(phase));
+        }
+        let error = "link".parse::<Phase>().expect_err("not a phase");
+        assert_eq!(error, UnknownPhase("link".to_owned()));
+        assert!(error.to_string().contains("\"link\""));
+    }
+
+    #[test]
+    fn rustc_stops_after_its_phase() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-phase-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the test directory");
+        let rustc = dir.join("rustc");
+        fs::write(&rustc, "#!/bin/sh\necho \"$@\"\n").expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let driver = Rustc {
+            phase: Phase::Analysis,
+            ..Rustc::new(&rustc)
+        };
+        let outcome = driver.run(["--crate-type=lib", "-"], b"").expect("runs");
+        assert_eq!(outcome.output().stdout, "-Zno-codegen --crate-type=lib -\n");
+        assert_eq!(outcome.phase(), Some(Phase::Analysis));
+        // Other programs run to completion.
+        let other = driver.run_program(&rustc, ["-V"], b"").expect("runs");
+        assert_eq!(other.output().stdout, "-V\n");
+        assert_eq!(other.phase(), None);
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,366 @@
+//! Concurrent execution.
+//!
+//! Checking a seed is mostly waiting for one rustc process on one core. A
+//! [`Pool`] keeps several going at once on worker threads, each of which
+//! can be pinned to a CPU of its own; the compilers a worker starts inherit
+//! its pin, so they do not migrate between cores or crowd each other out.
+//! Jobs wait in a bounded queue and [`Pool::submit`] blocks while it is
+//! full, so whatever produces jobs (a generator, a mutator, a corpus walk)
+//! never runs more than the queue's length ahead of compilation. Combine
+//! it with [`Rustc::nice`](super::Rustc::nice) to keep the host usable.
+
+use std::fmt;
+use std::io;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::mpsc::{self, Receiver, Sender, SyncSender, TryRecvError, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+
+/// How many workers a pool has and where they run.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolOptions {
+    pub workers: usize,
+    /// Jobs that may wait for a worker before [`Pool::submit`] blocks.
+    pub queue: usize,
+    /// CPUs to pin workers to, worker `i` to `cpus[i % cpus.len()]`; empty
+    /// to leave them to the kernel.
+    pub cpus: Vec<usize>,
+}
+
+impl Default for PoolOptions {
+    /// A worker per available CPU, unpinned, with as many jobs queued.
+    fn default() -> Self {
+        let workers = thread::available_parallelism().map_or(1, |n| n.get());
+        PoolOptions {
+            workers,
+            queue: workers,
+            cpus: Vec::new(),
+        }
+    }
+}
+
+impl PoolOptions {
+    /// `workers` workers pinned one per CPU of `cpus`, if it names any.
+    pub fn new(workers: usize, cpus: Vec<usize>) -> Self {
+        PoolOptions {
+            workers,
+            queue: workers,
+            cpus,
+        }
+    }
+}
+
+type Outcome<O> = (usize, thread::Result<O>);
+
+/// Worker threads running jobs of type `I` to results of type `O`.
+pub struct Pool<I, O> {
+    jobs: Option<SyncSender<(usize, I)>>,
+    results: Receiver<Outcome<O>>,
+    workers: Vec<JoinHandle<()>>,
+    submitted: usize,
+    received: usize,
+}
+
+impl<I: Send + 'static, O: Send + 'static> Pool<I, O> {
+    /// Starts the workers of `options`, each running its jobs through the
+    /// function `worker` makes for it from its index. Fails if a worker
+    /// cannot be pinned.
+    pub fn new<W>(options: &PoolOptions, mut worker: impl FnMut(usize) -> W) -> io::Result<Self>
+    where
+        W: FnMut(I) -> O + Send + 'static,
+    {
+        let (jobs, queue) = mpsc::sync_channel::<(usize, I)>(options.queue);
+        let queue = Arc::new(Mutex::new(queue));
+        let (done, results) = mpsc::channel();
+        let (ready, started) = mpsc::channel();
+        let mut workers = Vec::new();
+        for index in 0..options.workers.max(1) {
+            let cpu = (!options.cpus.is_empty()).then(|| options.cpus[index % options.cpus.len()]);
+            let run = worker(index);
+            let queue = Arc::clone(&queue);
+            let done = done.clone();
+            let ready = ready.clone();
+            // Workers log inside whatever span started them.
+            let span = tracing::debug_span!("worker", index, cpu);
+            workers.push(thread::spawn(move || {
+                let _worker = span.entered();
+                let pinned = cpu.map_or(Ok(()), pin);
+                let failed = pinned.is_err();
+                let _ = ready.send(pinned);
+                drop(ready);
+                if !failed {
+                    work(&queue, &done, run);
+                }
+            }));
+        }
+        drop(ready);
+        let pool = Pool {
+            jobs: Some(jobs),
+            results,
+            workers,
+            submitted: 0,
+            received: 0,
+        };
+        for pinned in started {
+            // Dropping the pool stops the workers that did start.
+            pinned?;
+        }
+        Ok(pool)
+    }
+}
+
+impl<I, O> Pool<I, O> {
+    /// Queues `input`, waiting while the queue is full. Returns the job's
+    /// number, which its result comes back with.
+    pub fn submit(&mut self, input: I) -> usize {
+        let job = self.submitted;
+        self.sender()
+            .send((job, input))
+            .expect("workers outlive the pool");
+        self.submitted += 1;
+        job
+    }
+
+    /// Queues `input` if there is room, or hands it back.
+    pub fn try_submit(&mut self, input: I) -> Result<usize, I> {
+        let job = self.submitted;
+        match self.sender().try_send((job, input)) {
+            Ok(()) => {
+                self.submitted += 1;
+                Ok(job)
+            }
+            Err(TrySendError::Full((_, input))) => Err(input),
+            Err(TrySendError::Disconnected(_)) => panic!("workers outlive the pool"),
+        }
+    }
+
+    fn sender(&self) -> &SyncSender<(usize, I)> {
+        self.jobs.as_ref().expect("the queue is open until drop")
+    }
+
+    /// Jobs submitted whose results have not been received.
+    pub fn pending(&self) -> usize {
+        self.submitted - self.received
+    }
+
+    /// The next result to finish, with its job number, waiting for it if
+    /// need be; `None` if no job is pending. A job that panicked panics
+    /// here.
+    pub fn recv(&mut self) -> Option<(usize, O)> {
+        if self.pending() == 0 {
+            return None;
+        }
+        let outcome = self.results.recv().expect("workers outlive the pool");
+        Some(self.unwrap(outcome))
+    }
+
+    /// A result that has finished already, if there is one.
+    pub fn try_recv(&mut self) -> Option<(usize, O)> {
+        match self.results.try_recv() {
+            Ok(outcome) => Some(self.unwrap(outcome)),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => panic!("workers outlive the pool"),
+        }
+    }
+
+    fn unwrap(&mut self, (job, result): Outcome<O>) -> (usize, O) {
+        self.received += 1;
+        match result {
+            Ok(output) => (job, output),
+            Err(payload) => panic::resume_unwind(payload),
+        }
+    }
+}
+
+impl<I, O> Drop for Pool<I, O> {
+    /// Closes the queue and waits for the workers to finish the jobs
+    /// already in it.
+    fn drop(&mut self) {
+        self.jobs = None;
+        for worker in self.workers.drain(..) {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn work<I, O>(
+    queue: &Mutex<Receiver<(usize, I)>>,
+    done: &Sender<Outcome<O>>,
+    mut run: impl FnMut(I) -> O,
+) {
+    loop {
+        // The lock is held only while waiting, so one idle worker waits
+        // at a time and the rest queue up behind it.
+        let job = queue
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .recv();
+        let Ok((job, input)) = job else {
+            return;
+        };
+        let _seed = tracing::debug_span!("seed", job).entered();
+        let result = panic::catch_unwind(AssertUnwindSafe(|| run(input)));
+        if result.is_err() {
+            tracing::error!("worker panicked");
+        }
+        if done.send((job, result)).is_err() {
+            return;
+        }
+    }
+}
+
+/// Pins the calling thread, and the processes it starts from now on, to
+/// `cpu`.
+#[cfg(target_os = "linux")]
+fn pin(cpu: usize) -> io::Result<()> {
+    if cpu >= libc::CPU_SETSIZE as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("there is no CPU {cpu}"),
+        ));
+    }
+    // SAFETY: `set` is a valid, zeroed cpu_set_t and `cpu` is in range.
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        if libc::sched_setaffinity(0, std::mem::size_of_val(&set), &set) != 0 {
+            let e = io::Error::last_os_error();
+            return Err(io::Error::new(
+                e.kind(),
+                format!("pinning to CPU {cpu}: {e}"),
+            ));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn pin(_cpu: usize) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "pinning to CPUs is only supported on Linux",
+    ))
+}
+
+/// Parses a CPU list in `taskset` syntax, such as `0-3,8,10-11`.
+pub fn parse_cpus(list: &str) -> Result<Vec<usize>, String> {
+    let mut cpus = Vec::new();
+    for part in list.split(',').map(str::trim) {
+        let number = |text: &str| {
+            text.trim()
+                .parse::<usize>()
+                .map_err(|_| format!("`{part}` is not a CPU or range of CPUs"))
+        };
+        match part.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (number(first)?, number(last)?);
+                if first > last {
+                    return Err(format!("`{part}` is an empty range"));
+                }
+                cpus.extend(first..=last);
+            }
+            None => cpus.push(number(part)?),
+        }
+    }
+    Ok(cpus)
+}
+
+impl<I, O> fmt::Debug for Pool<I, O> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Pool")
+            .field("workers", &self.workers.len())
+            .field("submitted", &self.submitted)
+            .field("received", &self.received)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cpu_lists_parse_like_taskset() {
+        assert_eq!(parse_cpus("0-3,8, 10-11"), Ok(vec![0, 1, 2, 3, 8, 10, 11]));
+        assert_eq!(parse_cpus("5"), Ok(vec![5]));
+        assert!(parse_cpus("3-1").is_err());
+        assert!(parse_cpus("a").is_err());
+        assert!(parse_cpus("").is_err());
+    }
+
+    #[test]
+    fn every_job_comes_back_with_its_number() {
+        let mut pool = Pool::new(&PoolOptions::new(3, Vec::new()), |worker| {
+            move |n: u64| (worker, n * n)
+        })
+        .expect("starts");
+        assert_eq!(pool.recv(), None);
+        for n in 0..20 {
+            assert_eq!(pool.submit(n), n as usize);
+        }
+        let mut results = Vec::new();
+        while let Some((job, (worker, square))) = pool.recv() {
+            assert!(worker < 3);
+            results.push((job, square));
+        }
+        results.sort_unstable();
+        let expected: Vec<(usize, u64)> = (0..20).map(|n| (n as usize, n * n)).collect();
+        assert_eq!(results, expected);
+        assert_eq!(pool.pending(), 0);
+    }
+
+    #[test]
+    fn submitting_to_a_full_queue_hands_the_job_back() {
+        let (started, running) = mpsc::channel();
+        let (open, gate) = mpsc::channel::<()>();
+        let mut gate = Some(gate);
+        let options = PoolOptions {
+            queue: 1,
+            ..PoolOptions::new(1, Vec::new())
+        };
+        let mut pool = Pool::new(&options, |_| {
+            let gate = gate.take().expect("one worker");
+            let started = started.clone();
+            move |n: u32| {
+                let _ = started.send(());
+                let _ = gate.recv();
+                n
+            }
+        })
+        .expect("starts");
+        pool.submit(1);
+        running.recv().expect("the worker took the first job");
+        assert_eq!(pool.try_submit(2), Ok(1));
+        assert_eq!(pool.try_submit(3), Err(3));
+        assert_eq!(pool.try_recv(), None);
+        open.send(()).expect("opens the gate");
+        open.send(()).expect("opens the gate");
+        assert_eq!(pool.recv(), Some((0, 1)));
+        assert_eq!(pool.recv(), Some((1, 2)));
+        assert_eq!(pool.recv(), None);
+    }
+
+    #[test]
+    fn panics_reach_the_receiver() {
+        let mut pool = Pool::new(&PoolOptions::new(1, Vec::new()), |_| {
+            |n: u32| {
+                assert_ne!(n, 0, "job zero");
+                n
+            }
+        })
+        .expect("starts");
+        pool.submit(0);
+        let received = panic::catch_unwind(AssertUnwindSafe(|| pool.recv()));
+        assert!(received.is_err());
+        // The worker survives its job's panic.
+        pool.submit(1);
+        assert_eq!(pool.recv(), Some((1, 1)));
+    }
+
+    #[test]
+    fn workers_that_cannot_be_pinned_fail_the_pool() {
+        let error = Pool::new(&PoolOptions::new(2, vec![usize::MAX]), |_| |n: u32| n)
+            .expect_err("no such CPU");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput, "{error}");
+    }
+}
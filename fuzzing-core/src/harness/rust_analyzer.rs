@@ -0,0 +1,520 @@
+//! rust-analyzer harness.
+//!
+//! Speaks LSP to a spawned `rust-analyzer`. One server is reused across
+//! inputs: each input replaces `src/lib.rs` of a throwaway Cargo project
+//! through `didChange` and is then asked for hovers and completions at
+//! random offsets, while the server computes diagnostics in the
+//! background. rust-analyzer catches panics in request handlers and turns
+//! them into error responses, and logs panics in background work to
+//! stderr; both count as findings, as does the server going away.
+//!
+//! rust-analyzer's parser is built for error recovery and its HIR is its
+//! own, so it breaks on different inputs than rustc does.
+
+use rand::{Rng, RngCore};
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::PathBuf;
+use std::process::{Child, ChildStdin, Command, Stdio};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::rustc::Scratch;
+use crate::triage::Severity;
+
+/// LSP error codes that say "try again", not "something broke".
+const CONTENT_MODIFIED: i64 = -32801;
+const REQUEST_CANCELLED: i64 = -32800;
+
+/// Lines of the server's stderr kept for reports.
+const STDERR_TAIL: usize = 20;
+
+/// Configuration for [`Session`]s.
+#[derive(Debug, Clone)]
+pub struct RustAnalyzer {
+    pub path: PathBuf,
+    /// How long a single request may take.
+    pub timeout: Duration,
+    /// How long loading the workspace at startup may take.
+    pub load_timeout: Duration,
+    /// Hover and completion requests per input.
+    pub queries: usize,
+    /// Directory under which the throwaway project is created.
+    pub scratch_root: PathBuf,
+}
+
+impl Default for RustAnalyzer {
+    fn default() -> Self {
+        RustAnalyzer {
+            path: PathBuf::from("rust-analyzer"),
+            timeout: Duration::from_secs(10),
+            load_timeout: Duration::from_secs(60),
+            queries: 8,
+            scratch_root: std::env::temp_dir(),
+        }
+    }
+}
+
+/// What rust-analyzer made of one input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AnalyzerOutcome {
+    Clean,
+    /// A request handler panicked.
+    Panic {
+        method: &'static str,
+        message: String,
+    },
+    /// A panic was logged from background work such as diagnostics.
+    LoggedPanic(String),
+    /// A request got no answer in time.
+    Timeout {
+        method: &'static str,
+    },
+    /// The server exited; holds the tail of its stderr.
+    Exited(String),
+}
+
+impl AnalyzerOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            AnalyzerOutcome::Clean => None,
+            AnalyzerOutcome::Timeout { .. } => Some(Severity::Hang),
+            AnalyzerOutcome::Panic { .. }
+            | AnalyzerOutcome::LoggedPanic(_)
+            | AnalyzerOutcome::Exited(_) => Some(Severity::Ice),
+        }
+    }
+}
+
+impl fmt::Display for AnalyzerOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AnalyzerOutcome::Clean => f.write_str("clean"),
+            AnalyzerOutcome::Panic { method, message } => {
+                write!(f, "{method} panicked: {message}")
+            }
+            AnalyzerOutcome::LoggedPanic(message) => write!(f, "background panic: {message}"),
+            AnalyzerOutcome::Timeout { method } => write!(f, "{method} timed out"),
+            AnalyzerOutcome::Exited(stderr) => write!(f, "server exited:\n{stderr}"),
+        }
+    }
+}
+
+impl RustAnalyzer {
+    /// Starts a server on a fresh project and waits for it to finish
+    /// loading the workspace.
+    pub fn start(&self) -> io::Result<Session> {
+        let project = Scratch::new(&self.scratch_root)?;
+        fs::create_dir_all(project.path().join("src"))?;
+        fs::write(
+            project.path().join("Cargo.toml"),
+            "[package]\nname = \"fuzz\"\nversion = \"0.0.0\"\nedition = \"2021\"\n\n[workspace]\n",
+        )?;
+        fs::write(project.path().join("src/lib.rs"), "")?;
+
+        let mut child = Command::new(&self.path)
+            .current_dir(project.path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped");
+        let messages = read_messages(child.stdout.take().expect("stdout is piped"));
+        let stderr = Arc::new(Mutex::new(Stderr::default()));
+        collect_stderr(
+            child.stderr.take().expect("stderr is piped"),
+            stderr.clone(),
+        );
+
+        let root = format!("file://{}", project.path().display());
+        let mut session = Session {
+            uri: format!("{root}/src/lib.rs"),
+            config: self.clone(),
+            child,
+            stdin,
+            messages,
+            stderr,
+            next_id: 0,
+            version: 1,
+            _project: project,
+        };
+        let init = session.request(
+            "initialize",
+            json!({
+                "processId": std::process::id(),
+                "rootUri": root,
+                "capabilities": {
+                    "textDocument": {
+                        "hover": { "contentFormat": ["plaintext"] },
+                        "completion": {},
+                        "publishDiagnostics": {},
+                    },
+                    "experimental": { "serverStatusNotification": true },
+                },
+                "initializationOptions": {
+                    "checkOnSave": false,
+                    "cachePriming": { "enable": false },
+                    "cargo": { "buildScripts": { "enable": false } },
+                    "procMacro": { "enable": false },
+                },
+            }),
+        )?;
+        if init != AnalyzerOutcome::Clean {
+            return Err(io::Error::other(format!("initialize failed: {init}")));
+        }
+        session.notify("initialized", json!({}))?;
+        session.notify(
+            "textDocument/didOpen",
+            json!({
+                "textDocument": {
+                    "uri": session.uri,
+                    "languageId": "rust",
+                    "version": session.version,
+                    "text": "",
+                },
+            }),
+        )?;
+        session.wait_quiescent()?;
+        Ok(session)
+    }
+}
+
+/// A running rust-analyzer with one open file.
+pub struct Session {
+    config: RustAnalyzer,
+    child: Child,
+    stdin: ChildStdin,
+    messages: Receiver<Value>,
+    stderr: Arc<Mutex<Stderr>>,
+    next_id: i64,
+    version: i64,
+    uri: String,
+    _project: Scratch,
+}
+
+impl Session {
+    /// Replaces the open file with `source` and queries it.
+    ///
+    /// `Err` means talking to the server failed; a server that dies is an
+    /// [`AnalyzerOutcome::Exited`], after which the session is unusable.
+    pub fn check(&mut self, source: &str, rng: &mut dyn RngCore) -> io::Result<AnalyzerOutcome> {
+        self.version += 1;
+        self.notify(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": self.uri, "version": self.version },
+                "contentChanges": [{ "text": source }],
+            }),
+        )?;
+        for _ in 0..self.config.queries {
+            let position = position(source, random_offset(source, rng));
+            let params = json!({
+                "textDocument": { "uri": self.uri },
+                "position": position,
+            });
+            for method in ["textDocument/hover", "textDocument/completion"] {
+                let outcome = self.request(method, params.clone())?;
+                if outcome != AnalyzerOutcome::Clean {
+                    return Ok(outcome);
+                }
+            }
+        }
+        Ok(self.logged_panic().unwrap_or(AnalyzerOutcome::Clean))
+    }
+
+    fn request(&mut self, method: &'static str, params: Value) -> io::Result<AnalyzerOutcome> {
+        self.next_id += 1;
+        let id = self.next_id;
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params }))?;
+        self.wait_for(method, id)
+    }
+
+    fn notify(&mut self, method: &str, params: Value) -> io::Result<()> {
+        self.send(&json!({ "jsonrpc": "2.0", "method": method, "params": params }))
+    }
+
+    fn send(&mut self, message: &Value) -> io::Result<()> {
+        let body = message.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{body}", body.len())?;
+        self.stdin.flush()
+    }
+
+    /// Waits for the response to request `id`, answering server requests
+    /// on the way.
+    fn wait_for(&mut self, method: &'static str, id: i64) -> io::Result<AnalyzerOutcome> {
+        let deadline = Instant::now() + self.config.timeout;
+        loop {
+            let message = match self.recv(deadline)? {
+                Ok(message) => message,
+                Err(pending) => return Ok(pending.at(method)),
+            };
+            if message.get("method").is_some() {
+                self.answer(&message)?;
+                continue;
+            }
+            if message["id"] != id {
+                continue;
+            }
+            let Some(error) = message.get("error") else {
+                return Ok(AnalyzerOutcome::Clean);
+            };
+            let code = error["code"].as_i64().unwrap_or_default();
+            let text = error["message"].as_str().unwrap_or_default();
+            if code == CONTENT_MODIFIED || code == REQUEST_CANCELLED || !text.contains("panic") {
+                return Ok(AnalyzerOutcome::Clean);
+            }
+            return Ok(AnalyzerOutcome::Panic {
+                method,
+                message: text.to_owned(),
+            });
+        }
+    }
+
+    /// Waits until the server reports it has finished loading.
+    fn wait_quiescent(&mut self) -> io::Result<()> {
+        let deadline = Instant::now() + self.config.load_timeout;
+        loop {
+            let message = self.recv(deadline)?.map_err(|outcome| {
+                io::Error::other(format!(
+                    "waiting for workspace load: {}",
+                    outcome.at("serverStatus")
+                ))
+            })?;
+            if message.get("id").is_some() && message.get("method").is_some() {
+                self.answer(&message)?;
+            } else if message["method"] == "experimental/serverStatus"
+                && message["params"]["quiescent"] == true
+            {
+                return Ok(());
+            }
+        }
+    }
+
+    /// The next message, or the outcome that prevented one.
+    fn recv(&mut self, deadline: Instant) -> io::Result<Result<Value, Pending>> {
+        let timeout = deadline.saturating_duration_since(Instant::now());
+        match self.messages.recv_timeout(timeout) {
+            Ok(message) => Ok(Ok(message)),
+            Err(RecvTimeoutError::Timeout) => Ok(Err(Pending::Timeout)),
+            Err(RecvTimeoutError::Disconnected) => {
+                let _ = self.child.wait();
+                Ok(Err(Pending::Exited(self.stderr_tail())))
+            }
+        }
+    }
+
+    /// Replies to a server-to-client request with an empty result.
+    fn answer(&mut self, message: &Value) -> io::Result<()> {
+        let Some(id) = message.get("id") else {
+            return Ok(());
+        };
+        let result = match message["method"].as_str() {
+            Some("workspace/configuration") => {
+                let items = message["params"]["items"].as_array().map_or(0, Vec::len);
+                Value::Array(vec![Value::Null; items])
+            }
+            _ => Value::Null,
+        };
+        self.send(&json!({ "jsonrpc": "2.0", "id": id, "result": result }))
+    }
+
+    /// A panic logged to stderr since the last call, if any.
+    fn logged_panic(&mut self) -> Option<AnalyzerOutcome> {
+        let mut stderr = self.stderr.lock().unwrap_or_else(|e| e.into_inner());
+        let first = stderr.panics.drain(..).next();
+        first.map(AnalyzerOutcome::LoggedPanic)
+    }
+
+    fn stderr_tail(&self) -> String {
+        let stderr = self.stderr.lock().unwrap_or_else(|e| e.into_inner());
+        Vec::from(stderr.tail.clone()).join("\n")
+    }
+}
+
+impl Drop for Session {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Why no message arrived.
+enum Pending {
+    Timeout,
+    Exited(String),
+}
+
+impl Pending {
+    fn at(self, method: &'static str) -> AnalyzerOutcome {
+        match self {
+            Pending::Timeout => AnalyzerOutcome::Timeout { method },
+            Pending::Exited(stderr) => AnalyzerOutcome::Exited(stderr),
+        }
+    }
+}
+
+/// Decodes LSP base-protocol frames from `stdout` on a reader thread.
+fn read_messages(stdout: impl Read + Send + 'static) -> Receiver<Value> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut reader = BufReader::new(stdout);
+        loop {
+            let mut length = None;
+            loop {
+                let mut header = String::new();
+                if reader.read_line(&mut header).unwrap_or(0) == 0 {
+                    return;
+                }
+                let header = header.trim_end();
+                if header.is_empty() {
+                    break;
+                }
+                if let Some(n) = header.strip_prefix("Content-Length: ") {
+                    length = n.parse().ok();
+                }
+            }
+            let Some(length) = length else { return };
+            let mut body = vec![0; length];
+            if reader.read_exact(&mut body).is_err() {
+                return;
+            }
+            let Ok(message) = serde_json::from_slice(&body) else {
+                continue;
+            };
+            if tx.send(message).is_err() {
+                return;
+            }
+        }
+    });
+    rx
+}
+
+/// What the server printed to stderr that matters.
+#[derive(Default)]
+struct Stderr {
+    /// Panic lines not yet reported.
+    panics: Vec<String>,
+    tail: VecDeque<String>,
+}
+
+fn collect_stderr(stderr: impl Read + Send + 'static, shared: Arc<Mutex<Stderr>>) {
+    thread::spawn(move || {
+        for line in BufReader::new(stderr).lines() {
+            let Ok(line) = line else { return };
+            let mut shared = shared.lock().unwrap_or_else(|e| e.into_inner());
+            if line.contains("panicked at") {
+                shared.panics.push(line.clone());
+            }
+            if shared.tail.len() == STDERR_TAIL {
+                shared.tail.pop_front();
+            }
+            shared.tail.push_back(line);
+        }
+    });
+}
+
+/// A random char boundary in `source`, end of input included.
+fn random_offset(source: &str, rng: &mut dyn RngCore) -> usize {
+    let mut offset = rng.gen_range(0..=source.len());
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+/// The LSP position (line, UTF-16 column) of byte `offset`.
+fn position(source: &str, offset: usize) -> Value {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    json!({
+        "line": before.matches('\n').count(),
+        "character": before[line_start..].encode_utf16().count(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn positions_count_utf16_columns() {
+        let source = "fn f() {}\nlet 🦀 = é;\n";
+        let offset = source.find('=').expect("has an `=`");
+        assert_eq!(position(source, offset), json!({"line": 1, "character": 7}));
+        assert_eq!(position(source, 0), json!({"line": 0, "character": 0}));
+    }
+
+    #[test]
+    fn offsets_fall_on_char_boundaries() {
+        let source = "é🦀ü";
+        let mut rng = StdRng::seed_from_u64(0);
+        for _ in 0..100 {
+            assert!(source.is_char_boundary(random_offset(source, &mut rng)));
+        }
+    }
+
+    #[test]
+    fn messages_are_read_frame_by_frame() {
+        let frame = |body: &str| format!("Content-Length: {}\r\n\r\n{body}", body.len());
+        let stdout = [
+            frame(r#"{"id":1}"#),
+            frame("not json"),
+            frame(r#"{"id":2,"result":null}"#),
+        ]
+        .concat();
+        let messages = read_messages(io::Cursor::new(stdout.into_bytes()));
+        let ids: Vec<Value> = messages.iter().map(|m| m["id"].clone()).collect();
+        assert_eq!(ids, [json!(1), json!(2)]);
+    }
+
+    #[test]
+    fn stderr_keeps_panics_and_a_tail() {
+        let lines: Vec<String> = (0..30)
+            .map(|i| match i {
+                3 => "thread 'Worker' panicked at crates/hir/src/lib.rs:1:1:".to_owned(),
+                _ => format!("line {i}"),
+            })
+            .collect();
+        let shared = Arc::new(Mutex::new(Stderr::default()));
+        collect_stderr(io::Cursor::new(lines.join("\n")), Arc::clone(&shared));
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while shared.lock().expect("not poisoned").tail.back() != Some(&"line 29".to_owned()) {
+            assert!(Instant::now() < deadline, "stderr was not read");
+            thread::sleep(Duration::from_millis(10));
+        }
+        let stderr = shared.lock().expect("not poisoned");
+        assert_eq!(stderr.panics, [lines[3].clone()]);
+        assert_eq!(stderr.tail.len(), STDERR_TAIL);
+        assert_eq!(stderr.tail[0], "line 10");
+    }
+
+    #[test]
+    fn timeouts_hang_and_panics_are_ices() {
+        assert_eq!(AnalyzerOutcome::Clean.severity(), None);
+        assert_eq!(
+            AnalyzerOutcome::Timeout { method: "hover" }.severity(),
+            Some(Severity::Hang)
+        );
+        let panic = AnalyzerOutcome::Panic {
+            method: "textDocument/completion",
+            message: "index out of bounds".to_owned(),
+        };
+        assert_eq!(panic.severity(), Some(Severity::Ice));
+        assert_eq!(
+            panic.to_string(),
+            "textDocument/completion panicked: index out of bounds"
+        );
+    }
+}
@@ -0,0 +1,869 @@
+//! The rustc driver.
+//!
+//! Each execution gets its own scratch directory (working directory and
+//! `TMPDIR`), its own process group so a hung linker dies with its
+//! parent, a wall-clock deadline, an optional memory limit, and capped
+//! output capture. Its peak resident set size is recorded from `wait4`.
+//! Core dumps are disabled: a fuzzer that crashes rustc thousands of
+//! times a day does not want thousands of cores. Compiled seeds run in
+//! a [`Sandbox`] as well. With a [`Container`], all of it happens in a
+//! container instead.
+
+use std::ffi::{CString, OsStr, OsString};
+use std::fmt;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::container::{self, Container};
+use super::{Phase, Sandbox};
+use crate::triage::ice::{self, IceReport};
+
+/// rustc's exit code for an internal compiler error.
+const ICE_EXIT_CODE: i32 = 101;
+
+/// Messages an allocator or LLVM prints before aborting on exhaustion.
+const OOM_MESSAGES: &[&str] = &["memory allocation of", "LLVM ERROR: out of memory"];
+
+/// What [`Rustc::default`] returns once [`Rustc::set_default`] is called.
+static DEFAULT: OnceLock<Rustc> = OnceLock::new();
+
+/// How a child's memory is bounded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemoryLimit {
+    /// `RLIMIT_AS` on rustc itself. Unprivileged and cheap, but counts
+    /// reserved address space as well as resident memory, and does not
+    /// cover subprocesses such as the linker. Set it too low (a few hundred
+    /// MiB) and rustc dies with `SIGSEGV` before reading its input.
+    AddressSpace(u64),
+    /// A cgroup v2 directory the fuzzer may write to. Its `memory.max` is
+    /// set to `bytes` and every execution is moved into it, so the whole
+    /// process tree is accounted by RSS. Executions sharing one cgroup
+    /// share the budget, so give each worker its own.
+    Cgroup { path: PathBuf, bytes: u64 },
+}
+
+/// Spawns rustc with resource limits.
+#[derive(Debug, Clone)]
+pub struct Rustc {
+    pub path: PathBuf,
+    /// Where compilation stops; its flags precede the caller's arguments.
+    pub phase: Phase,
+    /// Wall-clock budget per execution, after which the process group is
+    /// killed.
+    pub timeout: Duration,
+    pub memory: Option<MemoryLimit>,
+    /// Niceness the child runs at, so a pool of compilations leaves the
+    /// host responsive; `None` inherits the fuzzer's. Unprivileged, it can
+    /// only be raised.
+    pub nice: Option<i32>,
+    /// Bytes of stdout and of stderr kept per execution; the rest is read
+    /// and discarded so the child never blocks on a full pipe.
+    pub output_limit: usize,
+    /// Directory under which per-execution scratch directories are made.
+    pub scratch_root: PathBuf,
+    /// Extra environment for every execution.
+    pub env: Vec<(OsString, OsString)>,
+    /// Confinement for [`Rustc::run_sandboxed`].
+    pub sandbox: Sandbox,
+    /// Run every execution in a container of this image rather than on
+    /// the host; the program is then looked up in the image.
+    pub container: Option<Container>,
+}
+
+impl Default for Rustc {
+    /// The driver a campaign config set with [`Rustc::set_default`], or
+    /// else `rustc` from `PATH` with a ten-second timeout and 4 GiB of
+    /// address space.
+    fn default() -> Self {
+        if let Some(rustc) = DEFAULT.get() {
+            return rustc.clone();
+        }
+        Rustc {
+            path: PathBuf::from("rustc"),
+            phase: Phase::default(),
+            timeout: Duration::from_secs(10),
+            memory: Some(MemoryLimit::AddressSpace(4 << 30)),
+            nice: None,
+            output_limit: 1 << 20,
+            scratch_root: std::env::temp_dir(),
+            // `-Z` flags are unlocked so a stable rustc works as well as a
+            // nightly one.
+            env: vec![("RUSTC_BOOTSTRAP".into(), "1".into())],
+            sandbox: Sandbox::default(),
+            container: None,
+        }
+    }
+}
+
+/// What a finished execution printed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Captured {
+    pub stdout: String,
+    pub stderr: String,
+    /// Whether either stream hit [`Rustc::output_limit`].
+    pub truncated: bool,
+    pub elapsed: Duration,
+    /// The phase a rustc execution was limited to; `None` for other
+    /// programs.
+    pub phase: Option<Phase>,
+    /// Peak resident set size in bytes, of the process and the children it
+    /// waited for; `None` if the kernel did not say.
+    pub peak_rss: Option<u64>,
+}
+
+/// The classified result of one rustc execution.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExecOutcome {
+    /// rustc exited successfully.
+    Success(Captured),
+    /// rustc rejected the input with ordinary diagnostics.
+    Error { code: i32, output: Captured },
+    /// rustc reported an internal compiler error.
+    Ice(Captured),
+    /// rustc died from a signal we did not send, e.g. a stack overflow
+    /// turned into `SIGABRT` or a raw `SIGSEGV`.
+    Crash { signal: i32, output: Captured },
+    /// rustc exceeded [`Rustc::timeout`] and was killed, or a sandboxed
+    /// program used up its [`Sandbox::cpu_seconds`].
+    Timeout(Captured),
+    /// rustc ran out of memory under the configured [`MemoryLimit`], or
+    /// was killed by the kernel's OOM killer.
+    OutOfMemory(Captured),
+}
+
+impl ExecOutcome {
+    pub fn output(&self) -> &Captured {
+        match self {
+            ExecOutcome::Success(output)
+            | ExecOutcome::Ice(output)
+            | ExecOutcome::Timeout(output)
+            | ExecOutcome::OutOfMemory(output) => output,
+            ExecOutcome::Error { output, .. } | ExecOutcome::Crash { output, .. } => output,
+        }
+    }
+
+    /// The phase a rustc execution was limited to.
+    pub fn phase(&self) -> Option<Phase> {
+        self.output().phase
+    }
+
+    /// Short stable name, used in logs and statistics.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ExecOutcome::Success(_) => "success",
+            ExecOutcome::Error { .. } => "error",
+            ExecOutcome::Ice(_) => "ice",
+            ExecOutcome::Crash { .. } => "crash",
+            ExecOutcome::Timeout(_) => "timeout",
+            ExecOutcome::OutOfMemory(_) => "oom",
+        }
+    }
+
+    pub fn is_success(&self) -> bool {
+        matches!(self, ExecOutcome::Success(_))
+    }
+
+    /// Whether this is a compiler bug rather than a verdict on the input.
+    pub fn is_crash(&self) -> bool {
+        matches!(self, ExecOutcome::Ice(_) | ExecOutcome::Crash { .. })
+    }
+
+    /// What an internal compiler error's stderr says about it.
+    pub fn ice(&self) -> Option<IceReport> {
+        match self {
+            ExecOutcome::Ice(output) => ice::parse(&output.stderr),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for ExecOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExecOutcome::Success(_) => f.write_str("success"),
+            ExecOutcome::Error { code, .. } => write!(f, "error (exit code {code})"),
+            ExecOutcome::Ice(_) => f.write_str("internal compiler error"),
+            ExecOutcome::Crash { signal, .. } => write!(f, "crash (signal {signal})"),
+            ExecOutcome::Timeout(output) => write!(f, "timeout after {:?}", output.elapsed),
+            ExecOutcome::OutOfMemory(output) => match output.peak_rss {
+                Some(bytes) => write!(f, "out of memory (peak RSS {} MiB)", bytes >> 20),
+                None => f.write_str("out of memory"),
+            },
+        }
+    }
+}
+
+impl Rustc {
+    /// A driver for the rustc at `path` with default limits.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Rustc {
+            path: path.into(),
+            ..Rustc::default()
+        }
+    }
+
+    /// Makes [`Rustc::default`] return `rustc` from now on, so the
+    /// toolchain and limits of a campaign config reach every harness. Only
+    /// the first call has an effect; returns whether it was this one.
+    pub fn set_default(rustc: Rustc) -> bool {
+        DEFAULT.set(rustc).is_ok()
+    }
+
+    /// Runs rustc with `args`, feeding it `stdin`.
+    ///
+    /// Relative paths in `args` resolve against the scratch directory,
+    /// which is removed afterwards; pass `-` and the source as `stdin`, or
+    /// absolute paths. `Err` means the driver itself failed, never that
+    /// rustc did.
+    pub fn run<I, S>(&self, args: I, stdin: &[u8]) -> io::Result<ExecOutcome>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let args = self
+            .phase
+            .args()
+            .iter()
+            .map(OsString::from)
+            .chain(args.into_iter().map(|a| a.as_ref().to_owned()))
+            .collect();
+        self.exec(self.path.as_os_str(), args, stdin, Some(self.phase), None)
+    }
+
+    /// Runs some other program (rustfmt, rustdoc, a compiled test binary)
+    /// under the same limits and classification as rustc. The phase does
+    /// not apply.
+    pub fn run_program<I, S>(
+        &self,
+        program: &Path,
+        args: I,
+        stdin: &[u8],
+    ) -> io::Result<ExecOutcome>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let args = args.into_iter().map(|a| a.as_ref().to_owned()).collect();
+        self.exec(program.as_os_str(), args, stdin, None, None)
+    }
+
+    /// Like [`Rustc::run_program`], for programs that are not to be
+    /// trusted: compiled seeds. The program also runs in
+    /// [`Rustc::sandbox`], with an empty environment apart from `TMPDIR`.
+    pub fn run_sandboxed<I, S>(
+        &self,
+        program: &Path,
+        args: I,
+        stdin: &[u8],
+    ) -> io::Result<ExecOutcome>
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        let args = args.into_iter().map(|a| a.as_ref().to_owned()).collect();
+        self.exec(program.as_os_str(), args, stdin, None, Some(&self.sandbox))
+    }
+
+    fn exec(
+        &self,
+        program: &OsStr,
+        args: Vec<OsString>,
+        stdin: &[u8],
+        phase: Option<Phase>,
+        sandbox: Option<&Sandbox>,
+    ) -> io::Result<ExecOutcome> {
+        let scratch = Scratch::new(&self.scratch_root)?;
+        tracing::trace!(
+            program = %program.to_string_lossy(),
+            ?args,
+            scratch = %scratch.path().display(),
+            container = self.container.is_some(),
+            "running"
+        );
+        let mut command = match &self.container {
+            Some(container) => {
+                let mut env = match sandbox {
+                    Some(_) => Vec::new(),
+                    None => self.env.clone(),
+                };
+                env.push(("TMPDIR".into(), scratch.path().into()));
+                let memory = self.memory.as_ref().map(|memory| match memory {
+                    MemoryLimit::AddressSpace(bytes) | MemoryLimit::Cgroup { bytes, .. } => *bytes,
+                });
+                container.command(program, &args, &env, scratch.path(), memory)
+            }
+            None => {
+                let mut command = Command::new(resolve(program)?);
+                command.args(args);
+                if sandbox.is_some() {
+                    command.env_clear();
+                } else {
+                    command.envs(self.env.iter().map(|(k, v)| (k, v)));
+                }
+                command.env("TMPDIR", scratch.path());
+                command
+            }
+        };
+        command
+            .current_dir(scratch.path())
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0);
+        // The container is limited and confined instead of the engine's
+        // client, which needs more address space and system calls than
+        // rustc does.
+        let (memory, sandbox) = match &self.container {
+            Some(_) => (None, None),
+            None => (self.memory.as_ref(), sandbox),
+        };
+
+        let oom_kills_before = match memory {
+            Some(MemoryLimit::Cgroup { path, bytes }) => {
+                fs::write(path.join("memory.max"), bytes.to_string())?;
+                oom_kills(path)?
+            }
+            _ => 0,
+        };
+        let limits = Limits::new(memory, self.nice)?;
+        let sandbox = sandbox.cloned();
+        // SAFETY: `Limits::apply` and `Sandbox::apply` only make
+        // async-signal-safe calls on memory prepared before the fork.
+        unsafe {
+            command.pre_exec(move || {
+                limits.apply()?;
+                match &sandbox {
+                    Some(sandbox) => sandbox.apply(),
+                    None => Ok(()),
+                }
+            });
+        }
+
+        let start = Instant::now();
+        let mut child = command.spawn().map_err(|e| {
+            io::Error::new(
+                e.kind(),
+                format!("running {}: {e}", program.to_string_lossy()),
+            )
+        })?;
+        let pid = child.id() as libc::pid_t;
+        tracing::trace!(pid, "started");
+        let mut input = child.stdin.take().expect("stdin is piped");
+        let stdin = stdin.to_vec();
+        let writer = thread::spawn(move || {
+            // A compiler that exits early closes the pipe; that is its
+            // business, not an error.
+            let _ = input.write_all(&stdin);
+        });
+        let limit = self.output_limit;
+        let stdout = child.stdout.take().expect("stdout is piped");
+        let stderr = child.stderr.take().expect("stderr is piped");
+        let stdout = thread::spawn(move || capture(stdout, limit));
+        let stderr = thread::spawn(move || capture(stderr, limit));
+
+        let (mut status, timed_out, mut peak_rss) = wait(&child, start + self.timeout)?;
+        // Reap whatever rustc left behind (a linker, say) so the pipes
+        // close and the reader threads finish.
+        kill_group(pid);
+        if let (Some(container), true) = (&self.container, timed_out) {
+            // Killing the client leaves the container running.
+            let _ = container
+                .remove(&scratch.name())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status();
+        }
+        let elapsed = start.elapsed();
+        let _ = writer.join();
+        let (stdout, stdout_truncated) = stdout.join().expect("reader panicked")?;
+        let (stderr, stderr_truncated) = stderr.join().expect("reader panicked")?;
+        if let (Some(container), false) = (&self.container, timed_out) {
+            // The client's peak RSS says nothing about the program's.
+            peak_rss = None;
+            match status.code() {
+                Some(code) if container::ENGINE_ERRORS.contains(&code) => {
+                    return Err(io::Error::other(format!(
+                        "{} could not run {} (exit code {code}): {}",
+                        container.engine.display(),
+                        program.to_string_lossy(),
+                        String::from_utf8_lossy(&stderr).trim()
+                    )));
+                }
+                // The engine reports death by signal N as exit code 128 + N.
+                Some(code @ 129..=192) => status = ExitStatus::from_raw(code - 128),
+                _ => {}
+            }
+        }
+        let output = Captured {
+            stdout: String::from_utf8_lossy(&stdout).into_owned(),
+            stderr: String::from_utf8_lossy(&stderr).into_owned(),
+            truncated: stdout_truncated || stderr_truncated,
+            elapsed,
+            phase,
+            peak_rss,
+        };
+
+        let oom_killed = match memory {
+            Some(MemoryLimit::Cgroup { path, .. }) => oom_kills(path)? > oom_kills_before,
+            _ => false,
+        };
+        let outcome = classify(status, timed_out, oom_killed, output);
+        tracing::debug!(
+            pid,
+            outcome = outcome.kind(),
+            elapsed_ms = elapsed.as_millis() as u64,
+            peak_rss = outcome.output().peak_rss,
+            truncated = outcome.output().truncated,
+            "exited"
+        );
+        Ok(outcome)
+    }
+}
+
+/// `program` as the caller would find it. The child runs in its scratch
+/// directory, so a relative path is made absolute against the caller's
+/// working directory; a bare name is left to the `PATH` lookup.
+fn resolve(program: &OsStr) -> io::Result<PathBuf> {
+    let path = Path::new(program);
+    if path.is_relative() && path.components().count() > 1 {
+        std::path::absolute(path)
+    } else {
+        Ok(path.to_owned())
+    }
+}
+
+fn classify(
+    status: ExitStatus,
+    timed_out: bool,
+    oom_killed: bool,
+    output: Captured,
+) -> ExecOutcome {
+    // A sandboxed program that used up its CPU time is as hung as one
+    // that ran out of wall-clock time.
+    if timed_out || status.signal() == Some(libc::SIGXCPU) {
+        return ExecOutcome::Timeout(output);
+    }
+    let exhausted = OOM_MESSAGES.iter().any(|m| output.stderr.contains(m));
+    // The only SIGKILL we send is on timeout. The CPU limit of a sandboxed
+    // program sends SIGXCPU first and SIGKILL only if the program survives
+    // it, which a seed in safe Rust cannot arrange. Any other SIGKILL comes
+    // from the kernel's OOM killer, whether or not it was our cgroup's.
+    let killed = status.signal() == Some(libc::SIGKILL);
+    if !status.success() && (oom_killed || exhausted || killed) {
+        return ExecOutcome::OutOfMemory(output);
+    }
+    match status.code() {
+        Some(0) => ExecOutcome::Success(output),
+        Some(ICE_EXIT_CODE) => ExecOutcome::Ice(output),
+        Some(code) => ExecOutcome::Error { code, output },
+        None => ExecOutcome::Crash {
+            signal: status.signal().unwrap_or_default(),
+            output,
+        },
+    }
+}
+
+/// Waits for `child` until `deadline`, killing its process group if it is
+/// still running then. Returns the exit status, whether it timed out, and
+/// the child's peak RSS. The child is reaped with `wait4` rather than
+/// through [`std::process::Child`], which does not report resource usage.
+fn wait(
+    child: &std::process::Child,
+    deadline: Instant,
+) -> io::Result<(ExitStatus, bool, Option<u64>)> {
+    let pid = child.id() as libc::pid_t;
+    let mut pause = Duration::from_millis(1);
+    let mut timed_out = false;
+    loop {
+        let mut status = 0;
+        // SAFETY: an all-zero rusage is a valid value.
+        let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+        let options = if timed_out { 0 } else { libc::WNOHANG };
+        // SAFETY: `status` and `usage` are valid for writes during the
+        // call, and `pid` is our unreaped child.
+        match unsafe { libc::wait4(pid, &mut status, options, &mut usage) } {
+            reaped if reaped == pid => {
+                return Ok((ExitStatus::from_raw(status), timed_out, peak_rss(&usage)))
+            }
+            0 => {}
+            _ => {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+        }
+        let now = Instant::now();
+        if now >= deadline {
+            tracing::debug!(pid, "deadline passed; killing its process group");
+            kill_group(pid);
+            timed_out = true;
+            continue;
+        }
+        thread::sleep(pause.min(deadline - now));
+        pause = (pause * 2).min(Duration::from_millis(20));
+    }
+}
+
+/// `ru_maxrss` in bytes: Linux counts it in kibibytes, macOS in bytes.
+fn peak_rss(usage: &libc::rusage) -> Option<u64> {
+    let maxrss = u64::try_from(usage.ru_maxrss).ok().filter(|&n| n > 0)?;
+    Some(if cfg!(target_os = "macos") {
+        maxrss
+    } else {
+        maxrss << 10
+    })
+}
+
+fn kill_group(pid: libc::pid_t) {
+    // SAFETY: plain syscall; the group was created for this child, and
+    // ESRCH once everything has exited is expected.
+    unsafe {
+        libc::kill(-pid, libc::SIGKILL);
+    }
+}
+
+/// Reads `reader` to the end, keeping at most `limit` bytes.
+fn capture(mut reader: impl Read, limit: usize) -> io::Result<(Vec<u8>, bool)> {
+    let mut kept = Vec::new();
+    let mut truncated = false;
+    let mut buf = [0; 8192];
+    loop {
+        let n = match reader.read(&mut buf) {
+            Ok(0) => return Ok((kept, truncated)),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        let room = limit - kept.len();
+        kept.extend_from_slice(&buf[..n.min(room)]);
+        truncated |= n > room;
+    }
+}
+
+/// The `oom_kill` counter from a cgroup's `memory.events`.
+fn oom_kills(cgroup: &Path) -> io::Result<u64> {
+    let events = fs::read_to_string(cgroup.join("memory.events"))?;
+    Ok(events
+        .lines()
+        .find_map(|line| line.strip_prefix("oom_kill "))
+        .and_then(|n| n.trim().parse().ok())
+        .unwrap_or(0))
+}
+
+/// Limits applied in the child between fork and exec.
+struct Limits {
+    address_space: Option<u64>,
+    cgroup_procs: Option<CString>,
+    nice: Option<i32>,
+}
+
+impl Limits {
+    fn new(memory: Option<&MemoryLimit>, nice: Option<i32>) -> io::Result<Self> {
+        let mut limits = Limits {
+            address_space: None,
+            cgroup_procs: None,
+            nice,
+        };
+        match memory {
+            Some(MemoryLimit::AddressSpace(bytes)) => limits.address_space = Some(*bytes),
+            Some(MemoryLimit::Cgroup { path, .. }) => {
+                let procs = path.join("cgroup.procs");
+                limits.cgroup_procs = Some(
+                    CString::new(procs.as_os_str().as_bytes())
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?,
+                );
+            }
+            None => {}
+        }
+        Ok(limits)
+    }
+
+    fn apply(&self) -> io::Result<()> {
+        setrlimit(libc::RLIMIT_CORE, 0)?;
+        if let Some(nice) = self.nice {
+            // SAFETY: setpriority is async-signal-safe; `0` is this child.
+            if unsafe { libc::setpriority(libc::PRIO_PROCESS, 0, nice) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        if let Some(bytes) = self.address_space {
+            setrlimit(libc::RLIMIT_AS, bytes)?;
+        }
+        if let Some(procs) = &self.cgroup_procs {
+            // Writing "0" moves the writing process, i.e. this child.
+            // SAFETY: open/write/close are async-signal-safe and `procs`
+            // was allocated before the fork.
+            unsafe {
+                let fd = libc::open(procs.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+                if fd < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let written = libc::write(fd, b"0".as_ptr().cast(), 1);
+                libc::close(fd);
+                if written != 1 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(target_os = "linux", target_env = "gnu"))]
+pub(super) type Resource = libc::__rlimit_resource_t;
+#[cfg(not(all(target_os = "linux", target_env = "gnu")))]
+pub(super) type Resource = libc::c_int;
+
+pub(super) fn setrlimit(resource: Resource, bytes: u64) -> io::Result<()> {
+    setrlimits(resource, bytes, bytes)
+}
+
+/// Sets the soft limit of `resource` to `soft` and its hard limit to
+/// `hard`, for resources whose soft limit only sends a signal.
+pub(super) fn setrlimits(resource: Resource, soft: u64, hard: u64) -> io::Result<()> {
+    let limit = libc::rlimit {
+        rlim_cur: soft as libc::rlim_t,
+        rlim_max: hard as libc::rlim_t,
+    };
+    // SAFETY: `limit` is a valid rlimit for the duration of the call.
+    if unsafe { libc::setrlimit(resource, &limit) } == 0 {
+        Ok(())
+    } else {
+        Err(io::Error::last_os_error())
+    }
+}
+
+/// A per-execution directory, removed on drop.
+pub(crate) struct Scratch(PathBuf);
+
+impl Scratch {
+    /// A new directory under `root`, by its absolute path, so it means the
+    /// same from a child's working directory and inside a container.
+    pub(crate) fn new(root: &Path) -> io::Result<Self> {
+        static NEXT: AtomicU64 = AtomicU64::new(0);
+        let n = NEXT.fetch_add(1, Ordering::Relaxed);
+        let path =
+            std::path::absolute(root)?.join(format!("rustc-fuzz-{}-{n}", std::process::id()));
+        fs::create_dir_all(&path)?;
+        Ok(Scratch(path))
+    }
+
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// The directory's name, unique among running executions.
+    pub(crate) fn name(&self) -> String {
+        self.0
+            .file_name()
+            .map_or_else(String::new, |name| name.to_string_lossy().into_owned())
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn exited(code: i32) -> ExitStatus {
+        ExitStatus::from_raw(code << 8)
+    }
+
+    fn signalled(signal: i32) -> ExitStatus {
+        ExitStatus::from_raw(signal)
+    }
+
+    #[test]
+    fn classify_maps_exit_codes() {
+        let output = Captured::default;
+        assert_eq!(
+            classify(exited(0), false, false, output()),
+            ExecOutcome::Success(output())
+        );
+        assert_eq!(
+            classify(exited(ICE_EXIT_CODE), false, false, output()),
+            ExecOutcome::Ice(output())
+        );
+        assert_eq!(
+            classify(exited(1), false, false, output()),
+            ExecOutcome::Error {
+                code: 1,
+                output: output()
+            }
+        );
+    }
+
+    #[test]
+    fn classify_maps_signals() {
+        let output = Captured::default;
+        assert_eq!(
+            classify(signalled(libc::SIGSEGV), false, false, output()),
+            ExecOutcome::Crash {
+                signal: libc::SIGSEGV,
+                output: output()
+            }
+        );
+        assert_eq!(
+            classify(signalled(libc::SIGABRT), false, false, output()),
+            ExecOutcome::Crash {
+                signal: libc::SIGABRT,
+                output: output()
+            }
+        );
+        // Ours, on timeout.
+        assert_eq!(
+            classify(signalled(libc::SIGKILL), true, false, output()),
+            ExecOutcome::Timeout(output())
+        );
+    }
+
+    #[test]
+    fn classify_counts_the_cpu_limit_as_a_timeout() {
+        assert_eq!(
+            classify(signalled(libc::SIGXCPU), false, false, Captured::default()),
+            ExecOutcome::Timeout(Captured::default())
+        );
+    }
+
+    #[test]
+    fn classify_recognizes_memory_exhaustion() {
+        let stderr = |text: &str| Captured {
+            stderr: text.to_owned(),
+            ..Captured::default()
+        };
+        assert_eq!(
+            classify(exited(1), false, true, Captured::default()),
+            ExecOutcome::OutOfMemory(Captured::default())
+        );
+        let aborted = stderr("memory allocation of 1048576 bytes failed\n");
+        assert_eq!(
+            classify(signalled(libc::SIGABRT), false, false, aborted.clone()),
+            ExecOutcome::OutOfMemory(aborted)
+        );
+        let llvm = stderr("LLVM ERROR: out of memory\n");
+        assert_eq!(
+            classify(exited(1), false, false, llvm.clone()),
+            ExecOutcome::OutOfMemory(llvm)
+        );
+        // The kernel's OOM killer; our own SIGKILL only comes on timeout.
+        assert_eq!(
+            classify(signalled(libc::SIGKILL), false, false, Captured::default()),
+            ExecOutcome::OutOfMemory(Captured::default())
+        );
+        // A cgroup OOM kill of some child does not spoil a success.
+        assert_eq!(
+            classify(exited(0), false, true, Captured::default()),
+            ExecOutcome::Success(Captured::default())
+        );
+    }
+
+    #[test]
+    fn relative_programs_resolve_against_the_working_directory() {
+        let cwd = std::env::current_dir().expect("has a working directory");
+        assert_eq!(
+            resolve("bin/rustc".as_ref()).expect("resolves"),
+            cwd.join("bin/rustc")
+        );
+        assert_eq!(
+            resolve("rustc".as_ref()).expect("resolves"),
+            Path::new("rustc")
+        );
+        assert_eq!(
+            resolve("/usr/bin/rustc".as_ref()).expect("resolves"),
+            Path::new("/usr/bin/rustc")
+        );
+    }
+
+    #[test]
+    fn compiles_record_their_peak_rss() {
+        let outcome = Rustc::new("/bin/sh")
+            .run(["-c", "cat >/dev/null"], b"fn main() {}")
+            .expect("runs");
+        assert!(outcome.is_success(), "{outcome}");
+        assert!(outcome.output().peak_rss.is_some_and(|bytes| bytes > 0));
+        let oom = ExecOutcome::OutOfMemory(Captured {
+            peak_rss: Some(3 << 20),
+            ..Captured::default()
+        });
+        assert_eq!(oom.to_string(), "out of memory (peak RSS 3 MiB)");
+    }
+
+    #[test]
+    fn compilers_run_at_their_niceness() {
+        let rustc = Rustc {
+            nice: Some(5),
+            ..Rustc::new("/bin/sh")
+        };
+        let outcome = rustc.run(["-c", "nice"], b"").expect("runs");
+        assert_eq!(outcome.output().stdout.trim(), "5", "{outcome}");
+    }
+
+    #[test]
+    fn executions_go_through_the_container_engine() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-engine-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("creates the directory");
+        // Logs its arguments and runs what follows the image on the host.
+        let engine = dir.join("docker");
+        let log = dir.join("log");
+        std::fs::write(
+            &engine,
+            format!(
+                "#!/bin/sh\necho \"$*\" >> {}\n\
+                 while [ \"$1\" != img@sha256:0 ]; do shift; done\nshift\nexec \"$@\"\n",
+                log.display()
+            ),
+        )
+        .expect("writes the engine");
+        std::fs::set_permissions(&engine, std::fs::Permissions::from_mode(0o755))
+            .expect("makes it runnable");
+        let rustc = Rustc {
+            container: Some(Container {
+                engine,
+                ..Container::new("img@sha256:0")
+            }),
+            memory: Some(MemoryLimit::AddressSpace(64 << 20)),
+            ..Rustc::new("/bin/sh")
+        };
+        let outcome = rustc.run(["-c", "cat"], b"hi").expect("runs");
+        assert_eq!(outcome.output().stdout, "hi", "{outcome}");
+        let logged = std::fs::read_to_string(&log).expect("reads the log");
+        assert!(logged.contains("--network=none"), "{logged}");
+        assert!(
+            logged.contains(&format!("--memory={}", 64 << 20)),
+            "{logged}"
+        );
+        let crash = rustc.run(["-c", "exit 139"], b"").expect("runs");
+        assert_eq!(crash.kind(), "crash", "{crash}");
+        let error = rustc
+            .run(["-c", "exit 125"], b"")
+            .expect_err("an engine failure");
+        assert!(error.to_string().contains("exit code 125"), "{error}");
+        std::fs::remove_dir_all(&dir).expect("cleans up");
+    }
+
+    #[test]
+    fn spawn_errors_name_the_program() {
+        let error = Rustc::new("bin/no-such-rustc")
+            .run(["-V"], b"")
+            .expect_err("no such program");
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert!(error.to_string().contains("bin/no-such-rustc"), "{error}");
+    }
+}
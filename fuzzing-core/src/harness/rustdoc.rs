@@ -0,0 +1,250 @@
+//! rustdoc harness.
+//!
+//! Documents each seed as HTML and as rustdoc JSON, and again after
+//! injecting the doc-comment payloads of [`DocInjection`], which is where
+//! rustdoc's own passes (intra-doc link resolution, doctest collection,
+//! markdown rendering) get their input. rustdoc is rustc plus its own HIR
+//! cleaning and rendering, so an ICE is attributed to whichever of the two
+//! panicked: plain compilation may never reach the code that failed.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use rand::RngCore;
+
+use super::{ExecOutcome, Rustc};
+use crate::mutate::{DocInjection, Mutator};
+use crate::triage::Severity;
+
+/// Runs rustdoc on seeds under the limits of a [`Rustc`] driver.
+#[derive(Debug, Clone)]
+pub struct Rustdoc {
+    pub path: PathBuf,
+    pub edition: String,
+    /// Doc-comment-injected variants tried after the seed itself.
+    pub payloads: usize,
+    /// Supplies timeout, memory and output limits.
+    pub driver: Rustc,
+}
+
+impl Default for Rustdoc {
+    fn default() -> Self {
+        Rustdoc {
+            path: PathBuf::from("rustdoc"),
+            edition: "2021".to_owned(),
+            payloads: 4,
+            driver: Rustc::default(),
+        }
+    }
+}
+
+/// Output backend rustdoc was asked for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DocFormat {
+    Html,
+    Json,
+}
+
+impl DocFormat {
+    pub const ALL: [DocFormat; 2] = [DocFormat::Html, DocFormat::Json];
+
+    fn args(self) -> &'static [&'static str] {
+        match self {
+            DocFormat::Html => &[],
+            DocFormat::Json => &["-Zunstable-options", "--output-format", "json"],
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            DocFormat::Html => "html",
+            DocFormat::Json => "json",
+        }
+    }
+}
+
+impl fmt::Display for DocFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// What documenting one seed led to. Findings carry the input that
+/// triggered them, which is a doc-injected variant of the seed unless
+/// the seed failed on its own.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DocOutcome {
+    /// Every input documented, or was rejected, in every format.
+    Clean,
+    /// rustdoc panicked in its own code.
+    RustdocIce {
+        format: DocFormat,
+        input: String,
+        outcome: ExecOutcome,
+    },
+    /// rustdoc panicked inside the compiler it is built on.
+    RustcIce {
+        format: DocFormat,
+        input: String,
+        outcome: ExecOutcome,
+    },
+    /// rustdoc crashed by signal, hung or ran out of memory.
+    Failed {
+        format: DocFormat,
+        input: String,
+        outcome: ExecOutcome,
+    },
+}
+
+impl DocOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            DocOutcome::Clean => None,
+            DocOutcome::RustdocIce { outcome, .. }
+            | DocOutcome::RustcIce { outcome, .. }
+            | DocOutcome::Failed { outcome, .. } => Some(Severity::of_failure(outcome)),
+        }
+    }
+}
+
+impl fmt::Display for DocOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DocOutcome::Clean => f.write_str("clean"),
+            DocOutcome::RustdocIce { format, .. } => write!(f, "rustdoc ICE ({format})"),
+            DocOutcome::RustcIce { format, .. } => write!(f, "rustc ICE under rustdoc ({format})"),
+            DocOutcome::Failed {
+                format, outcome, ..
+            } => write!(f, "rustdoc failed ({format}): {outcome}"),
+        }
+    }
+}
+
+impl Rustdoc {
+    /// Documents `source`, then up to [`Rustdoc::payloads`] variants of it
+    /// with injected doc comments, stopping at the first finding.
+    pub fn check(&self, source: &str, rng: &mut dyn RngCore) -> io::Result<DocOutcome> {
+        let outcome = self.document(source)?;
+        if outcome.is_finding() {
+            return Ok(outcome);
+        }
+        for _ in 0..self.payloads {
+            let Some(variant) = DocInjection.mutate(source, rng) else {
+                break;
+            };
+            let outcome = self.document(&variant)?;
+            if outcome.is_finding() {
+                return Ok(outcome);
+            }
+        }
+        Ok(DocOutcome::Clean)
+    }
+
+    /// Documents `source` in each format.
+    pub fn document(&self, source: &str) -> io::Result<DocOutcome> {
+        for format in DocFormat::ALL {
+            let mut args = vec!["--edition", &self.edition, "--document-private-items"];
+            args.extend(format.args());
+            args.push("-");
+            let outcome = self
+                .driver
+                .run_program(&self.path, args, source.as_bytes())?;
+            let input = source.to_owned();
+            match outcome {
+                ExecOutcome::Success(_) | ExecOutcome::Error { .. } => {}
+                ExecOutcome::Ice(ref output) if in_rustdoc(&output.stderr) => {
+                    return Ok(DocOutcome::RustdocIce {
+                        format,
+                        input,
+                        outcome,
+                    })
+                }
+                ExecOutcome::Ice(_) => {
+                    return Ok(DocOutcome::RustcIce {
+                        format,
+                        input,
+                        outcome,
+                    })
+                }
+                outcome => {
+                    return Ok(DocOutcome::Failed {
+                        format,
+                        input,
+                        outcome,
+                    })
+                }
+            }
+        }
+        Ok(DocOutcome::Clean)
+    }
+}
+
+/// Whether the ICE on `stderr` comes from rustdoc's own source rather than
+/// the compiler crates. Panics name their location; `bug!` and
+/// `span_bug!` panic inside `rustc_middle` or `rustc_errors` but name
+/// their caller in the ICE message.
+fn in_rustdoc(stderr: &str) -> bool {
+    stderr
+        .lines()
+        .filter(|line| line.contains("panicked at") || line.contains("internal compiler error:"))
+        .any(|line| line.contains("src/librustdoc/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn ices_are_blamed_on_whoever_panicked() {
+        let librustdoc = "thread 'rustc' panicked at src/librustdoc/clean/mod.rs:1:1:";
+        let middle = "thread 'rustc' panicked at compiler/rustc_middle/src/ty/mod.rs:1:1:";
+        assert!(in_rustdoc(librustdoc));
+        assert!(!in_rustdoc(middle));
+        assert!(in_rustdoc(&format!(
+            "error: internal compiler error: src/librustdoc/html/render/mod.rs:9:1: bad\n{middle}"
+        )));
+    }
+
+    #[test]
+    fn doc_comments_reach_rustdoc() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-rustdoc-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        // Panics in its JSON backend on anything with documentation.
+        let path = dir.join("rustdoc");
+        fs::write(
+            &path,
+            "#!/bin/sh\ncase \"$*\" in *json*) ;; *) exit 0 ;; esac\n\
+             if grep -qE 'doc|/[/*]'; then\n  \
+             echo \"thread 'rustc' panicked at src/librustdoc/json/mod.rs:1:1:\" >&2\n  \
+             exit 101\nfi\n",
+        )
+        .expect("writes the rustdoc");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let rustdoc = Rustdoc {
+            path,
+            ..Rustdoc::default()
+        };
+        let source = "fn f() {}\nstruct S;\n";
+        assert_eq!(rustdoc.document(source).expect("runs"), DocOutcome::Clean);
+        let outcome = rustdoc
+            .check(source, &mut StdRng::seed_from_u64(0))
+            .expect("runs");
+        let DocOutcome::RustdocIce { format, input, .. } = &outcome else {
+            panic!("{outcome:?}");
+        };
+        assert_eq!(*format, DocFormat::Json);
+        assert_ne!(input, source);
+        assert_eq!(outcome.severity(), Some(Severity::Ice));
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,210 @@
+//! rustfmt harness.
+//!
+//! Formats each seed, checks that rustfmt neither panics nor reports an
+//! internal error, formats the output again and requires it to be
+//! unchanged, and compares the `syn` ASTs of input and output. Formatting
+//! that is not idempotent or that changes what the code means is a bug
+//! even when nothing crashes.
+
+use std::fmt;
+use std::io;
+use std::mem;
+use std::panic;
+
+use std::path::PathBuf;
+use syn::visit_mut::{self, VisitMut};
+
+use super::{ExecOutcome, Rustc};
+use crate::triage::Severity;
+
+/// Runs rustfmt on seeds under the limits of a [`Rustc`] driver.
+#[derive(Debug, Clone)]
+pub struct Rustfmt {
+    pub path: PathBuf,
+    pub edition: String,
+    /// Supplies timeout, memory and output limits.
+    pub driver: Rustc,
+}
+
+impl Default for Rustfmt {
+    fn default() -> Self {
+        Rustfmt {
+            path: PathBuf::from("rustfmt"),
+            edition: "2021".to_owned(),
+            driver: Rustc::default(),
+        }
+    }
+}
+
+/// What formatting one seed led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FormatOutcome {
+    /// Formatted, idempotent and meaning-preserving.
+    Stable,
+    /// rustfmt declined the input, which does not parse.
+    Rejected,
+    /// rustfmt panicked, crashed, hung or reported an internal error.
+    Failed(ExecOutcome),
+    /// Formatting the output again changed it.
+    NotIdempotent { first: String, second: String },
+    /// The output parses to a different AST than the input, or not at all.
+    AstChanged { output: String },
+}
+
+impl FormatOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            FormatOutcome::Failed(outcome) => Some(Severity::of_failure(outcome)),
+            FormatOutcome::AstChanged { .. } => Some(Severity::WrongCode),
+            FormatOutcome::NotIdempotent { .. } => Some(Severity::Diagnostic),
+            FormatOutcome::Stable | FormatOutcome::Rejected => None,
+        }
+    }
+}
+
+impl fmt::Display for FormatOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatOutcome::Stable => f.write_str("stable"),
+            FormatOutcome::Rejected => f.write_str("rejected"),
+            FormatOutcome::Failed(outcome) => write!(f, "rustfmt failed: {outcome}"),
+            FormatOutcome::NotIdempotent { .. } => f.write_str("formatting is not idempotent"),
+            FormatOutcome::AstChanged { .. } => f.write_str("formatting changed the AST"),
+        }
+    }
+}
+
+impl Rustfmt {
+    pub fn check(&self, source: &str) -> io::Result<FormatOutcome> {
+        let first = match self.format(source)? {
+            Ok(output) => output,
+            Err(outcome) => return Ok(outcome),
+        };
+        let second = match self.format(&first)? {
+            Ok(output) => output,
+            // Output rustfmt cannot format again is a bug in itself.
+            Err(FormatOutcome::Rejected) => return Ok(FormatOutcome::AstChanged { output: first }),
+            Err(outcome) => return Ok(outcome),
+        };
+        if first != second {
+            return Ok(FormatOutcome::NotIdempotent { first, second });
+        }
+        // syn and rustc disagree at the edges; only inputs syn accepts
+        // can be compared. Pretty-printing both sides first erases what
+        // rustfmt may legitimately change in the AST, trailing commas and
+        // the separators between `macro_rules!` arms and doubled
+        // parentheses among them.
+        if let Some(before) = printed(source) {
+            if printed(&first).as_ref() != Some(&before) {
+                return Ok(FormatOutcome::AstChanged { output: first });
+            }
+        }
+        Ok(FormatOutcome::Stable)
+    }
+
+    /// Formats `source`, or says why not.
+    fn format(&self, source: &str) -> io::Result<Result<String, FormatOutcome>> {
+        let outcome =
+            self.driver
+                .run_program(&self.path, ["--edition", &self.edition], source.as_bytes())?;
+        Ok(match outcome {
+            ExecOutcome::Success(output) if !internal_error(&output.stderr) => Ok(output.stdout),
+            ExecOutcome::Error { output, .. } if !internal_error(&output.stderr) => {
+                Err(FormatOutcome::Rejected)
+            }
+            // rustfmt exits with 101 on some lexer errors too; only a
+            // panic message makes it a real one.
+            ExecOutcome::Ice(output) if !internal_error(&output.stderr) => {
+                Err(FormatOutcome::Rejected)
+            }
+            outcome => Err(FormatOutcome::Failed(outcome)),
+        })
+    }
+}
+
+/// `source` parsed by syn and pretty-printed, if both succeed. prettyplease
+/// panics on `macro_rules!` bodies it cannot make sense of.
+fn printed(source: &str) -> Option<String> {
+    let mut file = syn::parse_file(source).ok()?;
+    CollapseParens.visit_file_mut(&mut file);
+    panic::catch_unwind(|| prettyplease::unparse(&file)).ok()
+}
+
+/// Rewrites `((e))` to `(e)`, as rustfmt does.
+struct CollapseParens;
+
+impl VisitMut for CollapseParens {
+    fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+        if let syn::Expr::Paren(outer) = expr {
+            while let syn::Expr::Paren(inner) = &mut *outer.expr {
+                *outer.expr = mem::replace(&mut *inner.expr, syn::Expr::PLACEHOLDER);
+            }
+        }
+        visit_mut::visit_expr_mut(self, expr);
+    }
+}
+
+/// A panic, or one of rustfmt's own consistency checks such as leftover
+/// trailing whitespace.
+fn internal_error(stderr: &str) -> bool {
+    stderr.contains("panicked at") || stderr.contains("error[internal]")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Checks `source` with a rustfmt that runs `script`.
+    fn check(name: &str, script: &str, source: &str) -> FormatOutcome {
+        let dir =
+            std::env::temp_dir().join(format!("rustc-fuzz-rustfmt-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        let path = dir.join("rustfmt");
+        fs::write(&path, format!("#!/bin/sh\n{script}\n")).expect("writes the rustfmt");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let rustfmt = Rustfmt {
+            path,
+            ..Rustfmt::default()
+        };
+        let outcome = rustfmt.check(source).expect("runs rustfmt");
+        fs::remove_dir_all(&dir).expect("cleans up");
+        outcome
+    }
+
+    #[test]
+    fn formatting_is_held_to_its_input() {
+        let source = "fn f() -> i32 { ((1 + 2)) }\n";
+        assert_eq!(check("cat", "cat", source), FormatOutcome::Stable);
+        let changed = check("sed", "sed 's/+/-/'", source);
+        assert!(
+            matches!(changed, FormatOutcome::AstChanged { .. }),
+            "{changed}"
+        );
+        assert_eq!(changed.severity(), Some(Severity::WrongCode));
+        let growing = check("grow", "cat; echo", source);
+        assert!(
+            matches!(growing, FormatOutcome::NotIdempotent { .. }),
+            "{growing}"
+        );
+    }
+
+    #[test]
+    fn only_panics_are_failures() {
+        let source = "fn f( {}\n";
+        let rejected = "echo 'error: expected one of `)`' >&2; exit 1";
+        assert_eq!(check("reject", rejected, source), FormatOutcome::Rejected);
+        let lexer = "echo 'error: unterminated block comment' >&2; exit 101";
+        assert_eq!(check("lexer", lexer, source), FormatOutcome::Rejected);
+        let panic = "echo \"thread 'main' panicked at src/lib.rs:1:1:\" >&2; exit 101";
+        let failed = check("panic", panic, source);
+        assert!(matches!(failed, FormatOutcome::Failed(_)), "{failed}");
+        assert!(failed.is_finding());
+    }
+}
@@ -0,0 +1,132 @@
+//! Confinement for compiled seeds.
+//!
+//! The differential harnesses run binaries built from generated and mutated
+//! programs, and a well-typed program may make any system call. Those runs
+//! go through [`Rustc::run_sandboxed`](super::Rustc::run_sandboxed), which
+//! on top of the usual limits clears the environment, caps CPU time, file
+//! size and open files, sets `PR_SET_NO_NEW_PRIVS`, and moves the program
+//! into fresh user, network, IPC and UTS namespaces: it runs as an
+//! unmapped user that owns nothing on the host, with no network but its
+//! own loopback. A fuzzer running as root drops to `nobody` first, since
+//! root's files stay root's inside the namespace. Compilers and other
+//! trusted tools run without any of this.
+
+use std::io;
+
+use super::rustc::{setrlimit, setrlimits};
+
+/// The overflow user and group ID, `nobody` on most systems.
+const NOBODY: libc::uid_t = 65534;
+
+/// Seconds of CPU time past [`Sandbox::cpu_seconds`] before the kernel
+/// sends `SIGKILL` to a program that catches or ignores `SIGXCPU`.
+const CPU_GRACE_SECONDS: u64 = 5;
+
+/// What a sandboxed program is allowed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sandbox {
+    /// Seconds of CPU time, after which the kernel sends `SIGXCPU`. A
+    /// backstop behind the wall-clock timeout for programs that escape
+    /// their process group.
+    pub cpu_seconds: u64,
+    /// Largest file the program may write, in bytes.
+    pub file_size: u64,
+    /// Highest file descriptor number plus one.
+    pub open_files: u64,
+    /// Unshare the user, network, IPC and UTS namespaces. Needs user
+    /// namespaces, which some container runtimes and hardened kernels
+    /// disable; the run then fails rather than proceed unconfined, and
+    /// this has to be turned off explicitly.
+    pub namespaces: bool,
+}
+
+impl Default for Sandbox {
+    fn default() -> Self {
+        Sandbox {
+            cpu_seconds: 10,
+            file_size: 16 << 20,
+            open_files: 64,
+            namespaces: true,
+        }
+    }
+}
+
+impl Sandbox {
+    /// Confines the calling process. Runs in the child between fork and
+    /// exec, so it only makes async-signal-safe calls, and after the memory
+    /// limit: a process in a new user namespace may not move itself into a
+    /// cgroup.
+    pub(super) fn apply(&self) -> io::Result<()> {
+        setrlimits(
+            libc::RLIMIT_CPU,
+            self.cpu_seconds,
+            self.cpu_seconds.saturating_add(CPU_GRACE_SECONDS),
+        )?;
+        setrlimit(libc::RLIMIT_FSIZE, self.file_size)?;
+        setrlimit(libc::RLIMIT_NOFILE, self.open_files)?;
+        // SAFETY: plain syscalls with constant arguments.
+        unsafe {
+            if libc::geteuid() == 0
+                && (libc::setgroups(0, std::ptr::null()) != 0
+                    || libc::setgid(NOBODY) != 0
+                    || libc::setuid(NOBODY) != 0)
+            {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if self.namespaces
+                && libc::unshare(
+                    libc::CLONE_NEWUSER
+                        | libc::CLONE_NEWNET
+                        | libc::CLONE_NEWIPC
+                        | libc::CLONE_NEWUTS,
+                ) != 0
+            {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::{ExecOutcome, Rustc};
+    use std::fs;
+    use std::iter;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn sandboxed_programs_see_nothing_of_the_host() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-sandbox-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        let program = dir.join("seed");
+        fs::write(
+            &program,
+            "#!/bin/sh\necho \"home=$HOME\"\necho \"interfaces=$(tail -n +3 /proc/net/dev | wc -l)\"\n\
+             ulimit -n\n",
+        )
+        .expect("writes the program");
+        fs::set_permissions(&program, fs::Permissions::from_mode(0o755))
+            .expect("makes it runnable");
+        let driver = Rustc {
+            sandbox: Sandbox {
+                open_files: 32,
+                ..Sandbox::default()
+            },
+            ..Rustc::new(&program)
+        };
+        let outcome = driver
+            .run_sandboxed(&program, iter::empty::<&str>(), &[])
+            .expect("runs");
+        let ExecOutcome::Success(output) = &outcome else {
+            panic!("{outcome:?}");
+        };
+        // A fresh network namespace has nothing but its own loopback.
+        assert_eq!(output.stdout, "home=\ninterfaces=1\n32\n");
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
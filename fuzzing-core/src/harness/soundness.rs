@@ -0,0 +1,180 @@
+//! Soundness oracle.
+//!
+//! Seeds come in two kinds. Those [`UnsoundGen`] wrote carry a marker
+//! naming a pattern the borrow checker, variance or the auto traits must
+//! reject: rustc accepting one is a hole in the type system, whatever
+//! the program goes on to do. Every other seed is safe code rustc should
+//! be right to accept, so it is interpreted under [`Miri`], whose
+//! undefined behavior in safe code is the same hole seen from the other
+//! side.
+//!
+//! [`UnsoundGen`]: crate::gen::unsound::UnsoundGen
+
+use std::fmt;
+use std::io;
+
+use super::{ExecOutcome, Miri, MiriOutcome, Phase, Rustc};
+use crate::gen::unsound;
+use crate::triage::Severity;
+
+/// Checks that marked seeds are rejected and unmarked ones are free of
+/// undefined behavior.
+#[derive(Debug, Clone)]
+pub struct Soundness {
+    /// Compiles marked seeds. Borrow checking is done by the end of
+    /// analysis, so that is where it stops by default.
+    pub driver: Rustc,
+    pub edition: String,
+    /// Interprets unmarked seeds.
+    pub miri: Miri,
+}
+
+impl Default for Soundness {
+    fn default() -> Self {
+        Soundness {
+            driver: Rustc {
+                phase: Phase::Analysis,
+                ..Rustc::default()
+            },
+            edition: "2021".to_owned(),
+            miri: Miri::default(),
+        }
+    }
+}
+
+/// What checking one seed led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SoundnessOutcome {
+    /// rustc rejected a marked seed, as it must.
+    Rejected { pattern: String },
+    /// rustc accepted a marked seed.
+    Accepted { pattern: String },
+    /// Compiling a marked seed timed out.
+    Timeout { pattern: String },
+    /// rustc ICEd, crashed or ran out of memory on a marked seed.
+    Failed {
+        pattern: String,
+        outcome: ExecOutcome,
+    },
+    /// What Miri made of an unmarked seed.
+    Miri(MiriOutcome),
+}
+
+impl SoundnessOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            SoundnessOutcome::Accepted { .. } => Some(Severity::Unsound),
+            SoundnessOutcome::Failed { outcome, .. } => Some(Severity::of_failure(outcome)),
+            SoundnessOutcome::Miri(outcome) => outcome.severity(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SoundnessOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SoundnessOutcome::Rejected { pattern } => write!(f, "{pattern}: rejected"),
+            SoundnessOutcome::Accepted { pattern } => {
+                write!(f, "{pattern}: accepted a program that must not compile")
+            }
+            SoundnessOutcome::Timeout { pattern } => write!(f, "{pattern}: timeout"),
+            SoundnessOutcome::Failed { pattern, outcome } => write!(f, "{pattern}: {outcome}"),
+            SoundnessOutcome::Miri(outcome) => write!(f, "{outcome}"),
+        }
+    }
+}
+
+impl Soundness {
+    pub fn check(&self, source: &str) -> io::Result<SoundnessOutcome> {
+        let Some(pattern) = unsound::marked(source) else {
+            return Ok(SoundnessOutcome::Miri(self.miri.check(source)?));
+        };
+        let pattern = pattern.to_owned();
+        let args = ["--edition", &self.edition, "-"];
+        Ok(match self.driver.run(args, source.as_bytes())? {
+            ExecOutcome::Success(_) => SoundnessOutcome::Accepted { pattern },
+            ExecOutcome::Error { .. } => SoundnessOutcome::Rejected { pattern },
+            ExecOutcome::Timeout(_) => SoundnessOutcome::Timeout { pattern },
+            outcome => SoundnessOutcome::Failed { pattern, outcome },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+
+    fn script(dir: &std::path::Path, name: &str, body: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, format!("#!/bin/sh\n{body}")).expect("writes the script");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        path
+    }
+
+    #[test]
+    fn marked_seeds_must_be_rejected_and_the_rest_go_to_miri() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-soundness-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        // Accepts seeds that mention `hole`, ICEs on `boom`, rejects the
+        // rest.
+        let rustc = script(
+            &dir,
+            "rustc",
+            "input=$(cat)\ncase $input in\n  *hole*) exit 0 ;;\n  \
+             *boom*) echo 'error: internal compiler error: boom' >&2; exit 101 ;;\nesac\n\
+             echo 'error[E0515]: cannot return reference to local variable' >&2\nexit 1\n",
+        );
+        let miri = script(&dir, "miri", "cat >/dev/null\n");
+        let soundness = Soundness {
+            driver: Rustc::new(&rustc),
+            miri: Miri {
+                path: miri,
+                sysroot: None,
+                ..Miri::default()
+            },
+            ..Soundness::default()
+        };
+        let marked = |body: &str| format!("{}return-local\n{body}", unsound::MARKER);
+
+        let rejected = soundness.check(&marked("fn main() {}")).expect("runs");
+        assert_eq!(
+            rejected,
+            SoundnessOutcome::Rejected {
+                pattern: "return-local".to_owned()
+            }
+        );
+        assert!(!rejected.is_finding());
+        let accepted = soundness.check(&marked("// hole")).expect("runs");
+        assert_eq!(accepted.severity(), Some(Severity::Unsound));
+        assert_eq!(
+            accepted.to_string(),
+            "return-local: accepted a program that must not compile"
+        );
+        let failed = soundness.check(&marked("// boom")).expect("runs");
+        assert!(
+            matches!(
+                &failed,
+                SoundnessOutcome::Failed {
+                    outcome: ExecOutcome::Ice(_),
+                    ..
+                }
+            ),
+            "{failed:?}"
+        );
+        assert_eq!(failed.severity(), Some(Severity::Ice));
+        assert_eq!(
+            soundness.check("fn main() {}").expect("runs"),
+            SoundnessOutcome::Miri(MiriOutcome::Clean)
+        );
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
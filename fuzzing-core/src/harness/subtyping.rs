@@ -0,0 +1,258 @@
+//! Variance and subtyping harness.
+//!
+//! Seeds from [`VarianceGen`] coerce between instantiations of a struct
+//! whose variance follows from its fields, and are marked with whether
+//! the coercions are all allowed. Seeds from [`HigherRankedGen`] pass
+//! closures and functions where higher-ranked bounds are required, and
+//! are marked with whether they meet them. Region errors are reported by
+//! the borrow checker, so seeds are checked to the end of analysis. rustc
+//! accepting a marked seed it must reject lets a reference outlive what
+//! it points to; rejecting one it must accept is wrong too, if less
+//! dangerously. Mutants have lost their mark, but are still checked, for
+//! ICEs in variance computation, higher-ranked inference and reporting
+//! region errors.
+//!
+//! [`VarianceGen`]: crate::gen::variance::VarianceGen
+//! [`HigherRankedGen`]: crate::gen::higher_ranked::HigherRankedGen
+
+use std::fmt;
+use std::io;
+
+use super::{ExecOutcome, Phase, Rustc};
+use crate::gen::marker::{self, Expect};
+use crate::gen::{higher_ranked, variance};
+use crate::oracle::stability;
+use crate::triage::Severity;
+
+/// Checks that marked seeds are accepted or rejected as they say.
+#[derive(Debug, Clone)]
+pub struct Subtyping {
+    /// Compiles seeds, to the end of analysis by default.
+    pub driver: Rustc,
+    pub edition: String,
+}
+
+impl Default for Subtyping {
+    fn default() -> Self {
+        Subtyping {
+            driver: Rustc {
+                phase: Phase::Analysis,
+                ..Rustc::default()
+            },
+            edition: "2021".to_owned(),
+        }
+    }
+}
+
+/// What checking one seed led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SubtypingOutcome {
+    /// rustc decided as the seed's case expects, or the seed has no case.
+    Agreed {
+        case: Option<String>,
+        accepted: bool,
+    },
+    /// rustc accepted a seed its case says it must reject.
+    Accepted {
+        case: String,
+    },
+    /// rustc rejected a seed its case says it must accept; `errors` are
+    /// its error codes.
+    Rejected {
+        case: String,
+        errors: Vec<String>,
+    },
+    Timeout,
+    /// rustc ICEd, crashed or ran out of memory.
+    Failed(ExecOutcome),
+}
+
+impl SubtypingOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            SubtypingOutcome::Accepted { .. } => Some(Severity::Unsound),
+            SubtypingOutcome::Rejected { .. } => Some(Severity::Diagnostic),
+            SubtypingOutcome::Failed(outcome) => Some(Severity::of_failure(outcome)),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for SubtypingOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubtypingOutcome::Agreed {
+                case: Some(case),
+                accepted,
+            } => write!(
+                f,
+                "{case}: {}",
+                if *accepted { "accepted" } else { "rejected" }
+            ),
+            SubtypingOutcome::Agreed { case: None, .. } => f.write_str("unmarked seed"),
+            SubtypingOutcome::Accepted { case } => {
+                write!(f, "{case}: accepted a program that must not compile")
+            }
+            SubtypingOutcome::Rejected { case, errors } => write!(
+                f,
+                "{case}: rejected a program that must compile: [{}]",
+                errors.join(", ")
+            ),
+            SubtypingOutcome::Timeout => f.write_str("timeout"),
+            SubtypingOutcome::Failed(outcome) => write!(f, "{outcome}"),
+        }
+    }
+}
+
+impl Subtyping {
+    pub fn check(&self, source: &str) -> io::Result<SubtypingOutcome> {
+        let args = ["--edition", &self.edition, "-"];
+        let outcome = self.driver.run(args, source.as_bytes())?;
+        let accepted = match outcome {
+            ExecOutcome::Success(_) => true,
+            ExecOutcome::Error { .. } => false,
+            ExecOutcome::Timeout(_) => return Ok(SubtypingOutcome::Timeout),
+            outcome => return Ok(SubtypingOutcome::Failed(outcome)),
+        };
+        let marked = marker::marked(variance::MARKER, source)
+            .or_else(|| marker::marked(higher_ranked::MARKER, source));
+        let Some((expect, case)) = marked else {
+            return Ok(SubtypingOutcome::Agreed {
+                case: None,
+                accepted,
+            });
+        };
+        let case = case.to_owned();
+        Ok(match (expect, accepted) {
+            (Expect::Reject, true) => SubtypingOutcome::Accepted { case },
+            (Expect::Accept, false) => SubtypingOutcome::Rejected {
+                case,
+                errors: stability::error_codes(&outcome.output().stderr),
+            },
+            _ => SubtypingOutcome::Agreed {
+                case: Some(case),
+                accepted,
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn marked_seeds_are_checked() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-subtyping-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        // Rejects programs with `Dangling` in them and ICEs on `Ice`.
+        let path = dir.join("rustc");
+        fs::write(
+            &path,
+            "#!/bin/sh\ncase \"$(cat)\" in\n  \
+             *Dangling*) echo 'error: lifetime may not live long enough' >&2; exit 1 ;;\n  \
+             *Ice*) echo 'error: internal compiler error: variance' >&2; exit 101 ;;\n\
+             esac\n",
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let harness = Subtyping {
+            driver: Rustc {
+                path,
+                scratch_root: dir.clone(),
+                ..Subtyping::default().driver
+            },
+            ..Subtyping::default()
+        };
+        let check = |mark: &str, item: &str| {
+            harness
+                .check(&format!("{mark}\nstruct {item};\n"))
+                .expect("runs")
+        };
+        let reject = format!("{}reject covariant", variance::MARKER);
+        let accept = format!("{}accept invariant", variance::MARKER);
+
+        assert_eq!(
+            check(&reject, "Dangling"),
+            SubtypingOutcome::Agreed {
+                case: Some("covariant".to_owned()),
+                accepted: false,
+            }
+        );
+        assert_eq!(
+            check(&accept, "Fine"),
+            SubtypingOutcome::Agreed {
+                case: Some("invariant".to_owned()),
+                accepted: true,
+            }
+        );
+        assert_eq!(
+            check("", "Fine"),
+            SubtypingOutcome::Agreed {
+                case: None,
+                accepted: true,
+            }
+        );
+        let accepted = check(&reject, "Fine");
+        assert_eq!(
+            accepted,
+            SubtypingOutcome::Accepted {
+                case: "covariant".to_owned()
+            }
+        );
+        assert_eq!(accepted.severity(), Some(Severity::Unsound));
+        let rejected = check(&accept, "Dangling");
+        assert_eq!(
+            rejected,
+            SubtypingOutcome::Rejected {
+                case: "invariant".to_owned(),
+                errors: vec!["error".to_owned()],
+            }
+        );
+        assert_eq!(rejected.severity(), Some(Severity::Diagnostic));
+        let failed = check(&accept, "Ice");
+        assert!(matches!(failed, SubtypingOutcome::Failed(_)), "{failed:?}");
+        assert_eq!(failed.severity(), Some(Severity::Ice));
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+
+    #[test]
+    fn higher_ranked_seeds_are_checked_too() {
+        let dir =
+            std::env::temp_dir().join(format!("rustc-fuzz-higher-ranked-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        let path = dir.join("rustc");
+        fs::write(
+            &path,
+            "#!/bin/sh
+cat >/dev/null
+exit 0
+",
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let harness = Subtyping {
+            driver: Rustc {
+                path,
+                scratch_root: dir.clone(),
+                ..Subtyping::default().driver
+            },
+            ..Subtyping::default()
+        };
+        let source = format!("{}reject early-bound\nfn f() {{}}\n", higher_ranked::MARKER);
+        assert_eq!(
+            harness.check(&source).expect("runs"),
+            SubtypingOutcome::Accepted {
+                case: "early-bound".to_owned()
+            }
+        );
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,281 @@
+//! `syn` parser differential.
+//!
+//! Parses each seed with `syn` and with rustc's parser and compares
+//! whether they accept it. Most proc macros parse their input with `syn`,
+//! so where it rejects code rustc accepts, a derive or attribute fails on a
+//! valid item; where it accepts code rustc rejects, macros can emit code
+//! that does not compile. When both accept the seed, the number of tokens
+//! `proc_macro2` sees is compared as well. rustc does not expose its token
+//! stream, so its side of that count comes from [`crate::lex`], which
+//! follows rustc's lexer.
+
+use std::fmt;
+use std::io;
+use std::panic;
+use std::str::FromStr;
+
+use proc_macro2::{TokenStream, TokenTree};
+
+use super::{ExecOutcome, Phase, Rustc};
+use crate::lex::{self, TokenKind};
+use crate::triage::Severity;
+
+/// Runs both parsers on seeds.
+#[derive(Debug, Clone)]
+pub struct ParserDiff {
+    /// The reference parser; stops after parsing by default.
+    pub rustc: Rustc,
+    pub edition: String,
+}
+
+impl Default for ParserDiff {
+    fn default() -> Self {
+        ParserDiff {
+            rustc: Rustc {
+                phase: Phase::Parse,
+                ..Rustc::default()
+            },
+            edition: "2021".to_owned(),
+        }
+    }
+}
+
+/// Which parser a finding is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Parser {
+    Syn,
+    Rustc,
+}
+
+impl fmt::Display for Parser {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Parser::Syn => "syn",
+            Parser::Rustc => "rustc",
+        })
+    }
+}
+
+/// What parsing one seed with both parsers led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParserOutcome {
+    /// Both accepted with the same number of tokens, or both rejected.
+    Agree,
+    /// Only `accepted_by` accepted the seed; `error` is the other parser's
+    /// first error message.
+    AcceptanceDiffers { accepted_by: Parser, error: String },
+    /// Both accepted the seed but split it into different numbers of
+    /// tokens.
+    TokenCountDiffers { syn: usize, rustc: usize },
+    /// `syn` or `proc_macro2` panicked.
+    Panicked(String),
+    /// rustc crashed, hung or ICEd.
+    Failed(ExecOutcome),
+}
+
+impl ParserOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            ParserOutcome::Agree => None,
+            ParserOutcome::AcceptanceDiffers { .. } | ParserOutcome::TokenCountDiffers { .. } => {
+                Some(Severity::Diagnostic)
+            }
+            ParserOutcome::Panicked(_) => Some(Severity::Ice),
+            ParserOutcome::Failed(outcome) => Some(Severity::of_failure(outcome)),
+        }
+    }
+}
+
+impl fmt::Display for ParserOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParserOutcome::Agree => f.write_str("parsers agree"),
+            ParserOutcome::AcceptanceDiffers { accepted_by, error } => {
+                write!(f, "only {accepted_by} accepts: {error}")
+            }
+            ParserOutcome::TokenCountDiffers { syn, rustc } => {
+                write!(f, "token counts differ: syn {syn}, rustc {rustc}")
+            }
+            ParserOutcome::Panicked(message) => write!(f, "syn panicked: {message}"),
+            ParserOutcome::Failed(outcome) => write!(f, "rustc failed: {outcome}"),
+        }
+    }
+}
+
+impl ParserDiff {
+    pub fn check(&self, source: &str) -> io::Result<ParserOutcome> {
+        let rustc = self.rustc.run(
+            ["--crate-type=lib", "--edition", &self.edition, "-"],
+            source.as_bytes(),
+        )?;
+        if !matches!(rustc, ExecOutcome::Success(_) | ExecOutcome::Error { .. }) {
+            return Ok(ParserOutcome::Failed(rustc));
+        }
+
+        let parsed = match panic::catch_unwind(|| syn::parse_file(source)) {
+            Ok(parsed) => parsed,
+            Err(payload) => return Ok(ParserOutcome::Panicked(panic_message(&*payload))),
+        };
+        Ok(match (rustc.is_success(), parsed) {
+            (true, Ok(_)) => {
+                let body = strip_shebang(source);
+                let Ok(stream) = TokenStream::from_str(body) else {
+                    // syn lexed the same text a moment ago.
+                    return Ok(ParserOutcome::Panicked(
+                        "proc_macro2 rejected source syn parsed".to_owned(),
+                    ));
+                };
+                let (syn, rustc) = (leaves(stream), lexed(body));
+                if syn == rustc {
+                    ParserOutcome::Agree
+                } else {
+                    ParserOutcome::TokenCountDiffers { syn, rustc }
+                }
+            }
+            (true, Err(error)) => ParserOutcome::AcceptanceDiffers {
+                accepted_by: Parser::Rustc,
+                error: error.to_string(),
+            },
+            (false, Ok(_)) => ParserOutcome::AcceptanceDiffers {
+                accepted_by: Parser::Syn,
+                error: rustc
+                    .output()
+                    .stderr
+                    .lines()
+                    .find(|line| line.starts_with("error"))
+                    .unwrap_or_default()
+                    .to_owned(),
+            },
+            (false, Err(_)) => ParserOutcome::Agree,
+        })
+    }
+}
+
+/// `syn::parse_file` skips a leading `#!` line that does not start an inner
+/// attribute; the token streams are counted without it.
+fn strip_shebang(source: &str) -> &str {
+    match source.strip_prefix("#!") {
+        Some(rest) if !rest.trim_start().starts_with('[') => {
+            rest.find('\n').map_or("", |end| &rest[end..])
+        }
+        _ => source,
+    }
+}
+
+/// Tokens as `proc_macro2` splits them: every punctuation character
+/// separately, and a group as its two delimiters plus its contents.
+fn leaves(stream: TokenStream) -> usize {
+    stream
+        .into_iter()
+        .map(|tree| match tree {
+            TokenTree::Group(group) => 2 + leaves(group.stream()),
+            _ => 1,
+        })
+        .sum()
+}
+
+/// Tokens as rustc's lexer splits them, counted the way [`leaves`] counts:
+/// multi-character punctuation as one per character, a lifetime as its
+/// quote and name, and a doc comment as the `#[doc = "..."]` it desugars
+/// to.
+fn lexed(source: &str) -> usize {
+    lex::tokenize(source)
+        .iter()
+        .filter(|token| !token.kind.is_trivia())
+        .map(|token| {
+            let text = token.text(source);
+            match token.kind {
+                TokenKind::Punct => text.chars().count(),
+                TokenKind::Lifetime => 2,
+                // `#`, `[`, `doc`, `=`, the literal and `]`, and `!` for an
+                // inner one.
+                TokenKind::LineComment { .. } | TokenKind::BlockComment { .. } => {
+                    if text.starts_with("//!") || text.starts_with("/*!") {
+                        7
+                    } else {
+                        6
+                    }
+                }
+                _ => 1,
+            }
+        })
+        .sum()
+}
+
+fn panic_message(payload: &(dyn std::any::Any + Send)) -> String {
+    payload
+        .downcast_ref::<&str>()
+        .map(|s| (*s).to_owned())
+        .or_else(|| payload.downcast_ref::<String>().cloned())
+        .unwrap_or_else(|| "non-string panic payload".to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn both_counts_split_tokens_alike() {
+        for source in [
+            "fn f<'a>(x: &'a mut u8) { *x >>= 1; }",
+            "//! Crate docs.\n/// Item docs.\n/** More. */\nstruct S; // not docs\n",
+            "#!/usr/bin/env run-cargo-script\nfn main() { let _ = 1..=2; }",
+            "#![allow(dead_code)]\nconst C: &str = r#\"x\"#;",
+        ] {
+            let body = strip_shebang(source);
+            let stream = TokenStream::from_str(body).expect("lexes");
+            assert_eq!(leaves(stream), lexed(body), "{source:?}");
+        }
+    }
+
+    #[test]
+    fn only_shebangs_are_stripped() {
+        assert_eq!(strip_shebang("#!/bin/run\nfn f() {}"), "\nfn f() {}");
+        assert_eq!(strip_shebang("#![no_std]"), "#![no_std]");
+        assert_eq!(strip_shebang("#! [no_std]"), "#! [no_std]");
+    }
+
+    #[test]
+    fn acceptance_is_compared() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-syn-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        // Rejects anything with a `yield`.
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            "#!/bin/sh\nif grep -q yield; then\n  \
+             echo 'error: yield syntax is experimental' >&2\n  exit 1\nfi\n",
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let diff = ParserDiff {
+            rustc: Rustc::new(&rustc),
+            ..ParserDiff::default()
+        };
+        let check = |source| diff.check(source).expect("parses");
+        assert_eq!(check("fn f() -> u8 { 1 }"), ParserOutcome::Agree);
+        assert_eq!(
+            check("fn f() { yield 1; }"),
+            ParserOutcome::AcceptanceDiffers {
+                accepted_by: Parser::Syn,
+                error: "error: yield syntax is experimental".to_owned(),
+            }
+        );
+        let ParserOutcome::AcceptanceDiffers {
+            accepted_by: Parser::Rustc,
+            ..
+        } = check("fn f() { k#raw }")
+        else {
+            panic!("syn rejects reserved prefixes");
+        };
+        assert_eq!(check("fn f( { yield"), ParserOutcome::Agree);
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,211 @@
+//! Cross-compilation target matrix.
+//!
+//! Compiles each seed for every triple in a list and tags crashes with the
+//! triple. Layout, ABI and codegen differ per target, and bugs in them are
+//! invisible to fuzzing on the host alone. Every target's standard library
+//! (or `core`, for bare-metal ones) must be installed, as with `rustup
+//! target add`; codegen stops before linking, so no cross linker is needed.
+
+use std::fmt;
+use std::io;
+
+use super::{ExecOutcome, Rustc};
+use crate::triage::Severity;
+
+/// A target triple to compile for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Target {
+    pub triple: String,
+    /// Whether the target has `std`. Seeds are compiled as `#![no_std]`
+    /// for targets without it.
+    pub std: bool,
+}
+
+impl Target {
+    /// Bare-metal triples, with `none` for the OS, are taken to have no
+    /// `std`.
+    pub fn new(triple: impl Into<String>) -> Self {
+        let triple = triple.into();
+        let std = !triple.split('-').any(|part| part == "none");
+        Target { triple, std }
+    }
+}
+
+impl fmt::Display for Target {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.triple)
+    }
+}
+
+/// Compiles seeds for several targets.
+#[derive(Debug, Clone)]
+pub struct TargetMatrix {
+    pub driver: Rustc,
+    pub targets: Vec<Target>,
+    pub edition: String,
+}
+
+impl Default for TargetMatrix {
+    fn default() -> Self {
+        TargetMatrix {
+            driver: Rustc::default(),
+            targets: [
+                "x86_64-unknown-linux-gnu",
+                "aarch64-unknown-none",
+                "wasm32-wasip1",
+                "thumbv7em-none-eabi",
+            ]
+            .map(Target::new)
+            .to_vec(),
+            edition: "2021".to_owned(),
+        }
+    }
+}
+
+/// What compiling one seed for every target led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TargetOutcome {
+    /// No target crashed rustc.
+    Clean,
+    /// rustc crashed compiling for `target`, the first in the matrix that
+    /// did.
+    Crash {
+        target: Target,
+        outcome: ExecOutcome,
+    },
+}
+
+impl TargetOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            TargetOutcome::Clean => None,
+            TargetOutcome::Crash { outcome, .. } => Some(Severity::of_failure(outcome)),
+        }
+    }
+}
+
+impl fmt::Display for TargetOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TargetOutcome::Clean => f.write_str("clean"),
+            TargetOutcome::Crash { target, outcome } => write!(f, "{outcome} on {target}"),
+        }
+    }
+}
+
+impl TargetMatrix {
+    /// Fails with an I/O error when a target is not installed, rather than
+    /// reporting every seed as rejected for it.
+    pub fn check(&self, source: &str) -> io::Result<TargetOutcome> {
+        for target in &self.targets {
+            let outcome = self.compile(target, source)?;
+            if outcome.is_crash() {
+                return Ok(TargetOutcome::Crash {
+                    target: target.clone(),
+                    outcome,
+                });
+            }
+            if let ExecOutcome::Error { output, .. } = &outcome {
+                if not_installed(&output.stderr) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("the {target} target is not installed"),
+                    ));
+                }
+            }
+        }
+        Ok(TargetOutcome::Clean)
+    }
+
+    fn compile(&self, target: &Target, source: &str) -> io::Result<ExecOutcome> {
+        let source = if target.std {
+            source.to_owned()
+        } else {
+            // Inner attributes may come in any order, so this can go
+            // before the seed's own.
+            format!("#![no_std]\n{source}")
+        };
+        self.driver.run(
+            [
+                "--crate-type=lib",
+                "--edition",
+                &self.edition,
+                "--target",
+                &target.triple,
+                "-",
+            ],
+            source.as_bytes(),
+        )
+    }
+}
+
+/// Whether rustc failed for want of the target's standard library.
+pub(crate) fn not_installed(stderr: &str) -> bool {
+    stderr.contains("target may not be installed")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn bare_metal_targets_have_no_std() {
+        assert!(Target::new("x86_64-unknown-linux-gnu").std);
+        assert!(Target::new("wasm32-wasip1").std);
+        assert!(!Target::new("aarch64-unknown-none").std);
+        assert!(!Target::new("thumbv7em-none-eabi").std);
+    }
+
+    /// Checks a seed with a rustc that runs `script`.
+    fn check(name: &str, script: &str) -> io::Result<TargetOutcome> {
+        let dir =
+            std::env::temp_dir().join(format!("rustc-fuzz-targets-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        let rustc = dir.join("rustc");
+        fs::write(&rustc, format!("#!/bin/sh\n{script}\n")).expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let matrix = TargetMatrix {
+            driver: Rustc::new(&rustc),
+            ..TargetMatrix::default()
+        };
+        let outcome = matrix.check("fn f() {}");
+        fs::remove_dir_all(&dir).expect("cleans up");
+        outcome
+    }
+
+    #[test]
+    fn crashes_name_their_target() {
+        // ICEs on `no_std` seeds only.
+        let script = "if grep -q no_std; then\n  \
+                      echo 'error: internal compiler error: layout' >&2\n  exit 101\nfi";
+        let outcome = check("crash", script).expect("compiles");
+        let TargetOutcome::Crash { target, .. } = &outcome else {
+            panic!("{outcome:?}");
+        };
+        assert_eq!(target.triple, "aarch64-unknown-none");
+        assert!(outcome.to_string().ends_with(" on aarch64-unknown-none"));
+        assert_eq!(
+            check("clean", "cat >/dev/null").expect("compiles"),
+            TargetOutcome::Clean
+        );
+    }
+
+    #[test]
+    fn missing_targets_are_an_error() {
+        let script = "echo \"error[E0463]: can't find crate for \\`core\\`\" >&2\n\
+                      echo '  = note: the target may not be installed' >&2\nexit 1";
+        let error = check("missing", script).expect_err("not installed");
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert!(
+            error.to_string().contains("x86_64-unknown-linux-gnu"),
+            "{error}"
+        );
+    }
+}
@@ -0,0 +1,201 @@
+//! Parallel frontend stress harness.
+//!
+//! Compiles each seed several times at each of a few `-Zthreads` values
+//! and compares every run with a single-threaded one. The parallel
+//! frontend is meant to be invisible: an ICE or hang only some runs hit,
+//! or errors reported in a different order, points at a data race, a lock
+//! ordering bug or a query cycle that depends on scheduling.
+
+use std::fmt;
+use std::io;
+
+use super::{ExecOutcome, Phase, Rustc};
+use crate::oracle::stability::{self, Divergence};
+use crate::triage::Severity;
+
+/// Runs seeds under the parallel frontend.
+#[derive(Debug, Clone)]
+pub struct ParallelStress {
+    /// The parallel frontend ends where codegen starts, so the driver
+    /// stops after analysis by default. Its timeout is what counts as a
+    /// deadlock.
+    pub rustc: Rustc,
+    /// Thread counts to try besides the single-threaded reference.
+    pub threads: Vec<usize>,
+    /// Runs per thread count; scheduling bugs rarely show on every run.
+    pub repeats: usize,
+    pub edition: String,
+}
+
+impl Default for ParallelStress {
+    fn default() -> Self {
+        ParallelStress {
+            rustc: Rustc {
+                phase: Phase::Analysis,
+                ..Rustc::default()
+            },
+            threads: vec![2, 4, 8],
+            repeats: 3,
+            edition: "2021".to_owned(),
+        }
+    }
+}
+
+/// What compiling one seed at every thread count led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ThreadsOutcome {
+    /// Every run matched the single-threaded one.
+    Deterministic,
+    /// The single-threaded run timed out, so there is nothing to compare.
+    Timeout,
+    /// rustc crashed with `threads` threads. One means it crashes without
+    /// the parallel frontend too.
+    Ice {
+        threads: usize,
+        outcome: ExecOutcome,
+    },
+    /// A run with `threads` threads hit the timeout the single-threaded
+    /// run did not.
+    Deadlock { threads: usize },
+    /// A run with `threads` threads reported different errors, or the same
+    /// errors in a different order.
+    DiagnosticsDiffer {
+        threads: usize,
+        divergence: Divergence,
+    },
+}
+
+impl ThreadsOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            ThreadsOutcome::Deterministic | ThreadsOutcome::Timeout => None,
+            ThreadsOutcome::Ice { outcome, .. } => Some(Severity::of_failure(outcome)),
+            ThreadsOutcome::Deadlock { .. } => Some(Severity::Hang),
+            ThreadsOutcome::DiagnosticsDiffer { .. } => Some(Severity::Diagnostic),
+        }
+    }
+}
+
+impl fmt::Display for ThreadsOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThreadsOutcome::Deterministic => f.write_str("deterministic"),
+            ThreadsOutcome::Timeout => f.write_str("timeout with one thread"),
+            ThreadsOutcome::Ice { threads, outcome } => {
+                write!(f, "{outcome} with -Zthreads={threads}")
+            }
+            ThreadsOutcome::Deadlock { threads } => {
+                write!(f, "deadlock with -Zthreads={threads}")
+            }
+            ThreadsOutcome::DiagnosticsDiffer {
+                threads,
+                divergence,
+            } => write!(f, "-Zthreads={threads}: {divergence}"),
+        }
+    }
+}
+
+impl ParallelStress {
+    pub fn check(&self, source: &str) -> io::Result<ThreadsOutcome> {
+        let reference = self.compile(source, 1)?;
+        match reference {
+            ExecOutcome::Success(_) | ExecOutcome::Error { .. } => {}
+            ExecOutcome::Timeout(_) | ExecOutcome::OutOfMemory(_) => {
+                return Ok(ThreadsOutcome::Timeout)
+            }
+            outcome => {
+                return Ok(ThreadsOutcome::Ice {
+                    threads: 1,
+                    outcome,
+                })
+            }
+        }
+        let expected = stability::error_codes(&reference.output().stderr);
+        for &threads in &self.threads {
+            for _ in 0..self.repeats {
+                let outcome = self.compile(source, threads)?;
+                match outcome {
+                    ExecOutcome::Success(_) | ExecOutcome::Error { .. } => {}
+                    ExecOutcome::Timeout(_) => return Ok(ThreadsOutcome::Deadlock { threads }),
+                    outcome => return Ok(ThreadsOutcome::Ice { threads, outcome }),
+                }
+                let errors = stability::error_codes(&outcome.output().stderr);
+                if let Err(divergence) = stability::compare(expected.clone(), errors) {
+                    return Ok(ThreadsOutcome::DiagnosticsDiffer {
+                        threads,
+                        divergence,
+                    });
+                }
+            }
+        }
+        Ok(ThreadsOutcome::Deterministic)
+    }
+
+    fn compile(&self, source: &str, threads: usize) -> io::Result<ExecOutcome> {
+        self.rustc.run(
+            [
+                "--crate-type=lib",
+                "--edition",
+                &self.edition,
+                &format!("-Zthreads={threads}"),
+                "-",
+            ],
+            source.as_bytes(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Duration;
+
+    #[test]
+    fn scheduling_dependent_runs_are_findings() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-threads-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        // Reports two errors, in the other order with eight threads, and
+        // never finishes with four.
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            "#!/bin/sh\ncat >/dev/null\ncase \"$*\" in\n  \
+             *-Zthreads=4*) exec sleep 10 ;;\n  \
+             *-Zthreads=8*) first=E0599 second=E0308 ;;\n  \
+             *) first=E0308 second=E0599 ;;\nesac\n\
+             echo \"error[$first]: one\" >&2\necho \"error[$second]: two\" >&2\nexit 1\n",
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let stress = |threads: Vec<usize>| ParallelStress {
+            rustc: Rustc {
+                timeout: Duration::from_millis(500),
+                ..Rustc::new(&rustc)
+            },
+            threads,
+            repeats: 2,
+            ..ParallelStress::default()
+        };
+        let check = |threads| stress(threads).check("fn f() {}").expect("compiles");
+        assert_eq!(check(vec![2]), ThreadsOutcome::Deterministic);
+        let reordered = check(vec![2, 8]);
+        assert!(
+            matches!(
+                reordered,
+                ThreadsOutcome::DiagnosticsDiffer { threads: 8, .. }
+            ),
+            "{reordered}"
+        );
+        let deadlock = check(vec![2, 4, 8]);
+        assert_eq!(deadlock, ThreadsOutcome::Deadlock { threads: 4 });
+        assert_eq!(deadlock.severity(), Some(Severity::Hang));
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
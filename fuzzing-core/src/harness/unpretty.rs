@@ -0,0 +1,273 @@
+//! Pretty-printer round-trip oracle.
+//!
+//! Pretty-prints each seed with `-Zunpretty=normal` (the AST as parsed)
+//! and `-Zunpretty=expanded` (after macro expansion), compiles the printed
+//! code, and compares acceptance and error codes with the original. The
+//! printer is meant to be faithful; where it drops parentheses,
+//! attributes or hygiene the printed program means something else, and
+//! the compiler says so.
+
+use std::fmt;
+use std::io;
+
+use super::{ExecOutcome, Phase, Rustc};
+use crate::oracle::stability::{self, Divergence};
+use crate::triage::Severity;
+
+/// Expanded code calls the unstable internals that standard macros are
+/// allowed to use (`format_args!` plumbing, `StructuralPartialEq`), which
+/// the printed crate is not.
+const UNSTABLE_INTERNALS: &str = "E0658";
+
+/// What `-Zunpretty` prints.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Unpretty {
+    Normal,
+    Expanded,
+}
+
+impl Unpretty {
+    pub const ALL: [Unpretty; 2] = [Unpretty::Normal, Unpretty::Expanded];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Unpretty::Normal => "normal",
+            Unpretty::Expanded => "expanded",
+        }
+    }
+}
+
+impl fmt::Display for Unpretty {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Round-trips seeds through rustc's pretty-printer.
+#[derive(Debug, Clone)]
+pub struct RoundTrip {
+    /// Compiles the original and the printed code; stops after analysis
+    /// by default, since the printer only affects the frontend.
+    pub rustc: Rustc,
+    pub edition: String,
+}
+
+impl Default for RoundTrip {
+    fn default() -> Self {
+        RoundTrip {
+            rustc: Rustc {
+                phase: Phase::Analysis,
+                ..Rustc::default()
+            },
+            edition: "2021".to_owned(),
+        }
+    }
+}
+
+/// What round-tripping one seed led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RoundTripOutcome {
+    /// The printed code compiled the way the original did, in every mode
+    /// rustc could print.
+    Faithful,
+    /// Printing, or compiling the printed code, crashed or ICEd.
+    Failed {
+        mode: Unpretty,
+        outcome: ExecOutcome,
+    },
+    /// The printed code is accepted where the original was not, or the
+    /// other way round.
+    AcceptanceChanged {
+        mode: Unpretty,
+        accepted: bool,
+        printed: String,
+    },
+    /// Both were rejected, with different errors.
+    DiagnosticsChanged {
+        mode: Unpretty,
+        divergence: Divergence,
+        printed: String,
+    },
+}
+
+impl RoundTripOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            RoundTripOutcome::Faithful => None,
+            RoundTripOutcome::Failed { outcome, .. } => Some(Severity::of_failure(outcome)),
+            RoundTripOutcome::AcceptanceChanged { .. }
+            | RoundTripOutcome::DiagnosticsChanged { .. } => Some(Severity::Diagnostic),
+        }
+    }
+}
+
+impl fmt::Display for RoundTripOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RoundTripOutcome::Faithful => f.write_str("faithful"),
+            RoundTripOutcome::Failed { mode, outcome } => {
+                write!(f, "-Zunpretty={mode} round trip: {outcome}")
+            }
+            RoundTripOutcome::AcceptanceChanged {
+                mode,
+                accepted: true,
+                ..
+            } => write!(
+                f,
+                "-Zunpretty={mode} output is accepted, the original is not"
+            ),
+            RoundTripOutcome::AcceptanceChanged { mode, .. } => {
+                write!(
+                    f,
+                    "-Zunpretty={mode} output is rejected, the original is not"
+                )
+            }
+            RoundTripOutcome::DiagnosticsChanged {
+                mode, divergence, ..
+            } => write!(f, "-Zunpretty={mode} round trip: {divergence}"),
+        }
+    }
+}
+
+impl RoundTrip {
+    pub fn check(&self, source: &str) -> io::Result<RoundTripOutcome> {
+        let original = self.compile(source)?;
+        if !matches!(
+            original,
+            ExecOutcome::Success(_) | ExecOutcome::Error { .. }
+        ) {
+            // A plain rustc finding, not the printer's.
+            return Ok(RoundTripOutcome::Faithful);
+        }
+        let before = stability::error_codes(&original.output().stderr);
+        for mode in Unpretty::ALL {
+            let printed = self.rustc.run_program(
+                &self.rustc.path,
+                [
+                    &format!("-Zunpretty={mode}"),
+                    "--crate-type=lib",
+                    "--edition",
+                    &self.edition,
+                    "-",
+                ],
+                source.as_bytes(),
+            )?;
+            let mut printed = match printed {
+                ExecOutcome::Success(output) => output.stdout,
+                // Nothing to print, such as after a parse error.
+                ExecOutcome::Error { .. } => continue,
+                outcome => return Ok(RoundTripOutcome::Failed { mode, outcome }),
+            };
+            if mode == Unpretty::Expanded {
+                // `$crate` in `format!` and `vec!` is printed as `::alloc`,
+                // which only the standard library's own macros can name.
+                printed.push_str("extern crate alloc;\n");
+            }
+            let recompiled = self.compile(&printed)?;
+            if !matches!(
+                recompiled,
+                ExecOutcome::Success(_) | ExecOutcome::Error { .. }
+            ) {
+                return Ok(RoundTripOutcome::Failed {
+                    mode,
+                    outcome: recompiled,
+                });
+            }
+            let mut after = stability::error_codes(&recompiled.output().stderr);
+            if mode == Unpretty::Expanded {
+                after.retain(|code| code != UNSTABLE_INTERNALS);
+            }
+            if before.is_empty() != after.is_empty() {
+                return Ok(RoundTripOutcome::AcceptanceChanged {
+                    mode,
+                    accepted: after.is_empty(),
+                    printed,
+                });
+            }
+            if let Err(divergence) = stability::compare(before.clone(), after) {
+                return Ok(RoundTripOutcome::DiagnosticsChanged {
+                    mode,
+                    divergence,
+                    printed,
+                });
+            }
+        }
+        Ok(RoundTripOutcome::Faithful)
+    }
+
+    fn compile(&self, source: &str) -> io::Result<ExecOutcome> {
+        self.rustc.run(
+            ["--crate-type=lib", "--edition", &self.edition, "-"],
+            source.as_bytes(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Round-trips `source` through a rustc that prints expanded code
+    /// with `expand` and rejects code that matches `rejects` with `code`.
+    fn check(name: &str, expand: &str, rejects: (&str, &str), source: &str) -> RoundTripOutcome {
+        let (rejects, code) = rejects;
+        let dir =
+            std::env::temp_dir().join(format!("rustc-fuzz-unpretty-{name}-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            format!(
+                "#!/bin/sh\ncase \"$1\" in\n  \
+                 -Zunpretty=normal) exec cat ;;\n  \
+                 -Zunpretty=expanded) exec {expand} ;;\nesac\n\
+                 if grep -q '{rejects}'; then\n  \
+                 echo 'error[{code}]: rejected' >&2\n  exit 1\nfi\n"
+            ),
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let round_trip = RoundTrip {
+            rustc: Rustc::new(&rustc),
+            ..RoundTrip::default()
+        };
+        let outcome = round_trip.check(source).expect("round-trips");
+        fs::remove_dir_all(&dir).expect("cleans up");
+        outcome
+    }
+
+    #[test]
+    fn unstable_internals_in_expanded_code_are_forgiven() {
+        let source = "fn f() -> String { format!(\"{}\", 1) }\n";
+        let expand = "sed 's/format!/::alloc::fmt::format/'";
+        assert_eq!(
+            check("internals", expand, ("::alloc", "E0658"), source),
+            RoundTripOutcome::Faithful
+        );
+    }
+
+    #[test]
+    fn printers_that_change_meaning_are_caught() {
+        let source = "fn f() -> i32 { (1 + 2) * 3 }\n";
+        let expand = "sed 's/(1 + 2) \\* 3/1 + 2 * 3/'";
+        let outcome = check("parens", expand, ("{ 1 + 2", "E0308"), source);
+        let RoundTripOutcome::AcceptanceChanged {
+            mode,
+            accepted,
+            printed,
+        } = &outcome
+        else {
+            panic!("{outcome:?}");
+        };
+        assert_eq!((*mode, *accepted), (Unpretty::Expanded, false));
+        assert!(printed.ends_with("extern crate alloc;\n"), "{printed}");
+        assert_eq!(outcome.severity(), Some(Severity::Diagnostic));
+    }
+}
@@ -0,0 +1,287 @@
+//! WebAssembly differential: `wasm32-wasip1` under wasmtime against the
+//! host.
+//!
+//! Builds each executable seed for the host and for `wasm32-wasip1`, runs
+//! the host binary directly and the module in wasmtime, and compares what
+//! they printed. Besides being a cross-target oracle, this is the only
+//! harness that exercises the WASM codegen path end to end. Needs the
+//! `wasm32-wasip1` target (`rustup target add`) and `wasmtime` on `PATH`.
+
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use super::behavior::{build, build_and_run, Behavior};
+use super::rustc::Scratch;
+use super::targets::not_installed;
+use super::{Captured, ExecOutcome, MemoryLimit, Rustc};
+use crate::lex::{self, TokenKind};
+use crate::triage::Severity;
+
+const TARGET: &str = "wasm32-wasip1";
+
+/// Which side of the comparison a finding is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Platform {
+    Native,
+    Wasm,
+}
+
+impl fmt::Display for Platform {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Platform::Native => "native",
+            Platform::Wasm => TARGET,
+        })
+    }
+}
+
+/// Builds and runs seeds natively and as WASI modules.
+#[derive(Debug, Clone)]
+pub struct WasmDiff {
+    /// Compiles the seeds and runs both programs. Its phase is ignored.
+    pub driver: Rustc,
+    pub wasmtime: PathBuf,
+    /// Codegen flags shared by both builds.
+    pub codegen: Vec<String>,
+    pub edition: String,
+    /// Rewrites `usize` and `isize` to `u64` and `i64` in both builds.
+    /// Pointer-sized integers are 32 bits wide on `wasm32`, so without this
+    /// every seed that computes with them near their limits diverges.
+    pub widen: bool,
+}
+
+impl Default for WasmDiff {
+    fn default() -> Self {
+        WasmDiff {
+            driver: Rustc::default(),
+            wasmtime: PathBuf::from("wasmtime"),
+            codegen: vec!["-Copt-level=0".to_owned()],
+            edition: "2021".to_owned(),
+            widen: true,
+        }
+    }
+}
+
+/// What building and running one seed on both platforms led to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WasmOutcome {
+    /// Both programs behaved the same.
+    Agree,
+    /// The seed does not compile for the host.
+    Rejected,
+    /// The seed compiles for the host but not for `wasm32-wasip1`, which
+    /// lacks threads, processes and most of the file system.
+    Unsupported(Captured),
+    /// A run timed out, so there is nothing reliable to compare.
+    Timeout { platform: Platform },
+    /// Compiling for `platform` ICEd or crashed.
+    Ice {
+        platform: Platform,
+        outcome: ExecOutcome,
+    },
+    /// The two programs behaved differently.
+    Diverged { native: Behavior, wasm: Behavior },
+}
+
+impl WasmOutcome {
+    pub fn is_finding(&self) -> bool {
+        self.severity().is_some()
+    }
+
+    /// How bad the finding is, or `None` if this is not one.
+    pub fn severity(&self) -> Option<Severity> {
+        match self {
+            WasmOutcome::Ice { outcome, .. } => Some(Severity::of_failure(outcome)),
+            WasmOutcome::Diverged { .. } => Some(Severity::WrongCode),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for WasmOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WasmOutcome::Agree => f.write_str("platforms agree"),
+            WasmOutcome::Rejected => f.write_str("rejected"),
+            WasmOutcome::Unsupported(_) => write!(f, "unsupported on {TARGET}"),
+            WasmOutcome::Timeout { platform } => write!(f, "timeout ({platform})"),
+            WasmOutcome::Ice { platform, outcome } => write!(f, "{platform} build: {outcome}"),
+            WasmOutcome::Diverged { native, wasm } if native.exit != wasm.exit => write!(
+                f,
+                "platforms diverge: native {} but {TARGET} {}",
+                native.exit, wasm.exit
+            ),
+            WasmOutcome::Diverged { .. } => f.write_str("platform outputs diverge"),
+        }
+    }
+}
+
+impl WasmDiff {
+    /// Fails with an I/O error when the target or wasmtime is missing,
+    /// rather than reporting every seed as unsupported.
+    pub fn check(&self, source: &str) -> io::Result<WasmOutcome> {
+        let source = if self.widen {
+            widen(source)
+        } else {
+            source.to_owned()
+        };
+        let dir = Scratch::new(&self.driver.scratch_root)?;
+        let mut args = vec!["--edition".to_owned(), self.edition.clone()];
+        args.extend(self.codegen.iter().cloned());
+
+        let native = match build_and_run(&self.driver, &args, &source, &dir.path().join("native"))?
+        {
+            Ok(run) => run,
+            Err(ExecOutcome::Error { .. }) => return Ok(WasmOutcome::Rejected),
+            Err(outcome) => {
+                return Ok(WasmOutcome::Ice {
+                    platform: Platform::Native,
+                    outcome,
+                })
+            }
+        };
+
+        args.push(format!("--target={TARGET}"));
+        let module = dir.path().join("seed.wasm");
+        let wasm = match build(&self.driver, &args, &source, &module)? {
+            ExecOutcome::Success(_) => self.runner().run_program(
+                &self.wasmtime,
+                ["run", &module.display().to_string()],
+                &[],
+            )?,
+            ExecOutcome::Error { output, .. } if not_installed(&output.stderr) => {
+                return Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("the {TARGET} target is not installed"),
+                ))
+            }
+            ExecOutcome::Error { output, .. } => return Ok(WasmOutcome::Unsupported(output)),
+            outcome => {
+                return Ok(WasmOutcome::Ice {
+                    platform: Platform::Wasm,
+                    outcome,
+                })
+            }
+        };
+
+        for (platform, run) in [(Platform::Native, &native), (Platform::Wasm, &wasm)] {
+            if let ExecOutcome::Timeout(_) = run {
+                return Ok(WasmOutcome::Timeout { platform });
+            }
+        }
+        // Panics abort under WASI, so they fail differently from native ones.
+        let (native, wasm) = (Behavior::verdict(&native), Behavior::verdict(&wasm));
+        Ok(if native == wasm {
+            WasmOutcome::Agree
+        } else {
+            WasmOutcome::Diverged { native, wasm }
+        })
+    }
+
+    /// The driver for wasmtime. WASM runtimes reserve several GiB of
+    /// address space per linear memory up front, which an address-space
+    /// limit refuses; a cgroup limit only counts what is used.
+    fn runner(&self) -> Rustc {
+        match self.driver.memory {
+            Some(MemoryLimit::AddressSpace(_)) => Rustc {
+                memory: None,
+                ..self.driver.clone()
+            },
+            _ => self.driver.clone(),
+        }
+    }
+}
+
+/// `source` with `usize` and `isize`, as types and as literal suffixes,
+/// replaced by their 64-bit counterparts.
+fn widen(source: &str) -> String {
+    lex::tokenize(source)
+        .iter()
+        .map(|token| {
+            let text = token.text(source);
+            match token.kind {
+                TokenKind::Ident if text == "usize" => "u64".to_owned(),
+                TokenKind::Ident if text == "isize" => "i64".to_owned(),
+                TokenKind::Literal if text.starts_with(|c: char| c.is_ascii_digit()) => {
+                    match text.strip_suffix("usize") {
+                        Some(digits) => format!("{digits}u64"),
+                        None => match text.strip_suffix("isize") {
+                            Some(digits) => format!("{digits}i64"),
+                            None => text.to_owned(),
+                        },
+                    }
+                }
+                _ => text.to_owned(),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn pointer_sized_integers_are_widened_in_code_only() {
+        assert_eq!(
+            widen("let n: usize = usize::MAX - 1usize; // usize\nlet s = \"isize\"; -2isize"),
+            "let n: u64 = u64::MAX - 1u64; // usize\nlet s = \"isize\"; -2i64"
+        );
+        assert_eq!(widen("let usize_count = 0x1f;"), "let usize_count = 0x1f;");
+    }
+
+    #[test]
+    fn wasmtime_is_not_limited_by_address_space() {
+        let limited = |memory| WasmDiff {
+            driver: Rustc {
+                memory: Some(memory),
+                ..Rustc::default()
+            },
+            ..WasmDiff::default()
+        };
+        assert_eq!(
+            limited(MemoryLimit::AddressSpace(1 << 30)).runner().memory,
+            None
+        );
+        let cgroup = MemoryLimit::Cgroup {
+            path: PathBuf::from("/sys/fs/cgroup/fuzz"),
+            bytes: 1 << 30,
+        };
+        assert_eq!(limited(cgroup.clone()).runner().memory, Some(cgroup));
+    }
+
+    #[test]
+    fn platforms_are_compared() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-wasm-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        // Native binaries print 1 where the module prints 2.
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            "#!/bin/sh\ncat >/dev/null\nfor arg; do [ \"$prev\" = -o ] && out=$arg; prev=$arg; done\n\
+             printf '#!/bin/sh\\necho 1\\n' > \"$out\"\nchmod +x \"$out\"\n",
+        )
+        .expect("writes the rustc");
+        let wasmtime = dir.join("wasmtime");
+        fs::write(&wasmtime, "#!/bin/sh\necho 2\n").expect("writes the wasmtime");
+        for program in [&rustc, &wasmtime] {
+            fs::set_permissions(program, fs::Permissions::from_mode(0o755))
+                .expect("makes it runnable");
+        }
+        let diff = WasmDiff {
+            driver: Rustc::new(&rustc),
+            wasmtime,
+            ..WasmDiff::default()
+        };
+        let outcome = diff.check("fn main() {}").expect("builds and runs");
+        let WasmOutcome::Diverged { native, wasm } = &outcome else {
+            panic!("{outcome:?}");
+        };
+        assert_eq!((&*native.stdout, &*wasm.stdout), ("1\n", "2\n"));
+        assert_eq!(outcome.to_string(), "platform outputs diverge");
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,377 @@
+//! A lossless Rust lexer.
+//!
+//! Unlike `proc_macro2`, this keeps whitespace and comments as tokens, so
+//! concatenating the text of every token reproduces the input exactly. It
+//! follows rustc's maximal-munch rules closely enough for token-level
+//! mutators; it never fails, and anything it does not recognise becomes an
+//! [`TokenKind::Unknown`] token.
+
+use std::ops::Range;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    Whitespace,
+    /// `// ...`, not including the trailing newline.
+    LineComment {
+        doc: bool,
+    },
+    /// `/* ... */`, possibly nested.
+    BlockComment {
+        doc: bool,
+    },
+    Ident,
+    Lifetime,
+    Literal,
+    Punct,
+    Unknown,
+}
+
+impl TokenKind {
+    /// Whitespace and non-doc comments, which carry no meaning.
+    pub fn is_trivia(self) -> bool {
+        matches!(
+            self,
+            TokenKind::Whitespace
+                | TokenKind::LineComment { doc: false }
+                | TokenKind::BlockComment { doc: false }
+        )
+    }
+
+    pub fn is_comment(self) -> bool {
+        matches!(
+            self,
+            TokenKind::LineComment { .. } | TokenKind::BlockComment { .. }
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub kind: TokenKind,
+    /// Byte range of the token in the source.
+    pub span: Range<usize>,
+}
+
+impl Token {
+    pub fn text<'s>(&self, src: &'s str) -> &'s str {
+        &src[self.span.clone()]
+    }
+}
+
+/// Multi-character punctuation, longest first.
+pub const PUNCT: &[&str] = &[
+    "<<=", ">>=", "...", "..=", "..", "::", "->", "=>", "==", "!=", "<=", ">=", "&&", "||", "+=",
+    "-=", "*=", "/=", "%=", "^=", "&=", "|=", "<<", ">>",
+];
+
+/// Reserved words, including the ones reserved for future use.
+pub const KEYWORDS: &[&str] = &[
+    "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern",
+    "false", "fn", "for", "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "type",
+    "unsafe", "use", "where", "while", "abstract", "become", "box", "do", "final", "gen", "macro",
+    "override", "priv", "try", "typeof", "unsized", "virtual", "yield", "union",
+];
+
+pub fn is_keyword(word: &str) -> bool {
+    KEYWORDS.contains(&word)
+}
+
+/// Splits `src` into tokens covering every byte.
+pub fn tokenize(src: &str) -> Vec<Token> {
+    let mut lexer = Lexer { src, pos: 0 };
+    let mut tokens = Vec::new();
+    while lexer.pos < src.len() {
+        let start = lexer.pos;
+        let kind = lexer.next_kind();
+        tokens.push(Token {
+            kind,
+            span: start..lexer.pos,
+        });
+    }
+    tokens
+}
+
+/// Source text of the non-trivia tokens, in order.
+pub fn significant<'s>(src: &'s str, tokens: &[Token]) -> Vec<&'s str> {
+    tokens
+        .iter()
+        .filter(|t| !t.kind.is_trivia())
+        .map(|t| t.text(src))
+        .collect()
+}
+
+struct Lexer<'s> {
+    src: &'s str,
+    pos: usize,
+}
+
+impl<'s> Lexer<'s> {
+    fn rest(&self) -> &'s str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn peek_nth(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn eat_while(&mut self, f: impl Fn(char) -> bool) {
+        while self.peek().is_some_and(&f) {
+            self.bump();
+        }
+    }
+
+    fn next_kind(&mut self) -> TokenKind {
+        let rest = self.rest();
+        let c = self.peek().expect("lexer called at end of input");
+
+        if c.is_whitespace() {
+            self.eat_while(char::is_whitespace);
+            return TokenKind::Whitespace;
+        }
+        if rest.starts_with("//") {
+            let doc =
+                (rest.starts_with("///") && !rest.starts_with("////")) || rest.starts_with("//!");
+            self.eat_while(|c| c != '\n');
+            return TokenKind::LineComment { doc };
+        }
+        if rest.starts_with("/*") {
+            let doc =
+                (rest.starts_with("/**") && !rest.starts_with("/**/") && !rest.starts_with("/***"))
+                    || rest.starts_with("/*!");
+            self.block_comment();
+            return TokenKind::BlockComment { doc };
+        }
+        if let Some(kind) = self.prefixed_literal() {
+            return kind;
+        }
+        if rest.starts_with("r#") && self.peek_nth(2).is_some_and(is_ident_start) {
+            self.pos += 2;
+            self.eat_while(is_ident_continue);
+            return TokenKind::Ident;
+        }
+        if is_ident_start(c) {
+            self.eat_while(is_ident_continue);
+            return TokenKind::Ident;
+        }
+        if c.is_ascii_digit() {
+            self.number();
+            return TokenKind::Literal;
+        }
+        match c {
+            '"' => {
+                self.bump();
+                self.quoted('"');
+                self.suffix();
+                TokenKind::Literal
+            }
+            '\'' => self.quote(),
+            _ => {
+                if let Some(p) = PUNCT.iter().find(|p| rest.starts_with(**p)) {
+                    self.pos += p.len();
+                    return TokenKind::Punct;
+                }
+                self.bump();
+                if c.is_ascii_punctuation() {
+                    TokenKind::Punct
+                } else {
+                    TokenKind::Unknown
+                }
+            }
+        }
+    }
+
+    fn block_comment(&mut self) {
+        self.pos += 2;
+        let mut depth = 1;
+        while depth > 0 {
+            let rest = self.rest();
+            if rest.is_empty() {
+                return;
+            }
+            if rest.starts_with("/*") {
+                depth += 1;
+                self.pos += 2;
+            } else if rest.starts_with("*/") {
+                depth -= 1;
+                self.pos += 2;
+            } else {
+                self.bump();
+            }
+        }
+    }
+
+    /// `b"..."`, `b'.'`, `c"..."` and the raw forms.
+    fn prefixed_literal(&mut self) -> Option<TokenKind> {
+        let rest = self.rest();
+        let (prefix, raw) = ["br", "cr", "r", "b", "c"]
+            .iter()
+            .find(|p| rest.starts_with(**p))
+            .map(|p| (p.len(), p.ends_with('r')))?;
+        let after = &rest[prefix..];
+        if raw {
+            let hashes = after.bytes().take_while(|&b| b == b'#').count();
+            if after[hashes..].starts_with('"') {
+                self.pos += prefix + hashes + 1;
+                let close = format!("\"{}", "#".repeat(hashes));
+                match self.rest().find(&close) {
+                    Some(i) => self.pos += i + close.len(),
+                    None => self.pos = self.src.len(),
+                }
+                self.suffix();
+                return Some(TokenKind::Literal);
+            }
+            return None;
+        }
+        if after.starts_with('"') {
+            self.pos += prefix + 1;
+            self.quoted('"');
+            self.suffix();
+            return Some(TokenKind::Literal);
+        }
+        if rest.starts_with("b'") {
+            self.pos += 2;
+            self.quoted('\'');
+            return Some(TokenKind::Literal);
+        }
+        None
+    }
+
+    /// Consumes up to and including the closing `close`, honouring escapes.
+    fn quoted(&mut self, close: char) {
+        while let Some(c) = self.bump() {
+            if c == '\\' {
+                self.bump();
+            } else if c == close {
+                return;
+            }
+        }
+    }
+
+    /// A character literal or a lifetime, both starting with `'`.
+    fn quote(&mut self) -> TokenKind {
+        self.bump();
+        let is_char = matches!(
+            (self.peek(), self.peek_nth(1)),
+            (Some('\\'), _) | (Some(_), Some('\''))
+        );
+        if is_char {
+            self.quoted('\'');
+            self.suffix();
+            return TokenKind::Literal;
+        }
+        if self.peek().is_some_and(is_ident_start) {
+            self.eat_while(is_ident_continue);
+            return TokenKind::Lifetime;
+        }
+        TokenKind::Unknown
+    }
+
+    fn number(&mut self) {
+        let rest = self.rest();
+        if rest.starts_with("0x") || rest.starts_with("0o") || rest.starts_with("0b") {
+            self.pos += 2;
+            self.eat_while(|c| c.is_ascii_hexdigit() || c == '_');
+            self.suffix();
+            return;
+        }
+        self.eat_while(|c| c.is_ascii_digit() || c == '_');
+        // `1.0` and `1.` are floats, but `1..2` and `1.foo()` are not.
+        if self.peek() == Some('.')
+            && self.peek_nth(1) != Some('.')
+            && !self.peek_nth(1).is_some_and(is_ident_start)
+        {
+            self.bump();
+            self.eat_while(|c| c.is_ascii_digit() || c == '_');
+        }
+        if matches!(self.peek(), Some('e' | 'E'))
+            && (self.peek_nth(1).is_some_and(|c| c.is_ascii_digit())
+                || (matches!(self.peek_nth(1), Some('+' | '-'))
+                    && self.peek_nth(2).is_some_and(|c| c.is_ascii_digit())))
+        {
+            self.bump();
+            self.bump();
+            self.eat_while(|c| c.is_ascii_digit() || c == '_');
+        }
+        self.suffix();
+    }
+
+    fn suffix(&mut self) {
+        if self.peek().is_some_and(is_ident_start) {
+            self.eat_while(is_ident_continue);
+        }
+    }
+}
+
+pub fn is_ident_start(c: char) -> bool {
+    c == '_' || c.is_alphabetic()
+}
+
+pub fn is_ident_continue(c: char) -> bool {
+    c == '_' || c.is_alphanumeric()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn kinds(src: &str) -> Vec<(TokenKind, &str)> {
+        tokenize(src)
+            .iter()
+            .map(|token| (token.kind, token.text(src)))
+            .collect()
+    }
+
+    #[test]
+    fn tokens_cover_the_input() {
+        let src = "fn f<'a>(x: &'a str) -> char { /* a /* nested */ comment */\n\
+                   let s = r#\"raw \" string\"#; // done\n    'x' }\n\u{1F980}";
+        let text: String = tokenize(src).iter().map(|token| token.text(src)).collect();
+        assert_eq!(text, src);
+    }
+
+    #[test]
+    fn tells_lifetimes_from_characters() {
+        assert_eq!(
+            kinds("'a 'b' '\\n'"),
+            [
+                (TokenKind::Lifetime, "'a"),
+                (TokenKind::Whitespace, " "),
+                (TokenKind::Literal, "'b'"),
+                (TokenKind::Whitespace, " "),
+                (TokenKind::Literal, "'\\n'"),
+            ]
+        );
+    }
+
+    #[test]
+    fn munches_the_longest_punctuation() {
+        let src = "a..=b<<=c::d";
+        assert_eq!(
+            significant(src, &tokenize(src)),
+            ["a", "..=", "b", "<<=", "c", "::", "d"]
+        );
+    }
+
+    #[test]
+    fn comments_nest_and_doc_comments_are_not_trivia() {
+        let tokens = kinds("/* a /* b */ c */ /// doc\n");
+        assert_eq!(
+            tokens[0],
+            (TokenKind::BlockComment { doc: false }, "/* a /* b */ c */")
+        );
+        assert_eq!(tokens[2], (TokenKind::LineComment { doc: true }, "/// doc"));
+        assert!(tokens[0].0.is_trivia());
+        assert!(!tokens[2].0.is_trivia());
+    }
+}
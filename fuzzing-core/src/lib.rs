@@ -0,0 +1,31 @@
+//! Structured fuzzing for the Rust compiler: the reusable parts.
+//!
+//! Seeds are plain Rust source files. Generators in [`gen`] write new ones,
+//! and mutators in [`mutate`] rewrite them at the AST or token level so
+//! that most generated inputs get past the parser and exercise the later
+//! stages of the compiler. Oracles in [`oracle`] decide which compiler
+//! behaviours count as findings, based on the [`harness::ExecOutcome`] of
+//! running rustc through [`harness::Rustc`]; [`corpus`] maintains seed
+//! collections and [`triage`] sorts, reduces and reports findings.
+//!
+//! Nothing here runs a campaign. The `rustc-fuzz` crate builds its command
+//! line, scheduling, checkpoints and results database on top of this one,
+//! and a program that only wants, say, the program generator can depend on
+//! this crate alone.
+
+pub mod corpus;
+pub mod coverage;
+pub mod dict;
+pub mod flags;
+pub mod gen;
+pub mod harness;
+pub mod lex;
+pub mod libfuzzer;
+pub mod mutate;
+pub mod oracle;
+pub mod plugin;
+pub mod rng;
+pub mod seed;
+pub mod target;
+pub mod toolchains;
+pub mod triage;
@@ -0,0 +1,66 @@
+//! Structured mutation entry points for libFuzzer.
+//!
+//! These back `LLVMFuzzerCustomMutator` and `LLVMFuzzerCustomCrossOver` in
+//! the cargo-fuzz targets under `fuzz/`, replacing byte havoc with the
+//! [`mutate`](crate::mutate) operators. Both return `None` when no
+//! structured mutation applies, in which case the caller should fall back
+//! to libFuzzer's own mutator.
+
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::sync::OnceLock;
+
+use crate::mutate::{self, Engine};
+use crate::seed;
+
+fn engine() -> &'static Engine {
+    static ENGINE: OnceLock<Engine> = OnceLock::new();
+    ENGINE.get_or_init(Engine::default)
+}
+
+/// Mutates the first `size` bytes of `data` in place, returning the new
+/// size, which never exceeds `max_size`.
+pub fn custom_mutate(data: &mut [u8], size: usize, max_size: usize, seed: u32) -> Option<usize> {
+    let mut rng = StdRng::seed_from_u64(u64::from(seed));
+    let input = seed::decode(&data[..size]).into_owned();
+    let mutated = engine().mutate(&input, &mut rng)?;
+    let limit = max_size.min(data.len());
+    seed::encode_into(&mutated.output, &mut data[..limit])
+}
+
+/// Writes a crossover of `data1` and `data2` into `out`, returning its size.
+pub fn custom_crossover(data1: &[u8], data2: &[u8], out: &mut [u8], seed: u32) -> Option<usize> {
+    let mut rng = StdRng::seed_from_u64(u64::from(seed));
+    let child = mutate::crossover(&seed::decode(data1), &seed::decode(data2), &mut rng)?;
+    seed::encode_into(&child, out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mutants_fit_the_buffer() {
+        let seed = b"fn main() { let x: i32 = 1 + 2; }";
+        for max_size in [seed.len(), 4096] {
+            let mut data = [0; 4096];
+            data[..seed.len()].copy_from_slice(seed);
+            if let Some(size) = custom_mutate(&mut data, seed.len(), max_size, 7) {
+                assert!(size <= max_size);
+                assert!(std::str::from_utf8(&data[..size]).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn crossovers_write_into_the_output() {
+        let mut out = [0; 256];
+        let size = custom_crossover(b"fn a() {}", b"fn b() {}", &mut out, 1).expect("a child");
+        let child = std::str::from_utf8(&out[..size]).expect("utf-8");
+        assert!(syn::parse_file(child).is_ok(), "{child}");
+        assert_eq!(
+            custom_crossover(b"fn a() {}", b"fn b() {}", &mut [0; 2], 1),
+            None
+        );
+    }
+}
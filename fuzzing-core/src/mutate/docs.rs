@@ -0,0 +1,133 @@
+//! Comment and doc-attribute injection.
+//!
+//! Inserts doc comments, `#[doc = ...]` attributes and code-like block
+//! comments at arbitrary token boundaries. Doc comments are attributes, so
+//! this stresses attribute collection wherever the parser accepts (or
+//! wrongly accepts, or badly recovers from) an attribute.
+
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+
+use super::Mutator;
+use crate::lex::{self, TokenKind};
+
+/// Injects doc comments, doc attributes and block comments.
+pub struct DocInjection;
+
+/// Doc text that looks like code, markdown or intra-doc links.
+const PAYLOADS: &[&str] = &[
+    "fn main() {}",
+    "```\nlet x: u8 = 256;\n```",
+    "```rust,compile_fail\nstruct S;\n```",
+    "```ignore\n}}}\n```",
+    "[`Vec`]",
+    "[Self::new]",
+    "[crate::missing::Item]",
+    "#[derive(Debug)]",
+    "/* nested /* comment */ */",
+    "'a: loop {}",
+    "<T as Trait>::Assoc",
+    "\\u{0}",
+    "é∀🦀",
+    "\t",
+    "",
+    "# Safety",
+    "$crate::m!()",
+    "#![allow(unused)]",
+];
+
+impl Mutator for DocInjection {
+    fn name(&self) -> &'static str {
+        "doc-injection"
+    }
+
+    fn mutate(&self, input: &str, rng: &mut dyn RngCore) -> Option<String> {
+        let tokens = lex::tokenize(input);
+        if tokens.is_empty() {
+            return None;
+        }
+        // Attributes are legal after these in items, fields, statements,
+        // match arms and parameter lists; elsewhere is fair game too.
+        let plausible: Vec<usize> = (0..tokens.len())
+            .filter(|&i| {
+                tokens[i].kind == TokenKind::Punct
+                    && matches!(tokens[i].text(input), "{" | "}" | ";" | ",")
+            })
+            .collect();
+        let index = match plausible.choose(rng) {
+            Some(&i) if rng.gen_ratio(3, 4) => i,
+            _ => rng.gen_range(0..tokens.len()),
+        };
+        let payload = *PAYLOADS.choose(rng).unwrap();
+        let mut injected = match rng.gen_range(0..8) {
+            0 => format!("#[doc = {payload:?}]"),
+            1 => format!("#![doc = {payload:?}]"),
+            2 => format!("#[doc = r#\"{payload}\"#]"),
+            3 => line_comment("///", payload),
+            4 => line_comment("//!", payload),
+            5 => format!("/** {} */", block_comment_body(payload)),
+            6 => format!("/*! {} */", block_comment_body(payload)),
+            _ => format!("/* {} */", block_comment_body(payload)),
+        };
+        if matches!(tokens[index].kind, TokenKind::LineComment { .. }) {
+            injected.insert(0, '\n');
+        }
+        let at = tokens[index].span.end;
+        Some(format!("{}{}{}", &input[..at], injected, &input[at..]))
+    }
+}
+
+/// `payload` with nothing that closes a block comment or opens a nested
+/// one, which would comment out the rest of the seed.
+fn block_comment_body(payload: &str) -> String {
+    payload.replace("*/", "* /").replace("/*", "/ *")
+}
+
+/// A `///` or `//!` comment per payload line, each ending in a newline.
+fn line_comment(marker: &str, payload: &str) -> String {
+    let mut out = String::from("\n");
+    for line in payload.split('\n') {
+        out.push_str(marker);
+        out.push(' ');
+        out.push_str(line);
+        out.push('\n');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn injections_keep_the_input_around_them() {
+        let input = "fn f(a: u8, b: u8) { let x = a; } // end";
+        for seed in 0..64 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mutant = DocInjection.mutate(input, &mut rng).expect("a mutant");
+            let injected = (0..=input.len())
+                .filter(|&at| mutant.starts_with(&input[..at]) && mutant.ends_with(&input[at..]))
+                .map(|at| &mutant[at..mutant.len() - (input.len() - at)]);
+            let starts = ["#[doc", "#![doc", "///", "//!", "/*"];
+            let mut injected = injected.map(|text| text.trim_start_matches('\n'));
+            assert!(
+                injected.any(|text| starts.iter().any(|start| text.starts_with(start))),
+                "{mutant}"
+            );
+            // Nothing the input had after the insertion is commented out.
+            let tokens = lex::tokenize(&mutant);
+            let significant = lex::significant(&mutant, &tokens);
+            for word in ["fn", "f", "let", "x", "}"] {
+                assert!(significant.contains(&word), "{mutant}");
+            }
+        }
+    }
+
+    #[test]
+    fn nothing_to_inject_into() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(DocInjection.mutate("", &mut rng), None);
+    }
+}
@@ -0,0 +1,269 @@
+//! Byte-level havoc with repair.
+//!
+//! Plain byte mutations are cheap and occasionally find lexer and parser
+//! bugs the structured operators never will, but most of their output dies
+//! on invalid UTF-8 or unbalanced delimiters before reaching anything
+//! interesting. [`Havoc`] mutates bytes freely and then repairs the result:
+//! invalid UTF-8 is dropped, stray closing delimiters are removed, and
+//! unclosed delimiters, strings and block comments are closed at the end.
+
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+
+use super::Mutator;
+use crate::lex::{self, TokenKind};
+
+/// Bytes and short sequences worth splicing into Rust source.
+const INTERESTING: &[&[u8]] = &[
+    b"{",
+    b"}",
+    b"(",
+    b")",
+    b"[",
+    b"]",
+    b"<",
+    b">",
+    b";",
+    b",",
+    b"::",
+    b"'",
+    b"\"",
+    b"#",
+    b"!",
+    b"&",
+    b"*",
+    b"?",
+    b"..",
+    b"=>",
+    b"->",
+    b"'a",
+    b"r#",
+    b"$",
+    b"\\",
+    b"\n",
+    b"\0",
+    b"\xc3\xa9",
+    b"\xf0\x9f\xa6\x80",
+];
+
+/// Random byte mutations followed by UTF-8 and delimiter repair.
+#[derive(Debug, Clone)]
+pub struct Havoc {
+    /// Upper bound on stacked byte mutations per call.
+    pub max_rounds: usize,
+}
+
+impl Default for Havoc {
+    fn default() -> Self {
+        Havoc { max_rounds: 8 }
+    }
+}
+
+impl Mutator for Havoc {
+    fn name(&self) -> &'static str {
+        "havoc"
+    }
+
+    fn mutate(&self, input: &str, rng: &mut dyn RngCore) -> Option<String> {
+        let mut bytes = input.as_bytes().to_vec();
+        for _ in 0..rng.gen_range(1..=self.max_rounds) {
+            mutate_bytes(&mut bytes, rng);
+        }
+        Some(repair(&bytes))
+    }
+}
+
+fn mutate_bytes(bytes: &mut Vec<u8>, rng: &mut dyn RngCore) {
+    let len = bytes.len();
+    match rng.gen_range(0..6) {
+        0 if len > 0 => {
+            let i = rng.gen_range(0..len);
+            bytes[i] ^= 1 << rng.gen_range(0..8);
+        }
+        1 if len > 0 => {
+            let i = rng.gen_range(0..len);
+            bytes[i] = rng.gen();
+        }
+        2 if len > 0 => {
+            let start = rng.gen_range(0..len);
+            let end = rng.gen_range(start..=len.min(start + 16));
+            bytes.drain(start..end);
+        }
+        3 if len > 0 => {
+            let start = rng.gen_range(0..len);
+            let end = rng.gen_range(start..=len.min(start + 32));
+            let chunk = bytes[start..end].to_vec();
+            let at = rng.gen_range(0..=len);
+            bytes.splice(at..at, chunk);
+        }
+        4 if len > 1 => {
+            let (a, b) = (rng.gen_range(0..len), rng.gen_range(0..len));
+            bytes.swap(a, b);
+        }
+        _ => {
+            let at = rng.gen_range(0..=len);
+            let insert = INTERESTING.choose(rng).unwrap();
+            bytes.splice(at..at, insert.iter().copied());
+        }
+    }
+}
+
+/// Turns arbitrary bytes into UTF-8 source with balanced delimiters.
+pub fn repair(bytes: &[u8]) -> String {
+    let mut source = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        source.push_str(chunk.valid());
+    }
+    close_trailing(&mut source);
+    balance(&source)
+}
+
+/// Closes a string literal or block comment left open at end of input.
+fn close_trailing(source: &mut String) {
+    let tokens = lex::tokenize(source);
+    let Some(last) = tokens.last() else { return };
+    let text = last.text(source);
+    let closer = match last.kind {
+        TokenKind::BlockComment { .. } => "*/".repeat(open_comments(text)),
+        TokenKind::Literal => unterminated_string(text).unwrap_or_default(),
+        _ => return,
+    };
+    source.push_str(&closer);
+}
+
+/// How many levels of a (nested) block comment are still open.
+fn open_comments(comment: &str) -> usize {
+    let bytes = comment.as_bytes();
+    let (mut depth, mut i) = (0usize, 0);
+    while i + 1 < bytes.len() {
+        match &bytes[i..i + 2] {
+            b"/*" => {
+                depth += 1;
+                i += 2;
+            }
+            b"*/" => {
+                depth = depth.saturating_sub(1);
+                i += 2;
+            }
+            _ => i += 1,
+        }
+    }
+    depth
+}
+
+/// The text that would terminate `literal`, if it is an unterminated
+/// (raw, byte or C) string.
+fn unterminated_string(literal: &str) -> Option<String> {
+    let body = literal.trim_start_matches(['b', 'c']);
+    if let Some(raw) = body.strip_prefix('r') {
+        let hashes = raw.bytes().take_while(|&b| b == b'#').count();
+        let closer = format!("\"{}", "#".repeat(hashes));
+        let inner = &raw[hashes..];
+        return (!inner[1..].contains(closer.as_str())).then_some(closer);
+    }
+    let inner = body.strip_prefix('"')?;
+    // An escaped quote does not terminate the string.
+    let mut escaped = false;
+    for c in inner.chars() {
+        match c {
+            '\\' if !escaped => escaped = true,
+            '"' if !escaped => return None,
+            _ => escaped = false,
+        }
+    }
+    // A trailing backslash escapes the first quote we add.
+    Some(if escaped { "\"\"" } else { "\"" }.to_owned())
+}
+
+/// Drops unmatched closing delimiters and stray quotes, and appends
+/// missing closers.
+fn balance(source: &str) -> String {
+    let tokens = lex::tokenize(source);
+    let mut out = String::with_capacity(source.len());
+    let mut open = Vec::new();
+    for token in &tokens {
+        let text = token.text(source);
+        // rustc reads a lone `'` as the start of an unterminated char.
+        if token.kind == TokenKind::Unknown && text == "'" {
+            continue;
+        }
+        if token.kind == TokenKind::Punct {
+            match text {
+                "(" | "[" | "{" => open.push(text),
+                ")" | "]" | "}" => {
+                    let opener = match text {
+                        ")" => "(",
+                        "]" => "[",
+                        _ => "{",
+                    };
+                    if open.last() == Some(&opener) {
+                        open.pop();
+                    } else {
+                        continue;
+                    }
+                }
+                _ => {}
+            }
+        }
+        out.push_str(text);
+    }
+    if matches!(
+        tokens.last().map(|t| t.kind),
+        Some(TokenKind::LineComment { .. })
+    ) && !open.is_empty()
+    {
+        out.push('\n');
+    }
+    for opener in open.iter().rev() {
+        out.push_str(match *opener {
+            "(" => ")",
+            "[" => "]",
+            _ => "}",
+        });
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn balanced(source: &str) -> bool {
+        let mut open = Vec::new();
+        for token in lex::tokenize(source) {
+            if token.kind != TokenKind::Punct {
+                continue;
+            }
+            match token.text(source) {
+                "(" => open.push(")"),
+                "[" => open.push("]"),
+                "{" => open.push("}"),
+                close @ (")" | "]" | "}") if open.pop() != Some(close) => return false,
+                _ => {}
+            }
+        }
+        open.is_empty()
+    }
+
+    #[test]
+    fn repairs_delimiters_strings_and_comments() {
+        assert_eq!(repair(b"fn f() { (x]"), "fn f() { (x)}");
+        assert_eq!(repair(b"fn \xfff() {}"), "fn f() {}");
+        assert_eq!(repair(b"let s = \"a\\"), "let s = \"a\\\"\"");
+        assert_eq!(repair(b"let s = r#\"a"), "let s = r#\"a\"#");
+        assert_eq!(repair(b"x /* a /* b */"), "x /* a /* b */*/");
+        assert_eq!(repair(b"f(' // c"), "f( // c\n)");
+    }
+
+    #[test]
+    fn havoc_output_is_balanced() {
+        let input = "fn main() { let v = vec![1, 2]; println!(\"{v:?}\"); }";
+        for seed in 0..200 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mutant = Havoc::default().mutate(input, &mut rng).expect("a mutant");
+            assert!(balanced(&mutant), "{mutant}");
+        }
+    }
+}
@@ -0,0 +1,251 @@
+//! Source-level mutators.
+//!
+//! Every operator implements [`Mutator`]. The [`Engine`] owns a set of
+//! operators and picks one for each mutation through a [`Bandit`], which
+//! learns from [`Engine::reward`] which operators pay off. A configurable
+//! fraction of mutations, and every input no structured operator can
+//! handle, go to byte-level [`Havoc`] instead. Each application is
+//! recorded as a replayable [`Step`]. Two-parent recombination lives in
+//! [`crossover`]; [`FragmentSplice`] splices in fragments from a corpus
+//! [`Library`](crate::corpus::library::Library) instead.
+
+mod docs;
+mod havoc;
+mod regen;
+mod schedule;
+mod splice;
+mod stability;
+mod trace;
+mod types;
+
+pub use docs::DocInjection;
+pub use havoc::{repair, Havoc};
+pub use regen::Regenerate;
+pub use schedule::{ArmState, Bandit, Feedback, OperatorStats};
+pub use splice::{crossover, FragmentSplice};
+pub use stability::TriviaMutator;
+pub use trace::{changed_range, step_rng, ReplayError, Step, Trace};
+pub use types::TypeSubstitution;
+
+use rand::{Rng, RngCore};
+use std::sync::{Mutex, MutexGuard};
+
+/// A single mutation operator over Rust source.
+pub trait Mutator: Send + Sync {
+    /// Stable operator name, used in logs and statistics.
+    fn name(&self) -> &'static str;
+
+    /// Mutates `input`, returning `None` when the operator has nothing to
+    /// rewrite (for example because the input does not parse).
+    fn mutate(&self, input: &str, rng: &mut dyn RngCore) -> Option<String>;
+}
+
+/// Result of a successful [`Engine::mutate`] call.
+#[derive(Debug, Clone)]
+pub struct Mutated {
+    /// Name of the operator that produced `output`.
+    pub operator: &'static str,
+    pub output: String,
+    /// Seed the operator's RNG was created from.
+    pub seed: u64,
+    /// Byte range of the input that was rewritten.
+    pub location: std::ops::Range<usize>,
+}
+
+impl Mutated {
+    /// The replayable record of this mutation.
+    pub fn step(&self) -> Step {
+        Step {
+            operator: self.operator.to_owned(),
+            seed: self.seed,
+            location: self.location.clone(),
+        }
+    }
+}
+
+/// A collection of mutators with adaptive operator selection.
+pub struct Engine {
+    mutators: Vec<Box<dyn Mutator>>,
+    scheduler: Mutex<Bandit>,
+    havoc: Option<Havoc>,
+    /// Fraction of mutations handed straight to `havoc`.
+    havoc_rate: f64,
+}
+
+/// How many operators [`Engine::mutate`] tries before giving up.
+const MAX_ATTEMPTS: usize = 8;
+
+impl Engine {
+    /// An engine with no operators and no havoc fallback.
+    pub fn empty() -> Self {
+        Engine {
+            mutators: Vec::new(),
+            scheduler: Mutex::new(Bandit::default()),
+            havoc: None,
+            havoc_rate: 0.0,
+        }
+    }
+
+    /// Sends `rate` of all mutations to byte-level havoc, and uses it as the
+    /// fallback when no structured operator applies. A rate of zero keeps
+    /// the fallback only.
+    pub fn set_havoc(&mut self, havoc: Havoc, rate: f64) {
+        assert!((0.0..=1.0).contains(&rate), "havoc rate must be in [0, 1]");
+        self.havoc = Some(havoc);
+        self.havoc_rate = rate;
+    }
+
+    /// Turns havoc off entirely, fallback included.
+    pub fn disable_havoc(&mut self) {
+        self.havoc = None;
+        self.havoc_rate = 0.0;
+    }
+
+    /// Adds an operator to the engine.
+    pub fn push(&mut self, mutator: Box<dyn Mutator>) {
+        self.scheduler().add(mutator.name());
+        self.mutators.push(mutator);
+    }
+
+    fn scheduler(&self) -> MutexGuard<'_, Bandit> {
+        // The bandit holds no invariants a panicking holder could break.
+        self.scheduler
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// The diagnostics-stability mode: only meaning-preserving mutations,
+    /// to be checked with [`crate::oracle::stability`].
+    pub fn diagnostics_stability() -> Self {
+        let mut engine = Engine::empty();
+        engine.push(Box::new(TriviaMutator));
+        engine
+    }
+
+    /// Names of the registered operators, in registration order.
+    pub fn operators(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.mutators.iter().map(|m| m.name())
+    }
+
+    /// Applies one scheduled operator to `input`.
+    ///
+    /// Operators that decline are skipped. If none produced a change within
+    /// [`MAX_ATTEMPTS`] picks, havoc gets a turn; `None` means that was not
+    /// enough either.
+    pub fn mutate(&self, input: &str, rng: &mut dyn RngCore) -> Option<Mutated> {
+        if let Some(havoc) = &self.havoc {
+            if self.havoc_rate > 0.0 && rng.gen_bool(self.havoc_rate) {
+                return apply(havoc, input, rng.gen());
+            }
+        }
+        for _ in 0..MAX_ATTEMPTS {
+            let Some(index) = self.scheduler().select(rng) else {
+                break;
+            };
+            if let Some(mutated) = apply(&*self.mutators[index], input, rng.gen()) {
+                return Some(mutated);
+            }
+        }
+        apply(self.havoc.as_ref()?, input, rng.gen())
+    }
+
+    fn find(&self, operator: &str) -> Option<&dyn Mutator> {
+        let havoc = self.havoc.iter().map(|h| h as &dyn Mutator);
+        self.mutators
+            .iter()
+            .map(|m| &**m)
+            .chain(havoc)
+            .find(|m| m.name() == operator)
+    }
+
+    /// Re-applies a recorded step to the input it was recorded on.
+    pub fn replay_step(&self, input: &str, step: &Step) -> Result<String, ReplayError> {
+        let mutator = self
+            .find(&step.operator)
+            .ok_or_else(|| ReplayError::UnknownOperator(step.operator.clone()))?;
+        // A different changed range means the parent is not the one the
+        // step was recorded against.
+        apply(mutator, input, step.seed)
+            .filter(|m| m.location == step.location)
+            .map(|m| m.output)
+            .ok_or(ReplayError::Diverged { step: 0 })
+    }
+
+    /// Re-derives a child from its parent by replaying `steps` in order.
+    pub fn replay(&self, parent: &str, steps: &[Step]) -> Result<String, ReplayError> {
+        let mut current = parent.to_owned();
+        for (i, step) in steps.iter().enumerate() {
+            current = self.replay_step(&current, step).map_err(|e| match e {
+                ReplayError::Diverged { .. } => ReplayError::Diverged { step: i },
+                other => other,
+            })?;
+        }
+        Ok(current)
+    }
+
+    /// Reports what executing the output of `operator` led to, so future
+    /// selections favour productive operators.
+    pub fn reward(&self, operator: &str, feedback: Feedback) {
+        self.scheduler().reward(operator, feedback);
+    }
+
+    /// Per-operator selection statistics.
+    pub fn stats(&self) -> Vec<OperatorStats> {
+        self.scheduler().stats()
+    }
+
+    /// What operator selection has learnt so far, for a checkpoint.
+    pub fn operator_state(&self) -> Vec<ArmState> {
+        self.scheduler().state()
+    }
+
+    /// Resumes operator selection from a checkpoint's [`operator_state`](Self::operator_state).
+    pub fn restore_operators(&self, state: &[ArmState]) {
+        self.scheduler().restore(state);
+    }
+}
+
+impl Default for Engine {
+    /// An engine with every built-in operator registered, and those of
+    /// the installed [plugin registry](crate::plugin::installed).
+    fn default() -> Self {
+        let mut engine = Engine::empty();
+        engine.push(Box::new(TypeSubstitution));
+        engine.push(Box::new(DocInjection));
+        engine.push(Box::new(Regenerate::default()));
+        for mutator in crate::plugin::installed()
+            .into_iter()
+            .flat_map(|r| r.mutators())
+        {
+            engine.push(mutator);
+        }
+        engine.set_havoc(Havoc::default(), 0.05);
+        engine
+    }
+}
+
+fn apply(mutator: &dyn Mutator, input: &str, seed: u64) -> Option<Mutated> {
+    let Some(output) = mutator
+        .mutate(input, &mut step_rng(seed))
+        .filter(|output| output != input)
+    else {
+        tracing::trace!(operator = mutator.name(), "declined");
+        return None;
+    };
+    Some(Mutated {
+        operator: mutator.name(),
+        location: changed_range(input, &output),
+        output,
+        seed,
+    })
+}
+
+/// Parses `input` as a whole Rust file.
+pub(crate) fn parse_file(input: &str) -> Option<syn::File> {
+    syn::parse_file(input).ok()
+}
+
+/// Pretty-prints a file back to source.
+pub(crate) fn unparse(file: &syn::File) -> String {
+    prettyplease::unparse(file)
+}
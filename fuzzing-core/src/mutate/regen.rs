@@ -0,0 +1,240 @@
+//! Hole-based regeneration.
+//!
+//! Cuts a random expression or statement out of a function body and fills
+//! the hole with fresh code from [`crate::gen`]. Where the expected type of
+//! the hole is known (a typed `let`, a function's tail expression) the
+//! replacement is generated at that type, and variables from enclosing
+//! parameters and typed `let`s are offered as leaves.
+
+use rand::{Rng, RngCore};
+use syn::visit_mut::{self, VisitMut};
+use syn::{Block, Expr, FnArg, ImplItemFn, ItemFn, Lit, Local, Pat, ReturnType, Signature, Stmt};
+
+use super::{parse_file, unparse, Mutator};
+use crate::gen::{ExprGen, Scope, Ty};
+
+/// Replaces an expression or statement with a freshly generated one.
+pub struct Regenerate {
+    pub gen: ExprGen,
+}
+
+impl Default for Regenerate {
+    fn default() -> Self {
+        Regenerate {
+            gen: ExprGen {
+                max_depth: 3,
+                ..ExprGen::default()
+            },
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Target {
+    Expr,
+    Stmt,
+}
+
+impl Mutator for Regenerate {
+    fn name(&self) -> &'static str {
+        "regenerate"
+    }
+
+    fn mutate(&self, input: &str, rng: &mut dyn RngCore) -> Option<String> {
+        let mut file = parse_file(input)?;
+        let target = if rng.gen_bool(0.25) {
+            Target::Stmt
+        } else {
+            Target::Expr
+        };
+
+        let mut counter = Holes::new(target, usize::MAX, &self.gen, rng);
+        counter.visit_file_mut(&mut file);
+        if counter.index == 0 {
+            return None;
+        }
+        let chosen = counter.rng.gen_range(0..counter.index);
+
+        let mut filler = Holes::new(target, chosen, &self.gen, rng);
+        filler.visit_file_mut(&mut file);
+        filler.filled.then(|| unparse(&file))
+    }
+}
+
+/// Walks function bodies, numbering holes of the requested kind and
+/// refilling the `chosen` one.
+struct Holes<'a> {
+    target: Target,
+    chosen: usize,
+    index: usize,
+    filled: bool,
+    gen: &'a ExprGen,
+    rng: &'a mut dyn RngCore,
+    scope: Scope,
+    /// Expected type of the next expression visited, if known.
+    hint: Option<Ty>,
+    in_fn: bool,
+}
+
+impl<'a> Holes<'a> {
+    fn new(target: Target, chosen: usize, gen: &'a ExprGen, rng: &'a mut dyn RngCore) -> Self {
+        Holes {
+            target,
+            chosen,
+            index: 0,
+            filled: false,
+            gen,
+            rng,
+            scope: Scope::new(),
+            hint: None,
+            in_fn: false,
+        }
+    }
+
+    /// Advances the hole counter, returning whether this is the chosen hole.
+    fn hit(&mut self) -> bool {
+        let hit = self.index == self.chosen;
+        self.index += 1;
+        hit
+    }
+
+    fn visit_fn(&mut self, sig: &mut Signature, block: &mut Block) {
+        let outer = std::mem::take(&mut self.scope);
+        let was_in_fn = std::mem::replace(&mut self.in_fn, true);
+        for input in &sig.inputs {
+            if let FnArg::Typed(arg) = input {
+                if let (Pat::Ident(p), Some(ty)) = (&*arg.pat, Ty::from_syn(&arg.ty)) {
+                    self.scope.push(p.ident.to_string(), ty);
+                }
+            }
+        }
+        let ret = match &sig.output {
+            ReturnType::Default => Some(Ty::Unit),
+            ReturnType::Type(_, ty) => Ty::from_syn(ty),
+        };
+        self.visit_body(block, ret);
+        self.scope = outer;
+        self.in_fn = was_in_fn;
+    }
+
+    /// Visits a block whose tail expression has type `tail`.
+    fn visit_body(&mut self, block: &mut Block, tail: Option<Ty>) {
+        let mark = self.scope.len();
+        let last = block.stmts.len().saturating_sub(1);
+        for (i, stmt) in block.stmts.iter_mut().enumerate() {
+            if self.in_fn && self.target == Target::Stmt && self.hit() {
+                let mut scope = self.scope.clone();
+                let code = self.gen.stmt(&mut scope, self.rng);
+                if let Ok(new) = syn::parse_str::<Stmt>(&code) {
+                    *stmt = new;
+                    self.filled = true;
+                }
+                continue;
+            }
+            if i == last {
+                if let Stmt::Expr(_, None) = stmt {
+                    self.hint = tail.clone();
+                }
+            }
+            self.visit_stmt_mut(stmt);
+        }
+        self.scope.truncate(mark);
+    }
+}
+
+impl VisitMut for Holes<'_> {
+    fn visit_item_fn_mut(&mut self, item: &mut ItemFn) {
+        self.visit_fn(&mut item.sig, &mut item.block);
+    }
+
+    fn visit_impl_item_fn_mut(&mut self, item: &mut ImplItemFn) {
+        self.visit_fn(&mut item.sig, &mut item.block);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        self.visit_body(block, None);
+    }
+
+    fn visit_local_mut(&mut self, local: &mut Local) {
+        let typed = match &local.pat {
+            Pat::Type(pt) => match (&*pt.pat, Ty::from_syn(&pt.ty)) {
+                (Pat::Ident(p), Some(ty)) => Some((p.ident.to_string(), ty)),
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Some(init) = &mut local.init {
+            self.hint = typed.as_ref().map(|(_, ty)| ty.clone());
+            self.visit_expr_mut(&mut init.expr);
+            if let Some((_, diverge)) = &mut init.diverge {
+                self.visit_expr_mut(diverge);
+            }
+        }
+        if let Some((name, ty)) = typed {
+            self.scope.push(name, ty);
+        }
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut Expr) {
+        let hint = self.hint.take();
+        if self.in_fn && self.target == Target::Expr && self.hit() {
+            let ty = hint
+                .or_else(|| infer(expr, &self.scope))
+                .unwrap_or_else(|| Ty::random(2, self.rng));
+            let code = self.gen.expr(&ty, &self.scope, self.rng);
+            if let Ok(new) = syn::parse_str::<Expr>(&code) {
+                *expr = new;
+                self.filled = true;
+            }
+            return;
+        }
+        visit_mut::visit_expr_mut(self, expr);
+    }
+}
+
+/// The type of `expr` when it is obvious without type checking: a variable
+/// in scope or a literal that fixes its own type.
+fn infer(expr: &Expr, scope: &Scope) -> Option<Ty> {
+    match expr {
+        Expr::Path(p) => scope.lookup(&p.path.get_ident()?.to_string()).cloned(),
+        Expr::Paren(p) => infer(&p.expr, scope),
+        Expr::Lit(lit) => match &lit.lit {
+            Lit::Bool(_) => Some(Ty::Bool),
+            Lit::Char(_) => Some(Ty::Char),
+            Lit::Str(_) => Some(Ty::Str),
+            Lit::Int(i) => Ty::from_syn(&syn::parse_str(i.suffix()).ok()?),
+            Lit::Float(f) => Ty::from_syn(&syn::parse_str(f.suffix()).ok()?),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn regenerated_code_still_parses() {
+        let input = "fn f(x: i32) -> i32 {\n    let y: bool = x > 1;\n    x + 1\n}\n";
+        let mut changed = 0;
+        for seed in 0..32 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let Some(mutant) = Regenerate::default().mutate(input, &mut rng) else {
+                continue;
+            };
+            assert!(syn::parse_file(&mutant).is_ok(), "{mutant}");
+            assert!(mutant.starts_with("fn f(x: i32) -> i32 {"), "{mutant}");
+            changed += usize::from(mutant != input);
+        }
+        assert!(changed > 0);
+    }
+
+    #[test]
+    fn nothing_to_regenerate_outside_functions() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(Regenerate::default().mutate("struct S;", &mut rng), None);
+    }
+}
@@ -0,0 +1,269 @@
+//! Adaptive operator selection.
+//!
+//! [`Bandit`] implements Exp3: every operator has a weight, selection
+//! probabilities mix the normalised weights with a uniform exploration
+//! term, and rewarding an operator raises its weight by an
+//! importance-weighted amount. Operators that keep finding new coverage or
+//! new crash signatures get picked more often, while the exploration term
+//! guarantees nobody starves, which matters because an operator's
+//! usefulness drifts as the corpus matures.
+
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// What an execution of a mutated input led to.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Feedback {
+    pub new_coverage: bool,
+    pub new_crash: bool,
+}
+
+impl Feedback {
+    /// Reward in `[0, 1]`; a new crash signature is worth the most.
+    pub fn reward(self) -> f64 {
+        match (self.new_crash, self.new_coverage) {
+            (true, _) => 1.0,
+            (false, true) => 0.5,
+            (false, false) => 0.0,
+        }
+    }
+}
+
+/// Selection counts for one operator.
+#[derive(Debug, Clone, PartialEq)]
+pub struct OperatorStats {
+    pub name: &'static str,
+    pub selected: u64,
+    pub rewarded: u64,
+    pub probability: f64,
+}
+
+/// What a [`Bandit`] has learnt about one operator, as [`Bandit::state`]
+/// saves it for [`Bandit::restore`].
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArmState {
+    pub name: String,
+    pub log_weight: f64,
+    pub selected: u64,
+    pub rewarded: u64,
+}
+
+#[derive(Debug, Clone)]
+struct Arm {
+    name: &'static str,
+    log_weight: f64,
+    selected: u64,
+    rewarded: u64,
+}
+
+/// Exp3 scheduler over named operators.
+#[derive(Debug, Clone)]
+pub struct Bandit {
+    arms: Vec<Arm>,
+    /// Exploration rate in `(0, 1]`.
+    gamma: f64,
+}
+
+impl Bandit {
+    pub fn new(gamma: f64) -> Self {
+        assert!(gamma > 0.0 && gamma <= 1.0, "gamma must be in (0, 1]");
+        Bandit {
+            arms: Vec::new(),
+            gamma,
+        }
+    }
+
+    pub fn add(&mut self, name: &'static str) {
+        // New arms start at the current mean so they are neither favoured
+        // nor ignored.
+        let log_weight = if self.arms.is_empty() {
+            0.0
+        } else {
+            self.arms.iter().map(|a| a.log_weight).sum::<f64>() / self.arms.len() as f64
+        };
+        self.arms.push(Arm {
+            name,
+            log_weight,
+            selected: 0,
+            rewarded: 0,
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.arms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.arms.is_empty()
+    }
+
+    fn probabilities(&self) -> Vec<f64> {
+        let k = self.arms.len() as f64;
+        let max = self
+            .arms
+            .iter()
+            .map(|a| a.log_weight)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let weights: Vec<f64> = self
+            .arms
+            .iter()
+            .map(|a| (a.log_weight - max).exp())
+            .collect();
+        let total: f64 = weights.iter().sum();
+        weights
+            .iter()
+            .map(|w| (1.0 - self.gamma) * w / total + self.gamma / k)
+            .collect()
+    }
+
+    /// Picks an operator index, or `None` if there are no operators.
+    pub fn select(&mut self, rng: &mut dyn RngCore) -> Option<usize> {
+        if self.arms.is_empty() {
+            return None;
+        }
+        let mut x = rng.gen::<f64>();
+        let probabilities = self.probabilities();
+        let mut chosen = probabilities.len() - 1;
+        for (i, p) in probabilities.iter().enumerate() {
+            if x < *p {
+                chosen = i;
+                break;
+            }
+            x -= p;
+        }
+        self.arms[chosen].selected += 1;
+        Some(chosen)
+    }
+
+    /// Credits the operator called `name` with the outcome of one of its
+    /// mutations. Unknown names are ignored.
+    pub fn reward(&mut self, name: &str, feedback: Feedback) {
+        let Some(index) = self.arms.iter().position(|a| a.name == name) else {
+            return;
+        };
+        let reward = feedback.reward();
+        if reward == 0.0 {
+            return;
+        }
+        let p = self.probabilities()[index];
+        let k = self.arms.len() as f64;
+        let arm = &mut self.arms[index];
+        arm.rewarded += 1;
+        arm.log_weight += self.gamma * (reward / p) / k;
+    }
+
+    pub fn stats(&self) -> Vec<OperatorStats> {
+        self.arms
+            .iter()
+            .zip(self.probabilities())
+            .map(|(arm, probability)| OperatorStats {
+                name: arm.name,
+                selected: arm.selected,
+                rewarded: arm.rewarded,
+                probability,
+            })
+            .collect()
+    }
+
+    pub fn state(&self) -> Vec<ArmState> {
+        self.arms
+            .iter()
+            .map(|arm| ArmState {
+                name: arm.name.to_owned(),
+                log_weight: arm.log_weight,
+                selected: arm.selected,
+                rewarded: arm.rewarded,
+            })
+            .collect()
+    }
+
+    /// Takes the weights and counts in `state` for the operators it names.
+    /// Operators it does not name keep theirs and names this bandit lacks
+    /// are ignored, so a state saved with other operators still applies.
+    pub fn restore(&mut self, state: &[ArmState]) {
+        for saved in state {
+            if let Some(arm) = self.arms.iter_mut().find(|arm| arm.name == saved.name) {
+                arm.log_weight = saved.log_weight;
+                arm.selected = saved.selected;
+                arm.rewarded = saved.rewarded;
+            }
+        }
+    }
+}
+
+impl Default for Bandit {
+    fn default() -> Self {
+        Bandit::new(0.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn bandit() -> Bandit {
+        let mut bandit = Bandit::default();
+        bandit.add("a");
+        bandit.add("b");
+        bandit
+    }
+
+    #[test]
+    fn rewarded_operators_are_picked_more_often() {
+        let mut bandit = bandit();
+        let crash = Feedback {
+            new_crash: true,
+            ..Feedback::default()
+        };
+        for _ in 0..200 {
+            bandit.reward("b", crash);
+        }
+        bandit.reward("unknown", crash);
+        let stats = bandit.stats();
+        assert_eq!(stats[1].rewarded, 200);
+        assert!(stats[1].probability > 0.9, "{stats:?}");
+        // The exploration term keeps the other one alive.
+        assert!(stats[0].probability >= 0.1 / 2.0, "{stats:?}");
+        let total: f64 = stats.iter().map(|s| s.probability).sum();
+        assert!((total - 1.0).abs() < 1e-9);
+
+        let mut rng = StdRng::seed_from_u64(0);
+        let picks = (0..100)
+            .filter(|_| bandit.select(&mut rng) == Some(1))
+            .count();
+        assert!(picks > 80, "{picks}");
+        assert_eq!(bandit.stats()[1].selected, picks as u64);
+    }
+
+    #[test]
+    fn restored_state_applies_to_the_operators_it_names() {
+        let mut trained = bandit();
+        trained.reward(
+            "a",
+            Feedback {
+                new_coverage: true,
+                ..Feedback::default()
+            },
+        );
+        let mut state = trained.state();
+        state.push(ArmState {
+            name: "gone".to_owned(),
+            log_weight: 5.0,
+            selected: 1,
+            rewarded: 1,
+        });
+        let mut fresh = bandit();
+        fresh.add("new");
+        fresh.restore(&state);
+        assert_eq!(fresh.state()[..2], trained.state()[..]);
+        assert_eq!(fresh.state()[2].log_weight, 0.0);
+    }
+
+    #[test]
+    fn nothing_is_picked_without_operators() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(Bandit::default().select(&mut rng), None);
+    }
+}
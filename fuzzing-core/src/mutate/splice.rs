@@ -0,0 +1,133 @@
+//! Crossover between two seeds.
+//!
+//! When both parents parse, whole items are exchanged so the child stays
+//! syntactically valid. Otherwise the child is a prefix of one parent glued
+//! to a suffix of the other at token boundaries.
+//!
+//! [`FragmentSplice`] is the single-parent counterpart: the donor is a
+//! fragment from a [`Library`] rather than another seed.
+
+use std::sync::Arc;
+
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+
+use super::{parse_file, unparse, Mutator};
+use crate::corpus::library::{top_level_names, Library};
+use crate::lex;
+
+/// Combines `a` and `b` into a new seed.
+pub fn crossover(a: &str, b: &str, rng: &mut dyn RngCore) -> Option<String> {
+    match (parse_file(a), parse_file(b)) {
+        (Some(mut a), Some(b)) if !b.items.is_empty() => {
+            let donor = b.items.choose(rng)?.clone();
+            if a.items.is_empty() || rng.gen_bool(0.5) {
+                let at = rng.gen_range(0..=a.items.len());
+                a.items.insert(at, donor);
+            } else {
+                let at = rng.gen_range(0..a.items.len());
+                a.items[at] = donor;
+            }
+            Some(unparse(&a))
+        }
+        _ => token_splice(a, b, rng),
+    }
+}
+
+fn token_splice(a: &str, b: &str, rng: &mut dyn RngCore) -> Option<String> {
+    let (ta, tb) = (lex::tokenize(a), lex::tokenize(b));
+    if ta.is_empty() || tb.is_empty() {
+        return None;
+    }
+    let cut_a = ta[rng.gen_range(0..ta.len())].span.end;
+    let cut_b = tb[rng.gen_range(0..tb.len())].span.start;
+    Some(format!("{}{}", &a[..cut_a], &b[cut_b..]))
+}
+
+/// Inserts a library fragment that defines nothing the input already
+/// has: its `use`s at the top, its other items at a random position.
+#[derive(Debug, Clone)]
+pub struct FragmentSplice {
+    pub library: Arc<Library>,
+}
+
+impl Mutator for FragmentSplice {
+    fn name(&self) -> &'static str {
+        "fragment-splice"
+    }
+
+    fn mutate(&self, input: &str, rng: &mut dyn RngCore) -> Option<String> {
+        let mut file = parse_file(input)?;
+        let names = top_level_names(&file);
+        let fragment = self.library.choose(rng, |f| f.fits(&names))?;
+        let donor = parse_file(&fragment.source)?;
+        let (uses, items): (Vec<syn::Item>, Vec<syn::Item>) = donor
+            .items
+            .into_iter()
+            .partition(|item| matches!(item, syn::Item::Use(_)));
+        let at = rng.gen_range(0..=file.items.len());
+        file.items.splice(at..at, items);
+        file.items.splice(0..0, uses);
+        Some(unparse(&file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn parsed_parents_exchange_whole_items() {
+        for seed in 0..16 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let child =
+                crossover("fn a() {}\nfn b() {}\n", "struct S;\n", &mut rng).expect("a child");
+            let file = syn::parse_file(&child).expect("the child parses");
+            assert!(child.contains("struct S;"), "{child}");
+            assert!((2..=3).contains(&file.items.len()), "{child}");
+        }
+    }
+
+    #[test]
+    fn unparsed_parents_are_cut_at_tokens() {
+        let mut rng = StdRng::seed_from_u64(0);
+        let (a, b) = ("fn a( {", "let x = 1; }");
+        let child = crossover(a, b, &mut rng).expect("a child");
+        let prefix = (1..=a.len()).any(|n| child.starts_with(&a[..n]) && b.ends_with(&child[n..]));
+        assert!(prefix, "{child}");
+        assert_eq!(crossover("", b, &mut rng), None);
+    }
+
+    #[test]
+    fn fragments_go_where_their_names_are_free() {
+        let mut library = Library::default();
+        for fragment in crate::corpus::library::extract(
+            "use std::rc::Rc;
+fn shared() -> Rc<u8> { Rc::new(1) }
+fn main() {}
+",
+        ) {
+            library.add(fragment);
+        }
+        let splice = FragmentSplice {
+            library: Arc::new(library),
+        };
+        for seed in 0..8 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let child = splice
+                .mutate(
+                    "fn main() {}
+",
+                    &mut rng,
+                )
+                .expect("a fragment fits");
+            assert!(child.starts_with("use std::rc::Rc;\n"), "{child}");
+            assert!(child.contains("fn shared()"), "{child}");
+            assert!(child.contains("fn main()"), "{child}");
+        }
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(splice.mutate("fn shared() {}\n", &mut rng), None);
+    }
+}
@@ -0,0 +1,315 @@
+//! Meaning-preserving mutations for the diagnostics-stability mode.
+//!
+//! These only touch whitespace, comments and the length of locally defined
+//! identifiers, so a correct compiler must report the same errors, in the
+//! same order, for the mutated input. Pair with
+//! [`crate::oracle::stability`].
+
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+use std::collections::HashSet;
+
+use super::Mutator;
+use crate::lex::{self, Token, TokenKind};
+
+/// Perturbs trivia and identifier lengths without changing semantics.
+pub struct TriviaMutator;
+
+const WHITESPACE: &[&str] = &[" ", "  ", "\t", "\n", "\n\n", "\n    ", " \t "];
+
+const COMMENT_WORDS: &[&str] = &[
+    "fn", "x", "let", "}", "{", "TODO", "é", "∀", "'a", "\"", "::", "#[cfg]", "0xff", "->",
+];
+
+/// Keywords that introduce a definition of the identifier after them.
+const DEFINERS: &[&str] = &[
+    "fn",
+    "struct",
+    "enum",
+    "union",
+    "trait",
+    "type",
+    "mod",
+    "const",
+    "static",
+    "macro_rules",
+];
+
+/// Names whose meaning comes from outside the file even when defined in it:
+/// the entry point and members of std traits that seeds commonly implement.
+const RESERVED: &[&str] = &[
+    "main",
+    "Item",
+    "Output",
+    "Target",
+    "Error",
+    "next",
+    "fmt",
+    "drop",
+    "deref",
+    "deref_mut",
+    "clone",
+    "eq",
+    "ne",
+    "partial_cmp",
+    "cmp",
+    "hash",
+    "from",
+    "into",
+    "poll",
+    "call",
+    "call_mut",
+    "call_once",
+    "index",
+    "index_mut",
+    "add",
+    "sub",
+    "mul",
+    "div",
+    "rem",
+    "neg",
+    "not",
+    "default",
+    "as_ref",
+    "borrow",
+    "try_from",
+    "from_str",
+    "into_iter",
+];
+
+impl Mutator for TriviaMutator {
+    fn name(&self) -> &'static str {
+        "diagnostics-stability"
+    }
+
+    fn mutate(&self, input: &str, rng: &mut dyn RngCore) -> Option<String> {
+        let tokens = lex::tokenize(input);
+        if tokens.is_empty() {
+            return None;
+        }
+        match rng.gen_range(0..3) {
+            0 => whitespace(input, &tokens, rng),
+            1 => comment(input, &tokens, rng),
+            _ => rename(input, &tokens, rng),
+        }
+    }
+}
+
+/// Rebuilds the source, replacing token `index` with `replacement`.
+fn splice(src: &str, tokens: &[Token], index: usize, replacement: &str) -> String {
+    let span = &tokens[index].span;
+    format!("{}{}{}", &src[..span.start], replacement, &src[span.end..])
+}
+
+/// Inserts `text` right after token `index`.
+fn insert_after(src: &str, tokens: &[Token], index: usize, text: &str) -> String {
+    let at = tokens[index].span.end;
+    format!("{}{}{}", &src[..at], text, &src[at..])
+}
+
+fn random_whitespace(rng: &mut dyn RngCore) -> String {
+    (0..rng.gen_range(1..=3))
+        .map(|_| *WHITESPACE.choose(rng).unwrap())
+        .collect()
+}
+
+fn whitespace(src: &str, tokens: &[Token], rng: &mut dyn RngCore) -> Option<String> {
+    let index = rng.gen_range(0..tokens.len());
+    let after_line_comment =
+        index > 0 && matches!(tokens[index - 1].kind, TokenKind::LineComment { .. });
+    match tokens[index].kind {
+        TokenKind::Whitespace => {
+            let mut ws = random_whitespace(rng);
+            if after_line_comment && !ws.starts_with('\n') {
+                ws.insert(0, '\n');
+            }
+            Some(splice(src, tokens, index, &ws))
+        }
+        TokenKind::LineComment { .. } => Some(insert_after(src, tokens, index, "\n")),
+        _ => Some(insert_after(src, tokens, index, &random_whitespace(rng))),
+    }
+}
+
+fn comment(src: &str, tokens: &[Token], rng: &mut dyn RngCore) -> Option<String> {
+    let comments: Vec<usize> = (0..tokens.len())
+        .filter(|&i| tokens[i].kind.is_comment() && tokens[i].kind.is_trivia())
+        .collect();
+    let text = comment_text(rng);
+    match (comments.choose(rng), rng.gen_bool(0.5)) {
+        (Some(&i), true) => {
+            let replacement = match tokens[i].kind {
+                TokenKind::LineComment { .. } if rng.gen_bool(0.5) => format!("// {text}"),
+                TokenKind::LineComment { .. } => String::new(),
+                _ if rng.gen_bool(0.5) => format!("/* {text} */"),
+                _ => " ".to_owned(),
+            };
+            Some(splice(src, tokens, i, &replacement))
+        }
+        _ => {
+            let index = rng.gen_range(0..tokens.len());
+            let comment = if rng.gen_bool(0.5) {
+                format!("/* {text} */")
+            } else {
+                format!("// {text}\n")
+            };
+            match tokens[index].kind {
+                TokenKind::LineComment { .. } => {
+                    Some(insert_after(src, tokens, index, &format!("\n{comment}")))
+                }
+                _ => Some(insert_after(src, tokens, index, &comment)),
+            }
+        }
+    }
+}
+
+/// Random comment body that cannot close or reopen a block comment.
+fn comment_text(rng: &mut dyn RngCore) -> String {
+    let words: Vec<&str> = (0..rng.gen_range(1..6))
+        .map(|_| *COMMENT_WORDS.choose(rng).unwrap())
+        .collect();
+    words.join(" ")
+}
+
+/// Renames an identifier defined in this file, everywhere it occurs.
+fn rename(src: &str, tokens: &[Token], rng: &mut dyn RngCore) -> Option<String> {
+    let significant: Vec<&Token> = tokens.iter().filter(|t| !t.kind.is_trivia()).collect();
+    let idents: HashSet<&str> = significant
+        .iter()
+        .filter(|t| t.kind == TokenKind::Ident)
+        .map(|t| t.text(src))
+        .collect();
+
+    let mut items = HashSet::new();
+    let mut locals = HashSet::new();
+    let mut projected = HashSet::new();
+    for i in 1..significant.len() {
+        let before = if i >= 2 {
+            significant[i - 2].text(src)
+        } else {
+            ""
+        };
+        let (prev, tok) = (significant[i - 1].text(src), significant[i]);
+        if tok.kind != TokenKind::Ident {
+            continue;
+        }
+        let name = tok.text(src);
+        match prev {
+            "let" => {
+                locals.insert(name);
+            }
+            // `let mut x`, but not `&mut T` or `*mut T`.
+            "mut" if before != "&" && before != "*" => {
+                locals.insert(name);
+            }
+            "." | "::" => {
+                projected.insert(name);
+            }
+            _ if DEFINERS.contains(&prev) => {
+                items.insert(name);
+            }
+            _ => {}
+        }
+    }
+
+    // Locals that share a name with a field or path segment may refer to
+    // something defined elsewhere, and format strings capture names by text.
+    let literals: Vec<&str> = significant
+        .iter()
+        .filter(|t| t.kind == TokenKind::Literal)
+        .map(|t| t.text(src))
+        .collect();
+    let mut candidates: Vec<&str> = items
+        .iter()
+        .chain(locals.difference(&projected))
+        .copied()
+        .filter(|name| {
+            !lex::is_keyword(name)
+                && !name.starts_with('_')
+                && !name.starts_with("r#")
+                && !RESERVED.contains(name)
+                && !literals.iter().any(|l| l.contains(&format!("{{{name}")))
+        })
+        .collect();
+    candidates.sort_unstable();
+    candidates.dedup();
+    let old = *candidates.choose(rng)?;
+
+    let new = resized(old, &idents, rng)?;
+    let mut out = String::with_capacity(src.len() + 16);
+    let mut last = 0;
+    for tok in tokens {
+        if tok.kind == TokenKind::Ident && tok.text(src) == old {
+            out.push_str(&src[last..tok.span.start]);
+            out.push_str(&new);
+            last = tok.span.end;
+        }
+    }
+    out.push_str(&src[last..]);
+    Some(out)
+}
+
+/// A fresh name with a different length but the same case style.
+fn resized(old: &str, taken: &HashSet<&str>, rng: &mut dyn RngCore) -> Option<String> {
+    let filler = if old.chars().all(|c| !c.is_lowercase()) {
+        'X'
+    } else {
+        'x'
+    };
+    for _ in 0..16 {
+        let candidate = if old.chars().count() > 1 && rng.gen_bool(0.5) {
+            let keep = rng.gen_range(1..old.chars().count());
+            let prefix: String = old.chars().take(keep).collect();
+            format!("{prefix}{}", rng.gen_range(0..100))
+        } else {
+            let pad: String = std::iter::repeat_n(filler, rng.gen_range(1..24)).collect();
+            format!("{old}{pad}")
+        };
+        if candidate.len() != old.len()
+            && !taken.contains(candidate.as_str())
+            && !lex::is_keyword(&candidate)
+        {
+            return Some(candidate);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    const SEED: &str = "\
+struct Point { x: i32 }
+
+fn main() {
+    // the origin
+    let mut count = 0;
+    let p = Point { x: count };
+    count += p.x;
+    println!(\"{count}\");
+}
+";
+
+    #[test]
+    fn only_trivia_and_local_names_change() {
+        let before = lex::significant(SEED, &lex::tokenize(SEED));
+        for seed in 0..64 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let Some(mutant) = TriviaMutator.mutate(SEED, &mut rng) else {
+                continue;
+            };
+            let tokens = lex::tokenize(&mutant);
+            let after = lex::significant(&mutant, &tokens);
+            assert_eq!(after.len(), before.len(), "{mutant}");
+            for (old, new) in before.iter().zip(&after) {
+                // `count` is captured by the format string, and `x` is a
+                // field, so neither may be renamed.
+                if old != new {
+                    assert!(["Point", "p"].contains(old), "{old} -> {new}");
+                }
+            }
+        }
+    }
+}
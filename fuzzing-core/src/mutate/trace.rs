@@ -0,0 +1,132 @@
+//! Mutation traces.
+//!
+//! Every operator application is driven by its own RNG seed, drawn from the
+//! caller's RNG, so a [`Step`] (operator, seed, and the byte range it
+//! changed) is enough to re-derive a child from its parent. A [`Trace`] is
+//! the chain of steps from a corpus seed to an interesting input; replaying
+//! it is how the mutators themselves get debugged, and replaying prefixes
+//! of it is how long mutation chains get minimized.
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops::Range;
+
+/// One recorded operator application.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Step {
+    pub operator: String,
+    /// Seed of the RNG the operator ran with.
+    pub seed: u64,
+    /// Byte range of the parent that the operator rewrote.
+    pub location: Range<usize>,
+}
+
+/// The steps that turned a parent into a child, oldest first.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Trace {
+    pub steps: Vec<Step>,
+}
+
+impl Trace {
+    /// Appends the step that produced `mutated`.
+    pub fn record(&mut self, mutated: &super::Mutated) {
+        self.steps.push(mutated.step());
+    }
+
+    /// The trace extended by one more step.
+    pub fn then(&self, mutated: &super::Mutated) -> Trace {
+        let mut trace = self.clone();
+        trace.record(mutated);
+        trace
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayError {
+    /// The trace names an operator the engine does not have.
+    UnknownOperator(String),
+    /// The operator declined or produced no change, so the engine it is
+    /// being replayed on is configured differently from the recording one.
+    Diverged { step: usize },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ReplayError::UnknownOperator(name) => write!(f, "unknown operator `{name}`"),
+            ReplayError::Diverged { step } => {
+                write!(
+                    f,
+                    "step {step} did not reproduce; operator configuration differs"
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ReplayError {}
+
+/// The RNG an operator runs with for a given step seed. ChaCha8 is used
+/// explicitly because its output, unlike `StdRng`'s, is stable across
+/// `rand` releases, which old traces depend on.
+pub fn step_rng(seed: u64) -> ChaCha8Rng {
+    ChaCha8Rng::seed_from_u64(seed)
+}
+
+/// The byte range of `before` that differs from `after`, found by trimming
+/// their common prefix and suffix.
+pub fn changed_range(before: &str, after: &str) -> Range<usize> {
+    let (a, b) = (before.as_bytes(), after.as_bytes());
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let max_suffix = a.len().min(b.len()) - prefix;
+    let suffix = a
+        .iter()
+        .rev()
+        .zip(b.iter().rev())
+        .take(max_suffix)
+        .take_while(|(x, y)| x == y)
+        .count();
+    prefix..a.len() - suffix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mutate::Engine;
+    use rand::rngs::StdRng;
+
+    #[test]
+    fn changed_ranges_trim_the_common_ends() {
+        assert_eq!(changed_range("let x = 1;", "let x = 22;"), 8..9);
+        assert_eq!(changed_range("abc", "abXbc"), 2..2);
+        assert_eq!(changed_range("aaaa", "aa"), 2..4);
+        assert_eq!(changed_range("same", "same"), 4..4);
+    }
+
+    #[test]
+    fn traces_replay_to_the_same_child() {
+        let engine = Engine::default();
+        let parent = "fn f(x: i32) -> i32 {\n    let y: i32 = x + 1;\n    y * 2\n}\n";
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut trace = Trace::default();
+        let mut child = parent.to_owned();
+        for _ in 0..4 {
+            let Some(mutated) = engine.mutate(&child, &mut rng) else {
+                continue;
+            };
+            trace = trace.then(&mutated);
+            child = mutated.output;
+        }
+        assert!(!trace.steps.is_empty());
+        assert_eq!(engine.replay(parent, &trace.steps), Ok(child));
+
+        let mut unknown = trace.steps[0].clone();
+        unknown.operator = "no-such-operator".to_owned();
+        assert_eq!(
+            engine.replay(parent, &[unknown]),
+            Err(ReplayError::UnknownOperator("no-such-operator".to_owned()))
+        );
+    }
+}
@@ -0,0 +1,269 @@
+//! Type substitution.
+//!
+//! Rewrites one type in a signature, ascription or generic argument to a
+//! closely related type (`i32` to `i64`, `&T` to `Box<T>`, `Vec<T>` to
+//! `[T; N]`, ...). The result still parses, so any error it provokes is
+//! reported by type checking rather than by the parser.
+
+use quote::quote;
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore};
+use syn::visit::{self, Visit};
+use syn::visit_mut::{self, VisitMut};
+use syn::{parse_quote, GenericArgument, PathArguments, Type};
+
+use super::{parse_file, unparse, Mutator};
+
+/// Replaces a random type with a related one from a compatibility table.
+pub struct TypeSubstitution;
+
+/// Primitive and std types and the types they are most often confused with.
+const COMPATIBLE: &[(&str, &[&str])] = &[
+    ("i8", &["i16", "u8", "i32"]),
+    ("i16", &["i8", "i32", "u16"]),
+    ("i32", &["i64", "i16", "u32", "isize"]),
+    ("i64", &["i32", "i128", "u64"]),
+    ("i128", &["i64", "u128"]),
+    ("isize", &["usize", "i64", "i32"]),
+    ("u8", &["u16", "i8", "char", "bool"]),
+    ("u16", &["u8", "u32", "i16"]),
+    ("u32", &["u64", "u16", "i32", "char"]),
+    ("u64", &["u32", "u128", "i64", "usize"]),
+    ("u128", &["u64", "i128"]),
+    ("usize", &["isize", "u64", "u32"]),
+    ("f32", &["f64", "i32"]),
+    ("f64", &["f32", "i64"]),
+    ("bool", &["u8", "()"]),
+    ("char", &["u32", "u8", "&str"]),
+    ("str", &["[u8]", "String"]),
+    ("String", &["&str", "Box<str>", "Vec<u8>"]),
+];
+
+impl Mutator for TypeSubstitution {
+    fn name(&self) -> &'static str {
+        "type-substitution"
+    }
+
+    fn mutate(&self, input: &str, rng: &mut dyn RngCore) -> Option<String> {
+        let mut file = parse_file(input)?;
+
+        let mut counter = CountTypes(0);
+        counter.visit_file(&file);
+        if counter.0 == 0 {
+            return None;
+        }
+
+        let mut rewriter = Rewrite {
+            index: 0,
+            target: rng.gen_range(0..counter.0),
+            rng,
+            changed: false,
+        };
+        rewriter.visit_file_mut(&mut file);
+        rewriter.changed.then(|| unparse(&file))
+    }
+}
+
+struct CountTypes(usize);
+
+impl<'ast> Visit<'ast> for CountTypes {
+    fn visit_type(&mut self, ty: &'ast Type) {
+        self.0 += 1;
+        visit::visit_type(self, ty);
+    }
+}
+
+/// Replaces the `target`-th type in pre-order.
+struct Rewrite<'r> {
+    index: usize,
+    target: usize,
+    rng: &'r mut dyn RngCore,
+    changed: bool,
+}
+
+impl VisitMut for Rewrite<'_> {
+    fn visit_type_mut(&mut self, ty: &mut Type) {
+        if self.index == self.target {
+            self.index += 1;
+            if let Some(related) = related(ty, self.rng) {
+                *ty = related;
+                self.changed = true;
+            }
+            return;
+        }
+        self.index += 1;
+        visit_mut::visit_type_mut(self, ty);
+    }
+}
+
+/// Picks a type related to `ty`, or `None` if no rule applies.
+fn related(ty: &Type, rng: &mut dyn RngCore) -> Option<Type> {
+    let candidates: Vec<Type> = match ty {
+        Type::Reference(r) => {
+            let elem = &r.elem;
+            if r.mutability.is_some() {
+                vec![
+                    parse_quote!(&#elem),
+                    parse_quote!(Box<#elem>),
+                    parse_quote!(*mut #elem),
+                ]
+            } else {
+                vec![
+                    parse_quote!(Box<#elem>),
+                    parse_quote!(&mut #elem),
+                    parse_quote!(std::rc::Rc<#elem>),
+                    parse_quote!(*const #elem),
+                    parse_quote!(&'static #elem),
+                    (**elem).clone(),
+                ]
+            }
+        }
+        Type::Path(p) if p.qself.is_none() => {
+            let last = p.path.segments.last()?;
+            let name = last.ident.to_string();
+            match (name.as_str(), generic_args(&last.arguments).as_slice()) {
+                ("Box", [t]) => vec![
+                    parse_quote!(&#t),
+                    parse_quote!(std::rc::Rc<#t>),
+                    parse_quote!(Option<Box<#t>>),
+                    (*t).clone(),
+                ],
+                ("Vec", [t]) => {
+                    let n = rng.gen_range(0..8usize);
+                    vec![
+                        parse_quote!([#t; #n]),
+                        parse_quote!(&[#t]),
+                        parse_quote!(Box<[#t]>),
+                        parse_quote!(std::collections::VecDeque<#t>),
+                    ]
+                }
+                ("Option", [t]) => vec![
+                    (*t).clone(),
+                    parse_quote!(Result<#t, ()>),
+                    parse_quote!(Option<Option<#t>>),
+                ],
+                ("Result", [t, e]) => vec![parse_quote!(Option<#t>), parse_quote!(Result<#e, #t>)],
+                ("Rc", [t]) => vec![parse_quote!(std::sync::Arc<#t>), parse_quote!(Box<#t>)],
+                ("Arc", [t]) => vec![parse_quote!(std::rc::Rc<#t>), parse_quote!(&#t)],
+                (_, []) if p.path.segments.len() == 1 => match lookup(&name) {
+                    Some(related) => related
+                        .iter()
+                        .filter_map(|s| syn::parse_str(s).ok())
+                        .collect(),
+                    None => vec![
+                        parse_quote!(Option<#ty>),
+                        parse_quote!(&#ty),
+                        parse_quote!(Box<#ty>),
+                    ],
+                },
+                _ => vec![parse_quote!(&#ty), parse_quote!(Box<#ty>)],
+            }
+        }
+        Type::Array(a) => {
+            let (elem, len) = (&a.elem, &a.len);
+            vec![
+                parse_quote!(Vec<#elem>),
+                parse_quote!([#elem; #len + 1]),
+                parse_quote!(&[#elem]),
+                parse_quote!([#elem]),
+            ]
+        }
+        Type::Slice(s) => {
+            let elem = &s.elem;
+            vec![
+                parse_quote!([#elem; 4]),
+                parse_quote!(Vec<#elem>),
+                parse_quote!(str),
+            ]
+        }
+        Type::Tuple(t) if t.elems.is_empty() => vec![parse_quote!(!), parse_quote!(bool)],
+        Type::Tuple(t) => {
+            let elems: Vec<&Type> = t.elems.iter().collect();
+            let reversed = elems.iter().rev();
+            let dropped = &elems[..elems.len() - 1];
+            vec![
+                parse_quote!((#(#reversed,)*)),
+                parse_quote!((#(#dropped,)*)),
+                parse_quote!(()),
+            ]
+        }
+        Type::Ptr(p) => {
+            let elem = &p.elem;
+            vec![
+                parse_quote!(&#elem),
+                parse_quote!(*mut #elem),
+                parse_quote!(*const *const #elem),
+            ]
+        }
+        Type::ImplTrait(i) => {
+            let bounds = &i.bounds;
+            vec![parse_quote!(Box<dyn #bounds>), parse_quote!(&dyn #bounds)]
+        }
+        Type::TraitObject(d) => {
+            let bounds = &d.bounds;
+            vec![parse_quote!(impl #bounds), parse_quote!(dyn #bounds + Send)]
+        }
+        Type::BareFn(f) => {
+            let inputs = f.inputs.iter().map(|arg| &arg.ty);
+            let output = &f.output;
+            let tokens = quote!(&dyn Fn(#(#inputs),*) #output);
+            vec![syn::parse2(tokens).ok()?]
+        }
+        Type::Never(_) => vec![parse_quote!(()), parse_quote!(std::convert::Infallible)],
+        Type::Paren(p) => vec![(*p.elem).clone()],
+        Type::Group(g) => vec![(*g.elem).clone()],
+        _ => return None,
+    };
+    candidates.choose(rng).cloned()
+}
+
+fn lookup(name: &str) -> Option<&'static [&'static str]> {
+    COMPATIBLE
+        .iter()
+        .find(|(ty, _)| *ty == name)
+        .map(|(_, related)| *related)
+}
+
+/// The type arguments of a path segment, ignoring lifetimes and constants.
+fn generic_args(args: &PathArguments) -> Vec<&Type> {
+    match args {
+        PathArguments::AngleBracketed(a) => a
+            .args
+            .iter()
+            .filter_map(|arg| match arg {
+                GenericArgument::Type(t) => Some(t),
+                _ => None,
+            })
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn substitutes_a_compatible_type() {
+        for seed in 0..16 {
+            let mut rng = StdRng::seed_from_u64(seed);
+            let mutant = TypeSubstitution
+                .mutate("fn f(x: i32) {}", &mut rng)
+                .expect("one type to substitute");
+            let related = ["i64", "i16", "u32", "isize"]
+                .iter()
+                .any(|ty| mutant.contains(&format!("x: {ty}")));
+            assert!(related, "{mutant}");
+            assert!(syn::parse_file(&mutant).is_ok(), "{mutant}");
+        }
+    }
+
+    #[test]
+    fn nothing_to_substitute_without_types() {
+        let mut rng = StdRng::seed_from_u64(0);
+        assert_eq!(TypeSubstitution.mutate("fn f() {}", &mut rng), None);
+        assert_eq!(TypeSubstitution.mutate("fn f(", &mut rng), None);
+    }
+}
@@ -0,0 +1,4 @@
+//! Oracles decide whether the outcome of compiling a seed is a finding.
+
+pub mod regression;
+pub mod stability;
@@ -0,0 +1,406 @@
+//! Acceptance-regression oracle.
+//!
+//! A corpus that compiled one way with yesterday's compiler is a test
+//! suite for today's. [`ToolchainDiff`] compiles a seed with a baseline and
+//! a candidate rustc and compares what each made of it: whether it was
+//! accepted, rejected or crashed the compiler, and, from JSON diagnostics,
+//! the error codes, lint names and suggestion applicabilities. A
+//! [`Regression`] says how they differ, from a newly ICE-ing, accepted or
+//! rejected program down to diagnostics that changed on a program both
+//! compilers otherwise agree on.
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::harness::{ExecOutcome, Rustc};
+use crate::triage::Severity;
+
+/// What one compiler made of a seed.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Profile {
+    /// [`ExecOutcome::kind`].
+    pub outcome: String,
+    /// Error codes, or `error` for errors without one, sorted.
+    pub errors: Vec<String>,
+    /// Names of the lints that warned.
+    pub lints: BTreeSet<String>,
+    /// `code: Applicability` for each kind of suggestion made.
+    pub suggestions: BTreeSet<String>,
+}
+
+impl Profile {
+    /// The profile of a compile with `--error-format=json`.
+    pub fn of(outcome: &ExecOutcome) -> Self {
+        let mut profile = Profile {
+            outcome: outcome.kind().to_owned(),
+            ..Profile::default()
+        };
+        for line in outcome.output().stderr.lines() {
+            let Ok(diagnostic) = serde_json::from_str::<Diagnostic>(line) else {
+                continue;
+            };
+            let code = diagnostic.code.as_ref().map(|code| code.code.as_str());
+            match diagnostic.level.as_str() {
+                "error" if !diagnostic.message.starts_with("aborting due to") => {
+                    profile.errors.push(code.unwrap_or("error").to_owned());
+                }
+                "warning" => {
+                    if let Some(code) = code {
+                        profile.lints.insert(code.to_owned());
+                    }
+                }
+                _ => {}
+            }
+            collect_applicabilities(
+                code.unwrap_or(&diagnostic.level),
+                &diagnostic,
+                &mut profile.suggestions,
+            );
+        }
+        profile.errors.sort();
+        profile
+    }
+
+    fn is_crash(&self) -> bool {
+        matches!(self.outcome.as_str(), "ice" | "crash")
+    }
+
+    /// Timeouts and memory exhaustion say more about the machine than
+    /// about the compiler, unless they reproduce.
+    fn is_inconclusive(&self) -> bool {
+        matches!(self.outcome.as_str(), "timeout" | "oom")
+    }
+}
+
+impl fmt::Display for Profile {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.outcome)?;
+        if !self.errors.is_empty() {
+            write!(f, " [{}]", self.errors.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct Diagnostic {
+    #[serde(default)]
+    message: String,
+    #[serde(default)]
+    level: String,
+    code: Option<Code>,
+    #[serde(default)]
+    spans: Vec<Span>,
+    #[serde(default)]
+    children: Vec<Diagnostic>,
+}
+
+#[derive(Deserialize)]
+struct Code {
+    code: String,
+}
+
+#[derive(Deserialize)]
+struct Span {
+    suggestion_applicability: Option<String>,
+}
+
+fn collect_applicabilities(code: &str, diagnostic: &Diagnostic, out: &mut BTreeSet<String>) {
+    for span in &diagnostic.spans {
+        if let Some(applicability) = &span.suggestion_applicability {
+            out.insert(format!("{code}: {applicability}"));
+        }
+    }
+    for child in &diagnostic.children {
+        collect_applicabilities(code, child, out);
+    }
+}
+
+/// How the candidate's verdict differs from the baseline's, worst first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Change {
+    /// The candidate ICEs or crashes where the baseline did not.
+    NewIce,
+    /// The candidate accepts what the baseline rejected.
+    NewlyAccepted,
+    /// The candidate rejects what the baseline accepted.
+    NewlyRejected,
+    /// Both reject it, with different errors.
+    Errors,
+    /// Both agree on the verdict, but warn with different lints.
+    Lints,
+    /// Both agree on the verdict, but suggest fixes with a different
+    /// applicability.
+    Suggestions,
+    /// The baseline ICEd or crashed and the candidate does not.
+    Fixed,
+}
+
+impl Change {
+    pub const ALL: [Change; 7] = [
+        Change::NewIce,
+        Change::NewlyAccepted,
+        Change::NewlyRejected,
+        Change::Errors,
+        Change::Lints,
+        Change::Suggestions,
+        Change::Fixed,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Change::NewIce => "new ICE",
+            Change::NewlyAccepted => "newly accepted",
+            Change::NewlyRejected => "newly rejected",
+            Change::Errors => "errors changed",
+            Change::Lints => "lints changed",
+            Change::Suggestions => "suggestions changed",
+            Change::Fixed => "fixed",
+        }
+    }
+
+    /// How bad the change is, or `None` for a fix.
+    pub fn severity(self) -> Option<Severity> {
+        match self {
+            Change::NewIce => Some(Severity::Ice),
+            Change::Fixed => None,
+            _ => Some(Severity::Diagnostic),
+        }
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A seed the two compilers disagree on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Regression {
+    pub change: Change,
+    pub baseline: Profile,
+    pub candidate: Profile,
+}
+
+impl Regression {
+    /// How `candidate` differs from `baseline`, or `None` if it does not
+    /// or either is inconclusive.
+    pub fn between(baseline: Profile, candidate: Profile) -> Option<Self> {
+        if baseline.is_inconclusive() || candidate.is_inconclusive() {
+            return None;
+        }
+        let change = if candidate.is_crash() && !baseline.is_crash() {
+            Change::NewIce
+        } else if baseline.is_crash() && !candidate.is_crash() {
+            Change::Fixed
+        } else if baseline.is_crash() {
+            return None;
+        } else if baseline.outcome != candidate.outcome {
+            if candidate.outcome == "success" {
+                Change::NewlyAccepted
+            } else {
+                Change::NewlyRejected
+            }
+        } else if baseline.errors != candidate.errors {
+            Change::Errors
+        } else if baseline.lints != candidate.lints {
+            Change::Lints
+        } else if baseline.suggestions != candidate.suggestions {
+            Change::Suggestions
+        } else {
+            return None;
+        };
+        Some(Regression {
+            change,
+            baseline,
+            candidate,
+        })
+    }
+}
+
+impl fmt::Display for Regression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: {} -> {}",
+            self.change, self.baseline, self.candidate
+        )?;
+        let (before, after) = match self.change {
+            Change::Lints => (&self.baseline.lints, &self.candidate.lints),
+            Change::Suggestions => (&self.baseline.suggestions, &self.candidate.suggestions),
+            _ => return Ok(()),
+        };
+        for gone in before.difference(after) {
+            write!(f, ", -{gone}")?;
+        }
+        for new in after.difference(before) {
+            write!(f, ", +{new}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Compiles seeds with two compilers to compare them.
+#[derive(Debug, Clone)]
+pub struct ToolchainDiff {
+    pub baseline: Rustc,
+    pub candidate: Rustc,
+    /// Arguments before the input, which is read from stdin.
+    pub args: Vec<String>,
+}
+
+impl ToolchainDiff {
+    pub fn new(baseline: Rustc, candidate: Rustc) -> Self {
+        ToolchainDiff {
+            baseline,
+            candidate,
+            args: vec!["--crate-type=lib".to_owned()],
+        }
+    }
+
+    /// How the candidate's verdict on `source` differs from the
+    /// baseline's, if it does.
+    pub fn check(&self, source: &str) -> io::Result<Option<Regression>> {
+        let baseline = self.profile(&self.baseline, source)?;
+        let candidate = self.profile(&self.candidate, source)?;
+        Ok(Regression::between(baseline, candidate))
+    }
+
+    fn profile(&self, driver: &Rustc, source: &str) -> io::Result<Profile> {
+        let mut args = self.args.clone();
+        args.extend(["--error-format=json".to_owned(), "-".to_owned()]);
+        Ok(Profile::of(&driver.run(&args, source.as_bytes())?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::Captured;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    const DIAGNOSTICS: &str = r#"{"message":"unused variable: `x`","level":"warning","code":{"code":"unused_variables"},"spans":[],"children":[{"message":"prefix it","level":"help","code":null,"spans":[{"suggestion_applicability":"MaybeIncorrect"}],"children":[]}]}
+{"message":"mismatched types","level":"error","code":{"code":"E0308"},"spans":[],"children":[]}
+{"message":"cannot find value","level":"error","code":null,"spans":[],"children":[]}
+{"message":"aborting due to 2 previous errors","level":"error","code":null,"spans":[],"children":[]}
+"#;
+
+    fn profile(outcome: &str, errors: &[&str], lints: &[&str]) -> Profile {
+        Profile {
+            outcome: outcome.to_owned(),
+            errors: errors.iter().map(|e| e.to_string()).collect(),
+            lints: lints.iter().map(|l| l.to_string()).collect(),
+            suggestions: BTreeSet::new(),
+        }
+    }
+
+    #[test]
+    fn profiles_come_from_json_diagnostics() {
+        let outcome = ExecOutcome::Error {
+            code: 1,
+            output: Captured {
+                stderr: DIAGNOSTICS.to_owned(),
+                ..Captured::default()
+            },
+        };
+        let profile = Profile::of(&outcome);
+        assert_eq!(profile.outcome, "error");
+        assert_eq!(profile.errors, ["E0308", "error"]);
+        assert_eq!(
+            profile.lints,
+            BTreeSet::from(["unused_variables".to_owned()])
+        );
+        assert_eq!(
+            profile.suggestions,
+            BTreeSet::from(["unused_variables: MaybeIncorrect".to_owned()])
+        );
+        assert_eq!(profile.to_string(), "error [E0308, error]");
+    }
+
+    #[test]
+    fn the_worst_difference_wins() {
+        let change = |baseline, candidate| {
+            Regression::between(baseline, candidate).map(|regression| regression.change)
+        };
+        let ok = || profile("success", &[], &[]);
+        assert_eq!(change(ok(), profile("ice", &[], &[])), Some(Change::NewIce));
+        assert_eq!(
+            change(profile("crash", &[], &[]), ok()),
+            Some(Change::Fixed)
+        );
+        assert_eq!(
+            change(profile("ice", &[], &[]), profile("crash", &[], &[])),
+            None
+        );
+        assert_eq!(
+            change(profile("error", &["E0308"], &[]), ok()),
+            Some(Change::NewlyAccepted)
+        );
+        assert_eq!(
+            change(ok(), profile("error", &["E0308"], &[])),
+            Some(Change::NewlyRejected)
+        );
+        assert_eq!(
+            change(
+                profile("error", &["E0308"], &[]),
+                profile("error", &["E0277"], &[])
+            ),
+            Some(Change::Errors)
+        );
+        assert_eq!(change(ok(), profile("timeout", &[], &[])), None);
+        assert_eq!(change(ok(), ok()), None);
+        let lints = Regression::between(
+            profile("success", &[], &["dead_code"]),
+            profile("success", &[], &["unused_mut"]),
+        )
+        .expect("lints changed");
+        assert_eq!(
+            lints.to_string(),
+            "lints changed: success -> success, -dead_code, +unused_mut"
+        );
+    }
+
+    #[test]
+    fn fixes_are_not_findings() {
+        assert_eq!(Change::NewIce.severity(), Some(Severity::Ice));
+        assert_eq!(Change::Lints.severity(), Some(Severity::Diagnostic));
+        assert_eq!(Change::Fixed.severity(), None);
+    }
+
+    #[test]
+    fn seeds_are_compiled_by_both_toolchains() {
+        let dir =
+            std::env::temp_dir().join(format!("rustc-fuzz-regression-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        let write = |name: &str, script: &str| {
+            let path = dir.join(name);
+            fs::write(&path, script).expect("writes the rustc");
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755))
+                .expect("makes it runnable");
+            path
+        };
+        let baseline = write("baseline", "#!/bin/sh\ncat >/dev/null\n");
+        let candidate = write(
+            "candidate",
+            "#!/bin/sh\ncat >/dev/null\n\
+             echo '{\"message\":\"mismatched types\",\"level\":\"error\",\"code\":{\"code\":\"E0308\"}}' >&2\n\
+             exit 1\n",
+        );
+        let diff = ToolchainDiff::new(Rustc::new(&baseline), Rustc::new(&candidate));
+        let regression = diff
+            .check("fn f() {}")
+            .expect("runs")
+            .expect("the candidate rejects it");
+        assert_eq!(regression.change, Change::NewlyRejected);
+        assert_eq!(regression.candidate.errors, ["E0308"]);
+        let same = ToolchainDiff::new(Rustc::new(&baseline), Rustc::new(&baseline));
+        assert_eq!(same.check("fn f() {}").expect("runs"), None);
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,91 @@
+//! Diagnostics-stability oracle.
+//!
+//! Compares the errors rustc reports for a seed and for a variant produced
+//! by [`crate::mutate::TriviaMutator`]. Since the variant only differs in
+//! whitespace, comments and identifier lengths, the error codes and their
+//! relative order must match; a difference points at span handling or
+//! diagnostic deduplication.
+
+use std::fmt;
+
+/// The errors a compilation reported, in emission order.
+///
+/// Each entry is the error code (`E0308`) or `error` for errors without
+/// one, such as parse errors. Warnings and the trailing summary lines are
+/// ignored.
+pub fn error_codes(stderr: &str) -> Vec<String> {
+    stderr
+        .lines()
+        .filter_map(|line| {
+            if let Some(rest) = line.strip_prefix("error[") {
+                return rest.split_once(']').map(|(code, _)| code.to_owned());
+            }
+            let message = line.strip_prefix("error: ")?;
+            let summary =
+                message.starts_with("aborting due to") || message.starts_with("could not compile");
+            (!summary).then(|| "error".to_owned())
+        })
+        .collect()
+}
+
+/// Error sequences that should have been identical but were not.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "diagnostics changed: [{}] -> [{}]",
+            self.before.join(", "),
+            self.after.join(", ")
+        )
+    }
+}
+
+/// Checks that two compilations of equivalent inputs reported the same
+/// errors in the same order.
+pub fn check(before_stderr: &str, after_stderr: &str) -> Result<(), Divergence> {
+    compare(error_codes(before_stderr), error_codes(after_stderr))
+}
+
+/// Checks that two sequences from [`error_codes`] are identical.
+pub fn compare(before: Vec<String>, after: Vec<String>) -> Result<(), Divergence> {
+    if before == after {
+        Ok(())
+    } else {
+        Err(Divergence { before, after })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_codes_skip_warnings_and_the_summary() {
+        let stderr = "\
+warning: unused variable: `x`
+error[E0308]: mismatched types
+error: expected `;`, found `}`
+error[E0425]: cannot find value `y` in this scope
+error: aborting due to 3 previous errors
+";
+        assert_eq!(error_codes(stderr), ["E0308", "error", "E0425"]);
+    }
+
+    #[test]
+    fn reordered_errors_diverge() {
+        let before = "error[E0308]: a\nerror[E0425]: b\n";
+        let after = "error[E0425]: b\nerror[E0308]: a\n";
+        assert_eq!(check(before, before), Ok(()));
+        let divergence = check(before, after).expect_err("reordered");
+        assert_eq!(
+            divergence.to_string(),
+            "diagnostics changed: [E0308, E0425] -> [E0425, E0308]"
+        );
+    }
+}
@@ -0,0 +1,458 @@
+//! Plugins: generators, mutators and oracles from outside this crate.
+//!
+//! Fuzzing a compiler fork or a DSL needs components this crate does not
+//! have. They implement [`SeedGenerator`], [`Mutator`] or [`Oracle`] and
+//! are added to a [`Registry`]. Once a registry is [`install`]ed, every
+//! [`Engine`](crate::mutate::Engine) built with `Engine::default` gets its
+//! mutators, and the `rustc-fuzz` commands find its generators and oracles
+//! by name (`generate --generator`, `run --oracle`, `fuzz --oracle`).
+//!
+//! There are two ways in. A program built on this library registers its
+//! components at compile time and installs the registry itself. A shared
+//! library built with [`export_plugin!`](crate::export_plugin) is loaded
+//! at run time instead, with `--plugin FILE` or `plugins` in a campaign
+//! config, when `rustc-fuzz` is built with the `plugins` feature. It only
+//! needs to depend on this crate, not on `rustc-fuzz`. Rust has
+//! no stable ABI, so such a library must be built against the same
+//! version of this crate, with the same compiler; the version is checked,
+//! the compiler is not.
+//!
+//! ```ignore
+//! use fuzzing_core::plugin::{Oracle, Registry};
+//!
+//! struct MyCompiler;
+//!
+//! impl Oracle for MyCompiler {
+//!     fn name(&self) -> &'static str {
+//!         "my-compiler"
+//!     }
+//!
+//!     fn check(&mut self, source: &str) -> io::Result<Option<(Severity, String)>> {
+//!         // Run the fork on `source` and judge its outcome.
+//!     }
+//! }
+//!
+//! fn register(registry: &mut Registry) {
+//!     registry.add_oracle(|| Box::new(MyCompiler));
+//! }
+//!
+//! fuzzing_core::export_plugin!(register);
+//! ```
+
+use std::any::Any;
+use std::fmt;
+use std::io;
+use std::path::Path;
+use std::sync::OnceLock;
+
+use rand::RngCore;
+
+pub use crate::mutate::Mutator;
+
+use crate::gen::{
+    CoherenceGen, ExprGen, HigherRankedGen, Level, ProgramGen, TraitAliasGen, UnsoundGen,
+    VarianceGen,
+};
+use crate::triage::Severity;
+
+/// What a plugin library must have been built against: this crate's
+/// version.
+pub const ABI: &str = env!("CARGO_PKG_VERSION");
+
+/// The registry [`installed`] returns.
+static INSTALLED: OnceLock<Registry> = OnceLock::new();
+
+/// Generates whole seeds.
+pub trait SeedGenerator: Send + Sync {
+    /// Stable name, used to select it and in provenance.
+    fn name(&self) -> &'static str;
+
+    /// Newest Rust its programs may need, recorded in their provenance.
+    /// Generators that write for a nightly rustc keep the default.
+    fn level(&self) -> Level {
+        Level::Latest
+    }
+
+    /// A program drawn with `rng`; the same numbers must give the same
+    /// program.
+    fn generate(&self, rng: &mut dyn RngCore) -> String;
+}
+
+/// Decides whether a seed finds something, usually by compiling it.
+///
+/// Oracles may keep state between seeds; a campaign checking seeds in
+/// parallel makes one for each worker.
+pub trait Oracle: Send {
+    /// Stable name, used to select it and in the event log.
+    fn name(&self) -> &'static str;
+
+    /// How bad what `source` finds is and a one-line summary of it, or
+    /// `None` for nothing. `Err` means the oracle itself failed.
+    fn check(&mut self, source: &str) -> io::Result<Option<(Severity, String)>>;
+}
+
+impl SeedGenerator for ProgramGen {
+    fn name(&self) -> &'static str {
+        "program"
+    }
+
+    fn level(&self) -> Level {
+        self.gen.level
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> String {
+        ProgramGen::generate(self, rng)
+    }
+}
+
+impl SeedGenerator for UnsoundGen {
+    fn name(&self) -> &'static str {
+        "unsound"
+    }
+
+    fn level(&self) -> Level {
+        self.gen.level
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> String {
+        UnsoundGen::generate(self, rng)
+    }
+}
+
+impl SeedGenerator for CoherenceGen {
+    fn name(&self) -> &'static str {
+        "coherence"
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> String {
+        CoherenceGen::generate(self, rng)
+    }
+}
+
+impl SeedGenerator for VarianceGen {
+    fn name(&self) -> &'static str {
+        "variance"
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> String {
+        VarianceGen::generate(self, rng)
+    }
+}
+
+impl SeedGenerator for HigherRankedGen {
+    fn name(&self) -> &'static str {
+        "higher-ranked"
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> String {
+        HigherRankedGen::generate(self, rng)
+    }
+}
+
+impl SeedGenerator for TraitAliasGen {
+    fn name(&self) -> &'static str {
+        "trait-alias"
+    }
+
+    fn generate(&self, rng: &mut dyn RngCore) -> String {
+        TraitAliasGen::generate(self, rng)
+    }
+}
+
+/// The built-in generators `generate --generator` finds by name as it
+/// does a plugin's, those that take one writing for `level`.
+pub fn builtin_generators(level: Level) -> Vec<Box<dyn SeedGenerator>> {
+    vec![
+        Box::new(UnsoundGen {
+            gen: ExprGen {
+                level,
+                ..ExprGen::default()
+            },
+        }),
+        Box::new(CoherenceGen),
+        Box::new(VarianceGen),
+        Box::new(HigherRankedGen),
+        Box::new(TraitAliasGen),
+    ]
+}
+
+type Factory<T> = Box<dyn Fn() -> Box<T> + Send + Sync>;
+
+/// Components added from outside this crate, by name.
+#[derive(Default)]
+pub struct Registry {
+    generators: Vec<Box<dyn SeedGenerator>>,
+    mutators: Vec<(&'static str, Factory<dyn Mutator>)>,
+    oracles: Vec<(&'static str, Factory<dyn Oracle>)>,
+    /// Libraries the components above came from. Declared last, so they
+    /// are unloaded after everything that points into them. Their type is
+    /// erased so the layout, which plugins share, is the same with and
+    /// without the `plugins` feature.
+    libraries: Vec<Box<dyn Any + Send + Sync>>,
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Registry")
+            .field("generators", &self.generator_names().collect::<Vec<_>>())
+            .field("mutators", &self.mutator_names().collect::<Vec<_>>())
+            .field("oracles", &self.oracle_names().collect::<Vec<_>>())
+            .field("libraries", &self.libraries.len())
+            .finish()
+    }
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Registry::default()
+    }
+
+    pub fn add_generator(&mut self, generator: Box<dyn SeedGenerator>) {
+        self.generators.push(generator);
+    }
+
+    /// Adds a mutator; every engine gets one made by `make`.
+    pub fn add_mutator(&mut self, make: impl Fn() -> Box<dyn Mutator> + Send + Sync + 'static) {
+        let name = make().name();
+        self.mutators.push((name, Box::new(make)));
+    }
+
+    /// Adds an oracle; every worker that uses it gets one made by `make`.
+    pub fn add_oracle(&mut self, make: impl Fn() -> Box<dyn Oracle> + Send + Sync + 'static) {
+        let name = make().name();
+        self.oracles.push((name, Box::new(make)));
+    }
+
+    /// The generator called `name`, if one was added.
+    pub fn generator(&self, name: &str) -> Option<&dyn SeedGenerator> {
+        self.generators
+            .iter()
+            .map(|generator| &**generator)
+            .find(|generator| generator.name() == name)
+    }
+
+    /// A new instance of every mutator, in the order they were added.
+    pub fn mutators(&self) -> impl Iterator<Item = Box<dyn Mutator>> + '_ {
+        self.mutators.iter().map(|(_, make)| make())
+    }
+
+    /// A new instance of the oracle called `name`, if one was added.
+    pub fn oracle(&self, name: &str) -> Option<Box<dyn Oracle>> {
+        self.oracles
+            .iter()
+            .find(|(oracle, _)| *oracle == name)
+            .map(|(_, make)| make())
+    }
+
+    pub fn generator_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.generators.iter().map(|generator| generator.name())
+    }
+
+    pub fn mutator_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.mutators.iter().map(|(name, _)| *name)
+    }
+
+    pub fn oracle_names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.oracles.iter().map(|(name, _)| *name)
+    }
+
+    /// Loads the plugin library at `path` and adds what it registers.
+    /// Without the `plugins` feature this always fails.
+    ///
+    /// # Safety
+    ///
+    /// Loading a library runs its initializers, and its components run
+    /// as part of this process. It must be a plugin built with
+    /// [`export_plugin!`](crate::export_plugin) against this version of
+    /// this crate and by the same compiler, or anything can happen.
+    pub unsafe fn load(&mut self, path: &Path) -> Result<(), PluginError> {
+        #[cfg(not(feature = "plugins"))]
+        {
+            let _ = path;
+            Err(PluginError::Unsupported)
+        }
+        #[cfg(feature = "plugins")]
+        {
+            let library = libloading::Library::new(path).map_err(PluginError::Load)?;
+            {
+                let abi: libloading::Symbol<fn() -> &'static str> = library
+                    .get(b"rustc_fuzz_plugin_abi")
+                    .map_err(PluginError::Load)?;
+                let abi = abi();
+                if abi != ABI {
+                    return Err(PluginError::Version(abi.to_owned()));
+                }
+                let register: libloading::Symbol<fn(&mut Registry)> = library
+                    .get(b"rustc_fuzz_plugin_register")
+                    .map_err(PluginError::Load)?;
+                register(self);
+            }
+            self.libraries.push(Box::new(library));
+            Ok(())
+        }
+    }
+}
+
+/// Why a plugin could not be loaded.
+#[derive(Debug)]
+pub enum PluginError {
+    /// The library or one of its symbols could not be loaded.
+    #[cfg(feature = "plugins")]
+    Load(libloading::Error),
+    /// It was built against another version of this crate.
+    Version(String),
+    /// This build has no `plugins` feature.
+    Unsupported,
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            #[cfg(feature = "plugins")]
+            PluginError::Load(e) => write!(f, "{e}"),
+            PluginError::Version(version) => write!(
+                f,
+                "built against fuzzing-core {version}, but this is fuzzing-core {ABI}"
+            ),
+            PluginError::Unsupported => {
+                f.write_str("this rustc-fuzz was built without the `plugins` feature")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PluginError {}
+
+/// Makes `registry` the one [`installed`] returns. Only the first call has
+/// an effect; returns whether it was this one.
+pub fn install(registry: Registry) -> bool {
+    INSTALLED.set(registry).is_ok()
+}
+
+/// The installed registry, if any.
+pub fn installed() -> Option<&'static Registry> {
+    INSTALLED.get()
+}
+
+/// Exports `register`, a `fn(&mut Registry)`, from a plugin library for
+/// [`Registry::load`]. Build the library as a `cdylib`.
+#[macro_export]
+macro_rules! export_plugin {
+    ($register:path) => {
+        #[no_mangle]
+        pub fn rustc_fuzz_plugin_abi() -> &'static str {
+            $crate::plugin::ABI
+        }
+
+        #[no_mangle]
+        pub fn rustc_fuzz_plugin_register(registry: &mut $crate::plugin::Registry) {
+            $register(registry)
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn only_the_unsound_builtin_follows_the_level() {
+        for gen in builtin_generators(Level::Rust1_54) {
+            let expected = if gen.name() == "unsound" {
+                Level::Rust1_54
+            } else {
+                Level::Latest
+            };
+            assert_eq!(gen.level(), expected, "{}", gen.name());
+        }
+    }
+
+    struct Constant;
+
+    impl SeedGenerator for Constant {
+        fn name(&self) -> &'static str {
+            "constant"
+        }
+
+        fn generate(&self, rng: &mut dyn RngCore) -> String {
+            format!("const N: u32 = {};\n", rng.next_u32())
+        }
+    }
+
+    struct Reverse;
+
+    impl Mutator for Reverse {
+        fn name(&self) -> &'static str {
+            "reverse"
+        }
+
+        fn mutate(&self, input: &str, _rng: &mut dyn RngCore) -> Option<String> {
+            Some(input.chars().rev().collect())
+        }
+    }
+
+    /// Finds an ICE in every seed it sees after the first.
+    #[derive(Default)]
+    struct Counting {
+        seen: usize,
+    }
+
+    impl Oracle for Counting {
+        fn name(&self) -> &'static str {
+            "counting"
+        }
+
+        fn check(&mut self, _source: &str) -> io::Result<Option<(Severity, String)>> {
+            self.seen += 1;
+            Ok((self.seen > 1).then(|| (Severity::Ice, format!("seed {}", self.seen))))
+        }
+    }
+
+    #[test]
+    fn components_are_found_by_name() {
+        let mut registry = Registry::new();
+        registry.add_generator(Box::new(Constant));
+        registry.add_mutator(|| Box::new(Reverse));
+        registry.add_oracle(|| Box::<Counting>::default());
+        assert_eq!(registry.generator_names().collect::<Vec<_>>(), ["constant"]);
+        assert_eq!(registry.mutator_names().collect::<Vec<_>>(), ["reverse"]);
+        assert_eq!(registry.oracle_names().collect::<Vec<_>>(), ["counting"]);
+        let generator = registry.generator("constant").expect("registered");
+        assert_eq!(generator.level(), Level::Latest);
+        assert!(registry.generator("program").is_none());
+        let mut rng = StdRng::seed_from_u64(0);
+        let mutated: Vec<Option<String>> = registry
+            .mutators()
+            .map(|mutator| mutator.mutate("ab", &mut rng))
+            .collect();
+        assert_eq!(mutated, [Some("ba".to_owned())]);
+
+        // Each worker gets an oracle of its own.
+        let mut first = registry.oracle("counting").expect("registered");
+        assert_eq!(first.check("").expect("checks"), None);
+        assert!(first.check("").expect("checks").is_some());
+        let mut second = registry.oracle("counting").expect("registered");
+        assert_eq!(second.check("").expect("checks"), None);
+        assert!(registry.oracle("rustc").is_none());
+    }
+
+    #[cfg(not(feature = "plugins"))]
+    #[test]
+    fn loading_needs_the_plugins_feature() {
+        let mut registry = Registry::new();
+        // SAFETY: nothing is loaded without the feature.
+        let error = unsafe { registry.load(Path::new("libplugin.so")) }.expect_err("no feature");
+        assert!(matches!(error, PluginError::Unsupported), "{error}");
+    }
+
+    #[test]
+    fn version_mismatches_name_both_versions() {
+        let error = PluginError::Version("0.0.1".to_owned());
+        assert_eq!(
+            error.to_string(),
+            format!("built against fuzzing-core 0.0.1, but this is fuzzing-core {ABI}")
+        );
+    }
+}
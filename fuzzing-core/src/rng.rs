@@ -0,0 +1,100 @@
+//! Splittable random streams.
+//!
+//! A campaign makes random choices in several places: which seed to
+//! mutate next, how to mutate it, what to generate. Drawn from one stream,
+//! those choices are tangled: an operator that draws one number more
+//! shifts every later choice of the scheduler, and the thousandth program
+//! of a run can only be had by generating the 999 before it. A
+//! [`SplitRng`] derives a separate generator for every [`Stream`] and step
+//! from one master seed instead, so each choice depends on the seed, the
+//! component, the step and nothing else.
+
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha12Rng;
+
+/// The components of a campaign that draw random numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Stream {
+    /// Picks the seed to mutate next.
+    Scheduler,
+    /// Picks the operator and everything it draws, including the programs
+    /// regenerated inside a seed.
+    Mutator,
+    /// Generates whole programs.
+    Generator,
+}
+
+impl Stream {
+    /// The ChaCha stream the component's generators are derived from.
+    /// Never renumber these: it would change every recorded campaign.
+    fn id(self) -> u64 {
+        match self {
+            Stream::Scheduler => 1,
+            Stream::Mutator => 2,
+            Stream::Generator => 3,
+        }
+    }
+}
+
+/// A master seed that generators for any component and step derive from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SplitRng {
+    seed: u64,
+}
+
+impl SplitRng {
+    pub fn new(seed: u64) -> Self {
+        SplitRng { seed }
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The generator for step `index` of `stream`. Each is seeded with 32
+    /// bytes of its own from the master ChaCha stream for `stream`, so the
+    /// same step always gets the same numbers however many earlier steps
+    /// drew, or were skipped.
+    pub fn rng(&self, stream: Stream, index: u64) -> ChaCha12Rng {
+        let mut master = ChaCha12Rng::seed_from_u64(self.seed);
+        master.set_stream(stream.id());
+        master.set_word_pos(u128::from(index) * 8);
+        let mut seed = [0; 32];
+        master.fill_bytes(&mut seed);
+        ChaCha12Rng::from_seed(seed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_step_has_a_stream_of_its_own() {
+        let split = SplitRng::new(7);
+        let draw = |split: SplitRng, stream, index| split.rng(stream, index).next_u64();
+        assert_eq!(
+            draw(split, Stream::Mutator, 3),
+            draw(split, Stream::Mutator, 3)
+        );
+        let mut draws = vec![
+            draw(split, Stream::Mutator, 3),
+            draw(split, Stream::Mutator, 4),
+            draw(split, Stream::Scheduler, 3),
+            draw(split, Stream::Generator, 3),
+            draw(SplitRng::new(8), Stream::Mutator, 3),
+        ];
+        draws.sort_unstable();
+        draws.dedup();
+        assert_eq!(draws.len(), 5);
+    }
+
+    #[test]
+    fn recorded_campaigns_replay() {
+        // Changing this value changes every recorded campaign's choices.
+        assert_eq!(
+            SplitRng::new(1).rng(Stream::Generator, 0).next_u64(),
+            16976179546297826892
+        );
+    }
+}
@@ -0,0 +1,42 @@
+//! Byte encoding of seeds.
+//!
+//! Byte-oriented fuzzers (libFuzzer, AFL++) hand us raw buffers. A seed's
+//! encoding is simply its UTF-8 source, so corpus files can be used as
+//! fuzzer inputs unchanged. Decoding is total: invalid UTF-8 is replaced
+//! with U+FFFD, which rustc rejects in the lexer like any other stray
+//! character.
+
+use std::borrow::Cow;
+
+/// Decodes a fuzzer buffer into source text.
+pub fn decode(bytes: &[u8]) -> Cow<'_, str> {
+    String::from_utf8_lossy(bytes)
+}
+
+/// Writes `source` into `buf`, returning the encoded length, or `None` if
+/// it does not fit. Seeds are never truncated, since cutting source text
+/// almost always produces a parse error.
+pub fn encode_into(source: &str, buf: &mut [u8]) -> Option<usize> {
+    let bytes = source.as_bytes();
+    let dest = buf.get_mut(..bytes.len())?;
+    dest.copy_from_slice(bytes);
+    Some(bytes.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn seeds_are_not_truncated() {
+        let mut buf = [0; 8];
+        assert_eq!(encode_into("fn f(){}", &mut buf), Some(8));
+        assert_eq!(decode(&buf), "fn f(){}");
+        assert_eq!(encode_into("fn f() {}", &mut buf), None);
+    }
+
+    #[test]
+    fn invalid_utf8_decodes_to_replacement_characters() {
+        assert_eq!(decode(b"fn \xff() {}"), "fn \u{FFFD}() {}");
+    }
+}
@@ -0,0 +1,39 @@
+//! Fuzz target bodies shared by the harnesses.
+//!
+//! The cargo-fuzz targets under `fuzz/`, the AFL++ binary and the
+//! honggfuzz binary call the same functions, so an input that fails in
+//! one harness reproduces in the others. Targets panic on a finding.
+
+use crate::{lex, seed};
+
+/// Decodes `data` as source, then checks that the lexer is lossless and
+/// that pretty-printed output parses again.
+pub fn parse(data: &[u8]) {
+    let source = seed::decode(data);
+    let tokens = lex::tokenize(&source);
+    let relexed: String = tokens.iter().map(|t| t.text(&source)).collect();
+    assert_eq!(relexed, source);
+
+    if let Ok(file) = syn::parse_file(&source) {
+        let printed = prettyplease::unparse(&file);
+        syn::parse_file(&printed).expect("pretty-printed source must parse");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_accepts_anything_the_tools_agree_on() {
+        for data in [
+            &b"fn main() { let x = [1, 2][0]; }"[..],
+            b"fn f( {",
+            b"\xff\xfe /* unterminated",
+            b"r#\"raw\"# 'a' 'lifetime",
+            b"",
+        ] {
+            parse(data);
+        }
+    }
+}
@@ -0,0 +1,675 @@
+//! Toolchain management.
+//!
+//! A campaign that tracks nightly should not need someone to run `rustup
+//! update` every morning, and bisection and channel matrices need many
+//! nightlies installed for a while and then gone. [`Toolchains`] installs
+//! them with rustup, or straight from the dist server where there is no
+//! rustup, from a declarative [`Spec`]: `nightly` for the latest nightly,
+//! refreshed once a day, `nightly-2024-06-01` for one date, and
+//! `nightly-2024-06-01..nightly-2024-07-01` for every nightly between two.
+//! Latest nightlies are installed under their date, so a finding can name
+//! the exact one it was found with.
+//!
+//! Toolchains it installs are recorded in `toolchains.json` in its store
+//! directory, with when they were last used. [`Toolchains::gc`]
+//! uninstalls those unused for a while unless they are pinned; toolchains
+//! installed some other way are never touched.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::triage::bisect::Date;
+
+/// How far back [`Toolchains::latest`] looks for a nightly that installs.
+pub const LATEST_LOOKBACK_DAYS: i64 = 7;
+
+/// Which toolchains a campaign wants.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Spec {
+    /// The latest nightly, looked for again once it is older than
+    /// [`Toolchains::refresh`].
+    Latest,
+    /// The nightly of one date.
+    Nightly(Date),
+    /// Every nightly from the first date to the second, both included.
+    Range(Date, Date),
+    /// Any other rustup toolchain name, such as `stable` or `1.79.0`.
+    Named(String),
+}
+
+impl fmt::Display for Spec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Spec::Latest => f.write_str("nightly"),
+            Spec::Nightly(date) => f.write_str(&date.toolchain()),
+            Spec::Range(start, end) => write!(f, "{}..{}", start.toolchain(), end.toolchain()),
+            Spec::Named(name) => f.write_str(name),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BadSpec(pub String);
+
+impl fmt::Display for BadSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "bad toolchain spec {:?}; expected nightly, nightly-YYYY-MM-DD, \
+             nightly-YYYY-MM-DD..nightly-YYYY-MM-DD or a rustup toolchain name",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for BadSpec {}
+
+impl FromStr for Spec {
+    type Err = BadSpec;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bad = || BadSpec(s.to_owned());
+        let date = |text: &str| Date::parse(text.strip_prefix("nightly-").unwrap_or(text));
+        match s.trim() {
+            "" => Err(bad()),
+            "nightly" => Ok(Spec::Latest),
+            text => match text.split_once("..") {
+                Some((start, end)) => {
+                    let (start, end) = (date(start).ok_or_else(bad)?, date(end).ok_or_else(bad)?);
+                    if start > end {
+                        return Err(bad());
+                    }
+                    Ok(Spec::Range(start, end))
+                }
+                None => match text.strip_prefix("nightly-") {
+                    // `nightly-x86_64-unknown-linux-gnu` is a name too.
+                    Some(rest) if rest.starts_with(|c: char| c.is_ascii_digit()) => {
+                        Ok(Spec::Nightly(Date::parse(rest).ok_or_else(bad)?))
+                    }
+                    _ => Ok(Spec::Named(text.to_owned())),
+                },
+            },
+        }
+    }
+}
+
+/// Where toolchains are installed from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    /// `rustup toolchain install`, with the minimal profile.
+    #[default]
+    Rustup,
+    /// The rustc and rust-std tarballs of a dated nightly from the dist
+    /// server, unpacked into the store directory.
+    Dist,
+}
+
+impl Source {
+    pub fn name(self) -> &'static str {
+        match self {
+            Source::Rustup => "rustup",
+            Source::Dist => "dist",
+        }
+    }
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownSource(pub String);
+
+impl fmt::Display for UnknownSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown toolchain source {:?}; expected rustup or dist",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownSource {}
+
+impl FromStr for Source {
+    type Err = UnknownSource;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Source::Rustup, Source::Dist]
+            .into_iter()
+            .find(|source| source.name() == s)
+            .ok_or_else(|| UnknownSource(s.to_owned()))
+    }
+}
+
+/// A toolchain [`Toolchains`] installed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Installed {
+    pub source: Source,
+    /// Seconds since the epoch.
+    pub installed: u64,
+    pub used: u64,
+    /// Kept by [`Toolchains::gc`] however long it goes unused.
+    #[serde(default)]
+    pub pinned: bool,
+}
+
+/// The latest nightly found, and when.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Latest {
+    pub toolchain: String,
+    pub checked: u64,
+}
+
+/// The contents of `toolchains.json`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct State {
+    #[serde(default)]
+    pub installed: BTreeMap<String, Installed>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest: Option<Latest>,
+}
+
+/// Installs, finds and removes toolchains.
+#[derive(Debug, Clone)]
+pub struct Toolchains {
+    pub rustup: PathBuf,
+    /// Where the state file and dist installs live.
+    pub store: PathBuf,
+    pub source: Source,
+    /// How old the latest nightly may get before it is looked for again.
+    pub refresh: Duration,
+    /// The dist server, for [`Source::Dist`].
+    pub dist_server: String,
+    /// The host triple of dist tarballs.
+    pub host: String,
+}
+
+impl Toolchains {
+    /// The state file's name in the store directory.
+    pub const FILE: &'static str = "toolchains.json";
+
+    pub fn new(store: impl Into<PathBuf>) -> Self {
+        Toolchains {
+            rustup: PathBuf::from("rustup"),
+            store: store.into(),
+            source: Source::default(),
+            refresh: Duration::from_secs(24 * 60 * 60),
+            dist_server: "https://static.rust-lang.org".to_owned(),
+            host: host(),
+        }
+    }
+
+    /// The toolchain names `spec` stands for, oldest first. Only the
+    /// latest nightly is installed to find out which it is.
+    pub fn resolve(&self, spec: &Spec) -> io::Result<Vec<String>> {
+        Ok(match spec {
+            Spec::Latest => vec![self.latest()?],
+            Spec::Nightly(date) => vec![date.toolchain()],
+            Spec::Range(start, end) => {
+                let mut names = Vec::new();
+                let mut date = *start;
+                while date <= *end {
+                    names.push(date.toolchain());
+                    date = date.add_days(1);
+                }
+                names
+            }
+            Spec::Named(name) => vec![name.clone()],
+        })
+    }
+
+    /// The latest nightly, installed. It is the one found last time while
+    /// that is younger than [`Toolchains::refresh`]; after that the
+    /// nightlies of the last few days are tried newest first, and the old
+    /// one is kept if none installs, as when offline.
+    pub fn latest(&self) -> io::Result<String> {
+        let previous = self.state()?.latest;
+        if let Some(latest) = &previous {
+            if now().saturating_sub(latest.checked) < self.refresh.as_secs()
+                && self.rustc(&latest.toolchain)?.is_some()
+            {
+                return Ok(latest.toolchain.clone());
+            }
+        }
+        let today = Date::today();
+        for days in 0..=LATEST_LOOKBACK_DAYS {
+            let toolchain = today.add_days(-days).toolchain();
+            if self.install(&toolchain)? {
+                let mut state = self.state()?;
+                state.latest = Some(Latest {
+                    toolchain: toolchain.clone(),
+                    checked: now(),
+                });
+                self.save(&state)?;
+                return Ok(toolchain);
+            }
+        }
+        match previous {
+            Some(latest) if self.rustc(&latest.toolchain)?.is_some() => Ok(latest.toolchain),
+            _ => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no nightly of the last {LATEST_LOOKBACK_DAYS} days could be installed"),
+            )),
+        }
+    }
+
+    /// The rustc of `toolchain`, if it is installed.
+    pub fn rustc(&self, toolchain: &str) -> io::Result<Option<PathBuf>> {
+        let dist = self.dist_prefix(toolchain).join("bin").join("rustc");
+        if dist.exists() {
+            return Ok(Some(dist));
+        }
+        if self.source == Source::Dist {
+            return Ok(None);
+        }
+        Ok(self
+            .rustup(&["which", "rustc", "--toolchain", toolchain])?
+            .map(|path| PathBuf::from(path.trim())))
+    }
+
+    /// Installs `toolchain` unless it is installed already. Returns
+    /// whether it is installed now; a nightly that was never published
+    /// is not.
+    pub fn install(&self, toolchain: &str) -> io::Result<bool> {
+        if self.rustc(toolchain)?.is_some() {
+            return Ok(true);
+        }
+        tracing::info!(toolchain, source = self.source.name(), "installing");
+        let installed = match self.source {
+            Source::Rustup => self
+                .rustup(&[
+                    "toolchain",
+                    "install",
+                    toolchain,
+                    "--profile",
+                    "minimal",
+                    "--no-self-update",
+                ])?
+                .is_some(),
+            Source::Dist => self.install_dist(toolchain)?,
+        };
+        if installed {
+            let mut state = self.state()?;
+            state.installed.insert(
+                toolchain.to_owned(),
+                Installed {
+                    source: self.source,
+                    installed: now(),
+                    used: now(),
+                    pinned: false,
+                },
+            );
+            self.save(&state)?;
+        }
+        Ok(installed)
+    }
+
+    /// The rustc of `toolchain`, installing it first if need be, and
+    /// marks it used.
+    pub fn ensure(&self, toolchain: &str) -> io::Result<PathBuf> {
+        let rustc = if self.install(toolchain)? {
+            self.rustc(toolchain)?
+        } else {
+            None
+        };
+        let rustc = rustc.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("cannot install {toolchain}"),
+            )
+        })?;
+        let mut state = self.state()?;
+        if let Some(installed) = state.installed.get_mut(toolchain) {
+            installed.used = now();
+            self.save(&state)?;
+        }
+        Ok(rustc)
+    }
+
+    /// Uninstalls `toolchain`, however it was installed.
+    pub fn uninstall(&self, toolchain: &str) -> io::Result<()> {
+        let mut state = self.state()?;
+        let source = state.installed.remove(toolchain).map(|i| i.source);
+        if state
+            .latest
+            .as_ref()
+            .is_some_and(|latest| latest.toolchain == toolchain)
+        {
+            state.latest = None;
+        }
+        self.save(&state)?;
+        let prefix = self.dist_prefix(toolchain);
+        if source == Some(Source::Dist) || prefix.exists() {
+            return fs::remove_dir_all(&prefix);
+        }
+        self.rustup(&["toolchain", "uninstall", toolchain])?;
+        Ok(())
+    }
+
+    /// Pins or unpins a toolchain installed here. Returns whether it was.
+    pub fn set_pinned(&self, toolchain: &str, pinned: bool) -> io::Result<bool> {
+        let mut state = self.state()?;
+        let Some(installed) = state.installed.get_mut(toolchain) else {
+            return Ok(false);
+        };
+        installed.pinned = pinned;
+        self.save(&state)?;
+        Ok(true)
+    }
+
+    /// Uninstalls the toolchains installed here that are not pinned, not
+    /// the latest nightly, not in `keep` and unused for `max_age`, or only
+    /// lists them if `dry_run`. Returns their names.
+    pub fn gc(&self, keep: &[String], max_age: Duration, dry_run: bool) -> io::Result<Vec<String>> {
+        let state = self.state()?;
+        let latest = state.latest.as_ref().map(|latest| &latest.toolchain);
+        let stale: Vec<String> = state
+            .installed
+            .iter()
+            .filter(|(name, installed)| {
+                !installed.pinned
+                    && Some(*name) != latest
+                    && !keep.contains(name)
+                    && now().saturating_sub(installed.used) >= max_age.as_secs()
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        if !dry_run {
+            for name in &stale {
+                self.uninstall(name)?;
+            }
+        }
+        Ok(stale)
+    }
+
+    /// The recorded state, empty if there is none yet.
+    pub fn state(&self) -> io::Result<State> {
+        match fs::read_to_string(self.store.join(Self::FILE)) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(State::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn save(&self, state: &State) -> io::Result<()> {
+        fs::create_dir_all(&self.store)?;
+        let path = self.store.join(Self::FILE);
+        let partial = path.with_extension("json.partial");
+        let mut json = serde_json::to_string_pretty(state)?;
+        json.push('\n');
+        fs::write(&partial, json)?;
+        fs::rename(&partial, &path)
+    }
+
+    fn dist_prefix(&self, toolchain: &str) -> PathBuf {
+        self.store.join("dist").join(toolchain)
+    }
+
+    /// Downloads and installs the rustc and rust-std of a dated nightly.
+    /// Returns false if the dist server does not have them.
+    fn install_dist(&self, toolchain: &str) -> io::Result<bool> {
+        let Some(date) = toolchain.strip_prefix("nightly-").and_then(Date::parse) else {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("only dated nightlies install from dist, not {toolchain}"),
+            ));
+        };
+        let prefix = std::path::absolute(self.dist_prefix(toolchain))?;
+        let work = prefix.with_extension("partial");
+        let _ = fs::remove_dir_all(&work);
+        fs::create_dir_all(&work)?;
+        let result = self.unpack_dist(date, &work, &prefix);
+        let _ = fs::remove_dir_all(&work);
+        if !matches!(result, Ok(true)) {
+            let _ = fs::remove_dir_all(&prefix);
+        }
+        result
+    }
+
+    fn unpack_dist(&self, date: Date, work: &Path, prefix: &Path) -> io::Result<bool> {
+        for component in ["rustc", "rust-std"] {
+            let base = format!("{component}-nightly-{}", self.host);
+            let url = format!("{}/dist/{date}/{base}.tar.xz", self.dist_server);
+            let archive = work.join(format!("{base}.tar.xz"));
+            let fetched = Command::new("curl")
+                .args(["--fail", "--silent", "--show-error", "--location"])
+                .arg("--output")
+                .arg(&archive)
+                .arg(&url)
+                .output()
+                .map_err(|e| io::Error::new(e.kind(), format!("running curl: {e}")))?;
+            if !fetched.status.success() {
+                return Ok(false);
+            }
+            run(Command::new("tar")
+                .arg("-xJf")
+                .arg(&archive)
+                .arg("-C")
+                .arg(work))?;
+            let mut prefix_arg = std::ffi::OsString::from("--prefix=");
+            prefix_arg.push(prefix);
+            run(Command::new("sh")
+                .arg(work.join(&base).join("install.sh"))
+                .arg(prefix_arg)
+                .arg("--disable-ldconfig"))?;
+        }
+        Ok(true)
+    }
+
+    /// Runs rustup, returning its stdout if it succeeds.
+    fn rustup(&self, args: &[&str]) -> io::Result<Option<String>> {
+        let output = Command::new(&self.rustup)
+            .args(args)
+            .output()
+            .map_err(|e| {
+                io::Error::new(e.kind(), format!("running {}: {e}", self.rustup.display()))
+            })?;
+        Ok(output
+            .status
+            .success()
+            .then(|| String::from_utf8_lossy(&output.stdout).into_owned()))
+    }
+}
+
+/// Runs `command`, failing with its stderr if it fails.
+fn run(command: &mut Command) -> io::Result<()> {
+    let program = command.get_program().to_string_lossy().into_owned();
+    let output = command
+        .output()
+        .map_err(|e| io::Error::new(e.kind(), format!("running {program}: {e}")))?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{program}: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(())
+}
+
+/// The triple of the host, as dist names it.
+fn host() -> String {
+    let arch = std::env::consts::ARCH;
+    match std::env::consts::OS {
+        "macos" => format!("{arch}-apple-darwin"),
+        "freebsd" => format!("{arch}-unknown-freebsd"),
+        _ => format!("{arch}-unknown-linux-gnu"),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// A rustup whose toolchains are marker files in `dir`, which cannot
+    /// install the toolchains listed in `dir/unpublished`, and which logs
+    /// its arguments to `dir/log`.
+    fn toolchains(dir: &Path) -> Toolchains {
+        fs::create_dir_all(dir).expect("creates the directory");
+        let rustup = dir.join("rustup");
+        fs::write(
+            &rustup,
+            format!(
+                "#!/bin/sh\nd={}\necho \"$*\" >> $d/log\ncase \"$1\" in\n\
+                 which) [ -e \"$d/has-$4\" ] && echo \"/toolchains/$4/bin/rustc\";;\n\
+                 toolchain) case \"$2\" in\n  \
+                 install) grep -qx \"$3\" $d/unpublished 2>/dev/null && exit 1; touch \"$d/has-$3\";;\n  \
+                 uninstall) rm \"$d/has-$3\";;\n  esac;;\nesac\n",
+                dir.display()
+            ),
+        )
+        .expect("writes the rustup");
+        fs::set_permissions(&rustup, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        Toolchains {
+            rustup,
+            ..Toolchains::new(dir.join("store"))
+        }
+    }
+
+    fn installs(dir: &Path) -> usize {
+        fs::read_to_string(dir.join("log"))
+            .unwrap_or_default()
+            .lines()
+            .filter(|line| line.starts_with("toolchain install"))
+            .count()
+    }
+
+    fn dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!(
+            "rustc-fuzz-toolchains-{name}-{}",
+            std::process::id()
+        ))
+    }
+
+    #[test]
+    fn specs_parse_and_print_back() {
+        let date = |text| Date::parse(text).expect("a date");
+        for (text, spec) in [
+            ("nightly", Spec::Latest),
+            ("nightly-2024-06-01", Spec::Nightly(date("2024-06-01"))),
+            (
+                "nightly-2024-06-30..nightly-2024-07-02",
+                Spec::Range(date("2024-06-30"), date("2024-07-02")),
+            ),
+            (
+                "nightly-x86_64-unknown-linux-gnu",
+                Spec::Named("nightly-x86_64-unknown-linux-gnu".to_owned()),
+            ),
+            ("1.79.0", Spec::Named("1.79.0".to_owned())),
+        ] {
+            assert_eq!(text.parse::<Spec>(), Ok(spec.clone()));
+            assert_eq!(spec.to_string(), text);
+        }
+        assert!("".parse::<Spec>().is_err());
+        assert!("nightly-2024-13-01".parse::<Spec>().is_err());
+        assert!("nightly-2024-07-02..nightly-2024-06-30"
+            .parse::<Spec>()
+            .is_err());
+        assert_eq!("dist".parse::<Source>(), Ok(Source::Dist));
+        assert!("apt".parse::<Source>().is_err());
+
+        let range = "nightly-2024-06-30..nightly-2024-07-02"
+            .parse()
+            .expect("a range");
+        assert_eq!(
+            Toolchains::new(dir("resolve"))
+                .resolve(&range)
+                .expect("resolves"),
+            [
+                "nightly-2024-06-30",
+                "nightly-2024-07-01",
+                "nightly-2024-07-02"
+            ]
+        );
+    }
+
+    #[test]
+    fn installs_once_and_collects_what_goes_unused() {
+        let dir = dir("gc");
+        let toolchains = toolchains(&dir);
+        for name in [
+            "nightly-2024-06-01",
+            "nightly-2024-06-02",
+            "nightly-2024-06-03",
+        ] {
+            assert_eq!(
+                toolchains.ensure(name).expect("installs"),
+                PathBuf::from(format!("/toolchains/{name}/bin/rustc"))
+            );
+        }
+        toolchains
+            .ensure("nightly-2024-06-01")
+            .expect("is installed");
+        assert_eq!(installs(&dir), 3);
+        fs::write(dir.join("unpublished"), "nightly-2099-01-01\n").expect("writes");
+        let error = toolchains
+            .ensure("nightly-2099-01-01")
+            .expect_err("never published");
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+
+        assert!(toolchains
+            .set_pinned("nightly-2024-06-02", true)
+            .expect("pins"));
+        assert!(!toolchains.set_pinned("stable", true).expect("pins"));
+        let keep = ["nightly-2024-06-03".to_owned()];
+        let stale = toolchains.gc(&keep, Duration::ZERO, true).expect("lists");
+        assert_eq!(stale, ["nightly-2024-06-01"]);
+        assert!(dir.join("has-nightly-2024-06-01").exists());
+        toolchains
+            .gc(&keep, Duration::ZERO, false)
+            .expect("collects");
+        assert!(!dir.join("has-nightly-2024-06-01").exists());
+        let state = toolchains.state().expect("reads");
+        let left: Vec<&str> = state.installed.keys().map(String::as_str).collect();
+        assert_eq!(left, ["nightly-2024-06-02", "nightly-2024-06-03"]);
+        // Nothing goes before it has been unused long enough.
+        assert!(toolchains
+            .gc(&[], Duration::from_secs(3600), false)
+            .expect("collects")
+            .is_empty());
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+
+    #[test]
+    fn the_latest_nightly_is_the_newest_that_installs() {
+        let dir = dir("latest");
+        let toolchains = toolchains(&dir);
+        let today = Date::today();
+        fs::write(dir.join("unpublished"), format!("{}\n", today.toolchain())).expect("writes");
+        let yesterday = today.add_days(-1).toolchain();
+        assert_eq!(toolchains.latest().expect("installs"), yesterday);
+        assert_eq!(
+            toolchains.resolve(&Spec::Latest).expect("resolves"),
+            std::slice::from_ref(&yesterday)
+        );
+        assert_eq!(installs(&dir), 2);
+        // The latest nightly is never collected.
+        assert!(toolchains
+            .gc(&[], Duration::ZERO, true)
+            .expect("lists")
+            .is_empty());
+        toolchains.uninstall(&yesterday).expect("uninstalls");
+        assert_eq!(toolchains.state().expect("reads"), State::default());
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
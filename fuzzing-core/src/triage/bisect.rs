@@ -0,0 +1,538 @@
+//! Regression bisection.
+//!
+//! An ICE report is filed faster and fixed sooner when it names the
+//! nightly that introduced the bug. [`Bisector`] installs nightlies with
+//! [`Toolchains`] and binary-searches the dates for the first one on which the
+//! seed crashes with the bucket's [`Signature`]. Signatures leave out
+//! compiler line numbers, so the same bug matches across months of
+//! nightlies; a different crash counts as not reproducing it. Nightlies
+//! that are missing or fail to install are skipped.
+//!
+//! Given a rust-lang/rust checkout, [`Bisector::commits`] goes on to
+//! bisect the merge commits between the two nightlies with CI artifacts
+//! installed by `rustup-toolchain-install-master`, and reads the pull
+//! request number off the first bad merge. CI keeps artifacts for about
+//! half a year, so older ranges stop at the nightly.
+
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::harness::{ExecOutcome, Rustc};
+use crate::toolchains::Toolchains;
+use crate::triage::Signature;
+
+/// A nightly's date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    /// Parses `YYYY-MM-DD`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let mut parts = text.trim().splitn(3, '-');
+        let year = parts.next()?.parse().ok()?;
+        let month = parts.next()?.parse().ok()?;
+        let day = parts.next()?.parse().ok()?;
+        let date = Date { year, month, day };
+        (Date::from_days(date.days()) == date).then_some(date)
+    }
+
+    /// The current date in UTC.
+    pub fn today() -> Self {
+        let secs = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        Date::from_days((secs / 86_400) as i64)
+    }
+
+    /// The date `days` later, or earlier if negative.
+    pub fn add_days(self, days: i64) -> Self {
+        Date::from_days(self.days() + days)
+    }
+
+    /// Days since 1970-01-01.
+    fn days(self) -> i64 {
+        // Howard Hinnant's `days_from_civil`.
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let month = i64::from(self.month);
+        let day_of_year =
+            (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(self.day) - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        era * 146_097 + day_of_era - 719_468
+    }
+
+    fn from_days(days: i64) -> Self {
+        let days = days + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days - era * 146_097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let mp = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        Date {
+            year: year as i32,
+            month,
+            day,
+        }
+    }
+
+    /// The rustup toolchain name of the nightly of this date.
+    pub fn toolchain(self) -> String {
+        format!("nightly-{self}")
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+/// What one toolchain does with the seed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+    /// It does not crash with the signature.
+    Good,
+    /// It does.
+    Bad,
+    /// The toolchain could not be installed.
+    Skip,
+}
+
+/// Finds where `candidates` turn bad, given that the first is good, the
+/// last is bad, and everything after a bad one is bad too. Skipped
+/// candidates are dropped. Returns the last good and first bad ones.
+pub fn bisect<T: Clone, E>(
+    mut candidates: Vec<T>,
+    mut test: impl FnMut(&T) -> Result<Verdict, E>,
+) -> Result<(T, T), E> {
+    assert!(candidates.len() >= 2, "bisection needs both ends");
+    let (mut good, mut bad) = (0, candidates.len() - 1);
+    while bad - good > 1 {
+        let mid = good + (bad - good) / 2;
+        match test(&candidates[mid])? {
+            Verdict::Good => good = mid,
+            Verdict::Bad => bad = mid,
+            Verdict::Skip => {
+                candidates.remove(mid);
+                bad -= 1;
+            }
+        }
+    }
+    Ok((candidates[good].clone(), candidates[bad].clone()))
+}
+
+/// Where a crash regressed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bisection {
+    /// The id of the signature that was bisected.
+    pub signature: String,
+    /// The last nightly that does not crash with it, as a toolchain name.
+    pub last_good: String,
+    /// The first nightly that does.
+    pub first_bad: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commits: Option<CommitRange>,
+}
+
+/// The merge commits a crash regressed between.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommitRange {
+    pub last_good: String,
+    pub first_bad: String,
+    /// The rust-lang/rust pull request the first bad merge merged.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pr: Option<u64>,
+}
+
+impl fmt::Display for Bisection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "regressed in {} (last good {})",
+            self.first_bad, self.last_good
+        )?;
+        if let Some(commits) = &self.commits {
+            write!(
+                f,
+                ", between {} and {}",
+                commits.last_good, commits.first_bad
+            )?;
+            if let Some(pr) = commits.pr {
+                write!(f, ", rust-lang/rust#{pr}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Why a bisection could not start.
+#[derive(Debug)]
+pub enum BisectError {
+    Io(io::Error),
+    /// The newest toolchain does not crash with the signature.
+    NotReproduced(String),
+    /// No nightly as far back as bisection looks was good.
+    NoGoodNightly(Date),
+}
+
+impl fmt::Display for BisectError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BisectError::Io(e) => e.fmt(f),
+            BisectError::NotReproduced(toolchain) => {
+                write!(f, "{toolchain} does not crash with the signature")
+            }
+            BisectError::NoGoodNightly(date) => {
+                write!(f, "every nightly back to {date} crashes with the signature")
+            }
+        }
+    }
+}
+
+impl std::error::Error for BisectError {}
+
+impl From<io::Error> for BisectError {
+    fn from(e: io::Error) -> Self {
+        BisectError::Io(e)
+    }
+}
+
+/// How far back [`Bisector::nightlies`] looks for a good nightly when it
+/// is not given one.
+pub const MAX_LOOKBACK_DAYS: i64 = 2 * 365;
+
+/// Bisects a seed's crash over toolchains.
+#[derive(Debug, Clone)]
+pub struct Bisector {
+    pub toolchains: Toolchains,
+    /// `rustup-toolchain-install-master`, for CI artifacts.
+    pub install_master: PathBuf,
+    /// Limits and phase for every run; the path is replaced by each
+    /// toolchain's rustc.
+    pub driver: Rustc,
+    /// Arguments before the input, which is read from stdin.
+    pub args: Vec<String>,
+    /// Keep the toolchains it installs instead of uninstalling them after
+    /// use; [`Toolchains::gc`] removes them once they go unused.
+    pub keep: bool,
+}
+
+impl Bisector {
+    pub fn new(toolchains: Toolchains, driver: Rustc) -> Self {
+        Bisector {
+            toolchains,
+            install_master: PathBuf::from("rustup-toolchain-install-master"),
+            driver,
+            args: vec!["--crate-type=lib".to_owned()],
+            keep: false,
+        }
+    }
+
+    /// The signature of the crash `source` causes with `toolchain`, or
+    /// `None` if it does not crash or `toolchain` cannot be installed.
+    pub fn signature(&self, toolchain: &str, source: &str) -> io::Result<Option<Signature>> {
+        Ok(self
+            .run(toolchain, source, false)?
+            .and_then(|outcome| Signature::of_outcome(&outcome)))
+    }
+
+    /// Bisects nightlies from `start`, which must be good, to `end`, which
+    /// must be bad, for the first that crashes with `signature`. Without
+    /// `start`, it looks back from `end` a week, two, four and so on for a
+    /// good nightly.
+    pub fn nightlies(
+        &self,
+        source: &str,
+        signature: &Signature,
+        start: Option<Date>,
+        end: Date,
+    ) -> Result<Bisection, BisectError> {
+        if self.verdict(&end.toolchain(), source, signature, false)? != Verdict::Bad {
+            return Err(BisectError::NotReproduced(end.toolchain()));
+        }
+        let (start, end) = match start {
+            Some(start) => (start, end),
+            None => self.look_back(source, signature, end)?,
+        };
+        let dates: Vec<Date> = (0..=end.days() - start.days())
+            .map(|days| start.add_days(days))
+            .collect();
+        let (good, bad) = bisect(dates, |date| {
+            self.verdict(&date.toolchain(), source, signature, false)
+        })?;
+        Ok(Bisection {
+            signature: signature.id(),
+            last_good: good.toolchain(),
+            first_bad: bad.toolchain(),
+            commits: None,
+        })
+    }
+
+    /// A good nightly before bad `end`, and the latest bad one after it
+    /// seen on the way.
+    fn look_back(
+        &self,
+        source: &str,
+        signature: &Signature,
+        mut end: Date,
+    ) -> Result<(Date, Date), BisectError> {
+        let last = end;
+        let mut step = 7;
+        while step <= MAX_LOOKBACK_DAYS {
+            let date = last.add_days(-step);
+            step *= 2;
+            match self.verdict(&date.toolchain(), source, signature, false)? {
+                Verdict::Good => return Ok((date, end)),
+                Verdict::Bad => end = date,
+                Verdict::Skip => {}
+            }
+        }
+        Err(BisectError::NoGoodNightly(
+            last.add_days(-MAX_LOOKBACK_DAYS),
+        ))
+    }
+
+    /// Narrows `bisection` to the merge commits between its nightlies,
+    /// listed from the rust-lang/rust checkout at `repo`.
+    pub fn commits(
+        &self,
+        source: &str,
+        signature: &Signature,
+        repo: &Path,
+        bisection: &mut Bisection,
+    ) -> io::Result<()> {
+        let good = self.commit_of(&bisection.last_good)?;
+        let bad = self.commit_of(&bisection.first_bad)?;
+        let mut commits = vec![good.clone()];
+        commits.extend(
+            git(
+                repo,
+                &[
+                    "rev-list",
+                    "--first-parent",
+                    "--reverse",
+                    &format!("{good}..{bad}"),
+                ],
+            )?
+            .lines()
+            .map(str::to_owned),
+        );
+        if commits.len() < 2 {
+            return Ok(());
+        }
+        let (good, bad) = bisect(commits, |commit| {
+            self.verdict(commit, source, signature, true)
+        })?;
+        let subject = git(repo, &["log", "-1", "--format=%s", &bad])?;
+        bisection.commits = Some(CommitRange {
+            last_good: good,
+            first_bad: bad,
+            pr: pr_number(&subject),
+        });
+        Ok(())
+    }
+
+    /// The commit `toolchain` was built from, by `rustc -vV`.
+    fn commit_of(&self, toolchain: &str) -> io::Result<String> {
+        self.with_toolchain(toolchain, false, |rustc| {
+            let outcome = self.driver.run_program(rustc, ["-vV"], &[])?;
+            let ExecOutcome::Success(output) = &outcome else {
+                return Err(io::Error::other(format!(
+                    "{} -vV: {outcome}",
+                    rustc.display()
+                )));
+            };
+            output
+                .stdout
+                .lines()
+                .find_map(|line| line.strip_prefix("commit-hash: "))
+                .map(|hash| hash.trim().to_owned())
+                .ok_or_else(|| io::Error::other(format!("{} -vV names no commit", rustc.display())))
+        })?
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("cannot install {toolchain}"),
+            )
+        })
+    }
+
+    /// Whether `source` crashes with `signature` under `toolchain`, a
+    /// nightly by date or a CI build by commit with `master`.
+    fn verdict(
+        &self,
+        toolchain: &str,
+        source: &str,
+        signature: &Signature,
+        master: bool,
+    ) -> io::Result<Verdict> {
+        let verdict = match self.run(toolchain, source, master)? {
+            None => Verdict::Skip,
+            Some(outcome)
+                if Signature::of_outcome(&outcome)
+                    .is_some_and(|found| found.matches(signature)) =>
+            {
+                Verdict::Bad
+            }
+            Some(_) => Verdict::Good,
+        };
+        tracing::info!(toolchain, ?verdict, "bisection step");
+        Ok(verdict)
+    }
+
+    /// Runs `source` with `toolchain`, or `None` if it cannot be
+    /// installed.
+    fn run(&self, toolchain: &str, source: &str, master: bool) -> io::Result<Option<ExecOutcome>> {
+        self.with_toolchain(toolchain, master, |path| {
+            let rustc = Rustc {
+                path: path.to_owned(),
+                ..self.driver.clone()
+            };
+            let mut args = self.args.clone();
+            args.push("-".to_owned());
+            rustc.run(&args, source.as_bytes())
+        })
+    }
+
+    /// Calls `f` with the rustc of `toolchain`, installing it first if
+    /// needed and uninstalling it afterwards unless [`Bisector::keep`].
+    /// `None` if it cannot be installed.
+    fn with_toolchain<T>(
+        &self,
+        toolchain: &str,
+        master: bool,
+        f: impl FnOnce(&Path) -> io::Result<T>,
+    ) -> io::Result<Option<T>> {
+        let installed = self.toolchains.rustc(toolchain)?.is_some();
+        if !installed && !self.install(toolchain, master)? {
+            return Ok(None);
+        }
+        let Some(rustc) = self.toolchains.rustc(toolchain)? else {
+            return Ok(None);
+        };
+        let result = f(&rustc);
+        if !installed && !self.keep {
+            self.toolchains.uninstall(toolchain)?;
+        }
+        result.map(Some)
+    }
+
+    fn install(&self, toolchain: &str, master: bool) -> io::Result<bool> {
+        if master {
+            let status = Command::new(&self.install_master)
+                .arg(toolchain)
+                .output()
+                .map_err(|e| {
+                    io::Error::new(
+                        e.kind(),
+                        format!("running {}: {e}", self.install_master.display()),
+                    )
+                })?
+                .status;
+            return Ok(status.success());
+        }
+        self.toolchains.install(toolchain)
+    }
+}
+
+fn git(repo: &Path, args: &[&str]) -> io::Result<String> {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(repo)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "git {}: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// The pull request number in a bors merge subject, like `Auto merge of
+/// #12345 - user:branch, r=reviewer`.
+fn pr_number(subject: &str) -> Option<u64> {
+    let (_, rest) = subject.split_once("merge of #")?;
+    let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+    digits.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dates_count_days_across_months_and_leap_years() {
+        let date = Date::parse("2024-02-28").expect("a date");
+        assert_eq!(date.add_days(1).to_string(), "2024-02-29");
+        assert_eq!(date.add_days(2).to_string(), "2024-03-01");
+        assert_eq!(date.add_days(-59).to_string(), "2023-12-31");
+        assert_eq!(Date::parse("1970-01-01").map(Date::days), Some(0));
+        assert_eq!(date.toolchain(), "nightly-2024-02-28");
+        assert_eq!(Date::parse("2023-02-29"), None);
+        assert_eq!(Date::parse("2024-13-01"), None);
+        assert_eq!(Date::parse("yesterday"), None);
+    }
+
+    #[test]
+    fn bisection_finds_the_first_bad_candidate_past_skips() {
+        let mut tested = Vec::new();
+        let (good, bad) = bisect((0..20).collect(), |&n: &i32| {
+            tested.push(n);
+            Ok::<_, ()>(match n {
+                11 | 12 => Verdict::Skip,
+                n if n >= 13 => Verdict::Bad,
+                _ => Verdict::Good,
+            })
+        })
+        .expect("infallible");
+        assert_eq!((good, bad), (10, 13));
+        assert!(tested.len() <= 8, "{tested:?}");
+        assert_eq!(
+            bisect(vec![0, 1, 2], |_: &i32| Err::<Verdict, _>("no rustup")),
+            Err("no rustup")
+        );
+    }
+
+    #[test]
+    fn regressions_name_their_pull_request() {
+        assert_eq!(
+            pr_number("Auto merge of #12345 - user:branch, r=reviewer"),
+            Some(12345)
+        );
+        assert_eq!(pr_number("Rollup merge of #678 - fix"), Some(678));
+        assert_eq!(pr_number("Update cargo"), None);
+        let bisection = Bisection {
+            signature: "abc".to_owned(),
+            last_good: "nightly-2024-01-01".to_owned(),
+            first_bad: "nightly-2024-01-02".to_owned(),
+            commits: Some(CommitRange {
+                last_good: "aaa".to_owned(),
+                first_bad: "bbb".to_owned(),
+                pr: Some(42),
+            }),
+        };
+        assert_eq!(
+            bisection.to_string(),
+            "regressed in nightly-2024-01-02 (last good nightly-2024-01-01), \
+             between aaa and bbb, rust-lang/rust#42"
+        );
+    }
+}
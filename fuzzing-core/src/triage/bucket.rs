@@ -0,0 +1,532 @@
+//! Crash buckets.
+//!
+//! A campaign that finds one ICE usually finds it thousands of times, in
+//! mutants that differ in everything but what breaks the compiler. A
+//! [`Signature`] keeps only what identifies the bug: the kind of crash,
+//! the compiler source file to blame, the message with numbers and spans
+//! scrubbed, and the innermost [`FRAMES`] queries. Findings with the same
+//! signature go in the same [`Bucket`], and [`Buckets`] keeps the index of
+//! a findings directory with each bucket's smallest reproducer.
+//!
+//! Lines and columns in the compiler's source are left out of the
+//! signature, since they move between nightlies while the bug stays put.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::corpus::meta;
+use crate::harness::ExecOutcome;
+use crate::triage::ice::{self, IceReport};
+use crate::triage::{Bisection, Candidate, Decision, KnownBug, KnownBugs, Severity};
+
+/// Query-stack frames a signature keeps, innermost first.
+pub const FRAMES: usize = 3;
+
+/// Characters of the scrubbed message a signature keeps.
+const MESSAGE_LEN: usize = 200;
+
+/// What identifies a crash across the inputs that trigger it.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Signature {
+    /// An [`IceKind`](crate::triage::IceKind) name, `signal N` for a
+    /// crash without an ICE report, or `oom` for memory exhaustion.
+    pub kind: String,
+    /// The compiler source file the report blames.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// The first line of the message, scrubbed by [`normalize`].
+    pub message: String,
+    /// The innermost query names.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub queries: Vec<String>,
+    /// The crash reproduces only some of the time, as
+    /// [`FlakeCheck`](crate::triage::FlakeCheck) found. Flaky crashes get
+    /// buckets of their own.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub flaky: bool,
+}
+
+impl Signature {
+    /// The signature of an ICE.
+    pub fn of(report: &IceReport) -> Self {
+        let mut kind = report.kind.name().to_owned();
+        if let Some(code) = &report.error_code {
+            kind = format!("{kind}[{code}]");
+        }
+        Signature {
+            kind,
+            file: report.location().map(|location| location.file.clone()),
+            message: normalize(&report.message),
+            queries: report
+                .query_stack
+                .iter()
+                .take(FRAMES)
+                .map(|query| query.name.clone())
+                .collect(),
+            flaky: false,
+        }
+    }
+
+    /// The signature of an ICE, crash or memory exhaustion, or `None` for
+    /// any other outcome.
+    pub fn of_outcome(outcome: &ExecOutcome) -> Option<Self> {
+        match outcome {
+            ExecOutcome::Ice(output) => Some(match ice::parse(&output.stderr) {
+                Some(report) => Signature::of(&report),
+                None => Signature {
+                    kind: "ice".to_owned(),
+                    file: None,
+                    message: normalize(first_line(&output.stderr)),
+                    queries: Vec::new(),
+                    flaky: false,
+                },
+            }),
+            ExecOutcome::Crash { signal, output } => {
+                let report = ice::parse(&output.stderr);
+                // A stack overflow says so on a line of its own, without
+                // panicking.
+                let message = match output
+                    .stderr
+                    .lines()
+                    .find(|line| line.contains("has overflowed its stack"))
+                {
+                    Some(line) => line,
+                    None => report
+                        .as_ref()
+                        .map(|report| report.message.as_str())
+                        .unwrap_or_default(),
+                };
+                Some(Signature {
+                    kind: format!("signal {signal}"),
+                    file: report
+                        .as_ref()
+                        .and_then(|report| report.location())
+                        .map(|location| location.file.clone()),
+                    message: normalize(message),
+                    queries: report
+                        .map(|report| {
+                            report
+                                .query_stack
+                                .into_iter()
+                                .take(FRAMES)
+                                .map(|query| query.name)
+                                .collect()
+                        })
+                        .unwrap_or_default(),
+                    flaky: false,
+                })
+            }
+            // What little there is to tell them apart by: whether the
+            // allocator, LLVM or the kernel gave up.
+            ExecOutcome::OutOfMemory(output) => Some(Signature {
+                kind: "oom".to_owned(),
+                file: None,
+                message: normalize(
+                    output
+                        .stderr
+                        .lines()
+                        .find(|line| {
+                            line.contains("memory allocation of") || line.contains("out of memory")
+                        })
+                        .unwrap_or_default(),
+                ),
+                queries: Vec::new(),
+                flaky: false,
+            }),
+            _ => None,
+        }
+    }
+
+    /// How bad a crash with this signature is: memory exhaustion ranks
+    /// with hangs, everything else with ICEs.
+    pub fn severity(&self) -> Severity {
+        if self.kind == "oom" {
+            Severity::Hang
+        } else {
+            Severity::Ice
+        }
+    }
+
+    /// The same signature, marked flaky.
+    pub fn flaky(self) -> Self {
+        Signature {
+            flaky: true,
+            ..self
+        }
+    }
+
+    /// Whether a crash with this signature is the same as one with
+    /// `other`, flaky or not.
+    pub fn matches(&self, other: &Signature) -> bool {
+        self.kind == other.kind
+            && self.file == other.file
+            && self.message == other.message
+            && self.queries == other.queries
+    }
+
+    /// A short stable name for the signature, used as its bucket's id.
+    pub fn id(&self) -> String {
+        meta::content_hash(&self.to_string())
+    }
+}
+
+impl fmt::Display for Signature {
+    /// `kind in file: message [query < query]`, on one line, after
+    /// `flaky ` for a flaky crash.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.flaky {
+            f.write_str("flaky ")?;
+        }
+        f.write_str(&self.kind)?;
+        if let Some(file) = &self.file {
+            write!(f, " in {file}")?;
+        }
+        write!(f, ": {}", self.message)?;
+        if !self.queries.is_empty() {
+            write!(f, " [{}]", self.queries.join(" < "))?;
+        }
+        Ok(())
+    }
+}
+
+/// The first line of `message` with what differs between inputs that hit
+/// the same bug scrubbed: spans into the input become `SPAN`, and numbers,
+/// hex hashes and addresses become `N`, including the ones in `DefId`s,
+/// inference variables and crate disambiguators.
+pub fn normalize(message: &str) -> String {
+    let line = first_line(message);
+    let mut words = Vec::new();
+    for word in line.split_whitespace() {
+        if word.contains(".rs:") || word.starts_with("<anon>:") {
+            words.push("SPAN".to_owned());
+        } else {
+            words.push(scrub_numbers(word));
+        }
+    }
+    let mut normalized = words.join(" ");
+    if let Some((end, _)) = normalized.char_indices().nth(MESSAGE_LEN) {
+        normalized.truncate(end);
+    }
+    normalized
+}
+
+fn first_line(text: &str) -> &str {
+    text.lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty())
+        .unwrap_or_default()
+}
+
+/// Replaces the numbers in `word`: a run of letters and digits that is a
+/// hex number with at least one digit in it becomes `N` whole, and digits
+/// in any other run become `N`.
+fn scrub_numbers(word: &str) -> String {
+    let mut scrubbed = String::new();
+    let mut rest = word;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphanumeric()) {
+        scrubbed.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let end = rest
+            .find(|c: char| !c.is_ascii_alphanumeric())
+            .unwrap_or(rest.len());
+        let run = &rest[..end];
+        let hex = run.strip_prefix("0x").unwrap_or(run);
+        if run.bytes().any(|b| b.is_ascii_digit()) && hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            scrubbed.push('N');
+        } else {
+            let mut in_number = false;
+            for c in run.chars() {
+                if !c.is_ascii_digit() {
+                    scrubbed.push(c);
+                } else if !in_number {
+                    scrubbed.push('N');
+                }
+                in_number = c.is_ascii_digit();
+            }
+        }
+        rest = &rest[end..];
+    }
+    scrubbed.push_str(rest);
+    scrubbed
+}
+
+/// The findings that share one signature.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bucket {
+    pub id: String,
+    pub signature: Signature,
+    /// Findings that reproduce it, as paths relative to the findings
+    /// directory.
+    pub findings: Vec<String>,
+    /// The smallest of them.
+    pub best: String,
+    /// Its length in bytes.
+    pub best_len: usize,
+    /// Where it regressed, once `triage bisect` has found out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bisection: Option<Bisection>,
+    /// The issue it was reported in, if it is a [`KnownBug`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub issue: Option<String>,
+    /// Upstream issues that may report it already, from an
+    /// [`IssueSearch`](crate::triage::IssueSearch) when it was new.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub duplicates: Vec<Candidate>,
+    /// What `triage review` decided to do about it, if anyone has.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub decision: Option<Decision>,
+}
+
+/// The buckets of a findings directory, kept in its [`Buckets::FILE`].
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Buckets {
+    buckets: BTreeMap<String, Bucket>,
+}
+
+impl Buckets {
+    /// The index's file name in a findings directory.
+    pub const FILE: &'static str = "buckets.json";
+
+    pub fn new() -> Self {
+        Buckets::default()
+    }
+
+    /// The index of findings directory `dir`, empty if it has none yet.
+    pub fn load(dir: &Path) -> io::Result<Self> {
+        match fs::read_to_string(dir.join(Self::FILE)) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Buckets::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, dir: &Path) -> io::Result<()> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        fs::write(dir.join(Self::FILE), json)
+    }
+
+    /// Files `finding`, `len` bytes long, under `signature`, making it the
+    /// bucket's best reproducer if it is the smallest. Returns whether the
+    /// bucket is new.
+    pub fn add(&mut self, signature: Signature, finding: &str, len: usize) -> bool {
+        let id = signature.id();
+        if let Some(bucket) = self.buckets.get_mut(&id) {
+            if !bucket.findings.iter().any(|f| f == finding) {
+                bucket.findings.push(finding.to_owned());
+            }
+            if len < bucket.best_len {
+                bucket.best = finding.to_owned();
+                bucket.best_len = len;
+            }
+            return false;
+        }
+        self.buckets.insert(
+            id.clone(),
+            Bucket {
+                id,
+                signature,
+                findings: vec![finding.to_owned()],
+                best: finding.to_owned(),
+                best_len: len,
+                bisection: None,
+                issue: None,
+                duplicates: Vec::new(),
+                decision: None,
+            },
+        );
+        true
+    }
+
+    /// Records the issue of every bucket whose signature is in `known`,
+    /// and forgets it for the others. Returns how many are known.
+    pub fn mark_known(&mut self, known: &KnownBugs) -> usize {
+        let mut count = 0;
+        for bucket in self.buckets.values_mut() {
+            bucket.issue = known
+                .lookup(&bucket.signature)
+                .map(|bug: &KnownBug| bug.issue.clone());
+            count += usize::from(bucket.issue.is_some());
+        }
+        count
+    }
+
+    /// The bucket `finding` is filed in, if any.
+    pub fn bucket_of(&self, finding: &str) -> Option<&Bucket> {
+        self.buckets
+            .values()
+            .find(|bucket| bucket.findings.iter().any(|f| f == finding))
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Bucket> {
+        self.buckets.get(id)
+    }
+
+    pub fn get_mut(&mut self, id: &str) -> Option<&mut Bucket> {
+        self.buckets.get_mut(id)
+    }
+
+    /// Every bucket, the most severe first and then the ones with the most
+    /// findings.
+    pub fn list(&self) -> Vec<&Bucket> {
+        let mut buckets: Vec<&Bucket> = self.buckets.values().collect();
+        buckets.sort_by(|a, b| {
+            a.signature
+                .severity()
+                .cmp(&b.signature.severity())
+                .then_with(|| b.findings.len().cmp(&a.findings.len()))
+                .then_with(|| a.id.cmp(&b.id))
+        });
+        buckets
+    }
+
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+}
+
+impl fmt::Display for Buckets {
+    /// One line per bucket: its id, number of findings, best reproducer
+    /// and signature, followed by its issue and where it regressed if
+    /// known, by candidate duplicates if it is not, and by the review
+    /// decision if there is one.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for bucket in self.list() {
+            writeln!(
+                f,
+                "{} {:>6}  {} ({} bytes)  {}",
+                bucket.id,
+                bucket.findings.len(),
+                bucket.best,
+                bucket.best_len,
+                bucket.signature
+            )?;
+            if let Some(issue) = &bucket.issue {
+                writeln!(f, "{:>16} {:>6}  known: {issue}", "", "")?;
+            } else {
+                for candidate in &bucket.duplicates {
+                    writeln!(f, "{:>16} {:>6}  duplicate of {candidate}?", "", "")?;
+                }
+            }
+            if let Some(decision) = &bucket.decision {
+                writeln!(f, "{:>16} {:>6}  decided: {decision}", "", "")?;
+            }
+            if let Some(bisection) = &bucket.bisection {
+                writeln!(f, "{:>16} {:>6}  {bisection}", "", "")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::Captured;
+
+    fn ice(line: u32, message: &str, query: &str) -> ExecOutcome {
+        ExecOutcome::Ice(Captured {
+            stderr: format!(
+                "error: internal compiler error: compiler/rustc_hir_typeck/src/lib.rs:{line}:9: {message}\n\n\
+                 query stack during panic:\n\
+                 #0 [{query}] type-checking `main`\n\
+                 end of query stack\n"
+            ),
+            ..Captured::default()
+        })
+    }
+
+    #[test]
+    fn normalize_scrubs_numbers_and_spans() {
+        assert_eq!(
+            normalize("unexpected region in DefId(0:3 ~ x[12ab]::f) at src/main.rs:3:9\nmore"),
+            "unexpected region in DefId(N:N ~ x[N]::f) at SPAN"
+        );
+        assert_eq!(
+            normalize("  \n?T42 at 0x7ffd1234 for v123"),
+            "?TN at N for vN"
+        );
+        assert_eq!(normalize("deadbeef stays"), "deadbeef stays");
+        assert_eq!(normalize(&"x".repeat(300)).len(), MESSAGE_LEN);
+    }
+
+    #[test]
+    fn signatures_ignore_what_varies_between_inputs() {
+        let a = Signature::of_outcome(&ice(100, "bad type `?12t`", "typeck")).expect("an ICE");
+        let b = Signature::of_outcome(&ice(200, "bad type `?7t`", "typeck")).expect("an ICE");
+        let c = Signature::of_outcome(&ice(100, "bad type `?12t`", "check_mod")).expect("an ICE");
+        assert_eq!(a.kind, "bug");
+        assert_eq!(
+            a.file.as_deref(),
+            Some("compiler/rustc_hir_typeck/src/lib.rs")
+        );
+        assert_eq!(a.message, "bad type `?Nt`");
+        assert_eq!(a.queries, ["typeck"]);
+        assert_eq!(a, b);
+        assert_eq!(a.id(), b.id());
+        assert_ne!(a.id(), c.id());
+        assert!(a.matches(&b.clone().flaky()));
+        assert_ne!(a.id(), b.flaky().id());
+    }
+
+    #[test]
+    fn signatures_of_crashes_and_exhaustion() {
+        let overflow = ExecOutcome::Crash {
+            signal: libc::SIGABRT,
+            output: Captured {
+                stderr: "\nthread 'rustc' has overflowed its stack\nfatal runtime error: stack overflow\n"
+                    .to_owned(),
+                ..Captured::default()
+            },
+        };
+        let signature = Signature::of_outcome(&overflow).expect("a crash");
+        assert_eq!(signature.kind, format!("signal {}", libc::SIGABRT));
+        assert_eq!(signature.message, "thread 'rustc' has overflowed its stack");
+        assert_eq!(signature.severity(), Severity::Ice);
+
+        let oom = ExecOutcome::OutOfMemory(Captured {
+            stderr: "memory allocation of 4294967296 bytes failed\n".to_owned(),
+            ..Captured::default()
+        });
+        let signature = Signature::of_outcome(&oom).expect("exhaustion");
+        assert_eq!(signature.kind, "oom");
+        assert_eq!(signature.message, "memory allocation of N bytes failed");
+        assert_eq!(signature.severity(), Severity::Hang);
+
+        assert_eq!(
+            Signature::of_outcome(&ExecOutcome::Success(Captured::default())),
+            None
+        );
+    }
+
+    #[test]
+    fn the_most_severe_buckets_are_listed_first() {
+        let oom = Signature::of_outcome(&ExecOutcome::OutOfMemory(Captured::default()))
+            .expect("exhaustion");
+        let typeck = Signature::of_outcome(&ice(1, "typeck failed", "typeck")).expect("an ICE");
+        let borrowck =
+            Signature::of_outcome(&ice(2, "borrowck failed", "mir_borrowck")).expect("an ICE");
+        let mut buckets = Buckets::new();
+        for (signature, finding) in [
+            (oom.clone(), "a.rs"),
+            (oom.clone(), "b.rs"),
+            (oom, "c.rs"),
+            (typeck.clone(), "d.rs"),
+            (borrowck.clone(), "e.rs"),
+            (borrowck.clone(), "f.rs"),
+        ] {
+            buckets.add(signature, finding, 1);
+        }
+        let listed: Vec<&str> = buckets.list().iter().map(|b| b.best.as_str()).collect();
+        assert_eq!(listed, ["e.rs", "d.rs", "a.rs"]);
+    }
+}
@@ -0,0 +1,248 @@
+//! Self-contained reproducers.
+//!
+//! A [`Report`] is written for an issue tracker; a [`Bundle`] is the same
+//! evidence as a directory someone can run without this crate: the
+//! reproducer as [`FILE`], its metadata sidecar, the error output and
+//! backtrace it was recorded with, the issue draft, and a `repro.sh` that
+//! installs the exact toolchain with rustup and compiles the reproducer
+//! with the exact flags.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+
+use crate::corpus::meta::Metadata;
+use crate::triage::bisect::Date;
+use crate::triage::report::FILE;
+use crate::triage::Report;
+
+/// One finding, ready to be handed over.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bundle {
+    pub report: Report,
+    /// The rustup toolchain the report was made with, if it is one
+    /// rustup can install.
+    pub toolchain: Option<String>,
+    /// The reproducer's metadata, if it has any.
+    pub metadata: Option<Metadata>,
+}
+
+impl Bundle {
+    pub fn new(report: Report, metadata: Option<Metadata>) -> Self {
+        Bundle {
+            toolchain: toolchain(&report.version),
+            report,
+            metadata,
+        }
+    }
+
+    /// Writes the bundle's files to `dir`, creating it.
+    pub fn write(&self, dir: &Path) -> io::Result<()> {
+        fs::create_dir_all(dir)?;
+        let source = dir.join(FILE);
+        fs::write(&source, &self.report.source)?;
+        if let Some(metadata) = &self.metadata {
+            metadata.save(&source)?;
+        }
+        fs::write(dir.join("stderr.txt"), format!("{}\n", self.report.error))?;
+        fs::write(
+            dir.join("backtrace.txt"),
+            format!("{}\n", self.report.backtrace),
+        )?;
+        fs::write(dir.join("README.md"), self.report.to_string())?;
+        let script = dir.join("repro.sh");
+        fs::write(&script, self.script())?;
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755))
+    }
+
+    /// `repro.sh`: compiles [`FILE`] with the recorded toolchain and
+    /// arguments, or with `$TOOLCHAIN` if set, and exits as rustc did.
+    pub fn script(&self) -> String {
+        let report = &self.report;
+        let mut script = String::from("#!/bin/sh\n");
+        let _ = writeln!(script, "# {}", report.title);
+        let _ = writeln!(
+            script,
+            "# rustc-fuzz bucket {}: {}",
+            report.bucket.id, report.bucket.signature
+        );
+        script.push_str("#\n# Recorded with:\n");
+        for line in report.version.lines() {
+            let _ = writeln!(script, "#   {line}");
+        }
+        script.push_str(
+            "#\n# The expected error output is in stderr.txt. Set TOOLCHAIN to try\n\
+             # another rustup toolchain, or to an empty string for the rustc on PATH.\n\
+             set -u\ncd \"$(dirname \"$0\")\"\n",
+        );
+        let _ = writeln!(
+            script,
+            "TOOLCHAIN=${{TOOLCHAIN-{}}}",
+            self.toolchain.as_deref().unwrap_or_default()
+        );
+        script.push_str(
+            "if [ -n \"$TOOLCHAIN\" ]; then\n    \
+             rustup toolchain install --profile minimal \"$TOOLCHAIN\" >&2 || exit\n    \
+             set -- rustup run \"$TOOLCHAIN\" rustc\nelse\n    set -- rustc\nfi\n",
+        );
+        if report.bootstrap {
+            script.push_str("export RUSTC_BOOTSTRAP=1\n");
+        }
+        let args: Vec<String> = report.args.iter().map(|arg| quote(arg)).collect();
+        let _ = writeln!(
+            script,
+            "exec \"$@\" {} --out-dir \"${{TMPDIR:-/tmp}}\" {FILE}",
+            args.join(" ")
+        );
+        script
+    }
+}
+
+/// The rustup toolchain `rustc -vV` output describes: the release for a
+/// stable compiler, the nightly or beta of the day after the commit date
+/// otherwise, as that is when the nightly built from it is published.
+/// `None` for local builds.
+pub fn toolchain(version: &str) -> Option<String> {
+    let field = |name: &str| {
+        version
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))
+            .map(str::trim)
+    };
+    let release = field("release")?;
+    let channel = match release.split_once('-') {
+        None => return Some(release.to_owned()),
+        Some((_, pre)) if pre.starts_with("nightly") => "nightly",
+        Some((_, pre)) if pre.starts_with("beta") => "beta",
+        Some(_) => return None,
+    };
+    let date = Date::parse(field("commit-date")?)?.add_days(1);
+    Some(format!("{channel}-{date}"))
+}
+
+/// `arg` quoted for the shell, if it needs to be.
+fn quote(arg: &str) -> String {
+    if arg
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "-_=.,/:+@".contains(c))
+    {
+        arg.to_owned()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::corpus::meta::Provenance;
+    use crate::triage::{ice, Buckets, Signature};
+    use std::process::Command;
+
+    const NIGHTLY: &str = "rustc 1.81.0-nightly (0123456789 2024-05-31)\n\
+                           binary: rustc\ncommit-date: 2024-05-31\nrelease: 1.81.0-nightly\n";
+
+    #[test]
+    fn toolchains_are_what_rustup_installs() {
+        assert_eq!(toolchain(NIGHTLY).as_deref(), Some("nightly-2024-06-01"));
+        let beta = NIGHTLY.replace("nightly", "beta.3");
+        assert_eq!(toolchain(&beta).as_deref(), Some("beta-2024-06-01"));
+        let stable = "rustc 1.80.0\ncommit-date: 2024-07-21\nrelease: 1.80.0\n";
+        assert_eq!(toolchain(stable).as_deref(), Some("1.80.0"));
+        assert_eq!(toolchain("rustc 1.81.0-dev\nrelease: 1.81.0-dev\n"), None);
+        assert_eq!(toolchain("rustc 1.80.0\n"), None);
+    }
+
+    #[test]
+    fn quotes_only_what_the_shell_would_split() {
+        assert_eq!(quote("--edition=2021"), "--edition=2021");
+        assert_eq!(
+            quote("-Zcrate-attr=feature(x)"),
+            "'-Zcrate-attr=feature(x)'"
+        );
+        assert_eq!(quote("it's"), r"'it'\''s'");
+    }
+
+    #[test]
+    fn bundles_reproduce_on_their_own() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-bundle-{}", std::process::id()));
+        let stderr =
+            "error: internal compiler error: compiler/rustc_middle/src/ty/mod.rs:1:2: boom";
+        let signature = Signature::of(&ice::parse(stderr).expect("an ICE"));
+        let mut buckets = Buckets::new();
+        buckets.add(signature.clone(), "a.rs", 12);
+        let report = Report {
+            bucket: buckets.get(&signature.id()).expect("the bucket").clone(),
+            source: "fn boom() {}".to_owned(),
+            args: vec![
+                "--crate-type=lib".to_owned(),
+                "-Zcrate-attr=feature(x)".to_owned(),
+            ],
+            bootstrap: true,
+            version: NIGHTLY.trim().to_owned(),
+            error: stderr.to_owned(),
+            backtrace: format!("{stderr}\n   0: std::panicking::begin_panic"),
+            title: "ICE: boom".to_owned(),
+        };
+        let metadata = Metadata::new(&report.source, Provenance::Unknown);
+        let bundle = Bundle::new(report, Some(metadata.clone()));
+        assert_eq!(bundle.toolchain.as_deref(), Some("nightly-2024-06-01"));
+        let script = bundle.script();
+        assert!(
+            script.contains("TOOLCHAIN=${TOOLCHAIN-nightly-2024-06-01}\n"),
+            "{script}"
+        );
+        assert!(script.contains("export RUSTC_BOOTSTRAP=1\n"), "{script}");
+        assert!(
+            script.contains("--crate-type=lib '-Zcrate-attr=feature(x)' --out-dir"),
+            "{script}"
+        );
+
+        bundle.write(&dir).expect("writes");
+        assert_eq!(
+            fs::read_to_string(dir.join(FILE)).expect("reads"),
+            "fn boom() {}"
+        );
+        let saved = Metadata::load(&dir.join(FILE))
+            .expect("reads")
+            .expect("has a sidecar");
+        assert_eq!(saved, metadata);
+        assert_eq!(
+            fs::read_to_string(dir.join("stderr.txt")).expect("reads"),
+            format!("{stderr}\n")
+        );
+        // With no toolchain, repro.sh runs the rustc on PATH: here one that
+        // prints its arguments and ICEs.
+        let bin = dir.join("bin");
+        fs::create_dir_all(&bin).expect("creates the bin directory");
+        let rustc = bin.join("rustc");
+        fs::write(
+            &rustc,
+            "#!/bin/sh\necho \"$RUSTC_BOOTSTRAP $*\"\nexit 101\n",
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let path = format!(
+            "{}:{}",
+            bin.display(),
+            std::env::var("PATH").unwrap_or_default()
+        );
+        let output = Command::new(dir.join("repro.sh"))
+            .env("PATH", path)
+            .env("TOOLCHAIN", "")
+            .env("TMPDIR", &dir)
+            .output()
+            .expect("runs repro.sh");
+        assert_eq!(output.status.code(), Some(101));
+        assert_eq!(
+            String::from_utf8_lossy(&output.stdout),
+            format!(
+                "1 --crate-type=lib -Zcrate-attr=feature(x) --out-dir {} {FILE}\n",
+                dir.display()
+            )
+        );
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,350 @@
+//! Duplicate search.
+//!
+//! A new bucket is new to this campaign, not necessarily to rust-lang/rust.
+//! Before one is announced, [`IssueSearch`] asks GitHub's issue search API
+//! for issues quoting the longest stretch of its message that scrubbing
+//! left alone, and the hits are attached to the bucket as [`Candidate`]s
+//! for a person to check.
+//!
+//! The search API allows ten requests a minute without a token and thirty
+//! with one, so [`IssueSearch`] spaces its requests accordingly, and it
+//! caches answers in the findings directory's [`IssueSearch::CACHE`] so a
+//! rerun asks nothing it asked recently.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::harness::{ExecOutcome, Rustc};
+use crate::triage::Signature;
+
+/// Words of a signature's message a query quotes at most.
+const QUERY_WORDS: usize = 12;
+
+/// An issue that may report the same crash.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candidate {
+    pub number: u64,
+    pub title: String,
+    pub url: String,
+    /// `open` or `closed`.
+    pub state: String,
+}
+
+impl fmt::Display for Candidate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{} ({}): {}", self.number, self.state, self.title)
+    }
+}
+
+/// Answers already fetched, by query.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct Cache {
+    queries: BTreeMap<String, Cached>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Cached {
+    /// Seconds since the Unix epoch.
+    fetched: u64,
+    issues: Vec<Candidate>,
+}
+
+#[derive(Deserialize)]
+struct Results {
+    items: Vec<Item>,
+}
+
+#[derive(Deserialize)]
+struct Item {
+    number: u64,
+    title: String,
+    html_url: String,
+    state: String,
+}
+
+/// Searches an issue tracker for crashes like a bucket's.
+#[derive(Debug, Clone)]
+pub struct IssueSearch {
+    /// Runs `curl`, with its own timeout.
+    pub driver: Rustc,
+    pub curl: PathBuf,
+    pub api: String,
+    /// The repository to search, as `owner/name`.
+    pub repo: String,
+    /// A GitHub token, for the higher rate limit.
+    pub token: Option<String>,
+    pub user_agent: String,
+    /// Candidates to keep per bucket.
+    pub limit: usize,
+    /// How long a cached answer is good for.
+    pub max_age: Duration,
+    /// Where answers are cached; nothing is cached if `None`.
+    pub cache: Option<PathBuf>,
+    last: Option<Instant>,
+}
+
+impl Default for IssueSearch {
+    fn default() -> Self {
+        IssueSearch {
+            driver: Rustc {
+                timeout: Duration::from_secs(30),
+                ..Rustc::default()
+            },
+            curl: PathBuf::from("curl"),
+            api: "https://api.github.com".to_owned(),
+            repo: "rust-lang/rust".to_owned(),
+            token: None,
+            user_agent: "rustc-fuzz duplicate search".to_owned(),
+            limit: 5,
+            max_age: Duration::from_secs(24 * 60 * 60),
+            cache: None,
+            last: None,
+        }
+    }
+}
+
+impl IssueSearch {
+    /// The cache's file name in a findings directory.
+    pub const CACHE: &'static str = "issue-search.json";
+
+    /// A search caching in findings directory `dir`, with the token in
+    /// `GITHUB_TOKEN` if there is one.
+    pub fn new(dir: &Path) -> Self {
+        IssueSearch {
+            token: std::env::var("GITHUB_TOKEN")
+                .ok()
+                .filter(|token| !token.is_empty()),
+            cache: Some(dir.join(Self::CACHE)),
+            ..IssueSearch::default()
+        }
+    }
+
+    /// Time to leave between requests.
+    pub fn delay(&self) -> Duration {
+        Duration::from_secs(if self.token.is_some() { 2 } else { 6 })
+    }
+
+    /// Issues that may report a crash with `signature`; none if its
+    /// message has nothing distinctive left to search for.
+    pub fn search(&mut self, signature: &Signature) -> io::Result<Vec<Candidate>> {
+        let Some(query) = query(signature, &self.repo) else {
+            return Ok(Vec::new());
+        };
+        let mut cache = self.load()?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        if let Some(cached) = cache.queries.get(&query) {
+            if now.saturating_sub(cached.fetched) < self.max_age.as_secs() {
+                return Ok(cached.issues.clone());
+            }
+        }
+        let issues = self.fetch(&query)?;
+        cache.queries.insert(
+            query,
+            Cached {
+                fetched: now,
+                issues: issues.clone(),
+            },
+        );
+        self.save(&cache)?;
+        Ok(issues)
+    }
+
+    fn fetch(&mut self, query: &str) -> io::Result<Vec<Candidate>> {
+        if let Some(last) = self.last {
+            thread::sleep(self.delay().saturating_sub(last.elapsed()));
+        }
+        self.last = Some(Instant::now());
+        let url = format!(
+            "{}/search/issues?q={}&per_page={}",
+            self.api,
+            encode(query),
+            self.limit
+        );
+        // Headers are read from stdin so the token stays out of `ps`.
+        let mut headers = String::from("Accept: application/vnd.github+json\n");
+        if let Some(token) = &self.token {
+            let _ = writeln!(headers, "Authorization: Bearer {token}");
+        }
+        let args = [
+            "--silent",
+            "--show-error",
+            "--fail",
+            "--user-agent",
+            &self.user_agent,
+            "--header",
+            "@-",
+            &url,
+        ];
+        let body = match self
+            .driver
+            .run_program(&self.curl, args, headers.as_bytes())?
+        {
+            ExecOutcome::Success(output) => output.stdout,
+            outcome => {
+                return Err(io::Error::other(format!(
+                    "searching {} issues: {outcome}",
+                    self.repo
+                )))
+            }
+        };
+        let results: Results = serde_json::from_str(&body)?;
+        Ok(results
+            .items
+            .into_iter()
+            .map(|item| Candidate {
+                number: item.number,
+                title: item.title,
+                url: item.html_url,
+                state: item.state,
+            })
+            .collect())
+    }
+
+    fn load(&self) -> io::Result<Cache> {
+        let Some(path) = &self.cache else {
+            return Ok(Cache::default());
+        };
+        match fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Cache::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn save(&self, cache: &Cache) -> io::Result<()> {
+        let Some(path) = &self.cache else {
+            return Ok(());
+        };
+        let mut json = serde_json::to_string_pretty(cache)?;
+        json.push('\n');
+        fs::write(path, json)
+    }
+}
+
+/// The search for issues in `repo` quoting the longest run of words of
+/// `signature`'s message that scrubbing left alone, or `None` if there is
+/// no such run of at least two words.
+pub fn query(signature: &Signature, repo: &str) -> Option<String> {
+    // Scrubbing leaves `N` for numbers, inside words too (`?Nt`, `vN`),
+    // and `SPAN` for spans. A word with a capital N may have had either,
+    // and quoting it would match nothing.
+    let scrubbed = |word: &str| word.contains('N');
+    let words: Vec<&str> = signature.message.split_whitespace().collect();
+    let run = words
+        .split(|word| scrubbed(word))
+        .max_by_key(|run| run.len())?;
+    if run.len() < 2 {
+        return None;
+    }
+    let phrase = run[..run.len().min(QUERY_WORDS)].join(" ").replace('"', "");
+    Some(format!("repo:{repo} is:issue \"{phrase}\""))
+}
+
+/// `text` percent-encoded for a URL query.
+fn encode(text: &str) -> String {
+    let mut encoded = String::with_capacity(text.len());
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn signature(message: &str) -> Signature {
+        Signature {
+            kind: "bug".to_owned(),
+            file: None,
+            message: message.to_owned(),
+            queries: Vec::new(),
+            flaky: false,
+        }
+    }
+
+    fn query_of(message: &str) -> Option<String> {
+        query(&signature(message), "o/r")
+    }
+
+    #[test]
+    fn queries_quote_the_longest_unscrubbed_run() {
+        let query = query(
+            &signature("bad type `?Nt` in \"the\" closure signature at SPAN"),
+            "rust-lang/rust",
+        );
+        assert_eq!(
+            query.as_deref(),
+            Some("repo:rust-lang/rust is:issue \"in the closure signature at\"")
+        );
+        assert_eq!(query_of("expected N found vN"), None);
+        assert_eq!(encode("is:issue \"a b\""), "is%3Aissue%20%22a%20b%22");
+    }
+
+    #[test]
+    fn answers_are_cached() {
+        let dir =
+            std::env::temp_dir().join(format!("rustc-fuzz-duplicates-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        // Answers with one issue, and counts its calls and the headers it
+        // was given.
+        let curl = dir.join("curl");
+        fs::write(
+            &curl,
+            format!(
+                "#!/bin/sh\ncat >> {dir}/headers\necho called >> {dir}/calls\n\
+                 echo '{{\"items\": [{{\"number\": 7, \"title\": \"ICE: bad type\", \
+                 \"html_url\": \"https://example.com/7\", \"state\": \"open\"}}]}}'\n",
+                dir = dir.display()
+            ),
+        )
+        .expect("writes the curl");
+        fs::set_permissions(&curl, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let mut search = IssueSearch {
+            curl,
+            token: Some("secret".to_owned()),
+            ..IssueSearch::new(&dir)
+        };
+        let bad = signature("bad type in closure");
+        let found = search.search(&bad).expect("searches");
+        assert_eq!(
+            found,
+            [Candidate {
+                number: 7,
+                title: "ICE: bad type".to_owned(),
+                url: "https://example.com/7".to_owned(),
+                state: "open".to_owned(),
+            }]
+        );
+        assert_eq!(found[0].to_string(), "#7 (open): ICE: bad type");
+        assert_eq!(search.search(&bad).expect("searches"), found);
+        assert_eq!(
+            fs::read_to_string(dir.join("calls")).expect("reads"),
+            "called\n"
+        );
+        let headers = fs::read_to_string(dir.join("headers")).expect("reads");
+        assert!(
+            headers.contains("Authorization: Bearer secret\n"),
+            "{headers}"
+        );
+        assert!(dir.join(IssueSearch::CACHE).exists());
+        // Nothing to search for, so nothing is asked.
+        assert!(search.search(&signature("N")).expect("searches").is_empty());
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
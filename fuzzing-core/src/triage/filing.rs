@@ -0,0 +1,186 @@
+//! Filing findings into buckets.
+//!
+//! A [`Filer`] compiles every finding of a findings directory that is not
+//! in its [`Buckets`] yet, with the edition and flags its metadata
+//! records, and files it under the [`Signature`] of the crash. With
+//! [`Filer::runs`] set it reruns each crash first and files the ones that
+//! do not reproduce every time apart, recording the [`FlakeReport`] in
+//! the finding's metadata. New buckets of bugs [`KnownBugs`] does not know
+//! are searched for in the issue tracker when there is an
+//! [`IssueSearch`].
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::corpus::meta::{Metadata, Provenance};
+use crate::corpus::Corpus;
+use crate::harness::{ExecOutcome, Rustc};
+use crate::triage::{Buckets, FlakeCheck, FlakeReport, IssueSearch, KnownBugs, Signature};
+
+/// Compiles findings and files them by their crash.
+#[derive(Debug, Clone)]
+pub struct Filer {
+    pub rustc: Rustc,
+    /// How often each crash is rerun to tell whether it is flaky; not at
+    /// all if 0.
+    pub runs: u32,
+}
+
+/// What [`Filer::file`] did.
+#[derive(Debug, Default)]
+pub struct Filing {
+    /// Findings filed.
+    pub filed: usize,
+    /// Ids of the new buckets of bugs not known yet, in filing order.
+    pub new: Vec<String>,
+    /// New buckets of bugs already known.
+    pub new_known: usize,
+    /// Findings that crash only some of the time.
+    pub flaky: Vec<(PathBuf, FlakeReport)>,
+    /// Findings that do not crash any more, with what rustc does instead.
+    pub gone: Vec<(PathBuf, ExecOutcome)>,
+    /// New buckets the issue search failed for, and why.
+    pub unsearched: Vec<(String, io::Error)>,
+}
+
+impl Filer {
+    pub fn new(rustc: Rustc) -> Self {
+        Filer { rustc, runs: 0 }
+    }
+
+    /// Files the findings in `dir` that are not in `buckets` yet, looking
+    /// new buckets up in `known` and, for those it does not know, with
+    /// `search`, whose candidates become the bucket's duplicates.
+    pub fn file(
+        &self,
+        dir: &Path,
+        buckets: &mut Buckets,
+        known: &KnownBugs,
+        mut search: Option<&mut IssueSearch>,
+    ) -> io::Result<Filing> {
+        let mut filing = Filing::default();
+        for entry in Corpus::open(dir).entries()? {
+            let name = entry
+                .path
+                .strip_prefix(dir)
+                .unwrap_or(&entry.path)
+                .display()
+                .to_string();
+            if buckets.bucket_of(&name).is_some() {
+                continue;
+            }
+            let metadata = Metadata::load(&entry.path)
+                .map_err(about("reading metadata for", &entry.path))?
+                .filter(|m| m.matches(&entry.source));
+            let mut args = vec!["--crate-type=lib".to_owned()];
+            if let Some(metadata) = &metadata {
+                if let Some(edition) = &metadata.edition {
+                    args.extend(["--edition".to_owned(), edition.clone()]);
+                }
+                args.extend(metadata.flags.iter().cloned());
+            }
+            let input = args.iter().cloned().chain(["-".to_owned()]);
+            let outcome = self.rustc.run(input, entry.source.as_bytes())?;
+            let Some(mut signature) = Signature::of_outcome(&outcome) else {
+                filing.gone.push((entry.path, outcome));
+                continue;
+            };
+            if self.runs > 0 {
+                let check = FlakeCheck {
+                    args,
+                    runs: self.runs,
+                    ..FlakeCheck::new(self.rustc.clone())
+                };
+                let flake = check
+                    .check(&entry.source, &signature)
+                    .map_err(about("rerunning", &entry.path))?;
+                if flake.is_flaky() {
+                    signature = signature.flaky();
+                    filing.flaky.push((entry.path.clone(), flake.clone()));
+                }
+                let mut metadata =
+                    metadata.unwrap_or_else(|| Metadata::new(&entry.source, Provenance::Unknown));
+                metadata.flake = Some(flake);
+                metadata
+                    .save(&entry.path)
+                    .map_err(about("writing metadata for", &entry.path))?;
+            }
+            filing.filed += 1;
+            if !buckets.add(signature.clone(), &name, entry.source.len()) {
+                continue;
+            }
+            if known.lookup(&signature).is_some() {
+                filing.new_known += 1;
+                continue;
+            }
+            let id = signature.id();
+            if let Some(search) = search.as_deref_mut() {
+                match search.search(&signature) {
+                    Ok(candidates) => {
+                        if let Some(bucket) = buckets.get_mut(&id) {
+                            bucket.duplicates = candidates;
+                        }
+                    }
+                    Err(e) => filing.unsearched.push((id.clone(), e)),
+                }
+            }
+            filing.new.push(id);
+        }
+        Ok(filing)
+    }
+}
+
+/// Says what was being done to the finding at `path` when an error came.
+fn about(what: &'static str, path: &Path) -> impl FnOnce(io::Error) -> io::Error {
+    let path = path.display().to_string();
+    move |e| io::Error::new(e.kind(), format!("{what} {path}: {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn files_crashes_and_sets_aside_what_no_longer_crashes() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-filing-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the findings");
+        // ICEs on any seed that mentions `boom`.
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            "#!/bin/sh\nif grep -q boom; then\n  \
+             echo 'error: internal compiler error: boom at src/lib.rs:3:9' >&2\n  \
+             exit 101\nfi\n",
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        fs::write(dir.join("a.rs"), "fn boom() {}").expect("writes a finding");
+        fs::write(dir.join("b.rs"), "fn boom() { boom() }").expect("writes a finding");
+        fs::write(dir.join("c.rs"), "fn fixed() {}").expect("writes a finding");
+
+        let filer = Filer::new(Rustc::new(&rustc));
+        let mut buckets = Buckets::new();
+        let filing = filer
+            .file(&dir, &mut buckets, &KnownBugs::new(), None)
+            .expect("files");
+        assert_eq!(filing.filed, 2);
+        assert_eq!(filing.new.len(), 1);
+        assert_eq!(filing.new_known, 0);
+        let [(gone, ExecOutcome::Success(_))] = &filing.gone[..] else {
+            panic!("{:?}", filing.gone);
+        };
+        assert_eq!(gone, &dir.join("c.rs"));
+        let bucket = buckets.get(&filing.new[0]).expect("the new bucket");
+        assert_eq!(bucket.findings, ["a.rs", "b.rs"]);
+        assert_eq!(bucket.best, "a.rs");
+
+        // Filed findings are not compiled again.
+        let again = filer
+            .file(&dir, &mut buckets, &KnownBugs::new(), None)
+            .expect("files");
+        assert_eq!((again.filed, again.new.len()), (0, 0));
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,308 @@
+//! Crash flakiness.
+//!
+//! A crash that reproduces only some of the time cannot be reduced or
+//! bisected as it is: every test along the way may lie. [`FlakeCheck`]
+//! runs a new crasher again [`FlakeCheck::runs`] times and counts how
+//! often it crashes with the same [`Signature`]. When that is not every
+//! time, it runs it as often again under `-Zthreads=1`, and without
+//! `-Cincremental` if it was compiled with it, to tell whether the
+//! parallel front end or the incremental cache is the source of the
+//! nondeterminism. Nondeterministic crashes are bucketed apart from
+//! deterministic ones with the same signature.
+
+use std::fmt;
+use std::io;
+
+use serde::{Deserialize, Serialize};
+
+use crate::harness::Rustc;
+use crate::triage::Signature;
+
+/// How often a crash reproduced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Determinism {
+    pub runs: u32,
+    /// Runs that crashed with the signature.
+    pub reproduced: u32,
+}
+
+impl Determinism {
+    /// The share of runs that reproduced the crash.
+    pub fn score(self) -> f64 {
+        if self.runs == 0 {
+            1.0
+        } else {
+            f64::from(self.reproduced) / f64::from(self.runs)
+        }
+    }
+
+    /// Whether every run agreed, crashing or not.
+    pub fn is_deterministic(self) -> bool {
+        self.reproduced == 0 || self.reproduced == self.runs
+    }
+}
+
+impl fmt::Display for Determinism {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} runs", self.reproduced, self.runs)
+    }
+}
+
+/// What a nondeterministic crash turned out to depend on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlakeCause {
+    /// It is deterministic with `-Zthreads=1`.
+    Threads,
+    /// It is deterministic without `-Cincremental`.
+    Incremental,
+    /// Neither made it deterministic.
+    Unknown,
+}
+
+impl FlakeCause {
+    pub fn name(self) -> &'static str {
+        match self {
+            FlakeCause::Threads => "threads",
+            FlakeCause::Incremental => "incremental",
+            FlakeCause::Unknown => "unknown",
+        }
+    }
+}
+
+impl fmt::Display for FlakeCause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The verdict on one crasher.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlakeReport {
+    /// With the arguments it was found with.
+    pub determinism: Determinism,
+    /// With `-Zthreads=1`, if it was not deterministic.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub threads: Option<Determinism>,
+    /// Without `-Cincremental`, if it was not deterministic and had it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub incremental: Option<Determinism>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cause: Option<FlakeCause>,
+}
+
+impl FlakeReport {
+    /// Whether some rerun did not crash as the first run did.
+    pub fn is_flaky(&self) -> bool {
+        self.determinism.reproduced < self.determinism.runs
+    }
+}
+
+impl fmt::Display for FlakeReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "reproduced in {}", self.determinism)?;
+        if let Some(threads) = self.threads {
+            write!(f, ", {threads} with -Zthreads=1")?;
+        }
+        if let Some(incremental) = self.incremental {
+            write!(f, ", {incremental} without -Cincremental")?;
+        }
+        if let Some(cause) = self.cause {
+            write!(f, "; cause: {cause}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reruns crashers to score their determinism.
+#[derive(Debug, Clone)]
+pub struct FlakeCheck {
+    pub driver: Rustc,
+    /// Arguments before the input, which is read from stdin.
+    pub args: Vec<String>,
+    /// Runs per configuration.
+    pub runs: u32,
+}
+
+impl FlakeCheck {
+    pub fn new(driver: Rustc) -> Self {
+        FlakeCheck {
+            driver,
+            args: vec!["--crate-type=lib".to_owned()],
+            runs: 5,
+        }
+    }
+
+    /// How reliably `source` crashes with `signature`, and if not
+    /// reliably, why.
+    pub fn check(&self, source: &str, signature: &Signature) -> io::Result<FlakeReport> {
+        let determinism = self.score(source, signature, &self.args)?;
+        let mut report = FlakeReport {
+            determinism,
+            threads: None,
+            incremental: None,
+            cause: None,
+        };
+        if !report.is_flaky() {
+            return Ok(report);
+        }
+        let mut threads: Vec<String> = self
+            .args
+            .iter()
+            .filter(|arg| !arg.starts_with("-Zthreads"))
+            .cloned()
+            .collect();
+        threads.push("-Zthreads=1".to_owned());
+        let score = self.score(source, signature, &threads)?;
+        report.threads = Some(score);
+        if score.is_deterministic() {
+            report.cause = Some(FlakeCause::Threads);
+            return Ok(report);
+        }
+        if let Some(args) = without_incremental(&self.args) {
+            let score = self.score(source, signature, &args)?;
+            report.incremental = Some(score);
+            if score.is_deterministic() {
+                report.cause = Some(FlakeCause::Incremental);
+                return Ok(report);
+            }
+        }
+        report.cause = Some(FlakeCause::Unknown);
+        Ok(report)
+    }
+
+    fn score(
+        &self,
+        source: &str,
+        signature: &Signature,
+        args: &[String],
+    ) -> io::Result<Determinism> {
+        let mut args = args.to_vec();
+        args.push("-".to_owned());
+        let mut reproduced = 0;
+        for _ in 0..self.runs {
+            let outcome = self.driver.run(&args, source.as_bytes())?;
+            if Signature::of_outcome(&outcome).is_some_and(|found| found.matches(signature)) {
+                reproduced += 1;
+            }
+        }
+        Ok(Determinism {
+            runs: self.runs,
+            reproduced,
+        })
+    }
+}
+
+/// `args` without `-Cincremental=DIR` or `-C incremental=DIR`, or `None`
+/// if they do not have it.
+fn without_incremental(args: &[String]) -> Option<Vec<String>> {
+    let mut kept = Vec::new();
+    let mut found = false;
+    let mut iter = args.iter().peekable();
+    while let Some(arg) = iter.next() {
+        if arg.starts_with("-Cincremental") {
+            found = true;
+        } else if arg == "-C"
+            && iter
+                .peek()
+                .is_some_and(|value| value.starts_with("incremental"))
+        {
+            found = true;
+            iter.next();
+        } else {
+            kept.push(arg.clone());
+        }
+    }
+    found.then_some(kept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::triage::ice;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    const ICE: &str =
+        "error: internal compiler error: compiler/rustc_middle/src/ty/mod.rs:1:2: boom";
+
+    #[test]
+    fn determinism_scores() {
+        let half = Determinism {
+            runs: 4,
+            reproduced: 2,
+        };
+        assert_eq!(half.score(), 0.5);
+        assert!(!half.is_deterministic());
+        assert!(Determinism {
+            runs: 4,
+            reproduced: 0
+        }
+        .is_deterministic());
+        assert_eq!(half.to_string(), "2 of 4 runs");
+    }
+
+    #[test]
+    fn incremental_flags_come_off_in_both_spellings() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            without_incremental(&args(&["-Cincremental=/tmp/i", "-O"])),
+            Some(args(&["-O"]))
+        );
+        assert_eq!(
+            without_incremental(&args(&["-C", "incremental=/tmp/i", "-C", "opt-level=3"])),
+            Some(args(&["-C", "opt-level=3"]))
+        );
+        assert_eq!(without_incremental(&args(&["-C", "opt-level=3"])), None);
+    }
+
+    #[test]
+    fn flaky_crashes_are_traced_to_their_cause() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-flaky-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        // ICEs every time, except every other time with `-Zthreads=4` or
+        // an incremental cache.
+        let rustc = dir.join("rustc");
+        let count = dir.join("count");
+        fs::write(
+            &rustc,
+            format!(
+                "#!/bin/sh\ncase \"$*\" in *threads=4*|*incremental*)\n  \
+                 n=$(cat {count} 2>/dev/null || echo 0)\n  echo $((n + 1)) > {count}\n  \
+                 [ $((n % 2)) = 0 ] || exit 0\nesac\necho '{ICE}' >&2\nexit 101\n",
+                count = count.display()
+            ),
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let signature = Signature::of(&ice::parse(ICE).expect("an ICE"));
+        let check = |args: &[&str]| {
+            FlakeCheck {
+                args: args.iter().map(|arg| arg.to_string()).collect(),
+                runs: 4,
+                ..FlakeCheck::new(Rustc::new(&rustc))
+            }
+            .check("fn boom() {}", &signature)
+            .expect("runs")
+        };
+
+        let steady = check(&["--crate-type=lib"]);
+        assert!(!steady.is_flaky());
+        assert_eq!((steady.threads, steady.cause), (None, None));
+
+        let threads = check(&["--crate-type=lib", "-Zthreads=4"]);
+        assert!(threads.is_flaky());
+        assert_eq!(threads.determinism.reproduced, 2);
+        assert_eq!(threads.cause, Some(FlakeCause::Threads));
+        assert_eq!(
+            threads.to_string(),
+            "reproduced in 2 of 4 runs, 4 of 4 runs with -Zthreads=1; cause: threads"
+        );
+
+        let incremental = check(&["--crate-type=lib", "-C", "incremental=/tmp/i"]);
+        assert_eq!(incremental.threads.map(|d| d.reproduced), Some(2));
+        assert_eq!(incremental.incremental.map(|d| d.reproduced), Some(4));
+        assert_eq!(incremental.cause, Some(FlakeCause::Incremental));
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
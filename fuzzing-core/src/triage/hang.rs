@@ -0,0 +1,328 @@
+//! Timeout triage.
+//!
+//! A seed that timed out once may hang rustc, may take super-linear time
+//! that a bigger budget would see through, or may have met a machine busy
+//! with something else. [`HangTriage`] tells them apart by running it
+//! again: at the original budget, then at twice, four times and so on up
+//! to [`HangTriage::max_factor`] times it. A compile that finishes within
+//! the original budget now was [`HangClass::Overload`]. One that finishes
+//! only with more is run again at that budget: if it times out this time
+//! it is a [`HangClass::Nondeterministic`] hang, otherwise a
+//! [`HangClass::Blowup`], which is profiled to record the query that
+//! dominates it. One that never finishes is a [`HangClass::Hang`].
+//!
+//! Profiles come from `-Zself-profile` read with measureme's `summarize`
+//! when it is installed, and from `-Ztime-passes` otherwise, which names
+//! compiler passes rather than queries.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::harness::rustc::Scratch;
+use crate::harness::{ExecOutcome, Rustc};
+
+/// What a timeout turned out to be.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HangClass {
+    /// It finishes within the original budget now.
+    Overload,
+    /// It finishes, consistently, but only with a bigger budget.
+    Blowup,
+    /// It finished once with a bigger budget and timed out with the same
+    /// budget again.
+    Nondeterministic,
+    /// It never finished.
+    Hang,
+}
+
+impl HangClass {
+    pub const ALL: [HangClass; 4] = [
+        HangClass::Overload,
+        HangClass::Blowup,
+        HangClass::Nondeterministic,
+        HangClass::Hang,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            HangClass::Overload => "overload",
+            HangClass::Blowup => "blowup",
+            HangClass::Nondeterministic => "nondeterministic",
+            HangClass::Hang => "hang",
+        }
+    }
+}
+
+impl fmt::Display for HangClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// The verdict on one timeout.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HangReport {
+    pub class: HangClass,
+    /// The original budget, in milliseconds.
+    pub budget_ms: u64,
+    /// How long the compile took when it finished, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<u64>,
+    /// The query, or pass with `-Ztime-passes`, that took the most time
+    /// of a blowup.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dominant: Option<String>,
+}
+
+impl fmt::Display for HangReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.class)?;
+        if let Some(elapsed) = self.elapsed_ms {
+            write!(
+                f,
+                " ({elapsed} ms against a budget of {} ms)",
+                self.budget_ms
+            )?;
+        }
+        if let Some(dominant) = &self.dominant {
+            write!(f, ", mostly {dominant}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Reruns timeouts to classify them.
+#[derive(Debug, Clone)]
+pub struct HangTriage {
+    /// Runs at the original budget, [`Rustc::timeout`].
+    pub driver: Rustc,
+    /// Arguments before the input, which is read from stdin.
+    pub args: Vec<String>,
+    /// The biggest multiple of the budget to try.
+    pub max_factor: u32,
+    /// measureme's `summarize`.
+    pub summarize: PathBuf,
+}
+
+impl HangTriage {
+    pub fn new(driver: Rustc) -> Self {
+        HangTriage {
+            driver,
+            args: vec!["--crate-type=lib".to_owned()],
+            max_factor: 8,
+            summarize: PathBuf::from("summarize"),
+        }
+    }
+
+    /// Classifies the timeout `source` caused.
+    pub fn classify(&self, source: &str) -> io::Result<HangReport> {
+        let budget = self.driver.timeout;
+        let report = |class, elapsed: Option<Duration>, dominant| HangReport {
+            class,
+            budget_ms: budget.as_millis() as u64,
+            elapsed_ms: elapsed.map(|elapsed| elapsed.as_millis() as u64),
+            dominant,
+        };
+        if let Some(elapsed) = self.finishes(source, budget, &[])? {
+            return Ok(report(HangClass::Overload, Some(elapsed), None));
+        }
+        let mut factor = 2;
+        while factor <= self.max_factor {
+            let longer = budget * factor;
+            factor *= 2;
+            let Some(elapsed) = self.finishes(source, longer, &[])? else {
+                continue;
+            };
+            if self.finishes(source, longer, &[])?.is_none() {
+                return Ok(report(HangClass::Nondeterministic, Some(elapsed), None));
+            }
+            let dominant = self.dominant(source, longer)?;
+            return Ok(report(HangClass::Blowup, Some(elapsed), dominant));
+        }
+        Ok(report(HangClass::Hang, None, None))
+    }
+
+    /// How long compiling `source` with `extra` arguments takes, or `None`
+    /// if it takes longer than `budget`.
+    fn finishes(
+        &self,
+        source: &str,
+        budget: Duration,
+        extra: &[String],
+    ) -> io::Result<Option<Duration>> {
+        Ok(match self.run(source, budget, extra)? {
+            ExecOutcome::Timeout(_) => None,
+            outcome => Some(outcome.output().elapsed),
+        })
+    }
+
+    fn run(&self, source: &str, budget: Duration, extra: &[String]) -> io::Result<ExecOutcome> {
+        let driver = Rustc {
+            timeout: budget,
+            ..self.driver.clone()
+        };
+        let mut args = self.args.clone();
+        args.extend(extra.iter().cloned());
+        args.push("-".to_owned());
+        driver.run(&args, source.as_bytes())
+    }
+
+    /// The query that takes the most self time compiling `source`, or the
+    /// longest pass if `summarize` is not installed.
+    fn dominant(&self, source: &str, budget: Duration) -> io::Result<Option<String>> {
+        let dir = Scratch::new(&self.driver.scratch_root)?;
+        let profile = [format!("-Zself-profile={}", dir.path().display())];
+        self.run(source, budget, &profile)?;
+        if let Some(data) = profile_data(dir.path())? {
+            match Command::new(&self.summarize)
+                .args(["summarize", "--json"])
+                .arg(&data)
+                .output()
+            {
+                Ok(output) if output.status.success() => {
+                    return Ok(slowest_query(&String::from_utf8_lossy(&output.stdout)));
+                }
+                Ok(_) => {}
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+        }
+        let outcome = self.run(source, budget, &["-Ztime-passes".to_owned()])?;
+        Ok(slowest_pass(&outcome.output().stderr))
+    }
+}
+
+/// The `.mm_profdata` file `-Zself-profile` wrote to `dir`.
+fn profile_data(dir: &Path) -> io::Result<Option<PathBuf>> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|ext| ext == "mm_profdata") {
+            return Ok(Some(path));
+        }
+    }
+    Ok(None)
+}
+
+/// The label with the most self time in `summarize --json` output, whose
+/// `query_data` entries have a `label` and a `self_time` of `secs` and
+/// `nanos`.
+fn slowest_query(json: &str) -> Option<String> {
+    let results: serde_json::Value = serde_json::from_str(json).ok()?;
+    let self_time = |query: &serde_json::Value| {
+        let time = &query["self_time"];
+        time["secs"].as_u64().unwrap_or(0) as f64 + time["nanos"].as_u64().unwrap_or(0) as f64 / 1e9
+    };
+    results["query_data"]
+        .as_array()?
+        .iter()
+        .max_by(|a, b| self_time(a).total_cmp(&self_time(b)))
+        .and_then(|query| query["label"].as_str())
+        .map(str::to_owned)
+}
+
+/// The longest pass in `-Ztime-passes` output, whose lines look like
+/// `time:   0.002; rss:   32MB ->   34MB (   +2MB) parse_crate`. The
+/// `total` line is left out.
+fn slowest_pass(stderr: &str) -> Option<String> {
+    stderr
+        .lines()
+        .filter_map(|line| {
+            let rest = line.strip_prefix("time:")?;
+            let (seconds, rest) = rest.split_once(';')?;
+            let seconds: f64 = seconds.trim().parse().ok()?;
+            let pass = rest.rsplit(['\t', ')']).next()?.trim();
+            (pass != "total" && !pass.is_empty()).then(|| (seconds, pass.to_owned()))
+        })
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, pass)| pass)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Takes half a second on input that mentions `slow` and five on
+    /// input that mentions `stuck`; reports `typeck` as the slowest pass.
+    const RUSTC: &str = "#!/bin/sh
+for arg; do
+  [ \"$arg\" = -Ztime-passes ] && printf 'time:   0.100; rss:   32MB ->   34MB (   +2MB)\\ttypeck\\ntime:   0.300; rss:   32MB ->   40MB (   +8MB)\\ttotal\\n' >&2
+done
+input=$(cat)
+case $input in
+  *slow*) exec sleep 0.5 ;;
+  *stuck*) exec sleep 5 ;;
+esac
+";
+
+    #[test]
+    fn timeouts_are_told_apart_by_running_them_again() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-hang-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        let rustc = dir.join("rustc");
+        fs::write(&rustc, RUSTC).expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let triage = HangTriage {
+            max_factor: 4,
+            summarize: dir.join("no-summarize"),
+            ..HangTriage::new(Rustc {
+                timeout: Duration::from_millis(200),
+                ..Rustc::new(&rustc)
+            })
+        };
+
+        let overload = triage.classify("fn fast() {}").expect("runs");
+        assert_eq!(overload.class, HangClass::Overload);
+        let blowup = triage.classify("fn slow() {}").expect("runs");
+        assert_eq!(blowup.class, HangClass::Blowup, "{blowup}");
+        assert_eq!(blowup.dominant.as_deref(), Some("typeck"));
+        assert!(blowup.elapsed_ms.is_some_and(|ms| ms >= 500), "{blowup}");
+        let hang = HangTriage {
+            max_factor: 2,
+            ..triage
+        }
+        .classify("fn stuck() {}")
+        .expect("runs");
+        assert_eq!(
+            hang,
+            HangReport {
+                class: HangClass::Hang,
+                budget_ms: 200,
+                elapsed_ms: None,
+                dominant: None,
+            }
+        );
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+
+    #[test]
+    fn profiles_name_the_slowest_query() {
+        let json = r#"{"query_data": [
+            {"label": "typeck", "self_time": {"secs": 1, "nanos": 0}},
+            {"label": "mir_borrowck", "self_time": {"secs": 1, "nanos": 5}}
+        ]}"#;
+        assert_eq!(slowest_query(json).as_deref(), Some("mir_borrowck"));
+        assert_eq!(slowest_query("not json"), None);
+        let passes = "time:   0.002; rss:   32MB ->   34MB (   +2MB)\tparse_crate\n\
+                      time:   0.010; rss:   34MB ->   40MB (   +6MB)\ttype_check_crate\n\
+                      time:   0.020; rss:   32MB ->   40MB (   +8MB)\ttotal\n";
+        assert_eq!(slowest_pass(passes).as_deref(), Some("type_check_crate"));
+        let report = HangReport {
+            class: HangClass::Blowup,
+            budget_ms: 100,
+            elapsed_ms: Some(250),
+            dominant: Some("typeck".to_owned()),
+        };
+        assert_eq!(
+            report.to_string(),
+            "blowup (250 ms against a budget of 100 ms), mostly typeck"
+        );
+    }
+}
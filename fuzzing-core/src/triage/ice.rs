@@ -0,0 +1,486 @@
+//! Internal compiler error reports.
+//!
+//! An ICE is a few hundred lines of stderr in one of several shapes: a
+//! `bug!` or `span_bug!` names its caller in the `internal compiler
+//! error:` line, a plain panic names its location in the `panicked at`
+//! line, delayed bugs that were never emitted are each noted with where
+//! they were delayed, and under `-Ztreat-err-as-bug` an ordinary error
+//! becomes an ICE with its error code. Most of them carry a query stack.
+//! [`parse`] reads all of these into an [`IceReport`], and
+//! [`IceReport::location`] picks the compiler source line that is
+//! actually to blame out of them.
+
+use std::fmt;
+
+/// What kind of ICE a report is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IceKind {
+    /// `bug!`, `span_bug!` or another `internal compiler error:` with a
+    /// message.
+    Bug,
+    /// A panic with no `internal compiler error:` line of its own: a
+    /// failed assertion, an `unwrap`, an index out of bounds.
+    Panic,
+    /// Bugs delayed in case no error was emitted, and none was.
+    DelayedBug,
+    /// An ordinary error turned into an ICE by `-Ztreat-err-as-bug`.
+    TreatErrAsBug,
+}
+
+impl IceKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            IceKind::Bug => "bug",
+            IceKind::Panic => "panic",
+            IceKind::DelayedBug => "delayed-bug",
+            IceKind::TreatErrAsBug => "treat-err-as-bug",
+        }
+    }
+}
+
+impl fmt::Display for IceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A place in the compiler's source.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Location {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl Location {
+    /// Parses `file:line:column`.
+    pub fn parse(text: &str) -> Option<Self> {
+        let (rest, column) = text.trim().rsplit_once(':')?;
+        let (file, line) = rest.rsplit_once(':')?;
+        if file.is_empty() || file.contains(' ') {
+            return None;
+        }
+        Some(Location {
+            file: file.to_owned(),
+            line: line.parse().ok()?,
+            column: column.parse().ok()?,
+        })
+    }
+
+    /// The compiler crate the file belongs to, like `rustc_middle`.
+    pub fn krate(&self) -> Option<&str> {
+        self.file
+            .split('/')
+            .find(|part| part.starts_with("rustc_") || *part == "librustdoc")
+    }
+
+    /// Whether this is inside the machinery that turns a `bug!` or a
+    /// delayed bug into a panic, rather than where the bug is.
+    fn is_bug_machinery(&self) -> bool {
+        ["rustc_middle/src/util/bug.rs", "rustc_errors/src/"]
+            .iter()
+            .any(|path| self.file.contains(path))
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+/// The `thread '...' panicked at` part of a report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Panic {
+    pub thread: String,
+    pub location: Option<Location>,
+    pub message: String,
+}
+
+/// One frame of the query stack, innermost first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Query {
+    /// The query's name, like `typeck`.
+    pub name: String,
+    /// What it was doing, like ``type-checking `main` ``.
+    pub description: String,
+}
+
+/// A delayed bug that was never emitted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DelayedBug {
+    pub message: String,
+    pub location: Option<Location>,
+}
+
+/// Everything an ICE's stderr says about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IceReport {
+    pub kind: IceKind,
+    /// The `internal compiler error:` message without its location, or
+    /// the panic message if there is none.
+    pub message: String,
+    /// Where the `internal compiler error:` line says the bug is.
+    pub bug_location: Option<Location>,
+    pub panic: Option<Panic>,
+    pub query_stack: Vec<Query>,
+    pub delayed: Vec<DelayedBug>,
+    /// The error code of an error under `-Ztreat-err-as-bug`.
+    pub error_code: Option<String>,
+    /// The `--> file:line:col` of the input the first ICE points at.
+    pub span: Option<String>,
+    /// The `rustc x.y.z (hash date)` the report names.
+    pub rustc: Option<String>,
+}
+
+impl IceReport {
+    /// The compiler source line to blame: where a `bug!` was raised,
+    /// where the first delayed bug was delayed, or where the panic
+    /// happened, in that order; panics inside the machinery that reports
+    /// bugs are passed over while anything better is known.
+    pub fn location(&self) -> Option<&Location> {
+        let panic = self.panic.as_ref().and_then(|p| p.location.as_ref());
+        self.bug_location
+            .as_ref()
+            .or_else(|| self.delayed.iter().find_map(|d| d.location.as_ref()))
+            .or(panic.filter(|location| !location.is_bug_machinery()))
+            .or(panic)
+    }
+
+    /// The innermost query, if there was a query stack.
+    pub fn query(&self) -> Option<&str> {
+        self.query_stack.first().map(|query| query.name.as_str())
+    }
+}
+
+impl fmt::Display for IceReport {
+    /// `kind at location: message`, on one line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.kind)?;
+        if let Some(code) = &self.error_code {
+            write!(f, "[{code}]")?;
+        }
+        if let Some(location) = self.location() {
+            write!(f, " at {location}")?;
+        }
+        match self.message.lines().next() {
+            Some(message) if !message.is_empty() => write!(f, ": {message}"),
+            _ => Ok(()),
+        }
+    }
+}
+
+const ICE_PREFIX: &str = "error: internal compiler error";
+const QUERY_STACK_START: &str = "query stack during panic:";
+const QUERY_STACK_END: &str = "end of query stack";
+const DELAYED_AT: &str = "delayed at ";
+const NO_ERRORS: &str = "no errors encountered even though delayed bugs were created";
+
+/// Reads the ICE on rustc's `stderr`, or `None` if there is none.
+pub fn parse(stderr: &str) -> Option<IceReport> {
+    let lines: Vec<&str> = stderr.lines().collect();
+    let mut ices = Vec::new();
+    let mut panic = None;
+    let mut query_stack = Vec::new();
+    let mut delayed = Vec::new();
+    let mut span = None;
+    let mut rustc = None;
+    let mut in_query_stack = false;
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        if in_query_stack {
+            if trimmed.starts_with(QUERY_STACK_END) {
+                in_query_stack = false;
+            } else if let Some(query) = parse_query(trimmed) {
+                query_stack.push(query);
+            }
+            continue;
+        }
+        if trimmed == QUERY_STACK_START {
+            in_query_stack = true;
+        } else if let Some(rest) = line.strip_prefix(ICE_PREFIX) {
+            ices.push(parse_ice_line(rest));
+        } else if let Some(rest) = trimmed.strip_prefix("thread '") {
+            if panic.is_none() {
+                panic = parse_panic(rest, &lines[i + 1..]);
+            }
+        } else if let Some(rest) = trimmed.split_once(DELAYED_AT).map(|(_, rest)| rest) {
+            let location = rest.split(" - ").next().and_then(Location::parse);
+            // The delayed bug's own message is on the nearest
+            // `internal compiler error:` line above.
+            let message = ices
+                .last()
+                .map(|ice: &IceLine| ice.message.clone())
+                .unwrap_or_default();
+            delayed.push(DelayedBug { message, location });
+        } else if let Some(rest) = trimmed.strip_prefix("--> ") {
+            if span.is_none() && !ices.is_empty() {
+                span = Some(rest.to_owned());
+            }
+        } else if let Some(rest) = trimmed.strip_prefix("note: rustc ") {
+            if let Some((version, _)) = rest.split_once(" running on ") {
+                rustc = Some(format!("rustc {version}"));
+            }
+        }
+    }
+    if ices.is_empty() && panic.is_none() {
+        return None;
+    }
+
+    // The summary line of unemitted delayed bugs says nothing about them.
+    let first = ices.iter().find(|ice| ice.message != NO_ERRORS);
+    let treat_err_as_bug = panic
+        .as_ref()
+        .is_some_and(|p: &Panic| p.message.contains("treat-err-as-bug"))
+        || first.is_some_and(|ice| ice.code.is_some());
+    let kind = if treat_err_as_bug {
+        IceKind::TreatErrAsBug
+    } else if !delayed.is_empty() || ices.iter().any(|ice| ice.message == NO_ERRORS) {
+        IceKind::DelayedBug
+    } else if ices.is_empty() {
+        IceKind::Panic
+    } else {
+        IceKind::Bug
+    };
+    let (error_code, bug_location, message) = match first {
+        Some(ice) => (ice.code.clone(), ice.location.clone(), ice.message.clone()),
+        None => (
+            None,
+            None,
+            panic
+                .as_ref()
+                .map(|p| p.message.clone())
+                .unwrap_or_default(),
+        ),
+    };
+    // A delayed bug's location is where it was delayed, not where the
+    // message says it was finally reported.
+    let bug_location = bug_location.filter(|_| kind != IceKind::DelayedBug);
+    Some(IceReport {
+        kind,
+        message,
+        bug_location,
+        panic,
+        query_stack,
+        delayed,
+        error_code: error_code.filter(|_| kind == IceKind::TreatErrAsBug),
+        span,
+        rustc,
+    })
+}
+
+/// One `error: internal compiler error` line.
+struct IceLine {
+    code: Option<String>,
+    /// The location a `bug!` names.
+    location: Option<Location>,
+    message: String,
+}
+
+/// Parses what follows `error: internal compiler error`.
+fn parse_ice_line(rest: &str) -> IceLine {
+    let mut code = None;
+    let mut rest = rest;
+    if let Some(bracketed) = rest.strip_prefix('[') {
+        if let Some((inner, after)) = bracketed.split_once(']') {
+            code = Some(inner.to_owned());
+            rest = after;
+        }
+    }
+    let rest = rest.strip_prefix(':').unwrap_or(rest).trim();
+    // `bug!` prefixes its message with `file:line:col: `.
+    if let Some(end) = rest.find(".rs:") {
+        if let Some((prefix, message)) = rest[end..]
+            .match_indices(": ")
+            .next()
+            .map(|(i, _)| (&rest[..end + i], &rest[end + i + 2..]))
+        {
+            if let Some(location) = Location::parse(prefix) {
+                return IceLine {
+                    code,
+                    location: Some(location),
+                    message: message.trim().to_owned(),
+                };
+            }
+        }
+    }
+    IceLine {
+        code,
+        location: None,
+        message: rest.to_owned(),
+    }
+}
+
+/// Parses what follows `thread '` on a panic line, and the message lines
+/// after it in the current format.
+fn parse_panic(rest: &str, following: &[&str]) -> Option<Panic> {
+    let (thread, rest) = rest.split_once("' panicked at ")?;
+    let thread = thread.to_owned();
+    // Before 1.73: `panicked at 'message', file:line:col`.
+    if let Some(quoted) = rest.strip_prefix('\'') {
+        let (message, location) = quoted.rsplit_once("', ")?;
+        return Some(Panic {
+            thread,
+            location: Location::parse(location),
+            message: message.to_owned(),
+        });
+    }
+    // Since: `panicked at file:line:col:` and the message below.
+    let location = Location::parse(rest.trim_end().trim_end_matches(':'));
+    let message: Vec<&str> = following
+        .iter()
+        .take_while(|line| {
+            !line.trim().is_empty()
+                && !line.starts_with("stack backtrace:")
+                && !line.starts_with("note:")
+                && !line.starts_with("error:")
+        })
+        .copied()
+        .collect();
+    Some(Panic {
+        thread,
+        location,
+        message: message.join("\n"),
+    })
+}
+
+/// Parses `#0 [typeck] type-checking `main``.
+fn parse_query(line: &str) -> Option<Query> {
+    let rest = line.strip_prefix('#')?;
+    let (_, rest) = rest.split_once(" [")?;
+    let (name, description) = rest.split_once(']')?;
+    Some(Query {
+        name: name.to_owned(),
+        description: description.trim().to_owned(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUG: &str = "\
+error: internal compiler error: compiler/rustc_middle/src/ty/mod.rs:1234:5: unexpected region `'a/#0`
+ --> src/main.rs:3:9
+  |
+3 |     let x: &'a u8 = y;
+  |         ^
+
+thread 'rustc' panicked at compiler/rustc_middle/src/util/bug.rs:35:44:
+Box<dyn Any>
+stack backtrace:
+   0: std::panicking::begin_panic
+
+note: rustc 1.80.0-nightly (0123456789 2024-05-01) running on x86_64-unknown-linux-gnu
+
+query stack during panic:
+#0 [typeck] type-checking `main`
+#1 [analysis] running analysis passes on this crate
+end of query stack
+";
+
+    #[test]
+    fn parses_a_bug() {
+        let report = parse(BUG).expect("an ICE");
+        assert_eq!(report.kind, IceKind::Bug);
+        assert_eq!(report.message, "unexpected region `'a/#0`");
+        assert_eq!(
+            report.location(),
+            Some(&Location {
+                file: "compiler/rustc_middle/src/ty/mod.rs".to_owned(),
+                line: 1234,
+                column: 5,
+            })
+        );
+        assert_eq!(report.query(), Some("typeck"));
+        assert_eq!(report.query_stack.len(), 2);
+        assert_eq!(report.span.as_deref(), Some("src/main.rs:3:9"));
+        assert_eq!(
+            report.rustc.as_deref(),
+            Some("rustc 1.80.0-nightly (0123456789 2024-05-01)")
+        );
+        assert_eq!(report.panic.expect("a panic").message, "Box<dyn Any>");
+    }
+
+    #[test]
+    fn parses_a_panic_in_either_format() {
+        let old =
+            "thread 'rustc' panicked at 'index out of bounds: the len is 3 but the index is 5', \
+                   compiler/rustc_mir_transform/src/gvn.rs:100:9\n";
+        let new = "thread 'rustc' panicked at compiler/rustc_mir_transform/src/gvn.rs:100:9:\n\
+                   index out of bounds: the len is 3 but the index is 5\n\
+                   note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\n";
+        for stderr in [old, new] {
+            let report = parse(stderr).expect("an ICE");
+            assert_eq!(report.kind, IceKind::Panic);
+            assert_eq!(
+                report.message,
+                "index out of bounds: the len is 3 but the index is 5"
+            );
+            let location = report.location().expect("a location");
+            assert_eq!(location.file, "compiler/rustc_mir_transform/src/gvn.rs");
+            assert_eq!(location.krate(), Some("rustc_mir_transform"));
+        }
+    }
+
+    #[test]
+    fn parses_delayed_bugs() {
+        let stderr = "\
+error: internal compiler error: no errors encountered even though delayed bugs were created
+
+error: internal compiler error: broken MIR in DefId(0:4 ~ x[ab12]::main): bad assignment
+  --> src/main.rs:2:5
+note: delayed at compiler/rustc_borrowck/src/type_check/mod.rs:1050:13 - disabled backtrace
+";
+        let report = parse(stderr).expect("an ICE");
+        assert_eq!(report.kind, IceKind::DelayedBug);
+        assert_eq!(
+            report.message,
+            "broken MIR in DefId(0:4 ~ x[ab12]::main): bad assignment"
+        );
+        assert_eq!(report.delayed.len(), 1);
+        assert_eq!(
+            report.location().map(|l| l.file.as_str()),
+            Some("compiler/rustc_borrowck/src/type_check/mod.rs")
+        );
+    }
+
+    #[test]
+    fn parses_treat_err_as_bug() {
+        let stderr = "\
+error: internal compiler error[E0308]: mismatched types
+ --> <anon>:2:5
+
+thread 'rustc' panicked at compiler/rustc_errors/src/lib.rs:1700:30:
+aborting due to `-Z treat-err-as-bug=1`
+";
+        let report = parse(stderr).expect("an ICE");
+        assert_eq!(report.kind, IceKind::TreatErrAsBug);
+        assert_eq!(report.error_code.as_deref(), Some("E0308"));
+        assert_eq!(
+            report.to_string(),
+            "treat-err-as-bug[E0308] at compiler/rustc_errors/src/lib.rs:1700:30: mismatched types"
+        );
+    }
+
+    #[test]
+    fn ordinary_errors_are_not_ices() {
+        assert_eq!(parse("error[E0308]: mismatched types\n"), None);
+        assert_eq!(parse(""), None);
+    }
+
+    #[test]
+    fn parses_locations() {
+        assert_eq!(
+            Location::parse("compiler/rustc_hir/src/def.rs:10:5"),
+            Some(Location {
+                file: "compiler/rustc_hir/src/def.rs".to_owned(),
+                line: 10,
+                column: 5,
+            })
+        );
+        assert_eq!(Location::parse("not a location"), None);
+        assert_eq!(Location::parse("a file.rs:1:2"), None);
+        assert_eq!(Location::parse("file.rs:x:2"), None);
+    }
+}
@@ -0,0 +1,156 @@
+//! Known bugs.
+//!
+//! Open ICEs stay open for months, and every campaign finds them again. A
+//! [`KnownBugs`] database maps crash [`Signature`]s to the issues they
+//! were reported in. Each findings directory has its own in
+//! [`KnownBugs::FILE`], and shared ones, say in a repository a team syncs,
+//! are merged into it. Buckets whose signature is known carry its issue,
+//! are not announced as new, and get no report drafted, but are still
+//! listed and counted.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+
+use crate::triage::Signature;
+
+/// A crash signature already reported upstream.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownBug {
+    pub signature: Signature,
+    /// Where it was reported, usually a rust-lang/rust issue URL.
+    pub issue: String,
+}
+
+/// Known bugs by signature id.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KnownBugs {
+    bugs: BTreeMap<String, KnownBug>,
+}
+
+impl KnownBugs {
+    /// The database's file name in a findings directory.
+    pub const FILE: &'static str = "known-bugs.json";
+
+    pub fn new() -> Self {
+        KnownBugs::default()
+    }
+
+    /// The database at `path`, empty if there is none yet.
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match fs::read_to_string(path) {
+            Ok(json) => Ok(serde_json::from_str(&json)?),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(KnownBugs::new()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        fs::write(path, json)
+    }
+
+    /// Records that `signature` was reported at `issue`, returning the
+    /// issue it was recorded with before, if any.
+    pub fn add(&mut self, signature: Signature, issue: &str) -> Option<String> {
+        self.bugs
+            .insert(
+                signature.id(),
+                KnownBug {
+                    signature,
+                    issue: issue.to_owned(),
+                },
+            )
+            .map(|old| old.issue)
+    }
+
+    /// Adds the bugs of `other`, keeping this database's issue for
+    /// signatures both know.
+    pub fn merge(&mut self, other: KnownBugs) {
+        for (id, bug) in other.bugs {
+            self.bugs.entry(id).or_insert(bug);
+        }
+    }
+
+    /// The known bug with `signature`, if it is one.
+    pub fn lookup(&self, signature: &Signature) -> Option<&KnownBug> {
+        self.bugs.get(&signature.id())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &KnownBug> {
+        self.bugs.values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.bugs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bugs.is_empty()
+    }
+}
+
+impl fmt::Display for KnownBugs {
+    /// One line per bug: its signature id, issue and signature.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (id, bug) in &self.bugs {
+            writeln!(f, "{id}  {}  {}", bug.issue, bug.signature)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signature(message: &str) -> Signature {
+        Signature {
+            kind: "bug".to_owned(),
+            file: None,
+            message: message.to_owned(),
+            queries: vec!["typeck".to_owned()],
+            flaky: false,
+        }
+    }
+
+    #[test]
+    fn merging_keeps_the_local_issue() {
+        let mut local = KnownBugs::new();
+        assert_eq!(local.add(signature("a"), "#1"), None);
+        assert_eq!(local.add(signature("a"), "#2"), Some("#1".to_owned()));
+        let mut shared = KnownBugs::new();
+        shared.add(signature("a"), "#3");
+        shared.add(signature("b"), "#4");
+        local.merge(shared);
+        assert_eq!(local.len(), 2);
+        assert_eq!(
+            local.lookup(&signature("a")).map(|bug| bug.issue.as_str()),
+            Some("#2")
+        );
+        assert_eq!(
+            local.lookup(&signature("b")).map(|bug| bug.issue.as_str()),
+            Some("#4")
+        );
+        assert!(local.lookup(&signature("c")).is_none());
+        let line = format!("{}  #2  {}\n", signature("a").id(), signature("a"));
+        assert!(local.to_string().contains(&line), "{local}");
+    }
+
+    #[test]
+    fn databases_round_trip_and_start_empty() {
+        let path =
+            std::env::temp_dir().join(format!("rustc-fuzz-known-{}.json", std::process::id()));
+        assert!(KnownBugs::load(&path).expect("no database yet").is_empty());
+        let mut known = KnownBugs::new();
+        known.add(signature("a"), "https://github.com/rust-lang/rust/issues/1");
+        known.save(&path).expect("saves");
+        assert_eq!(KnownBugs::load(&path).expect("loads"), known);
+        fs::remove_file(&path).expect("cleans up");
+    }
+}
@@ -0,0 +1,45 @@
+//! Making sense of findings.
+//!
+//! Harnesses tell a finding from a clean run; [`ice`] reads what an
+//! internal compiler error says about itself, for everything that sorts,
+//! deduplicates and reports findings afterwards. [`bucket`] files
+//! findings by the bug they hit, as [`filing`] finds it, [`reduce`] shrinks a crasher while it
+//! still hits the same one, [`bisect`] finds the nightly it regressed
+//! in, and [`report`] drafts the issue unless [`known`] says it was
+//! reported already, or [`duplicates`] finds issues that may have, and
+//! [`review`] records what a person decided to do about it; [`flaky`] tells crashes that reproduce every time
+//! from ones that do not, and [`bundle`] packs the same evidence into a directory
+//! that reproduces it without this crate. [`regressions`] keeps one reduced
+//! crasher per signature to recheck on new nightlies. [`hang`] sorts timeouts into hangs, compile-time
+//! blowups and a busy machine, and [`severity`] ranks findings by how
+//! much they matter.
+
+pub mod bisect;
+pub mod bucket;
+pub mod bundle;
+pub mod duplicates;
+pub mod filing;
+pub mod flaky;
+pub mod hang;
+pub mod ice;
+pub mod known;
+pub mod reduce;
+pub mod regressions;
+pub mod report;
+pub mod review;
+pub mod severity;
+
+pub use bisect::{Bisection, Bisector};
+pub use bucket::{Bucket, Buckets, Signature};
+pub use bundle::Bundle;
+pub use duplicates::{Candidate, IssueSearch};
+pub use filing::{Filer, Filing};
+pub use flaky::{Determinism, FlakeCause, FlakeCheck, FlakeReport};
+pub use hang::{HangClass, HangReport, HangTriage};
+pub use ice::{IceKind, IceReport};
+pub use known::{KnownBug, KnownBugs};
+pub use reduce::{Reducer, Reduction};
+pub use regressions::Recheck;
+pub use report::{Report, Reporter};
+pub use review::{Action, Decision, Verdict};
+pub use severity::Severity;
@@ -0,0 +1,635 @@
+//! Crash reduction.
+//!
+//! A finding is a mutant of a mutant of a seed, and hundreds of lines
+//! long; an issue wants the ten that matter. [`reduce`] shrinks a crasher
+//! by delta debugging on its syntax tree: besides the item, member and
+//! statement removal of [`corpus::reduce`](crate::corpus::reduce), it
+//! inlines inline modules into their parent, replaces expressions with one
+//! of their operands or with `()`, `0` or `loop {}`, and drops attributes,
+//! parameters, generic parameters, where-clauses, return types, fields,
+//! variants and match arms. Each candidate is kept only if it is shorter
+//! and still interesting, until no pass makes progress.
+//!
+//! [`Reducer`] supplies the test for crashers: the candidate must crash
+//! rustc with the same [`Signature`] as the original, so reduction never
+//! slides from one bug to another. With [`Reducer::progress`] set, every
+//! smaller crasher it finds is written out on the way, so a reduction cut
+//! short by a reboot can start again from there. [`reduce_finding`] does
+//! all of that for a finding on disk, writing the reduction beside it.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+use syn::punctuated::Punctuated;
+use syn::visit_mut::{self, VisitMut};
+
+use crate::corpus::meta::{self, Metadata, Provenance};
+use crate::corpus::{self, Entry};
+use crate::harness::Rustc;
+use crate::triage::Signature;
+
+/// Shrinks `source` while `interesting` holds, returning the smallest
+/// version found. `source` itself is assumed to be interesting.
+pub fn reduce<E>(
+    source: &str,
+    mut interesting: impl FnMut(&str) -> Result<bool, E>,
+) -> Result<String, E> {
+    let mut best = corpus::reduce::reduce(source, &mut interesting)?;
+    loop {
+        let Ok(mut file) = syn::parse_file(&best) else {
+            return Ok(best);
+        };
+        let before = best.len();
+        for pass in [Pass::Inline, Pass::Strip, Pass::Expr] {
+            if let Some(text) = run_pass(pass, &mut file, before, &mut interesting)? {
+                tracing::debug!(?pass, bytes = text.len(), "pass made progress");
+                best = text;
+            }
+        }
+        best = corpus::reduce::reduce(&best, &mut interesting)?;
+        tracing::debug!(before, after = best.len(), "reduction round");
+        if best.len() >= before {
+            return Ok(best);
+        }
+    }
+}
+
+/// Runs the rustc a crasher was found with, with the same arguments.
+#[derive(Debug, Clone)]
+pub struct Reducer {
+    pub driver: Rustc,
+    /// Arguments before the input, which is read from stdin.
+    pub args: Vec<String>,
+    /// Where the smallest crasher so far is kept while reducing.
+    pub progress: Option<PathBuf>,
+}
+
+/// The outcome of [`Reducer::reduce`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reduction {
+    pub source: String,
+    /// How many candidates were compiled.
+    pub tests: usize,
+}
+
+impl Reducer {
+    pub fn new(driver: Rustc) -> Self {
+        Reducer {
+            driver,
+            args: vec!["--crate-type=lib".to_owned()],
+            progress: None,
+        }
+    }
+
+    /// The signature of the crash `source` causes, if it causes one.
+    pub fn signature(&self, source: &str) -> io::Result<Option<Signature>> {
+        let mut args = self.args.clone();
+        args.push("-".to_owned());
+        let outcome = self.driver.run(&args, source.as_bytes())?;
+        Ok(Signature::of_outcome(&outcome))
+    }
+
+    /// Shrinks `source` while it crashes with `signature`.
+    pub fn reduce(&self, source: &str, signature: &Signature) -> io::Result<Reduction> {
+        // The passes see the same text again after one of them changes
+        // something elsewhere, and formatting repeats some.
+        let mut seen: HashMap<String, bool> = HashMap::new();
+        let mut tests = 0;
+        let mut smallest = source.len();
+        let source = reduce(source, |candidate| -> io::Result<bool> {
+            if let Some(&verdict) = seen.get(candidate) {
+                return Ok(verdict);
+            }
+            tests += 1;
+            let verdict = self
+                .signature(candidate)?
+                .is_some_and(|found| found.matches(signature));
+            seen.insert(candidate.to_owned(), verdict);
+            if let Some(path) = self.progress.as_ref().filter(|_| verdict) {
+                if candidate.len() < smallest {
+                    smallest = candidate.len();
+                    fs::write(path, candidate)?;
+                }
+            }
+            Ok(verdict)
+        })?;
+        Ok(Reduction { source, tests })
+    }
+}
+
+/// What [`reduce_finding`] made of a finding.
+#[derive(Debug, Clone)]
+pub enum Crasher {
+    /// It is a reduction, or was reduced already.
+    Reduced,
+    /// It does not crash this rustc.
+    NoCrash,
+    /// It has been reduced now.
+    Shrunk(Box<Shrunk>),
+}
+
+/// A finding [`reduce_finding`] reduced.
+#[derive(Debug, Clone)]
+pub struct Shrunk {
+    /// Where the reduction was written: `<finding>.reduced.rs`.
+    pub output: PathBuf,
+    /// The reduction's sidecar.
+    pub metadata: Metadata,
+    pub signature: Signature,
+    pub reduction: Reduction,
+    /// The partial reduction it started from, if an earlier run was cut
+    /// short.
+    pub resumed: Option<PathBuf>,
+}
+
+/// Reduces the crasher `entry` with `driver`, unless it is a reduction
+/// itself or has one, compiling it with the edition and flags its metadata
+/// records. The reduction is written beside it, as is a reduction in
+/// progress, which is picked up again if an earlier run was cut short and
+/// it still crashes the same way.
+pub fn reduce_finding(driver: &Rustc, entry: &Entry) -> io::Result<Crasher> {
+    let stem = entry.path.with_extension("");
+    if stem
+        .extension()
+        .is_some_and(|ext| ext == "reduced" || ext == "reducing")
+    {
+        return Ok(Crasher::Reduced);
+    }
+    let output = stem.with_extension("reduced.rs");
+    let progress = stem.with_extension("reducing.rs");
+    if output.exists() {
+        return Ok(Crasher::Reduced);
+    }
+    let metadata = Metadata::load(&entry.path)?.filter(|m| m.matches(&entry.source));
+    let (edition, flags) = metadata
+        .map(|metadata| (metadata.edition, metadata.flags))
+        .unwrap_or_default();
+    let mut reducer = Reducer::new(driver.clone());
+    if let Some(edition) = &edition {
+        reducer
+            .args
+            .extend(["--edition".to_owned(), edition.clone()]);
+    }
+    reducer.args.extend(flags.iter().cloned());
+    let Some(signature) = reducer.signature(&entry.source)? else {
+        return Ok(Crasher::NoCrash);
+    };
+    // A partial reduction only counts if it still crashes the same way
+    // on this rustc.
+    let mut start = entry.source.clone();
+    let mut resumed = None;
+    if let Ok(partial) = fs::read_to_string(&progress) {
+        if reducer
+            .signature(&partial)?
+            .is_some_and(|found| found.matches(&signature))
+        {
+            tracing::info!(progress = %progress.display(), "resuming");
+            start = partial;
+            resumed = Some(progress.clone());
+        }
+    }
+    reducer.progress = Some(progress.clone());
+    let reduction = reducer.reduce(&start, &signature)?;
+    fs::write(&output, &reduction.source)?;
+    match fs::remove_file(&progress) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    let provenance = Provenance::Reduced {
+        parent: entry.path.display().to_string(),
+        parent_hash: meta::content_hash(&entry.source),
+        signature: signature.id(),
+    };
+    let metadata = Metadata {
+        edition,
+        flags,
+        reduced: true,
+        ..Metadata::new(&reduction.source, provenance)
+    };
+    metadata.save(&output)?;
+    Ok(Crasher::Shrunk(Box::new(Shrunk {
+        output,
+        metadata,
+        signature,
+        reduction,
+        resumed,
+    })))
+}
+
+/// The kinds of edit a pass tries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pass {
+    /// Moves the items of an inline module into its parent.
+    Inline,
+    /// Removes an attribute, parameter, generic parameter, where
+    /// predicate, return type, field, variant or match arm.
+    Strip,
+    /// Replaces an expression with one of its operands or a placeholder.
+    Expr,
+}
+
+/// Tries every edit of `pass` on `file`, last first, keeping the ones
+/// that are shorter than what came before and interesting. Returns the
+/// text of the last one kept.
+fn run_pass<E>(
+    pass: Pass,
+    file: &mut syn::File,
+    mut len: usize,
+    interesting: &mut impl FnMut(&str) -> Result<bool, E>,
+) -> Result<Option<String>, E> {
+    let mut best = None;
+    let mut counter = Edit::at(pass, usize::MAX);
+    counter.visit_file_mut(&mut file.clone());
+    // An edit only renumbers the candidates after it, so one pass from
+    // the last to the first tries each once.
+    for target in (0..counter.seen).rev() {
+        let mut candidate = file.clone();
+        let mut edit = Edit::at(pass, target);
+        edit.visit_file_mut(&mut candidate);
+        if !edit.done {
+            continue;
+        }
+        let text = prettyplease::unparse(&candidate);
+        if text.len() < len && interesting(&text)? {
+            *file = candidate;
+            len = text.len();
+            best = Some(text);
+        }
+    }
+    Ok(best)
+}
+
+/// Applies the `target`th candidate edit of `pass`, counting candidates in
+/// pre-order.
+struct Edit {
+    pass: Pass,
+    target: usize,
+    seen: usize,
+    done: bool,
+}
+
+impl Edit {
+    fn at(pass: Pass, target: usize) -> Self {
+        Edit {
+            pass,
+            target,
+            seen: 0,
+            done: false,
+        }
+    }
+
+    /// Whether this is the `target`th of `n` candidates at one node, and
+    /// which; counts them if not.
+    fn choose(&mut self, n: usize) -> Option<usize> {
+        if self.done {
+            return None;
+        }
+        if (self.seen..self.seen + n).contains(&self.target) {
+            self.done = true;
+            return Some(self.target - self.seen);
+        }
+        self.seen += n;
+        None
+    }
+
+    /// Removes an element of `list` if it is the target, and visits the
+    /// rest.
+    fn list<T>(&mut self, list: &mut Vec<T>, mut visit: impl FnMut(&mut Self, &mut T)) {
+        let mut i = 0;
+        while !self.done && i < list.len() {
+            if self.choose(1).is_some() {
+                list.remove(i);
+                return;
+            }
+            visit(self, &mut list[i]);
+            i += 1;
+        }
+    }
+
+    fn punctuated<T, P: Default>(
+        &mut self,
+        list: &mut Punctuated<T, P>,
+        visit: impl FnMut(&mut Self, &mut T),
+    ) {
+        let mut items: Vec<T> = std::mem::take(list).into_iter().collect();
+        self.list(&mut items, visit);
+        *list = items.into_iter().collect();
+    }
+
+    /// Inlines the target among the modules in `items`, and visits the
+    /// rest.
+    fn inline(&mut self, items: &mut Vec<syn::Item>) {
+        let mut i = 0;
+        while !self.done && i < items.len() {
+            if let syn::Item::Mod(syn::ItemMod {
+                content: Some(_), ..
+            }) = &items[i]
+            {
+                if self.choose(1).is_some() {
+                    let syn::Item::Mod(module) = items.remove(i) else {
+                        unreachable!()
+                    };
+                    let (_, inner) = module.content.expect("checked above");
+                    items.splice(i..i, inner);
+                    return;
+                }
+            }
+            self.visit_item_mut(&mut items[i]);
+            i += 1;
+        }
+    }
+}
+
+impl VisitMut for Edit {
+    fn visit_file_mut(&mut self, file: &mut syn::File) {
+        match self.pass {
+            Pass::Inline => self.inline(&mut file.items),
+            Pass::Strip => {
+                self.list(&mut file.attrs, |_, _| {});
+                for item in &mut file.items {
+                    self.visit_item_mut(item);
+                }
+            }
+            Pass::Expr => visit_mut::visit_file_mut(self, file),
+        }
+    }
+
+    fn visit_item_mod_mut(&mut self, item: &mut syn::ItemMod) {
+        if self.pass == Pass::Inline {
+            if let Some((_, items)) = &mut item.content {
+                self.inline(items);
+            }
+        } else {
+            visit_mut::visit_item_mod_mut(self, item);
+        }
+    }
+
+    fn visit_item_mut(&mut self, item: &mut syn::Item) {
+        if self.pass == Pass::Strip {
+            if let Some(attrs) = item_attrs(item) {
+                self.list(attrs, |_, _| {});
+            }
+        }
+        if !self.done {
+            visit_mut::visit_item_mut(self, item);
+        }
+    }
+
+    fn visit_item_enum_mut(&mut self, item: &mut syn::ItemEnum) {
+        if self.pass == Pass::Strip {
+            self.visit_generics_mut(&mut item.generics);
+            self.punctuated(&mut item.variants, |v, variant| {
+                v.visit_variant_mut(variant)
+            });
+        } else {
+            visit_mut::visit_item_enum_mut(self, item);
+        }
+    }
+
+    fn visit_generics_mut(&mut self, generics: &mut syn::Generics) {
+        if self.pass == Pass::Strip {
+            self.punctuated(&mut generics.params, |v, param| {
+                v.visit_generic_param_mut(param)
+            });
+            if let Some(clause) = &mut generics.where_clause {
+                self.punctuated(&mut clause.predicates, |v, predicate| {
+                    v.visit_where_predicate_mut(predicate)
+                });
+            }
+        } else {
+            visit_mut::visit_generics_mut(self, generics);
+        }
+    }
+
+    fn visit_signature_mut(&mut self, signature: &mut syn::Signature) {
+        if self.pass == Pass::Strip {
+            self.visit_generics_mut(&mut signature.generics);
+            self.punctuated(&mut signature.inputs, |v, input| v.visit_fn_arg_mut(input));
+            if let syn::ReturnType::Type(..) = signature.output {
+                if self.choose(1).is_some() {
+                    signature.output = syn::ReturnType::Default;
+                    return;
+                }
+            }
+            self.visit_return_type_mut(&mut signature.output);
+        } else {
+            visit_mut::visit_signature_mut(self, signature);
+        }
+    }
+
+    fn visit_fields_named_mut(&mut self, fields: &mut syn::FieldsNamed) {
+        if self.pass == Pass::Strip {
+            self.punctuated(&mut fields.named, |v, field| v.visit_field_mut(field));
+        } else {
+            visit_mut::visit_fields_named_mut(self, fields);
+        }
+    }
+
+    fn visit_fields_unnamed_mut(&mut self, fields: &mut syn::FieldsUnnamed) {
+        if self.pass == Pass::Strip {
+            self.punctuated(&mut fields.unnamed, |v, field| v.visit_field_mut(field));
+        } else {
+            visit_mut::visit_fields_unnamed_mut(self, fields);
+        }
+    }
+
+    fn visit_expr_match_mut(&mut self, expr: &mut syn::ExprMatch) {
+        if self.pass == Pass::Strip {
+            self.visit_expr_mut(&mut expr.expr);
+            self.list(&mut expr.arms, |v, arm| v.visit_arm_mut(arm));
+        } else {
+            visit_mut::visit_expr_match_mut(self, expr);
+        }
+    }
+
+    fn visit_expr_mut(&mut self, expr: &mut syn::Expr) {
+        if self.done {
+            return;
+        }
+        if self.pass == Pass::Expr {
+            let mut replacements = children(expr);
+            if !is_trivial(expr) {
+                replacements.extend(placeholders());
+            }
+            if let Some(i) = self.choose(replacements.len()) {
+                *expr = replacements.swap_remove(i);
+                return;
+            }
+        }
+        visit_mut::visit_expr_mut(self, expr);
+    }
+}
+
+/// The attributes of `item`, for the kinds of item that have them.
+fn item_attrs(item: &mut syn::Item) -> Option<&mut Vec<syn::Attribute>> {
+    use syn::Item;
+    Some(match item {
+        Item::Const(item) => &mut item.attrs,
+        Item::Enum(item) => &mut item.attrs,
+        Item::ExternCrate(item) => &mut item.attrs,
+        Item::Fn(item) => &mut item.attrs,
+        Item::ForeignMod(item) => &mut item.attrs,
+        Item::Impl(item) => &mut item.attrs,
+        Item::Macro(item) => &mut item.attrs,
+        Item::Mod(item) => &mut item.attrs,
+        Item::Static(item) => &mut item.attrs,
+        Item::Struct(item) => &mut item.attrs,
+        Item::Trait(item) => &mut item.attrs,
+        Item::TraitAlias(item) => &mut item.attrs,
+        Item::Type(item) => &mut item.attrs,
+        Item::Union(item) => &mut item.attrs,
+        Item::Use(item) => &mut item.attrs,
+        _ => return None,
+    })
+}
+
+/// The operands `expr` could be replaced with.
+fn children(expr: &syn::Expr) -> Vec<syn::Expr> {
+    use syn::Expr;
+    let boxed = |expr: &Expr| vec![expr.clone()];
+    match expr {
+        Expr::Array(e) => e.elems.iter().cloned().collect(),
+        Expr::Tuple(e) => e.elems.iter().cloned().collect(),
+        Expr::Binary(e) => vec![(*e.left).clone(), (*e.right).clone()],
+        Expr::Assign(e) => boxed(&e.right),
+        Expr::Unary(e) => boxed(&e.expr),
+        Expr::Paren(e) => boxed(&e.expr),
+        Expr::Group(e) => boxed(&e.expr),
+        Expr::Cast(e) => boxed(&e.expr),
+        Expr::Reference(e) => boxed(&e.expr),
+        Expr::Field(e) => boxed(&e.base),
+        Expr::Try(e) => boxed(&e.expr),
+        Expr::Await(e) => boxed(&e.base),
+        Expr::Index(e) => vec![(*e.expr).clone(), (*e.index).clone()],
+        Expr::Call(e) => e.args.iter().cloned().collect(),
+        Expr::MethodCall(e) => std::iter::once((*e.receiver).clone())
+            .chain(e.args.iter().cloned())
+            .collect(),
+        Expr::If(e) => std::iter::once((*e.cond).clone())
+            .chain(tail(&e.then_branch))
+            .chain(e.else_branch.as_ref().map(|(_, expr)| (**expr).clone()))
+            .collect(),
+        Expr::Match(e) => std::iter::once((*e.expr).clone())
+            .chain(e.arms.iter().map(|arm| (*arm.body).clone()))
+            .collect(),
+        Expr::Block(e) => tail(&e.block).into_iter().collect(),
+        Expr::Unsafe(e) => tail(&e.block).into_iter().collect(),
+        Expr::Closure(e) => boxed(&e.body),
+        Expr::Return(e) => e.expr.iter().map(|expr| (**expr).clone()).collect(),
+        Expr::Break(e) => e.expr.iter().map(|expr| (**expr).clone()).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// The value a block ends with, if it ends with one.
+fn tail(block: &syn::Block) -> Option<syn::Expr> {
+    match block.stmts.last() {
+        Some(syn::Stmt::Expr(expr, None)) => Some(expr.clone()),
+        _ => None,
+    }
+}
+
+/// Expressions no placeholder is simpler than.
+fn is_trivial(expr: &syn::Expr) -> bool {
+    match expr {
+        syn::Expr::Lit(_) | syn::Expr::Path(_) => true,
+        syn::Expr::Tuple(tuple) => tuple.elems.is_empty(),
+        syn::Expr::Loop(body) => body.body.stmts.is_empty(),
+        _ => false,
+    }
+}
+
+/// What any expression may be replaced with: the unit value, an integer,
+/// and `loop {}`, which has every type.
+fn placeholders() -> [syn::Expr; 3] {
+    [
+        syn::parse_quote!(()),
+        syn::parse_quote!(0),
+        syn::parse_quote!(loop {}),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn findings_are_reduced_beside_themselves_once() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-reduce-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the findings");
+        // ICEs on any seed that mentions `boom`.
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            "#!/bin/sh\nif grep -q boom; then\n  \
+             echo 'error: internal compiler error: boom' >&2\n  exit 101\nfi\n",
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let driver = Rustc::new(&rustc);
+        let entry = |name: &str, source: &str| {
+            let path = dir.join(name);
+            fs::write(&path, source).expect("writes a finding");
+            Entry {
+                path,
+                source: source.to_owned(),
+            }
+        };
+
+        let crasher = entry(
+            "a.rs",
+            "fn boom() {}\n\nfn other(x: u32) -> u32 {\n    x + 1\n}\n",
+        );
+        let Crasher::Shrunk(shrunk) = reduce_finding(&driver, &crasher).expect("reduces") else {
+            panic!("a.rs crashes");
+        };
+        assert_eq!(shrunk.output, dir.join("a.reduced.rs"));
+        assert!(shrunk.reduction.source.contains("boom"));
+        assert!(!shrunk.reduction.source.contains("other"));
+        assert_eq!(
+            fs::read_to_string(&shrunk.output).expect("written"),
+            shrunk.reduction.source
+        );
+        let metadata = Metadata::load(&shrunk.output)
+            .expect("reads")
+            .expect("has a sidecar");
+        assert!(metadata.reduced);
+        assert!(matches!(metadata.provenance, Provenance::Reduced { .. }));
+        assert!(!dir.join("a.reducing.rs").exists());
+
+        assert!(matches!(
+            reduce_finding(&driver, &crasher).expect("skips"),
+            Crasher::Reduced
+        ));
+        let reduction = entry("a.reduced.rs", &shrunk.reduction.source);
+        assert!(matches!(
+            reduce_finding(&driver, &reduction).expect("skips"),
+            Crasher::Reduced
+        ));
+        let fixed = entry("b.rs", "fn fine() {}\n");
+        assert!(matches!(
+            reduce_finding(&driver, &fixed).expect("compiles"),
+            Crasher::NoCrash
+        ));
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+
+    #[test]
+    fn passes_inline_strip_and_simplify() {
+        let source = "mod m {\n    #[inline]\n    pub fn boom(x: u32, y: u32) -> u32 {\n        \
+                      x + y * 2\n    }\n}\n\nfn main() {\n    m::boom(1, 2);\n}\n";
+        // Still parses and still has a `boom`.
+        let interesting = |text: &str| -> Result<bool, ()> {
+            Ok(syn::parse_file(text).is_ok() && text.contains("fn boom"))
+        };
+        let reduced = reduce(source, interesting).expect("reduces");
+        assert!(reduced.len() < source.len());
+        for gone in ["mod m", "#[inline]", "u32", "y * 2"] {
+            assert!(!reduced.contains(gone), "{gone} in {reduced}");
+        }
+        assert!(syn::parse_file(&reduced).is_ok(), "{reduced}");
+    }
+}
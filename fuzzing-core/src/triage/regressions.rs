@@ -0,0 +1,207 @@
+//! The regression corpus.
+//!
+//! Every crasher `triage reduce` shrinks is also [`admit`]ted to a
+//! regression corpus: one seed per signature, named after its id, keeping
+//! the smallest reproducer, with the signature in its metadata. Compiling
+//! that corpus first on each new nightly with [`Recheck`] tells which
+//! known crashes were fixed, which still crash as they did, and which now
+//! crash differently.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::Serialize;
+
+use crate::corpus::meta::Metadata;
+use crate::harness::Rustc;
+use crate::triage::Signature;
+
+/// Adds `source`, a reproducer of `signature` with `metadata`, to the
+/// regression corpus at `dir` as `<signature id>.rs`, unless that
+/// signature already has a reproducer there no larger. Returns where it
+/// was written, if it was.
+pub fn admit(
+    dir: &Path,
+    source: &str,
+    metadata: &Metadata,
+    signature: &Signature,
+) -> io::Result<Option<PathBuf>> {
+    let path = dir.join(format!("{}.rs", signature.id()));
+    match fs::read_to_string(&path) {
+        Ok(existing) if existing.len() <= source.len() => return Ok(None),
+        Ok(_) => {}
+        Err(err) if err.kind() == io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+    fs::create_dir_all(dir)?;
+    fs::write(&path, source)?;
+    Metadata {
+        signature: Some(signature.clone()),
+        ..metadata.clone()
+    }
+    .save(&path)?;
+    Ok(Some(path))
+}
+
+/// What a regression seed does on the compiler under test.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Status {
+    /// It crashes with the signature it was admitted with.
+    Crashes,
+    /// It crashes with a different one.
+    Changed { signature: Signature },
+    /// It no longer crashes; [`ExecOutcome::kind`](crate::harness::ExecOutcome::kind)
+    /// of what it does instead.
+    Fixed { outcome: String },
+    /// It timed out, which says nothing either way.
+    Inconclusive,
+}
+
+impl fmt::Display for Status {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Status::Crashes => f.write_str("still crashes"),
+            Status::Changed { signature } => write!(f, "changed: now {signature}"),
+            Status::Fixed { outcome } => write!(f, "fixed: now {outcome}"),
+            Status::Inconclusive => f.write_str("timed out"),
+        }
+    }
+}
+
+/// Compiles regression seeds and compares their crashes with the ones they
+/// were admitted with.
+#[derive(Debug, Clone)]
+pub struct Recheck {
+    pub driver: Rustc,
+}
+
+impl Recheck {
+    pub fn new(driver: Rustc) -> Self {
+        Recheck { driver }
+    }
+
+    /// What `source` does now, with the edition and flags in `metadata`,
+    /// or `None` if `metadata` records no signature to compare with.
+    pub fn check(&self, source: &str, metadata: &Metadata) -> io::Result<Option<Status>> {
+        let Some(expected) = &metadata.signature else {
+            return Ok(None);
+        };
+        let mut args = vec!["--crate-type=lib".to_owned()];
+        if let Some(edition) = &metadata.edition {
+            args.extend(["--edition".to_owned(), edition.clone()]);
+        }
+        args.extend(metadata.flags.iter().cloned());
+        args.push("-".to_owned());
+        let outcome = self.driver.run(&args, source.as_bytes())?;
+        Ok(Some(match Signature::of_outcome(&outcome) {
+            Some(found) if found.matches(expected) => Status::Crashes,
+            Some(found) => Status::Changed { signature: found },
+            None if outcome.kind() == "timeout" => Status::Inconclusive,
+            None => Status::Fixed {
+                outcome: outcome.kind().to_owned(),
+            },
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::corpus::meta::Provenance;
+    use crate::triage::ice;
+    use std::os::unix::fs::PermissionsExt;
+    use std::time::Duration;
+
+    fn ice(message: &str) -> String {
+        format!(
+            "error: internal compiler error: compiler/rustc_middle/src/ty/mod.rs:1:2: {message}"
+        )
+    }
+
+    fn signature(message: &str) -> Signature {
+        Signature::of(&ice::parse(&ice(message)).expect("an ICE"))
+    }
+
+    #[test]
+    fn keeps_the_smallest_reproducer_per_signature() {
+        let dir = std::env::temp_dir().join(format!(
+            "rustc-fuzz-regressions-admit-{}",
+            std::process::id()
+        ));
+        let boom = signature("boom");
+        let metadata = Metadata::new("", Provenance::Unknown);
+        let path = admit(&dir, "fn boom() { 1 }", &metadata, &boom)
+            .expect("admits")
+            .expect("is new");
+        assert_eq!(path, dir.join(format!("{}.rs", boom.id())));
+        assert_eq!(
+            admit(&dir, "fn boom() { 12 }", &metadata, &boom).expect("admits"),
+            None
+        );
+        assert_eq!(
+            admit(&dir, "fn boom() {}", &metadata, &boom).expect("admits"),
+            Some(path.clone())
+        );
+        assert_eq!(fs::read_to_string(&path).expect("reads"), "fn boom() {}");
+        let saved = Metadata::load(&path)
+            .expect("reads")
+            .expect("has a sidecar");
+        assert_eq!(saved.signature, Some(boom));
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+
+    #[test]
+    fn rechecks_tell_fixed_from_changed() {
+        let dir = std::env::temp_dir().join(format!(
+            "rustc-fuzz-regressions-recheck-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            format!(
+                "#!/bin/sh\ninput=$(cat)\ncase \"$input\" in\n  \
+                 *boom*) echo '{}' >&2; exit 101;;\n  \
+                 *bang*) echo '{}' >&2; exit 101;;\n  \
+                 *spin*) sleep 5;;\nesac\n",
+                ice("boom"),
+                ice("bang")
+            ),
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let recheck = Recheck::new(Rustc {
+            timeout: Duration::from_millis(200),
+            ..Rustc::new(&rustc)
+        });
+        let metadata = Metadata {
+            signature: Some(signature("boom")),
+            ..Metadata::new("", Provenance::Unknown)
+        };
+        let status = |source: &str| {
+            recheck
+                .check(source, &metadata)
+                .expect("runs")
+                .expect("has a signature")
+        };
+        assert_eq!(status("fn boom() {}"), Status::Crashes);
+        assert_eq!(
+            status("fn bang() {}"),
+            Status::Changed {
+                signature: signature("bang")
+            }
+        );
+        assert_eq!(status("fn fine() {}").to_string(), "fixed: now success");
+        assert_eq!(status("fn spin() {}"), Status::Inconclusive);
+        let unsigned = Metadata::new("", Provenance::Unknown);
+        assert_eq!(
+            recheck.check("fn boom() {}", &unsigned).expect("runs"),
+            None
+        );
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
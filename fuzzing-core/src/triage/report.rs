@@ -0,0 +1,299 @@
+//! Issue reports.
+//!
+//! rust-lang/rust asks ICE reports for the code, the exact command,
+//! `rustc --version --verbose`, the error output and a full backtrace.
+//! [`Reporter`] runs a bucket's best reproducer again to collect them, and
+//! a [`Report`] formats them as Markdown in the layout of the ICE issue
+//! template, with the regression range when the bucket was bisected.
+
+use std::fmt;
+use std::io;
+
+use crate::harness::{ExecOutcome, Rustc};
+use crate::triage::{ice, Bucket, Signature};
+
+/// The name the reproducer is given in reports. rustc reads it from
+/// stdin, where it is called `<anon>`.
+pub const FILE: &str = "lib.rs";
+
+/// Characters of error output kept in a report.
+const ERROR_LIMIT: usize = 16 << 10;
+
+/// Characters of backtrace kept in a report.
+const BACKTRACE_LIMIT: usize = 64 << 10;
+
+/// Everything an issue about one bucket needs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Report {
+    pub bucket: Bucket,
+    pub source: String,
+    /// rustc's arguments before the file name.
+    pub args: Vec<String>,
+    /// Whether the arguments need `RUSTC_BOOTSTRAP=1` on the compiler
+    /// reported with.
+    pub bootstrap: bool,
+    /// `rustc --version --verbose`.
+    pub version: String,
+    /// stderr without a backtrace.
+    pub error: String,
+    /// stderr under `RUST_BACKTRACE=full`.
+    pub backtrace: String,
+    pub title: String,
+}
+
+/// Runs reproducers again to report on them.
+#[derive(Debug, Clone)]
+pub struct Reporter {
+    pub driver: Rustc,
+    /// Arguments before the input, which is read from stdin; the phase's
+    /// are added in front.
+    pub args: Vec<String>,
+}
+
+impl Reporter {
+    pub fn new(driver: Rustc) -> Self {
+        Reporter {
+            driver,
+            args: vec!["--crate-type=lib".to_owned()],
+        }
+    }
+
+    /// The report on `bucket`, whose best reproducer is `source`, or
+    /// `None` if it no longer crashes with the bucket's signature.
+    pub fn report(&self, bucket: &Bucket, source: &str) -> io::Result<Option<Report>> {
+        let outcome = self.run(source, "0")?;
+        if !Signature::of_outcome(&outcome).is_some_and(|found| found.matches(&bucket.signature)) {
+            return Ok(None);
+        }
+        let backtrace = self.run(source, "full")?;
+        let version = match self.driver.run_program(&self.driver.path, ["-vV"], &[])? {
+            ExecOutcome::Success(output) => output.stdout.trim().to_owned(),
+            outcome => {
+                return Err(io::Error::other(format!(
+                    "{} -vV: {outcome}",
+                    self.driver.path.display()
+                )))
+            }
+        };
+        let mut args: Vec<String> = self
+            .driver
+            .phase
+            .args()
+            .iter()
+            .map(|arg| arg.to_string())
+            .collect();
+        args.extend(self.args.iter().cloned());
+        let bootstrap =
+            args.iter().any(|arg| arg.starts_with("-Z")) && !version.contains("-nightly");
+        Ok(Some(Report {
+            title: title(&outcome),
+            bucket: bucket.clone(),
+            source: source.to_owned(),
+            args,
+            bootstrap,
+            version,
+            error: clip(&outcome.output().stderr, ERROR_LIMIT),
+            backtrace: clip(&backtrace.output().stderr, BACKTRACE_LIMIT),
+        }))
+    }
+
+    fn run(&self, source: &str, backtrace: &str) -> io::Result<ExecOutcome> {
+        let mut driver = self.driver.clone();
+        driver.env.push(("RUST_BACKTRACE".into(), backtrace.into()));
+        let mut args = self.args.clone();
+        args.push("-".to_owned());
+        driver.run(&args, source.as_bytes())
+    }
+}
+
+/// `ICE: message` with the innermost query, or what signal rustc died
+/// of.
+fn title(outcome: &ExecOutcome) -> String {
+    let stderr = &outcome.output().stderr;
+    let report = ice::parse(stderr);
+    let message = report
+        .as_ref()
+        .and_then(|report| report.message.lines().next())
+        .unwrap_or_default();
+    let mut title = match outcome {
+        ExecOutcome::Crash { .. } if stderr.contains("has overflowed its stack") => {
+            "rustc overflows its stack".to_owned()
+        }
+        ExecOutcome::Crash { signal, .. } => format!("rustc crashes with signal {signal}"),
+        _ => format!("ICE: {}", message.replace("<anon>", FILE)),
+    };
+    if let Some(query) = report.as_ref().and_then(|report| report.query()) {
+        title.push_str(&format!(" in `{query}`"));
+    }
+    title
+}
+
+/// `text` with the input's name replaced by [`FILE`], cut to `limit`
+/// characters at a line boundary.
+fn clip(text: &str, limit: usize) -> String {
+    let text = text.replace("<anon>", FILE);
+    if text.len() <= limit {
+        return text.trim_end().to_owned();
+    }
+    let mut end = limit;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    let end = text[..end].rfind('\n').unwrap_or(end);
+    format!("{}\n[... {} bytes cut]", &text[..end], text.len() - end)
+}
+
+impl fmt::Display for Report {
+    /// The issue body as Markdown, headed by the title.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "# {}", self.title)?;
+        writeln!(f)?;
+        writeln!(
+            f,
+            "<!-- rustc-fuzz bucket {}: {} -->",
+            self.bucket.id, self.bucket.signature
+        )?;
+        writeln!(f)?;
+        writeln!(f, "### Code")?;
+        writeln!(f)?;
+        writeln!(f, "```rust")?;
+        writeln!(f, "{}", self.source.trim_end())?;
+        writeln!(f, "```")?;
+        writeln!(f)?;
+        writeln!(f, "Compiled with:")?;
+        writeln!(f)?;
+        writeln!(f, "```sh")?;
+        if self.bootstrap {
+            write!(f, "RUSTC_BOOTSTRAP=1 ")?;
+        }
+        writeln!(f, "rustc {} {FILE}", self.args.join(" "))?;
+        writeln!(f, "```")?;
+        writeln!(f)?;
+        writeln!(f, "### Meta")?;
+        writeln!(f)?;
+        writeln!(f, "`rustc --version --verbose`:")?;
+        writeln!(f, "```")?;
+        writeln!(f, "{}", self.version)?;
+        writeln!(f, "```")?;
+        if let Some(bisection) = &self.bucket.bisection {
+            writeln!(f)?;
+            writeln!(f, "### Regression")?;
+            writeln!(f)?;
+            write!(
+                f,
+                "Regressed in `{}`; `{}` is the last good nightly.",
+                bisection.first_bad, bisection.last_good
+            )?;
+            if let Some(commits) = &bisection.commits {
+                write!(
+                    f,
+                    " It regressed between merges {} and {}",
+                    commits.last_good, commits.first_bad
+                )?;
+                match commits.pr {
+                    Some(pr) => write!(f, ", which merged #{pr}.")?,
+                    None => write!(f, ".")?,
+                }
+            }
+            writeln!(f)?;
+        }
+        writeln!(f)?;
+        writeln!(f, "### Error output")?;
+        writeln!(f)?;
+        writeln!(f, "```")?;
+        writeln!(f, "{}", self.error)?;
+        writeln!(f, "```")?;
+        writeln!(f)?;
+        writeln!(f, "<details><summary><strong>Backtrace</strong></summary>")?;
+        writeln!(f, "<p>")?;
+        writeln!(f)?;
+        writeln!(f, "```")?;
+        writeln!(f, "{}", self.backtrace)?;
+        writeln!(f, "```")?;
+        writeln!(f)?;
+        writeln!(f, "</p>")?;
+        writeln!(f, "</details>")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::triage::Buckets;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// ICEs in `typeck` on input that mentions `boom`, with a backtrace
+    /// under `RUST_BACKTRACE=full`.
+    const RUSTC: &str = r#"#!/bin/sh
+if [ "$1" = -vV ]; then printf 'rustc 1.80.0 (0123456789 2024-05-01)\nhost: x86_64-unknown-linux-gnu\n'; exit 0; fi
+grep -q boom || exit 0
+cat >&2 <<'EOF'
+error: internal compiler error: compiler/rustc_middle/src/ty/mod.rs:1234:5: unexpected region in <anon>
+query stack during panic:
+#0 [typeck] type-checking `boom`
+end of query stack
+EOF
+[ "$RUST_BACKTRACE" = full ] && echo '   0: std::panicking::begin_panic' >&2
+exit 101
+"#;
+
+    #[test]
+    fn reports_follow_the_ice_template() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-report-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        let rustc = dir.join("rustc");
+        fs::write(&rustc, RUSTC).expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let driver = Rustc::new(&rustc);
+        let crash = driver.run(["-"], b"fn boom() {}").expect("runs");
+        let signature = Signature::of_outcome(&crash).expect("an ICE");
+        let mut buckets = Buckets::new();
+        buckets.add(signature.clone(), "a.rs", 12);
+        let bucket = buckets.get(&signature.id()).expect("the bucket");
+
+        let reporter = Reporter {
+            args: vec![
+                "--crate-type=lib".to_owned(),
+                "-Zmir-opt-level=4".to_owned(),
+            ],
+            ..Reporter::new(driver)
+        };
+        let report = reporter
+            .report(bucket, "fn boom() {}")
+            .expect("runs")
+            .expect("still crashes");
+        assert_eq!(report.title, "ICE: unexpected region in lib.rs in `typeck`");
+        assert!(report.bootstrap, "-Z on a stable-looking rustc");
+        assert!(
+            report.version.starts_with("rustc 1.80.0"),
+            "{}",
+            report.version
+        );
+        assert!(!report.error.contains("begin_panic"), "{}", report.error);
+        assert!(
+            report.backtrace.contains("begin_panic"),
+            "{}",
+            report.backtrace
+        );
+        let body = report.to_string();
+        assert!(body.starts_with("# ICE: unexpected region"), "{body}");
+        assert!(
+            body.contains("RUSTC_BOOTSTRAP=1 rustc --crate-type=lib -Zmir-opt-level=4 lib.rs\n"),
+            "{body}"
+        );
+        assert!(!body.contains("### Regression"), "{body}");
+
+        assert_eq!(
+            reporter.report(bucket, "fn fixed() {}").expect("runs"),
+            None
+        );
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+
+    #[test]
+    fn long_output_is_cut_at_a_line() {
+        assert_eq!(clip("at <anon>:1\n", 100), "at lib.rs:1");
+        assert_eq!(clip("a\nb\nccc", 4), "a\nb\n[... 4 bytes cut]");
+    }
+}
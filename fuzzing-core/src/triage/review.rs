@@ -0,0 +1,223 @@
+//! Human review of buckets.
+//!
+//! Everything else in triage is automatic; deciding what to do about a
+//! bucket is not. `triage review` steps through the buckets nobody has
+//! decided on yet, shows each one's reproducer and backtrace, and reads an
+//! [`Action`] per bucket from the terminal. The [`Decision`] is kept with
+//! the bucket in `buckets.json`, so a review can stop and pick up where it
+//! left off, and later commands can act on it.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::triage::Candidate;
+
+/// What to do about a bucket.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "verdict", rename_all = "snake_case")]
+pub enum Verdict {
+    /// It is a new bug worth an issue.
+    Report,
+    /// It was reported already, at `issue`.
+    Duplicate { issue: String },
+    /// It is not worth reporting: expected behavior, a harness problem,
+    /// or a bug nobody will fix.
+    Wontfix,
+}
+
+impl Verdict {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Verdict::Report => "report",
+            Verdict::Duplicate { .. } => "duplicate",
+            Verdict::Wontfix => "wontfix",
+        }
+    }
+}
+
+impl fmt::Display for Verdict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Verdict::Duplicate { issue } => write!(f, "duplicate of {issue}"),
+            verdict => f.write_str(verdict.name()),
+        }
+    }
+}
+
+/// A reviewer's verdict on a bucket.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Decision {
+    #[serde(flatten)]
+    pub verdict: Verdict,
+    /// Why, if the reviewer said.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+impl fmt::Display for Decision {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.verdict)?;
+        if let Some(note) = &self.note {
+            write!(f, ": {note}")?;
+        }
+        Ok(())
+    }
+}
+
+/// One line of reviewer input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Action {
+    Decide(Decision),
+    /// Leave the bucket for later.
+    Skip,
+    /// Stop reviewing.
+    Quit,
+    /// Show [`HELP`].
+    Help,
+}
+
+/// What [`Action::parse`] understands.
+pub const HELP: &str = "\
+r [note]          report it
+d ISSUE [note]    duplicate of ISSUE: a URL, a rust-lang/rust issue number,
+                  or #N for the Nth candidate duplicate listed
+w [note]          won't fix
+s                 skip for now
+q                 quit";
+
+impl Action {
+    /// Parses `line`; `candidates` are the bucket's candidate duplicates,
+    /// which `d #N` refers to.
+    pub fn parse(line: &str, candidates: &[Candidate]) -> Result<Self, String> {
+        let line = line.trim();
+        let (command, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let note = |text: &str| Some(text.trim().to_owned()).filter(|note| !note.is_empty());
+        let verdict = match command {
+            "r" | "report" => Verdict::Report,
+            "w" | "wontfix" => Verdict::Wontfix,
+            "d" | "duplicate" => {
+                let rest = rest.trim_start();
+                let (issue, rest) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+                let issue = resolve(issue, candidates)?;
+                return Ok(Action::Decide(Decision {
+                    verdict: Verdict::Duplicate { issue },
+                    note: note(rest),
+                }));
+            }
+            "s" | "skip" | "" => return Ok(Action::Skip),
+            "q" | "quit" => return Ok(Action::Quit),
+            "?" | "h" | "help" => return Ok(Action::Help),
+            other => return Err(format!("unknown command `{other}`; `?` for help")),
+        };
+        Ok(Action::Decide(Decision {
+            verdict,
+            note: note(rest),
+        }))
+    }
+}
+
+/// The issue URL `issue` names.
+fn resolve(issue: &str, candidates: &[Candidate]) -> Result<String, String> {
+    if let Some(index) = issue.strip_prefix('#') {
+        let candidate = index
+            .parse::<usize>()
+            .ok()
+            .and_then(|index| candidates.get(index.checked_sub(1)?))
+            .ok_or_else(|| format!("no candidate duplicate {issue}"))?;
+        return Ok(candidate.url.clone());
+    }
+    if issue.parse::<u64>().is_ok() {
+        return Ok(format!("https://github.com/rust-lang/rust/issues/{issue}"));
+    }
+    if issue.contains("://") {
+        return Ok(issue.to_owned());
+    }
+    Err(if issue.is_empty() {
+        "which issue is it a duplicate of?".to_owned()
+    } else {
+        format!("`{issue}` is not an issue URL or number")
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decide(verdict: Verdict, note: Option<&str>) -> Action {
+        Action::Decide(Decision {
+            verdict,
+            note: note.map(str::to_owned),
+        })
+    }
+
+    #[test]
+    fn parses_reviewer_input() {
+        let candidates = [Candidate {
+            number: 7,
+            title: "ICE: bad type".to_owned(),
+            url: "https://github.com/rust-lang/rust/issues/7".to_owned(),
+            state: "open".to_owned(),
+        }];
+        let parse = |line| Action::parse(line, &candidates);
+        assert_eq!(parse("r"), Ok(decide(Verdict::Report, None)));
+        assert_eq!(
+            parse("  wontfix  needs -Zunstable  "),
+            Ok(decide(Verdict::Wontfix, Some("needs -Zunstable")))
+        );
+        let duplicate = |issue: &str| Verdict::Duplicate {
+            issue: issue.to_owned(),
+        };
+        assert_eq!(
+            parse("d #1 same query"),
+            Ok(decide(duplicate(&candidates[0].url), Some("same query")))
+        );
+        assert_eq!(
+            parse("d 123"),
+            Ok(decide(
+                duplicate("https://github.com/rust-lang/rust/issues/123"),
+                None
+            ))
+        );
+        assert_eq!(
+            parse("duplicate https://example.com/1"),
+            Ok(decide(duplicate("https://example.com/1"), None))
+        );
+        assert_eq!(
+            (parse(""), parse("q")),
+            (Ok(Action::Skip), Ok(Action::Quit))
+        );
+        assert_eq!(parse("?"), Ok(Action::Help));
+        assert_eq!(parse("d #2"), Err("no candidate duplicate #2".to_owned()));
+        assert_eq!(parse("d #0"), Err("no candidate duplicate #0".to_owned()));
+        assert_eq!(
+            parse("d"),
+            Err("which issue is it a duplicate of?".to_owned())
+        );
+        assert!(parse("x").is_err());
+    }
+
+    #[test]
+    fn decisions_keep_the_verdict_flat() {
+        let decision = Decision {
+            verdict: Verdict::Duplicate {
+                issue: "https://example.com/1".to_owned(),
+            },
+            note: Some("same query".to_owned()),
+        };
+        assert_eq!(
+            decision.to_string(),
+            "duplicate of https://example.com/1: same query"
+        );
+        let json = serde_json::to_string(&decision).expect("serializes");
+        assert_eq!(
+            json,
+            r#"{"verdict":"duplicate","issue":"https://example.com/1","note":"same query"}"#
+        );
+        let back: Decision = serde_json::from_str(&json).expect("deserializes");
+        assert_eq!(back, decision);
+        let report: Decision =
+            serde_json::from_str(r#"{"verdict":"report"}"#).expect("deserializes");
+        assert_eq!(report.to_string(), "report");
+    }
+}
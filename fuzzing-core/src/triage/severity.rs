@@ -0,0 +1,163 @@
+//! Finding severity.
+//!
+//! Not every finding deserves the same attention. A program that compiles
+//! to the wrong behavior can hide in production code; undefined behavior
+//! in safe code breaks the language's promise; an ICE at least fails
+//! loudly; a hang or memory blowup costs build time; a diagnostic that
+//! changed or a suggestion that breaks code costs a user some confusion.
+//! [`Severity`] orders them so, and every harness outcome that is a
+//! finding has one, so listings and notifications can put the worst
+//! first.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::harness::ExecOutcome;
+
+/// How bad a finding is. Variants are declared worst first, so sorting
+/// puts the worst first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    /// Compiled programs behave differently across backends, optimization
+    /// levels, targets or builds, or a tool changed a program's meaning.
+    WrongCode,
+    /// Miri found undefined behavior in safe code, or rustc accepted a
+    /// program that must not compile.
+    Unsound,
+    /// A compiler or tool crashed, or failed on an input it should have
+    /// handled.
+    Ice,
+    /// A compiler or tool hung, deadlocked or ran out of memory.
+    Hang,
+    /// Diagnostics, suggestions or printed output that changed or are
+    /// wrong, on a program whose meaning did not.
+    Diagnostic,
+}
+
+impl Severity {
+    pub const ALL: [Severity; 5] = [
+        Severity::WrongCode,
+        Severity::Unsound,
+        Severity::Ice,
+        Severity::Hang,
+        Severity::Diagnostic,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::WrongCode => "wrong-code",
+            Severity::Unsound => "unsound",
+            Severity::Ice => "ice",
+            Severity::Hang => "hang",
+            Severity::Diagnostic => "diagnostic",
+        }
+    }
+
+    /// The severity of a plain rustc outcome: `None` unless it crashed or
+    /// ran out of time or memory.
+    pub fn of(outcome: &ExecOutcome) -> Option<Severity> {
+        match outcome {
+            ExecOutcome::Ice(_) | ExecOutcome::Crash { .. } => Some(Severity::Ice),
+            ExecOutcome::Timeout(_) | ExecOutcome::OutOfMemory(_) => Some(Severity::Hang),
+            ExecOutcome::Success(_) | ExecOutcome::Error { .. } => None,
+        }
+    }
+
+    /// The severity of a compiler or tool failing on an input it should
+    /// have handled: a hang if it ran out of time or memory, an ICE
+    /// however else it failed.
+    pub fn of_failure(outcome: &ExecOutcome) -> Severity {
+        Severity::of(outcome).unwrap_or(Severity::Ice)
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownSeverity(pub String);
+
+impl fmt::Display for UnknownSeverity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown severity {:?}; expected wrong-code, unsound, ice, hang or diagnostic",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownSeverity {}
+
+impl FromStr for Severity {
+    type Err = UnknownSeverity;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Severity::ALL
+            .into_iter()
+            .find(|severity| severity.name() == s)
+            .ok_or_else(|| UnknownSeverity(s.to_owned()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::harness::Captured;
+
+    #[test]
+    fn only_crashes_and_exhaustion_are_findings() {
+        let output = Captured::default;
+        assert_eq!(
+            Severity::of(&ExecOutcome::Ice(output())),
+            Some(Severity::Ice)
+        );
+        assert_eq!(
+            Severity::of(&ExecOutcome::Crash {
+                signal: 11,
+                output: output()
+            }),
+            Some(Severity::Ice)
+        );
+        assert_eq!(
+            Severity::of(&ExecOutcome::Timeout(output())),
+            Some(Severity::Hang)
+        );
+        assert_eq!(
+            Severity::of(&ExecOutcome::OutOfMemory(output())),
+            Some(Severity::Hang)
+        );
+        assert_eq!(Severity::of(&ExecOutcome::Success(output())), None);
+        let rejected = ExecOutcome::Error {
+            code: 1,
+            output: output(),
+        };
+        assert_eq!(Severity::of(&rejected), None);
+        assert_eq!(Severity::of_failure(&rejected), Severity::Ice);
+    }
+
+    #[test]
+    fn severities_sort_worst_first_and_parse_by_name() {
+        let mut sorted = Severity::ALL;
+        sorted.sort();
+        assert_eq!(sorted, Severity::ALL);
+        assert!(Severity::WrongCode < Severity::Diagnostic);
+        for severity in Severity::ALL {
+            assert_eq!(severity.to_string().parse(), Ok(severity));
+        }
+        assert_eq!(
+            "bad".parse::<Severity>(),
+            Err(UnknownSeverity("bad".to_owned()))
+        );
+        assert_eq!(
+            serde_json::to_string(&Severity::WrongCode).expect("serializes"),
+            "\"wrong-code\""
+        );
+    }
+}
@@ -0,0 +1,17 @@
+//! AFL++ harness for [`rustc_fuzz::target::parse`].
+//!
+//! Runs in persistent mode with shared-memory test-case delivery, both
+//! provided by the `afl` crate. Build and run with cargo-afl:
+//!
+//! ```text
+//! cargo afl build --release --features afl --bin rustc-fuzz-afl
+//! cargo afl fuzz -i corpus -o findings target/release/rustc-fuzz-afl
+//! ```
+
+use rustc_fuzz::target;
+
+fn main() {
+    afl::fuzz!(|data: &[u8]| {
+        target::parse(data);
+    });
+}
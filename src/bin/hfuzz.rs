@@ -0,0 +1,18 @@
+//! honggfuzz harness for [`rustc_fuzz::target::parse`].
+//!
+//! Build and run with honggfuzz-rs:
+//!
+//! ```text
+//! cargo install honggfuzz
+//! HFUZZ_BUILD_ARGS="--features honggfuzz" HFUZZ_INPUT=corpus cargo hfuzz run rustc-fuzz-hfuzz
+//! ```
+
+use rustc_fuzz::target;
+
+fn main() {
+    loop {
+        honggfuzz::fuzz!(|data: &[u8]| {
+            target::parse(data);
+        });
+    }
+}
@@ -0,0 +1,133 @@
+//! Coverage-guided rustc fuzzer built on LibAFL.
+//!
+//! Inputs are mutated by the structured engine, run through the resource
+//! limited rustc driver, and kept when they reach new edges in an
+//! instrumented rustc. Inputs that make rustc crash or ICE are written to
+//! the solutions directory.
+//!
+//! rustc must be built with AFL-style edge instrumentation that reports to
+//! the shared memory in `__AFL_SHM_ID` (sanitizer coverage plus AFL++'s
+//! compiler runtime, as cargo-afl does for ordinary crates). A stock rustc
+//! works too, but then only crashes are found and the corpus never grows.
+
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use libafl::corpus::{InMemoryCorpus, OnDiskCorpus};
+use libafl::events::SimpleEventManager;
+use libafl::feedback_or;
+use libafl::feedbacks::{MaxMapFeedback, TimeFeedback};
+use libafl::fuzzer::{Fuzzer, StdFuzzer};
+use libafl::inputs::BytesInput;
+use libafl::monitors::SimpleMonitor;
+use libafl::observers::{CanTrack, HitcountsMapObserver, StdMapObserver, TimeObserver};
+use libafl::schedulers::{IndexesLenTimeMinimizerScheduler, QueueScheduler};
+use libafl::stages::StdMutationalStage;
+use libafl::state::StdState;
+use libafl_bolts::rands::StdRand;
+use libafl_bolts::shmem::{ShMem, ShMemProvider, UnixShMemProvider};
+use libafl_bolts::tuples::{tuple_list, Handled};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use rustc_fuzz::harness::{Phase, Rustc};
+use rustc_fuzz::libafl::{
+    CrashOracle, EngineMutator, OutcomeObserver, ProgramGenerator, RustcExecutor,
+};
+use rustc_fuzz::mutate::Engine;
+
+#[derive(Parser)]
+#[command(name = "libafl_rustc", about = "Coverage-guided rustc fuzzer")]
+struct Args {
+    /// The (instrumented) rustc to fuzz.
+    #[arg(long, default_value = "rustc")]
+    rustc: PathBuf,
+    /// Seed corpus directories; without any, seeds are generated.
+    #[arg(long)]
+    corpus: Vec<PathBuf>,
+    /// Where crashing inputs are written.
+    #[arg(long, default_value = "solutions")]
+    solutions: PathBuf,
+    /// Phase after which rustc stops: parse, metadata, analysis or codegen.
+    #[arg(long, default_value_t = Phase::Codegen)]
+    phase: Phase,
+    /// Per-execution timeout in milliseconds.
+    #[arg(long, default_value_t = 10_000)]
+    timeout_ms: u64,
+    /// Size of the edge coverage map.
+    #[arg(long, default_value_t = 1 << 16)]
+    map_size: usize,
+    /// Seeds to generate when no corpus is given.
+    #[arg(long, default_value_t = 32)]
+    generate: usize,
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Arguments passed to rustc; the input arrives on stdin.
+    #[arg(last = true, default_values = ["--crate-type=lib", "--edition=2021", "-"])]
+    rustc_args: Vec<String>,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+
+    let mut shmem_provider = UnixShMemProvider::new()?;
+    let mut shmem = shmem_provider.new_shmem(args.map_size)?;
+    // SAFETY: nothing else touches the environment yet.
+    unsafe { shmem.write_to_env("__AFL_SHM_ID")? };
+    std::env::set_var("AFL_MAP_SIZE", args.map_size.to_string());
+    // SAFETY: the map lives in `shmem`, which outlives the fuzzer.
+    let edges = unsafe { StdMapObserver::new("edges", &mut shmem[..]) };
+    let edges = HitcountsMapObserver::new(edges).track_indices();
+    let time = TimeObserver::new("time");
+    let outcome = OutcomeObserver::new("outcome");
+
+    let mut feedback = feedback_or!(MaxMapFeedback::new(&edges), TimeFeedback::new(&time));
+    let mut objective = CrashOracle::new(&outcome);
+    let rand = match args.seed {
+        Some(seed) => StdRand::with_seed(seed),
+        None => StdRand::new(),
+    };
+    let mut state = StdState::new(
+        rand,
+        InMemoryCorpus::<BytesInput>::new(),
+        OnDiskCorpus::new(&args.solutions)?,
+        &mut feedback,
+        &mut objective,
+    )?;
+
+    let monitor = SimpleMonitor::new(|s| println!("{s}"));
+    let mut mgr = SimpleEventManager::new(monitor);
+    let scheduler = IndexesLenTimeMinimizerScheduler::new(&edges, QueueScheduler::new());
+    let mut fuzzer = StdFuzzer::new(scheduler, feedback, objective);
+
+    let mut rustc = Rustc::new(&args.rustc);
+    rustc.phase = args.phase;
+    rustc.timeout = Duration::from_millis(args.timeout_ms);
+    let handle = outcome.handle();
+    let mut executor = RustcExecutor::new(
+        rustc,
+        args.rustc_args.clone(),
+        tuple_list!(edges, time, outcome),
+        handle,
+    );
+
+    if args.corpus.is_empty() {
+        let mut generator = ProgramGenerator::default();
+        state.generate_initial_inputs_forced(
+            &mut fuzzer,
+            &mut executor,
+            &mut generator,
+            &mut mgr,
+            args.generate,
+        )?;
+    } else {
+        state.load_initial_inputs_forced(&mut fuzzer, &mut executor, &mut mgr, &args.corpus)?;
+    }
+
+    let mut stages = tuple_list!(StdMutationalStage::new(EngineMutator::new(
+        Engine::default()
+    )));
+    fuzzer
+        .fuzz_loop(&mut stages, &mut executor, &mut state, &mut mgr)
+        .map_err(|e| anyhow!("fuzzing stopped: {e}"))?;
+    Ok(())
+}
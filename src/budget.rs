@@ -0,0 +1,294 @@
+//! Campaign budgets.
+//!
+//! Fuzzing in CI needs a ceiling on what a campaign costs. A [`Budget`]
+//! caps the CPU time of the fuzzer and the compilers it runs, the
+//! wall-clock time, and the disk its artifacts take. A [`Meter`] tells a
+//! campaign when one of them is spent, so it can stop starting work, let
+//! what is running finish, and save its state and report as it would at
+//! the end.
+//!
+//! CPU time is the fuzzer's own plus that of every child it has waited
+//! for, so compilers that run in a container, outside its process tree,
+//! are not counted.
+
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+/// How often a [`Meter`] measures disk use; walking a findings directory
+/// on every check would cost more than the checks.
+const DISK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// The most a campaign may use; `None` for no limit.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Budget {
+    /// CPU minutes, of the fuzzer and the compilers it runs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cpu_minutes: Option<u64>,
+    /// Wall-clock minutes.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub wall_minutes: Option<u64>,
+    /// MiB of artifacts: everything in the findings directory.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disk_mb: Option<u64>,
+}
+
+/// Time spent by earlier runs of a campaign, which a checkpoint carries so
+/// a resumed campaign stays within the budget of the whole.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Spent {
+    pub cpu_ms: u64,
+    pub wall_ms: u64,
+}
+
+/// A budgeted resource.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Cpu,
+    Wall,
+    Disk,
+}
+
+impl Resource {
+    pub fn name(self) -> &'static str {
+        match self {
+            Resource::Cpu => "cpu",
+            Resource::Wall => "wall-clock",
+            Resource::Disk => "disk",
+        }
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A resource whose budget is spent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Exhausted {
+    pub resource: Resource,
+    /// Minutes, or MiB for disk.
+    pub used: u64,
+    pub limit: u64,
+}
+
+impl fmt::Display for Exhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match self.resource {
+            Resource::Disk => "MiB",
+            Resource::Cpu | Resource::Wall => "minutes",
+        };
+        write!(
+            f,
+            "{} budget spent: {} of {} {unit}",
+            self.resource, self.used, self.limit
+        )
+    }
+}
+
+/// Measures a campaign against its [`Budget`].
+#[derive(Debug, Clone)]
+pub struct Meter {
+    budget: Budget,
+    before: Spent,
+    started: Instant,
+    cpu_at_start: Duration,
+    /// Directories whose contents count against the disk budget.
+    dirs: Vec<PathBuf>,
+    /// Bytes in `dirs` when last measured, and when that was.
+    disk: Option<(u64, Instant)>,
+}
+
+impl Meter {
+    /// Starts measuring a campaign whose earlier runs spent `before`.
+    pub fn start(budget: Budget, before: Spent) -> Self {
+        Meter {
+            budget,
+            before,
+            started: Instant::now(),
+            cpu_at_start: cpu_time(),
+            dirs: Vec::new(),
+            disk: None,
+        }
+    }
+
+    /// Counts what is in `dir` against the disk budget.
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dirs.push(dir.into());
+        self
+    }
+
+    /// What the campaign has spent, earlier runs included.
+    pub fn spent(&self) -> Spent {
+        let cpu = cpu_time().saturating_sub(self.cpu_at_start);
+        Spent {
+            cpu_ms: self.before.cpu_ms + cpu.as_millis() as u64,
+            wall_ms: self.before.wall_ms + self.started.elapsed().as_millis() as u64,
+        }
+    }
+
+    /// The first resource whose budget is spent, if any.
+    pub fn check(&mut self) -> io::Result<Option<Exhausted>> {
+        let spent = self.spent();
+        let minutes = |ms: u64| ms / 60_000;
+        if let Some(limit) = self.budget.cpu_minutes {
+            if spent.cpu_ms >= limit * 60_000 {
+                return Ok(Some(Exhausted {
+                    resource: Resource::Cpu,
+                    used: minutes(spent.cpu_ms),
+                    limit,
+                }));
+            }
+        }
+        if let Some(limit) = self.budget.wall_minutes {
+            if spent.wall_ms >= limit * 60_000 {
+                return Ok(Some(Exhausted {
+                    resource: Resource::Wall,
+                    used: minutes(spent.wall_ms),
+                    limit,
+                }));
+            }
+        }
+        if let Some(limit) = self.budget.disk_mb {
+            let stale = self
+                .disk
+                .is_none_or(|(_, at)| at.elapsed() >= DISK_INTERVAL);
+            if stale {
+                let mut bytes = 0;
+                for dir in &self.dirs {
+                    bytes += disk_usage(dir)?;
+                }
+                tracing::debug!(mib = bytes >> 20, limit, "measured disk use");
+                self.disk = Some((bytes, Instant::now()));
+            }
+            let bytes = self.disk.map_or(0, |(bytes, _)| bytes);
+            if bytes >= limit << 20 {
+                return Ok(Some(Exhausted {
+                    resource: Resource::Disk,
+                    used: bytes >> 20,
+                    limit,
+                }));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// CPU time this process and the children it has waited for have used.
+pub fn cpu_time() -> Duration {
+    [libc::RUSAGE_SELF, libc::RUSAGE_CHILDREN]
+        .into_iter()
+        .map(|who| {
+            // SAFETY: rusage is plain data, and getrusage only writes the
+            // struct it is given.
+            let usage = unsafe {
+                let mut usage: libc::rusage = std::mem::zeroed();
+                (libc::getrusage(who, &mut usage) == 0).then_some(usage)
+            };
+            let Some(usage) = usage else {
+                return Duration::ZERO;
+            };
+            let time =
+                |tv: libc::timeval| Duration::new(tv.tv_sec as u64, tv.tv_usec as u32 * 1000);
+            time(usage.ru_utime) + time(usage.ru_stime)
+        })
+        .sum()
+}
+
+/// Bytes in the files under `path`; 0 if there is nothing there.
+pub fn disk_usage(path: &Path) -> io::Result<u64> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e),
+    };
+    if !metadata.is_dir() {
+        return Ok(metadata.len());
+    }
+    let mut bytes = 0;
+    for entry in fs::read_dir(path)? {
+        bytes += disk_usage(&entry?.path())?;
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn earlier_runs_count_against_the_budget() {
+        let budget = Budget {
+            cpu_minutes: Some(2),
+            wall_minutes: Some(1),
+            ..Budget::default()
+        };
+        let mut fresh = Meter::start(budget, Spent::default());
+        assert_eq!(fresh.check().expect("checks"), None);
+        let mut resumed = Meter::start(
+            budget,
+            Spent {
+                cpu_ms: 0,
+                wall_ms: 90_000,
+            },
+        );
+        let exhausted = resumed.check().expect("checks").expect("spent");
+        assert_eq!(
+            exhausted,
+            Exhausted {
+                resource: Resource::Wall,
+                used: 1,
+                limit: 1
+            }
+        );
+        assert_eq!(
+            exhausted.to_string(),
+            "wall-clock budget spent: 1 of 1 minutes"
+        );
+        assert!(resumed.spent().wall_ms >= 90_000);
+        let mut busy = Meter::start(
+            budget,
+            Spent {
+                cpu_ms: 120_000,
+                wall_ms: 0,
+            },
+        );
+        assert_eq!(
+            busy.check().expect("checks").map(|e| e.resource),
+            Some(Resource::Cpu)
+        );
+        assert_eq!(
+            Meter::start(Budget::default(), busy.spent())
+                .check()
+                .expect("checks"),
+            None
+        );
+    }
+
+    #[test]
+    fn artifacts_count_against_the_disk_budget() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-budget-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).expect("creates the findings");
+        fs::write(dir.join("a.rs"), vec![b'x'; 512 << 10]).expect("writes");
+        fs::write(dir.join("nested").join("b.rs"), vec![b'x'; 512 << 10]).expect("writes");
+        assert_eq!(disk_usage(&dir).expect("measures"), 1 << 20);
+        assert_eq!(disk_usage(&dir.join("missing")).expect("measures"), 0);
+        let budget = |disk_mb| Budget {
+            disk_mb: Some(disk_mb),
+            ..Budget::default()
+        };
+        let mut within = Meter::start(budget(2), Spent::default()).with_dir(&dir);
+        assert_eq!(within.check().expect("checks"), None);
+        let mut over = Meter::start(budget(1), Spent::default()).with_dir(&dir);
+        let exhausted = over.check().expect("checks").expect("spent");
+        assert_eq!(exhausted.to_string(), "disk budget spent: 1 of 1 MiB");
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,620 @@
+//! Setting harnesses up for a run of seeds.
+//!
+//! Every built-in [`Harness`] and every oracle of an installed plugin
+//! runs as a [`Check`]: a closure from a seed to how bad what it found is
+//! and a summary of it. `run`, `fuzz`, `work` and the corpus commands that
+//! run seeds all drive their harness through one, and [`checker`] and
+//! [`oracle_checker`] log every execution as it goes. [`run`] checks a
+//! set of seeds with several at once on a pool of workers.
+
+use anyhow::{Context, Result};
+use clap::ValueEnum;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::path::PathBuf;
+use std::time::Instant;
+
+use crate::budget::{Budget, Exhausted, Meter, Spent};
+use crate::corpus::meta::{self, Metadata};
+use crate::corpus::Entry;
+use crate::events::{self, EventKind};
+use crate::flags::FlagFuzzer;
+use crate::harness::pool::{Pool, PoolOptions};
+use crate::harness::{
+    BackendDiff, Clippy, Coherence, ConstEval, DebugInfo, ExecOutcome, Gccrs, Incremental,
+    LayoutRandomization, LtoMatrix, ManglingDiff, MirOptDiff, Miri, Mrustc, OptLevelDiff,
+    ParallelStress, ParserDiff, Phase, RoundTrip, RustAnalyzer, Rustc, Rustdoc, Rustfmt, Soundness,
+    Subtyping, Target, TargetMatrix, WasmDiff,
+};
+use crate::mutate::Engine;
+use crate::plugin;
+use crate::triage::Severity;
+
+/// A built-in harness, by the name the command line and campaign configs
+/// give it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Harness {
+    /// rustc crashes and ICEs.
+    Rustc,
+    /// rustc crashes and ICEs under sampled `-C`/`-Z` flag combinations.
+    Flags,
+    /// rustc crashes and ICEs across a matrix of `--target` triples.
+    Targets,
+    /// Incremental rebuilds of mutants that differ from clean builds.
+    Incremental,
+    /// ICEs, deadlocks and diagnostic-order changes under `-Zthreads`.
+    Threads,
+    /// Clippy lint-pass panics, and with `--fix` broken suggestions.
+    Clippy,
+    /// rust-analyzer panics on hover, completion and diagnostics.
+    RustAnalyzer,
+    /// Output differences and ICEs between the LLVM and Cranelift
+    /// backends.
+    Backend,
+    /// Output differences between native and `wasm32-wasip1` builds run
+    /// in wasmtime.
+    Wasm,
+    /// Pretty-printed code that compiles differently from the original.
+    Unpretty,
+    /// Accept/reject and token-count disagreements between `syn` and
+    /// rustc's parser.
+    Syn,
+    /// Accept/reject and error-code disagreements between gccrs and rustc.
+    Gccrs,
+    /// Accept/reject disagreements between mrustc and rustc.
+    Mrustc,
+    /// Invalid DWARF, as `llvm-dwarfdump --verify` sees it, and
+    /// debuginfo ICEs.
+    Debuginfo,
+    /// Output differences under `-Clto`, codegen-unit, panic-strategy and
+    /// bitcode-embedding combinations.
+    Lto,
+    /// Mangler ICEs and symbols that do not demangle, under both
+    /// `-Csymbol-mangling-version`s.
+    Mangling,
+    /// Output differences between `-Zmir-opt-level`s.
+    MirOpt,
+    /// Output differences and ICEs under `-Zrandomize-layout`.
+    Layout,
+    /// Values const evaluation computes differently from compiled code,
+    /// in programs from `generate --const`.
+    ConstEval,
+    /// Output differences between `-Copt-level=0` and `3` builds:
+    /// miscompilations.
+    OptLevel,
+    /// Undefined behavior Miri finds in seeds without `unsafe`.
+    Miri,
+    /// Programs from `generate --generator unsound` that rustc accepts, and
+    /// undefined behavior Miri finds in every other seed.
+    Soundness,
+    /// Pairs of crates from `generate --generator coherence` that rustc
+    /// accepts or rejects against the orphan and overlap rules, and ICEs
+    /// checking any seed with a `mod upstream` against it as a separate
+    /// crate.
+    Coherence,
+    /// Programs from `generate --generator variance` that rustc accepts or
+    /// rejects against the variance of the types they coerce, or from
+    /// `generate --generator higher-ranked` against their higher-ranked
+    /// bounds, and ICEs checking any seed to the end of analysis.
+    Subtyping,
+    /// rustdoc ICEs in HTML and JSON output, with injected doc comments.
+    Rustdoc,
+    /// rustfmt panics, non-idempotent formatting and AST changes.
+    Rustfmt,
+}
+
+impl Harness {
+    /// The name it goes by, e.g. `rust-analyzer`.
+    pub fn name(self) -> String {
+        self.to_possible_value()
+            .expect("no harness is skipped")
+            .get_name()
+            .to_owned()
+    }
+}
+
+/// The names of the built-in harnesses.
+pub fn harness_names() -> Vec<String> {
+    Harness::value_variants()
+        .iter()
+        .map(|harness| harness.name())
+        .collect()
+}
+
+/// The harnesses and the oracles of the installed plugins.
+pub fn oracle_names() -> Vec<String> {
+    let mut names = harness_names();
+    if let Some(registry) = plugin::installed() {
+        names.extend(registry.oracle_names().map(str::to_owned));
+    }
+    names
+}
+
+/// Runs one seed through a harness, returning how bad what it found is
+/// and its summary.
+pub type Check = Box<dyn FnMut(&str) -> Result<Option<(Severity, String)>> + Send>;
+
+/// What [`run`] found.
+#[derive(Debug, Default)]
+pub struct Run {
+    /// The worst new finding of each seed that has one, worst first.
+    pub findings: Vec<(Severity, PathBuf, String)>,
+    /// The worst finding of each seed whose metadata names the issues it is
+    /// known to reproduce, with those issues.
+    pub known: Vec<(PathBuf, String, Vec<u64>)>,
+    /// How many seeds were started; those after them were not run.
+    pub started: usize,
+    /// The budget that stopped the run early, if one did.
+    pub exhausted: Option<Exhausted>,
+}
+
+/// Runs `seeds` through the harnesses and oracles called `names`, set up
+/// by `checker` for each worker of a pool with `options`. Once `budget` is
+/// spent no more seeds are started; the result covers those that were.
+pub fn run(
+    seeds: &[Entry],
+    names: &[String],
+    checker: impl Fn(&str) -> Result<Check>,
+    options: &PoolOptions,
+    budget: Budget,
+) -> Result<Run> {
+    let _campaign = tracing::info_span!("campaign", command = "run").entered();
+    tracing::info!(seeds = seeds.len(), workers = options.workers, oracles = ?names, "starting");
+    // Harnesses keep state between seeds, so each worker gets its own.
+    let mut workers = (0..options.workers)
+        .map(|_| {
+            names
+                .iter()
+                .map(|name| Ok((name.clone(), checker(name)?)))
+                .collect::<Result<Vec<_>>>()
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let several = names.len() > 1;
+    let mut pool = Pool::new(options, |index| {
+        let mut checks = std::mem::take(&mut workers[index]);
+        move |source: String| -> Result<Vec<(Severity, String)>> {
+            let mut found = Vec::new();
+            for (name, check) in &mut checks {
+                if let Some((severity, finding)) = check(&source)? {
+                    // Which harness found it only matters when there are several.
+                    if several {
+                        found.push((severity, format!("{name}: {finding}")));
+                    } else {
+                        found.push((severity, finding));
+                    }
+                }
+            }
+            Ok(found)
+        }
+    })?;
+    let mut meter = Meter::start(budget, Spent::default());
+    let mut results = vec![Vec::new(); seeds.len()];
+    let mut run = Run::default();
+    for entry in seeds {
+        run.exhausted = meter.check()?;
+        if run.exhausted.is_some() {
+            break;
+        }
+        pool.submit(entry.source.clone());
+        run.started += 1;
+        while let Some((job, found)) = pool.try_recv() {
+            results[job] = found?;
+        }
+    }
+    while let Some((job, found)) = pool.recv() {
+        results[job] = found?;
+    }
+    for (entry, found) in seeds.iter().zip(results).take(run.started) {
+        let Some((severity, finding)) = found.into_iter().min() else {
+            continue;
+        };
+        let issues = Metadata::load(&entry.path)?
+            .filter(|metadata| metadata.matches(&entry.source))
+            .map(|metadata| metadata.known_issues)
+            .unwrap_or_default();
+        if issues.is_empty() {
+            run.findings.push((severity, entry.path.clone(), finding));
+        } else {
+            run.known.push((entry.path.clone(), finding, issues));
+        }
+    }
+    // Worst first, once all are in.
+    run.findings.sort();
+    Ok(run)
+}
+
+/// Sets up `harness` for a run of seeds, logging each execution.
+pub fn checker(
+    harness: Harness,
+    phase: Phase,
+    fix: bool,
+    targets: &[String],
+    flags: &[String],
+) -> Result<Check> {
+    let check = harness_checker(harness, phase, fix, targets, flags)?;
+    Ok(logged(harness.name(), check))
+}
+
+/// Sets up the oracle called `name` from the installed plugins for a run
+/// of seeds, logging each execution.
+pub fn oracle_checker(name: &str) -> Result<Check> {
+    let mut oracle = plugin::installed()
+        .and_then(|registry| registry.oracle(name))
+        .with_context(|| format!("no harness or plugin oracle is called {name:?}"))?;
+    let check: Check = Box::new(move |source| Ok(oracle.check(source)?));
+    Ok(logged(name.to_owned(), check))
+}
+
+/// `check`, emitting an execution event under `name` for each seed.
+fn logged(name: String, mut check: Check) -> Check {
+    Box::new(move |source| {
+        let input = meta::content_hash(source);
+        let _exec = tracing::debug_span!("exec", harness = %name, %input).entered();
+        let start = Instant::now();
+        let result = check(source)?;
+        let elapsed_ms = start.elapsed().as_millis() as u64;
+        let (severity, finding) = result.clone().unzip();
+        tracing::debug!(
+            elapsed_ms,
+            severity = severity.map(Severity::name),
+            finding = finding.as_deref(),
+            "checked"
+        );
+        events::emit(EventKind::Exec {
+            harness: name.clone(),
+            input,
+            elapsed_ms: Some(elapsed_ms),
+            severity,
+            finding,
+        })?;
+        Ok(result)
+    })
+}
+
+fn harness_checker(
+    harness: Harness,
+    phase: Phase,
+    fix: bool,
+    targets: &[String],
+    flags: &[String],
+) -> Result<Check> {
+    Ok(match harness {
+        Harness::Rustc => {
+            let rustc = Rustc {
+                phase,
+                ..Rustc::default()
+            };
+            Box::new(move |source| {
+                let outcome = rustc.run(["--crate-type=lib", "-"], source.as_bytes())?;
+                // A timeout alone is not a finding; `triage hangs` is for
+                // telling whether it is one.
+                let severity = match outcome {
+                    ExecOutcome::Timeout(_) => None,
+                    _ => Severity::of(&outcome),
+                };
+                Ok(severity.map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Flags => {
+            let fuzzer = FlagFuzzer {
+                matrix: flags
+                    .iter()
+                    .map(|set| set.split_whitespace().map(str::to_owned).collect())
+                    .collect(),
+                ..FlagFuzzer::default()
+            };
+            let mut rng = StdRng::seed_from_u64(0);
+            Box::new(move |source| {
+                let outcome = fuzzer.check(source, &mut rng)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Targets => {
+            let mut matrix = TargetMatrix::default();
+            if !targets.is_empty() {
+                matrix.targets = targets.iter().map(Target::new).collect();
+            }
+            Box::new(move |source| {
+                let outcome = matrix.check(source)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Incremental => {
+            let incremental = Incremental::default();
+            let engine = Engine::default();
+            let mut rng = StdRng::seed_from_u64(0);
+            Box::new(move |source| {
+                let outcome = incremental.check(source, &engine, &mut rng)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Threads => {
+            let stress = ParallelStress::default();
+            Box::new(move |source| {
+                let outcome = stress.check(source)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Clippy => {
+            let clippy = Clippy {
+                fix,
+                ..Clippy::default()
+            };
+            Box::new(move |source| {
+                let outcome = clippy.check(source)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Backend => {
+            let diff = BackendDiff::default();
+            Box::new(move |source| {
+                let outcome = diff.check(source)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Wasm => {
+            let diff = WasmDiff::default();
+            Box::new(move |source| {
+                let outcome = diff.check(source).context("running wasmtime")?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Unpretty => {
+            let round_trip = RoundTrip::default();
+            Box::new(move |source| {
+                let outcome = round_trip.check(source)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Syn => {
+            let parsers = ParserDiff::default();
+            Box::new(move |source| {
+                let outcome = parsers.check(source)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Gccrs => {
+            let gccrs = Gccrs::default();
+            Box::new(move |source| {
+                let outcome = gccrs.check(source).context("running gccrs")?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Mrustc => {
+            let mrustc = Mrustc::default();
+            Box::new(move |source| {
+                let outcome = mrustc.check(source).context("running mrustc")?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Debuginfo => {
+            let debuginfo = DebugInfo::default();
+            Box::new(move |source| {
+                let outcome = debuginfo.check(source).context("running llvm-dwarfdump")?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Lto => {
+            let matrix = LtoMatrix::default();
+            let mut rng = StdRng::seed_from_u64(0);
+            Box::new(move |source| {
+                let outcome = matrix.check(source, &mut rng)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Mangling => {
+            let diff = ManglingDiff::default();
+            Box::new(move |source| {
+                let outcome = diff.check(source).context("running nm")?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::MirOpt => {
+            let diff = MirOptDiff::default();
+            Box::new(move |source| {
+                let outcome = diff.check(source)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Layout => {
+            let randomization = LayoutRandomization::default();
+            Box::new(move |source| {
+                let outcome = randomization.check(source)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::ConstEval => {
+            let diff = ConstEval::default();
+            Box::new(move |source| {
+                let outcome = diff.check(source)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::OptLevel => {
+            let diff = OptLevelDiff::default();
+            Box::new(move |source| {
+                let outcome = diff.check(source)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Miri => {
+            let miri = Miri::default();
+            Box::new(move |source| {
+                let outcome = miri.check(source)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Soundness => {
+            let soundness = Soundness::default();
+            Box::new(move |source| {
+                let outcome = soundness.check(source)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Coherence => {
+            let coherence = Coherence::default();
+            Box::new(move |source| {
+                let outcome = coherence.check(source)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Subtyping => {
+            let subtyping = Subtyping::default();
+            Box::new(move |source| {
+                let outcome = subtyping.check(source)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Rustdoc => {
+            let rustdoc = Rustdoc::default();
+            let mut rng = StdRng::seed_from_u64(0);
+            Box::new(move |source| {
+                let outcome = rustdoc.check(source, &mut rng)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::Rustfmt => {
+            let rustfmt = Rustfmt::default();
+            Box::new(move |source| {
+                let outcome = rustfmt.check(source)?;
+                Ok(outcome
+                    .severity()
+                    .map(|severity| (severity, outcome.to_string())))
+            })
+        }
+        Harness::RustAnalyzer => {
+            let analyzer = RustAnalyzer::default();
+            let mut session = analyzer.start().context("starting rust-analyzer")?;
+            let mut rng = StdRng::seed_from_u64(0);
+            Box::new(move |source| {
+                let outcome = session.check(source, &mut rng)?;
+                let Some(severity) = outcome.severity() else {
+                    return Ok(None);
+                };
+                // Whatever state the server is in now is not to be trusted
+                // for the next seed.
+                session = analyzer.start().context("restarting rust-analyzer")?;
+                Ok(Some((severity, outcome.to_string())))
+            })
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn harnesses_parse_by_name() {
+        for name in harness_names() {
+            let harness = Harness::from_str(&name, false).unwrap();
+            assert_eq!(harness.name(), name);
+        }
+        assert_eq!(Harness::RustAnalyzer.name(), "rust-analyzer");
+        assert_eq!(Harness::ConstEval.name(), "const-eval");
+    }
+
+    #[test]
+    fn unknown_oracles_are_an_error() {
+        let err = oracle_checker("no-such-oracle").err().unwrap();
+        assert!(err.to_string().contains("\"no-such-oracle\""));
+    }
+
+    #[test]
+    fn runs_set_known_issues_apart() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-check-run-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("creates the seeds");
+        let seed = |name: &str, source: &str| {
+            let path = dir.join(name);
+            std::fs::write(&path, source).expect("writes a seed");
+            Entry {
+                path,
+                source: source.to_owned(),
+            }
+        };
+        let seeds = [
+            seed("a.rs", "fn boom() {}"),
+            seed("b.rs", "fn fine() {}"),
+            seed("c.rs", "fn boom() { boom() }"),
+        ];
+        Metadata {
+            known_issues: vec![12345],
+            ..Metadata::new(&seeds[2].source, meta::Provenance::Unknown)
+        }
+        .save(&seeds[2].path)
+        .expect("writes the sidecar");
+        let checker = |_: &str| -> Result<Check> {
+            Ok(Box::new(|source: &str| {
+                Ok(source
+                    .contains("boom")
+                    .then(|| (Severity::Ice, "boom".to_owned())))
+            }))
+        };
+        let names = ["stub".to_owned()];
+        let run = run(
+            &seeds,
+            &names,
+            checker,
+            &PoolOptions::new(1, vec![]),
+            Budget::default(),
+        )
+        .expect("runs");
+        assert_eq!(run.started, 3);
+        assert!(run.exhausted.is_none());
+        assert_eq!(
+            run.findings,
+            [(Severity::Ice, dir.join("a.rs"), "boom".to_owned())]
+        );
+        assert_eq!(
+            run.known,
+            [(dir.join("c.rs"), "boom".to_owned(), vec![12345])]
+        );
+        std::fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,151 @@
+//! Campaign checkpoints.
+//!
+//! Multi-day campaigns die to reboots and OOM kills. `fuzz` saves a
+//! [`Checkpoint`] to its findings directory every few minutes and when it
+//! finishes: where its corpus and findings are, how far it got, the
+//! [`Scheduler`]'s and the mutation engine's state, and its counters.
+//! Every iteration draws from streams of its own (see [`crate::rng`]), so
+//! how far the run got is all there is to know about them. `fuzz --resume
+//! DIR` restores all of that, so the resumed run makes the same choices
+//! the killed one would have made from the checkpoint on. Random streams
+//! private to a harness start over.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::budget::Spent;
+use crate::mutate::ArmState;
+use crate::scheduler::{Policy, Scheduler};
+use crate::triage::Severity;
+
+/// Everything a `fuzz` run needs to carry on after being killed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub corpus: PathBuf,
+    pub findings: PathBuf,
+    /// The master seed the run's random streams derive from.
+    pub seed: u64,
+    /// Iterations the run was asked for.
+    pub iterations: u64,
+    /// Iterations started before the checkpoint; a resumed run repeats
+    /// none of them.
+    pub done: u64,
+    pub scheduler: Scheduler,
+    /// The seeds the scheduler's indices refer to, in order; empty until
+    /// the run has read its corpus.
+    pub seeds: Vec<PathBuf>,
+    pub operators: Vec<ArmState>,
+    /// Severities and summaries of the findings so far, which tell new
+    /// buckets apart.
+    pub signatures: BTreeSet<(Severity, String)>,
+    pub found: u64,
+    pub known: u64,
+    pub added: u64,
+    /// Time spent so far, which counts against a budget.
+    #[serde(default)]
+    pub spent: Spent,
+}
+
+impl Checkpoint {
+    /// The checkpoint's file name in a findings directory.
+    pub const FILE: &'static str = "checkpoint.json";
+
+    /// The state of a run that has not started.
+    pub fn new(corpus: &Path, findings: &Path, policy: Policy, seed: u64, iterations: u64) -> Self {
+        Checkpoint {
+            corpus: corpus.to_owned(),
+            findings: findings.to_owned(),
+            seed,
+            iterations,
+            done: 0,
+            scheduler: Scheduler::new(policy),
+            seeds: Vec::new(),
+            operators: Vec::new(),
+            signatures: BTreeSet::new(),
+            found: 0,
+            known: 0,
+            added: 0,
+            spent: Spent::default(),
+        }
+    }
+
+    /// The checkpoint in findings directory `dir`, if there is one.
+    pub fn load(dir: &Path) -> io::Result<Option<Self>> {
+        match fs::read_to_string(dir.join(Self::FILE)) {
+            Ok(json) => Ok(Some(serde_json::from_str(&json)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Writes the checkpoint to the findings directory. It is written
+    /// beside the old one and renamed over it, so a kill while saving
+    /// leaves the old one whole.
+    pub fn save(&self) -> io::Result<()> {
+        let path = self.findings.join(Self::FILE);
+        let partial = path.with_extension("json.partial");
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        fs::write(&partial, json)?;
+        fs::rename(&partial, &path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_whole_and_loads_back() {
+        let dir =
+            std::env::temp_dir().join(format!("rustc-fuzz-checkpoint-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the findings");
+        assert!(Checkpoint::load(&dir).expect("reads").is_none());
+        let mut checkpoint = Checkpoint::new(Path::new("corpus"), &dir, Policy::default(), 7, 100);
+        checkpoint.done = 42;
+        checkpoint.seeds = vec![PathBuf::from("corpus/a.rs")];
+        checkpoint
+            .signatures
+            .insert((Severity::Ice, "ICE: bad type".to_owned()));
+        checkpoint.found = 3;
+        checkpoint.save().expect("saves");
+        checkpoint.done = 43;
+        checkpoint.save().expect("saves over the last one");
+        assert!(!dir.join("checkpoint.json.partial").exists());
+
+        let loaded = Checkpoint::load(&dir)
+            .expect("reads")
+            .expect("has a checkpoint");
+        assert_eq!(loaded.corpus, PathBuf::from("corpus"));
+        assert_eq!((loaded.seed, loaded.iterations), (7, 100));
+        assert_eq!((loaded.done, loaded.found), (43, 3));
+        assert_eq!(loaded.seeds, checkpoint.seeds);
+        assert_eq!(loaded.signatures, checkpoint.signatures);
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+
+    #[test]
+    fn checkpoints_from_before_budgets_load() {
+        let dir = std::env::temp_dir().join(format!(
+            "rustc-fuzz-checkpoint-spent-{}",
+            std::process::id()
+        ));
+        fs::create_dir_all(&dir).expect("creates the findings");
+        let checkpoint = Checkpoint::new(Path::new("corpus"), &dir, Policy::default(), 7, 100);
+        let mut json = serde_json::to_value(&checkpoint).expect("serializes");
+        json.as_object_mut()
+            .expect("an object")
+            .remove("spent")
+            .expect("has the time spent");
+        fs::write(dir.join(Checkpoint::FILE), json.to_string()).expect("writes");
+        let loaded = Checkpoint::load(&dir)
+            .expect("reads")
+            .expect("has a checkpoint");
+        assert_eq!(loaded.spent, Spent::default());
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
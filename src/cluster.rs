@@ -0,0 +1,712 @@
+//! Spreading a campaign over machines.
+//!
+//! A [`Coordinator`] owns the corpus, the findings and the schedule;
+//! [`Worker`]s hold no state and ask it for one job at a time over plain
+//! HTTP/1.1 with JSON bodies, so a worker can be killed or added at any
+//! point. A job is either a corpus seed or an RNG seed for the program
+//! generator, always with the harness to run it through. Jobs a worker
+//! takes and never reports on are handed out again once their lease
+//! expires.
+//!
+//! Findings are written under the coordinator's findings directory, one
+//! subdirectory per bucket. A bucket is the harness's one-line summary
+//! with numbers blanked out, which is coarse (every ICE from the `rustc`
+//! harness shares one) but never splits a bug, so each bucket keeps a few
+//! distinct reproducers rather than one.
+//!
+//! Endpoints: `POST /job` returns a [`Job`], or 204 once there is no work
+//! left; `POST /report` takes a [`Report`]; `GET /status` returns a
+//! [`Status`]; `GET /metrics` returns [`Metrics`] for Prometheus; `GET /`
+//! is the [`dashboard`](crate::dashboard), which links to reproducers
+//! under `GET /findings/<bucket>/<hash>.rs`. Nothing is
+//! authenticated: listen on a private network.
+//!
+//! New buckets go to the [`events`] log; workers log their executions to
+//! their own.
+
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::corpus::meta::{self, Metadata, Provenance};
+use crate::corpus::Entry;
+use crate::coverage::Matrix;
+use crate::dashboard;
+use crate::events::{self, EventKind};
+use crate::gen::{Level, ProgramGen};
+use crate::metrics::Metrics;
+use crate::rng::{SplitRng, Stream};
+use crate::triage::Severity;
+
+/// Largest request body the coordinator reads.
+const BODY_LIMIT: usize = 16 << 20;
+
+/// How long a finished coordinator keeps telling workers there is no work
+/// left before it exits, so they stop rather than wait for it to return.
+const DRAIN: Duration = Duration::from_secs(5);
+
+/// One unit of work.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    /// The harness to run, by its command-line name.
+    pub harness: String,
+    pub input: Input,
+}
+
+/// What a job runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Input {
+    /// A corpus seed, named by its path on the coordinator.
+    Seed { name: String, source: String },
+    /// The program [`ProgramGen`] generates from `seed` at `level`: the
+    /// one `rustc-fuzz generate --seed <seed>` writes first.
+    Generate { seed: u64, level: String },
+}
+
+impl Input {
+    /// The source the job runs. Generation is deterministic, so the
+    /// coordinator and every worker agree on it.
+    pub fn source(&self) -> io::Result<String> {
+        match self {
+            Input::Seed { source, .. } => Ok(source.clone()),
+            Input::Generate { seed, level } => {
+                let mut gen = ProgramGen::default();
+                gen.gen.level = level
+                    .parse()
+                    .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+                Ok(gen.generate(&mut SplitRng::new(*seed).rng(Stream::Generator, 0)))
+            }
+        }
+    }
+
+    /// Metadata for the job's source: a seed's own, if the coordinator
+    /// has it, or where the source came from.
+    fn metadata(&self, source: &str) -> io::Result<Metadata> {
+        let provenance = match self {
+            Input::Seed { name, .. } => match Metadata::load(Path::new(name))? {
+                Some(metadata) if metadata.matches(source) => return Ok(metadata),
+                _ => Provenance::Imported {
+                    origin: name.clone(),
+                },
+            },
+            Input::Generate { seed, level } => Provenance::Generated {
+                seed: *seed,
+                index: 0,
+                level: level.clone(),
+                constant: false,
+                library: None,
+                weights: None,
+                generator: None,
+            },
+        };
+        Ok(Metadata::new(source, provenance))
+    }
+
+    fn origin(&self) -> String {
+        match self {
+            Input::Seed { name, .. } => name.clone(),
+            Input::Generate { seed, level } => format!("generated from seed {seed} at {level}"),
+        }
+    }
+}
+
+/// What running a job led to.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Report {
+    pub job: u64,
+    /// The harness's summary of what it found, if anything.
+    pub finding: Option<String>,
+    /// How bad the finding is. Workers from before severities do not say.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    /// How long the job took the worker, in milliseconds. Workers from
+    /// before metrics do not say.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<u64>,
+}
+
+/// Campaign progress, as `GET /status` returns it.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Status {
+    /// Jobs handed out, including ones handed out again.
+    pub issued: u64,
+    pub reported: u64,
+    /// Jobs out with a worker right now.
+    pub pending: usize,
+    pub findings: u64,
+    /// Findings on seeds that reproduce a known issue; not counted in
+    /// `findings` nor kept.
+    pub known: u64,
+    /// Findings kept, after deduplication.
+    pub kept: u64,
+    /// Findings per bucket.
+    pub buckets: HashMap<String, u64>,
+    /// Findings per severity, of those that have one.
+    #[serde(default)]
+    pub severities: BTreeMap<Severity, u64>,
+    /// Seeds in the corpus being served.
+    #[serde(default)]
+    pub corpus: usize,
+    /// Seconds since the coordinator started.
+    #[serde(default)]
+    pub uptime_secs: u64,
+    /// Seconds since the start at which each new bucket appeared, with
+    /// the number of buckets from then on.
+    #[serde(default)]
+    pub history: Vec<(u64, usize)>,
+}
+
+/// Serves jobs to workers and collects their findings.
+#[derive(Debug, Clone)]
+pub struct Coordinator {
+    /// The harness every job runs through.
+    pub harness: String,
+    /// Where findings are written.
+    pub findings: PathBuf,
+    /// Once the corpus has been handed out, keep generating programs at
+    /// this level. Without it the campaign ends with the corpus.
+    pub generate: Option<Level>,
+    /// First generator seed; each generation job takes the next one.
+    pub seed: u64,
+    /// How long a worker has to report on a job before it is handed to
+    /// another. Covers the slowest harness, not the typical one.
+    pub lease: Duration,
+    /// Reproducers kept per bucket.
+    pub per_bucket: usize,
+}
+
+impl Default for Coordinator {
+    fn default() -> Self {
+        Coordinator {
+            harness: "rustc".to_owned(),
+            findings: PathBuf::from("findings"),
+            generate: None,
+            seed: 0,
+            lease: Duration::from_secs(600),
+            per_bucket: 5,
+        }
+    }
+}
+
+impl Coordinator {
+    /// Serves `corpus` on `listener` until every seed has been reported
+    /// on, which with generation enabled is never.
+    pub fn serve(&self, listener: TcpListener, corpus: Vec<Entry>) -> io::Result<Status> {
+        fs::create_dir_all(&self.findings)?;
+        let corpus_size = corpus.len();
+        let mut coverage = Matrix::new();
+        for entry in &corpus {
+            coverage.add(&entry.source);
+        }
+        let mut state = State {
+            queue: corpus
+                .into_iter()
+                .map(|entry| Input::Seed {
+                    name: entry.path.display().to_string(),
+                    source: entry.source,
+                })
+                .collect(),
+            next_seed: self.seed,
+            next_id: 0,
+            pending: HashMap::new(),
+            kept: HashSet::new(),
+            started: Instant::now(),
+            coverage,
+            metrics: Metrics::default(),
+            status: Status {
+                corpus: corpus_size,
+                ..Status::default()
+            },
+        };
+        for stream in listener.incoming() {
+            // A misbehaving client costs a log line, not the campaign.
+            if let Err(e) = self.handle(&mut state, stream?) {
+                tracing::warn!("coordinator: {e}");
+            }
+            if state.done(self) {
+                break;
+            }
+        }
+        listener.set_nonblocking(true)?;
+        let mut last = Instant::now();
+        while last.elapsed() < DRAIN {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    stream.set_nonblocking(false)?;
+                    if let Err(e) = self.handle(&mut state, stream) {
+                        tracing::warn!("coordinator: {e}");
+                    }
+                    last = Instant::now();
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    thread::sleep(Duration::from_millis(50))
+                }
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(state.status)
+    }
+
+    fn handle(&self, state: &mut State, mut stream: TcpStream) -> io::Result<()> {
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        let (method, path, body) = read_request(&mut stream)?;
+        state.status.uptime_secs = state.started.elapsed().as_secs();
+        match (method.as_str(), path.as_str()) {
+            ("POST", "/job") => match state.next_job(self) {
+                Some(job) => respond(&mut stream, 200, &serde_json::to_vec(&job)?),
+                None => respond(&mut stream, 204, &[]),
+            },
+            ("POST", "/report") => {
+                let report: Report = serde_json::from_slice(&body)?;
+                let accepted = self.record(state, report)?;
+                respond(&mut stream, if accepted { 200 } else { 404 }, &[])
+            }
+            ("GET", "/status") => respond(&mut stream, 200, &serde_json::to_vec(&state.status)?),
+            ("GET", "/metrics") => {
+                let text = state.metrics.render(&self.harness, &state.status);
+                respond_with(
+                    &mut stream,
+                    200,
+                    "text/plain; version=0.0.4",
+                    text.as_bytes(),
+                )
+            }
+            ("GET", "/") => {
+                let mut reproducers: Vec<String> = state.kept.iter().cloned().collect();
+                reproducers.sort_unstable();
+                let html =
+                    dashboard::render(&self.harness, &state.status, &state.coverage, &reproducers);
+                respond_with(
+                    &mut stream,
+                    200,
+                    "text/html; charset=utf-8",
+                    html.as_bytes(),
+                )
+            }
+            // Only reproducers the coordinator wrote, which also keeps
+            // requests inside the findings directory.
+            ("GET", path) => match path.strip_prefix("/findings/") {
+                Some(name) if state.kept.contains(name) => {
+                    let source = fs::read(self.findings.join(name))?;
+                    respond_with(&mut stream, 200, "text/plain; charset=utf-8", &source)
+                }
+                _ => respond(&mut stream, 404, &[]),
+            },
+            _ => respond(&mut stream, 404, &[]),
+        }
+    }
+
+    /// Files a report. Returns false for jobs that are not pending, such
+    /// as ones that were handed out again and reported on twice.
+    fn record(&self, state: &mut State, report: Report) -> io::Result<bool> {
+        let Some((job, _)) = state.pending.remove(&report.job) else {
+            tracing::debug!(job = report.job, "report on a job that is not pending");
+            return Ok(false);
+        };
+        tracing::debug!(job = report.job, finding = ?report.finding, "reported");
+        state.status.reported += 1;
+        state.status.pending = state.pending.len();
+        if let Some(elapsed) = report.elapsed_ms {
+            state.metrics.job_seconds.observe(elapsed as f64 / 1000.0);
+        }
+        let Some(summary) = report.finding else {
+            return Ok(true);
+        };
+        let source = job.input.source()?;
+        let mut metadata = job.input.metadata(&source)?;
+        if !metadata.known_issues.is_empty() {
+            state.status.known += 1;
+            return Ok(true);
+        }
+        state.status.findings += 1;
+        if let Some(severity) = report.severity {
+            *state.status.severities.entry(severity).or_default() += 1;
+            metadata.severity = Some(severity);
+        }
+        let bucket = bucket(&summary);
+        let count = state.status.buckets.entry(bucket.clone()).or_default();
+        *count += 1;
+        let count = *count;
+        if let Some(severity) = report.severity {
+            state
+                .metrics
+                .bucket_severities
+                .entry(bucket.clone())
+                .or_insert(severity);
+        }
+        if count == 1 {
+            let secs = state.started.elapsed().as_secs();
+            let buckets = state.status.buckets.len();
+            state.status.history.push((secs, buckets));
+        }
+        if count > self.per_bucket as u64 {
+            return Ok(true);
+        }
+        let name = format!("{bucket}/{}.rs", meta::content_hash(&source));
+        if !state.kept.insert(name.clone()) {
+            return Ok(true);
+        }
+        state.status.kept += 1;
+        let path = self.findings.join(&name);
+        fs::create_dir_all(self.findings.join(&bucket))?;
+        fs::write(&path, &source)?;
+        metadata.save(&path)?;
+        let mut log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.findings.join("findings.log"))?;
+        match report.severity {
+            Some(severity) => writeln!(
+                log,
+                "{name}: {severity}: {summary} ({})",
+                job.input.origin()
+            )?,
+            None => writeln!(log, "{name}: {summary} ({})", job.input.origin())?,
+        }
+        if count == 1 {
+            events::emit(EventKind::NewBucket {
+                bucket,
+                severity: report.severity,
+                reproducer: path.display().to_string(),
+            })?;
+        }
+        Ok(true)
+    }
+}
+
+/// What the coordinator tracks while serving.
+struct State {
+    queue: VecDeque<Input>,
+    next_seed: u64,
+    next_id: u64,
+    /// Jobs out with workers, with when they were handed out.
+    pending: HashMap<u64, (Job, Instant)>,
+    /// Reproducers written, by path under the findings directory.
+    kept: HashSet<String>,
+    started: Instant,
+    /// Features of the corpus being served.
+    coverage: Matrix,
+    metrics: Metrics,
+    status: Status,
+}
+
+impl State {
+    fn next_job(&mut self, config: &Coordinator) -> Option<Job> {
+        let expired = self
+            .pending
+            .iter()
+            .find(|(_, (_, issued))| issued.elapsed() > config.lease)
+            .map(|(&id, _)| id);
+        if let Some(id) = expired {
+            tracing::info!(job = id, "lease expired; handing the job out again");
+        }
+        let input = match expired {
+            Some(id) => self.pending.remove(&id).map(|(job, _)| job.input),
+            None => self.queue.pop_front(),
+        };
+        let input = input.or_else(|| {
+            let level = config.generate?;
+            self.next_seed += 1;
+            Some(Input::Generate {
+                seed: self.next_seed - 1,
+                level: level.to_string(),
+            })
+        })?;
+        let job = Job {
+            id: self.next_id,
+            harness: config.harness.clone(),
+            input,
+        };
+        self.next_id += 1;
+        tracing::debug!(job = job.id, "issued");
+        self.pending.insert(job.id, (job.clone(), Instant::now()));
+        self.status.issued += 1;
+        self.status.pending = self.pending.len();
+        Some(job)
+    }
+
+    fn done(&self, config: &Coordinator) -> bool {
+        config.generate.is_none() && self.queue.is_empty() && self.pending.is_empty()
+    }
+}
+
+/// The bucket a finding summary is filed under: lowercase, numbers
+/// replaced by `N`, and anything but letters and digits made a dash.
+fn bucket(summary: &str) -> String {
+    let mut bucket = String::new();
+    for c in summary.chars().take(80) {
+        let c = match c {
+            '0'..='9' => 'N',
+            c if c.is_ascii_alphabetic() => c.to_ascii_lowercase(),
+            _ => '-',
+        };
+        if !(matches!(c, '-' | 'N') && bucket.ends_with(c)) {
+            bucket.push(c);
+        }
+    }
+    let bucket = bucket.trim_matches('-');
+    if bucket.is_empty() {
+        "finding".to_owned()
+    } else {
+        bucket.to_owned()
+    }
+}
+
+/// Runs jobs from a coordinator.
+#[derive(Debug, Clone)]
+pub struct Worker {
+    /// The coordinator's address, as `host:port`.
+    pub coordinator: String,
+    /// How long to keep retrying an unreachable coordinator before giving
+    /// up, so workers survive it restarting.
+    pub patience: Duration,
+}
+
+impl Worker {
+    pub fn new(coordinator: impl Into<String>) -> Self {
+        Worker {
+            coordinator: coordinator.into(),
+            patience: Duration::from_secs(60),
+        }
+    }
+
+    /// Takes jobs and reports on them until the coordinator runs out.
+    /// `check` runs a source through the named harness and returns its
+    /// finding and how bad it is; an error from it ends the worker
+    /// without reporting, so the job goes to another worker once its
+    /// lease expires. Returns the number of jobs done.
+    pub fn run<F, E>(&self, mut check: F) -> Result<u64, E>
+    where
+        F: FnMut(&str, &str) -> Result<Option<(Severity, String)>, E>,
+        E: From<io::Error>,
+    {
+        let mut done = 0;
+        loop {
+            let (status, body) = self.request("POST", "/job", &[])?;
+            if status == 204 {
+                return Ok(done);
+            }
+            if status != 200 {
+                return Err(invalid(format!("coordinator answered {status} for a job")).into());
+            }
+            let job: Job = serde_json::from_slice(&body).map_err(io::Error::from)?;
+            let _seed = tracing::debug_span!("seed", job = job.id).entered();
+            let start = Instant::now();
+            let finding = check(&job.harness, &job.input.source()?)?;
+            let report = Report {
+                job: job.id,
+                severity: finding.as_ref().map(|(severity, _)| *severity),
+                finding: finding.map(|(_, summary)| summary),
+                elapsed_ms: Some(start.elapsed().as_millis() as u64),
+            };
+            // A 404 means the lease expired and someone else has the job;
+            // either way this one is finished with it.
+            let report = serde_json::to_vec(&report).map_err(io::Error::from)?;
+            self.request("POST", "/report", &report)?;
+            done += 1;
+        }
+    }
+
+    /// Sends one request, retrying while the coordinator is unreachable.
+    fn request(&self, method: &str, path: &str, body: &[u8]) -> io::Result<(u16, Vec<u8>)> {
+        let start = Instant::now();
+        loop {
+            match request(&self.coordinator, method, path, body) {
+                Err(e) if is_unreachable(&e) && start.elapsed() < self.patience => {
+                    tracing::debug!(coordinator = %self.coordinator, "unreachable, retrying: {e}");
+                    thread::sleep(Duration::from_secs(1))
+                }
+                result => return result,
+            }
+        }
+    }
+}
+
+fn is_unreachable(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::NotConnected
+    )
+}
+
+/// Sends an HTTP request to `addr` and returns the status and body.
+fn request(addr: &str, method: &str, path: &str, body: &[u8]) -> io::Result<(u16, Vec<u8>)> {
+    let addr = addr
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address to connect to"))?;
+    let mut stream = TcpStream::connect_timeout(&addr, Duration::from_secs(10))?;
+    write!(
+        stream,
+        "{method} {path} HTTP/1.1\r\nHost: {addr}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)?;
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let status = line
+        .split_whitespace()
+        .nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| invalid(format!("malformed status line {line:?}")))?;
+    let length = read_headers(&mut reader)?;
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok((status, body))
+}
+
+/// Reads a request's method, path and body.
+fn read_request(stream: &mut TcpStream) -> io::Result<(String, String, Vec<u8>)> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader.read_line(&mut line)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(path)) = (parts.next(), parts.next()) else {
+        return Err(invalid(format!("malformed request line {line:?}")));
+    };
+    let (method, path) = (method.to_owned(), path.to_owned());
+    let length = read_headers(&mut reader)?;
+    if length > BODY_LIMIT {
+        return Err(invalid(format!("{length}-byte request body")));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body)?;
+    Ok((method, path, body))
+}
+
+/// Skips the headers, returning the `Content-Length`.
+fn read_headers(reader: &mut impl BufRead) -> io::Result<usize> {
+    let mut length = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 || line.trim().is_empty() {
+            return Ok(length);
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| invalid(format!("bad Content-Length {value:?}")))?;
+            }
+        }
+    }
+}
+
+fn respond(stream: &mut TcpStream, status: u16, body: &[u8]) -> io::Result<()> {
+    respond_with(stream, status, "application/json", body)
+}
+
+fn respond_with(
+    stream: &mut TcpStream,
+    status: u16,
+    content_type: &str,
+    body: &[u8],
+) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        204 => "No Content",
+        _ => "Not Found",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {status} {reason}\r\nContent-Type: {content_type}\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n",
+        body.len()
+    )?;
+    stream.write_all(body)
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn buckets_blank_out_numbers() {
+        assert_eq!(
+            bucket("ICE: index out of bounds: the len is 3 but the index is 10"),
+            "ice-index-out-of-bounds-the-len-is-N-but-the-index-is-N"
+        );
+        assert_eq!(bucket("--- 123 ---"), "N");
+        assert_eq!(bucket("!!!"), "finding");
+    }
+
+    #[test]
+    fn generation_jobs_regenerate_alike() {
+        let input = Input::Generate {
+            seed: 7,
+            level: "1.54".to_owned(),
+        };
+        assert_eq!(
+            input.source().expect("generates"),
+            input.source().expect("generates")
+        );
+        let unknown = Input::Generate {
+            seed: 7,
+            level: "2015".to_owned(),
+        };
+        let error = unknown.source().expect_err("unknown level");
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn workers_file_findings_with_the_coordinator() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-cluster-{}", std::process::id()));
+        let findings = dir.join("findings");
+        let corpus: Vec<Entry> = ["fn a() {}", "fn boom() {}", "fn boom() { boom() }"]
+            .iter()
+            .enumerate()
+            .map(|(i, source)| Entry {
+                path: dir.join(format!("seed-{i}.rs")),
+                source: (*source).to_owned(),
+            })
+            .collect();
+        let listener = TcpListener::bind("127.0.0.1:0").expect("binds");
+        let address = listener.local_addr().expect("has an address").to_string();
+        let coordinator = Coordinator {
+            findings: findings.clone(),
+            ..Coordinator::default()
+        };
+        let served = thread::spawn(move || coordinator.serve(listener, corpus));
+
+        let done = Worker::new(address)
+            .run(|harness, source| {
+                assert_eq!(harness, "rustc");
+                Ok::<_, io::Error>(
+                    source
+                        .contains("boom")
+                        .then(|| (Severity::Ice, format!("ICE at line {}", source.len()))),
+                )
+            })
+            .expect("works");
+        assert_eq!(done, 3);
+        let status = served.join().expect("serves").expect("serves");
+        assert_eq!((status.issued, status.reported), (3, 3));
+        assert_eq!((status.findings, status.kept), (2, 2));
+        assert_eq!(
+            status.buckets,
+            HashMap::from([("ice-at-line-N".to_owned(), 2)])
+        );
+        assert_eq!(status.severities, BTreeMap::from([(Severity::Ice, 2)]));
+        let log = fs::read_to_string(findings.join("findings.log")).expect("logs findings");
+        assert_eq!(log.lines().count(), 2);
+        assert!(log.contains(": ice: ICE at line 12 ("), "{log}");
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,752 @@
+//! Campaign configuration files.
+//!
+//! A long campaign passes the same toolchain, targets, flags and paths to
+//! every command. `--config FILE` reads them from a TOML [`Campaign`]
+//! instead; anything given on the command line still wins. Relative paths
+//! are relative to the working directory, as they are on the command line.
+//!
+//! ```toml
+//! oracles = ["rustc", "flags"]
+//! targets = ["x86_64-unknown-linux-gnu", "wasm32-wasip1"]
+//! flags = [["-Copt-level=3", "-Zmir-opt-level=4"], ["-Zpolonius"]]
+//! plugins = ["target/release/libmy_oracles.so"]
+//!
+//! [toolchain]
+//! channel = "nightly"
+//! phase = "analysis"
+//!
+//! [generator]
+//! level = "1.54"
+//! weights = "weights.json"
+//!
+//! [scheduler]
+//! policy = "coverage-greedy"
+//!
+//! [limits]
+//! timeout_secs = 30
+//! memory_mb = 8192
+//!
+//! [budget]
+//! cpu_minutes = 240
+//! wall_minutes = 60
+//! disk_mb = 2048
+//!
+//! [corpus]
+//! seeds = ["corpus"]
+//! findings = "findings"
+//!
+//! [container]
+//! engine = "docker"
+//! image = "docker.io/rustlang/rust@sha256:…"
+//!
+//! [notify]
+//! url = "https://hooks.slack.com/services/…"
+//! min_severity = "ice"
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::budget::Budget;
+use crate::gen::Level;
+use crate::harness::{Container, MemoryLimit, Phase, Rustc};
+use crate::notify::{Format, Webhook};
+use crate::scheduler::Policy;
+use crate::toolchains::{Source, Spec, Toolchains};
+use crate::triage::Severity;
+
+/// Everything a campaign config sets. Missing keys take the same defaults
+/// as the command line; unknown ones are an error, so a misspelt key does
+/// not go unnoticed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Campaign {
+    /// Harnesses, or oracles from plugins, every seed goes through;
+    /// commands that take one harness use the first.
+    pub oracles: Vec<String>,
+    /// Triples for the `targets` harness; empty for its defaults.
+    pub targets: Vec<String>,
+    /// Flag sets the `flags` harness tries on every seed before sampling
+    /// its own.
+    pub flags: Vec<Vec<String>>,
+    /// Plugin libraries to load, whose generators, mutators and oracles
+    /// are then available by name.
+    pub plugins: Vec<PathBuf>,
+    pub toolchain: Toolchain,
+    pub generator: Generator,
+    pub scheduler: SchedulerConfig,
+    pub limits: Limits,
+    /// What the whole campaign may use.
+    pub budget: Budget,
+    pub corpus: Paths,
+    /// Run every execution in a container rather than on the host.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<ContainerConfig>,
+    /// Post new buckets, divergences and fixes to a webhook.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notify: Option<NotifyConfig>,
+}
+
+impl Default for Campaign {
+    fn default() -> Self {
+        Campaign {
+            oracles: vec!["rustc".to_owned()],
+            targets: Vec::new(),
+            flags: Vec::new(),
+            plugins: Vec::new(),
+            toolchain: Toolchain::default(),
+            generator: Generator::default(),
+            scheduler: SchedulerConfig::default(),
+            limits: Limits::default(),
+            budget: Budget::default(),
+            corpus: Paths::default(),
+            container: None,
+            notify: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Toolchain {
+    /// The compiler under test.
+    pub rustc: PathBuf,
+    /// A toolchain [`Spec`] to install and test instead of `rustc`; the
+    /// newest toolchain of a range.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub channel: Option<String>,
+    /// Phase after which it stops.
+    pub phase: String,
+    /// Where [`Toolchains`] keeps its state and dist installs.
+    pub store: PathBuf,
+    /// `rustup` or `dist`.
+    pub source: String,
+    /// Hours before the latest nightly is looked for again.
+    pub refresh_hours: u64,
+}
+
+impl Default for Toolchain {
+    fn default() -> Self {
+        let toolchains = Toolchains::new("toolchains");
+        Toolchain {
+            rustc: PathBuf::from("rustc"),
+            channel: None,
+            phase: Phase::default().to_string(),
+            store: toolchains.store,
+            source: toolchains.source.to_string(),
+            refresh_hours: toolchains.refresh.as_secs() / 3600,
+        }
+    }
+}
+
+impl Toolchain {
+    /// The toolchain manager these settings describe, if they are valid.
+    pub fn toolchains(&self) -> Result<Toolchains> {
+        Ok(Toolchains {
+            source: self.source.parse()?,
+            refresh: Duration::from_secs(self.refresh_hours * 3600),
+            ..Toolchains::new(&self.store)
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Generator {
+    /// Newest Rust generated programs may need.
+    pub level: String,
+    /// First generator seed.
+    pub seed: u64,
+    /// Production weights from `corpus productions`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub weights: Option<PathBuf>,
+    /// Fragment library from `corpus library`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub library: Option<PathBuf>,
+}
+
+impl Default for Generator {
+    fn default() -> Self {
+        Generator {
+            level: Level::default().to_string(),
+            seed: 0,
+            weights: None,
+            library: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SchedulerConfig {
+    /// How `fuzz` picks the seed to mutate next: a [`Policy`] name.
+    pub policy: String,
+}
+
+impl Default for SchedulerConfig {
+    fn default() -> Self {
+        SchedulerConfig {
+            policy: Policy::default().to_string(),
+        }
+    }
+}
+
+/// What every execution of the compiler may use.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Limits {
+    /// Wall-clock seconds.
+    pub timeout_secs: u64,
+    /// MiB of address space; 0 for no limit.
+    pub memory_mb: u64,
+    /// Bytes of stdout and of stderr kept.
+    pub output_bytes: usize,
+    /// Niceness compilers run at; the fuzzer's own if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub nice: Option<i32>,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            timeout_secs: 10,
+            memory_mb: 4 << 10,
+            output_bytes: 1 << 20,
+            nice: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Paths {
+    /// Seed files or corpus directories.
+    pub seeds: Vec<PathBuf>,
+    /// Where findings are written.
+    pub findings: PathBuf,
+}
+
+impl Default for Paths {
+    fn default() -> Self {
+        Paths {
+            seeds: Vec::new(),
+            findings: PathBuf::from("findings"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContainerConfig {
+    /// `podman` or `docker`.
+    pub engine: PathBuf,
+    /// The image, pinned by digest.
+    pub image: String,
+    /// Let containers reach the network.
+    pub network: bool,
+    /// Further arguments to the engine's `run`.
+    pub args: Vec<String>,
+}
+
+impl Default for ContainerConfig {
+    fn default() -> Self {
+        let container = Container::new("");
+        ContainerConfig {
+            engine: container.engine,
+            image: container.image,
+            network: container.network,
+            args: container.args,
+        }
+    }
+}
+
+impl ContainerConfig {
+    pub fn container(&self) -> Container {
+        Container {
+            engine: self.engine.clone(),
+            image: self.image.clone(),
+            network: self.network,
+            args: self.args.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotifyConfig {
+    /// The webhook; `--notify` overrides it.
+    pub url: String,
+    /// `slack` or `json`.
+    pub format: String,
+    /// Only announce buckets this bad or worse.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_severity: Option<String>,
+    /// Messages allowed per window.
+    pub burst: usize,
+    pub window_secs: u64,
+    /// File of what has been announced, kept across restarts.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<PathBuf>,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        let webhook = Webhook::new("");
+        NotifyConfig {
+            url: webhook.url,
+            format: webhook.format.to_string(),
+            min_severity: None,
+            burst: webhook.burst,
+            window_secs: webhook.window.as_secs(),
+            state: None,
+        }
+    }
+}
+
+impl NotifyConfig {
+    /// The webhook these settings describe, if they are valid.
+    pub fn webhook(&self) -> Result<Webhook> {
+        Ok(Webhook {
+            format: self.format.parse::<Format>()?,
+            min_severity: self
+                .min_severity
+                .as_deref()
+                .map(str::parse::<Severity>)
+                .transpose()?,
+            burst: self.burst,
+            window: Duration::from_secs(self.window_secs),
+            state: self.state.clone(),
+            ..Webhook::new(&self.url)
+        })
+    }
+}
+
+impl Campaign {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("parsing {}", path.display()))
+    }
+
+    /// The config as TOML, every key included.
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    /// What is wrong with the config, if anything; `oracles` are the
+    /// harness and plugin oracle names it may enable.
+    pub fn problems(&self, oracles: &[&str]) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.oracles.is_empty() {
+            problems.push("oracles: no harness enabled".to_owned());
+        }
+        for oracle in &self.oracles {
+            if !oracles.contains(&oracle.as_str()) {
+                problems.push(format!(
+                    "oracles: unknown harness or oracle {oracle:?}; expected one of {}",
+                    oracles.join(", ")
+                ));
+            }
+        }
+        for (i, set) in self.flags.iter().enumerate() {
+            if let Some(flag) = set.iter().find(|flag| !flag.starts_with('-')) {
+                problems.push(format!("flags[{i}]: {flag:?} is not a flag"));
+            }
+        }
+        for path in self.plugins.iter().filter(|path| !path.exists()) {
+            problems.push(format!("plugins: {} does not exist", path.display()));
+        }
+        if let Some(channel) = &self.toolchain.channel {
+            if let Err(e) = channel.parse::<Spec>() {
+                problems.push(format!("toolchain.channel: {e}"));
+            }
+            if self.toolchain.rustc != Toolchain::default().rustc {
+                problems.push(
+                    "toolchain.channel: give either it or toolchain.rustc, not both".to_owned(),
+                );
+            }
+        }
+        if let Err(e) = self.toolchain.source.parse::<Source>() {
+            problems.push(format!("toolchain.source: {e}"));
+        }
+        if self.toolchain.refresh_hours == 0 {
+            problems.push("toolchain.refresh_hours: must be at least 1".to_owned());
+        }
+        if let Err(e) = self.toolchain.phase.parse::<Phase>() {
+            problems.push(format!("toolchain.phase: {e}"));
+        }
+        // A bare name is looked up in `PATH` when it runs.
+        let rustc = &self.toolchain.rustc;
+        if rustc.components().count() > 1 && !rustc.exists() {
+            problems.push(format!(
+                "toolchain.rustc: {} does not exist",
+                rustc.display()
+            ));
+        }
+        if let Err(e) = self.generator.level.parse::<Level>() {
+            problems.push(format!("generator.level: {e}"));
+        }
+        if let Err(e) = self.scheduler.policy.parse::<Policy>() {
+            problems.push(format!("scheduler.policy: {e}"));
+        }
+        for (key, path) in [
+            ("generator.weights", &self.generator.weights),
+            ("generator.library", &self.generator.library),
+        ] {
+            if let Some(path) = path.as_ref().filter(|path| !path.exists()) {
+                problems.push(format!("{key}: {} does not exist", path.display()));
+            }
+        }
+        if self.limits.timeout_secs == 0 {
+            problems.push("limits.timeout_secs: must be at least 1".to_owned());
+        }
+        if self.limits.output_bytes == 0 {
+            problems.push("limits.output_bytes: must be at least 1".to_owned());
+        }
+        for (key, limit) in [
+            ("budget.cpu_minutes", self.budget.cpu_minutes),
+            ("budget.wall_minutes", self.budget.wall_minutes),
+            ("budget.disk_mb", self.budget.disk_mb),
+        ] {
+            if limit == Some(0) {
+                problems.push(format!("{key}: must be at least 1"));
+            }
+        }
+        for path in self.corpus.seeds.iter().filter(|path| !path.exists()) {
+            problems.push(format!("corpus.seeds: {} does not exist", path.display()));
+        }
+        if let Some(config) = &self.container {
+            if config.image.is_empty() {
+                problems.push("container.image: no image given".to_owned());
+            } else if !config.container().is_pinned() {
+                problems.push(format!(
+                    "container.image: {:?} is not pinned; name it by digest, as in name@sha256:…",
+                    config.image
+                ));
+            }
+        }
+        if let Some(notify) = &self.notify {
+            if !notify.url.starts_with("https://") && !notify.url.starts_with("http://") {
+                problems.push(format!(
+                    "notify.url: {:?} is not an http(s) URL",
+                    notify.url
+                ));
+            }
+            if let Err(e) = notify.format.parse::<Format>() {
+                problems.push(format!("notify.format: {e}"));
+            }
+            if let Some(Err(e)) = notify.min_severity.as_deref().map(str::parse::<Severity>) {
+                problems.push(format!("notify.min_severity: {e}"));
+            }
+            if notify.burst == 0 {
+                problems.push("notify.burst: must be at least 1".to_owned());
+            }
+        }
+        problems
+    }
+
+    /// The driver every harness starts from: the configured toolchain
+    /// under the configured limits.
+    pub fn rustc(&self) -> Rustc {
+        Rustc {
+            timeout: Duration::from_secs(self.limits.timeout_secs),
+            memory: (self.limits.memory_mb > 0)
+                .then_some(MemoryLimit::AddressSpace(self.limits.memory_mb << 20)),
+            output_limit: self.limits.output_bytes,
+            nice: self.limits.nice,
+            container: self.container.as_ref().map(ContainerConfig::container),
+            // Containers mount the scratch root; keep the rest of the
+            // host's temporary directory out of them.
+            scratch_root: match &self.container {
+                Some(_) => std::env::temp_dir().join("rustc-fuzz"),
+                None => std::env::temp_dir(),
+            },
+            ..Rustc::new(&self.toolchain.rustc)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn defaults_round_trip_through_toml() {
+        let campaign = Campaign::default();
+        let text = campaign.to_toml().expect("serializes");
+        let back: Campaign = toml::from_str(&text).expect("parses");
+        assert_eq!(back, campaign);
+        assert!(campaign.problems(&["rustc"]).is_empty());
+    }
+
+    #[test]
+    fn missing_keys_default_and_unknown_ones_do_not_parse() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-config-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        let path = dir.join("campaign.toml");
+        fs::write(
+            &path,
+            "oracles = [\"miri\"]\n\n[limits]\ntimeout_secs = 30\n",
+        )
+        .expect("writes the config");
+        let campaign = Campaign::load(&path).expect("loads");
+        assert_eq!(campaign.oracles, ["miri"]);
+        assert_eq!(campaign.limits.timeout_secs, 30);
+        assert_eq!(campaign.limits.memory_mb, Limits::default().memory_mb);
+        assert_eq!(campaign.toolchain, Toolchain::default());
+
+        fs::write(&path, "[limits]\ntimeout = 30\n").expect("writes the config");
+        let error = Campaign::load(&path).expect_err("a misspelt key");
+        assert!(
+            format!("{error:#}").contains("unknown field `timeout`"),
+            "{error:#}"
+        );
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+
+    #[test]
+    fn problems_name_their_key() {
+        let campaign = Campaign {
+            oracles: vec!["rustc".to_owned(), "nope".to_owned()],
+            flags: vec![vec!["-O".to_owned(), "O".to_owned()]],
+            toolchain: Toolchain {
+                rustc: PathBuf::from("/nonexistent/rustc"),
+                phase: "lunch".to_owned(),
+                ..Toolchain::default()
+            },
+            generator: Generator {
+                level: "0.1".to_owned(),
+                weights: Some(PathBuf::from("/nonexistent/weights.json")),
+                ..Generator::default()
+            },
+            limits: Limits {
+                timeout_secs: 0,
+                ..Limits::default()
+            },
+            corpus: Paths {
+                seeds: vec![PathBuf::from("/nonexistent/corpus")],
+                ..Paths::default()
+            },
+            ..Campaign::default()
+        };
+        let problems = campaign.problems(&["rustc", "miri"]);
+        let keys: Vec<&str> = problems
+            .iter()
+            .map(|problem| problem.split(':').next().unwrap_or_default())
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "oracles",
+                "flags[0]",
+                "toolchain.phase",
+                "toolchain.rustc",
+                "generator.level",
+                "generator.weights",
+                "limits.timeout_secs",
+                "corpus.seeds"
+            ],
+            "{problems:#?}"
+        );
+        assert!(
+            problems[0].ends_with("expected one of rustc, miri"),
+            "{}",
+            problems[0]
+        );
+        assert_eq!(problems[1], "flags[0]: \"O\" is not a flag");
+    }
+
+    #[test]
+    fn limits_apply_to_the_driver() {
+        let campaign = Campaign {
+            limits: Limits {
+                timeout_secs: 3,
+                memory_mb: 2,
+                output_bytes: 100,
+                ..Limits::default()
+            },
+            ..Campaign::default()
+        };
+        let rustc = campaign.rustc();
+        assert_eq!(rustc.timeout, Duration::from_secs(3));
+        assert_eq!(rustc.memory, Some(MemoryLimit::AddressSpace(2 << 20)));
+        assert_eq!(rustc.output_limit, 100);
+        assert_eq!(rustc.path, PathBuf::from("rustc"));
+        let unlimited = Campaign {
+            limits: Limits {
+                memory_mb: 0,
+                ..Limits::default()
+            },
+            ..Campaign::default()
+        };
+        assert_eq!(unlimited.rustc().memory, None);
+    }
+
+    #[test]
+    fn containers_need_a_pinned_image() {
+        let problems = |image: &str| {
+            Campaign {
+                container: Some(ContainerConfig {
+                    image: image.to_owned(),
+                    ..ContainerConfig::default()
+                }),
+                ..Campaign::default()
+            }
+            .problems(&["rustc"])
+        };
+        assert_eq!(problems(""), ["container.image: no image given"]);
+        assert!(problems("rust:latest")[0].contains("is not pinned"));
+        let pinned = "docker.io/rustlang/rust@sha256:0";
+        assert!(problems(pinned).is_empty());
+        let campaign = Campaign {
+            container: Some(ContainerConfig {
+                image: pinned.to_owned(),
+                ..ContainerConfig::default()
+            }),
+            ..Campaign::default()
+        };
+        let rustc = campaign.rustc();
+        assert_eq!(rustc.container.map(|c| c.image).as_deref(), Some(pinned));
+        assert_eq!(rustc.scratch_root, std::env::temp_dir().join("rustc-fuzz"));
+    }
+
+    #[test]
+    fn toolchain_channels_are_specs() {
+        let problems = |toolchain: Toolchain| {
+            Campaign {
+                toolchain,
+                ..Campaign::default()
+            }
+            .problems(&["rustc"])
+        };
+        let channel = |channel: &str| Toolchain {
+            channel: Some(channel.to_owned()),
+            ..Toolchain::default()
+        };
+        assert!(problems(channel("nightly-2024-06-01")).is_empty());
+        assert!(problems(channel("nightly-2024-13-01"))[0].starts_with("toolchain.channel: bad"));
+        assert_eq!(
+            problems(Toolchain {
+                rustc: PathBuf::from("rustc-nightly"),
+                ..channel("nightly")
+            }),
+            ["toolchain.channel: give either it or toolchain.rustc, not both"]
+        );
+        let bad = Toolchain {
+            source: "apt".to_owned(),
+            refresh_hours: 0,
+            ..Toolchain::default()
+        };
+        let keys: Vec<String> = problems(bad.clone())
+            .iter()
+            .map(|problem| problem.split(':').next().unwrap_or_default().to_owned())
+            .collect();
+        assert_eq!(keys, ["toolchain.source", "toolchain.refresh_hours"]);
+        assert!(bad.toolchains().is_err());
+        let toolchains = Toolchain {
+            source: "dist".to_owned(),
+            refresh_hours: 2,
+            ..Toolchain::default()
+        }
+        .toolchains()
+        .expect("valid settings");
+        assert_eq!(toolchains.source, Source::Dist);
+        assert_eq!(toolchains.refresh, Duration::from_secs(2 * 3600));
+    }
+
+    #[test]
+    fn notify_settings_make_a_webhook() {
+        let bad = NotifyConfig {
+            url: "hooks.example.com".to_owned(),
+            format: "xml".to_owned(),
+            min_severity: Some("awful".to_owned()),
+            burst: 0,
+            ..NotifyConfig::default()
+        };
+        let problems = Campaign {
+            notify: Some(bad.clone()),
+            ..Campaign::default()
+        }
+        .problems(&["rustc"]);
+        let keys: Vec<&str> = problems
+            .iter()
+            .map(|problem| problem.split(':').next().unwrap_or_default())
+            .collect();
+        assert_eq!(
+            keys,
+            [
+                "notify.url",
+                "notify.format",
+                "notify.min_severity",
+                "notify.burst"
+            ]
+        );
+        assert!(bad.webhook().is_err());
+        let webhook = NotifyConfig {
+            url: "https://hooks.example.com/x".to_owned(),
+            format: "json".to_owned(),
+            min_severity: Some("ice".to_owned()),
+            window_secs: 60,
+            ..NotifyConfig::default()
+        }
+        .webhook()
+        .expect("valid settings");
+        assert_eq!(webhook.format, Format::Json);
+        assert_eq!(webhook.min_severity, Some(Severity::Ice));
+        assert_eq!(webhook.window, Duration::from_secs(60));
+        assert_eq!(webhook.url, "https://hooks.example.com/x");
+    }
+
+    #[test]
+    fn budgets_must_allow_something() {
+        let campaign = Campaign {
+            budget: Budget {
+                cpu_minutes: Some(0),
+                wall_minutes: Some(60),
+                disk_mb: Some(0),
+            },
+            ..Campaign::default()
+        };
+        assert_eq!(
+            campaign.problems(&["rustc"]),
+            [
+                "budget.cpu_minutes: must be at least 1",
+                "budget.disk_mb: must be at least 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn plugins_must_exist() {
+        let campaign = Campaign {
+            plugins: vec![PathBuf::from("/nonexistent/libplugin.so")],
+            ..Campaign::default()
+        };
+        assert_eq!(
+            campaign.problems(&["rustc"]),
+            ["plugins: /nonexistent/libplugin.so does not exist"]
+        );
+    }
+
+    #[test]
+    fn scheduler_policies_are_named() {
+        let campaign = Campaign {
+            scheduler: SchedulerConfig {
+                policy: "lifo".to_owned(),
+            },
+            ..Campaign::default()
+        };
+        let problems = campaign.problems(&["rustc"]);
+        assert_eq!(problems.len(), 1, "{problems:#?}");
+        assert!(
+            problems[0].starts_with("scheduler.policy: "),
+            "{problems:#?}"
+        );
+        assert!(problems[0].contains("lifo"), "{problems:#?}");
+        assert!(Campaign::default().problems(&["rustc"]).is_empty());
+    }
+}
@@ -0,0 +1,262 @@
+//! The campaign dashboard.
+//!
+//! [`render`] turns a coordinator's [`Status`] into a single HTML page:
+//! throughput, corpus size, findings per severity, distinct buckets over
+//! time, the corpus's feature coverage and links to every reproducer
+//! kept. The page has no scripts or external assets and reloads itself,
+//! so a browser left open on a long campaign stays current.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::cluster::Status;
+use crate::coverage::{Feature, Matrix};
+
+/// Seconds between reloads.
+const REFRESH: u32 = 30;
+
+/// Size of the buckets-over-time chart, in pixels.
+const CHART: (f64, f64) = (640.0, 160.0);
+
+/// The dashboard for a campaign running `harness`, whose corpus covers
+/// `coverage` and whose kept reproducers are at `reproducers`, paths
+/// `<bucket>/<hash>.rs` under the findings directory.
+pub fn render(harness: &str, status: &Status, coverage: &Matrix, reproducers: &[String]) -> String {
+    let mut html = String::new();
+    let _ = write!(
+        html,
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\">\
+         <meta http-equiv=\"refresh\" content=\"{REFRESH}\">\
+         <title>rustc-fuzz: {harness}</title>\n<style>{STYLE}</style></head><body>\n\
+         <h1>rustc-fuzz: {harness}</h1>\n",
+        harness = escape(harness)
+    );
+    summary(&mut html, status);
+    history(&mut html, status);
+    findings(&mut html, status, reproducers);
+    features(&mut html, coverage);
+    html.push_str("</body></html>\n");
+    html
+}
+
+const STYLE: &str = "body{font-family:sans-serif;margin:2em;max-width:60em}\
+    table{border-collapse:collapse}td,th{padding:.2em .8em;text-align:left}\
+    td.n{text-align:right;font-variant-numeric:tabular-nums}\
+    tr:nth-child(even){background:#f4f4f4}\
+    .bar{background:#4a7;height:.8em;display:inline-block}\
+    .missing{color:#b33}svg{border:1px solid #ccc}";
+
+fn summary(html: &mut String, status: &Status) {
+    let rate = if status.uptime_secs == 0 {
+        0.0
+    } else {
+        status.reported as f64 / status.uptime_secs as f64
+    };
+    let rows = [
+        ("uptime", duration(status.uptime_secs)),
+        ("execs/sec", format!("{rate:.2}")),
+        ("jobs reported", status.reported.to_string()),
+        ("jobs pending", status.pending.to_string()),
+        ("corpus seeds", status.corpus.to_string()),
+        ("findings", status.findings.to_string()),
+        ("kept", status.kept.to_string()),
+        ("known issues", status.known.to_string()),
+        ("distinct buckets", status.buckets.len().to_string()),
+    ];
+    html.push_str("<h2>Progress</h2>\n<table>\n");
+    for (name, value) in rows {
+        let _ = writeln!(html, "<tr><th>{name}</th><td class=\"n\">{value}</td></tr>");
+    }
+    for (severity, count) in &status.severities {
+        let _ = writeln!(
+            html,
+            "<tr><th>{severity}</th><td class=\"n\">{count}</td></tr>"
+        );
+    }
+    html.push_str("</table>\n");
+}
+
+/// A step chart of distinct buckets against time.
+fn history(html: &mut String, status: &Status) {
+    html.push_str("<h2>Distinct buckets over time</h2>\n");
+    let (width, height) = CHART;
+    let end = status
+        .history
+        .last()
+        .map_or(0, |&(secs, _)| secs)
+        .max(status.uptime_secs)
+        .max(1) as f64;
+    let top = status.buckets.len().max(1) as f64;
+    let x = |secs: u64| secs as f64 / end * width;
+    let y = |buckets: usize| height - buckets as f64 / top * height;
+    let mut points = format!("0,{height}");
+    let mut last = 0;
+    for &(secs, buckets) in &status.history {
+        let _ = write!(
+            points,
+            " {:.1},{:.1} {:.1},{:.1}",
+            x(secs),
+            y(last),
+            x(secs),
+            y(buckets)
+        );
+        last = buckets;
+    }
+    let _ = write!(points, " {width},{:.1}", y(last));
+    let _ = writeln!(
+        html,
+        "<svg width=\"{width}\" height=\"{height}\" viewBox=\"0 0 {width} {height}\">\
+         <polyline fill=\"none\" stroke=\"#4a7\" stroke-width=\"2\" points=\"{points}\"/></svg>\
+         <p>{} buckets after {}</p>",
+        status.buckets.len(),
+        duration(end as u64)
+    );
+}
+
+/// Buckets, most findings first, with links to their reproducers.
+fn findings(html: &mut String, status: &Status, reproducers: &[String]) {
+    let mut kept: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for path in reproducers {
+        if let Some((bucket, _)) = path.split_once('/') {
+            kept.entry(bucket).or_default().push(path);
+        }
+    }
+    let mut buckets: Vec<(&String, &u64)> = status.buckets.iter().collect();
+    buckets.sort_by(|a, b| b.1.cmp(a.1).then_with(|| a.0.cmp(b.0)));
+    html.push_str("<h2>Buckets</h2>\n<table>\n<tr><th>findings</th><th>bucket</th><th>reproducers</th></tr>\n");
+    for (bucket, count) in buckets {
+        let mut links = String::new();
+        let mut paths = kept.remove(bucket.as_str()).unwrap_or_default();
+        paths.sort_unstable();
+        for (i, path) in paths.into_iter().enumerate() {
+            let _ = write!(
+                links,
+                " <a href=\"/findings/{}\">{}</a>",
+                escape(path),
+                i + 1
+            );
+        }
+        let _ = writeln!(
+            html,
+            "<tr><td class=\"n\">{count}</td><td>{}</td><td>{links}</td></tr>",
+            escape(bucket)
+        );
+    }
+    html.push_str("</table>\n");
+}
+
+/// The share of parsed seeds using each feature.
+fn features(html: &mut String, coverage: &Matrix) {
+    let parsed = coverage.seeds - coverage.unparsed;
+    let _ = writeln!(
+        html,
+        "<h2>Feature coverage</h2>\n<p>{parsed} seeds parsed, {} did not</p>\n<table>",
+        coverage.unparsed
+    );
+    for feature in Feature::ALL {
+        let count = coverage.count(feature);
+        let share = if parsed == 0 {
+            0.0
+        } else {
+            100.0 * count as f64 / parsed as f64
+        };
+        let class = if count == 0 { " class=\"missing\"" } else { "" };
+        let _ = writeln!(
+            html,
+            "<tr{class}><td>{feature}</td><td class=\"n\">{count}</td>\
+             <td class=\"n\">{share:.1}%</td>\
+             <td><span class=\"bar\" style=\"width:{:.1}em\"></span></td></tr>",
+            share / 10.0
+        );
+    }
+    html.push_str("</table>\n");
+}
+
+fn duration(secs: u64) -> String {
+    format!("{}h{:02}m{:02}s", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// `text` with the characters HTML gives meaning to replaced by
+/// entities.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn the_page_links_every_kept_reproducer() {
+        let status = Status {
+            reported: 120,
+            uptime_secs: 3_725,
+            buckets: HashMap::from([("<ice>".to_owned(), 3), ("oom".to_owned(), 5)]),
+            history: vec![(60, 1), (600, 2)],
+            ..Status::default()
+        };
+        let mut coverage = Matrix::new();
+        coverage.add("enum E { A }");
+        let reproducers = [
+            "oom/b.rs".to_owned(),
+            "oom/a.rs".to_owned(),
+            "stray.rs".to_owned(),
+        ];
+        let html = render("rustc & friends", &status, &coverage, &reproducers);
+
+        assert!(
+            html.contains("<title>rustc-fuzz: rustc &amp; friends</title>"),
+            "{html}"
+        );
+        assert!(
+            html.contains("<th>uptime</th><td class=\"n\">1h02m05s</td>"),
+            "{html}"
+        );
+        assert!(
+            html.contains("<th>execs/sec</th><td class=\"n\">0.03</td>"),
+            "{html}"
+        );
+        let oom = html.find("<td>oom</td>").expect("the oom bucket");
+        let ice = html
+            .find("<td>&lt;ice&gt;</td>")
+            .expect("the escaped ICE bucket");
+        assert!(oom < ice, "most findings first");
+        assert!(
+            html.contains(
+                "<a href=\"/findings/oom/a.rs\">1</a> <a href=\"/findings/oom/b.rs\">2</a>"
+            ),
+            "{html}"
+        );
+        assert!(!html.contains("stray.rs"), "{html}");
+        assert!(html.contains("<p>2 buckets after 1h02m05s</p>"), "{html}");
+        assert!(
+            html.contains("<tr class=\"missing\"><td>struct</td>"),
+            "{html}"
+        );
+        assert!(
+            html.contains("<tr><td>enum</td><td class=\"n\">1</td>"),
+            "{html}"
+        );
+        assert!(html.ends_with("</body></html>\n"));
+    }
+
+    #[test]
+    fn markup_is_escaped() {
+        assert_eq!(
+            escape(r#"<a href="x">'&'</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;&#39;&amp;&#39;&lt;/a&gt;"
+        );
+    }
+}
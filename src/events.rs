@@ -0,0 +1,260 @@
+//! The campaign event log.
+//!
+//! With `--events FILE`, every significant thing a command does is
+//! appended to `FILE` as one JSON object per line: each harness execution,
+//! each seed added to a corpus, each new bucket of findings, each finished
+//! reduction, each bisection, each known crash found fixed and the totals
+//! of each `fuzz` run. Every line is an [`Event`], with a
+//! timestamp, the [`SCHEMA`] version and an `event` field naming its
+//! [`EventKind`], so tooling outside the fuzzer can follow a campaign
+//! without parsing its human-readable output. Fields are only ever added
+//! within a schema version, and optional ones are left out when empty.
+//!
+//! The log is process-wide: [`init`] opens it once and [`emit`] does
+//! nothing until it has, so code that emits events need not know whether
+//! anyone listens. [`init_db`] likewise sends every event to a results
+//! [`Database`] as well, or instead, and [`init_notifier`] to a
+//! [`Notifier`].
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::notify::Notifier;
+use crate::results::Database;
+use crate::triage::bisect::Bisection;
+use crate::triage::Severity;
+
+/// The version of the event schema. Bumped when a field is removed or
+/// changes meaning.
+pub const SCHEMA: u32 = 1;
+
+static LOG: OnceLock<Mutex<File>> = OnceLock::new();
+
+static DB: OnceLock<Mutex<Database>> = OnceLock::new();
+
+static NOTIFIER: OnceLock<Mutex<Notifier>> = OnceLock::new();
+
+/// One line of the log.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Event {
+    /// Milliseconds since the Unix epoch.
+    pub time_ms: u64,
+    /// [`SCHEMA`] at the time of writing.
+    pub schema: u32,
+    #[serde(flatten)]
+    pub kind: EventKind,
+}
+
+/// What happened.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum EventKind {
+    /// A seed ran through a harness.
+    Exec {
+        harness: String,
+        /// The content hash of the seed.
+        input: String,
+        /// How long the harness took, if it was timed here.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        elapsed_ms: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        severity: Option<Severity>,
+        /// The harness's summary of what it found, if anything.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        finding: Option<String>,
+    },
+    /// A seed was added to a corpus.
+    NewSeed {
+        path: String,
+        hash: String,
+        /// The seed it was mutated from.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        parent: Option<String>,
+    },
+    /// A finding fell in a bucket no earlier one did.
+    NewBucket {
+        bucket: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        severity: Option<Severity>,
+        /// Where its first reproducer was written.
+        reproducer: String,
+    },
+    /// A crasher was reduced.
+    Reduced {
+        path: String,
+        /// Where the reduced crasher was written.
+        output: String,
+        /// The id of the signature it was reduced under.
+        signature: String,
+        before_bytes: usize,
+        after_bytes: usize,
+        tests: usize,
+    },
+    /// A crash was bisected.
+    Bisected {
+        finding: String,
+        bisection: Bisection,
+    },
+    /// A regression seed no longer crashes.
+    Fixed {
+        path: String,
+        /// The id of the signature it used to crash with.
+        signature: String,
+        /// What it does instead.
+        outcome: String,
+    },
+    /// A `fuzz` run finished or spent its budget. A resumed run reports
+    /// again, with its totals so far.
+    FuzzRun {
+        /// Its findings directory, which names the run.
+        findings: String,
+        /// The scheduling policy.
+        policy: String,
+        seed: u64,
+        /// Iterations done.
+        iterations: u64,
+        /// New findings.
+        found: u64,
+        /// Findings in seeds with known issues.
+        known: u64,
+        /// Distinct findings.
+        buckets: u64,
+        /// Mutants added to the corpus.
+        added: u64,
+        wall_ms: u64,
+        cpu_ms: u64,
+    },
+}
+
+/// Opens `path` for appending and sends every later event there.
+/// Only the first call has an effect.
+pub fn init(path: &Path) -> io::Result<()> {
+    let file = OpenOptions::new().create(true).append(true).open(path)?;
+    let _ = LOG.set(Mutex::new(file));
+    Ok(())
+}
+
+/// Opens the results database at `path` and records every later event
+/// there. Only the first call has an effect.
+pub fn init_db(path: &Path) -> io::Result<()> {
+    let db = Database::open(path).map_err(io::Error::other)?;
+    let _ = DB.set(Mutex::new(db));
+    Ok(())
+}
+
+/// Sends every later event worth a message to `notifier`. Only the first
+/// call has an effect.
+pub fn init_notifier(notifier: Notifier) {
+    let _ = NOTIFIER.set(Mutex::new(notifier));
+}
+
+/// Appends `kind` to the log, records it in the database and notifies
+/// of it, if there are any. Each event is written with a single call, so lines from
+/// concurrent writers do not interleave.
+pub fn emit(kind: EventKind) -> io::Result<()> {
+    if LOG.get().is_none() && DB.get().is_none() && NOTIFIER.get().is_none() {
+        return Ok(());
+    }
+    let event = Event {
+        time_ms: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_millis() as u64),
+        schema: SCHEMA,
+        kind,
+    };
+    if let Some(db) = DB.get() {
+        db.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .record(&event)
+            .map_err(io::Error::other)?;
+    }
+    if let Some(notifier) = NOTIFIER.get() {
+        let mut notifier = notifier
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        // A webhook that is down must not stop the campaign.
+        if let Err(e) = notifier.notify(&event) {
+            tracing::warn!("notification failed: {e}");
+        }
+    }
+    let Some(log) = LOG.get() else {
+        return Ok(());
+    };
+    let mut line = serde_json::to_vec(&event)?;
+    line.push(b'\n');
+    let mut file = log.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    file.write_all(&line)
+}
+
+/// Reads a log. Lines that are not events, such as ones from a newer
+/// schema with kinds this build does not know, are skipped.
+pub fn read(path: &Path) -> io::Result<Vec<Event>> {
+    let mut events = Vec::new();
+    for line in BufReader::new(File::open(path)?).lines() {
+        if let Ok(event) = serde_json::from_str(&line?) {
+            events.push(event);
+        }
+    }
+    Ok(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn new_seed(path: &str) -> EventKind {
+        EventKind::NewSeed {
+            path: path.to_owned(),
+            hash: "0123456789abcdef".to_owned(),
+            parent: None,
+        }
+    }
+
+    #[test]
+    fn events_are_flat_json_lines() {
+        let event = Event {
+            time_ms: 1,
+            schema: SCHEMA,
+            kind: new_seed("corpus/a.rs"),
+        };
+        assert_eq!(
+            serde_json::to_string(&event).expect("serializes"),
+            r#"{"time_ms":1,"schema":1,"event":"new_seed","path":"corpus/a.rs","hash":"0123456789abcdef"}"#
+        );
+    }
+
+    #[test]
+    fn the_log_keeps_what_this_build_understands() {
+        let path =
+            std::env::temp_dir().join(format!("rustc-fuzz-events-{}.jsonl", std::process::id()));
+        // Nothing is written before the log is opened.
+        emit(new_seed("before.rs")).expect("does nothing");
+        init(&path).expect("opens the log");
+        emit(new_seed("events-test.rs")).expect("appends");
+        let mut file = OpenOptions::new().append(true).open(&path).expect("opens");
+        writeln!(
+            file,
+            r#"{{"time_ms":2,"schema":2,"event":"from_the_future"}}"#
+        )
+        .expect("appends");
+        writeln!(file, "not json").expect("appends");
+
+        let events = read(&path).expect("reads");
+        let paths: Vec<&str> = events
+            .iter()
+            .filter_map(|event| match &event.kind {
+                EventKind::NewSeed { path, .. } => Some(path.as_str()),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(paths, ["events-test.rs"]);
+        assert!(events.iter().all(|event| event.schema == SCHEMA));
+        fs::remove_file(&path).expect("cleans up");
+    }
+}
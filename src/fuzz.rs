@@ -0,0 +1,505 @@
+//! The local mutation loop.
+//!
+//! [`fuzz`] picks a seed from a corpus as its scheduler says, mutates it,
+//! checks the mutant and repeats, keeping mutants with feature tags no seed
+//! has yet and writing findings beside their sidecars. Every iteration is
+//! journaled and the campaign's state checkpointed in the findings
+//! directory, so [`replay`] can make the same choices again and
+//! `--resume` can pick the campaign up where it stopped.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::field;
+
+use crate::budget::{Budget, Exhausted, Meter};
+use crate::check::Check;
+use crate::checkpoint::Checkpoint;
+use crate::corpus::cmin;
+use crate::corpus::library::Library;
+use crate::corpus::meta::{self, Metadata, Provenance};
+use crate::corpus::{Corpus, Entry};
+use crate::coverage::tagger;
+use crate::events::{self, EventKind};
+use crate::journal::{self, Journal, Origin, Record};
+use crate::mutate::{Engine, Feedback, FragmentSplice, Mutated, Trace};
+use crate::rng::{SplitRng, Stream};
+use crate::scheduler::Scheduler;
+use crate::triage::Severity;
+
+/// The default mutation engine, plus `fragment-splice` over the library
+/// at `library` if there is one.
+pub fn engine(library: Option<&Path>) -> Result<Engine> {
+    let mut engine = Engine::default();
+    if let Some(path) = library {
+        engine.push(Box::new(FragmentSplice {
+            library: Arc::new(
+                Library::load(path)
+                    .with_context(|| format!("reading fragment library {}", path.display()))?,
+            ),
+        }));
+    }
+    Ok(engine)
+}
+
+/// How a [`fuzz`] run ended.
+#[derive(Debug)]
+pub struct Finished {
+    /// The campaign's state when it stopped, with its totals.
+    pub state: Checkpoint,
+    /// The budget that stopped it before its iterations were done, if one
+    /// did.
+    pub exhausted: Option<Exhausted>,
+}
+
+/// What [`replay`] went through, all of it matching the journal.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Replayed {
+    /// The master seed of the run.
+    pub seed: u64,
+    /// The iterations replayed.
+    pub replayed: usize,
+    /// The iterations the journal holds.
+    pub iterations: usize,
+}
+
+/// Mutates seeds from the corpus of `state` as its scheduler picks them,
+/// adding mutants with new feature tags to it and writing findings to its
+/// findings directory, until it has done its iterations or spent `budget`.
+/// `state` is saved there as a checkpoint `every` so often and at the end,
+/// and every iteration is journaled there. Each new finding is passed to
+/// `found` with where it was written as soon as it is.
+pub fn fuzz(
+    engine: &Engine,
+    library: Option<&Path>,
+    mut check: Check,
+    mut state: Checkpoint,
+    every: Option<Duration>,
+    budget: Budget,
+    mut found: impl FnMut(&Path, Severity, &str),
+) -> Result<Finished> {
+    let (corpus, findings) = (state.corpus.clone(), state.findings.clone());
+    let _campaign = tracing::info_span!(
+        "campaign",
+        command = "fuzz",
+        findings = %findings.display(),
+        policy = %state.scheduler.policy(),
+        seed = state.seed,
+    )
+    .entered();
+    fs::create_dir_all(&findings).with_context(|| format!("creating {}", findings.display()))?;
+    let journal_path = findings.join(journal::FILE);
+    let (mut seeds, mut journal) = if state.seeds.is_empty() {
+        let seeds = Corpus::open(&corpus)
+            .entries()
+            .with_context(|| format!("reading corpus {}", corpus.display()))?;
+        anyhow::ensure!(!seeds.is_empty(), "{} has no seeds", corpus.display());
+        for entry in &seeds {
+            state.scheduler.add(schedule_features(&entry.source));
+            state.seeds.push(entry.path.clone());
+        }
+        let start = Record::Start {
+            seed: state.seed,
+            policy: state.scheduler.policy(),
+            seeds: seeds
+                .iter()
+                .map(|entry| Origin {
+                    path: entry.path.clone(),
+                    hash: meta::content_hash(&entry.source),
+                })
+                .collect(),
+            library: library.map(Path::to_owned),
+        };
+        let journal = Journal::create(&findings, &start)
+            .with_context(|| format!("writing {}", journal_path.display()))?;
+        (seeds, journal)
+    } else {
+        engine.restore_operators(&state.operators);
+        let seeds = state
+            .seeds
+            .iter()
+            .map(|path| {
+                let source = fs::read_to_string(path)
+                    .with_context(|| format!("reading {}", path.display()))?;
+                Ok(Entry {
+                    path: path.clone(),
+                    source,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let journal = Journal::open(&findings)
+            .with_context(|| format!("writing {}", journal_path.display()))?;
+        (seeds, journal)
+    };
+    let findings = findings.as_path();
+    let corpus = corpus.as_path();
+    let rng = SplitRng::new(state.seed);
+    let mut meter = Meter::start(budget, state.spent).with_dir(findings);
+    let mut exhausted = None;
+    let mut saved = Instant::now();
+    while state.done < state.iterations {
+        // Saved before the iteration starts, so a resumed run repeats the
+        // one that was cut short.
+        if every.is_some_and(|every| saved.elapsed() >= every) {
+            checkpoint(&mut state, engine, &meter)?;
+            saved = Instant::now();
+        }
+        exhausted = meter.check()?;
+        if exhausted.is_some() {
+            break;
+        }
+        let iteration = state.done;
+        state.done += 1;
+        let span = tracing::debug_span!("seed", iteration, index = field::Empty);
+        let _seed = span.enter();
+        let (index, mutated) = mutant(&mut state.scheduler, &seeds, engine, rng, iteration);
+        span.record("index", index);
+        let Some(mutated) = mutated else {
+            tracing::trace!("no operator changed the seed");
+            journal
+                .append(&Record::Iteration(journal::Iteration {
+                    iteration,
+                    parent: index,
+                    mutant: None,
+                }))
+                .with_context(|| format!("writing {}", journal_path.display()))?;
+            continue;
+        };
+        let start = Instant::now();
+        let finding = check(&mutated.output)?;
+        let elapsed_ns = start.elapsed().as_nanos() as u64;
+        tracing::trace!(operator = mutated.operator, elapsed_ns, "checked a mutant");
+        journal
+            .append(&Record::Iteration(journal::Iteration {
+                iteration,
+                parent: index,
+                mutant: Some(journal::Mutant {
+                    operator: mutated.operator.to_owned(),
+                    hash: meta::content_hash(&mutated.output),
+                    elapsed_ns,
+                    finding: finding.clone(),
+                }),
+            }))
+            .with_context(|| format!("writing {}", journal_path.display()))?;
+        let (tags, new_coverage, new_crash) = learn(
+            &mut state,
+            engine,
+            index,
+            &mutated,
+            Duration::from_nanos(elapsed_ns),
+            finding.as_ref(),
+        );
+        if finding.is_none() && !new_coverage {
+            continue;
+        }
+        let parent = &seeds[index];
+        let inherited = Metadata::load(&parent.path)?.filter(|m| m.matches(&parent.source));
+        let provenance = Provenance::Mutated {
+            parent: parent.path.display().to_string(),
+            parent_hash: meta::content_hash(&parent.source),
+            trace: Trace {
+                steps: vec![mutated.step()],
+            },
+        };
+        let mut metadata = Metadata::new(&mutated.output, provenance);
+        if let Some(inherited) = &inherited {
+            metadata.edition = inherited.edition.clone();
+            metadata.flags = inherited.flags.clone();
+        }
+        let name = format!("{}.rs", meta::content_hash(&mutated.output));
+        if let Some((severity, finding)) = finding {
+            let issues = inherited.map(|m| m.known_issues).unwrap_or_default();
+            if !issues.is_empty() {
+                tracing::debug!(%finding, ?issues, "finding in a seed with known issues");
+                state.known += 1;
+                continue;
+            }
+            state.found += 1;
+            let path = findings.join(name);
+            fs::write(&path, &mutated.output)
+                .with_context(|| format!("writing {}", path.display()))?;
+            metadata.severity = Some(severity);
+            metadata.save(&path)?;
+            tracing::info!(%severity, %finding, path = %path.display(), new_crash, "finding");
+            if new_crash {
+                events::emit(EventKind::NewBucket {
+                    bucket: finding.clone(),
+                    severity: Some(severity),
+                    reproducer: path.display().to_string(),
+                })?;
+            }
+            found(&path, severity, &finding);
+        } else {
+            state.added += 1;
+            let path = corpus.join(name);
+            fs::write(&path, &mutated.output)
+                .with_context(|| format!("writing {}", path.display()))?;
+            metadata.save(&path)?;
+            tracing::debug!(path = %path.display(), "new coverage; seed added");
+            events::emit(EventKind::NewSeed {
+                path: path.display().to_string(),
+                hash: metadata.hash.clone(),
+                parent: Some(parent.path.display().to_string()),
+            })?;
+            state.scheduler.add(tags);
+            state.seeds.push(path.clone());
+            seeds.push(Entry {
+                path,
+                source: mutated.output,
+            });
+        }
+    }
+    // A campaign stopped by its budget can be resumed with a bigger one.
+    if every.is_some() || exhausted.is_some() {
+        checkpoint(&mut state, engine, &meter)?;
+    }
+    let spent = meter.spent();
+    events::emit(EventKind::FuzzRun {
+        findings: findings.display().to_string(),
+        policy: state.scheduler.policy().to_string(),
+        seed: state.seed,
+        iterations: state.done,
+        found: state.found,
+        known: state.known,
+        buckets: state.signatures.len() as u64,
+        added: state.added,
+        wall_ms: spent.wall_ms,
+        cpu_ms: spent.cpu_ms,
+    })?;
+    Ok(Finished { state, exhausted })
+}
+
+/// Picks the seed iteration `iteration` mutates and mutates it, each with
+/// that iteration's stream of `rng`. Returns the seed's index and the
+/// mutant, if an operator changed it.
+fn mutant(
+    scheduler: &mut Scheduler,
+    seeds: &[Entry],
+    engine: &Engine,
+    rng: SplitRng,
+    iteration: u64,
+) -> (usize, Option<Mutated>) {
+    let index = scheduler
+        .select(&mut rng.rng(Stream::Scheduler, iteration))
+        .expect("the corpus is not empty");
+    let mutated = engine.mutate(
+        &seeds[index].source,
+        &mut rng.rng(Stream::Mutator, iteration),
+    );
+    (index, mutated)
+}
+
+/// Tells the scheduler and `engine` how checking `mutated`, a mutant of
+/// seed `index`, went. Returns the mutant's scheduling features, whether
+/// any is new and whether its finding is.
+fn learn(
+    state: &mut Checkpoint,
+    engine: &Engine,
+    index: usize,
+    mutated: &Mutated,
+    elapsed: Duration,
+    finding: Option<&(Severity, String)>,
+) -> (BTreeSet<String>, bool, bool) {
+    let tags = schedule_features(&mutated.output);
+    let new_coverage = state.scheduler.is_new(&tags);
+    state
+        .scheduler
+        .observe(index, elapsed, finding.is_some(), new_coverage);
+    let new_crash = finding.is_some_and(|finding| state.signatures.insert(finding.clone()));
+    engine.reward(
+        mutated.operator,
+        Feedback {
+            new_coverage,
+            new_crash,
+        },
+    );
+    (tags, new_coverage, new_crash)
+}
+
+/// Makes the choices of the run journaled in findings directory `dir`
+/// again, checking each against the journal, with the findings and check
+/// times it records instead of a harness. Stops after `upto` iterations;
+/// writes the mutants to `inputs`, as `<iteration>.rs`.
+pub fn replay(
+    dir: &Path,
+    library: Option<&Path>,
+    upto: Option<u64>,
+    inputs: Option<&Path>,
+) -> Result<Replayed> {
+    let path = dir.join(journal::FILE);
+    let (start, iterations) =
+        journal::read(dir).with_context(|| format!("reading {}", path.display()))?;
+    let Record::Start {
+        seed,
+        policy,
+        seeds: origins,
+        library: recorded,
+    } = start
+    else {
+        unreachable!("journal::read returns a start record first");
+    };
+    let engine = engine(library.or(recorded.as_deref()))?;
+    let mut state = Checkpoint::new(Path::new(""), dir, policy, seed, 0);
+    let mut seeds = Vec::new();
+    for origin in origins {
+        let source = fs::read_to_string(&origin.path)
+            .with_context(|| format!("reading {}", origin.path.display()))?;
+        anyhow::ensure!(
+            meta::content_hash(&source) == origin.hash,
+            "{} has changed since the run started",
+            origin.path.display()
+        );
+        state.scheduler.add(schedule_features(&source));
+        seeds.push(Entry {
+            path: origin.path,
+            source,
+        });
+    }
+    if let Some(inputs) = inputs {
+        fs::create_dir_all(inputs).with_context(|| format!("creating {}", inputs.display()))?;
+    }
+    let rng = SplitRng::new(seed);
+    let count = upto.map_or(iterations.len(), |upto| iterations.len().min(upto as usize));
+    for recorded in &iterations[..count] {
+        let iteration = recorded.iteration;
+        let (index, mutated) = mutant(&mut state.scheduler, &seeds, &engine, rng, iteration);
+        let replayed = mutated
+            .as_ref()
+            .map(|m| (m.operator.to_owned(), meta::content_hash(&m.output)));
+        let journaled = recorded
+            .mutant
+            .as_ref()
+            .map(|m| (m.operator.clone(), m.hash.clone()));
+        if index != recorded.parent || replayed != journaled {
+            let describe = |parent: usize, mutant: Option<(String, String)>| match mutant {
+                Some((operator, hash)) => {
+                    format!("mutated seed {parent} with {operator} into {hash}")
+                }
+                None => format!("left seed {parent} unchanged"),
+            };
+            anyhow::bail!(
+                "iteration {iteration} diverged: the run {}, the replay {}",
+                describe(recorded.parent, journaled),
+                describe(index, replayed)
+            );
+        }
+        let (Some(mutated), Some(journaled)) = (mutated, &recorded.mutant) else {
+            continue;
+        };
+        if let Some(inputs) = inputs {
+            let path = inputs.join(format!("{iteration:08}.rs"));
+            fs::write(&path, &mutated.output)
+                .with_context(|| format!("writing {}", path.display()))?;
+        }
+        let finding = journaled.finding.as_ref();
+        let (tags, new_coverage, _) = learn(
+            &mut state,
+            &engine,
+            index,
+            &mutated,
+            Duration::from_nanos(journaled.elapsed_ns),
+            finding,
+        );
+        if finding.is_none() && new_coverage {
+            state.scheduler.add(tags);
+            seeds.push(Entry {
+                path: PathBuf::from(format!("{}.rs", journaled.hash)),
+                source: mutated.output,
+            });
+        }
+    }
+    Ok(Replayed {
+        seed,
+        replayed: count,
+        iterations: iterations.len(),
+    })
+}
+
+/// Saves `state` with what `engine` has learnt and what `meter` says has
+/// been spent.
+fn checkpoint(state: &mut Checkpoint, engine: &Engine, meter: &Meter) -> Result<()> {
+    tracing::debug!(done = state.done, "saving checkpoint");
+    state.spent = meter.spent();
+    state.operators = engine.operator_state();
+    state.save().with_context(|| {
+        format!(
+            "writing {}",
+            state.findings.join(Checkpoint::FILE).display()
+        )
+    })
+}
+
+/// What the scheduler tells seeds apart by: their token-level feature
+/// tags and their language-feature tags.
+fn schedule_features(source: &str) -> BTreeSet<String> {
+    let mut features = cmin::feature_tags(source);
+    features.extend(
+        tagger::tags(source)
+            .into_iter()
+            .map(|tag| format!("tag {tag}")),
+    );
+    features
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scheduler::Policy;
+
+    #[test]
+    fn runs_replay_from_their_journal() {
+        let root = std::env::temp_dir().join(format!("rustc-fuzz-fuzz-{}", std::process::id()));
+        let (corpus, findings) = (root.join("corpus"), root.join("findings"));
+        fs::create_dir_all(&corpus).unwrap();
+        fs::write(
+            corpus.join("a.rs"),
+            "fn main() {\n    let x: i32 = 1 + 2;\n    println!(\"{}\", x);\n}\n",
+        )
+        .unwrap();
+        fs::write(
+            corpus.join("b.rs"),
+            "struct S {\n    a: u8,\n}\n\nfn f(s: &S) -> u8 {\n    s.a * 2\n}\n",
+        )
+        .unwrap();
+        let state = Checkpoint::new(&corpus, &findings, Policy::default(), 7, 40);
+        // Odd-length mutants are findings, so both paths are taken.
+        let check: Check = Box::new(|source| {
+            Ok((source.len() % 2 == 1).then(|| (Severity::Ice, format!("{} bytes", source.len()))))
+        });
+        let mut reported = Vec::new();
+        let finished = fuzz(
+            &Engine::default(),
+            None,
+            check,
+            state,
+            None,
+            Budget::default(),
+            |path, _, _| reported.push(path.to_owned()),
+        )
+        .unwrap();
+        assert_eq!(finished.state.done, 40);
+        assert!(finished.exhausted.is_none());
+        assert!(!reported.is_empty());
+        assert_eq!(reported.len() as u64, finished.state.found);
+        for path in &reported {
+            let source = fs::read_to_string(path).unwrap();
+            let metadata = Metadata::load(path).unwrap().unwrap();
+            assert!(metadata.matches(&source));
+            assert_eq!(metadata.severity, Some(Severity::Ice));
+        }
+
+        let replayed = replay(&findings, None, None, None).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            replayed,
+            Replayed {
+                seed: 7,
+                replayed: 40,
+                iterations: 40,
+            }
+        );
+    }
+}
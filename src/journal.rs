@@ -0,0 +1,229 @@
+//! Campaign journals.
+//!
+//! `fuzz` appends a [`Record`] to [`FILE`] in its findings directory for
+//! every iteration: the seed it mutated, the operator, a hash of the
+//! mutant, and what checking the mutant found and how long it took. With
+//! the master seed, that is everything the run's choices depended on, so
+//! `fuzz --replay DIR` can make them all again without a compiler and stop
+//! at the first iteration that comes out different: a generator or
+//! mutator that is not deterministic.
+//!
+//! A resumed run repeats the iterations after its checkpoint, and appends
+//! them again; readers keep the later copy.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::scheduler::Policy;
+use crate::triage::Severity;
+
+/// The journal's file name in a findings directory.
+pub const FILE: &str = "journal.jsonl";
+
+/// One line of a journal.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "record", rename_all = "snake_case")]
+pub enum Record {
+    /// A run starts. Always the first line.
+    Start {
+        /// The master seed.
+        seed: u64,
+        policy: Policy,
+        /// The corpus the run started from, in scheduler order.
+        seeds: Vec<Origin>,
+        library: Option<PathBuf>,
+    },
+    Iteration(Iteration),
+}
+
+/// A seed a run started from.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Origin {
+    pub path: PathBuf,
+    /// Its content hash, to tell whether it has changed since.
+    pub hash: String,
+}
+
+/// What one iteration did.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Iteration {
+    /// Counted from 0.
+    pub iteration: u64,
+    /// The scheduler index of the seed mutated.
+    pub parent: usize,
+    /// `None` if no operator changed the seed.
+    pub mutant: Option<Mutant>,
+}
+
+/// A mutant and what checking it found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Mutant {
+    pub operator: String,
+    /// Its content hash.
+    pub hash: String,
+    /// How long checking it took, which the `cost` policy schedules by.
+    pub elapsed_ns: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finding: Option<(Severity, String)>,
+}
+
+/// A journal being written.
+#[derive(Debug)]
+pub struct Journal {
+    file: File,
+}
+
+impl Journal {
+    /// Starts the journal in findings directory `dir` over with `start`.
+    pub fn create(dir: &Path, start: &Record) -> io::Result<Self> {
+        let mut journal = Journal {
+            file: File::create(dir.join(FILE))?,
+        };
+        journal.append(start)?;
+        Ok(journal)
+    }
+
+    /// Opens the journal in findings directory `dir` to carry on with.
+    pub fn open(dir: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(dir.join(FILE))?;
+        Ok(Journal { file })
+    }
+
+    /// Appends `record` with a single write, so a kill leaves at most a
+    /// partial last line.
+    pub fn append(&mut self, record: &Record) -> io::Result<()> {
+        let mut line = serde_json::to_vec(record)?;
+        line.push(b'\n');
+        self.file.write_all(&line)
+    }
+}
+
+/// Reads the journal in findings directory `dir`: its start record and
+/// its iterations, in order. A partial last line is skipped.
+pub fn read(dir: &Path) -> io::Result<(Record, Vec<Iteration>)> {
+    let path = dir.join(FILE);
+    let mut lines = BufReader::new(File::open(&path)?).lines().peekable();
+    let mut start = None;
+    let mut iterations: Vec<Iteration> = Vec::new();
+    while let Some(line) = lines.next() {
+        let line = line?;
+        let record = match serde_json::from_str(&line) {
+            Ok(record) => record,
+            Err(_) if lines.peek().is_none() => break,
+            Err(e) => return Err(e.into()),
+        };
+        match record {
+            Record::Start { .. } if start.is_none() => start = Some(record),
+            Record::Start { .. } => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{} has more than one start record", path.display()),
+                ))
+            }
+            Record::Iteration(iteration) => {
+                // Repeated after a resume: the later copy is what the
+                // resumed run did.
+                let keep = iterations.partition_point(|i| i.iteration < iteration.iteration);
+                iterations.truncate(keep);
+                if iterations.len() as u64 != iteration.iteration {
+                    return Err(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!(
+                            "{} skips from iteration {} to {}",
+                            path.display(),
+                            iterations.len(),
+                            iteration.iteration
+                        ),
+                    ));
+                }
+                iterations.push(iteration);
+            }
+        }
+    }
+    let start = start.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} has no start record", path.display()),
+        )
+    })?;
+    Ok((start, iterations))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn iteration(iteration: u64, operator: &str) -> Record {
+        Record::Iteration(Iteration {
+            iteration,
+            parent: 0,
+            mutant: Some(Mutant {
+                operator: operator.to_owned(),
+                hash: format!("{iteration:016x}"),
+                elapsed_ns: 1000,
+                finding: None,
+            }),
+        })
+    }
+
+    #[test]
+    fn resumed_iterations_replace_the_killed_ones() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-journal-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the findings");
+        let start = Record::Start {
+            seed: 7,
+            policy: Policy::default(),
+            seeds: vec![Origin {
+                path: PathBuf::from("corpus/a.rs"),
+                hash: "abc".to_owned(),
+            }],
+            library: None,
+        };
+        let mut journal = Journal::create(&dir, &start).expect("creates");
+        for i in 0..3 {
+            journal.append(&iteration(i, "killed")).expect("appends");
+        }
+        drop(journal);
+        let mut resumed = Journal::open(&dir).expect("opens");
+        resumed.append(&iteration(1, "resumed")).expect("appends");
+        resumed.append(&iteration(2, "resumed")).expect("appends");
+        drop(resumed);
+        // Killed halfway through a line.
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(dir.join(FILE))
+            .expect("opens");
+        file.write_all(br#"{"record":"iterat"#).expect("writes");
+
+        let (read_start, iterations) = read(&dir).expect("reads");
+        assert_eq!(read_start, start);
+        let operators: Vec<&str> = iterations
+            .iter()
+            .filter_map(|i| i.mutant.as_ref())
+            .map(|mutant| mutant.operator.as_str())
+            .collect();
+        assert_eq!(operators, ["killed", "resumed", "resumed"]);
+
+        let mut journal = Journal::create(&dir, &start).expect("creates");
+        journal.append(&iteration(1, "gap")).expect("appends");
+        let error = read(&dir).expect_err("iteration 0 is missing");
+        assert!(
+            error.to_string().contains("skips from iteration 0 to 1"),
+            "{error}"
+        );
+        let mut journal = Journal::create(&dir, &start).expect("creates");
+        journal.append(&start).expect("appends");
+        assert_eq!(
+            read(&dir).expect_err("two starts").kind(),
+            io::ErrorKind::InvalidData
+        );
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,31 @@
+//! Structured fuzzing for the Rust compiler: campaigns.
+//!
+//! Generation, mutation, harnesses, oracles, corpora and triage live in
+//! [`fuzzing_core`], re-exported here under their usual paths. This crate
+//! adds what running a campaign takes: harness setup, the mutation loop,
+//! scheduling, checkpoints and journals, budgets, distributed workers,
+//! event logs, diagnostic logging, the results database and
+//! notifications, and the `rustc-fuzz` command line.
+
+pub use fuzzing_core::{
+    corpus, coverage, dict, export_plugin, flags, gen, harness, lex, libfuzzer, mutate, oracle,
+    plugin, rng, seed, target, toolchains, triage,
+};
+
+pub mod budget;
+pub mod check;
+pub mod checkpoint;
+pub mod cluster;
+pub mod config;
+pub mod dashboard;
+pub mod events;
+pub mod fuzz;
+pub mod journal;
+#[cfg(feature = "libafl")]
+pub mod libafl;
+pub mod logging;
+pub mod metrics;
+pub mod notify;
+pub mod pipeline;
+pub mod results;
+pub mod scheduler;
@@ -0,0 +1,367 @@
+//! LibAFL adapters.
+//!
+//! These plug the rest of the crate into LibAFL's fuzzing loop:
+//! [`EngineMutator`] exposes the [`mutate`](crate::mutate) engine as a
+//! LibAFL mutator and feeds new coverage and solutions back to its bandit,
+//! [`ProgramGenerator`] seeds an empty corpus from [`gen`](crate::gen),
+//! [`RustcExecutor`] runs inputs through [`harness::Rustc`] and leaves the
+//! [`ExecOutcome`] in an [`OutcomeObserver`], and [`CrashOracle`] decides
+//! from that outcome what counts as a solution.
+//!
+//! Coverage is not collected here: an instrumented rustc writes AFL-style
+//! edge counts to the shared memory named by `__AFL_SHM_ID`, which the
+//! fuzzer wraps in an ordinary map observer.
+
+use std::borrow::Cow;
+
+use ::libafl::corpus::{Corpus, CorpusId};
+use ::libafl::executors::{Executor, ExitKind, HasObservers};
+use ::libafl::feedbacks::{Feedback, StateInitializer};
+use ::libafl::generators::Generator;
+use ::libafl::inputs::BytesInput;
+use ::libafl::mutators::{MutationResult, Mutator};
+use ::libafl::observers::Observer;
+use ::libafl::state::{HasExecutions, HasRand, HasSolutions};
+use ::libafl::Error;
+use ::libafl_bolts::rands::Rand;
+use ::libafl_bolts::tuples::{Handle, Handled, MatchName, MatchNameRef, RefIndexable};
+use ::libafl_bolts::Named;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::gen::{ExprGen, Scope};
+use crate::harness::{ExecOutcome, Rustc};
+use crate::mutate::{step_rng, Engine, Feedback as Reward};
+use crate::seed;
+
+/// The structured mutation engine as a LibAFL mutator.
+pub struct EngineMutator {
+    engine: Engine,
+    /// Operator behind the input currently being executed.
+    last: Option<&'static str>,
+    solutions: usize,
+}
+
+impl EngineMutator {
+    pub fn new(engine: Engine) -> Self {
+        EngineMutator {
+            engine,
+            last: None,
+            solutions: 0,
+        }
+    }
+}
+
+impl Named for EngineMutator {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("EngineMutator");
+        &NAME
+    }
+}
+
+impl<S> Mutator<BytesInput, S> for EngineMutator
+where
+    S: HasRand + HasSolutions<BytesInput>,
+{
+    fn mutate(&mut self, state: &mut S, input: &mut BytesInput) -> Result<MutationResult, Error> {
+        let source = seed::decode(input.as_ref()).into_owned();
+        let mut rng = step_rng(state.rand_mut().next());
+        self.solutions = state.solutions().count();
+        let Some(mutated) = self.engine.mutate(&source, &mut rng) else {
+            return Ok(MutationResult::Skipped);
+        };
+        self.last = Some(mutated.operator);
+        *input = BytesInput::new(mutated.output.into_bytes());
+        Ok(MutationResult::Mutated)
+    }
+
+    fn post_exec(&mut self, state: &mut S, new_corpus_id: Option<CorpusId>) -> Result<(), Error> {
+        if let Some(operator) = self.last.take() {
+            let feedback = Reward {
+                new_coverage: new_corpus_id.is_some(),
+                new_crash: state.solutions().count() > self.solutions,
+            };
+            self.engine.reward(operator, feedback);
+        }
+        Ok(())
+    }
+}
+
+/// Generates whole programs: `fn main` around a few random statements.
+pub struct ProgramGenerator {
+    pub gen: ExprGen,
+    pub max_stmts: usize,
+}
+
+impl Default for ProgramGenerator {
+    fn default() -> Self {
+        ProgramGenerator {
+            gen: ExprGen::default(),
+            max_stmts: 8,
+        }
+    }
+}
+
+impl<S: HasRand> Generator<BytesInput, S> for ProgramGenerator {
+    fn generate(&mut self, state: &mut S) -> Result<BytesInput, Error> {
+        let mut rng = step_rng(state.rand_mut().next());
+        let mut scope = Scope::new();
+        let mut program = String::from("fn main() {\n");
+        for _ in 0..rng.gen_range(1..=self.max_stmts.max(1)) {
+            program.push_str("    ");
+            program.push_str(&self.gen.stmt(&mut scope, &mut rng));
+            program.push('\n');
+        }
+        program.push_str("}\n");
+        Ok(BytesInput::new(program.into_bytes()))
+    }
+}
+
+/// Holds the [`ExecOutcome`] of the last execution.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OutcomeObserver {
+    name: Cow<'static, str>,
+    #[serde(skip)]
+    last: Option<ExecOutcome>,
+}
+
+impl OutcomeObserver {
+    pub fn new(name: &'static str) -> Self {
+        OutcomeObserver {
+            name: Cow::Borrowed(name),
+            last: None,
+        }
+    }
+
+    pub fn last(&self) -> Option<&ExecOutcome> {
+        self.last.as_ref()
+    }
+}
+
+impl Named for OutcomeObserver {
+    fn name(&self) -> &Cow<'static, str> {
+        &self.name
+    }
+}
+
+impl<I, S> Observer<I, S> for OutcomeObserver {
+    fn pre_exec(&mut self, _state: &mut S, _input: &I) -> Result<(), Error> {
+        self.last = None;
+        Ok(())
+    }
+}
+
+/// Runs each input through [`Rustc`], passing it on stdin.
+pub struct RustcExecutor<OT> {
+    rustc: Rustc,
+    args: Vec<String>,
+    observers: OT,
+    outcome: Handle<OutcomeObserver>,
+}
+
+impl<OT> RustcExecutor<OT>
+where
+    OT: MatchName,
+{
+    /// `args` should make rustc read its input from stdin (`-`).
+    /// `observers` must contain the observer behind `outcome`.
+    pub fn new(
+        rustc: Rustc,
+        args: Vec<String>,
+        observers: OT,
+        outcome: Handle<OutcomeObserver>,
+    ) -> Self {
+        RustcExecutor {
+            rustc,
+            args,
+            observers,
+            outcome,
+        }
+    }
+}
+
+impl<OT> HasObservers for RustcExecutor<OT> {
+    type Observers = OT;
+
+    fn observers(&self) -> RefIndexable<&OT, OT> {
+        RefIndexable::from(&self.observers)
+    }
+
+    fn observers_mut(&mut self) -> RefIndexable<&mut OT, OT> {
+        RefIndexable::from(&mut self.observers)
+    }
+}
+
+impl<EM, OT, S, Z> Executor<EM, BytesInput, S, Z> for RustcExecutor<OT>
+where
+    OT: MatchName,
+    S: HasExecutions,
+{
+    fn run_target(
+        &mut self,
+        _fuzzer: &mut Z,
+        state: &mut S,
+        _mgr: &mut EM,
+        input: &BytesInput,
+    ) -> Result<ExitKind, Error> {
+        *state.executions_mut() += 1;
+        let outcome = self
+            .rustc
+            .run(&self.args, input.as_ref())
+            .map_err(|e| Error::os_error(e, "running rustc"))?;
+        let exit_kind = match outcome {
+            ExecOutcome::Ice(_) | ExecOutcome::Crash { .. } => ExitKind::Crash,
+            ExecOutcome::Timeout(_) => ExitKind::Timeout,
+            ExecOutcome::OutOfMemory(_) => ExitKind::Oom,
+            ExecOutcome::Success(_) | ExecOutcome::Error { .. } => ExitKind::Ok,
+        };
+        self.observers
+            .get_mut(&self.outcome)
+            .ok_or_else(|| Error::illegal_state("outcome observer missing"))?
+            .last = Some(outcome);
+        Ok(exit_kind)
+    }
+}
+
+/// Objective: the input made rustc crash or report an ICE.
+pub struct CrashOracle {
+    outcome: Handle<OutcomeObserver>,
+}
+
+impl CrashOracle {
+    pub fn new(outcome: &OutcomeObserver) -> Self {
+        CrashOracle {
+            outcome: outcome.handle(),
+        }
+    }
+}
+
+impl Named for CrashOracle {
+    fn name(&self) -> &Cow<'static, str> {
+        static NAME: Cow<'static, str> = Cow::Borrowed("CrashOracle");
+        &NAME
+    }
+}
+
+impl<S> StateInitializer<S> for CrashOracle {}
+
+impl<EM, I, OT, S> Feedback<EM, I, OT, S> for CrashOracle
+where
+    OT: MatchName,
+{
+    fn is_interesting(
+        &mut self,
+        _state: &mut S,
+        _manager: &mut EM,
+        _input: &I,
+        observers: &OT,
+        _exit_kind: &ExitKind,
+    ) -> Result<bool, Error> {
+        let observer = observers
+            .get(&self.outcome)
+            .ok_or_else(|| Error::illegal_state("outcome observer missing"))?;
+        Ok(observer.last().is_some_and(ExecOutcome::is_crash))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::libafl_bolts::rands::StdRand;
+    use ::libafl_bolts::tuples::tuple_list;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    /// Just enough state for the generator and the executor.
+    struct State {
+        rand: StdRand,
+        executions: u64,
+    }
+
+    impl HasRand for State {
+        type Rand = StdRand;
+
+        fn rand(&self) -> &StdRand {
+            &self.rand
+        }
+
+        fn rand_mut(&mut self) -> &mut StdRand {
+            &mut self.rand
+        }
+    }
+
+    impl HasExecutions for State {
+        fn executions(&self) -> &u64 {
+            &self.executions
+        }
+
+        fn executions_mut(&mut self) -> &mut u64 {
+            &mut self.executions
+        }
+    }
+
+    fn state() -> State {
+        State {
+            rand: StdRand::with_seed(7),
+            executions: 0,
+        }
+    }
+
+    #[test]
+    fn generated_programs_wrap_statements_in_main() {
+        let mut state = state();
+        let mut generator = ProgramGenerator::default();
+        for _ in 0..20 {
+            let input = generator.generate(&mut state).expect("generates");
+            let source = seed::decode(input.as_ref()).into_owned();
+            assert!(source.starts_with("fn main() {\n"), "{source}");
+            assert!(source.ends_with("\n}\n"), "{source}");
+            let statements = source.lines().count() - 2;
+            assert!((1..=generator.max_stmts).contains(&statements), "{source}");
+        }
+    }
+
+    #[test]
+    fn ices_are_solutions_and_errors_are_not() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-libafl-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the test directory");
+        // ICEs on any input that mentions `boom`.
+        let rustc = dir.join("rustc");
+        fs::write(
+            &rustc,
+            "#!/bin/sh\nif grep -q boom; then\n  \
+             echo 'error: internal compiler error: boom' >&2\n  exit 101\nfi\n\
+             echo 'error: expected item' >&2\nexit 1\n",
+        )
+        .expect("writes the rustc");
+        fs::set_permissions(&rustc, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+
+        let observer = OutcomeObserver::new("outcome");
+        let mut oracle = CrashOracle::new(&observer);
+        let handle = observer.handle();
+        let mut executor = RustcExecutor::new(
+            Rustc::new(&rustc),
+            vec!["-".to_owned()],
+            tuple_list!(observer),
+            handle,
+        );
+        let mut state = state();
+        for (input, exit_kind, solution) in [
+            ("fn boom() {}", ExitKind::Crash, true),
+            ("fn f(", ExitKind::Ok, false),
+        ] {
+            let input = BytesInput::new(input.as_bytes().to_vec());
+            let exit = executor
+                .run_target(&mut (), &mut state, &mut (), &input)
+                .expect("runs");
+            assert_eq!(exit, exit_kind);
+            let observers = executor.observers();
+            let interesting = oracle
+                .is_interesting(&mut state, &mut (), &input, &*observers, &exit)
+                .expect("judges");
+            assert_eq!(interesting, solution);
+        }
+        assert_eq!(state.executions, 2);
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
@@ -0,0 +1,219 @@
+//! Diagnostic logging.
+//!
+//! Every subsystem says what it is doing through `tracing`. A `fuzz` run
+//! opens a `campaign` span, each of its iterations a `seed` span inside
+//! that, and each harness execution an `exec` span inside that, so a line
+//! logged while rustc hangs says which campaign, iteration and input it
+//! belongs to. [`init`] writes them to stderr as human-readable lines or
+//! as JSON objects, one per line, filtered by `EnvFilter` directives such
+//! as `warn,rustc_fuzz=debug,fuzzing_core::harness=trace`.
+//!
+//! The filter can change while a campaign runs: [`watch`] rereads it from
+//! a file whenever the file changes, so a campaign that seems stuck can be
+//! made to say more without being restarted.
+//!
+//! This log is for debugging the fuzzer. What a campaign finds goes to
+//! the [event log](crate::events) instead.
+
+use std::fmt;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use tracing_subscriber::filter::ParseError;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{reload, EnvFilter, Registry};
+
+/// The filter when none is given: problems only.
+pub const DEFAULT_FILTER: &str = "warn";
+
+/// How often [`watch`] looks at its file.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+static FILTER: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// How log lines are written.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Format {
+    /// One line per event, with the spans it is in, for people.
+    #[default]
+    Human,
+    /// One JSON object per event, with the spans it is in as a list.
+    Json,
+}
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Human => "human",
+            Format::Json => "json",
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownFormat(pub String);
+
+impl fmt::Display for UnknownFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown log format {:?}; expected human or json", self.0)
+    }
+}
+
+impl std::error::Error for UnknownFormat {}
+
+impl FromStr for Format {
+    type Err = UnknownFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Format::Human, Format::Json]
+            .into_iter()
+            .find(|format| format.name() == s)
+            .ok_or_else(|| UnknownFormat(s.to_owned()))
+    }
+}
+
+/// Sends every later log line that `filter` lets through to stderr, in
+/// `format`. Only the first call has an effect.
+pub fn init(filter: &str, format: Format) -> Result<(), ParseError> {
+    let (filter, handle) = reload::Layer::new(EnvFilter::try_new(filter)?);
+    let registry = tracing_subscriber::registry().with(filter);
+    let installed = match format {
+        Format::Human => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .with_writer(io::stderr)
+                    .with_ansi(io::stderr().is_terminal()),
+            )
+            .try_init(),
+        Format::Json => registry
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_current_span(false)
+                    .with_span_list(true)
+                    .with_writer(io::stderr),
+            )
+            .try_init(),
+    };
+    if installed.is_ok() {
+        let _ = FILTER.set(handle);
+    }
+    Ok(())
+}
+
+/// Replaces the filter [`init`] installed. Does nothing before it has
+/// been called.
+pub fn reload(filter: &str) -> Result<(), ParseError> {
+    let filter = EnvFilter::try_new(filter)?;
+    if let Some(handle) = FILTER.get() {
+        // Fails only once the subscriber is gone, and a global one never
+        // is.
+        let _ = handle.reload(filter);
+    }
+    Ok(())
+}
+
+/// Takes the filter from the file at `path` now, and again whenever it
+/// changes for as long as the process runs. A filter that does not parse
+/// is logged and the one before it kept; so, once, is a file that cannot
+/// be read.
+pub fn watch(path: &Path) -> io::Result<()> {
+    let (filter, mut modified) = read_filter(path)?;
+    reload(&filter).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    let path = path.to_owned();
+    let mut unreadable = false;
+    thread::Builder::new()
+        .name("log-filter".into())
+        .spawn(move || loop {
+            thread::sleep(WATCH_INTERVAL);
+            let (filter, now) = match changed(&path, modified) {
+                Ok(Some(changed)) => changed,
+                Ok(None) => continue,
+                Err(e) => {
+                    if !unreadable {
+                        tracing::warn!(path = %path.display(), "keeping the log filter: {e}");
+                    }
+                    unreadable = true;
+                    continue;
+                }
+            };
+            (modified, unreadable) = (now, false);
+            match reload(&filter) {
+                Ok(()) => tracing::info!(%filter, "log filter reloaded"),
+                Err(e) => tracing::warn!(%filter, "keeping the log filter: {e}"),
+            }
+        })?;
+    Ok(())
+}
+
+/// The filter in `path`, with blank lines and `#` comments left out and
+/// the rest joined with commas, and when the file was last modified.
+fn read_filter(path: &Path) -> io::Result<(String, SystemTime)> {
+    let modified = fs::metadata(path)?.modified()?;
+    let text = fs::read_to_string(path)?;
+    let directives: Vec<&str> = text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .collect();
+    Ok((directives.join(","), modified))
+}
+
+/// [`read_filter`], if `path` has been modified since `since`.
+fn changed(path: &Path, since: SystemTime) -> io::Result<Option<(String, SystemTime)>> {
+    if fs::metadata(path)?.modified()? == since {
+        return Ok(None);
+    }
+    read_filter(path).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_parse_by_name() {
+        for format in [Format::Human, Format::Json] {
+            assert_eq!(format.to_string().parse(), Ok(format));
+        }
+        assert_eq!(
+            "yaml".parse::<Format>(),
+            Err(UnknownFormat("yaml".to_owned()))
+        );
+    }
+
+    #[test]
+    fn filter_files_skip_comments_and_blank_lines() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-logging-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        let path = dir.join("filter");
+        fs::write(&path, "# quiet by default\nwarn\n\n  rustc_fuzz=debug  \n").expect("writes");
+        let (filter, modified) = read_filter(&path).expect("reads");
+        assert_eq!(filter, "warn,rustc_fuzz=debug");
+        assert_eq!(changed(&path, modified).expect("reads"), None);
+        let (filter, _) = changed(&path, SystemTime::UNIX_EPOCH)
+            .expect("reads")
+            .expect("has changed");
+        assert_eq!(filter, "warn,rustc_fuzz=debug");
+        fs::remove_dir_all(&dir).expect("cleans up");
+        assert!(changed(&path, modified).is_err());
+    }
+
+    #[test]
+    fn bad_filters_are_refused() {
+        assert!(reload("rustc_fuzz=loudly").is_err());
+        assert!(reload(DEFAULT_FILTER).is_ok());
+    }
+}
@@ -0,0 +1,4100 @@
+use anyhow::{Context, Result};
+use clap::parser::ValueSource;
+use clap::{Args, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rustc_fuzz::budget::Budget;
+use rustc_fuzz::check::{
+    self, checker, harness_names, oracle_checker, oracle_names, Check, Harness,
+};
+use rustc_fuzz::checkpoint::Checkpoint;
+use rustc_fuzz::cluster::{Coordinator, Worker};
+use rustc_fuzz::config::Campaign;
+use rustc_fuzz::corpus::archive::{self, Zstd};
+use rustc_fuzz::corpus::cmin::{self, EdgeMap};
+use rustc_fuzz::corpus::dedup;
+use rustc_fuzz::corpus::export::{self, Format};
+use rustc_fuzz::corpus::generated;
+use rustc_fuzz::corpus::glacier;
+use rustc_fuzz::corpus::harvest::{CrateVersion, Harvester};
+use rustc_fuzz::corpus::library::{self, Library};
+use rustc_fuzz::corpus::meta::{self, Metadata, Provenance};
+use rustc_fuzz::corpus::migrate::{self, Migrator};
+use rustc_fuzz::corpus::stats::Stats;
+use rustc_fuzz::corpus::sync::{Direction, Remote, Syncer};
+use rustc_fuzz::corpus::ui;
+use rustc_fuzz::corpus::verify;
+use rustc_fuzz::corpus::Entry;
+use rustc_fuzz::corpus::{self, Corpus};
+use rustc_fuzz::coverage::{
+    productions, tagger, CorpusCoverage, Matrix, Profiler, Regions, SeedCoverage,
+};
+use rustc_fuzz::dict::Dictionary;
+use rustc_fuzz::events::{self, EventKind};
+use rustc_fuzz::fuzz::{self, engine};
+use rustc_fuzz::gen::{Level, ProgramGen, Weights};
+use rustc_fuzz::harness::pool::{self, PoolOptions};
+use rustc_fuzz::harness::{ExecOutcome, Phase, Rustc};
+use rustc_fuzz::logging;
+use rustc_fuzz::mutate::{Engine, Trace};
+use rustc_fuzz::notify::{Notifier, Webhook};
+use rustc_fuzz::oracle::regression::{Change, ToolchainDiff};
+use rustc_fuzz::plugin::{self, Registry};
+use rustc_fuzz::results::{Database, ExecutionQuery};
+use rustc_fuzz::scheduler::Policy;
+use rustc_fuzz::toolchains::{Source, Spec, Toolchains};
+use rustc_fuzz::triage::bisect::Date;
+use rustc_fuzz::triage::reduce::{reduce_finding, Crasher, Shrunk};
+use rustc_fuzz::triage::{regressions, review};
+use rustc_fuzz::triage::{
+    Action, Bisector, Bucket, Buckets, Bundle, Filer, HangClass, HangTriage, IssueSearch,
+    KnownBugs, Recheck, Report, Reporter, Severity, Verdict,
+};
+
+#[derive(Parser)]
+#[command(name = "rustc-fuzz", version, about)]
+struct Cli {
+    /// Append a JSON line for every execution, new seed, new bucket,
+    /// reduction and bisection to FILE.
+    #[arg(long, global = true, value_name = "FILE")]
+    events: Option<PathBuf>,
+    /// Record the same events in a SQLite results database at FILE,
+    /// which the `db` commands query.
+    #[arg(long, global = true, value_name = "FILE")]
+    db: Option<PathBuf>,
+    /// Post new buckets, divergences and fixed crashes to this webhook,
+    /// at most a few a time (see `[notify]` in the campaign config).
+    #[arg(long, global = true, value_name = "URL")]
+    notify: Option<String>,
+    /// Campaign config: a TOML file of toolchain, targets, flags,
+    /// generator, limits, corpus paths and oracles that supplies the
+    /// defaults of the other options.
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Print the config in effect, defaults included, and exit.
+    #[arg(long, global = true)]
+    print_effective_config: bool,
+    /// Run every compiler at niceness N, so the host stays responsive.
+    #[arg(long, global = true, value_name = "N", allow_hyphen_values = true)]
+    nice: Option<i32>,
+    /// Load the generators, mutators and oracles of plugin library FILE;
+    /// repeat for several. Needs a build with the `plugins` feature.
+    #[arg(long = "plugin", global = true, value_name = "FILE")]
+    plugins: Vec<PathBuf>,
+    /// Log what the fuzzer is doing to stderr, as far as the `EnvFilter`
+    /// directives FILTER let through: `debug`, say, or
+    /// `warn,fuzzing_core::harness=trace`. Defaults to `$RUST_LOG`, then
+    /// `warn`.
+    #[arg(long, global = true, value_name = "FILTER")]
+    log: Option<String>,
+    /// Write log lines as `human` text or `json` objects.
+    #[arg(long, global = true, value_name = "FORMAT", default_value_t = logging::Format::Human)]
+    log_format: logging::Format,
+    /// Take the log filter from FILE, one directive per line, and again
+    /// whenever FILE changes, so a running campaign can be made to log
+    /// more.
+    #[arg(long, global = true, value_name = "FILE", conflicts_with = "log")]
+    log_filter_file: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Maintain seed corpora.
+    Corpus {
+        #[command(subcommand)]
+        command: CorpusCommand,
+    },
+    /// Sort, deduplicate and report findings.
+    Triage {
+        #[command(subcommand)]
+        command: TriageCommand,
+    },
+    /// Measure what a corpus covers of a coverage-instrumented rustc.
+    Coverage {
+        #[command(subcommand)]
+        command: CoverageCommand,
+    },
+    /// Query the results database given with `--db`.
+    Db {
+        #[command(subcommand)]
+        command: DbCommand,
+    },
+    /// Install, pin and garbage-collect the toolchains campaigns test.
+    Toolchains {
+        #[command(subcommand)]
+        command: ToolchainsCommand,
+    },
+    /// Write an AFL++/libFuzzer dictionary of Rust tokens and corpus names.
+    Dict {
+        /// Corpus directories to harvest type, trait and macro names from.
+        #[arg(long)]
+        corpus: Vec<PathBuf>,
+        /// Leave out harvested names seen fewer times than this.
+        #[arg(long, default_value_t = 2)]
+        min_count: usize,
+        /// Output file; defaults to stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Generate executable seeds with deterministic output.
+    #[command(visible_alias = "gen")]
+    Generate {
+        /// Number of programs to write.
+        #[arg(short = 'n', long, default_value_t = 100)]
+        count: usize,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Newest Rust the programs may need: 1.54 or latest.
+        #[arg(long, default_value_t = Level::Latest)]
+        level: Level,
+        /// Write programs that compute the same values by const evaluation
+        /// and at run time, for the `const-eval` harness.
+        #[arg(long = "const", conflicts_with = "level")]
+        constant: bool,
+        /// Fragment library from `corpus library`; a few of its fragments go
+        /// before each program's `main`.
+        #[arg(long)]
+        library: Option<PathBuf>,
+        /// Production weights from `corpus productions`, which make the
+        /// generator favour what the corpus rarely exercises.
+        #[arg(long)]
+        weights: Option<PathBuf>,
+        /// Fill the holes of a template instead, writing up to `-n`
+        /// instantiations of it; repeat for several templates.
+        #[arg(
+            long = "template",
+            value_name = "FILE",
+            conflicts_with_all = ["constant", "library", "weights"]
+        )]
+        templates: Vec<PathBuf>,
+        /// Use the seed generator NAME instead: `unsound` for programs that
+        /// encode a pattern rustc must reject, for the `soundness` harness;
+        /// `coherence` for pairs of
+        /// crates, the upstream one inlined as `mod upstream`, whose impls
+        /// rustc must accept or reject by the orphan and overlap rules, for
+        /// the `coherence` harness; `variance` for structs of mixed
+        /// variance and coercions between their instantiations that rustc
+        /// must accept or reject, for the `subtyping` harness;
+        /// `higher-ranked` for closures and functions passed where
+        /// higher-ranked bounds are required, that rustc must accept or
+        /// reject, for the same harness; `trait-alias` for programs that use
+        /// trait aliases as trait objects and bounds, those that must not
+        /// compile marked for the `soundness` harness; or one from a
+        /// plugin. Only `unsound` takes `--level`; the others write for the
+        /// latest nightly.
+        #[arg(
+            long,
+            value_name = "NAME",
+            conflicts_with_all = ["constant", "library", "weights", "templates"]
+        )]
+        generator: Option<String>,
+        /// Directory to write `gen-NNNNN.rs` files to, `<template>-NNNNN.rs`
+        /// files with `--template`, or `<NAME>-NNNNN.rs` files with
+        /// `--generator`.
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Mutate seeds without checking the mutants, writing each with its
+    /// mutation trace.
+    Mutate {
+        /// Mutants to write.
+        #[arg(short = 'n', long, default_value_t = 10)]
+        count: usize,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Fragment library from `corpus library`; enables the
+        /// `fragment-splice` operator.
+        #[arg(long)]
+        library: Option<PathBuf>,
+        /// Directory to write `<hash>.rs` mutants to.
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Seed files or corpus directories to mutate.
+        #[arg(required = true)]
+        seeds: Vec<PathBuf>,
+    },
+    /// Draft issues for a findings directory's buckets; the same as
+    /// `triage report`.
+    Report(ReportArgs),
+    /// Run seeds through a harness and report the ones that find something.
+    Run {
+        /// Harness to run each seed through; repeat for several. Defaults
+        /// to rustc unless an oracle is given.
+        #[arg(long = "harness", value_enum)]
+        harnesses: Vec<Harness>,
+        /// Oracle from a plugin to run each seed through as well; repeat
+        /// for several.
+        #[arg(long = "oracle", value_name = "NAME")]
+        oracles: Vec<String>,
+        /// Phase after which rustc stops.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+        /// Clippy: apply machine-applicable suggestions and compile the
+        /// result again.
+        #[arg(long)]
+        fix: bool,
+        /// Targets: a triple to compile for; repeat for a matrix. Defaults
+        /// to the host, bare-metal AArch64 and Thumb, and WASI.
+        #[arg(long = "target", value_name = "TRIPLE")]
+        targets: Vec<String>,
+        /// Flags: a space-separated flag set to try on every seed before
+        /// sampling others; repeat for a matrix.
+        #[arg(long = "flags", value_name = "FLAGS")]
+        flags: Vec<String>,
+        #[command(flatten)]
+        pool: PoolArgs,
+        #[command(flatten)]
+        budget: BudgetArgs,
+        /// Seed files or corpus directories.
+        #[arg(required = true)]
+        seeds: Vec<PathBuf>,
+    },
+    /// Mutate seeds from a corpus and check the mutants, keeping the ones
+    /// that bring new feature tags and saving the findings.
+    Fuzz {
+        #[arg(long, value_enum, default_value_t = Harness::Rustc)]
+        harness: Harness,
+        /// Check mutants with oracle NAME from a plugin instead of a
+        /// harness.
+        #[arg(long, value_name = "NAME", conflicts_with = "harness")]
+        oracle: Option<String>,
+        /// Phase after which rustc stops.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+        /// Clippy: apply machine-applicable suggestions and compile the
+        /// result again.
+        #[arg(long)]
+        fix: bool,
+        /// Targets: a triple to compile for; repeat for a matrix.
+        #[arg(long = "target", value_name = "TRIPLE")]
+        targets: Vec<String>,
+        /// Flags: a space-separated flag set to try on every seed before
+        /// sampling others; repeat for a matrix.
+        #[arg(long = "flags", value_name = "FLAGS")]
+        flags: Vec<String>,
+        /// How to pick the seed to mutate next: round-robin, rarity,
+        /// crashes, cost, fifo, coverage-greedy, rare-feature-first or
+        /// crash-proximity.
+        #[arg(long, default_value_t = Policy::Rarity)]
+        policy: Policy,
+        /// Number of mutants to check.
+        #[arg(short = 'n', long, default_value_t = 1000)]
+        iterations: u64,
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Directory to write findings to.
+        #[arg(long, default_value = "findings")]
+        findings: PathBuf,
+        /// Fragment library from `corpus library`; enables the
+        /// `fragment-splice` operator.
+        #[arg(long)]
+        library: Option<PathBuf>,
+        /// Seconds between checkpoints of the run's state in the findings
+        /// directory; 0 for none.
+        #[arg(long, default_value_t = 300)]
+        checkpoint_secs: u64,
+        #[command(flatten)]
+        budget: BudgetArgs,
+        /// Carry on from the checkpoint in findings directory DIR, with
+        /// the corpus, policy, seed and iteration count it records.
+        #[arg(long, value_name = "DIR")]
+        resume: Option<PathBuf>,
+        /// Make the choices of the run journaled in findings directory DIR
+        /// again without checking anything, and stop at the first that
+        /// differs.
+        #[arg(long, value_name = "DIR", conflicts_with = "resume")]
+        replay: Option<PathBuf>,
+        /// Replay only the first N iterations.
+        #[arg(long, value_name = "N", requires = "replay")]
+        upto: Option<u64>,
+        /// Write the replayed mutants to DIR, as `<iteration>.rs`.
+        #[arg(long, value_name = "DIR", requires = "replay")]
+        inputs: Option<PathBuf>,
+        /// Corpus directory; interesting mutants are added to it.
+        #[arg(required_unless_present_any = ["resume", "replay"])]
+        corpus: Option<PathBuf>,
+    },
+    /// Serve seeds and generated programs to `work` processes, possibly on
+    /// other machines, and collect their findings.
+    Coordinate {
+        /// Address to listen on.
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        listen: String,
+        #[arg(long, value_enum, default_value_t = Harness::Rustc)]
+        harness: Harness,
+        /// Directory to write deduplicated findings to.
+        #[arg(long, default_value = "findings")]
+        findings: PathBuf,
+        /// Once the seeds are done, keep generating programs at this
+        /// level instead of stopping.
+        #[arg(long)]
+        generate: Option<Level>,
+        /// First generator seed.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Seconds a worker has to report on a job before it is handed to
+        /// another.
+        #[arg(long, default_value_t = 600)]
+        lease: u64,
+        /// Seed files or corpus directories.
+        seeds: Vec<PathBuf>,
+    },
+    /// Run jobs from a `coordinate` process until it runs out.
+    Work {
+        /// The coordinator's address.
+        #[arg(long, default_value = "127.0.0.1:7878")]
+        coordinator: String,
+        /// Phase after which rustc stops.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+        /// Clippy: apply machine-applicable suggestions and compile the
+        /// result again.
+        #[arg(long)]
+        fix: bool,
+        /// Targets: a triple to compile for; repeat for a matrix.
+        #[arg(long = "target", value_name = "TRIPLE")]
+        targets: Vec<String>,
+        /// Flags: a space-separated flag set to try on every seed before
+        /// sampling others; repeat for a matrix.
+        #[arg(long = "flags", value_name = "FLAGS")]
+        flags: Vec<String>,
+    },
+    /// Re-derive a mutated input from its parent and a recorded trace.
+    Replay {
+        /// The seed the trace starts from.
+        #[arg(long)]
+        parent: PathBuf,
+        /// JSON trace recorded when the input was produced.
+        #[arg(long)]
+        trace: PathBuf,
+        /// Replay only the first N steps.
+        #[arg(long)]
+        upto: Option<usize>,
+        /// Fail unless the result is byte-identical to this file.
+        #[arg(long)]
+        check: Option<PathBuf>,
+        /// Fragment library from `corpus library`, for traces with
+        /// `fragment-splice` steps.
+        #[arg(long)]
+        library: Option<PathBuf>,
+        /// Output file; defaults to stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CorpusCommand {
+    /// Rewrite seeds into normal form: no comments, canonical formatting,
+    /// positional local names.
+    Normalize {
+        /// Corpus directory to read.
+        dir: PathBuf,
+        /// Directory to write normalized seeds to, mirroring `dir`.
+        #[arg(short, long, required_unless_present = "in_place")]
+        output: Option<PathBuf>,
+        /// Overwrite the seeds in `dir` instead.
+        #[arg(long, conflicts_with = "output")]
+        in_place: bool,
+    },
+    /// Copy the smallest subset of seeds that keeps the corpus's coverage
+    /// and findings under a harness.
+    Cmin {
+        /// Corpus directory to read.
+        dir: PathBuf,
+        /// Directory to write the kept seeds to, mirroring `dir`.
+        #[arg(short, long)]
+        output: PathBuf,
+        #[arg(long, value_enum, default_value_t = Harness::Rustc)]
+        harness: Harness,
+        /// Phase after which rustc stops.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+        /// Clippy: apply machine-applicable suggestions and compile the
+        /// result again.
+        #[arg(long)]
+        fix: bool,
+        /// Targets: a triple to compile for; repeat for a matrix.
+        #[arg(long = "target", value_name = "TRIPLE")]
+        targets: Vec<String>,
+        /// Size of the edge coverage map an instrumented rustc writes to.
+        #[arg(long, default_value_t = 1 << 16)]
+        map_size: usize,
+    },
+    /// Add the seeds of other corpora that bring new coverage or findings
+    /// under a harness to a corpus.
+    Merge {
+        /// Corpus directories or seed files to merge in.
+        #[arg(required = true)]
+        dirs: Vec<PathBuf>,
+        /// Corpus to merge into; its seeds are kept and count as covered.
+        #[arg(short, long)]
+        output: PathBuf,
+        #[arg(long, value_enum, default_value_t = Harness::Rustc)]
+        harness: Harness,
+        /// Phase after which rustc stops.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+        /// Clippy: apply machine-applicable suggestions and compile the
+        /// result again.
+        #[arg(long)]
+        fix: bool,
+        /// Targets: a triple to compile for; repeat for a matrix.
+        #[arg(long = "target", value_name = "TRIPLE")]
+        targets: Vec<String>,
+        /// Size of the edge coverage map an instrumented rustc writes to.
+        #[arg(long, default_value_t = 1 << 16)]
+        map_size: usize,
+    },
+    /// Remove seeds whose normal form another seed already has, merging
+    /// their metadata into the one kept.
+    Dedup {
+        /// Corpus directory to deduplicate in place.
+        dir: PathBuf,
+        /// Which seed of each group of duplicates to keep.
+        #[arg(long, value_enum, default_value_t = Keep::Oldest)]
+        keep: Keep,
+        /// Only list what would be removed.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Cut seeds into one seed per function, impl, trait, macro and
+    /// module, with the items each needs, keeping the pieces that compile.
+    Split {
+        /// Seed files or corpus directories to split.
+        #[arg(required = true)]
+        seeds: Vec<PathBuf>,
+        /// Directory to write pieces to, as `<seed>-<n>.rs`.
+        #[arg(short, long)]
+        output: PathBuf,
+        /// The rustc pieces have to compile with.
+        #[arg(long, default_value = "rustc")]
+        rustc: PathBuf,
+        /// Phase pieces have to get through.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+    },
+    /// Report how many seeds use each language feature, and which
+    /// features none does.
+    Features {
+        /// Corpus directories to read.
+        #[arg(required = true)]
+        dirs: Vec<PathBuf>,
+    },
+    /// Shrink seeds in place while their tags, whether they compile, and
+    /// whether the harness finds something stay the same.
+    Reduce {
+        /// Corpus directory.
+        dir: PathBuf,
+        #[arg(long, value_enum, default_value_t = Harness::Rustc)]
+        harness: Harness,
+        /// Phase after which rustc stops.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+        /// Clippy: apply machine-applicable suggestions and compile the
+        /// result again.
+        #[arg(long)]
+        fix: bool,
+        /// Targets: a triple to compile for; repeat for a matrix.
+        #[arg(long = "target", value_name = "TRIPLE")]
+        targets: Vec<String>,
+        /// Keep running, looking for new seeds every SECS seconds.
+        #[arg(long, value_name = "SECS")]
+        interval: Option<u64>,
+    },
+    /// Count how many seeds use each generator production and write
+    /// weights that make `generate` favour the rare ones.
+    Productions {
+        /// Corpus directories to read.
+        #[arg(required = true)]
+        dirs: Vec<PathBuf>,
+        /// Weights file to write, for `generate --weights`.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Summarize corpora: seed sizes, tags, editions, provenance, compile
+    /// times, and the seeds findings derive from.
+    Stats {
+        /// Corpus directories.
+        #[arg(required = true)]
+        dirs: Vec<PathBuf>,
+        /// Findings directory, for the seeds findings derive from.
+        #[arg(long)]
+        findings: Option<PathBuf>,
+        /// Print JSON instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Compile every seed with its recorded edition and flags, and record
+    /// the rustc version and compile time in the metadata of those that
+    /// compile, and every seed's peak RSS. Seeds
+    /// without metadata get it.
+    Verify {
+        /// Corpus directory to read.
+        dir: PathBuf,
+        /// The rustc to verify with.
+        #[arg(long, default_value = "rustc")]
+        rustc: PathBuf,
+        /// Phase after which rustc stops.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+        /// Move seeds that compiled before and are rejected now here, with
+        /// their metadata, mirroring the corpus.
+        #[arg(long, value_name = "DIR")]
+        quarantine: Option<PathBuf>,
+    },
+    /// Compile every seed with two toolchains and report those that newly
+    /// ICE, are newly accepted or rejected, or get different error codes,
+    /// lints or suggestions.
+    Regressions {
+        /// Corpus directory to read.
+        dir: PathBuf,
+        /// The toolchain to compare against: a rustup toolchain name, or
+        /// a path to a rustc.
+        #[arg(long)]
+        baseline: String,
+        /// The toolchain under test, likewise.
+        #[arg(long)]
+        candidate: String,
+        /// Phase after which rustc stops.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+        /// Print JSON instead of text.
+        #[arg(long)]
+        json: bool,
+        #[command(flatten)]
+        toolchains: ToolchainArgs,
+    },
+    /// Import rustc's UI test suite, with each test's edition and flags
+    /// in its metadata.
+    ImportUi {
+        /// The `tests/ui` directory of a rust-lang/rust checkout.
+        suite: PathBuf,
+        /// Directory to write seeds to, mirroring the suite.
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Import rust-lang/glacier's ICE reproducers. Open ones are tagged
+    /// with their issue so they are not reported as new findings.
+    ImportGlacier {
+        /// A rust-lang/glacier checkout.
+        repo: PathBuf,
+        /// Directory to write seeds to, as `ices/N.rs` and `fixed/N.rs`.
+        #[arg(short, long)]
+        output: PathBuf,
+    },
+    /// Download crates from crates.io and add the pieces of them that
+    /// compile on their own as seeds.
+    Harvest {
+        /// Directory to write seeds to, one subdirectory per crate.
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Harvest the N most downloaded crates.
+        #[arg(long, value_name = "N")]
+        top: Option<usize>,
+        /// Harvest N crates picked at random.
+        #[arg(long, value_name = "N")]
+        random: Option<usize>,
+        /// Seed for `--random`.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Harvest an already downloaded `.crate` archive; repeatable.
+        #[arg(long = "archive", value_name = "FILE")]
+        archives: Vec<PathBuf>,
+        /// The rustc fragments have to compile with.
+        #[arg(long, default_value = "rustc")]
+        rustc: PathBuf,
+        /// Phase fragments have to get through.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+    },
+    /// Cut functions, impls, types and traits that compile on their own
+    /// out of seeds and add them to a fragment library.
+    Library {
+        /// Seed files or corpus directories.
+        #[arg(required = true)]
+        seeds: Vec<PathBuf>,
+        /// Library file to write; fragments already in it are kept.
+        #[arg(short, long)]
+        output: PathBuf,
+        /// The rustc fragments have to compile with.
+        #[arg(long, default_value = "rustc")]
+        rustc: PathBuf,
+        /// Phase fragments have to get through.
+        #[arg(long, default_value_t = Phase::Analysis)]
+        phase: Phase,
+    },
+    /// Bring seeds to newer editions with rustc's edition compatibility
+    /// lints, as `cargo fix --edition` would, keeping the originals.
+    Migrate {
+        /// Corpus directory to read.
+        dir: PathBuf,
+        /// Newest edition to migrate to; seeds get a variant for each
+        /// edition between theirs and this one.
+        #[arg(long, default_value = "2024")]
+        to: String,
+        /// Directory to write variants to, mirroring the corpus; defaults to
+        /// next to the originals.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// The rustc to migrate with.
+        #[arg(long, default_value = "rustc")]
+        rustc: PathBuf,
+        /// Phase variants have to get through.
+        #[arg(long, default_value_t = Phase::Analysis)]
+        phase: Phase,
+    },
+    /// Pack a corpus directory, metadata included, into one compressed
+    /// archive file.
+    Pack {
+        /// Corpus directory to read.
+        dir: PathBuf,
+        /// Archive file to write.
+        #[arg(short, long)]
+        output: PathBuf,
+        /// zstd compression level, 1 to 19.
+        #[arg(long, default_value_t = 9)]
+        level: u32,
+        /// The zstd program.
+        #[arg(long, default_value = "zstd")]
+        zstd: PathBuf,
+    },
+    /// Unpack an archive written by `corpus pack` into a directory.
+    Unpack {
+        /// Archive file to read.
+        archive: PathBuf,
+        /// Directory to write seeds and sidecars to.
+        #[arg(short, long)]
+        output: PathBuf,
+        /// The zstd program.
+        #[arg(long, default_value = "zstd")]
+        zstd: PathBuf,
+    },
+    /// Upload the seeds a shared remote corpus lacks and download the
+    /// ones this corpus lacks, by content hash.
+    Sync {
+        /// Corpus directory.
+        dir: PathBuf,
+        /// `s3://bucket/prefix`, or an rsync destination such as
+        /// `host:path`.
+        remote: String,
+        /// Only upload.
+        #[arg(long, conflicts_with = "pull")]
+        push: bool,
+        /// Only download.
+        #[arg(long)]
+        pull: bool,
+        /// Endpoint of an S3-compatible service other than AWS.
+        #[arg(long, value_name = "URL")]
+        endpoint: Option<String>,
+        /// Only list what would be transferred.
+        #[arg(long)]
+        dry_run: bool,
+        /// Print JSON instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Write seeds into a flat directory laid out and named the way
+    /// another fuzzer expects its input corpus.
+    Export {
+        /// Seed files or corpus directories.
+        #[arg(required = true)]
+        seeds: Vec<PathBuf>,
+        /// libfuzzer, aflpp or honggfuzz.
+        #[arg(long)]
+        format: Format,
+        /// Directory to write to.
+        #[arg(short, long)]
+        output: PathBuf,
+        /// Inline the files seeds pull in with `mod foo;`, and leave those
+        /// files out as seeds of their own.
+        #[arg(long)]
+        flatten: bool,
+        /// Skip seeds larger than this many bytes; defaults to what the
+        /// fuzzer loads.
+        #[arg(long)]
+        max_len: Option<usize>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TriageCommand {
+    /// Run findings again and file them in buckets by crash signature,
+    /// listing each bucket with its smallest reproducer.
+    Buckets {
+        /// Findings directory; the buckets are kept in its `buckets.json`.
+        findings: PathBuf,
+        /// The rustc to run findings with.
+        #[arg(long, default_value = "rustc")]
+        rustc: PathBuf,
+        /// Phase after which rustc stops.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+        /// Shared known-bug database to use besides the findings
+        /// directory's `known-bugs.json`; repeat for several.
+        #[arg(long = "known", value_name = "FILE")]
+        known: Vec<PathBuf>,
+        /// Times to run each new crasher again to score its determinism;
+        /// 0 to trust the first run.
+        #[arg(long, default_value_t = 5)]
+        runs: u32,
+        /// Search rust-lang/rust's issues for each new bucket's message
+        /// and list the hits as candidate duplicates. Uses `GITHUB_TOKEN`
+        /// if set.
+        #[arg(long)]
+        search_issues: bool,
+        /// Print JSON instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Record that a bucket's crash was reported upstream, so later
+    /// campaigns count it as known instead of announcing it as new.
+    Known {
+        /// Findings directory, filed with `triage buckets`.
+        findings: PathBuf,
+        /// The bucket's id.
+        bucket: String,
+        /// Where it was reported.
+        #[arg(long)]
+        issue: String,
+        /// Known-bug database to record it in; defaults to the findings
+        /// directory's `known-bugs.json`.
+        #[arg(long, value_name = "FILE")]
+        db: Option<PathBuf>,
+    },
+    /// Run seeds that timed out again with bigger budgets, and record in
+    /// their metadata whether they hang, blow up compile time, or met a
+    /// busy machine.
+    Hangs {
+        /// Seed files or directories.
+        #[arg(required = true)]
+        seeds: Vec<PathBuf>,
+        /// The rustc to run seeds with.
+        #[arg(long, default_value = "rustc")]
+        rustc: PathBuf,
+        /// Phase after which rustc stops.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+        /// The budget the seeds timed out with, in seconds.
+        #[arg(long, default_value_t = 10)]
+        timeout: u64,
+        /// The biggest multiple of the budget to try.
+        #[arg(long, default_value_t = 8)]
+        max_factor: u32,
+    },
+    /// Draft a Markdown issue for each bucket from its smallest
+    /// reproducer, written as `<bucket id>.md`.
+    Report(ReportArgs),
+    /// Write a directory per bucket that reproduces it without rustc-fuzz:
+    /// the reproducer, reduced if it was, its metadata, the recorded
+    /// error output and a `repro.sh` pinned to the exact toolchain.
+    Bundle {
+        /// Findings directory, filed with `triage buckets`.
+        findings: PathBuf,
+        /// Directory to write bundles to, as `<bucket id>/`.
+        #[arg(short, long)]
+        output: PathBuf,
+        /// The rustc to record with.
+        #[arg(long, default_value = "rustc")]
+        rustc: PathBuf,
+        /// Phase after which rustc stops.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+    },
+    /// Step through the buckets nobody has decided on, showing each one's
+    /// reproducer and backtrace, and record whether to report it, that it
+    /// duplicates an issue, or that it will not be fixed.
+    Review {
+        /// Findings directory, filed with `triage buckets`.
+        findings: PathBuf,
+        /// The rustc to show backtraces from.
+        #[arg(long, default_value = "rustc")]
+        rustc: PathBuf,
+        /// Phase after which rustc stops.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+        /// Known-bug database to record duplicates in; defaults to the
+        /// findings directory's `known-bugs.json`.
+        #[arg(long, value_name = "FILE")]
+        db: Option<PathBuf>,
+    },
+    /// Find the nightly, and with a rust-lang/rust checkout the pull
+    /// request, in which a finding started crashing, and record it in its
+    /// bucket.
+    Bisect {
+        /// The finding.
+        finding: PathBuf,
+        /// Findings directory whose buckets to record the result in;
+        /// defaults to the finding's directory.
+        #[arg(long)]
+        findings: Option<PathBuf>,
+        /// A nightly date known not to crash, as YYYY-MM-DD; found by
+        /// looking back from `--end` if not given.
+        #[arg(long)]
+        start: Option<String>,
+        /// A nightly date that crashes; defaults to today.
+        #[arg(long)]
+        end: Option<String>,
+        /// rust-lang/rust checkout, to go on to bisect merge commits with
+        /// CI artifacts.
+        #[arg(long, value_name = "DIR")]
+        rust_repo: Option<PathBuf>,
+        /// Phase after which rustc stops.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+        /// Keep the toolchains installed for bisection, until `toolchains
+        /// gc` finds them unused.
+        #[arg(long)]
+        keep: bool,
+        #[command(flatten)]
+        toolchains: ToolchainArgs,
+    },
+    /// Shrink crashers while they crash with the same signature, writing
+    /// each as `<stem>.reduced.rs` beside it and adding it to the
+    /// regression corpus.
+    Reduce {
+        /// Finding files or findings directories.
+        #[arg(required = true)]
+        findings: Vec<PathBuf>,
+        /// The rustc to run findings with.
+        #[arg(long, default_value = "rustc")]
+        rustc: PathBuf,
+        /// Phase after which rustc stops.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+        /// Regression corpus to keep the smallest reduced crasher of each
+        /// signature in.
+        #[arg(long, default_value = "regressions")]
+        regressions: PathBuf,
+    },
+    /// Compile a regression corpus and report which of its crashes were
+    /// fixed or now crash differently.
+    Recheck {
+        /// Regression corpus, filled by `triage reduce`.
+        #[arg(default_value = "regressions")]
+        regressions: PathBuf,
+        /// The rustc to recheck, usually a new nightly.
+        #[arg(long, default_value = "rustc")]
+        rustc: PathBuf,
+        /// Phase after which rustc stops.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+        /// Print JSON instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum DbCommand {
+    /// Record the events of `--events` logs.
+    Import {
+        #[arg(required = true, value_name = "LOG")]
+        logs: Vec<PathBuf>,
+    },
+    /// Count executions per harness, findings per severity, seeds,
+    /// buckets, reductions and bisections.
+    Summary {
+        /// Print JSON instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// List executions, oldest first.
+    Executions {
+        #[arg(long)]
+        harness: Option<String>,
+        /// Only executions of the seed with this content hash.
+        #[arg(long, value_name = "HASH")]
+        input: Option<String>,
+        /// Only findings this bad or worse.
+        #[arg(long)]
+        severity: Option<Severity>,
+        /// Only findings.
+        #[arg(long)]
+        findings: bool,
+        /// Only the last N hours.
+        #[arg(long, value_name = "N")]
+        since_hours: Option<u64>,
+        /// Only the newest N.
+        #[arg(long, value_name = "N")]
+        limit: Option<usize>,
+        /// Print JSON lines instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// List seeds added to corpora.
+    Seeds {
+        /// Print JSON lines instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// List buckets as first seen.
+    Buckets {
+        /// Print JSON lines instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// List finished reductions.
+    Reductions {
+        /// Print JSON lines instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// List finished bisections.
+    Bisections {
+        /// Print JSON lines instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// List regression seeds `triage recheck` found fixed.
+    Fixes {
+        /// Print JSON lines instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// List `fuzz` runs with their policy and totals.
+    Runs {
+        /// Print JSON lines instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+    /// Compare scheduling policies: buckets, findings and seeds added per
+    /// thousand iterations, and iterations per second, over their runs.
+    Policies {
+        /// Print JSON instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ToolchainsCommand {
+    /// Install the toolchains of each spec and print their rustc: the
+    /// latest nightly (`nightly`), a dated one (`nightly-2024-06-01`),
+    /// a range (`nightly-2024-06-01..nightly-2024-07-01`) or any rustup
+    /// toolchain name.
+    Install {
+        #[arg(required = true, value_name = "SPEC")]
+        specs: Vec<String>,
+        #[command(flatten)]
+        toolchains: ToolchainArgs,
+    },
+    /// List the toolchains installed here, with when they were last used.
+    List {
+        /// Print JSON instead of text.
+        #[arg(long)]
+        json: bool,
+        #[command(flatten)]
+        toolchains: ToolchainArgs,
+    },
+    /// Keep toolchains from garbage collection however long they go
+    /// unused.
+    Pin {
+        #[arg(required = true, value_name = "TOOLCHAIN")]
+        names: Vec<String>,
+        #[command(flatten)]
+        toolchains: ToolchainArgs,
+    },
+    /// Let garbage collection remove pinned toolchains again.
+    Unpin {
+        #[arg(required = true, value_name = "TOOLCHAIN")]
+        names: Vec<String>,
+        #[command(flatten)]
+        toolchains: ToolchainArgs,
+    },
+    /// Uninstall toolchains installed here that are unused, unpinned and
+    /// not the latest nightly.
+    Gc {
+        /// Days a toolchain may go unused before it is removed.
+        #[arg(long, default_value_t = 7)]
+        max_age_days: u64,
+        /// Keep the toolchains of this spec too; repeat for several.
+        #[arg(long = "keep", value_name = "SPEC")]
+        keep: Vec<String>,
+        /// List what would be removed without removing it.
+        #[arg(long)]
+        dry_run: bool,
+        #[command(flatten)]
+        toolchains: ToolchainArgs,
+    },
+}
+
+/// Where managed toolchains come from and are kept.
+#[derive(Args)]
+struct ToolchainArgs {
+    /// Directory of the toolchain state file and of dist installs.
+    #[arg(long, value_name = "DIR", default_value = "toolchains")]
+    store: PathBuf,
+    /// Install with rustup, or download dated nightlies from the dist
+    /// server.
+    #[arg(long, default_value_t = Source::Rustup)]
+    source: Source,
+    /// Hours before the latest nightly is looked for again.
+    #[arg(long, value_name = "HOURS", default_value_t = 24)]
+    refresh_hours: u64,
+}
+
+impl ToolchainArgs {
+    fn toolchains(&self) -> Toolchains {
+        Toolchains {
+            source: self.source,
+            refresh: Duration::from_secs(self.refresh_hours * 3600),
+            ..Toolchains::new(&self.store)
+        }
+    }
+}
+
+/// The most a campaign may use before it winds down.
+#[derive(Args)]
+struct BudgetArgs {
+    /// CPU minutes, of the fuzzer and the compilers it runs.
+    #[arg(long, value_name = "MINUTES")]
+    cpu_minutes: Option<u64>,
+    /// Wall-clock minutes.
+    #[arg(long, value_name = "MINUTES")]
+    wall_minutes: Option<u64>,
+    /// MiB of artifacts in the findings directory.
+    #[arg(long, value_name = "MIB")]
+    disk_mb: Option<u64>,
+}
+
+impl BudgetArgs {
+    fn budget(&self) -> Budget {
+        Budget {
+            cpu_minutes: self.cpu_minutes,
+            wall_minutes: self.wall_minutes,
+            disk_mb: self.disk_mb,
+        }
+    }
+}
+
+/// How many seeds to check at once, and where.
+#[derive(Args)]
+struct PoolArgs {
+    /// Seeds to check at once, each on a worker thread of its own.
+    #[arg(short, long, default_value_t = 1)]
+    jobs: usize,
+    /// Pin the workers, and the compilers they start, to these CPUs, in
+    /// `taskset` list syntax such as `0-3,8`.
+    #[arg(long, value_name = "LIST")]
+    cpus: Option<String>,
+}
+
+impl PoolArgs {
+    fn options(&self) -> Result<PoolOptions> {
+        let cpus = match &self.cpus {
+            Some(list) => pool::parse_cpus(list).map_err(anyhow::Error::msg)?,
+            None => Vec::new(),
+        };
+        Ok(PoolOptions::new(self.jobs.max(1), cpus))
+    }
+}
+
+/// Arguments of `triage report`, also available as `report`.
+#[derive(Args)]
+struct ReportArgs {
+    /// Findings directory, filed with `triage buckets`.
+    findings: PathBuf,
+    /// Directory to write reports to.
+    #[arg(short, long)]
+    output: PathBuf,
+    /// The rustc to report on.
+    #[arg(long, default_value = "rustc")]
+    rustc: PathBuf,
+    /// Phase after which rustc stops.
+    #[arg(long, default_value_t = Phase::Codegen)]
+    phase: Phase,
+}
+
+#[derive(Subcommand)]
+enum CoverageCommand {
+    /// Compile every seed with a rustc built with `-Cinstrument-coverage`
+    /// and record in its metadata which compiler regions it covers that
+    /// no seed before it did, and per module in the corpus's
+    /// `rustc-coverage.json`.
+    Measure {
+        /// Corpus directory to measure.
+        dir: PathBuf,
+        /// The instrumented rustc, as an absolute path into its sysroot.
+        #[arg(long)]
+        rustc: PathBuf,
+        /// Phase after which rustc stops.
+        #[arg(long, default_value_t = Phase::Codegen)]
+        phase: Phase,
+        #[arg(long, default_value = "llvm-profdata")]
+        profdata: PathBuf,
+        #[arg(long, default_value = "llvm-cov")]
+        llvm_cov: PathBuf,
+        /// An instrumented binary to read coverage of; repeat for several.
+        /// Defaults to rustc and the `librustc_driver` in its sysroot.
+        #[arg(long = "object", value_name = "FILE")]
+        objects: Vec<PathBuf>,
+    },
+    /// List every compiler module with how much of it the corpus covers,
+    /// how many seeds reach it and which seed reached most of it first,
+    /// the least covered first.
+    Report {
+        /// Corpus directory, measured with `coverage measure`.
+        dir: PathBuf,
+        /// Print JSON instead of text.
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+/// Which of a group of duplicate seeds `corpus dedup` keeps.
+#[derive(Clone, Copy, ValueEnum)]
+enum Keep {
+    /// The least recently modified.
+    Oldest,
+    /// The shortest.
+    Smallest,
+}
+
+fn main() -> Result<()> {
+    let campaign = match config_arg() {
+        Some(path) => Some((Campaign::load(&path)?, path)),
+        None => None,
+    };
+    // Before the config is checked, which may name their oracles.
+    let plugins: Vec<PathBuf> = campaign
+        .iter()
+        .flat_map(|(campaign, _)| campaign.plugins.clone())
+        .chain(path_args("--plugin"))
+        .collect();
+    if !plugins.is_empty() {
+        load_plugins(&plugins)?;
+    }
+    let mut campaign = match campaign {
+        Some((campaign, path)) => {
+            let names = oracle_names();
+            let names: Vec<&str> = names.iter().map(String::as_str).collect();
+            let problems = campaign.problems(&names);
+            anyhow::ensure!(
+                problems.is_empty(),
+                "{} is not a valid campaign config:\n  {}",
+                path.display(),
+                problems.join("\n  ")
+            );
+            Some(campaign)
+        }
+        None => None,
+    };
+    // Printing the config should not install anything.
+    let printing = std::env::args().any(|arg| arg == "--print-effective-config");
+    if let Some(campaign) = campaign.as_mut().filter(|_| !printing) {
+        if let Some(rustc) = channel_rustc(campaign)? {
+            campaign.toolchain.rustc = rustc;
+        }
+    }
+    let cli = parse(campaign.as_ref());
+    let filter = cli
+        .log
+        .clone()
+        .or_else(|| std::env::var("RUST_LOG").ok())
+        .unwrap_or_else(|| logging::DEFAULT_FILTER.to_owned());
+    logging::init(&filter, cli.log_format).context("parsing --log")?;
+    if let Some(path) = &cli.log_filter_file {
+        logging::watch(path).with_context(|| format!("reading {}", path.display()))?;
+    }
+    if cli.print_effective_config {
+        return print_effective_config(campaign.unwrap_or_default(), &cli);
+    }
+    install(&cli, campaign.as_ref())?;
+    let Some(command) = cli.command else {
+        Cli::command().print_help()?;
+        std::process::exit(2);
+    };
+    dispatch(command, cli.db)
+}
+
+/// Loads the plugins at `paths` into the registry every lookup of a
+/// harness, oracle or generator by name goes through.
+fn load_plugins(paths: &[PathBuf]) -> Result<()> {
+    let mut registry = Registry::new();
+    for path in paths {
+        // SAFETY: loading a plugin trusts it to have been built with
+        // `export_plugin!` against this crate, as `--plugin` says.
+        unsafe { registry.load(path) }
+            .with_context(|| format!("loading plugin {}", path.display()))?;
+    }
+    plugin::install(registry);
+    Ok(())
+}
+
+/// Parses the command line, with the defaults `campaign` sets.
+fn parse(campaign: Option<&Campaign>) -> Cli {
+    let mut command = Cli::command();
+    if let Some(campaign) = campaign {
+        command = configure(command, &config_defaults(campaign));
+    }
+    let matches = command.get_matches();
+    let mut cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+    // An oracle from the config gives way to a harness given here.
+    if let Some(Command::Fuzz { oracle, .. }) = &mut cli.command {
+        let fuzz = matches.subcommand_matches("fuzz");
+        if fuzz.and_then(|m| m.value_source("harness")) == Some(ValueSource::CommandLine) {
+            *oracle = None;
+        }
+    }
+    cli
+}
+
+/// Prints `campaign` as it would be with the options given in `cli`.
+fn print_effective_config(mut campaign: Campaign, cli: &Cli) -> Result<()> {
+    campaign.limits.nice = cli.nice.or(campaign.limits.nice);
+    for path in &cli.plugins {
+        if !campaign.plugins.contains(path) {
+            campaign.plugins.push(path.clone());
+        }
+    }
+    print!("{}", campaign.to_toml()?);
+    Ok(())
+}
+
+/// Sets up the rustc defaults and the event sinks `cli` and `campaign`
+/// ask for, before any command runs.
+fn install(cli: &Cli, campaign: Option<&Campaign>) -> Result<()> {
+    if campaign.is_some() || cli.nice.is_some() {
+        let mut rustc = campaign.map_or_else(Rustc::default, Campaign::rustc);
+        rustc.nice = cli.nice.or(rustc.nice);
+        Rustc::set_default(rustc);
+    }
+    if let Some(path) = &cli.events {
+        events::init(path).with_context(|| format!("opening {}", path.display()))?;
+    }
+    let notify = campaign.and_then(|campaign| campaign.notify.as_ref());
+    if cli.notify.is_some() || notify.is_some() {
+        let mut webhook = match notify {
+            Some(config) => config.webhook()?,
+            None => Webhook::new(""),
+        };
+        if let Some(url) = &cli.notify {
+            webhook.url = url.clone();
+        }
+        events::init_notifier(Notifier::new(webhook).context("reading notification state")?);
+    }
+    if let Some(path) = &cli.db {
+        // `db` commands open it themselves, to read.
+        if !matches!(cli.command, Some(Command::Db { .. })) {
+            events::init_db(path).with_context(|| format!("opening {}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs `command`, with the database at `db` if one was given.
+fn dispatch(command: Command, db: Option<PathBuf>) -> Result<()> {
+    match command {
+        Command::Corpus { command } => corpus(command),
+        Command::Generate {
+            count,
+            seed,
+            level,
+            templates,
+            output,
+            ..
+        } if !templates.is_empty() => instantiate(&templates, count, seed, level, &output),
+        Command::Generate {
+            count,
+            seed,
+            level,
+            generator: Some(name),
+            output,
+            ..
+        } => generate_plugin(&name, count, seed, level, &output),
+        Command::Generate {
+            count,
+            seed,
+            level,
+            constant,
+            library,
+            weights,
+            output,
+            ..
+        } => generate(
+            count,
+            seed,
+            level,
+            constant,
+            library.as_deref(),
+            weights.as_deref(),
+            &output,
+        ),
+        Command::Run {
+            mut harnesses,
+            oracles,
+            phase,
+            fix,
+            targets,
+            flags,
+            pool,
+            budget,
+            seeds,
+        } => {
+            if harnesses.is_empty() && oracles.is_empty() {
+                harnesses.push(Harness::Rustc);
+            }
+            let names: Vec<String> = harnesses
+                .iter()
+                .map(|harness| harness.name())
+                .chain(oracles)
+                .collect();
+            run(
+                &names,
+                |name| match Harness::from_str(name, false) {
+                    Ok(harness) => checker(harness, phase, fix, &targets, &flags),
+                    Err(_) => oracle_checker(name),
+                },
+                &pool,
+                &seeds,
+                budget.budget(),
+            )
+        }
+        Command::Fuzz {
+            library,
+            replay: Some(dir),
+            upto,
+            inputs,
+            ..
+        } => replay_fuzz(&dir, library.as_deref(), upto, inputs.as_deref()),
+        Command::Fuzz {
+            harness,
+            oracle,
+            phase,
+            fix,
+            targets,
+            flags,
+            policy,
+            iterations,
+            seed,
+            findings,
+            library,
+            checkpoint_secs,
+            budget,
+            resume,
+            corpus,
+            ..
+        } => {
+            let state = match &resume {
+                Some(dir) => Checkpoint::load(dir)
+                    .with_context(|| format!("reading the checkpoint in {}", dir.display()))?
+                    .with_context(|| format!("{} has no {}", dir.display(), Checkpoint::FILE))?,
+                None => {
+                    let corpus = corpus.expect("required without --resume");
+                    Checkpoint::new(&corpus, &findings, policy, seed, iterations)
+                }
+            };
+            let engine = engine(library.as_deref())?;
+            let check = match &oracle {
+                Some(name) => oracle_checker(name)?,
+                None => checker(harness, phase, fix, &targets, &flags)?,
+            };
+            let every = (checkpoint_secs > 0).then(|| Duration::from_secs(checkpoint_secs));
+            fuzz(
+                &engine,
+                library.as_deref(),
+                check,
+                state,
+                every,
+                budget.budget(),
+            )
+        }
+        Command::Coordinate {
+            listen,
+            harness,
+            findings,
+            generate,
+            seed,
+            lease,
+            seeds,
+        } => {
+            let coordinator = Coordinator {
+                harness: harness.name(),
+                findings,
+                generate,
+                seed,
+                lease: Duration::from_secs(lease),
+                ..Coordinator::default()
+            };
+            coordinate(&coordinator, &listen, &seeds)
+        }
+        Command::Work {
+            coordinator,
+            phase,
+            fix,
+            targets,
+            flags,
+        } => work(&coordinator, phase, fix, &targets, &flags),
+        Command::Mutate {
+            count,
+            seed,
+            library,
+            output,
+            seeds,
+        } => {
+            let engine = engine(library.as_deref())?;
+            mutate(&engine, &seeds, count, seed, &output)
+        }
+        Command::Report(args) => report(&args),
+        Command::Replay {
+            parent,
+            trace,
+            upto,
+            check,
+            library,
+            output,
+        } => {
+            let engine = engine(library.as_deref())?;
+            replay(&engine, &parent, &trace, upto, check.as_deref(), output)
+        }
+        Command::Dict {
+            corpus,
+            min_count,
+            output,
+        } => dict(&corpus, min_count, output),
+        Command::Coverage { command } => coverage(command),
+        Command::Db { command } => {
+            let path = db.context("`db` commands need `--db FILE`")?;
+            let mut db =
+                Database::open(&path).with_context(|| format!("opening {}", path.display()))?;
+            query_db(&mut db, command)
+        }
+        Command::Toolchains { command } => toolchains(command),
+        Command::Triage { command } => triage(command),
+    }
+}
+
+/// Runs a `corpus` subcommand.
+fn corpus(command: CorpusCommand) -> Result<()> {
+    match command {
+        CorpusCommand::Normalize {
+            dir,
+            output,
+            in_place: _,
+        } => normalize(&dir, output.as_deref()),
+        CorpusCommand::Cmin {
+            dir,
+            output,
+            harness,
+            phase,
+            fix,
+            targets,
+            map_size,
+        } => cmin(&dir, &output, harness, phase, fix, &targets, map_size),
+        CorpusCommand::Merge {
+            dirs,
+            output,
+            harness,
+            phase,
+            fix,
+            targets,
+            map_size,
+        } => merge(&dirs, &output, harness, phase, fix, &targets, map_size),
+        CorpusCommand::Dedup { dir, keep, dry_run } => dedup(&dir, keep, dry_run),
+        CorpusCommand::Split {
+            seeds,
+            output,
+            rustc,
+            phase,
+        } => split(&seeds, &output, rustc, phase),
+        CorpusCommand::Features { dirs } => features(&dirs),
+        CorpusCommand::Productions { dirs, output } => productions(&dirs, output.as_deref()),
+        CorpusCommand::Reduce {
+            dir,
+            harness,
+            phase,
+            fix,
+            targets,
+            interval,
+        } => {
+            let check = checker(harness, phase, fix, &targets, &[])?;
+            reduce(&dir, check, phase, interval.map(Duration::from_secs))
+        }
+        CorpusCommand::Stats {
+            dirs,
+            findings,
+            json,
+        } => stats(&dirs, findings.as_deref(), json),
+        CorpusCommand::Verify {
+            dir,
+            rustc,
+            phase,
+            quarantine,
+        } => verify(&dir, rustc, phase, quarantine.as_deref()),
+        CorpusCommand::Regressions {
+            dir,
+            baseline,
+            candidate,
+            phase,
+            json,
+            toolchains,
+        } => {
+            let toolchains = toolchains.toolchains();
+            let diff = ToolchainDiff::new(
+                Rustc {
+                    phase,
+                    ..Rustc::new(toolchain_rustc(&toolchains, &baseline)?)
+                },
+                Rustc {
+                    phase,
+                    ..Rustc::new(toolchain_rustc(&toolchains, &candidate)?)
+                },
+            );
+            regressions(&dir, &diff, json)
+        }
+        CorpusCommand::ImportUi { suite, output } => import_ui(&suite, &output),
+        CorpusCommand::ImportGlacier { repo, output } => import_glacier(&repo, &output),
+        CorpusCommand::Library {
+            seeds,
+            output,
+            rustc,
+            phase,
+        } => library(&seeds, &output, rustc, phase),
+        CorpusCommand::Migrate {
+            dir,
+            to,
+            output,
+            rustc,
+            phase,
+        } => migrate(&dir, &to, output.as_deref(), rustc, phase),
+        CorpusCommand::Pack {
+            dir,
+            output,
+            level,
+            zstd,
+        } => pack(
+            &dir,
+            &output,
+            Zstd {
+                program: zstd,
+                level,
+            },
+        ),
+        CorpusCommand::Unpack {
+            archive,
+            output,
+            zstd,
+        } => unpack(
+            &archive,
+            &output,
+            Zstd {
+                program: zstd,
+                ..Zstd::default()
+            },
+        ),
+        CorpusCommand::Sync {
+            dir,
+            remote,
+            push,
+            pull,
+            endpoint,
+            dry_run,
+            json,
+        } => {
+            let syncer = Syncer {
+                direction: match (push, pull) {
+                    (true, _) => Direction::Push,
+                    (_, true) => Direction::Pull,
+                    _ => Direction::Both,
+                },
+                dry_run,
+                ..Syncer::new(Remote::parse(&remote, endpoint))
+            };
+            sync(&syncer, &dir, json)
+        }
+        CorpusCommand::Export {
+            seeds,
+            format,
+            output,
+            flatten,
+            max_len,
+        } => export(
+            &seeds,
+            format,
+            &output,
+            flatten,
+            max_len.or(format.max_len()),
+        ),
+        CorpusCommand::Harvest {
+            output,
+            top,
+            random,
+            seed,
+            archives,
+            rustc,
+            phase,
+        } => {
+            let harvester = Harvester {
+                driver: Rustc {
+                    phase,
+                    ..Rustc::new(rustc)
+                },
+                ..Harvester::default()
+            };
+            let mut crates = Vec::new();
+            if let Some(count) = top {
+                crates.extend(harvester.top(count).context("listing crates")?);
+            }
+            if let Some(count) = random {
+                let mut rng = StdRng::seed_from_u64(seed);
+                crates.extend(
+                    harvester
+                        .sample(count, &mut rng)
+                        .context("listing crates")?,
+                );
+            }
+            harvest(&harvester, &crates, &archives, &output)
+        }
+    }
+}
+
+/// Runs a `coverage` subcommand.
+fn coverage(command: CoverageCommand) -> Result<()> {
+    match command {
+        CoverageCommand::Measure {
+            dir,
+            rustc,
+            phase,
+            profdata,
+            llvm_cov,
+            objects,
+        } => {
+            let driver = Rustc {
+                phase,
+                ..Rustc::new(rustc)
+            };
+            let mut profiler = Profiler::new(driver).context("finding rustc's sysroot")?;
+            profiler.profdata = profdata;
+            profiler.llvm_cov = llvm_cov;
+            if !objects.is_empty() {
+                profiler.objects = objects;
+            }
+            measure_coverage(&dir, &profiler)
+        }
+        CoverageCommand::Report { dir, json } => coverage_report(&dir, json),
+    }
+}
+
+/// Runs a `toolchains` subcommand.
+fn toolchains(command: ToolchainsCommand) -> Result<()> {
+    match command {
+        ToolchainsCommand::Install { specs, toolchains } => {
+            install_toolchains(&toolchains.toolchains(), &specs)
+        }
+        ToolchainsCommand::List { json, toolchains } => {
+            list_toolchains(&toolchains.toolchains(), json)
+        }
+        ToolchainsCommand::Pin { names, toolchains } => {
+            pin_toolchains(&toolchains.toolchains(), &names, true)
+        }
+        ToolchainsCommand::Unpin { names, toolchains } => {
+            pin_toolchains(&toolchains.toolchains(), &names, false)
+        }
+        ToolchainsCommand::Gc {
+            max_age_days,
+            keep,
+            dry_run,
+            toolchains,
+        } => gc_toolchains(
+            &toolchains.toolchains(),
+            &keep,
+            Duration::from_secs(max_age_days * 24 * 3600),
+            dry_run,
+        ),
+    }
+}
+
+/// Runs a `triage` subcommand.
+fn triage(command: TriageCommand) -> Result<()> {
+    match command {
+        TriageCommand::Buckets {
+            findings,
+            rustc,
+            phase,
+            known,
+            runs,
+            search_issues,
+            json,
+        } => {
+            let search = search_issues.then(|| IssueSearch::new(&findings));
+            buckets(&findings, rustc, phase, &known, runs, search, json)
+        }
+        TriageCommand::Known {
+            findings,
+            bucket,
+            issue,
+            db,
+        } => known(&findings, &bucket, &issue, db.as_deref()),
+        TriageCommand::Review {
+            findings,
+            rustc,
+            phase,
+            db,
+        } => review(&findings, rustc, phase, db.as_deref()),
+        TriageCommand::Reduce {
+            findings,
+            rustc,
+            phase,
+            regressions,
+        } => reduce_crashers(&findings, rustc, phase, &regressions),
+        TriageCommand::Recheck {
+            regressions,
+            rustc,
+            phase,
+            json,
+        } => recheck(&regressions, rustc, phase, json),
+        TriageCommand::Hangs {
+            seeds,
+            rustc,
+            phase,
+            timeout,
+            max_factor,
+        } => {
+            let triage = HangTriage {
+                max_factor,
+                ..HangTriage::new(Rustc {
+                    phase,
+                    timeout: Duration::from_secs(timeout),
+                    ..Rustc::new(rustc)
+                })
+            };
+            hangs(&seeds, &triage)
+        }
+        TriageCommand::Report(args) => report(&args),
+        TriageCommand::Bundle {
+            findings,
+            output,
+            rustc,
+            phase,
+        } => bundle(&findings, &output, rustc, phase),
+        TriageCommand::Bisect {
+            finding,
+            findings,
+            start,
+            end,
+            rust_repo,
+            phase,
+            keep,
+            toolchains,
+        } => {
+            let parse = |date: &str| {
+                Date::parse(date).with_context(|| format!("{date} is not a YYYY-MM-DD date"))
+            };
+            let start = start.as_deref().map(parse).transpose()?;
+            let end = end.as_deref().map(parse).transpose()?;
+            let dir = match findings {
+                Some(dir) => dir,
+                None => finding.parent().map(Path::to_path_buf).unwrap_or_default(),
+            };
+            let bisector = Bisector {
+                keep,
+                ..Bisector::new(
+                    toolchains.toolchains(),
+                    Rustc {
+                        phase,
+                        ..Rustc::default()
+                    },
+                )
+            };
+            bisect(
+                &finding,
+                &dir,
+                bisector,
+                start,
+                end.unwrap_or_else(Date::today),
+                rust_repo.as_deref(),
+            )
+        }
+    }
+}
+
+/// The `--config` file on the command line. It is looked for before
+/// parsing, since its contents become the parser's defaults.
+fn config_arg() -> Option<PathBuf> {
+    path_args("--config").into_iter().next()
+}
+
+/// The values of every `flag` option on the command line, which some
+/// options need before it is parsed.
+fn path_args(flag: &str) -> Vec<PathBuf> {
+    let mut paths = Vec::new();
+    let mut args = std::env::args_os().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            break;
+        }
+        if arg == flag {
+            paths.extend(args.next().map(PathBuf::from));
+        } else if let Some(path) = arg
+            .to_str()
+            .and_then(|arg| arg.strip_prefix(flag))
+            .and_then(|arg| arg.strip_prefix('='))
+        {
+            paths.push(PathBuf::from(path));
+        }
+    }
+    paths
+}
+
+/// The rustc of the newest toolchain of `campaign`'s channel, installed,
+/// if it names one.
+fn channel_rustc(campaign: &Campaign) -> Result<Option<PathBuf>> {
+    let Some(channel) = &campaign.toolchain.channel else {
+        return Ok(None);
+    };
+    let spec: Spec = channel.parse()?;
+    let toolchains = campaign.toolchain.toolchains()?;
+    let names = toolchains
+        .resolve(&spec)
+        .with_context(|| format!("resolving toolchain {spec}"))?;
+    let newest = names.last().context("empty toolchain range")?;
+    let rustc = toolchains
+        .ensure(newest)
+        .with_context(|| format!("installing {newest}"))?;
+    Ok(Some(rustc))
+}
+
+/// Command-line defaults `campaign` supplies, by argument id.
+fn config_defaults(campaign: &Campaign) -> Vec<(&'static str, Vec<String>)> {
+    let path = |path: &PathBuf| path.display().to_string();
+    // Harnesses go to `--harness`, and oracles from plugins to `--oracle`.
+    let builtin = harness_names();
+    let (harnesses, oracles): (Vec<String>, Vec<String>) = campaign
+        .oracles
+        .iter()
+        .cloned()
+        .partition(|name| builtin.contains(name));
+    let first = campaign.oracles.first();
+    let mut defaults = vec![
+        (
+            "harness",
+            first
+                .filter(|name| builtin.contains(name))
+                .cloned()
+                .into_iter()
+                .collect(),
+        ),
+        ("harnesses", harnesses),
+        (
+            "oracle",
+            first
+                .filter(|name| !builtin.contains(name))
+                .cloned()
+                .into_iter()
+                .collect(),
+        ),
+        ("oracles", oracles),
+        ("targets", campaign.targets.clone()),
+        (
+            "flags",
+            campaign.flags.iter().map(|set| set.join(" ")).collect(),
+        ),
+        ("rustc", vec![path(&campaign.toolchain.rustc)]),
+        ("phase", vec![campaign.toolchain.phase.clone()]),
+        ("store", vec![path(&campaign.toolchain.store)]),
+        ("source", vec![campaign.toolchain.source.clone()]),
+        (
+            "refresh_hours",
+            vec![campaign.toolchain.refresh_hours.to_string()],
+        ),
+        ("keep", campaign.toolchain.channel.iter().cloned().collect()),
+        ("level", vec![campaign.generator.level.clone()]),
+        ("seed", vec![campaign.generator.seed.to_string()]),
+        ("policy", vec![campaign.scheduler.policy.clone()]),
+        (
+            "weights",
+            campaign.generator.weights.iter().map(path).collect(),
+        ),
+        (
+            "library",
+            campaign.generator.library.iter().map(path).collect(),
+        ),
+        ("seeds", campaign.corpus.seeds.iter().map(path).collect()),
+        (
+            "corpus",
+            campaign.corpus.seeds.iter().take(1).map(path).collect(),
+        ),
+        ("findings", vec![path(&campaign.corpus.findings)]),
+        (
+            "cpu_minutes",
+            campaign
+                .budget
+                .cpu_minutes
+                .iter()
+                .map(u64::to_string)
+                .collect(),
+        ),
+        (
+            "wall_minutes",
+            campaign
+                .budget
+                .wall_minutes
+                .iter()
+                .map(u64::to_string)
+                .collect(),
+        ),
+        (
+            "disk_mb",
+            campaign.budget.disk_mb.iter().map(u64::to_string).collect(),
+        ),
+    ];
+    defaults.retain(|(_, values)| !values.is_empty());
+    defaults
+}
+
+/// Whether a config supplies argument `id` of subcommand `command`; a few
+/// ids mean something else in some commands.
+fn configurable(command: &str, id: &str) -> bool {
+    match id {
+        "seeds" => matches!(command, "run" | "coordinate" | "mutate"),
+        "seed" => matches!(command, "generate" | "mutate" | "fuzz" | "coordinate"),
+        "corpus" => command == "fuzz",
+        // `triage bisect --keep` is a switch.
+        "keep" => command == "gc",
+        "level" | "weights" => command == "generate",
+        // `triage reduce` takes finding files, and `coverage measure` an
+        // instrumented rustc.
+        "findings" => command != "reduce",
+        "rustc" => command != "measure",
+        _ => true,
+    }
+}
+
+/// `command` and its subcommands with `defaults` as the defaults of the
+/// arguments they have.
+fn configure(
+    mut command: clap::Command,
+    defaults: &[(&'static str, Vec<String>)],
+) -> clap::Command {
+    let name = command.get_name().to_owned();
+    for (id, values) in defaults {
+        if configurable(&name, id) && command.get_arguments().any(|arg| arg.get_id() == *id) {
+            let values = values.clone();
+            command = command.mut_arg(*id, |arg| arg.default_values(values).required(false));
+        }
+    }
+    let subcommands: Vec<String> = command
+        .get_subcommands()
+        .map(|sub| sub.get_name().to_owned())
+        .collect();
+    for sub in subcommands {
+        command = command.mut_subcommand(sub, |sub| configure(sub, defaults));
+    }
+    command
+}
+
+fn dict(corpora: &[PathBuf], min_count: usize, output: Option<PathBuf>) -> Result<()> {
+    let mut dict = Dictionary::new();
+    for dir in corpora {
+        let entries = Corpus::open(dir)
+            .entries()
+            .with_context(|| format!("reading corpus {}", dir.display()))?;
+        for entry in entries {
+            dict.harvest(&entry.source);
+        }
+    }
+    let rendered = dict.render(min_count);
+    match output {
+        Some(path) => {
+            fs::write(&path, rendered).with_context(|| format!("writing {}", path.display()))?
+        }
+        None => print!("{rendered}"),
+    }
+    Ok(())
+}
+
+/// Normalizes every seed in `dir`, writing to `output` or in place.
+fn normalize(dir: &Path, output: Option<&Path>) -> Result<()> {
+    let entries = Corpus::open(dir)
+        .entries()
+        .with_context(|| format!("reading corpus {}", dir.display()))?;
+    for entry in &entries {
+        let dest = match output {
+            Some(out) => out.join(entry.path.strip_prefix(dir)?),
+            None => entry.path.clone(),
+        };
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let normalized = corpus::normalize(&entry.source);
+        fs::write(&dest, &normalized).with_context(|| format!("writing {}", dest.display()))?;
+        if let Some(metadata) = Metadata::load(&entry.path)? {
+            // Same seed, new text: it has to be verified again.
+            Metadata {
+                hash: meta::content_hash(&normalized),
+                verified_with: None,
+                ..metadata
+            }
+            .save(&dest)?;
+        }
+    }
+    eprintln!("normalized {} seeds", entries.len());
+    Ok(())
+}
+
+/// Copies a minimal subset of the seeds in `dir` to `output`. Features
+/// are the edges an instrumented rustc reports or, when it reports none,
+/// syntactic tags, plus whatever the harness finds.
+fn cmin(
+    dir: &Path,
+    output: &Path,
+    harness: Harness,
+    phase: Phase,
+    fix: bool,
+    targets: &[String],
+    map_size: usize,
+) -> Result<()> {
+    let entries = Corpus::open(dir)
+        .entries()
+        .with_context(|| format!("reading corpus {}", dir.display()))?;
+    let (features, instrumented) = seed_features(&entries, harness, phase, fix, targets, map_size)?;
+    let seeds: Vec<_> = entries
+        .iter()
+        .zip(features)
+        .map(|(entry, features)| (entry.source.as_str(), features))
+        .collect();
+    let kept = cmin::minimize(&seeds);
+    for &i in &kept {
+        let entry = &entries[i];
+        let dest = output.join(entry.path.strip_prefix(dir)?);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&dest, &entry.source).with_context(|| format!("writing {}", dest.display()))?;
+        if let Some(metadata) = Metadata::load(&entry.path)? {
+            metadata.save(&dest)?;
+        }
+    }
+    eprintln!(
+        "kept {} of {} seeds by {}",
+        kept.len(),
+        entries.len(),
+        if instrumented {
+            "edge coverage"
+        } else {
+            "feature tags"
+        }
+    );
+    Ok(())
+}
+
+/// Removes duplicate seeds from `dir`, keeping one of each group and
+/// merging the metadata of the rest into its sidecar.
+fn dedup(dir: &Path, keep: Keep, dry_run: bool) -> Result<()> {
+    let entries = Corpus::open(dir)
+        .entries()
+        .with_context(|| format!("reading corpus {}", dir.display()))?;
+    let mut removed = 0;
+    for group in dedup::duplicates(&entries) {
+        let kept = match keep {
+            Keep::Oldest => {
+                let mut modified = Vec::new();
+                for &i in &group {
+                    let path = &entries[i].path;
+                    let time = fs::metadata(path)
+                        .and_then(|metadata| metadata.modified())
+                        .with_context(|| format!("reading {}", path.display()))?;
+                    modified.push((time, i));
+                }
+                modified.into_iter().min().expect("groups are not empty").1
+            }
+            Keep::Smallest => *group
+                .iter()
+                .min_by_key(|&&i| (entries[i].source.len(), i))
+                .expect("groups are not empty"),
+        };
+        let entry = &entries[kept];
+        let mut metadata = Metadata::load(&entry.path)?;
+        for &i in group.iter().filter(|&&i| i != kept) {
+            let duplicate = &entries[i];
+            println!(
+                "{} duplicates {}",
+                duplicate.path.display(),
+                entry.path.display()
+            );
+            removed += 1;
+            if dry_run {
+                continue;
+            }
+            if let Some(merged) = Metadata::load(&duplicate.path)? {
+                metadata
+                    .get_or_insert_with(|| Metadata::new(&entry.source, Provenance::Unknown))
+                    .merge(merged);
+                fs::remove_file(meta::sidecar(&duplicate.path))?;
+            }
+            fs::remove_file(&duplicate.path)
+                .with_context(|| format!("removing {}", duplicate.path.display()))?;
+        }
+        if let (Some(metadata), false) = (metadata, dry_run) {
+            metadata.save(&entry.path)?;
+        }
+    }
+    eprintln!(
+        "{} {removed} of {} seeds",
+        if dry_run { "would remove" } else { "removed" },
+        entries.len()
+    );
+    Ok(())
+}
+
+/// Splits every seed into pieces and writes the ones that compile, with
+/// the seed's edition and flags, to `output`.
+fn split(paths: &[PathBuf], output: &Path, rustc: PathBuf, phase: Phase) -> Result<()> {
+    let rustc = Rustc {
+        phase,
+        ..Rustc::new(rustc)
+    };
+    let version = rustc_version(&rustc)?;
+    fs::create_dir_all(output).with_context(|| format!("creating {}", output.display()))?;
+    let seeds = load_seeds(paths)?;
+    let mut written = 0;
+    for entry in &seeds {
+        let parent =
+            Metadata::load(&entry.path)?.filter(|metadata| metadata.matches(&entry.source));
+        let (edition, flags) = parent
+            .map(|metadata| (metadata.edition, metadata.flags))
+            .unwrap_or_default();
+        let stem = entry.path.file_stem().unwrap_or_default().to_string_lossy();
+        let mut kept = 0;
+        for piece in corpus::split::split(&entry.source) {
+            let mut args = vec!["--crate-type=lib".to_owned()];
+            if let Some(edition) = &edition {
+                args.extend(["--edition".to_owned(), edition.clone()]);
+            }
+            args.extend(flags.iter().cloned());
+            args.push("-".to_owned());
+            if !rustc.run(&args, piece.source.as_bytes())?.is_success() {
+                continue;
+            }
+            let path = output.join(format!("{stem}-{kept}.rs"));
+            fs::write(&path, &piece.source)
+                .with_context(|| format!("writing {}", path.display()))?;
+            let provenance = Provenance::Split {
+                parent: entry.path.display().to_string(),
+                parent_hash: meta::content_hash(&entry.source),
+                focus: piece.focus,
+            };
+            Metadata {
+                edition: edition.clone(),
+                flags: flags.clone(),
+                verified_with: Some(version.clone()),
+                ..Metadata::new(&piece.source, provenance)
+            }
+            .save(&path)?;
+            kept += 1;
+        }
+        written += kept;
+    }
+    eprintln!("split {} seeds into {written} pieces", seeds.len());
+    Ok(())
+}
+
+fn features(dirs: &[PathBuf]) -> Result<()> {
+    let mut matrix = Matrix::new();
+    for dir in dirs {
+        let entries = Corpus::open(dir)
+            .entries()
+            .with_context(|| format!("reading corpus {}", dir.display()))?;
+        for entry in entries {
+            // Tags recorded for this content save parsing it again.
+            let tags = Metadata::load(&entry.path)?
+                .filter(|metadata| metadata.matches(&entry.source))
+                .map(|metadata| metadata.tags)
+                .unwrap_or_default();
+            if tags.is_empty() {
+                matrix.add(&entry.source);
+            } else {
+                matrix.add_features(Some(tagger::features(&tags).collect()));
+            }
+        }
+    }
+    print!("{matrix}");
+    Ok(())
+}
+
+/// Prints how many seeds of `dirs` use each generator production, and
+/// writes the weights derived from that to `output`.
+fn productions(dirs: &[PathBuf], output: Option<&Path>) -> Result<()> {
+    let mut counts = productions::Counts::new();
+    for dir in dirs {
+        let entries = Corpus::open(dir)
+            .entries()
+            .with_context(|| format!("reading corpus {}", dir.display()))?;
+        for entry in entries {
+            counts.add(&entry.source);
+        }
+    }
+    print!("{counts}");
+    if let Some(output) = output {
+        Weights::from_counts(&counts)
+            .save(output)
+            .with_context(|| format!("writing {}", output.display()))?;
+    }
+    Ok(())
+}
+
+/// Reduces the seeds of `dir` not reduced yet, then with an `interval`
+/// waits and does it again, forever.
+fn reduce(dir: &Path, mut check: Check, phase: Phase, interval: Option<Duration>) -> Result<()> {
+    let rustc = Rustc {
+        phase,
+        ..Rustc::default()
+    };
+    loop {
+        let entries = Corpus::open(dir)
+            .entries()
+            .with_context(|| format!("reading corpus {}", dir.display()))?;
+        let (mut reduced, mut before, mut after) = (0, 0, 0);
+        for entry in &entries {
+            let metadata = match Metadata::load(&entry.path)? {
+                Some(metadata) if metadata.matches(&entry.source) => metadata,
+                Some(stale) => Metadata {
+                    edition: stale.edition,
+                    flags: stale.flags,
+                    ..Metadata::new(&entry.source, stale.provenance)
+                },
+                None => Metadata::new(&entry.source, Provenance::Unknown),
+            };
+            if metadata.reduced {
+                continue;
+            }
+            let mut args = vec!["--crate-type=lib".to_owned()];
+            if let Some(edition) = &metadata.edition {
+                args.extend(["--edition".to_owned(), edition.clone()]);
+            }
+            args.extend(metadata.flags.iter().cloned());
+            args.push("-".to_owned());
+            let compiles = rustc.run(&args, entry.source.as_bytes())?.is_success();
+            let finds = check(&entry.source)?.is_some();
+            let source = corpus::reduce::reduce(&entry.source, |candidate| -> Result<bool> {
+                // Cheapest test first.
+                Ok(tagger::tags(candidate) == metadata.tags
+                    && rustc.run(&args, candidate.as_bytes())?.is_success() == compiles
+                    && check(candidate)?.is_some() == finds)
+            })?;
+            before += entry.source.len();
+            after += source.len();
+            if source != entry.source {
+                fs::write(&entry.path, &source)
+                    .with_context(|| format!("writing {}", entry.path.display()))?;
+                reduced += 1;
+            }
+            Metadata {
+                hash: meta::content_hash(&source),
+                features: meta::feature_gates(&source),
+                reduced: true,
+                // Measured on the seed before reduction.
+                compile_ms: None,
+                peak_rss: None,
+                ..metadata
+            }
+            .save(&entry.path)?;
+        }
+        eprintln!("reduced {reduced} seeds, {before} bytes to {after}");
+        let Some(interval) = interval else {
+            return Ok(());
+        };
+        std::thread::sleep(interval);
+    }
+}
+
+fn stats(dirs: &[PathBuf], findings: Option<&Path>, json: bool) -> Result<()> {
+    let mut stats = Stats::new();
+    for dir in dirs {
+        let entries = Corpus::open(dir)
+            .entries()
+            .with_context(|| format!("reading corpus {}", dir.display()))?;
+        for entry in entries {
+            let metadata = Metadata::load(&entry.path)?;
+            stats.add(
+                &entry.path.display().to_string(),
+                &entry.source,
+                metadata.as_ref(),
+            );
+        }
+    }
+    if let Some(dir) = findings {
+        for path in Corpus::open(dir)
+            .paths()
+            .with_context(|| format!("reading findings {}", dir.display()))?
+        {
+            if let Some(metadata) = Metadata::load(&path)? {
+                stats.add_finding(&metadata);
+            }
+        }
+    }
+    stats.finish();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&stats)?);
+    } else {
+        print!("{stats}");
+    }
+    Ok(())
+}
+
+/// Compiles every seed in `dir` and records which rustc accepted it.
+/// Metadata recorded for other content is refreshed first.
+/// Verifies every seed of `dir` compiles, recording the compiler and the
+/// compile time and peak RSS. Seeds that compiled with an earlier compiler
+/// and are rejected now go to `quarantine` if there is one; ICEs, crashes,
+/// timeouts and memory exhaustion are verdicts on the compiler, not the
+/// seed, and stay.
+fn verify(dir: &Path, rustc: PathBuf, phase: Phase, quarantine_dir: Option<&Path>) -> Result<()> {
+    let rustc = Rustc {
+        phase,
+        ..Rustc::new(rustc)
+    };
+    let version = rustc_version(&rustc)?;
+    let verification = verify::verify(dir, &rustc, &version, quarantine_dir)?;
+    for (path, outcome) in &verification.oom {
+        eprintln!("{}: {outcome}", path.display());
+    }
+    for (path, last, error) in &verification.stale {
+        eprintln!(
+            "{}: compiled with {last}, rejected now: {error}",
+            path.display()
+        );
+    }
+    eprintln!(
+        "{} of {} seeds compile with {version}",
+        verification.verified, verification.seeds
+    );
+    let (oom, stale) = (verification.oom.len(), verification.stale.len());
+    if oom > 0 {
+        eprintln!("{oom} ran out of memory");
+    }
+    if stale > 0 {
+        match quarantine_dir {
+            Some(quarantine_dir) => eprintln!(
+                "{stale} seeds no longer compile; moved to {}",
+                quarantine_dir.display()
+            ),
+            None => {
+                eprintln!("{stale} seeds no longer compile; pass --quarantine DIR to move them")
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Writes the variants [`Migrator::migrate_corpus`] makes of the seeds of
+/// `dir` up to edition `to` under `output`, or beside them.
+fn migrate(
+    dir: &Path,
+    to: &str,
+    output: Option<&Path>,
+    rustc: PathBuf,
+    phase: Phase,
+) -> Result<()> {
+    anyhow::ensure!(
+        migrate::EDITIONS.contains(&to),
+        "unknown edition {to}; expected one of {}",
+        migrate::EDITIONS.join(", ")
+    );
+    let migrator = Migrator {
+        driver: Rustc {
+            phase,
+            ..Rustc::new(rustc)
+        },
+        ..Migrator::default()
+    };
+    let (written, seeds) = migrator.migrate_corpus(dir, to, output.unwrap_or(dir))?;
+    eprintln!("wrote {written} variants of {seeds} seeds");
+    Ok(())
+}
+
+/// Writes `paths` to `output` in `format`. With `flatten`, module files
+/// that go into another seed are not exported on their own.
+fn export(
+    paths: &[PathBuf],
+    format: Format,
+    output: &Path,
+    flatten: bool,
+    max_len: Option<usize>,
+) -> Result<()> {
+    let mut seeds = load_seeds(paths)?;
+    if flatten {
+        let mut modules = HashSet::new();
+        for entry in &mut seeds {
+            let flattened = export::flatten(&entry.path, &entry.source)
+                .with_context(|| format!("flattening {}", entry.path.display()))?;
+            if let Some(flattened) = flattened {
+                modules.extend(
+                    flattened
+                        .files
+                        .iter()
+                        .filter_map(|file| fs::canonicalize(file).ok()),
+                );
+                entry.source = flattened.source;
+            }
+        }
+        seeds.retain(|entry| {
+            fs::canonicalize(&entry.path).map_or(true, |path| !modules.contains(&path))
+        });
+    }
+    fs::create_dir_all(output).with_context(|| format!("creating {}", output.display()))?;
+    let mut written = HashSet::new();
+    let mut too_long = 0;
+    for entry in &seeds {
+        if max_len.is_some_and(|max| entry.source.len() > max) {
+            too_long += 1;
+            continue;
+        }
+        let name = entry
+            .path
+            .file_name()
+            .map(|name| name.to_string_lossy())
+            .unwrap_or_default();
+        let file = format.file_name(written.len(), &name, &entry.source);
+        if !written.insert(file.clone()) {
+            continue;
+        }
+        let path = output.join(file);
+        fs::write(&path, &entry.source).with_context(|| format!("writing {}", path.display()))?;
+    }
+    eprintln!(
+        "exported {} of {} seeds for {format}",
+        written.len(),
+        seeds.len()
+    );
+    if too_long > 0 {
+        eprintln!(
+            "skipped {too_long} seeds over {} bytes",
+            max_len.unwrap_or(0)
+        );
+    }
+    Ok(())
+}
+
+/// Copies the standalone tests of a UI suite to `output`, with metadata.
+fn import_ui(suite: &Path, output: &Path) -> Result<()> {
+    let seeds =
+        ui::import(suite).with_context(|| format!("reading UI suite {}", suite.display()))?;
+    for seed in &seeds {
+        let dest = output.join(&seed.path);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&dest, &seed.source).with_context(|| format!("writing {}", dest.display()))?;
+        seed.metadata.save(&dest)?;
+    }
+    eprintln!("imported {} tests", seeds.len());
+    Ok(())
+}
+
+/// Copies glacier's reproducers to `output`, with metadata.
+fn import_glacier(repo: &Path, output: &Path) -> Result<()> {
+    let reproducers = glacier::import(repo)
+        .with_context(|| format!("reading glacier checkout {}", repo.display()))?;
+    for reproducer in &reproducers {
+        let dest = output.join(&reproducer.path);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&dest, &reproducer.source)
+            .with_context(|| format!("writing {}", dest.display()))?;
+        reproducer.metadata.save(&dest)?;
+    }
+    let fixed = reproducers.iter().filter(|r| r.fixed).count();
+    eprintln!(
+        "imported {} open and {fixed} fixed ICEs",
+        reproducers.len() - fixed
+    );
+    Ok(())
+}
+
+/// Adds the fragments of `paths` that compile to the library at `output`.
+/// Seeds that need flags are skipped, since fragments do not record any;
+/// seeds without a recorded edition are tried at 2021.
+fn library(paths: &[PathBuf], output: &Path, rustc: PathBuf, phase: Phase) -> Result<()> {
+    let rustc = Rustc {
+        phase,
+        ..Rustc::new(rustc)
+    };
+    let mut library = if output.exists() {
+        load_library(output)?
+    } else {
+        Library::default()
+    };
+    let before = library.len();
+    let seeds = load_seeds(paths)?;
+    for entry in &seeds {
+        let metadata =
+            Metadata::load(&entry.path)?.filter(|metadata| metadata.matches(&entry.source));
+        let (edition, flags) = metadata
+            .map(|metadata| (metadata.edition, metadata.flags))
+            .unwrap_or_default();
+        if !flags.is_empty() {
+            continue;
+        }
+        let edition = edition.unwrap_or_else(|| "2021".to_owned());
+        for fragment in library::extract(&entry.source) {
+            if library.contains(&fragment.source) {
+                continue;
+            }
+            let args = ["--crate-type=lib", "--edition", &edition, "-"];
+            if !rustc.run(args, fragment.source.as_bytes())?.is_success() {
+                continue;
+            }
+            library.add(library::Fragment {
+                edition: Some(edition.clone()),
+                origin: entry.path.display().to_string(),
+                ..fragment
+            });
+        }
+    }
+    library
+        .save(output)
+        .with_context(|| format!("writing {}", output.display()))?;
+    eprintln!(
+        "added {} fragments from {} seeds, {} in the library",
+        library.len() - before,
+        seeds.len(),
+        library.len()
+    );
+    Ok(())
+}
+
+fn pack(dir: &Path, output: &Path, zstd: Zstd) -> Result<()> {
+    let count = archive::pack(dir, output, zstd)
+        .with_context(|| format!("packing {} into {}", dir.display(), output.display()))?;
+    eprintln!("packed {count} seeds into {}", output.display());
+    Ok(())
+}
+
+fn unpack(archive: &Path, output: &Path, zstd: Zstd) -> Result<()> {
+    let count = archive::unpack(archive, output, zstd)
+        .with_context(|| format!("unpacking {} into {}", archive.display(), output.display()))?;
+    eprintln!("unpacked {count} seeds into {}", output.display());
+    Ok(())
+}
+
+fn sync(syncer: &Syncer, dir: &Path, json: bool) -> Result<()> {
+    let report = syncer
+        .sync(dir)
+        .with_context(|| format!("syncing {}", dir.display()))?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+    let (pushed, pulled) = if syncer.dry_run {
+        ("would push", "would pull")
+    } else {
+        ("pushed", "pulled")
+    };
+    for hash in &report.pushed {
+        println!("{pushed} {hash}");
+    }
+    for hash in &report.pulled {
+        println!("{pulled} {hash}");
+    }
+    for hash in &report.corrupt {
+        eprintln!("dropped {hash}: its content does not match its hash");
+    }
+    eprintln!(
+        "{} local, {} remote; {pushed} {}, {pulled} {}",
+        report.local,
+        report.remote,
+        report.pushed.len(),
+        report.pulled.len()
+    );
+    Ok(())
+}
+
+/// What `rustc -V` prints.
+fn rustc_version(rustc: &Rustc) -> Result<String> {
+    match rustc.run_program(&rustc.path, ["-V"], &[])? {
+        ExecOutcome::Success(output) => Ok(output.stdout.trim().to_owned()),
+        outcome => anyhow::bail!("{} -V: {outcome}", rustc.path.display()),
+    }
+}
+
+/// Harvests `crates` from crates.io and the `.crate` files in `archives`
+/// into `output`, with metadata naming where each fragment came from.
+fn harvest(
+    harvester: &Harvester,
+    crates: &[CrateVersion],
+    archives: &[PathBuf],
+    output: &Path,
+) -> Result<()> {
+    anyhow::ensure!(
+        !crates.is_empty() || !archives.is_empty(),
+        "nothing to harvest: pass --top, --random or --archive"
+    );
+    let version = rustc_version(&harvester.driver)?;
+    let scratch = harvester
+        .driver
+        .scratch_root
+        .join(format!("rustc-fuzz-harvest-{}", std::process::id()));
+    fs::create_dir_all(&scratch).with_context(|| format!("creating {}", scratch.display()))?;
+    let harvested = harvester.harvest_into(crates, archives, &scratch, output, &version);
+    fs::remove_dir_all(&scratch)?;
+    let harvest = harvested.with_context(|| format!("harvesting into {}", output.display()))?;
+    for (krate, e) in &harvest.skipped {
+        eprintln!("skipping {}@{}: {e}", krate.name, krate.version);
+    }
+    for (krate, fragments) in &harvest.crates {
+        eprintln!("{}@{}: {fragments} fragments", krate.name, krate.version);
+    }
+    eprintln!(
+        "harvested {} seeds from {} crates",
+        harvest.seeds(),
+        harvest.crates.len()
+    );
+    Ok(())
+}
+
+/// Runs `entries` through a harness and describes each by the edges an
+/// instrumented rustc reports or, when it reports none for any seed, by
+/// syntactic tags, plus whatever the harness finds. Also returns whether
+/// the features are edges.
+fn seed_features(
+    entries: &[Entry],
+    harness: Harness,
+    phase: Phase,
+    fix: bool,
+    targets: &[String],
+    map_size: usize,
+) -> Result<(Vec<BTreeSet<String>>, bool)> {
+    let mut edges = EdgeMap::new(map_size).context("creating the coverage map")?;
+    // Inherited by every rustc the harness starts; compiled seeds run
+    // with an empty environment.
+    std::env::set_var("__AFL_SHM_ID", edges.id());
+    let mut check = checker(harness, phase, fix, targets, &[])?;
+    let mut runs = Vec::new();
+    for entry in entries {
+        let finding = check(&entry.source)?;
+        runs.push((edges.take(), finding));
+    }
+    let instrumented = runs.iter().any(|(edges, _)| !edges.is_empty());
+    let features = entries
+        .iter()
+        .zip(runs)
+        .map(|(entry, (edges, finding))| {
+            let mut features = if instrumented {
+                edges
+            } else {
+                cmin::feature_tags(&entry.source)
+            };
+            features.extend(finding.map(|(_, finding)| format!("finding {finding}")));
+            features
+        })
+        .collect();
+    Ok((features, instrumented))
+}
+
+/// Adds to `output` the seeds from `dirs` that bring a feature none of
+/// its seeds, nor a smaller seed merged before, has.
+fn merge(
+    dirs: &[PathBuf],
+    output: &Path,
+    harness: Harness,
+    phase: Phase,
+    fix: bool,
+    targets: &[String],
+    map_size: usize,
+) -> Result<()> {
+    let existing = if output.is_dir() {
+        Corpus::open(output)
+            .entries()
+            .with_context(|| format!("reading corpus {}", output.display()))?
+    } else {
+        Vec::new()
+    };
+    let candidates = load_seeds(dirs)?;
+    let mut entries = existing.clone();
+    entries.extend(candidates.iter().cloned());
+    let (mut features, instrumented) =
+        seed_features(&entries, harness, phase, fix, targets, map_size)?;
+    let candidate_features = features.split_off(existing.len());
+    let seeds: Vec<_> = candidates
+        .iter()
+        .zip(candidate_features)
+        .map(|(entry, features)| (entry.source.as_str(), features))
+        .collect();
+    let kept = cmin::merge(&features, &seeds);
+    fs::create_dir_all(output).with_context(|| format!("creating {}", output.display()))?;
+    for &i in &kept {
+        let entry = &candidates[i];
+        let dest = output.join(format!("{}.rs", meta::content_hash(&entry.source)));
+        fs::write(&dest, &entry.source).with_context(|| format!("writing {}", dest.display()))?;
+        if let Some(metadata) = Metadata::load(&entry.path)? {
+            metadata.save(&dest)?;
+        }
+    }
+    eprintln!(
+        "added {} of {} seeds to {} existing by {}",
+        kept.len(),
+        candidates.len(),
+        existing.len(),
+        if instrumented {
+            "edge coverage"
+        } else {
+            "feature tags"
+        }
+    );
+    Ok(())
+}
+
+/// Writes `count` mutants of `paths`, taken in turn, to `output`, each
+/// with the trace that replays it from its parent.
+fn mutate(
+    engine: &Engine,
+    paths: &[PathBuf],
+    count: usize,
+    seed: u64,
+    output: &Path,
+) -> Result<()> {
+    let seeds = load_seeds(paths)?;
+    anyhow::ensure!(!seeds.is_empty(), "no seeds to mutate");
+    fs::create_dir_all(output).with_context(|| format!("creating {}", output.display()))?;
+    let mut rng = StdRng::seed_from_u64(seed);
+    let mut written = 0;
+    for i in 0..count {
+        let parent = &seeds[i % seeds.len()];
+        let Some(mutated) = engine.mutate(&parent.source, &mut rng) else {
+            eprintln!("{}: no operator applies", parent.path.display());
+            continue;
+        };
+        let provenance = Provenance::Mutated {
+            parent: parent.path.display().to_string(),
+            parent_hash: meta::content_hash(&parent.source),
+            trace: Trace {
+                steps: vec![mutated.step()],
+            },
+        };
+        let mut metadata = Metadata::new(&mutated.output, provenance);
+        if let Some(inherited) = Metadata::load(&parent.path)?.filter(|m| m.matches(&parent.source))
+        {
+            metadata.edition = inherited.edition;
+            metadata.flags = inherited.flags;
+        }
+        let path = output.join(format!("{}.rs", metadata.hash));
+        fs::write(&path, &mutated.output).with_context(|| format!("writing {}", path.display()))?;
+        metadata
+            .save(&path)
+            .with_context(|| format!("writing metadata for {}", path.display()))?;
+        println!(
+            "{}: {} of {}",
+            path.display(),
+            mutated.operator,
+            parent.path.display()
+        );
+        written += 1;
+    }
+    eprintln!("wrote {written} mutants to {}", output.display());
+    Ok(())
+}
+
+fn replay(
+    engine: &Engine,
+    parent: &Path,
+    trace: &Path,
+    upto: Option<usize>,
+    check: Option<&Path>,
+    output: Option<PathBuf>,
+) -> Result<()> {
+    let source =
+        fs::read_to_string(parent).with_context(|| format!("reading {}", parent.display()))?;
+    let trace: Trace = serde_json::from_str(
+        &fs::read_to_string(trace).with_context(|| format!("reading {}", trace.display()))?,
+    )
+    .context("parsing trace")?;
+    let steps = &trace.steps[..upto.unwrap_or(trace.steps.len()).min(trace.steps.len())];
+    let child = engine.replay(&source, steps)?;
+    if let Some(expected) = check {
+        let expected = fs::read_to_string(expected)?;
+        anyhow::ensure!(
+            child == expected,
+            "replayed input differs from {}",
+            check.unwrap().display()
+        );
+    }
+    match output {
+        Some(path) => {
+            fs::write(&path, &child)?;
+            let provenance = Provenance::Mutated {
+                parent: parent.display().to_string(),
+                parent_hash: meta::content_hash(&source),
+                trace: Trace {
+                    steps: steps.to_vec(),
+                },
+            };
+            let mut metadata = Metadata::new(&child, provenance);
+            if let Some(inherited) = Metadata::load(parent)? {
+                metadata.edition = inherited.edition;
+                metadata.flags = inherited.flags;
+            }
+            metadata.save(&path)?;
+        }
+        None => print!("{child}"),
+    }
+    Ok(())
+}
+
+fn load_library(path: &Path) -> Result<Library> {
+    Library::load(path).with_context(|| format!("reading fragment library {}", path.display()))
+}
+
+/// Loads seed files and every seed under corpus directories.
+fn load_seeds(paths: &[PathBuf]) -> Result<Vec<Entry>> {
+    let mut seeds = Vec::new();
+    for path in paths {
+        if path.is_dir() {
+            seeds.extend(
+                Corpus::open(path)
+                    .entries()
+                    .with_context(|| format!("reading corpus {}", path.display()))?,
+            );
+        } else {
+            let source =
+                fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+            seeds.push(Entry {
+                path: path.clone(),
+                source,
+            });
+        }
+    }
+    Ok(seeds)
+}
+
+fn generate(
+    count: usize,
+    seed: u64,
+    level: Level,
+    constant: bool,
+    library: Option<&Path>,
+    weights: Option<&Path>,
+    output: &Path,
+) -> Result<()> {
+    let mut gen = ProgramGen::default();
+    gen.gen.level = level;
+    let mut library_hash = None;
+    if let Some(path) = library {
+        let json =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        library_hash = Some(meta::content_hash(&json));
+        gen.library = Some(Arc::new(load_library(path)?));
+    }
+    let mut weights_hash = None;
+    if let Some(path) = weights {
+        let json =
+            fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+        weights_hash = Some(meta::content_hash(&json));
+        let weights: Weights = serde_json::from_str(&json)
+            .with_context(|| format!("reading weights {}", path.display()))?;
+        gen.gen.weights = Some(Arc::new(weights));
+    }
+    generated::write(
+        output,
+        "gen",
+        count,
+        seed,
+        level,
+        |rng| {
+            if constant {
+                gen.generate_const(rng)
+            } else {
+                gen.generate(rng)
+            }
+        },
+        |index| Provenance::Generated {
+            seed,
+            index,
+            level: level.name().to_owned(),
+            constant,
+            library: library_hash.clone(),
+            weights: weights_hash.clone(),
+            generator: None,
+        },
+    )
+    .with_context(|| format!("writing programs to {}", output.display()))
+}
+
+/// Writes `count` programs from the built-in or plugin generator called
+/// `name`.
+fn generate_plugin(name: &str, count: usize, seed: u64, level: Level, output: &Path) -> Result<()> {
+    let builtin = plugin::builtin_generators(level);
+    let gen = plugin::installed()
+        .and_then(|registry| registry.generator(name))
+        .or_else(|| {
+            builtin
+                .iter()
+                .map(|gen| &**gen)
+                .find(|gen| gen.name() == name)
+        })
+        .with_context(|| format!("no generator is called {name:?}"))?;
+    // Provenance records the level the generator wrote for, so a
+    // `--level` it would ignore is refused rather than recorded.
+    if gen.level() != level {
+        anyhow::bail!(
+            "the {name} generator writes for Rust {}, not --level {level}",
+            gen.level()
+        );
+    }
+    generated::write(
+        output,
+        name,
+        count,
+        seed,
+        level,
+        |rng| gen.generate(rng),
+        |index| Provenance::Generated {
+            seed,
+            index,
+            level: level.name().to_owned(),
+            constant: false,
+            library: None,
+            weights: None,
+            generator: Some(name.to_owned()),
+        },
+    )
+    .with_context(|| format!("writing programs to {}", output.display()))
+}
+
+fn instantiate(
+    templates: &[PathBuf],
+    budget: usize,
+    seed: u64,
+    level: Level,
+    output: &Path,
+) -> Result<()> {
+    let counts = generated::instantiate(templates, budget, seed, level, output)
+        .with_context(|| format!("instantiating templates into {}", output.display()))?;
+    for (path, (holes, programs)) in templates.iter().zip(counts) {
+        println!(
+            "{}: {holes} holes, {programs} instantiations",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Runs the seeds under `paths` through the harnesses and oracles called
+/// `names`, set up by `checker`, and reports what they find. Once `budget`
+/// is spent no more seeds are started; the report covers those that were.
+fn run(
+    names: &[String],
+    checker: impl Fn(&str) -> Result<Check>,
+    pool: &PoolArgs,
+    paths: &[PathBuf],
+    budget: Budget,
+) -> Result<()> {
+    let seeds = load_seeds(paths)?;
+    let run = check::run(&seeds, names, checker, &pool.options()?, budget)?;
+    if let Some(exhausted) = run.exhausted {
+        eprintln!("{exhausted}; finishing the seeds already started");
+    }
+    for (path, finding, issues) in &run.known {
+        let issues: Vec<String> = issues
+            .iter()
+            .map(|issue| format!("rust-lang/rust#{issue}"))
+            .collect();
+        eprintln!(
+            "{}: {finding} (known: {})",
+            path.display(),
+            issues.join(", ")
+        );
+    }
+    for (severity, path, finding) in &run.findings {
+        println!("{}: {severity}: {finding}", path.display());
+    }
+    eprintln!(
+        "{} of {} seeds produced new findings, {} known ones",
+        run.findings.len(),
+        run.started,
+        run.known.len()
+    );
+    if run.started < seeds.len() {
+        eprintln!("{} seeds were not run", seeds.len() - run.started);
+    }
+    Ok(())
+}
+
+/// Runs [`fuzz::fuzz`], printing findings as they come and a summary.
+fn fuzz(
+    engine: &Engine,
+    library: Option<&Path>,
+    check: Check,
+    state: Checkpoint,
+    every: Option<Duration>,
+    budget: Budget,
+) -> Result<()> {
+    if !state.seeds.is_empty() {
+        eprintln!(
+            "resuming at iteration {} of {}",
+            state.done, state.iterations
+        );
+    }
+    let finished = fuzz::fuzz(
+        engine,
+        library,
+        check,
+        state,
+        every,
+        budget,
+        |path, severity, finding| println!("{}: {severity}: {finding}", path.display()),
+    )?;
+    let state = &finished.state;
+    if let Some(exhausted) = finished.exhausted {
+        eprintln!(
+            "{exhausted}; stopped after {} of {} iterations",
+            state.done, state.iterations
+        );
+    }
+    eprintln!(
+        "{} iterations by {}: {} new findings, {} known ones, {} seeds added",
+        state.done,
+        state.scheduler.policy(),
+        state.found,
+        state.known,
+        state.added
+    );
+    Ok(())
+}
+
+/// Runs [`fuzz::replay`] and says how far it got.
+fn replay_fuzz(
+    dir: &Path,
+    library: Option<&Path>,
+    upto: Option<u64>,
+    inputs: Option<&Path>,
+) -> Result<()> {
+    let replayed = fuzz::replay(dir, library, upto, inputs)?;
+    eprintln!(
+        "replayed {} of {} iterations from seed {}: all match",
+        replayed.replayed, replayed.iterations, replayed.seed
+    );
+    Ok(())
+}
+
+/// Serves the seeds under `paths` to workers until they are done.
+fn coordinate(coordinator: &Coordinator, listen: &str, paths: &[PathBuf]) -> Result<()> {
+    let _campaign = tracing::info_span!(
+        "campaign",
+        command = "coordinate",
+        harness = %coordinator.harness,
+        findings = %coordinator.findings.display(),
+    )
+    .entered();
+    let seeds = load_seeds(paths)?;
+    let listener = TcpListener::bind(listen).with_context(|| format!("listening on {listen}"))?;
+    eprintln!(
+        "serving {} seeds for the {} harness on {listen}",
+        seeds.len(),
+        coordinator.harness
+    );
+    let status = coordinator.serve(listener, seeds)?;
+    eprintln!(
+        "{} jobs done; {} findings, {} kept; {} known",
+        status.reported, status.findings, status.kept, status.known
+    );
+    Ok(())
+}
+
+/// Runs jobs from the coordinator at `addr` until it runs out.
+fn work(addr: &str, phase: Phase, fix: bool, targets: &[String], flags: &[String]) -> Result<()> {
+    let _campaign = tracing::info_span!("campaign", command = "work", coordinator = addr).entered();
+    let mut checkers: HashMap<String, Check> = HashMap::new();
+    let done = Worker::new(addr).run(|harness: &str, source: &str| -> Result<_> {
+        if !checkers.contains_key(harness) {
+            let parsed = Harness::from_str(harness, false)
+                .map_err(|e| anyhow::anyhow!("coordinator asked for {e}"))?;
+            checkers.insert(
+                harness.to_owned(),
+                checker(parsed, phase, fix, targets, flags)?,
+            );
+        }
+        checkers.get_mut(harness).expect("just inserted")(source)
+    })?;
+    eprintln!("{done} jobs done");
+    Ok(())
+}
+
+/// Files the findings in `dir` that are not in its buckets yet by the
+/// signature of the crash they reproduce, and lists the buckets. New
+/// buckets are announced unless the known-bug databases of `dir` and
+/// `shared` know their signature.
+fn buckets(
+    dir: &Path,
+    rustc: PathBuf,
+    phase: Phase,
+    shared: &[PathBuf],
+    runs: u32,
+    mut search: Option<IssueSearch>,
+    json: bool,
+) -> Result<()> {
+    let filer = Filer {
+        runs,
+        ..Filer::new(Rustc {
+            phase,
+            ..Rustc::new(rustc)
+        })
+    };
+    let mut buckets =
+        Buckets::load(dir).with_context(|| format!("reading buckets of {}", dir.display()))?;
+    let known = known_bugs(dir, shared)?;
+    let filing = filer
+        .file(dir, &mut buckets, &known, search.as_mut())
+        .with_context(|| format!("filing findings of {}", dir.display()))?;
+    for (path, outcome) in &filing.gone {
+        eprintln!("{}: does not crash any more: {outcome}", path.display());
+    }
+    for (path, flake) in &filing.flaky {
+        eprintln!("{}: nondeterministic: {flake}", path.display());
+    }
+    for id in &filing.new {
+        let bucket = buckets.get(id).expect("filed");
+        eprintln!("new bucket {id}: {}", bucket.signature);
+        for candidate in &bucket.duplicates {
+            eprintln!("  duplicate of {candidate}? {}", candidate.url);
+        }
+        if let Some((_, err)) = filing.unsearched.iter().find(|(bucket, _)| bucket == id) {
+            eprintln!("  no duplicate search: {err}");
+        }
+    }
+    let known_buckets = buckets.mark_known(&known);
+    buckets
+        .save(dir)
+        .with_context(|| format!("writing buckets of {}", dir.display()))?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&buckets.list())?);
+    } else {
+        print!("{buckets}");
+    }
+    eprintln!(
+        "{} findings filed, {} in new buckets ({} of known bugs), {} \
+         nondeterministic; {} buckets, {known_buckets} of known bugs; {} findings do not \
+         reproduce",
+        filing.filed,
+        filing.new.len() + filing.new_known,
+        filing.new_known,
+        filing.flaky.len(),
+        buckets.len(),
+        filing.gone.len()
+    );
+    Ok(())
+}
+
+/// The known-bug database of findings directory `dir` merged with
+/// `shared`.
+fn known_bugs(dir: &Path, shared: &[PathBuf]) -> Result<KnownBugs> {
+    let path = dir.join(KnownBugs::FILE);
+    let mut known =
+        KnownBugs::load(&path).with_context(|| format!("reading {}", path.display()))?;
+    for path in shared {
+        anyhow::ensure!(path.exists(), "{} does not exist", path.display());
+        known.merge(KnownBugs::load(path).with_context(|| format!("reading {}", path.display()))?);
+    }
+    Ok(known)
+}
+
+/// Records bucket `id` of `dir` as reported at `issue`, in `db` or the
+/// directory's own known-bug database.
+fn known(dir: &Path, id: &str, issue: &str, db: Option<&Path>) -> Result<()> {
+    let mut buckets =
+        Buckets::load(dir).with_context(|| format!("reading buckets of {}", dir.display()))?;
+    let bucket = buckets
+        .get_mut(id)
+        .with_context(|| format!("{} has no bucket {id}", dir.display()))?;
+    let path = db.map_or_else(|| dir.join(KnownBugs::FILE), Path::to_path_buf);
+    let mut known =
+        KnownBugs::load(&path).with_context(|| format!("reading {}", path.display()))?;
+    if let Some(old) = known.add(bucket.signature.clone(), issue) {
+        eprintln!("{id} was known as {old}");
+    }
+    known
+        .save(&path)
+        .with_context(|| format!("writing {}", path.display()))?;
+    bucket.issue = Some(issue.to_owned());
+    buckets
+        .save(dir)
+        .with_context(|| format!("writing buckets of {}", dir.display()))?;
+    println!("{id}: {issue}");
+    Ok(())
+}
+
+/// Reduces every crasher in `paths` that is not a reduction itself and
+/// has none yet, writing the result beside it. A reduction in progress is
+/// kept beside it too, and picked up again if the last run was cut short.
+fn reduce_crashers(
+    paths: &[PathBuf],
+    rustc: PathBuf,
+    phase: Phase,
+    regressions: &Path,
+) -> Result<()> {
+    let driver = Rustc {
+        phase,
+        ..Rustc::new(rustc)
+    };
+    let entries = load_seeds(paths)?;
+    let (mut reduced, mut admitted) = (0, 0);
+    for entry in &entries {
+        let shrunk = match reduce_finding(&driver, entry)
+            .with_context(|| format!("reducing {}", entry.path.display()))?
+        {
+            Crasher::Reduced => continue,
+            Crasher::NoCrash => {
+                eprintln!("{}: does not crash", entry.path.display());
+                continue;
+            }
+            Crasher::Shrunk(shrunk) => shrunk,
+        };
+        let Shrunk {
+            output,
+            metadata,
+            signature,
+            reduction,
+            resumed,
+        } = *shrunk;
+        if let Some(progress) = resumed {
+            eprintln!(
+                "{}: resumed from {}",
+                entry.path.display(),
+                progress.display()
+            );
+        }
+        events::emit(EventKind::Reduced {
+            path: entry.path.display().to_string(),
+            output: output.display().to_string(),
+            signature: signature.id(),
+            before_bytes: entry.source.len(),
+            after_bytes: reduction.source.len(),
+            tests: reduction.tests,
+        })?;
+        println!(
+            "{}: {} -> {} bytes in {} tests: {signature}",
+            output.display(),
+            entry.source.len(),
+            reduction.source.len(),
+            reduction.tests
+        );
+        reduced += 1;
+        if regressions::admit(regressions, &reduction.source, &metadata, &signature)
+            .with_context(|| format!("adding {} to {}", output.display(), regressions.display()))?
+            .is_some()
+        {
+            admitted += 1;
+        }
+    }
+    eprintln!(
+        "reduced {reduced} of {} findings; {admitted} new in {}",
+        entries.len(),
+        regressions.display()
+    );
+    Ok(())
+}
+
+/// Compiles the regression corpus `dir` with `rustc` and lists the seeds
+/// whose crash was fixed or changed.
+fn recheck(dir: &Path, rustc: PathBuf, phase: Phase, json: bool) -> Result<()> {
+    let recheck = Recheck::new(Rustc {
+        phase,
+        ..Rustc::new(rustc)
+    });
+    let entries = Corpus::open(dir)
+        .entries()
+        .with_context(|| format!("reading regression corpus {}", dir.display()))?;
+    let mut changes = Vec::new();
+    let (mut crashes, mut inconclusive, mut unrecorded) = (0, 0, 0);
+    for entry in &entries {
+        let Some(metadata) = Metadata::load(&entry.path)
+            .with_context(|| format!("reading metadata for {}", entry.path.display()))?
+            .filter(|m| m.matches(&entry.source))
+        else {
+            unrecorded += 1;
+            continue;
+        };
+        let Some(status) = recheck
+            .check(&entry.source, &metadata)
+            .with_context(|| format!("compiling {}", entry.path.display()))?
+        else {
+            unrecorded += 1;
+            continue;
+        };
+        if let (regressions::Status::Fixed { outcome }, Some(signature)) =
+            (&status, &metadata.signature)
+        {
+            events::emit(EventKind::Fixed {
+                path: entry.path.display().to_string(),
+                signature: signature.id(),
+                outcome: outcome.clone(),
+            })?;
+        }
+        match status {
+            regressions::Status::Crashes => crashes += 1,
+            regressions::Status::Inconclusive => inconclusive += 1,
+            status => changes.push((entry.path.display().to_string(), metadata.signature, status)),
+        }
+    }
+    if json {
+        let changes: Vec<_> = changes
+            .iter()
+            .map(|(path, signature, status)| {
+                serde_json::json!({ "path": path, "signature": signature, "now": status })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&changes)?);
+    } else {
+        for (path, signature, status) in &changes {
+            if let Some(signature) = signature {
+                println!("{path}: {signature}\n    {status}");
+            }
+        }
+    }
+    eprintln!(
+        "{} of {} crashes fixed or changed, {crashes} still crash, {inconclusive} timed out; \
+         {unrecorded} seeds have no recorded signature",
+        changes.len(),
+        entries.len() - unrecorded
+    );
+    Ok(())
+}
+
+/// Prints `rows` as JSON lines, or as text with `text`.
+fn print_rows<T: serde::Serialize>(
+    rows: &[T],
+    json: bool,
+    text: impl Fn(&T) -> String,
+) -> Result<()> {
+    for row in rows {
+        if json {
+            println!("{}", serde_json::to_string(row)?);
+        } else {
+            println!("{}", text(row));
+        }
+    }
+    Ok(())
+}
+
+/// Runs a `db` command against `db`.
+fn query_db(db: &mut Database, command: DbCommand) -> Result<()> {
+    match command {
+        DbCommand::Import { logs } => {
+            for log in &logs {
+                let events =
+                    events::read(log).with_context(|| format!("reading {}", log.display()))?;
+                db.import(&events)
+                    .with_context(|| format!("importing {}", log.display()))?;
+                eprintln!("{}: {} events", log.display(), events.len());
+            }
+        }
+        DbCommand::Summary { json } => {
+            let summary = db.summary()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&summary)?);
+                return Ok(());
+            }
+            println!("{} executions", summary.executions);
+            for (harness, stats) in &summary.harnesses {
+                let mean = stats
+                    .mean_ms
+                    .map_or(String::new(), |ms| format!(", {ms:.0} ms on average"));
+                println!(
+                    "  {harness}: {} executions, {} findings{mean}",
+                    stats.executions, stats.findings
+                );
+            }
+            for (severity, count) in &summary.findings {
+                println!("{count} {severity} findings");
+            }
+            println!(
+                "{} seeds, {} buckets, {} reductions, {} bisections, {} fixes, {} fuzz runs",
+                summary.seeds,
+                summary.buckets,
+                summary.reductions,
+                summary.bisections,
+                summary.fixes,
+                summary.fuzz_runs
+            );
+        }
+        DbCommand::Executions {
+            harness,
+            input,
+            severity,
+            findings,
+            since_hours,
+            limit,
+            json,
+        } => {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_millis() as u64);
+            let query = ExecutionQuery {
+                harness,
+                input,
+                severity,
+                findings,
+                since_ms: since_hours.map(|hours| now.saturating_sub(hours * 3_600_000)),
+                limit,
+            };
+            print_rows(&db.executions(&query)?, json, |e| {
+                let elapsed = e.elapsed_ms.map_or(String::new(), |ms| format!(" {ms} ms"));
+                let finding = match (&e.severity, &e.finding) {
+                    (Some(severity), Some(finding)) => format!(": {severity}: {finding}"),
+                    (Some(severity), None) => format!(": {severity}"),
+                    _ => String::new(),
+                };
+                format!("{} {} {}{elapsed}{finding}", e.time_ms, e.harness, e.input)
+            })?;
+        }
+        DbCommand::Seeds { json } => print_rows(&db.seeds()?, json, |s| match &s.parent {
+            Some(parent) => format!("{} {} (from {parent})", s.hash, s.path),
+            None => format!("{} {}", s.hash, s.path),
+        })?,
+        DbCommand::Buckets { json } => print_rows(&db.buckets()?, json, |b| {
+            let severity = b.severity.map_or("-".to_owned(), |s| s.to_string());
+            format!("{severity} {} ({})", b.bucket, b.reproducer)
+        })?,
+        DbCommand::Reductions { json } => print_rows(&db.reductions()?, json, |r| {
+            format!(
+                "{} -> {}: {} -> {} bytes in {} tests",
+                r.path, r.output, r.before_bytes, r.after_bytes, r.tests
+            )
+        })?,
+        DbCommand::Bisections { json } => print_rows(&db.bisections()?, json, |b| {
+            format!("{}: {}", b.finding, b.bisection)
+        })?,
+        DbCommand::Fixes { json } => print_rows(&db.fixes()?, json, |f| {
+            format!("{}: fixed, now {}", f.path, f.outcome)
+        })?,
+        DbCommand::Runs { json } => print_rows(&db.fuzz_runs()?, json, |r| {
+            format!(
+                "{}: {} iterations by {} from seed {}: {} new findings in {} buckets, {} seeds added",
+                r.findings, r.iterations, r.policy, r.seed, r.found, r.buckets, r.added
+            )
+        })?,
+        DbCommand::Policies { json } => {
+            let policies = db.policies()?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&policies)?);
+                return Ok(());
+            }
+            println!("per 1000 iterations: buckets, findings, seeds added; iterations/s");
+            for (policy, stats) in &policies {
+                println!(
+                    "{policy}: {} runs, {} iterations: {:.2} {:.2} {:.2}; {:.1}/s",
+                    stats.runs,
+                    stats.iterations,
+                    stats.per_thousand(stats.buckets),
+                    stats.per_thousand(stats.found),
+                    stats.per_thousand(stats.added),
+                    stats.rate()
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Installs the toolchains of `specs` and prints each one's rustc.
+/// Nightlies of a range that were never published are reported and
+/// skipped.
+fn install_toolchains(toolchains: &Toolchains, specs: &[String]) -> Result<()> {
+    for spec in specs {
+        let spec: Spec = spec.parse()?;
+        let names = toolchains
+            .resolve(&spec)
+            .with_context(|| format!("resolving toolchain {spec}"))?;
+        let range = matches!(spec, Spec::Range(..));
+        for name in &names {
+            if range && !toolchains.install(name)? {
+                eprintln!("{name}: not available");
+                continue;
+            }
+            let rustc = toolchains
+                .ensure(name)
+                .with_context(|| format!("installing {name}"))?;
+            println!("{name}\t{}", rustc.display());
+        }
+    }
+    Ok(())
+}
+
+fn list_toolchains(toolchains: &Toolchains, json: bool) -> Result<()> {
+    let state = toolchains
+        .state()
+        .with_context(|| format!("reading {}", toolchains.store.display()))?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&state)?);
+        return Ok(());
+    }
+    let latest = state.latest.as_ref().map(|latest| &latest.toolchain);
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs());
+    for (name, installed) in &state.installed {
+        let mut notes = vec![
+            installed.source.to_string(),
+            format!(
+                "used {} days ago",
+                now.saturating_sub(installed.used) / 86_400
+            ),
+        ];
+        if installed.pinned {
+            notes.push("pinned".to_owned());
+        }
+        if Some(name) == latest {
+            notes.push("latest".to_owned());
+        }
+        println!("{name}\t{}", notes.join(", "));
+    }
+    Ok(())
+}
+
+fn pin_toolchains(toolchains: &Toolchains, names: &[String], pinned: bool) -> Result<()> {
+    for name in names {
+        anyhow::ensure!(
+            toolchains.set_pinned(name, pinned)?,
+            "{name} was not installed by rustc-fuzz; install it with `toolchains install {name}`"
+        );
+    }
+    Ok(())
+}
+
+fn gc_toolchains(
+    toolchains: &Toolchains,
+    keep: &[String],
+    max_age: Duration,
+    dry_run: bool,
+) -> Result<()> {
+    let mut kept = Vec::new();
+    for spec in keep {
+        // The latest nightly is always kept, and resolving it could
+        // install a newer one.
+        match spec.parse()? {
+            Spec::Latest => {}
+            spec => kept.extend(toolchains.resolve(&spec)?),
+        }
+    }
+    let removed = toolchains
+        .gc(&kept, max_age, dry_run)
+        .with_context(|| format!("collecting {}", toolchains.store.display()))?;
+    for name in &removed {
+        println!("{name}");
+    }
+    eprintln!(
+        "{} {} toolchains",
+        if dry_run { "would remove" } else { "removed" },
+        removed.len()
+    );
+    Ok(())
+}
+
+/// Bisects the crash `finding` causes, recording the result in the
+/// buckets of `dir` if the finding is filed there. Its signature is its
+/// bucket's, or the one `end` crashes with.
+fn bisect(
+    finding: &Path,
+    dir: &Path,
+    mut bisector: Bisector,
+    start: Option<Date>,
+    end: Date,
+    rust_repo: Option<&Path>,
+) -> Result<()> {
+    if let Some(start) = start {
+        anyhow::ensure!(start < end, "--start {start} is not before --end {end}");
+    }
+    let source =
+        fs::read_to_string(finding).with_context(|| format!("reading {}", finding.display()))?;
+    let metadata = Metadata::load(finding)
+        .with_context(|| format!("reading metadata for {}", finding.display()))?
+        .filter(|m| m.matches(&source));
+    if let Some(metadata) = &metadata {
+        if let Some(edition) = &metadata.edition {
+            bisector
+                .args
+                .extend(["--edition".to_owned(), edition.clone()]);
+        }
+        bisector.args.extend(metadata.flags.iter().cloned());
+    }
+    let mut buckets =
+        Buckets::load(dir).with_context(|| format!("reading buckets of {}", dir.display()))?;
+    let name = finding
+        .strip_prefix(dir)
+        .unwrap_or(finding)
+        .display()
+        .to_string();
+    let id = buckets.bucket_of(&name).map(|bucket| bucket.id.clone());
+    let signature = match id.as_deref().and_then(|id| buckets.get(id)) {
+        Some(bucket) => bucket.signature.clone(),
+        None => bisector
+            .signature(&end.toolchain(), &source)?
+            .with_context(|| format!("{} does not crash {}", end.toolchain(), finding.display()))?,
+    };
+    eprintln!("bisecting {signature}");
+    let mut bisection = bisector.nightlies(&source, &signature, start, end)?;
+    if let Some(repo) = rust_repo {
+        bisector
+            .commits(&source, &signature, repo, &mut bisection)
+            .context("bisecting merge commits")?;
+    }
+    println!("{}: {bisection}", finding.display());
+    events::emit(EventKind::Bisected {
+        finding: finding.display().to_string(),
+        bisection: bisection.clone(),
+    })?;
+    match id.as_deref().and_then(|id| buckets.get_mut(id)) {
+        Some(bucket) => {
+            bucket.bisection = Some(bisection);
+            buckets
+                .save(dir)
+                .with_context(|| format!("writing buckets of {}", dir.display()))?;
+        }
+        None => eprintln!(
+            "{} is not in a bucket of {}; run `triage buckets` to file it",
+            finding.display(),
+            dir.display()
+        ),
+    }
+    Ok(())
+}
+
+/// Writes an issue report on each bucket of `dir` to `output`.
+fn report(args: &ReportArgs) -> Result<()> {
+    let (dir, output) = (args.findings.as_path(), args.output.as_path());
+    let driver = Rustc {
+        phase: args.phase,
+        ..Rustc::new(args.rustc.clone())
+    };
+    let buckets =
+        Buckets::load(dir).with_context(|| format!("reading buckets of {}", dir.display()))?;
+    anyhow::ensure!(
+        !buckets.is_empty(),
+        "{} has no buckets; run `triage buckets` first",
+        dir.display()
+    );
+    fs::create_dir_all(output).with_context(|| format!("creating {}", output.display()))?;
+    let mut written = 0;
+    let mut known = 0;
+    for bucket in buckets.list() {
+        if let Some(issue) = &bucket.issue {
+            eprintln!("{}: known as {issue}", bucket.id);
+            known += 1;
+            continue;
+        }
+        if let Some(decision) = &bucket.decision {
+            if decision.verdict != Verdict::Report {
+                eprintln!("{}: decided {decision}", bucket.id);
+                continue;
+            }
+        }
+        let best = dir.join(&bucket.best);
+        let Some((report, _)) = report_on(&driver, bucket, &best)? else {
+            eprintln!(
+                "{}: no longer crashes with the signature of bucket {}",
+                best.display(),
+                bucket.id
+            );
+            continue;
+        };
+        let path = output.join(format!("{}.md", bucket.id));
+        fs::write(&path, report.to_string())
+            .with_context(|| format!("writing {}", path.display()))?;
+        println!("{}: {}", path.display(), report.title);
+        written += 1;
+    }
+    eprintln!(
+        "wrote {written} reports for {} buckets, {known} of known bugs",
+        buckets.len()
+    );
+    Ok(())
+}
+
+/// Runs the reproducer at `path` again to report on `bucket`, with the
+/// edition and flags of its metadata. `None` if it no longer crashes with
+/// the bucket's signature.
+fn report_on(
+    driver: &Rustc,
+    bucket: &Bucket,
+    path: &Path,
+) -> Result<Option<(Report, Option<Metadata>)>> {
+    let source = fs::read_to_string(path).with_context(|| format!("reading {}", path.display()))?;
+    let metadata = Metadata::load(path)
+        .with_context(|| format!("reading metadata for {}", path.display()))?
+        .filter(|m| m.matches(&source));
+    let mut reporter = Reporter::new(driver.clone());
+    if let Some(metadata) = &metadata {
+        if let Some(edition) = &metadata.edition {
+            reporter
+                .args
+                .extend(["--edition".to_owned(), edition.clone()]);
+        }
+        reporter.args.extend(metadata.flags.iter().cloned());
+    }
+    Ok(reporter
+        .report(bucket, &source)?
+        .map(|report| (report, metadata)))
+}
+
+/// Reports on `bucket` of findings directory `dir` from its reduced best
+/// finding if `triage reduce` made one that still crashes the same way,
+/// or else from the best finding itself.
+fn best_report(
+    driver: &Rustc,
+    dir: &Path,
+    bucket: &Bucket,
+) -> Result<Option<(Report, Option<Metadata>)>> {
+    let best = dir.join(&bucket.best);
+    let reduced = best.with_extension("").with_extension("reduced.rs");
+    if reduced.exists() {
+        if let Some(found) = report_on(driver, bucket, &reduced)? {
+            return Ok(Some(found));
+        }
+    }
+    report_on(driver, bucket, &best)
+}
+
+/// Writes a self-contained reproducer of each bucket of `dir` to
+/// `output/<bucket id>`, from its reduced best finding if `triage reduce`
+/// made one that still crashes the same way.
+fn bundle(dir: &Path, output: &Path, rustc: PathBuf, phase: Phase) -> Result<()> {
+    let driver = Rustc {
+        phase,
+        ..Rustc::new(rustc)
+    };
+    let buckets =
+        Buckets::load(dir).with_context(|| format!("reading buckets of {}", dir.display()))?;
+    anyhow::ensure!(
+        !buckets.is_empty(),
+        "{} has no buckets; run `triage buckets` first",
+        dir.display()
+    );
+    let mut written = 0;
+    for bucket in buckets.list() {
+        let Some((report, metadata)) = best_report(&driver, dir, bucket)? else {
+            eprintln!(
+                "{}: no longer crashes with the signature of bucket {}",
+                dir.join(&bucket.best).display(),
+                bucket.id
+            );
+            continue;
+        };
+        let bundle = Bundle::new(report, metadata);
+        let path = output.join(&bucket.id);
+        bundle
+            .write(&path)
+            .with_context(|| format!("writing {}", path.display()))?;
+        println!(
+            "{}: {} ({})",
+            path.display(),
+            bundle.report.title,
+            bundle.toolchain.as_deref().unwrap_or("local rustc")
+        );
+        written += 1;
+    }
+    eprintln!("wrote {written} bundles for {} buckets", buckets.len());
+    Ok(())
+}
+
+/// Asks about each bucket of `dir` that is neither known nor decided on,
+/// saving every decision as it is made. Duplicates are also recorded in
+/// the known-bug database `db`, or the findings directory's.
+fn review(dir: &Path, rustc: PathBuf, phase: Phase, db: Option<&Path>) -> Result<()> {
+    // Enough to get past the panic machinery into the compiler.
+    const BACKTRACE_LINES: usize = 40;
+    let driver = Rustc {
+        phase,
+        ..Rustc::new(rustc)
+    };
+    let mut buckets =
+        Buckets::load(dir).with_context(|| format!("reading buckets of {}", dir.display()))?;
+    let pending: Vec<Bucket> = buckets
+        .list()
+        .into_iter()
+        .filter(|bucket| bucket.issue.is_none() && bucket.decision.is_none())
+        .cloned()
+        .collect();
+    let db = db.map_or_else(|| dir.join(KnownBugs::FILE), Path::to_path_buf);
+    let mut lines = std::io::stdin().lines();
+    let mut decided = 0;
+    'buckets: for (i, bucket) in pending.iter().enumerate() {
+        println!(
+            "\n== {}/{}: bucket {}, {} findings\n{}",
+            i + 1,
+            pending.len(),
+            bucket.id,
+            bucket.findings.len(),
+            bucket.signature
+        );
+        match best_report(&driver, dir, bucket)? {
+            Some((report, _)) => {
+                println!(
+                    "\n-- {} --\n{}",
+                    report.bucket.best,
+                    report.source.trim_end()
+                );
+                let backtrace: Vec<&str> = report.backtrace.lines().collect();
+                println!("\n-- backtrace --");
+                for line in backtrace.iter().take(BACKTRACE_LINES) {
+                    println!("{line}");
+                }
+                if backtrace.len() > BACKTRACE_LINES {
+                    println!("... {} more lines", backtrace.len() - BACKTRACE_LINES);
+                }
+            }
+            None => println!("\n{} no longer crashes like this", bucket.best),
+        }
+        for (n, candidate) in bucket.duplicates.iter().enumerate() {
+            println!("candidate #{}: {candidate} {}", n + 1, candidate.url);
+        }
+        let decision = loop {
+            eprint!("[r]eport, [d]uplicate ISSUE, [w]ontfix, [s]kip, [q]uit, ? > ");
+            let Some(line) = lines.next() else {
+                break 'buckets;
+            };
+            match Action::parse(&line?, &bucket.duplicates) {
+                Ok(Action::Decide(decision)) => break decision,
+                Ok(Action::Skip) => continue 'buckets,
+                Ok(Action::Quit) => break 'buckets,
+                Ok(Action::Help) => eprintln!("{}", review::HELP),
+                Err(err) => eprintln!("{err}"),
+            }
+        };
+        if let Verdict::Duplicate { issue } = &decision.verdict {
+            let mut known =
+                KnownBugs::load(&db).with_context(|| format!("reading {}", db.display()))?;
+            known.add(bucket.signature.clone(), issue);
+            known
+                .save(&db)
+                .with_context(|| format!("writing {}", db.display()))?;
+        }
+        let stored = buckets.get_mut(&bucket.id).expect("pending buckets exist");
+        if let Verdict::Duplicate { issue } = &decision.verdict {
+            stored.issue = Some(issue.clone());
+        }
+        stored.decision = Some(decision);
+        buckets
+            .save(dir)
+            .with_context(|| format!("writing buckets of {}", dir.display()))?;
+        decided += 1;
+    }
+    eprintln!(
+        "decided on {decided} of {} undecided buckets",
+        pending.len()
+    );
+    Ok(())
+}
+
+/// Measures the compiler coverage of each seed of `dir` in turn, recording
+/// it in the seed's metadata and the corpus's totals in
+/// [`CorpusCoverage::FILE`].
+fn measure_coverage(dir: &Path, profiler: &Profiler) -> Result<()> {
+    let entries = Corpus::open(dir)
+        .entries()
+        .with_context(|| format!("reading corpus {}", dir.display()))?;
+    let mut seen = Regions::default();
+    let mut totals = BTreeMap::new();
+    let mut measured = 0;
+    for entry in &entries {
+        let mut metadata = Metadata::load(&entry.path)
+            .with_context(|| format!("reading metadata for {}", entry.path.display()))?
+            .filter(|m| m.matches(&entry.source))
+            .unwrap_or_else(|| Metadata::new(&entry.source, Provenance::Unknown));
+        let mut profiler = profiler.clone();
+        if let Some(edition) = &metadata.edition {
+            profiler
+                .args
+                .extend(["--edition".to_owned(), edition.clone()]);
+        }
+        profiler.args.extend(metadata.flags.iter().cloned());
+        let Some(measurement) = profiler
+            .measure(&entry.source)
+            .with_context(|| format!("measuring {}", entry.path.display()))?
+        else {
+            anyhow::bail!(
+                "{} wrote no coverage profile; is it built with -Cinstrument-coverage?",
+                profiler.driver.path.display()
+            );
+        };
+        let new = measurement.covered.difference(&seen);
+        seen.extend(&new);
+        totals.extend(measurement.totals);
+        let coverage = SeedCoverage::new(&measurement.covered, &new);
+        println!(
+            "{}: {} regions, {} new",
+            entry.path.display(),
+            coverage.covered,
+            coverage.new
+        );
+        metadata.rustc_coverage = Some(coverage);
+        metadata
+            .save(&entry.path)
+            .with_context(|| format!("writing metadata for {}", entry.path.display()))?;
+        measured += 1;
+    }
+    let summary = CorpusCoverage {
+        rustc: rustc_version(&profiler.driver)?,
+        totals,
+        covered: seen.by_module(),
+    };
+    summary
+        .save(dir)
+        .with_context(|| format!("writing {}", dir.join(CorpusCoverage::FILE).display()))?;
+    eprintln!(
+        "measured {measured} seeds: {} of {} regions in {} of {} modules",
+        seen.len(),
+        summary.totals.values().sum::<usize>(),
+        summary.covered.len(),
+        summary.totals.len()
+    );
+    Ok(())
+}
+
+/// Lists the compiler modules `dir` covers, from what `coverage measure`
+/// recorded.
+fn coverage_report(dir: &Path, json: bool) -> Result<()> {
+    let summary = CorpusCoverage::load(dir)
+        .with_context(|| format!("reading {}", dir.join(CorpusCoverage::FILE).display()))?
+        .with_context(|| {
+            format!(
+                "{} has not been measured; run `coverage measure` first",
+                dir.display()
+            )
+        })?;
+    let mut seeds = Vec::new();
+    for entry in Corpus::open(dir)
+        .entries()
+        .with_context(|| format!("reading corpus {}", dir.display()))?
+    {
+        let coverage = Metadata::load(&entry.path)?
+            .filter(|metadata| metadata.matches(&entry.source))
+            .and_then(|metadata| metadata.rustc_coverage);
+        if let Some(coverage) = coverage {
+            let name = entry.path.strip_prefix(dir).unwrap_or(&entry.path);
+            seeds.push((name.display().to_string(), coverage));
+        }
+    }
+    let modules = summary.report(&seeds);
+    if json {
+        println!("{}", serde_json::to_string_pretty(&modules)?);
+    } else {
+        println!("{}", summary.rustc);
+        for module in &modules {
+            println!("{module}");
+        }
+    }
+    let unreached = modules.iter().filter(|module| module.covered == 0).count();
+    eprintln!(
+        "{} seeds reach {} of {} modules; {unreached} never reached",
+        seeds.len(),
+        modules.len() - unreached,
+        modules.len()
+    );
+    Ok(())
+}
+
+/// Classifies the timeouts of `paths` and records the verdict in their
+/// metadata.
+fn hangs(paths: &[PathBuf], triage: &HangTriage) -> Result<()> {
+    let entries = load_seeds(paths)?;
+    let mut counts: HashMap<HangClass, usize> = HashMap::new();
+    for entry in &entries {
+        let mut metadata = match Metadata::load(&entry.path)
+            .with_context(|| format!("reading metadata for {}", entry.path.display()))?
+        {
+            Some(metadata) if metadata.matches(&entry.source) => metadata,
+            _ => Metadata::new(&entry.source, Provenance::Unknown),
+        };
+        let mut triage = triage.clone();
+        if let Some(edition) = &metadata.edition {
+            triage
+                .args
+                .extend(["--edition".to_owned(), edition.clone()]);
+        }
+        triage.args.extend(metadata.flags.iter().cloned());
+        let report = triage
+            .classify(&entry.source)
+            .with_context(|| format!("running {}", entry.path.display()))?;
+        println!("{}: {report}", entry.path.display());
+        *counts.entry(report.class).or_default() += 1;
+        metadata.hang = Some(report);
+        metadata
+            .save(&entry.path)
+            .with_context(|| format!("writing metadata for {}", entry.path.display()))?;
+    }
+    let summary: Vec<String> = HangClass::ALL
+        .iter()
+        .map(|class| format!("{} {class}", counts.get(class).copied().unwrap_or(0)))
+        .collect();
+    eprintln!("{} seeds: {}", entries.len(), summary.join(", "));
+    Ok(())
+}
+
+/// The rustc of an installed toolchain, or `toolchain` itself if it is a
+/// path.
+fn toolchain_rustc(toolchains: &Toolchains, toolchain: &str) -> Result<PathBuf> {
+    if toolchain.contains(std::path::MAIN_SEPARATOR) {
+        return Ok(PathBuf::from(toolchain));
+    }
+    toolchains
+        .rustc(toolchain)
+        .with_context(|| format!("looking for toolchain {toolchain}"))?
+        .with_context(|| format!("no toolchain {toolchain} is installed"))
+}
+
+/// Compiles the seeds of `dir` with both of `diff`'s compilers and
+/// reports those they disagree on, worst first.
+fn regressions(dir: &Path, diff: &ToolchainDiff, json: bool) -> Result<()> {
+    let baseline = rustc_version(&diff.baseline)?;
+    let candidate = rustc_version(&diff.candidate)?;
+    let entries = Corpus::open(dir)
+        .entries()
+        .with_context(|| format!("reading corpus {}", dir.display()))?;
+    let mut found = Vec::new();
+    for entry in &entries {
+        let metadata = Metadata::load(&entry.path)
+            .with_context(|| format!("reading metadata for {}", entry.path.display()))?
+            .filter(|m| m.matches(&entry.source));
+        let mut diff = diff.clone();
+        if let Some(metadata) = &metadata {
+            if let Some(edition) = &metadata.edition {
+                diff.args.extend(["--edition".to_owned(), edition.clone()]);
+            }
+            diff.args.extend(metadata.flags.iter().cloned());
+        }
+        let regression = diff
+            .check(&entry.source)
+            .with_context(|| format!("compiling {}", entry.path.display()))?;
+        if let Some(regression) = regression {
+            found.push((entry.path.display().to_string(), regression));
+        }
+    }
+    found.sort_by(|a, b| a.1.change.cmp(&b.1.change).then(a.0.cmp(&b.0)));
+    if json {
+        let found: Vec<serde_json::Value> = found
+            .iter()
+            .map(|(seed, regression)| serde_json::json!({ "seed": seed, "regression": regression }))
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&found)?);
+    } else {
+        for (seed, regression) in &found {
+            println!("{seed}: {regression}");
+        }
+    }
+    let counts: Vec<String> = Change::ALL
+        .iter()
+        .filter_map(|change| {
+            let n = found.iter().filter(|(_, r)| r.change == *change).count();
+            (n > 0).then(|| format!("{n} {change}"))
+        })
+        .collect();
+    eprintln!(
+        "{} of {} seeds differ between {baseline} and {candidate}{}{}",
+        found.len(),
+        entries.len(),
+        if counts.is_empty() { "" } else { ": " },
+        counts.join(", ")
+    );
+    Ok(())
+}
@@ -0,0 +1,245 @@
+//! Prometheus metrics for a coordinated campaign.
+//!
+//! The coordinator serves [`Metrics::render`] at `GET /metrics` in the
+//! Prometheus text exposition format, so a campaign can be scraped,
+//! graphed and alerted on like any other service: executions, findings
+//! by severity and by bucket, hangs, corpus and bucket counts, and a
+//! histogram of how long workers took per job.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::cluster::Status;
+use crate::triage::Severity;
+
+/// Upper bounds of the job-time histogram's buckets, in seconds.
+const JOB_SECONDS: [f64; 12] = [
+    0.01, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 300.0,
+];
+
+/// A Prometheus histogram with fixed buckets.
+#[derive(Debug, Clone)]
+pub struct Histogram {
+    bounds: &'static [f64],
+    /// Observations at most each bound, not cumulative.
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    pub fn new(bounds: &'static [f64]) -> Self {
+        Histogram {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.0,
+            count: 0,
+        }
+    }
+
+    pub fn observe(&mut self, value: f64) {
+        if let Some(i) = self.bounds.iter().position(|&bound| value <= bound) {
+            self.counts[i] += 1;
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+
+    fn write(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {name} {help}\n# TYPE {name} histogram");
+        let mut cumulative = 0;
+        for (bound, count) in self.bounds.iter().zip(&self.counts) {
+            cumulative += count;
+            let _ = writeln!(out, "{name}_bucket{{le=\"{bound}\"}} {cumulative}");
+        }
+        let _ = writeln!(out, "{name}_bucket{{le=\"+Inf\"}} {}", self.count);
+        let _ = writeln!(out, "{name}_sum {}", self.sum);
+        let _ = writeln!(out, "{name}_count {}", self.count);
+    }
+}
+
+/// What the coordinator measures beyond its [`Status`].
+#[derive(Debug, Clone)]
+pub struct Metrics {
+    /// How long workers took per job, as they report it.
+    pub job_seconds: Histogram,
+    /// The severity of each bucket's first finding that had one.
+    pub bucket_severities: HashMap<String, Severity>,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics {
+            job_seconds: Histogram::new(&JOB_SECONDS),
+            bucket_severities: HashMap::new(),
+        }
+    }
+}
+
+impl Metrics {
+    /// The metrics of a coordinator running `harness` with `status`.
+    pub fn render(&self, harness: &str, status: &Status) -> String {
+        let mut out = String::new();
+        let harness = label(harness);
+        let counters = [
+            (
+                "rustc_fuzz_jobs_issued_total",
+                "Jobs handed out, including ones handed out again.",
+                status.issued,
+            ),
+            (
+                "rustc_fuzz_executions_total",
+                "Jobs workers reported on.",
+                status.reported,
+            ),
+            (
+                "rustc_fuzz_known_total",
+                "Findings on seeds that reproduce a known issue.",
+                status.known,
+            ),
+            (
+                "rustc_fuzz_kept_total",
+                "Reproducers kept after deduplication.",
+                status.kept,
+            ),
+        ];
+        for (name, help, value) in counters {
+            let _ = writeln!(
+                out,
+                "# HELP {name} {help}\n# TYPE {name} counter\n{name}{{harness=\"{harness}\"}} {value}"
+            );
+        }
+        let gauges = [
+            (
+                "rustc_fuzz_jobs_pending",
+                "Jobs out with a worker.",
+                status.pending as u64,
+            ),
+            (
+                "rustc_fuzz_corpus_seeds",
+                "Seeds in the corpus being served.",
+                status.corpus as u64,
+            ),
+            (
+                "rustc_fuzz_buckets",
+                "Distinct buckets of findings.",
+                status.buckets.len() as u64,
+            ),
+            (
+                "rustc_fuzz_uptime_seconds",
+                "Seconds since the coordinator started.",
+                status.uptime_secs,
+            ),
+        ];
+        for (name, help, value) in gauges {
+            let _ = writeln!(
+                out,
+                "# HELP {name} {help}\n# TYPE {name} gauge\n{name}{{harness=\"{harness}\"}} {value}"
+            );
+        }
+
+        let name = "rustc_fuzz_findings_total";
+        let _ = writeln!(
+            out,
+            "# HELP {name} Findings by severity.\n# TYPE {name} counter"
+        );
+        for severity in Severity::ALL {
+            let count = status.severities.get(&severity).copied().unwrap_or(0);
+            let _ = writeln!(
+                out,
+                "{name}{{harness=\"{harness}\",severity=\"{severity}\"}} {count}"
+            );
+        }
+        // Timeouts and memory exhaustion are reported as hangs.
+        let name = "rustc_fuzz_timeouts_total";
+        let _ = writeln!(
+            out,
+            "# HELP {name} Findings that are timeouts, hangs or memory exhaustion.\n\
+             # TYPE {name} counter\n{name}{{harness=\"{harness}\"}} {}",
+            status.severities.get(&Severity::Hang).copied().unwrap_or(0)
+        );
+
+        let name = "rustc_fuzz_bucket_findings_total";
+        let _ = writeln!(
+            out,
+            "# HELP {name} Findings by bucket; ICEs have severity \"ice\".\n# TYPE {name} counter"
+        );
+        let mut buckets: Vec<_> = status.buckets.iter().collect();
+        buckets.sort_unstable();
+        for (bucket, count) in buckets {
+            let severity = self
+                .bucket_severities
+                .get(bucket)
+                .map_or("unknown", |severity| severity.name());
+            let _ = writeln!(
+                out,
+                "{name}{{harness=\"{harness}\",bucket=\"{}\",severity=\"{severity}\"}} {count}",
+                label(bucket)
+            );
+        }
+
+        self.job_seconds.write(
+            &mut out,
+            "rustc_fuzz_job_seconds",
+            "How long workers took to run a job, compiling included.",
+        );
+        out
+    }
+}
+
+/// `value` escaped for use as a label value.
+fn label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn histograms_are_cumulative() {
+        static BOUNDS: [f64; 2] = [1.0, 10.0];
+        let mut histogram = Histogram::new(&BOUNDS);
+        for value in [0.5, 1.0, 5.0, 20.0] {
+            histogram.observe(value);
+        }
+        let mut out = String::new();
+        histogram.write(&mut out, "t", "Test.");
+        assert_eq!(
+            out,
+            "# HELP t Test.\n# TYPE t histogram\n\
+             t_bucket{le=\"1\"} 2\nt_bucket{le=\"10\"} 3\nt_bucket{le=\"+Inf\"} 4\n\
+             t_sum 26.5\nt_count 4\n"
+        );
+    }
+
+    #[test]
+    fn status_is_exposed_per_harness() {
+        let status = Status {
+            issued: 7,
+            buckets: HashMap::from([("a\"b".to_owned(), 2)]),
+            severities: BTreeMap::from([(Severity::Hang, 3)]),
+            ..Status::default()
+        };
+        let metrics = Metrics {
+            bucket_severities: HashMap::from([("a\"b".to_owned(), Severity::Hang)]),
+            ..Metrics::default()
+        };
+        let text = metrics.render("miri", &status);
+        for line in [
+            "rustc_fuzz_jobs_issued_total{harness=\"miri\"} 7",
+            "rustc_fuzz_buckets{harness=\"miri\"} 1",
+            "rustc_fuzz_findings_total{harness=\"miri\",severity=\"hang\"} 3",
+            "rustc_fuzz_findings_total{harness=\"miri\",severity=\"ice\"} 0",
+            "rustc_fuzz_timeouts_total{harness=\"miri\"} 3",
+            "rustc_fuzz_bucket_findings_total{harness=\"miri\",bucket=\"a\\\"b\",severity=\"hang\"} 2",
+            "rustc_fuzz_job_seconds_count 0",
+        ] {
+            assert!(text.lines().any(|l| l == line), "{line} in\n{text}");
+        }
+    }
+}
@@ -0,0 +1,368 @@
+//! Notifications of new findings.
+//!
+//! Nobody watches a week-long campaign's output. A [`Notifier`] posts to
+//! a chat webhook when a finding falls in a bucket no earlier one did,
+//! when a differential harness finds a divergence (a new wrong-code
+//! bucket), and when `triage recheck` finds a known crash fixed. It
+//! watches the same events as the event log, so whatever emits them need
+//! not know it is there.
+//!
+//! Each bucket or signature is announced once; with a state file, once
+//! across restarts too. A crash storm is held to [`Webhook::burst`]
+//! messages per [`Webhook::window`], and the next message that goes out
+//! says how many were held back. Posting goes through `curl`, and a
+//! webhook that cannot be reached is reported but never stops a campaign.
+
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+use crate::events::{Event, EventKind};
+use crate::triage::Severity;
+
+/// What a webhook expects to be posted.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// `{"text": …}`, which Slack, Mattermost and Rocket.Chat take.
+    #[default]
+    Slack,
+    /// `{"text": …, "event": …}`, with the event as in the event log.
+    Json,
+}
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::Slack => "slack",
+            Format::Json => "json",
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownFormat(pub String);
+
+impl fmt::Display for UnknownFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unknown webhook format {:?}; expected slack or json",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for UnknownFormat {}
+
+impl FromStr for Format {
+    type Err = UnknownFormat;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        [Format::Slack, Format::Json]
+            .into_iter()
+            .find(|format| format.name() == s)
+            .ok_or_else(|| UnknownFormat(s.to_owned()))
+    }
+}
+
+/// Where and how often to notify.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Webhook {
+    pub url: String,
+    pub format: Format,
+    /// Only announce buckets this bad or worse; fixes are always
+    /// announced.
+    pub min_severity: Option<Severity>,
+    /// Messages allowed per window.
+    pub burst: usize,
+    pub window: Duration,
+    /// File of what has been announced, kept across restarts.
+    pub state: Option<PathBuf>,
+    pub curl: PathBuf,
+}
+
+impl Webhook {
+    pub fn new(url: impl Into<String>) -> Self {
+        Webhook {
+            url: url.into(),
+            format: Format::default(),
+            min_severity: None,
+            burst: 5,
+            window: Duration::from_secs(10 * 60),
+            state: None,
+            curl: PathBuf::from("curl"),
+        }
+    }
+}
+
+/// Posts events worth a message to a [`Webhook`].
+#[derive(Debug)]
+pub struct Notifier {
+    webhook: Webhook,
+    /// Keys of what has been announced.
+    seen: BTreeSet<String>,
+    /// When the messages of the current window went out.
+    sent: VecDeque<Instant>,
+    /// Messages held back since the last one that went out.
+    held: usize,
+}
+
+impl Notifier {
+    /// A notifier for `webhook`, remembering what its state file says was
+    /// announced already.
+    pub fn new(webhook: Webhook) -> io::Result<Self> {
+        let seen = match &webhook.state {
+            Some(path) => match fs::read_to_string(path) {
+                Ok(json) => serde_json::from_str(&json)?,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => BTreeSet::new(),
+                Err(e) => return Err(e),
+            },
+            None => BTreeSet::new(),
+        };
+        Ok(Notifier {
+            webhook,
+            seen,
+            sent: VecDeque::new(),
+            held: 0,
+        })
+    }
+
+    /// Posts a message about `event` if it is worth one, has not been
+    /// announced and the rate limit allows. Returns whether it posted.
+    pub fn notify(&mut self, event: &Event) -> io::Result<bool> {
+        let Some((key, mut text)) = self.message(&event.kind) else {
+            return Ok(false);
+        };
+        if !self.seen.insert(key) {
+            return Ok(false);
+        }
+        if let Some(path) = &self.webhook.state {
+            let mut json = serde_json::to_string_pretty(&self.seen)?;
+            json.push('\n');
+            fs::write(path, json)?;
+        }
+        let now = Instant::now();
+        while self
+            .sent
+            .front()
+            .is_some_and(|sent| now.duration_since(*sent) >= self.webhook.window)
+        {
+            self.sent.pop_front();
+        }
+        if self.sent.len() >= self.webhook.burst {
+            self.held += 1;
+            tracing::debug!(held = self.held, "rate limited: {text}");
+            return Ok(false);
+        }
+        if self.held > 0 {
+            text.push_str(&format!(" ({} more held back by rate limiting)", self.held));
+        }
+        self.post(event, &text)?;
+        tracing::debug!("notified: {text}");
+        self.held = 0;
+        self.sent.push_back(now);
+        Ok(true)
+    }
+
+    /// The dedup key and text of the message `kind` is worth, if any.
+    fn message(&self, kind: &EventKind) -> Option<(String, String)> {
+        match kind {
+            EventKind::NewBucket {
+                bucket,
+                severity,
+                reproducer,
+            } => {
+                if let (Some(min), Some(severity)) = (self.webhook.min_severity, severity) {
+                    if *severity > min {
+                        return None;
+                    }
+                }
+                let text = match severity {
+                    Some(Severity::WrongCode) => {
+                        format!("Divergence: {bucket} ({reproducer})")
+                    }
+                    Some(severity) => format!("New {severity} bucket: {bucket} ({reproducer})"),
+                    None => format!("New bucket: {bucket} ({reproducer})"),
+                };
+                Some((format!("bucket:{bucket}"), text))
+            }
+            EventKind::Fixed {
+                path,
+                signature,
+                outcome,
+            } => Some((
+                format!("fixed:{signature}"),
+                format!("Fixed: {path} no longer crashes; now {outcome}"),
+            )),
+            _ => None,
+        }
+    }
+
+    fn post(&self, event: &Event, text: &str) -> io::Result<()> {
+        let payload = match self.webhook.format {
+            Format::Slack => json!({ "text": text }),
+            Format::Json => json!({ "text": text, "event": event }),
+        };
+        // The URL is a secret, so it goes to curl on stdin rather than on
+        // a command line anyone can list.
+        let config = format!(
+            "url = {}\ndata-binary = {}\n",
+            quote(&self.webhook.url),
+            quote(&serde_json::to_string(&payload)?)
+        );
+        let mut child = Command::new(&self.webhook.curl)
+            .args([
+                "--fail",
+                "--silent",
+                "--show-error",
+                "--max-time",
+                "10",
+                "--header",
+                "Content-Type: application/json",
+                "--config",
+                "-",
+            ])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                io::Error::new(
+                    e.kind(),
+                    format!("running {}: {e}", self.webhook.curl.display()),
+                )
+            })?;
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(config.as_bytes())?;
+        }
+        let output = child.wait_with_output()?;
+        if !output.status.success() {
+            return Err(io::Error::other(format!(
+                "posting to the webhook: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// `text` as a double-quoted curl config value.
+fn quote(text: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' | '\\' => {
+                quoted.push('\\');
+                quoted.push(c);
+            }
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::PermissionsExt;
+
+    fn bucket(bucket: &str, severity: Severity) -> Event {
+        Event {
+            time_ms: 0,
+            schema: crate::events::SCHEMA,
+            kind: EventKind::NewBucket {
+                bucket: bucket.to_owned(),
+                severity: Some(severity),
+                reproducer: format!("findings/{bucket}.rs"),
+            },
+        }
+    }
+
+    #[test]
+    fn config_values_are_quoted() {
+        assert_eq!(quote("a \"b\"\\\n"), r#""a \"b\"\\\n""#);
+    }
+
+    #[test]
+    fn announces_each_bucket_once_within_the_rate_limit() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-notify-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("creates the directory");
+        // Logs the data of every post.
+        let curl = dir.join("curl");
+        let log = dir.join("posts");
+        fs::write(
+            &curl,
+            format!("#!/bin/sh\ngrep '^data-binary' >> {}\n", log.display()),
+        )
+        .expect("writes the curl");
+        fs::set_permissions(&curl, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        let webhook = Webhook {
+            min_severity: Some(Severity::Ice),
+            burst: 1,
+            window: Duration::from_millis(300),
+            state: Some(dir.join("announced.json")),
+            curl,
+            ..Webhook::new("https://example.com/hook")
+        };
+        let mut notifier = Notifier::new(webhook.clone()).expect("starts");
+        assert!(notifier.notify(&bucket("a", Severity::Ice)).expect("posts"));
+        assert!(!notifier.notify(&bucket("a", Severity::Ice)).expect("posts"));
+        assert!(!notifier
+            .notify(&bucket("d", Severity::Diagnostic))
+            .expect("posts"));
+        assert!(!notifier
+            .notify(&bucket("b", Severity::WrongCode))
+            .expect("posts"));
+        std::thread::sleep(Duration::from_millis(350));
+        assert!(notifier.notify(&bucket("c", Severity::Ice)).expect("posts"));
+        let posts = fs::read_to_string(&log).expect("reads the posts");
+        let posts: Vec<&str> = posts.lines().collect();
+        assert_eq!(posts.len(), 2, "{posts:?}");
+        assert!(
+            posts[0].contains("New ice bucket: a (findings/a.rs)"),
+            "{}",
+            posts[0]
+        );
+        assert!(
+            posts[1].contains("(1 more held back by rate limiting)"),
+            "{}",
+            posts[1]
+        );
+
+        // What was announced stays announced after a restart.
+        let mut restarted = Notifier::new(webhook).expect("starts");
+        assert!(!restarted
+            .notify(&bucket("a", Severity::Ice))
+            .expect("posts"));
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+
+    #[test]
+    fn unreachable_webhooks_are_errors() {
+        let mut notifier = Notifier::new(Webhook {
+            curl: PathBuf::from("false"),
+            ..Webhook::new("https://example.com/hook")
+        })
+        .expect("starts");
+        assert!(notifier.notify(&bucket("a", Severity::Ice)).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+//! Multi-stage seed pipelines.
+//!
+//! [`Expand`] runs rustc's macro expansion over a seed and feeds the
+//! expanded source back into the mutation engine. Expanded code is full of
+//! constructs nobody writes by hand (`#[prelude_import]`, lowered
+//! `format_args!`, derive output, `#[rustc_*]` attributes) and reaches
+//! compiler paths plain seeds do not.
+
+use rand::RngCore;
+use std::fmt;
+use std::io;
+
+use crate::harness::{ExecOutcome, Rustc};
+use crate::mutate::{Engine, Mutated};
+
+/// Runs `rustc -Zunpretty=expanded` on seeds.
+#[derive(Debug, Clone)]
+pub struct Expand {
+    pub rustc: Rustc,
+    pub edition: String,
+}
+
+impl Default for Expand {
+    fn default() -> Self {
+        Expand {
+            rustc: Rustc::default(),
+            edition: "2021".to_owned(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ExpandError {
+    /// rustc could not be spawned or talked to.
+    Io(io::Error),
+    /// rustc did not expand the seed.
+    Rejected(ExecOutcome),
+    /// The expansion was cut off at the driver's output limit.
+    Truncated,
+}
+
+impl fmt::Display for ExpandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExpandError::Io(e) => write!(f, "failed to run rustc: {e}"),
+            ExpandError::Rejected(outcome) => write!(
+                f,
+                "rustc rejected the seed ({outcome}):\n{}",
+                outcome.output().stderr
+            ),
+            ExpandError::Truncated => f.write_str("expanded source exceeds the output limit"),
+        }
+    }
+}
+
+impl std::error::Error for ExpandError {}
+
+impl From<io::Error> for ExpandError {
+    fn from(e: io::Error) -> Self {
+        ExpandError::Io(e)
+    }
+}
+
+impl Expand {
+    /// Returns the macro-expanded form of `source`.
+    ///
+    /// The driver's default environment sets `RUSTC_BOOTSTRAP=1`, so a
+    /// stable rustc works as well as a nightly one.
+    pub fn expand(&self, source: &str) -> Result<String, ExpandError> {
+        let args = [
+            "-Zunpretty=expanded",
+            "--crate-type=lib",
+            "--edition",
+            &self.edition,
+            "-",
+        ];
+        match self.rustc.run(args, source.as_bytes())? {
+            ExecOutcome::Success(output) if output.truncated => Err(ExpandError::Truncated),
+            ExecOutcome::Success(output) => Ok(output.stdout),
+            outcome => Err(ExpandError::Rejected(outcome)),
+        }
+    }
+
+    /// Expands `source`, then applies one mutation from `engine` to the
+    /// expanded code. `Ok(None)` means no operator could mutate it.
+    pub fn then_mutate(
+        &self,
+        source: &str,
+        engine: &Engine,
+        rng: &mut dyn RngCore,
+    ) -> Result<Option<Mutated>, ExpandError> {
+        let expanded = self.expand(source)?;
+        Ok(engine.mutate(&expanded, rng))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::Path;
+
+    fn rustc(dir: &Path, script: &str) -> Rustc {
+        fs::create_dir_all(dir).expect("creates the test directory");
+        let path = dir.join("rustc");
+        fs::write(&path, script).expect("writes the rustc");
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).expect("makes it runnable");
+        Rustc::new(path)
+    }
+
+    #[test]
+    fn expansions_come_from_stdout() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-expand-{}", std::process::id()));
+        // Echoes the seed back with a prelude, as the real expansion does.
+        let expand = Expand {
+            rustc: rustc(&dir, "#!/bin/sh\necho '#[prelude_import]'\ncat\n"),
+            ..Expand::default()
+        };
+        let expanded = expand.expand("fn f() {}").expect("expands");
+        assert_eq!(expanded, "#[prelude_import]\nfn f() {}");
+
+        let rejecting = Expand {
+            rustc: rustc(&dir, "#!/bin/sh\necho 'error: expected item' >&2\nexit 1\n"),
+            ..Expand::default()
+        };
+        let error = rejecting.expand("fn f(").expect_err("rejected");
+        assert!(matches!(error, ExpandError::Rejected(_)), "{error}");
+        assert!(
+            error.to_string().contains("error: expected item"),
+            "{error}"
+        );
+        fs::remove_dir_all(&dir).expect("cleans up");
+    }
+}
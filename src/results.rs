@@ -0,0 +1,933 @@
+//! The results database.
+//!
+//! The event log is easy to append to and hard to ask questions of: which
+//! harness has found the most this week, how long executions of one seed
+//! took, which buckets were bisected. [`Database`] keeps the same events
+//! in an embedded SQLite file instead, one typed table per kind:
+//! executions, seeds, buckets, reductions, bisections, fixes and `fuzz`
+//! runs, whose totals per scheduling policy compare policies. `--db FILE`
+//! records every event there as it happens, `db import` loads existing
+//! logs, and the query methods here return rows as plain structs, so
+//! reports and tools need not write SQL.
+//!
+//! The file's `user_version` is [`SCHEMA`]; a file from a newer build is
+//! refused rather than misread.
+
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use rusqlite::types::ToSql;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+
+use crate::events::{Event, EventKind};
+use crate::triage::bisect::CommitRange;
+use crate::triage::severity::UnknownSeverity;
+use crate::triage::{Bisection, Severity};
+
+/// The version of the table layout.
+pub const SCHEMA: i32 = 2;
+
+const TABLES: &str = "
+CREATE TABLE IF NOT EXISTS executions (
+    id INTEGER PRIMARY KEY,
+    time_ms INTEGER NOT NULL,
+    harness TEXT NOT NULL,
+    input TEXT NOT NULL,
+    elapsed_ms INTEGER,
+    severity TEXT,
+    finding TEXT
+);
+CREATE INDEX IF NOT EXISTS executions_input ON executions (input);
+CREATE INDEX IF NOT EXISTS executions_severity ON executions (severity);
+CREATE TABLE IF NOT EXISTS seeds (
+    id INTEGER PRIMARY KEY,
+    time_ms INTEGER NOT NULL,
+    path TEXT NOT NULL,
+    hash TEXT NOT NULL,
+    parent TEXT
+);
+CREATE INDEX IF NOT EXISTS seeds_hash ON seeds (hash);
+CREATE TABLE IF NOT EXISTS buckets (
+    bucket TEXT PRIMARY KEY,
+    time_ms INTEGER NOT NULL,
+    severity TEXT,
+    reproducer TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS reductions (
+    id INTEGER PRIMARY KEY,
+    time_ms INTEGER NOT NULL,
+    path TEXT NOT NULL,
+    output TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    before_bytes INTEGER NOT NULL,
+    after_bytes INTEGER NOT NULL,
+    tests INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS bisections (
+    id INTEGER PRIMARY KEY,
+    time_ms INTEGER NOT NULL,
+    finding TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    last_good TEXT NOT NULL,
+    first_bad TEXT NOT NULL,
+    commit_good TEXT,
+    commit_bad TEXT,
+    pr INTEGER
+);
+CREATE TABLE IF NOT EXISTS fixes (
+    id INTEGER PRIMARY KEY,
+    time_ms INTEGER NOT NULL,
+    path TEXT NOT NULL,
+    signature TEXT NOT NULL,
+    outcome TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS fuzz_runs (
+    findings TEXT PRIMARY KEY,
+    time_ms INTEGER NOT NULL,
+    policy TEXT NOT NULL,
+    seed INTEGER NOT NULL,
+    iterations INTEGER NOT NULL,
+    found INTEGER NOT NULL,
+    known INTEGER NOT NULL,
+    buckets INTEGER NOT NULL,
+    added INTEGER NOT NULL,
+    wall_ms INTEGER NOT NULL,
+    cpu_ms INTEGER NOT NULL
+);
+";
+
+/// One harness execution.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Execution {
+    /// Milliseconds since the Unix epoch.
+    pub time_ms: u64,
+    pub harness: String,
+    /// The content hash of the seed.
+    pub input: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub elapsed_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub finding: Option<String>,
+}
+
+/// A seed added to a corpus.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeedRecord {
+    pub time_ms: u64,
+    pub path: String,
+    pub hash: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<String>,
+}
+
+/// A bucket, as first seen.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BucketRecord {
+    pub time_ms: u64,
+    pub bucket: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub severity: Option<Severity>,
+    pub reproducer: String,
+}
+
+/// A finished reduction.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Reduction {
+    pub time_ms: u64,
+    pub path: String,
+    pub output: String,
+    pub signature: String,
+    pub before_bytes: usize,
+    pub after_bytes: usize,
+    pub tests: usize,
+}
+
+/// A finished bisection.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BisectionRecord {
+    pub time_ms: u64,
+    pub finding: String,
+    pub bisection: Bisection,
+}
+
+/// A regression seed found fixed.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Fix {
+    pub time_ms: u64,
+    pub path: String,
+    pub signature: String,
+    pub outcome: String,
+}
+
+/// The totals of a `fuzz` run, as last reported.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FuzzRun {
+    pub time_ms: u64,
+    /// Its findings directory.
+    pub findings: String,
+    pub policy: String,
+    pub seed: u64,
+    pub iterations: u64,
+    pub found: u64,
+    pub known: u64,
+    pub buckets: u64,
+    pub added: u64,
+    pub wall_ms: u64,
+    pub cpu_ms: u64,
+}
+
+/// Totals over the `fuzz` runs of one scheduling policy.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PolicySummary {
+    pub runs: u64,
+    pub iterations: u64,
+    pub found: u64,
+    pub buckets: u64,
+    pub added: u64,
+    pub wall_ms: u64,
+    pub cpu_ms: u64,
+}
+
+impl PolicySummary {
+    /// `count` per thousand iterations, which compares policies over runs
+    /// of different lengths.
+    pub fn per_thousand(&self, count: u64) -> f64 {
+        if self.iterations == 0 {
+            return 0.0;
+        }
+        count as f64 * 1000.0 / self.iterations as f64
+    }
+
+    /// Iterations per wall-clock second.
+    pub fn rate(&self) -> f64 {
+        if self.wall_ms == 0 {
+            return 0.0;
+        }
+        self.iterations as f64 * 1000.0 / self.wall_ms as f64
+    }
+}
+
+/// Which executions [`Database::executions`] returns. The default is all
+/// of them.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionQuery {
+    pub harness: Option<String>,
+    pub input: Option<String>,
+    /// Only findings this bad or worse.
+    pub severity: Option<Severity>,
+    /// Only findings, of any severity.
+    pub findings: bool,
+    /// Only executions at or after this time, in milliseconds since the
+    /// Unix epoch.
+    pub since_ms: Option<u64>,
+    /// At most this many, the newest.
+    pub limit: Option<usize>,
+}
+
+/// Counts over one harness's executions.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct HarnessSummary {
+    pub executions: u64,
+    pub findings: u64,
+    /// Mean of the timed executions, in milliseconds.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mean_ms: Option<f64>,
+}
+
+/// What a database holds, in numbers.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Summary {
+    pub executions: u64,
+    pub harnesses: BTreeMap<String, HarnessSummary>,
+    pub findings: BTreeMap<Severity, u64>,
+    pub seeds: u64,
+    pub buckets: u64,
+    pub reductions: u64,
+    pub bisections: u64,
+    pub fixes: u64,
+    pub fuzz_runs: u64,
+}
+
+/// A results database.
+#[derive(Debug)]
+pub struct Database {
+    conn: Connection,
+}
+
+impl Database {
+    /// Opens the database at `path`, creating it if need be.
+    pub fn open(path: &Path) -> rusqlite::Result<Self> {
+        let conn = Connection::open(path)?;
+        // Concurrent readers, such as a report while a campaign runs, do
+        // not block the writer.
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        Database::init(conn)
+    }
+
+    /// A database that lives as long as it does.
+    pub fn in_memory() -> rusqlite::Result<Self> {
+        Database::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> rusqlite::Result<Self> {
+        let version: i32 = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        if version > SCHEMA {
+            return Err(rusqlite::Error::SqliteFailure(
+                rusqlite::ffi::Error::new(rusqlite::ffi::SQLITE_MISMATCH),
+                Some(format!(
+                    "results database has schema {version}, newer than this build's {SCHEMA}"
+                )),
+            ));
+        }
+        conn.execute_batch(TABLES)?;
+        conn.pragma_update(None, "user_version", SCHEMA)?;
+        Ok(Database { conn })
+    }
+
+    /// Records `event` in its table. A bucket seen before keeps its first
+    /// record.
+    pub fn record(&self, event: &Event) -> rusqlite::Result<()> {
+        insert(&self.conn, event)
+    }
+
+    /// Records `events` in one transaction, which is much faster than one
+    /// at a time.
+    pub fn import(&mut self, events: &[Event]) -> rusqlite::Result<()> {
+        let tx = self.conn.transaction()?;
+        for event in events {
+            insert(&tx, event)?;
+        }
+        tx.commit()
+    }
+
+    /// The executions `query` selects, oldest first.
+    pub fn executions(&self, query: &ExecutionQuery) -> rusqlite::Result<Vec<Execution>> {
+        let mut clauses = Vec::new();
+        let mut values: Vec<Box<dyn ToSql>> = Vec::new();
+        if let Some(harness) = &query.harness {
+            clauses.push("harness = ?".to_owned());
+            values.push(Box::new(harness.clone()));
+        }
+        if let Some(input) = &query.input {
+            clauses.push("input = ?".to_owned());
+            values.push(Box::new(input.clone()));
+        }
+        if let Some(worst) = query.severity {
+            let names: Vec<String> = Severity::ALL
+                .iter()
+                .filter(|severity| **severity <= worst)
+                .map(|severity| format!("'{}'", severity.name()))
+                .collect();
+            clauses.push(format!("severity IN ({})", names.join(", ")));
+        }
+        if query.findings {
+            clauses.push("severity IS NOT NULL".to_owned());
+        }
+        if let Some(since) = query.since_ms {
+            clauses.push("time_ms >= ?".to_owned());
+            values.push(Box::new(since as i64));
+        }
+        let filter = if clauses.is_empty() {
+            String::new()
+        } else {
+            format!("WHERE {}", clauses.join(" AND "))
+        };
+        let limit = query
+            .limit
+            .map_or(String::new(), |limit| format!("LIMIT {limit}"));
+        let sql = format!(
+            "SELECT * FROM (
+                SELECT time_ms, harness, input, elapsed_ms, severity, finding, id
+                FROM executions {filter} ORDER BY id DESC {limit}
+             ) ORDER BY id"
+        );
+        let mut statement = self.conn.prepare(&sql)?;
+        let params: Vec<&dyn ToSql> = values.iter().map(Box::as_ref).collect();
+        let rows = statement.query_map(params.as_slice(), |row| {
+            Ok(Execution {
+                time_ms: get_u64(row, 0)?,
+                harness: row.get(1)?,
+                input: row.get(2)?,
+                elapsed_ms: row.get::<_, Option<i64>>(3)?.map(|ms| ms as u64),
+                severity: get_severity(row, 4)?,
+                finding: row.get(5)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Every seed added, oldest first.
+    pub fn seeds(&self) -> rusqlite::Result<Vec<SeedRecord>> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT time_ms, path, hash, parent FROM seeds ORDER BY id")?;
+        let rows = statement.query_map([], |row| {
+            Ok(SeedRecord {
+                time_ms: get_u64(row, 0)?,
+                path: row.get(1)?,
+                hash: row.get(2)?,
+                parent: row.get(3)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Every bucket, in the order they were first seen.
+    pub fn buckets(&self) -> rusqlite::Result<Vec<BucketRecord>> {
+        let mut statement = self.conn.prepare(
+            "SELECT time_ms, bucket, severity, reproducer FROM buckets ORDER BY time_ms, bucket",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok(BucketRecord {
+                time_ms: get_u64(row, 0)?,
+                bucket: row.get(1)?,
+                severity: get_severity(row, 2)?,
+                reproducer: row.get(3)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// The bucket called `bucket`, if it has been seen.
+    pub fn bucket(&self, bucket: &str) -> rusqlite::Result<Option<BucketRecord>> {
+        self.conn
+            .query_row(
+                "SELECT time_ms, bucket, severity, reproducer FROM buckets WHERE bucket = ?1",
+                [bucket],
+                |row| {
+                    Ok(BucketRecord {
+                        time_ms: get_u64(row, 0)?,
+                        bucket: row.get(1)?,
+                        severity: get_severity(row, 2)?,
+                        reproducer: row.get(3)?,
+                    })
+                },
+            )
+            .optional()
+    }
+
+    /// Every finished reduction, oldest first.
+    pub fn reductions(&self) -> rusqlite::Result<Vec<Reduction>> {
+        let mut statement = self.conn.prepare(
+            "SELECT time_ms, path, output, signature, before_bytes, after_bytes, tests
+             FROM reductions ORDER BY id",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok(Reduction {
+                time_ms: get_u64(row, 0)?,
+                path: row.get(1)?,
+                output: row.get(2)?,
+                signature: row.get(3)?,
+                before_bytes: get_u64(row, 4)? as usize,
+                after_bytes: get_u64(row, 5)? as usize,
+                tests: get_u64(row, 6)? as usize,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Every finished bisection, oldest first.
+    pub fn bisections(&self) -> rusqlite::Result<Vec<BisectionRecord>> {
+        let mut statement = self.conn.prepare(
+            "SELECT time_ms, finding, signature, last_good, first_bad, commit_good, commit_bad, pr
+             FROM bisections ORDER BY id",
+        )?;
+        let rows = statement.query_map([], |row| {
+            let commits = match (row.get(5)?, row.get(6)?) {
+                (Some(last_good), Some(first_bad)) => Some(CommitRange {
+                    last_good,
+                    first_bad,
+                    pr: row.get::<_, Option<i64>>(7)?.map(|pr| pr as u64),
+                }),
+                _ => None,
+            };
+            Ok(BisectionRecord {
+                time_ms: get_u64(row, 0)?,
+                finding: row.get(1)?,
+                bisection: Bisection {
+                    signature: row.get(2)?,
+                    last_good: row.get(3)?,
+                    first_bad: row.get(4)?,
+                    commits,
+                },
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Every regression seed found fixed, oldest first.
+    pub fn fixes(&self) -> rusqlite::Result<Vec<Fix>> {
+        let mut statement = self
+            .conn
+            .prepare("SELECT time_ms, path, signature, outcome FROM fixes ORDER BY id")?;
+        let rows = statement.query_map([], |row| {
+            Ok(Fix {
+                time_ms: get_u64(row, 0)?,
+                path: row.get(1)?,
+                signature: row.get(2)?,
+                outcome: row.get(3)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// Every `fuzz` run, in the order they last reported.
+    pub fn fuzz_runs(&self) -> rusqlite::Result<Vec<FuzzRun>> {
+        let mut statement = self.conn.prepare(
+            "SELECT time_ms, findings, policy, seed, iterations, found, known, buckets, added,
+                 wall_ms, cpu_ms
+             FROM fuzz_runs ORDER BY time_ms, findings",
+        )?;
+        let rows = statement.query_map([], |row| {
+            Ok(FuzzRun {
+                time_ms: get_u64(row, 0)?,
+                findings: row.get(1)?,
+                policy: row.get(2)?,
+                seed: get_u64(row, 3)?,
+                iterations: get_u64(row, 4)?,
+                found: get_u64(row, 5)?,
+                known: get_u64(row, 6)?,
+                buckets: get_u64(row, 7)?,
+                added: get_u64(row, 8)?,
+                wall_ms: get_u64(row, 9)?,
+                cpu_ms: get_u64(row, 10)?,
+            })
+        })?;
+        rows.collect()
+    }
+
+    /// The totals of the `fuzz` runs of each scheduling policy.
+    pub fn policies(&self) -> rusqlite::Result<BTreeMap<String, PolicySummary>> {
+        let mut statement = self.conn.prepare(
+            "SELECT policy, COUNT(*), SUM(iterations), SUM(found), SUM(buckets), SUM(added),
+                 SUM(wall_ms), SUM(cpu_ms)
+             FROM fuzz_runs GROUP BY policy",
+        )?;
+        let mut rows = statement.query([])?;
+        let mut policies = BTreeMap::new();
+        while let Some(row) = rows.next()? {
+            policies.insert(
+                row.get(0)?,
+                PolicySummary {
+                    runs: get_u64(row, 1)?,
+                    iterations: get_u64(row, 2)?,
+                    found: get_u64(row, 3)?,
+                    buckets: get_u64(row, 4)?,
+                    added: get_u64(row, 5)?,
+                    wall_ms: get_u64(row, 6)?,
+                    cpu_ms: get_u64(row, 7)?,
+                },
+            );
+        }
+        Ok(policies)
+    }
+
+    /// Counts of everything recorded.
+    pub fn summary(&self) -> rusqlite::Result<Summary> {
+        let count = |table: &str| -> rusqlite::Result<u64> {
+            self.conn
+                .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                    get_u64(row, 0)
+                })
+        };
+        let mut summary = Summary {
+            executions: count("executions")?,
+            seeds: count("seeds")?,
+            buckets: count("buckets")?,
+            reductions: count("reductions")?,
+            bisections: count("bisections")?,
+            fixes: count("fixes")?,
+            fuzz_runs: count("fuzz_runs")?,
+            ..Summary::default()
+        };
+        let mut statement = self.conn.prepare(
+            "SELECT harness, COUNT(*), COUNT(severity), AVG(elapsed_ms)
+             FROM executions GROUP BY harness",
+        )?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            summary.harnesses.insert(
+                row.get(0)?,
+                HarnessSummary {
+                    executions: get_u64(row, 1)?,
+                    findings: get_u64(row, 2)?,
+                    mean_ms: row.get(3)?,
+                },
+            );
+        }
+        let mut statement = self.conn.prepare(
+            "SELECT severity, COUNT(*) FROM executions
+             WHERE severity IS NOT NULL GROUP BY severity",
+        )?;
+        let mut rows = statement.query([])?;
+        while let Some(row) = rows.next()? {
+            if let Some(severity) = get_severity(row, 0)? {
+                summary.findings.insert(severity, get_u64(row, 1)?);
+            }
+        }
+        Ok(summary)
+    }
+}
+
+fn insert(conn: &Connection, event: &Event) -> rusqlite::Result<()> {
+    let time = event.time_ms as i64;
+    match &event.kind {
+        EventKind::Exec {
+            harness,
+            input,
+            elapsed_ms,
+            severity,
+            finding,
+        } => conn.execute(
+            "INSERT INTO executions (time_ms, harness, input, elapsed_ms, severity, finding)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                time,
+                harness,
+                input,
+                elapsed_ms.map(|ms| ms as i64),
+                severity.map(Severity::name),
+                finding,
+            ],
+        )?,
+        EventKind::NewSeed { path, hash, parent } => conn.execute(
+            "INSERT INTO seeds (time_ms, path, hash, parent) VALUES (?1, ?2, ?3, ?4)",
+            params![time, path, hash, parent],
+        )?,
+        EventKind::NewBucket {
+            bucket,
+            severity,
+            reproducer,
+        } => conn.execute(
+            "INSERT OR IGNORE INTO buckets (bucket, time_ms, severity, reproducer)
+                 VALUES (?1, ?2, ?3, ?4)",
+            params![bucket, time, severity.map(Severity::name), reproducer],
+        )?,
+        EventKind::Reduced {
+            path,
+            output,
+            signature,
+            before_bytes,
+            after_bytes,
+            tests,
+        } => conn.execute(
+            "INSERT INTO reductions
+                 (time_ms, path, output, signature, before_bytes, after_bytes, tests)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            params![
+                time,
+                path,
+                output,
+                signature,
+                *before_bytes as i64,
+                *after_bytes as i64,
+                *tests as i64,
+            ],
+        )?,
+        EventKind::Bisected { finding, bisection } => {
+            let commits = bisection.commits.as_ref();
+            conn.execute(
+                "INSERT INTO bisections (time_ms, finding, signature, last_good, first_bad,
+                     commit_good, commit_bad, pr)
+                     VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    time,
+                    finding,
+                    bisection.signature,
+                    bisection.last_good,
+                    bisection.first_bad,
+                    commits.map(|c| &c.last_good),
+                    commits.map(|c| &c.first_bad),
+                    commits.and_then(|c| c.pr).map(|pr| pr as i64),
+                ],
+            )?
+        }
+        EventKind::Fixed {
+            path,
+            signature,
+            outcome,
+        } => conn.execute(
+            "INSERT INTO fixes (time_ms, path, signature, outcome) VALUES (?1, ?2, ?3, ?4)",
+            params![time, path, signature, outcome],
+        )?,
+        // A resumed run replaces what it reported before.
+        EventKind::FuzzRun {
+            findings,
+            policy,
+            seed,
+            iterations,
+            found,
+            known,
+            buckets,
+            added,
+            wall_ms,
+            cpu_ms,
+        } => conn.execute(
+            "INSERT OR REPLACE INTO fuzz_runs (findings, time_ms, policy, seed, iterations,
+                 found, known, buckets, added, wall_ms, cpu_ms)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                findings,
+                time,
+                policy,
+                *seed as i64,
+                *iterations as i64,
+                *found as i64,
+                *known as i64,
+                *buckets as i64,
+                *added as i64,
+                *wall_ms as i64,
+                *cpu_ms as i64,
+            ],
+        )?,
+    };
+    Ok(())
+}
+
+fn get_u64(row: &Row<'_>, index: usize) -> rusqlite::Result<u64> {
+    Ok(row.get::<_, i64>(index)? as u64)
+}
+
+fn get_severity(row: &Row<'_>, index: usize) -> rusqlite::Result<Option<Severity>> {
+    let Some(name) = row.get::<_, Option<String>>(index)? else {
+        return Ok(None);
+    };
+    name.parse().map(Some).map_err(|e: UnknownSeverity| {
+        rusqlite::Error::FromSqlConversionFailure(index, rusqlite::types::Type::Text, e.into())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(time_ms: u64, kind: EventKind) -> Event {
+        Event {
+            time_ms,
+            schema: crate::events::SCHEMA,
+            kind,
+        }
+    }
+
+    fn exec(time_ms: u64, harness: &str, severity: Option<Severity>) -> Event {
+        event(
+            time_ms,
+            EventKind::Exec {
+                harness: harness.to_owned(),
+                input: format!("seed-{time_ms}"),
+                elapsed_ms: Some(time_ms * 10),
+                severity,
+                finding: severity.map(|_| format!("findings/{time_ms}.rs")),
+            },
+        )
+    }
+
+    #[test]
+    fn executions_filter_and_keep_the_newest() {
+        let mut db = Database::in_memory().expect("opens");
+        db.import(&[
+            exec(1, "rustc", None),
+            exec(2, "rustc", Some(Severity::Diagnostic)),
+            exec(3, "miri", Some(Severity::Unsound)),
+            exec(4, "rustc", Some(Severity::Ice)),
+        ])
+        .expect("imports");
+        let times = |query: ExecutionQuery| -> Vec<u64> {
+            db.executions(&query)
+                .expect("queries")
+                .iter()
+                .map(|execution| execution.time_ms)
+                .collect()
+        };
+        assert_eq!(times(ExecutionQuery::default()), [1, 2, 3, 4]);
+        let rustc = || ExecutionQuery {
+            harness: Some("rustc".to_owned()),
+            ..ExecutionQuery::default()
+        };
+        assert_eq!(times(rustc()), [1, 2, 4]);
+        assert_eq!(
+            times(ExecutionQuery {
+                findings: true,
+                ..rustc()
+            }),
+            [2, 4]
+        );
+        assert_eq!(
+            times(ExecutionQuery {
+                severity: Some(Severity::Ice),
+                ..ExecutionQuery::default()
+            }),
+            [3, 4]
+        );
+        assert_eq!(
+            times(ExecutionQuery {
+                since_ms: Some(2),
+                limit: Some(2),
+                ..ExecutionQuery::default()
+            }),
+            [3, 4]
+        );
+        let [execution] = &db
+            .executions(&ExecutionQuery {
+                input: Some("seed-3".to_owned()),
+                ..ExecutionQuery::default()
+            })
+            .expect("queries")[..]
+        else {
+            panic!("one execution of seed-3");
+        };
+        assert_eq!(execution.severity, Some(Severity::Unsound));
+        assert_eq!(execution.finding.as_deref(), Some("findings/3.rs"));
+
+        let summary = db.summary().expect("summarizes");
+        assert_eq!(summary.executions, 4);
+        assert_eq!(summary.findings.values().sum::<u64>(), 3);
+        let rustc = &summary.harnesses["rustc"];
+        assert_eq!((rustc.executions, rustc.findings), (3, 2));
+        assert_eq!(rustc.mean_ms, Some(70.0 / 3.0));
+    }
+
+    #[test]
+    fn events_land_in_their_tables() {
+        let db = Database::in_memory().expect("opens");
+        let bucket = |time_ms, reproducer: &str| {
+            event(
+                time_ms,
+                EventKind::NewBucket {
+                    bucket: "ice-1234".to_owned(),
+                    severity: Some(Severity::Ice),
+                    reproducer: reproducer.to_owned(),
+                },
+            )
+        };
+        let bisection = Bisection {
+            signature: "ice-1234".to_owned(),
+            last_good: "nightly-2024-06-01".to_owned(),
+            first_bad: "nightly-2024-06-02".to_owned(),
+            commits: Some(CommitRange {
+                last_good: "aaaa".to_owned(),
+                first_bad: "bbbb".to_owned(),
+                pr: Some(12345),
+            }),
+        };
+        for event in [
+            event(
+                1,
+                EventKind::NewSeed {
+                    path: "corpus/a.rs".to_owned(),
+                    hash: "abc".to_owned(),
+                    parent: None,
+                },
+            ),
+            bucket(2, "findings/a.rs"),
+            bucket(3, "findings/b.rs"),
+            event(
+                4,
+                EventKind::Bisected {
+                    finding: "findings/a.rs".to_owned(),
+                    bisection: bisection.clone(),
+                },
+            ),
+            event(
+                5,
+                EventKind::Fixed {
+                    path: "regressions/ice-1234.rs".to_owned(),
+                    signature: "ice-1234".to_owned(),
+                    outcome: "success".to_owned(),
+                },
+            ),
+        ] {
+            db.record(&event).expect("records");
+        }
+        assert_eq!(db.seeds().expect("queries")[0].hash, "abc");
+        // A bucket keeps its first reproducer.
+        let first = db
+            .bucket("ice-1234")
+            .expect("queries")
+            .expect("has the bucket");
+        assert_eq!(
+            (first.time_ms, first.reproducer.as_str()),
+            (2, "findings/a.rs")
+        );
+        assert_eq!(db.buckets().expect("queries"), [first]);
+        assert_eq!(db.bucket("ice-0").expect("queries"), None);
+        assert_eq!(db.bisections().expect("queries")[0].bisection, bisection);
+        assert_eq!(db.fixes().expect("queries")[0].outcome, "success");
+        let summary = db.summary().expect("summarizes");
+        assert_eq!(
+            (
+                summary.seeds,
+                summary.buckets,
+                summary.bisections,
+                summary.fixes
+            ),
+            (1, 1, 1, 1)
+        );
+    }
+
+    #[test]
+    fn newer_schemas_are_refused() {
+        let dir = std::env::temp_dir().join(format!("rustc-fuzz-results-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("creates the directory");
+        let path = dir.join("results.db");
+        drop(Database::open(&path).expect("creates"));
+        let conn = Connection::open(&path).expect("opens");
+        conn.pragma_update(None, "user_version", SCHEMA + 1)
+            .expect("sets the version");
+        drop(conn);
+        let error = Database::open(&path).expect_err("a newer schema");
+        assert!(
+            error.to_string().contains("newer than this build"),
+            "{error}"
+        );
+        std::fs::remove_dir_all(&dir).expect("cleans up");
+    }
+
+    #[test]
+    fn resumed_fuzz_runs_replace_their_report() {
+        let db = Database::in_memory().expect("opens");
+        let run = |time_ms, findings: &str, policy: &str, iterations| {
+            event(
+                time_ms,
+                EventKind::FuzzRun {
+                    findings: findings.to_owned(),
+                    policy: policy.to_owned(),
+                    seed: 7,
+                    iterations,
+                    found: iterations / 100,
+                    known: 0,
+                    buckets: 1,
+                    added: 2,
+                    wall_ms: iterations * 10,
+                    cpu_ms: iterations * 20,
+                },
+            )
+        };
+        for event in [
+            run(1, "findings-a", "coverage", 500),
+            run(2, "findings-b", "fifo", 1000),
+            // `findings-a` was resumed.
+            run(3, "findings-a", "coverage", 2000),
+        ] {
+            db.record(&event).expect("records");
+        }
+        let runs = db.fuzz_runs().expect("queries");
+        let reported: Vec<(u64, &str, u64)> = runs
+            .iter()
+            .map(|run| (run.time_ms, run.findings.as_str(), run.iterations))
+            .collect();
+        assert_eq!(reported, [(2, "findings-b", 1000), (3, "findings-a", 2000)]);
+
+        let policies = db.policies().expect("summarizes");
+        assert_eq!(policies.keys().collect::<Vec<_>>(), ["coverage", "fifo"]);
+        let coverage = &policies["coverage"];
+        assert_eq!((coverage.runs, coverage.found), (1, 20));
+        assert_eq!(coverage.per_thousand(coverage.found), 10.0);
+        assert_eq!(coverage.rate(), 100.0);
+        assert_eq!(PolicySummary::default().per_thousand(3), 0.0);
+        assert_eq!(PolicySummary::default().rate(), 0.0);
+    }
+}
@@ -0,0 +1,700 @@
+//! Seed scheduling.
+//!
+//! Picking seeds first-in first-out spends most of a long run on the seeds
+//! that never lead anywhere: a big corpus is mostly near-duplicates of a
+//! few shapes. A [`Scheduler`] gives every seed an energy according to a
+//! [`Strategy`] and picks seeds by it, mostly with probability
+//! proportional to it, so the interesting ones get more mutations without
+//! the rest starving.
+//!
+//! The built-in strategies are the [`Policy`] variants, which a campaign
+//! names with `--policy` or `policy` in the `[scheduler]` section of its
+//! config. A program built on this library can bring its own with
+//! [`Scheduler::with_strategy`]. Every `fuzz` run records its policy and
+//! what it achieved in the results database, so `db policies` can compare
+//! them.
+
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use rand::{Rng, RngCore};
+use serde::{Deserialize, Serialize};
+
+/// How a [`Scheduler`] spreads mutations over the corpus.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Policy {
+    /// Every seed in turn.
+    RoundRobin,
+    /// Seeds with feature tags few other seeds have.
+    #[default]
+    Rarity,
+    /// Seeds whose mutants found something recently.
+    Crashes,
+    /// Seeds that are quick to check.
+    Cost,
+    /// Every seed in turn, from a queue: a seed goes to the back when it
+    /// is picked, and new seeds join at the back, behind every seed
+    /// already waiting.
+    Fifo,
+    /// Mostly the seed whose mutants have brought new feature tags most
+    /// often.
+    CoverageGreedy,
+    /// The seeds with the rarest feature in the corpus, least mutated
+    /// first.
+    RareFeatureFirst,
+    /// Seeds whose features resemble those of seeds whose mutants found
+    /// something.
+    CrashProximity,
+}
+
+impl Policy {
+    pub const ALL: [Policy; 8] = [
+        Policy::RoundRobin,
+        Policy::Rarity,
+        Policy::Crashes,
+        Policy::Cost,
+        Policy::Fifo,
+        Policy::CoverageGreedy,
+        Policy::RareFeatureFirst,
+        Policy::CrashProximity,
+    ];
+
+    pub fn name(self) -> &'static str {
+        self.strategy().name()
+    }
+
+    /// The strategy the policy stands for.
+    pub fn strategy(self) -> &'static dyn Strategy {
+        match self {
+            Policy::RoundRobin => &RoundRobin,
+            Policy::Rarity => &Rarity,
+            Policy::Crashes => &Crashes,
+            Policy::Cost => &Cost,
+            Policy::Fifo => &Fifo,
+            Policy::CoverageGreedy => &CoverageGreedy,
+            Policy::RareFeatureFirst => &RareFeatureFirst,
+            Policy::CrashProximity => &CrashProximity,
+        }
+    }
+}
+
+impl fmt::Display for Policy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnknownPolicy(pub String);
+
+impl fmt::Display for UnknownPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = Policy::ALL.iter().map(|policy| policy.name()).collect();
+        write!(
+            f,
+            "unknown scheduling policy {:?}; expected one of {}",
+            self.0,
+            names.join(", ")
+        )
+    }
+}
+
+impl std::error::Error for UnknownPolicy {}
+
+impl FromStr for Policy {
+    type Err = UnknownPolicy;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Policy::ALL
+            .into_iter()
+            .find(|p| p.name() == s)
+            .ok_or_else(|| UnknownPolicy(s.to_owned()))
+    }
+}
+
+/// Selections after which a finding's bonus under [`Policy::Crashes`] has
+/// halved.
+const CRASH_HALF_LIFE: f64 = 500.0;
+
+/// Energy a finding adds under [`Policy::Crashes`] while still fresh, and
+/// under [`Policy::CrashProximity`] to a seed whose mutants found it,
+/// against a baseline of one.
+const CRASH_BONUS: f64 = 16.0;
+
+/// How often [`Policy::CoverageGreedy`] picks a seed at random instead of
+/// the best one, so it notices when another has become better.
+const EXPLORE: f64 = 0.1;
+
+/// What the scheduler knows about one seed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Seed {
+    pub features: BTreeSet<String>,
+    pub selected: u64,
+    /// Mutants checked, which [`Scheduler::observe`] counts.
+    pub runs: u64,
+    pub elapsed: Duration,
+    /// Selection clock at the last finding among its mutants.
+    pub last_finding: Option<u64>,
+    /// Mutants that brought feature tags no seed had.
+    #[serde(default)]
+    pub gains: u64,
+    /// How much its features overlap those of the closest seed whose
+    /// mutants found something, from 0 to 1: 1 for such a seed itself.
+    #[serde(default)]
+    pub proximity: f64,
+    /// Selection clock when it joined the back of the [`Policy::Fifo`]
+    /// queue: when it was added, or last selected.
+    #[serde(default)]
+    pub queued: u64,
+}
+
+/// What a [`Strategy`] sees of a corpus.
+#[derive(Debug, Clone, Copy)]
+pub struct View<'a> {
+    /// By index, in the order they were added.
+    pub seeds: &'a [Seed],
+    /// How many seeds have each feature.
+    pub counts: &'a HashMap<String, u64>,
+    /// Selections so far.
+    pub clock: u64,
+}
+
+/// A way of spreading mutations over the corpus.
+pub trait Strategy: fmt::Debug + Send + Sync {
+    /// Stable name, used to select it and in the results database.
+    fn name(&self) -> &'static str;
+
+    /// The energy of every seed of a corpus of at least one, by index:
+    /// how much each deserves to be picked.
+    fn energies(&self, view: &View<'_>) -> Vec<f64>;
+
+    /// Picks a seed index from a corpus of at least one seed. By default,
+    /// with probability proportional to its energy.
+    fn select(&self, view: &View<'_>, rng: &mut dyn RngCore) -> usize {
+        weighted(&self.energies(view), rng)
+    }
+}
+
+/// An index drawn with probability proportional to its weight.
+fn weighted(weights: &[f64], rng: &mut dyn RngCore) -> usize {
+    let mut x = rng.gen::<f64>() * weights.iter().sum::<f64>();
+    for (i, weight) in weights.iter().enumerate() {
+        if x < *weight {
+            return i;
+        }
+        x -= weight;
+    }
+    weights.len() - 1
+}
+
+/// The index of the greatest of `keys`, the first of equals.
+fn best<K: PartialOrd>(keys: impl Iterator<Item = K>) -> usize {
+    let mut best: Option<(usize, K)> = None;
+    for (i, key) in keys.enumerate() {
+        if best.as_ref().is_none_or(|(_, top)| key > *top) {
+            best = Some((i, key));
+        }
+    }
+    best.map_or(0, |(i, _)| i)
+}
+
+/// [`Policy::RoundRobin`].
+#[derive(Debug, Clone, Copy)]
+pub struct RoundRobin;
+
+impl Strategy for RoundRobin {
+    fn name(&self) -> &'static str {
+        "round-robin"
+    }
+
+    fn energies(&self, view: &View<'_>) -> Vec<f64> {
+        vec![1.0; view.seeds.len()]
+    }
+
+    fn select(&self, view: &View<'_>, _: &mut dyn RngCore) -> usize {
+        (view.clock % view.seeds.len() as u64) as usize
+    }
+}
+
+/// [`Policy::Rarity`].
+#[derive(Debug, Clone, Copy)]
+pub struct Rarity;
+
+impl Strategy for Rarity {
+    fn name(&self) -> &'static str {
+        "rarity"
+    }
+
+    fn energies(&self, view: &View<'_>) -> Vec<f64> {
+        // A seed with nothing rare about it still gets a little.
+        let floor = 1.0 / view.seeds.len() as f64;
+        view.seeds
+            .iter()
+            .map(|seed| {
+                seed.features
+                    .iter()
+                    .map(|f| 1.0 / view.counts[f] as f64)
+                    .sum::<f64>()
+                    .max(floor)
+            })
+            .collect()
+    }
+}
+
+/// [`Policy::Crashes`].
+#[derive(Debug, Clone, Copy)]
+pub struct Crashes;
+
+impl Strategy for Crashes {
+    fn name(&self) -> &'static str {
+        "crashes"
+    }
+
+    fn energies(&self, view: &View<'_>) -> Vec<f64> {
+        view.seeds
+            .iter()
+            .map(|seed| {
+                1.0 + seed.last_finding.map_or(0.0, |at| {
+                    CRASH_BONUS * 0.5f64.powf((view.clock - at) as f64 / CRASH_HALF_LIFE)
+                })
+            })
+            .collect()
+    }
+}
+
+/// [`Policy::Cost`].
+#[derive(Debug, Clone, Copy)]
+pub struct Cost;
+
+impl Strategy for Cost {
+    fn name(&self) -> &'static str {
+        "cost"
+    }
+
+    fn energies(&self, view: &View<'_>) -> Vec<f64> {
+        let mean = |seed: &Seed| seed.elapsed.as_secs_f64() / seed.runs as f64;
+        // The shortest mean check time of any measured seed, or one
+        // second if none has been measured.
+        let fastest = view
+            .seeds
+            .iter()
+            .filter(|seed| seed.runs > 0)
+            .map(mean)
+            .reduce(f64::min)
+            .unwrap_or(1.0);
+        // Unmeasured seeds count as fast, so they get measured.
+        view.seeds
+            .iter()
+            .map(|seed| match seed.runs {
+                0 => 1.0 / fastest.max(f64::EPSILON),
+                _ => 1.0 / mean(seed).max(f64::EPSILON),
+            })
+            .collect()
+    }
+}
+
+/// [`Policy::Fifo`].
+#[derive(Debug, Clone, Copy)]
+pub struct Fifo;
+
+impl Strategy for Fifo {
+    fn name(&self) -> &'static str {
+        "fifo"
+    }
+
+    fn energies(&self, view: &View<'_>) -> Vec<f64> {
+        vec![1.0; view.seeds.len()]
+    }
+
+    /// The seed that has waited longest; of seeds that joined the queue
+    /// together, the one added first.
+    fn select(&self, view: &View<'_>, _: &mut dyn RngCore) -> usize {
+        best(view.seeds.iter().map(|seed| Reverse(seed.queued)))
+    }
+}
+
+/// [`Policy::CoverageGreedy`]. A seed's energy is the share of its mutants
+/// that brought new feature tags, counting one imagined success, so an
+/// unmutated seed looks as good as any until it has been tried.
+#[derive(Debug, Clone, Copy)]
+pub struct CoverageGreedy;
+
+impl Strategy for CoverageGreedy {
+    fn name(&self) -> &'static str {
+        "coverage-greedy"
+    }
+
+    fn energies(&self, view: &View<'_>) -> Vec<f64> {
+        view.seeds
+            .iter()
+            .map(|seed| (seed.gains + 1) as f64 / (seed.runs + 1) as f64)
+            .collect()
+    }
+
+    fn select(&self, view: &View<'_>, rng: &mut dyn RngCore) -> usize {
+        if rng.gen::<f64>() < EXPLORE {
+            return rng.gen_range(0..view.seeds.len());
+        }
+        // Of equals, the one picked least.
+        let energies = self.energies(view);
+        best(
+            energies
+                .iter()
+                .zip(view.seeds)
+                .map(|(energy, seed)| (*energy, -(seed.selected as f64))),
+        )
+    }
+}
+
+/// [`Policy::RareFeatureFirst`]. A seed's energy is one over the number of
+/// seeds that share its rarest feature.
+#[derive(Debug, Clone, Copy)]
+pub struct RareFeatureFirst;
+
+impl Strategy for RareFeatureFirst {
+    fn name(&self) -> &'static str {
+        "rare-feature-first"
+    }
+
+    fn energies(&self, view: &View<'_>) -> Vec<f64> {
+        view.seeds
+            .iter()
+            .map(|seed| {
+                let rarest = seed.features.iter().map(|f| view.counts[f]).min();
+                1.0 / rarest.unwrap_or(view.seeds.len() as u64) as f64
+            })
+            .collect()
+    }
+
+    fn select(&self, view: &View<'_>, _: &mut dyn RngCore) -> usize {
+        let energies = self.energies(view);
+        best(
+            energies
+                .iter()
+                .zip(view.seeds)
+                .map(|(energy, seed)| (*energy, -(seed.selected as f64))),
+        )
+    }
+}
+
+/// [`Policy::CrashProximity`].
+#[derive(Debug, Clone, Copy)]
+pub struct CrashProximity;
+
+impl Strategy for CrashProximity {
+    fn name(&self) -> &'static str {
+        "crash-proximity"
+    }
+
+    fn energies(&self, view: &View<'_>) -> Vec<f64> {
+        view.seeds
+            .iter()
+            .map(|seed| 1.0 + CRASH_BONUS * seed.proximity)
+            .collect()
+    }
+}
+
+/// Selection counts for one seed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SeedStats {
+    pub selected: u64,
+    pub energy: f64,
+}
+
+/// Picks the seed to mutate next. It serializes, so a campaign can be
+/// checkpointed and resumed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Scheduler {
+    policy: Policy,
+    /// A strategy used instead of the policy's. It is not saved, so a
+    /// scheduler read back needs [`Scheduler::set_strategy`] again.
+    #[serde(skip)]
+    strategy: Option<Arc<dyn Strategy>>,
+    seeds: Vec<Seed>,
+    /// How many seeds have each feature.
+    counts: HashMap<String, u64>,
+    /// Selections so far.
+    clock: u64,
+    /// Seeds whose mutants have found something.
+    #[serde(default)]
+    crashers: Vec<usize>,
+}
+
+impl Scheduler {
+    pub fn new(policy: Policy) -> Self {
+        Scheduler {
+            policy,
+            strategy: None,
+            seeds: Vec::new(),
+            counts: HashMap::new(),
+            clock: 0,
+            crashers: Vec::new(),
+        }
+    }
+
+    /// A scheduler that picks seeds by `strategy`.
+    pub fn with_strategy(strategy: Arc<dyn Strategy>) -> Self {
+        let mut scheduler = Scheduler::default();
+        scheduler.set_strategy(strategy);
+        scheduler
+    }
+
+    /// Picks seeds by `strategy` from now on.
+    pub fn set_strategy(&mut self, strategy: Arc<dyn Strategy>) {
+        self.strategy = Some(strategy);
+    }
+
+    /// The policy it was made with; not what it picks by if it was given
+    /// a strategy of its own.
+    pub fn policy(&self) -> Policy {
+        self.policy
+    }
+
+    /// What it picks seeds by.
+    pub fn strategy(&self) -> &dyn Strategy {
+        match &self.strategy {
+            Some(strategy) => &**strategy,
+            None => self.policy.strategy(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.seeds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.seeds.is_empty()
+    }
+
+    /// Adds a seed described by `features`, returning its index.
+    pub fn add(&mut self, features: BTreeSet<String>) -> usize {
+        for feature in &features {
+            *self.counts.entry(feature.clone()).or_default() += 1;
+        }
+        let proximity = self
+            .crashers
+            .iter()
+            .map(|&crasher| similarity(&features, &self.seeds[crasher].features))
+            .fold(0.0, f64::max);
+        self.seeds.push(Seed {
+            features,
+            proximity,
+            queued: self.clock,
+            ..Seed::default()
+        });
+        self.seeds.len() - 1
+    }
+
+    /// Whether any of `features` is on no seed yet.
+    pub fn is_new(&self, features: &BTreeSet<String>) -> bool {
+        features.iter().any(|f| !self.counts.contains_key(f))
+    }
+
+    /// Records that a mutant of seed `index` took `elapsed` to check,
+    /// whether it found something and whether it brought new feature
+    /// tags.
+    pub fn observe(&mut self, index: usize, elapsed: Duration, finding: bool, new_coverage: bool) {
+        let seed = &mut self.seeds[index];
+        seed.runs += 1;
+        seed.elapsed += elapsed;
+        seed.gains += u64::from(new_coverage);
+        if finding {
+            seed.last_finding = Some(self.clock);
+            if !self.crashers.contains(&index) {
+                tracing::debug!(seed = index, "first finding among its mutants");
+                self.crashers.push(index);
+                let features = self.seeds[index].features.clone();
+                for seed in &mut self.seeds {
+                    seed.proximity = seed.proximity.max(similarity(&seed.features, &features));
+                }
+            }
+        }
+    }
+
+    fn view(&self) -> View<'_> {
+        View {
+            seeds: &self.seeds,
+            counts: &self.counts,
+            clock: self.clock,
+        }
+    }
+
+    /// Picks a seed index, or `None` if there are no seeds.
+    pub fn select(&mut self, rng: &mut dyn RngCore) -> Option<usize> {
+        if self.seeds.is_empty() {
+            return None;
+        }
+        let chosen = self.strategy().select(&self.view(), rng);
+        tracing::trace!(
+            strategy = self.strategy().name(),
+            chosen,
+            selected = self.seeds[chosen].selected,
+            "selected"
+        );
+        self.clock += 1;
+        self.seeds[chosen].selected += 1;
+        self.seeds[chosen].queued = self.clock;
+        Some(chosen)
+    }
+
+    /// Per-seed selection statistics, by index.
+    pub fn stats(&self) -> Vec<SeedStats> {
+        if self.seeds.is_empty() {
+            return Vec::new();
+        }
+        self.seeds
+            .iter()
+            .zip(self.strategy().energies(&self.view()))
+            .map(|(seed, energy)| SeedStats {
+                selected: seed.selected,
+                energy,
+            })
+            .collect()
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Scheduler::new(Policy::default())
+    }
+}
+
+/// The Jaccard similarity of two feature sets: 1 for the same set, 0 for
+/// sets with nothing in common.
+fn similarity(a: &BTreeSet<String>, b: &BTreeSet<String>) -> f64 {
+    let shared = a.intersection(b).count();
+    let all = a.len() + b.len() - shared;
+    if all == 0 {
+        return 1.0;
+    }
+    shared as f64 / all as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::mock::StepRng;
+
+    /// The next `n` seeds `scheduler` picks.
+    fn picks(scheduler: &mut Scheduler, n: usize) -> Vec<usize> {
+        let mut rng = StepRng::new(0, 1);
+        (0..n)
+            .map(|_| scheduler.select(&mut rng).expect("has seeds"))
+            .collect()
+    }
+
+    #[test]
+    fn fifo_queues_new_seeds_behind_waiting_ones() {
+        let mut schedulers = [Policy::Fifo, Policy::RoundRobin].map(Scheduler::new);
+        for scheduler in &mut schedulers {
+            for _ in 0..3 {
+                scheduler.add(BTreeSet::new());
+            }
+            assert_eq!(picks(scheduler, 2), [0, 1]);
+            scheduler.add(BTreeSet::new());
+        }
+        let [fifo, round_robin] = &mut schedulers;
+        assert_eq!(picks(fifo, 5), [2, 0, 1, 3, 2]);
+        assert_eq!(picks(round_robin, 5), [2, 3, 0, 1, 2]);
+    }
+
+    #[test]
+    fn policies_parse_by_name() {
+        for policy in Policy::ALL {
+            assert_eq!(policy.name().parse(), Ok(policy));
+        }
+        assert!("lifo".parse::<Policy>().is_err());
+    }
+
+    fn features(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn coverage_greedy_follows_the_seeds_that_gain() {
+        let mut scheduler = Scheduler::new(Policy::CoverageGreedy);
+        for _ in 0..3 {
+            scheduler.add(BTreeSet::new());
+        }
+        // Untried seeds look equally good, so each gets a turn.
+        let mut rng = StepRng::new(u64::MAX / 2, 0);
+        let mut pick = || scheduler.select(&mut rng).expect("has seeds");
+        assert_eq!([pick(), pick(), pick()], [0, 1, 2]);
+        scheduler.observe(0, Duration::ZERO, false, false);
+        scheduler.observe(1, Duration::ZERO, false, true);
+        scheduler.observe(2, Duration::ZERO, false, false);
+        let energies: Vec<f64> = scheduler.stats().iter().map(|s| s.energy).collect();
+        assert_eq!(energies, [0.5, 1.0, 0.5]);
+        let mut rng = StepRng::new(u64::MAX / 2, 0);
+        assert_eq!(scheduler.select(&mut rng), Some(1));
+    }
+
+    #[test]
+    fn rare_features_go_first() {
+        let mut scheduler = Scheduler::new(Policy::RareFeatureFirst);
+        scheduler.add(features(&["fn", "loop"]));
+        scheduler.add(features(&["fn", "loop"]));
+        scheduler.add(features(&["fn", "gat"]));
+        scheduler.add(features(&["fn", "async"]));
+        scheduler.add(BTreeSet::new());
+        let energies: Vec<f64> = scheduler.stats().iter().map(|s| s.energy).collect();
+        assert_eq!(energies, [0.5, 0.5, 1.0, 1.0, 0.2]);
+        // Of the seeds with the rarest features, the least mutated.
+        assert_eq!(picks(&mut scheduler, 4), [2, 3, 2, 3]);
+    }
+
+    #[test]
+    fn crash_proximity_favors_seeds_like_the_crashers() {
+        let mut scheduler = Scheduler::new(Policy::CrashProximity);
+        scheduler.add(features(&["a", "b"]));
+        scheduler.add(features(&["a", "c"]));
+        scheduler.add(features(&["d"]));
+        scheduler.observe(0, Duration::ZERO, true, false);
+        let later = scheduler.add(features(&["a", "b", "c"]));
+        let energies: Vec<f64> = scheduler.stats().iter().map(|s| s.energy).collect();
+        let third = CRASH_BONUS / 3.0;
+        assert_eq!(
+            energies,
+            [1.0 + CRASH_BONUS, 1.0 + third, 1.0, 1.0 + 2.0 * third]
+        );
+        assert_eq!(later, 3);
+
+        // Proximity is kept across a checkpoint.
+        let json = serde_json::to_string(&scheduler).expect("serializes");
+        let restored: Scheduler = serde_json::from_str(&json).expect("deserializes");
+        assert_eq!(restored.stats(), scheduler.stats());
+    }
+
+    #[derive(Debug)]
+    struct Last;
+
+    impl Strategy for Last {
+        fn name(&self) -> &'static str {
+            "last"
+        }
+
+        fn energies(&self, view: &View<'_>) -> Vec<f64> {
+            (0..view.seeds.len()).map(|i| i as f64).collect()
+        }
+
+        fn select(&self, view: &View<'_>, _: &mut dyn RngCore) -> usize {
+            view.seeds.len() - 1
+        }
+    }
+
+    #[test]
+    fn programs_can_bring_their_own_strategy() {
+        let mut scheduler = Scheduler::with_strategy(Arc::new(Last));
+        assert_eq!(scheduler.select(&mut StepRng::new(0, 1)), None);
+        scheduler.add(BTreeSet::new());
+        scheduler.add(BTreeSet::new());
+        assert_eq!(scheduler.strategy().name(), "last");
+        assert_eq!(picks(&mut scheduler, 2), [1, 1]);
+        assert_eq!(scheduler.stats()[1].selected, 2);
+    }
+}